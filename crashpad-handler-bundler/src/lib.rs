@@ -2,6 +2,25 @@ use std::env;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Environment variables exported by [`emit_vcs_metadata`].
+const VCS_ENV: [&str; 3] = [
+    "CRASHPAD_GIT_COMMIT",
+    "CRASHPAD_GIT_BRANCH",
+    "CRASHPAD_GIT_DIRTY",
+];
+
+/// Git state of the crate being built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VcsMetadata {
+    /// Full commit hash of `HEAD`
+    pub commit: String,
+    /// Checked out branch, `None` for a detached `HEAD`
+    pub branch: Option<String>,
+    /// Tracked files have uncommitted changes
+    pub dirty: bool,
+}
 
 /// Primary API - bundles handler to the default target directory.
 ///
@@ -174,6 +193,94 @@ pub fn bundle_to(dest_dir: &Path) -> io::Result<PathBuf> {
 
 // --- helpers ---
 
+/// Opt-in: record the git commit, branch and dirty state of the crate being
+/// built, to be added to its dumps as annotations.
+///
+/// Call from the build script, next to [`bundle`]. Exports
+/// `CRASHPAD_GIT_COMMIT`, `CRASHPAD_GIT_BRANCH` and `CRASHPAD_GIT_DIRTY` to the
+/// crate, which `crashpad_rs::init!` and `crashpad_rs::vcs_annotations!` turn
+/// into `git_commit`, `git_branch` and `git_dirty` annotations.
+///
+/// If `CRASHPAD_GIT_COMMIT` is already set in the environment (e.g. by CI
+/// building from a source archive without `.git`), the variables are passed
+/// through instead of running git. Returns `None`, exporting nothing, if
+/// neither source is available.
+pub fn emit_vcs_metadata() -> Option<VcsMetadata> {
+    for key in VCS_ENV {
+        println!("cargo:rerun-if-env-changed={key}");
+    }
+
+    let metadata = match vcs_metadata_from_env() {
+        Some(metadata) => metadata,
+        None => {
+            let dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR")?);
+            for path in git_state_files(&dir) {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+            git_metadata(&dir)?
+        }
+    };
+
+    println!("cargo:rustc-env=CRASHPAD_GIT_COMMIT={}", metadata.commit);
+    if let Some(branch) = &metadata.branch {
+        println!("cargo:rustc-env=CRASHPAD_GIT_BRANCH={branch}");
+    }
+    println!("cargo:rustc-env=CRASHPAD_GIT_DIRTY={}", metadata.dirty);
+    Some(metadata)
+}
+
+fn vcs_metadata_from_env() -> Option<VcsMetadata> {
+    let commit = env::var("CRASHPAD_GIT_COMMIT").ok()?;
+    Some(VcsMetadata {
+        commit,
+        branch: env::var("CRASHPAD_GIT_BRANCH").ok(),
+        dirty: env::var("CRASHPAD_GIT_DIRTY").is_ok_and(|v| v == "true"),
+    })
+}
+
+/// Query git for the state of the work tree containing `dir`.
+fn git_metadata(dir: &Path) -> Option<VcsMetadata> {
+    let commit = git(dir, &["rev-parse", "HEAD"])?;
+    let branch = git(dir, &["rev-parse", "--abbrev-ref", "HEAD"]).filter(|b| b != "HEAD");
+    let status = git(dir, &["status", "--porcelain", "--untracked-files=no"])?;
+    Some(VcsMetadata {
+        commit,
+        branch,
+        dirty: !status.is_empty(),
+    })
+}
+
+/// Files that change when a commit is made, the branch is switched or the
+/// work tree is modified.
+fn git_state_files(dir: &Path) -> Vec<PathBuf> {
+    let Some(git_dir) = git(dir, &["rev-parse", "--absolute-git-dir"]) else {
+        return Vec::new();
+    };
+    let git_dir = PathBuf::from(git_dir);
+    let mut files = vec![git_dir.join("HEAD"), git_dir.join("index")];
+    if let Some(reference) = git(dir, &["symbolic-ref", "-q", "HEAD"]) {
+        files.push(git_dir.join(reference));
+    }
+    files.push(git_dir.join("packed-refs"));
+    // A missing file would make cargo rerun the build script every time
+    files.retain(|path| path.exists());
+    files
+}
+
+/// Trimmed stdout of a successful git command.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
 fn default_dest_path() -> io::Result<PathBuf> {
     let root = target_root_dir()?;
     let triple_dir = if is_cross_compile() {
@@ -398,6 +505,61 @@ mod tests {
         assert_eq!(p, src);
     }
 
+    #[test]
+    fn vcs_metadata_from_env_passthrough() {
+        let _g = ENV_MUTEX.get_or_init(|| Mutex::new(())).lock().unwrap();
+        clear_env(&VCS_ENV);
+        assert_eq!(vcs_metadata_from_env(), None);
+
+        std::env::set_var("CRASHPAD_GIT_COMMIT", "0123abcd");
+        std::env::set_var("CRASHPAD_GIT_DIRTY", "true");
+        let metadata = vcs_metadata_from_env().unwrap();
+        clear_env(&VCS_ENV);
+
+        assert_eq!(
+            metadata,
+            VcsMetadata {
+                commit: "0123abcd".to_string(),
+                branch: None,
+                dirty: true,
+            }
+        );
+    }
+
+    #[test]
+    fn git_metadata_of_work_tree() {
+        let td = TempDir::new().unwrap();
+        let dir = td.path();
+        let run = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .status()
+                .is_ok_and(|status| status.success())
+        };
+        if !run(&["init", "-q", "-b", "release"]) {
+            // git not installed
+            return;
+        }
+        fs::write(dir.join("file.txt"), "one").unwrap();
+        assert!(run(&["add", "file.txt"]));
+        assert!(run(&["commit", "-q", "-m", "initial"]));
+
+        let metadata = git_metadata(dir).unwrap();
+        assert_eq!(metadata.commit.len(), 40);
+        assert_eq!(metadata.branch.as_deref(), Some("release"));
+        assert!(!metadata.dirty);
+        assert!(git_state_files(dir).contains(&dir.join(".git/HEAD").canonicalize().unwrap()));
+
+        fs::write(dir.join("file.txt"), "two").unwrap();
+        assert!(git_metadata(dir).unwrap().dirty);
+
+        let not_a_repo = TempDir::new().unwrap();
+        assert_eq!(git_metadata(not_a_repo.path()), None);
+    }
+
     #[test]
     fn error_when_no_source_and_no_dest() {
         let _g = ENV_MUTEX.get_or_init(|| Mutex::new(())).lock().unwrap();
@@ -0,0 +1,35 @@
+//! crashpad_handler, built from crashpad-rs-sys.
+//!
+//! The same handler crashpad-rs-sys builds next to the libraries, packaged
+//! as a crate so it can be installed with `cargo install` or
+//! `cargo binstall` and updated on servers independently of the
+//! applications using it. Takes crashpad_handler's command line.
+
+use std::env;
+use std::ffi::{CString, OsString};
+use std::os::raw::c_char;
+
+use crashpad_rs_sys::crashpad_handler_main;
+
+fn main() {
+    let args: Vec<CString> = env::args_os()
+        .filter_map(|arg| CString::new(os_string_bytes(arg)).ok())
+        .collect();
+    let mut argv: Vec<*mut c_char> = args.iter().map(|arg| arg.as_ptr() as *mut _).collect();
+    argv.push(std::ptr::null_mut());
+
+    let code = unsafe { crashpad_handler_main(args.len() as i32, argv.as_mut_ptr()) };
+    std::process::exit(code);
+}
+
+#[cfg(unix)]
+fn os_string_bytes(arg: OsString) -> Vec<u8> {
+    use std::os::unix::ffi::OsStringExt;
+    arg.into_vec()
+}
+
+/// The handler expects UTF-8 arguments on Windows.
+#[cfg(windows)]
+fn os_string_bytes(arg: OsString) -> Vec<u8> {
+    arg.to_string_lossy().into_owned().into_bytes()
+}
@@ -40,6 +40,19 @@ fn main() {
         "Only one build strategy can be selected: vendored, vendored-depot, or prebuilt"
     );
 
+    // Prebuilt packages don't contain the handler's libraries
+    #[cfg(all(feature = "handler-linked", feature = "prebuilt"))]
+    compile_error!("The handler-linked feature requires the vendored or vendored-depot strategy");
+
+    // Prebuilt packages ship the full handler
+    #[cfg(all(feature = "minimal-handler", feature = "prebuilt"))]
+    compile_error!("The minimal-handler feature requires the vendored or vendored-depot strategy");
+
+    // Version handshake: crashpad-rs's build script checks it against its
+    // own version as DEP_CRASHPAD_VERSION, since the wrapper's C API changes
+    // between releases. Printed first, so docs.rs builds have it too.
+    println!("cargo:version={}", env!("CARGO_PKG_VERSION"));
+
     // Check if we're building on docs.rs
     if std::env::var("DOCS_RS").is_ok() {
         println!("cargo:warning=docs.rs build detected, skipping native build");
@@ -48,39 +61,9 @@ fn main() {
         let out_dir = std::env::var("OUT_DIR").unwrap();
         let bindings_path = std::path::Path::new(&out_dir).join("bindings.rs");
 
-        // Create minimal bindings to allow documentation build
-        // These match the actual C API in wrapper.h
-        std::fs::write(
-            &bindings_path,
-            r#"
-            // Dummy bindings for docs.rs build
-            // 
-            // These are placeholder types to allow documentation generation.
-            // Real bindings are generated during normal builds.
-            
-            use std::os::raw::{c_char, c_void};
-            
-            // Opaque handle types
-            pub type crashpad_client_t = *mut c_void;
-            
-            // Core functions from wrapper.h
-            extern "C" {
-                pub fn crashpad_client_new() -> crashpad_client_t;
-                pub fn crashpad_client_delete(client: crashpad_client_t);
-                pub fn crashpad_client_start_handler(
-                    client: crashpad_client_t,
-                    handler_path: *const c_char,
-                    database_path: *const c_char,
-                    metrics_path: *const c_char,
-                    url: *const c_char,
-                    annotations_keys: *const *const c_char,
-                    annotations_values: *const *const c_char,
-                    annotations_count: usize,
-                ) -> bool;
-            }
-        "#,
-        )
-        .expect("Failed to write dummy bindings");
+        // Declarations of wrapper.h for every platform, see the file
+        std::fs::write(&bindings_path, include_str!("docs_rs_bindings.rs"))
+            .expect("Failed to write dummy bindings");
 
         return;
     }
@@ -31,6 +31,8 @@ pub struct BuildConfig {
 
     // Build options
     pub verbose: bool,
+    pub handler_linked: bool, // Link crashpad_handler's code into the wrapper
+    pub minimal_handler: bool, // Build crashpad_handler for size, without HTTPS
 }
 
 impl BuildConfig {
@@ -69,6 +71,8 @@ impl BuildConfig {
             ],
             frameworks: Vec::new(),
             verbose: env::var("CRASHPAD_VERBOSE").is_ok(),
+            handler_linked: cfg!(feature = "handler-linked"),
+            minimal_handler: cfg!(feature = "minimal-handler"),
         };
 
         // Common GN args
@@ -102,9 +106,69 @@ impl BuildConfig {
             return Err(format!("Unsupported target: {target}. Supported targets: android, ios, darwin, windows-msvc, linux").into());
         }
 
+        if config.handler_linked {
+            config.setup_handler_linked(&target)?;
+        }
+
+        if config.minimal_handler {
+            config.setup_minimal_handler(&target);
+        }
+
         Ok(config)
     }
 
+    /// Configure linking crashpad_handler into the wrapper (`handler-linked` feature)
+    fn setup_handler_linked(&mut self, target: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if target.contains("android") || target.contains("ios") {
+            return Err(format!(
+                "The handler-linked feature is not supported on {target}. Supported: darwin, windows-msvc, linux"
+            )
+            .into());
+        }
+
+        // handler:handler holds HandlerMain, handler:common the upload and
+        // prune threads (copied as handler_common, its name clashes with
+        // client:common), util:net the HTTP transport
+        self.crashpad_libs.extend([
+            "handler".to_string(),
+            "handler_common".to_string(),
+            "net".to_string(),
+        ]);
+
+        if target.contains("windows") {
+            // Crashpad builds its own zlib on Windows
+            self.crashpad_libs.push("zlib".to_string());
+            self.link_libs.push("winhttp".to_string());
+        } else {
+            self.link_libs.push("z".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Configure a smaller crashpad_handler (`minimal-handler` feature)
+    fn setup_minimal_handler(&mut self, target: &str) {
+        // The socket transport without BoringSSL only speaks plain HTTP, so
+        // the handler carries no TLS stack (macOS and Windows use the
+        // system's HTTP client either way)
+        if target.contains("linux") || target.contains("android") {
+            self.gn_args.insert(
+                "crashpad_use_boringssl_for_http_transport_socket".to_string(),
+                "false".to_string(),
+            );
+        }
+
+        let (cflags, ldflags) = minimal_handler_flags(target);
+        let cflags = match self.gn_args.get("extra_cflags") {
+            Some(existing) => format!("{} {cflags}", existing.trim_matches('"')),
+            None => cflags.to_string(),
+        };
+        self.gn_args
+            .insert("extra_cflags".to_string(), format!("\"{cflags}\""));
+        self.gn_args
+            .insert("extra_ldflags".to_string(), format!("\"{ldflags}\""));
+    }
+
     /// Configure for Android
     fn setup_android(&mut self, target: &str) -> Result<(), Box<dyn std::error::Error>> {
         // Find NDK dynamically
@@ -449,3 +513,18 @@ impl BuildConfig {
         self.out_dir.join("bindings.rs")
     }
 }
+
+/// Compiler and linker flags optimizing for size and dropping unreferenced
+/// code, for the `minimal-handler` feature
+pub fn minimal_handler_flags(target: &str) -> (&'static str, &'static str) {
+    if target.contains("windows") {
+        ("/O1 /Gy", "/OPT:REF /OPT:ICF")
+    } else if target.contains("darwin") || target.contains("ios") {
+        ("-Os -ffunction-sections -fdata-sections", "-Wl,-dead_strip")
+    } else {
+        (
+            "-Os -ffunction-sections -fdata-sections",
+            "-Wl,--gc-sections -Wl,--strip-all",
+        )
+    }
+}
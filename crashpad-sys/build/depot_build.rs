@@ -108,11 +108,10 @@ pub fn build_crashpad_with_depot(
                 return Err("gclient sync failed".into());
             }
 
-            // Copy crashpad_wrapper.cc
-            fs::copy(
-                manifest_dir.join("crashpad_wrapper.cc"),
-                crashpad_dir.join("crashpad_wrapper.cc"),
-            )?;
+            // Copy crashpad_wrapper.cc and the header it includes
+            for file in ["crashpad_wrapper.cc", "wrapper.h"] {
+                fs::copy(manifest_dir.join(file), crashpad_dir.join(file))?;
+            }
         }
 
         return Ok(CrashpadBuildOutput {
@@ -159,11 +158,10 @@ pub fn build_crashpad_with_depot(
 
     let crashpad_dir = build_dir.join("crashpad");
 
-    // Copy crashpad_wrapper.cc (always copy to ensure it's up to date)
-    fs::copy(
-        manifest_dir.join("crashpad_wrapper.cc"),
-        crashpad_dir.join("crashpad_wrapper.cc"),
-    )?;
+    // Copy crashpad_wrapper.cc and its header (always copy to ensure they're up to date)
+    for file in ["crashpad_wrapper.cc", "wrapper.h"] {
+        fs::copy(manifest_dir.join(file), crashpad_dir.join(file))?;
+    }
 
     // Configure GN build args
     let mut gn_args = vec![
@@ -190,6 +188,22 @@ pub fn build_crashpad_with_depot(
         ));
     }
 
+    // A smaller handler, see BuildConfig::setup_minimal_handler
+    if cfg!(feature = "minimal-handler") {
+        if target.contains("linux") || target.contains("android") {
+            gn_args.push("crashpad_use_boringssl_for_http_transport_socket=false".to_string());
+        }
+        let (cflags, ldflags) = crate::config::minimal_handler_flags(target);
+        match gn_args
+            .iter_mut()
+            .find(|arg| arg.starts_with("extra_cflags="))
+        {
+            Some(arg) => *arg = format!("{} {cflags}\"", arg.trim_end_matches('"')),
+            None => gn_args.push(format!("extra_cflags=\"{cflags}\"")),
+        }
+        gn_args.push(format!("extra_ldflags=\"{ldflags}\""));
+    }
+
     // Create the output directory if it doesn't exist
     fs::create_dir_all(&final_build_dir)?;
 
@@ -210,18 +224,21 @@ pub fn build_crashpad_with_depot(
 
     // Run Ninja build - explicitly build library targets
     let ninja = depot_cmd(depot_tools_dir, "ninja");
-    let status = Command::new(&ninja)
-        .args([
-            "-C",
-            final_build_dir.to_str().unwrap(),
-            "client:client",
-            "client:common",
-            "util:util",
-            "third_party/mini_chromium/mini_chromium/base:base",
-            "handler:crashpad_handler",
-        ])
-        .current_dir(&crashpad_dir)
-        .status()?;
+    let mut cmd = Command::new(&ninja);
+    cmd.args([
+        "-C",
+        final_build_dir.to_str().unwrap(),
+        "client:client",
+        "client:common",
+        "util:util",
+        "third_party/mini_chromium/mini_chromium/base:base",
+        "handler:crashpad_handler",
+    ]);
+    // The handler's code itself for the handler-linked feature
+    if cfg!(feature = "handler-linked") {
+        cmd.args(["handler:handler", "handler:common", "util:net"]);
+    }
+    let status = cmd.current_dir(&crashpad_dir).status()?;
 
     if !status.success() {
         return Err("ninja build failed".into());
@@ -167,6 +167,11 @@ impl BuildPhases {
 
             // Add handler executable for non-iOS platforms
             cmd.arg("handler:crashpad_handler");
+
+            // The handler's code itself for the handler-linked feature
+            if self.config.handler_linked {
+                cmd.args(["handler:handler", "util:net"]);
+            }
         }
 
         let output = cmd.output()?;
@@ -216,6 +221,10 @@ impl BuildPhases {
             // Windows-specific flags
             build.flag_if_supported("/EHsc");
 
+            if self.config.handler_linked {
+                build.define("CRASHPAD_RS_HANDLER_LINKED", None);
+            }
+
             // Match the runtime library with what GN is using
             // GN builds with /MDd in debug mode, /MD in release mode
             if self.config.profile == "debug" {
@@ -261,6 +270,10 @@ impl BuildPhases {
             cmd.args(["-DTARGET_OS_IOS=1"]);
         }
 
+        if self.config.handler_linked {
+            cmd.arg("-DCRASHPAD_RS_HANDLER_LINKED");
+        }
+
         // Add include paths
         cmd.args([
             "-I",
@@ -300,6 +313,10 @@ impl BuildPhases {
             eprintln!("Creating static library...");
         }
 
+        if self.config.handler_linked {
+            self.copy_handler_common()?;
+        }
+
         // Windows: cc crate already created the library
         if self.config.target.contains("windows") {
             let lib_path = self.config.out_dir.join("crashpad_wrapper.lib");
@@ -442,6 +459,7 @@ impl BuildPhases {
             obj_dir.join("minidump"),
             obj_dir.join("snapshot"),
             obj_dir.join("handler"),
+            obj_dir.join("third_party/zlib"),
             self.config.out_dir.clone(),
         ];
 
@@ -525,6 +543,24 @@ impl BuildPhases {
     }
 
     /// Copy crashpad_handler to target directory for consistent access
+    /// Copy handler:common to the output directory as handler_common, so it
+    /// can be linked next to client:common, which has the same file name.
+    fn copy_handler_common(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let (src, dst) = if self.config.target.contains("windows") {
+            ("common.lib", "handler_common.lib")
+        } else {
+            ("libcommon.a", "libhandler_common.a")
+        };
+
+        let src = self.config.build_dir().join("obj/handler").join(src);
+        if !src.exists() {
+            return Err(format!("Handler library not found at {}", src.display()).into());
+        }
+        fs::copy(&src, self.config.out_dir.join(dst))?;
+
+        Ok(())
+    }
+
     fn copy_handler_to_target(&self) -> Result<(), Box<dyn std::error::Error>> {
         // iOS doesn't have external handler
         if self.config.target.contains("ios") {
@@ -1,11 +1,65 @@
+#include "wrapper.h"
+
+#include "client/annotation.h"
+#include "client/annotation_list.h"
+#include "client/crash_report_database.h"
 #include "client/crashpad_client.h"
+#include "client/crashpad_info.h"
+#include "client/prune_crash_reports.h"
+#include "client/simple_address_range_bag.h"
+#include "client/simple_string_dictionary.h"
+#include "util/misc/metrics.h"
+#include <errno.h>
+#include <string.h>
+#include <time.h>
+#include <map>
 #include <memory>
+#include <mutex>
+#include <set>
+#include <string>
+#include <utility>
+#include <vector>
+
+#if defined(__linux__) || defined(__ANDROID__)
+#include <signal.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/syscall.h>
+
+#include "util/linux/socket.h"
+
+#include <atomic>
+#endif
+
+#ifndef _WIN32
+#include <fcntl.h>
+#include <limits.h>
+#include <pthread.h>
+#include <stdio.h>
+#include <sys/stat.h>
+#include <unistd.h>
+#endif
 
 #ifdef _WIN32
+#include <windows.h>
+#include <tlhelp32.h>
+
+#include <algorithm>
+
 #include "base/strings/utf_string_conversions.h"
 #endif
 
+#ifdef CRASHPAD_RS_HANDLER_LINKED
+#include "handler/handler_main.h"
+#include "handler/user_stream_data_source.h"
+#include "minidump/minidump_user_extension_stream_data_source.h"
+#include "snapshot/module_snapshot.h"
+#include "snapshot/process_snapshot.h"
+#include "util/process/process_memory.h"
+#endif
+
 #include "util/misc/capture_context.h"
+#include "util/misc/uuid.h"
 
 // Platform-specific includes for simulate crash
 #if defined(__APPLE__)
@@ -14,6 +68,8 @@
     #include "client/simulate_crash_ios.h"
   #else
     #include "client/simulate_crash_mac.h"
+    #include <mach/mach.h>
+    #include <pthread.h>
   #endif
 #elif defined(__linux__) || defined(__ANDROID__)
   #include "client/simulate_crash_linux.h"
@@ -23,10 +79,20 @@
 
 using namespace crashpad;
 
-extern "C" {
+static_assert(SimpleStringDictionary::num_entries == CRASHPAD_SIMPLE_DICTIONARY_ENTRIES,
+              "CRASHPAD_SIMPLE_DICTIONARY_ENTRIES doesn't match Crashpad");
+static_assert(SimpleStringDictionary::key_size == CRASHPAD_SIMPLE_DICTIONARY_KEY_SIZE,
+              "CRASHPAD_SIMPLE_DICTIONARY_KEY_SIZE doesn't match Crashpad");
+static_assert(SimpleStringDictionary::value_size == CRASHPAD_SIMPLE_DICTIONARY_VALUE_SIZE,
+              "CRASHPAD_SIMPLE_DICTIONARY_VALUE_SIZE doesn't match Crashpad");
+static_assert(SimpleAddressRangeBag::num_entries == CRASHPAD_EXTRA_MEMORY_RANGES,
+              "CRASHPAD_EXTRA_MEMORY_RANGES doesn't match Crashpad");
+static_assert(Annotation::kNameMaxLength == CRASHPAD_ANNOTATION_NAME_MAX_LENGTH,
+              "CRASHPAD_ANNOTATION_NAME_MAX_LENGTH doesn't match Crashpad");
+static_assert(Annotation::kValueMaxSize == CRASHPAD_ANNOTATION_VALUE_MAX_SIZE,
+              "CRASHPAD_ANNOTATION_VALUE_MAX_SIZE doesn't match Crashpad");
 
-// Opaque handle for CrashpadClient
-typedef void* crashpad_client_t;
+extern "C" {
 
 crashpad_client_t crashpad_client_new() {
     return new CrashpadClient();
@@ -36,8 +102,273 @@ void crashpad_client_delete(crashpad_client_t client) {
     delete static_cast<CrashpadClient*>(client);
 }
 
-bool crashpad_client_start_handler(
-    crashpad_client_t client,
+namespace {
+
+// Why the last start on this thread failed, see crashpad_client_last_start_error
+thread_local int g_last_start_error = CRASHPAD_START_ERROR_NONE;
+thread_local int g_last_start_os_error = 0;
+
+int LastOSError() {
+#ifdef _WIN32
+  return static_cast<int>(GetLastError());
+#else
+  return errno;
+#endif
+}
+
+void ClearOSError() {
+#ifdef _WIN32
+  SetLastError(0);
+#else
+  errno = 0;
+#endif
+}
+
+bool FailStart(int error, int os_error) {
+  g_last_start_error = error;
+  g_last_start_os_error = os_error;
+  return false;
+}
+
+// Log file for handlers started from now on, empty to inherit stderr
+std::string g_handler_log_path;
+
+// Working directory for handlers started from now on, empty to inherit ours
+std::string g_handler_working_directory;
+
+// umask for handlers started from now on, negative to inherit ours
+int g_handler_umask = -1;
+
+#ifdef _WIN32
+// Whether the calling thread is the only one of this process
+bool IsOnlyThread() {
+  HANDLE snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0);
+  if (snapshot == INVALID_HANDLE_VALUE) {
+    return false;
+  }
+  DWORD process = GetCurrentProcessId();
+  int threads = 0;
+  THREADENTRY32 entry = {};
+  entry.dwSize = sizeof(entry);
+  for (BOOL found = Thread32First(snapshot, &entry); found && threads < 2;
+       found = Thread32Next(snapshot, &entry)) {
+    if (entry.th32OwnerProcessID == process) {
+      threads++;
+    }
+  }
+  CloseHandle(snapshot);
+  return threads == 1;
+}
+
+// Points this process's stderr at a file while the handler is spawned, so
+// the handler inherits the file as its stderr: Crashpad hands the child
+// this process's standard handles, and Windows can't change them in the
+// child after the spawn. The handler is spawned synchronously, so the
+// original stderr is restored right after. As the standard handles are
+// shared by all threads, this is refused while other threads run, whose
+// output would go to the handler log.
+class ScopedStderrRedirect {
+ public:
+  explicit ScopedStderrRedirect(const std::string& path) {
+    if (path.empty()) {
+      return;
+    }
+    if (!IsOnlyThread()) {
+      failed_ = true;
+      os_error_ = ERROR_BUSY;
+      return;
+    }
+    // Must be inheritable to be passed to the handler
+    SECURITY_ATTRIBUTES security = {sizeof(security), nullptr, TRUE};
+    file_ = CreateFileW(base::UTF8ToWide(path).c_str(),
+                        FILE_APPEND_DATA,
+                        FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                        &security,
+                        OPEN_ALWAYS,
+                        FILE_ATTRIBUTE_NORMAL,
+                        nullptr);
+    if (file_ == INVALID_HANDLE_VALUE) {
+      Fail();
+      return;
+    }
+    saved_ = GetStdHandle(STD_ERROR_HANDLE);
+    SetStdHandle(STD_ERROR_HANDLE, file_);
+  }
+
+  ~ScopedStderrRedirect() {
+    if (file_ != INVALID_HANDLE_VALUE) {
+      SetStdHandle(STD_ERROR_HANDLE, saved_);
+      CloseHandle(file_);
+    }
+  }
+
+  ScopedStderrRedirect(const ScopedStderrRedirect&) = delete;
+  ScopedStderrRedirect& operator=(const ScopedStderrRedirect&) = delete;
+
+  bool failed() const { return failed_; }
+  int os_error() const { return os_error_; }
+
+ private:
+  void Fail() {
+    failed_ = true;
+    os_error_ = LastOSError();
+  }
+
+  bool failed_ = false;
+  int os_error_ = 0;
+  HANDLE file_ = INVALID_HANDLE_VALUE;
+  HANDLE saved_ = nullptr;
+};
+
+// Changes this process's working directory while the handler is spawned,
+// as Windows can't change it in the child. Refused while other threads
+// run, like ScopedStderrRedirect, as they'd resolve relative paths against
+// the handler's directory.
+class ScopedWorkingDirectory {
+ public:
+  explicit ScopedWorkingDirectory(const std::string& path) {
+    if (path.empty()) {
+      return;
+    }
+    if (!IsOnlyThread()) {
+      failed_ = true;
+      os_error_ = ERROR_BUSY;
+      return;
+    }
+    DWORD length = GetCurrentDirectoryW(0, nullptr);
+    saved_.resize(length);
+    if (length == 0 ||
+        GetCurrentDirectoryW(length, &saved_[0]) == 0 ||
+        !SetCurrentDirectoryW(base::UTF8ToWide(path).c_str())) {
+      saved_.clear();
+      Fail();
+    }
+  }
+
+  ~ScopedWorkingDirectory() {
+    if (!saved_.empty()) {
+      SetCurrentDirectoryW(saved_.c_str());
+    }
+  }
+
+  ScopedWorkingDirectory(const ScopedWorkingDirectory&) = delete;
+  ScopedWorkingDirectory& operator=(const ScopedWorkingDirectory&) = delete;
+
+  bool failed() const { return failed_; }
+  int os_error() const { return os_error_; }
+
+ private:
+  void Fail() {
+    failed_ = true;
+    os_error_ = LastOSError();
+  }
+
+  bool failed_ = false;
+  int os_error_ = 0;
+  std::wstring saved_;
+};
+#else
+// Shell the handler is started through when its process state differs
+// from ours, see WrapHandlerInShell
+#if defined(__ANDROID__)
+constexpr char kShell[] = "/system/bin/sh";
+#else
+constexpr char kShell[] = "/bin/sh";
+#endif
+
+// `value` quoted for the shell
+std::string ShellQuote(const std::string& value) {
+  std::string quoted = "'";
+  for (char c : value) {
+    if (c == '\'') {
+      quoted += "'\\''";
+    } else {
+      quoted += c;
+    }
+  }
+  return quoted + "'";
+}
+
+// Starts the handler through the shell if it logs to a file, runs in
+// another working directory or with another umask: Crashpad puts arguments
+// right after the executable, so `sh -c <script> <handler> <arguments>`
+// runs a script that opens the log as its stderr, changes the directory
+// and umask, and then execs the handler with Crashpad's arguments. The
+// handler keeps the process, its PID and the descriptors Crashpad passes
+// it, and this process's stderr, working directory and umask are left
+// alone, also for its other threads and for handlers Crashpad restarts.
+//
+// Returns false with errno set if the log can't be opened or the directory
+// doesn't exist, which is checked here to report it as a setup failure.
+bool WrapHandlerInShell(base::FilePath* handler, std::vector<std::string>* arguments) {
+  std::string script;
+  if (!g_handler_log_path.empty()) {
+    int fd = open(g_handler_log_path.c_str(),
+                  O_WRONLY | O_CREAT | O_APPEND | O_CLOEXEC, 0644);
+    if (fd < 0) {
+      return false;
+    }
+    close(fd);
+    script += "exec 2>>" + ShellQuote(g_handler_log_path) + " && ";
+  }
+  if (!g_handler_working_directory.empty()) {
+    struct stat directory;
+    if (stat(g_handler_working_directory.c_str(), &directory) != 0) {
+      return false;
+    }
+    if (!S_ISDIR(directory.st_mode)) {
+      errno = ENOTDIR;
+      return false;
+    }
+    script += "cd -- " + ShellQuote(g_handler_working_directory) + " && ";
+  }
+  if (g_handler_umask >= 0) {
+    char mask[8];
+    snprintf(mask, sizeof(mask), "%03o", g_handler_umask & 0777);
+    script += std::string("umask ") + mask + " && ";
+  }
+  if (script.empty()) {
+    return true;
+  }
+  script += "exec \"$0\" \"$@\"";
+
+  std::string handler_path = handler->value();
+  if (!handler_path.empty() && handler_path[0] != '/') {
+    // Relative to our working directory, not the handler's; the shell
+    // would also look a bare name up in PATH, unlike Crashpad
+    char cwd[PATH_MAX];
+    if (!getcwd(cwd, sizeof(cwd))) {
+      return false;
+    }
+    handler_path = std::string(cwd) + "/" + handler_path;
+  }
+  arguments->insert(arguments->begin(), {"-c", script, handler_path});
+  *handler = base::FilePath(kShell);
+  return true;
+}
+#endif
+
+}  // namespace
+
+void crashpad_client_set_handler_log(const char* path) {
+    g_handler_log_path = path ? path : "";
+}
+
+void crashpad_client_set_handler_working_directory(const char* path) {
+    g_handler_working_directory = path ? path : "";
+}
+
+void crashpad_client_set_handler_umask(int mask) {
+    g_handler_umask = mask;
+}
+
+namespace {
+
+// Spawn a handler with Start(handler, database, metrics, url, annotations,
+// arguments, asynchronous_start), applying the handler log, working
+// directory and umask, and recording the reason of a failure
+template <typename Start>
+bool StartHandlerProcess(
     const char* handler_path,
     const char* database_path,
     const char* metrics_path,
@@ -46,9 +377,8 @@ bool crashpad_client_start_handler(
     const char** annotations_values,
     size_t annotations_count,
     const char** extra_arguments,
-    size_t extra_arguments_count) {
-    
-    auto* crashpad_client = static_cast<CrashpadClient*>(client);
+    size_t extra_arguments_count,
+    Start start) {
     
 #ifdef _WIN32
     // Windows uses wide strings for paths
@@ -60,52 +390,825 @@ bool crashpad_client_start_handler(
     base::FilePath database(database_path);
     base::FilePath metrics(metrics_path);
 #endif
+
+    g_last_start_error = CRASHPAD_START_ERROR_NONE;
+    g_last_start_os_error = 0;
+#ifdef _WIN32
+    if (GetFileAttributesW(handler.value().c_str()) == INVALID_FILE_ATTRIBUTES) {
+        return FailStart(CRASHPAD_START_ERROR_HANDLER_NOT_FOUND, LastOSError());
+    }
+#else
+    if (access(handler_path, F_OK) != 0) {
+        return FailStart(CRASHPAD_START_ERROR_HANDLER_NOT_FOUND, LastOSError());
+    }
+#endif
     
     std::string url_str(url ? url : "");
     
     std::map<std::string, std::string> annotations;
     for (size_t i = 0; i < annotations_count; i++) {
-        annotations[annotations_keys[i]] = annotations_values[i];
+        annotations[annotations_keys[i]] = annotations_values[i];
+    }
+    
+    std::vector<std::string> arguments;
+    
+    // Add extra arguments from caller
+    if (extra_arguments != nullptr) {
+        for (size_t i = 0; i < extra_arguments_count; i++) {
+            if (extra_arguments[i]) {
+                arguments.push_back(extra_arguments[i]);
+            }
+        }
+    }
+    
+    // Linux doesn't support asynchronous start
+    #ifdef __linux__
+    bool asynchronous_start = false;
+    #elif defined(_WIN32)
+    // Start asynchronously on Windows, unless the process state the
+    // handler inherits is changed for the duration of the spawn
+    bool asynchronous_start = g_handler_log_path.empty() &&
+                              g_handler_working_directory.empty();
+    #else
+    bool asynchronous_start = true;
+    #endif
+
+#ifdef _WIN32
+    // Windows has no umask
+    ScopedStderrRedirect redirect(g_handler_log_path);
+    if (redirect.failed()) {
+        return FailStart(CRASHPAD_START_ERROR_SETUP_FAILED, redirect.os_error());
+    }
+    ScopedWorkingDirectory working_directory(g_handler_working_directory);
+    if (working_directory.failed()) {
+        return FailStart(CRASHPAD_START_ERROR_SETUP_FAILED, working_directory.os_error());
+    }
+#else
+    if (!WrapHandlerInShell(&handler, &arguments)) {
+        return FailStart(CRASHPAD_START_ERROR_SETUP_FAILED, LastOSError());
+    }
+#endif
+
+    ClearOSError();
+    if (!start(handler, database, metrics, url_str, annotations, arguments,
+               asynchronous_start)) {
+        return FailStart(CRASHPAD_START_ERROR_SPAWN_FAILED, LastOSError());
+    }
+    return true;
+}
+
+}  // namespace
+
+bool crashpad_client_start_handler(
+    crashpad_client_t client,
+    const char* handler_path,
+    const char* database_path,
+    const char* metrics_path,
+    const char* url,
+    const char** annotations_keys,
+    const char** annotations_values,
+    size_t annotations_count,
+    const char** extra_arguments,
+    size_t extra_arguments_count) {
+    
+    auto* crashpad_client = static_cast<CrashpadClient*>(client);
+    
+    return StartHandlerProcess(
+        handler_path, database_path, metrics_path, url,
+        annotations_keys, annotations_values, annotations_count,
+        extra_arguments, extra_arguments_count,
+        [crashpad_client](const base::FilePath& handler,
+                          const base::FilePath& database,
+                          const base::FilePath& metrics,
+                          const std::string& url,
+                          const std::map<std::string, std::string>& annotations,
+                          const std::vector<std::string>& arguments,
+                          bool asynchronous_start) {
+            bool restartable = true;
+            return crashpad_client->StartHandler(
+                handler,
+                database,
+                metrics,
+                url,
+                annotations,
+                arguments,
+                restartable,
+                asynchronous_start
+            );
+        });
+}
+
+void crashpad_set_module_annotation(const char* key, const char* value) {
+    static std::mutex mutex;
+    std::lock_guard<std::mutex> lock(mutex);
+
+    CrashpadInfo* info = CrashpadInfo::GetCrashpadInfo();
+    SimpleStringDictionary* annotations = info->simple_annotations();
+    if (!annotations) {
+        // Owned by CrashpadInfo for the life of the process
+        annotations = new SimpleStringDictionary();
+        info->set_simple_annotations(annotations);
+    }
+    if (value) {
+        annotations->SetKeyValue(key, value);
+    } else {
+        annotations->RemoveKey(key);
+    }
+}
+
+bool crashpad_annotation_register(
+    const char* name,
+    uint16_t user_type,
+    void* value,
+    uint32_t size) {
+
+    if (!name || strlen(name) >= Annotation::kNameMaxLength ||
+        size >= Annotation::kValueMaxSize ||
+        user_type >= static_cast<uint16_t>(Annotation::Type::kUserDefinedStart)) {
+        return false;
+    }
+
+    // The list is looked up through CrashpadInfo when a dump is written
+    static AnnotationList* list =
+        AnnotationList::Get() ? AnnotationList::Get() : AnnotationList::Register();
+    (void)list;
+
+    // Annotations can't be removed from the list, so neither they nor their
+    // names are ever freed
+    auto* stored_name = new std::string(name);
+    auto* annotation = new Annotation(
+        Annotation::UserDefinedType(user_type), stored_name->c_str(), value);
+    annotation->SetSize(size);
+    return true;
+}
+
+crashpad_annotation_t crashpad_string_annotation_register(
+    const char* name,
+    void* buffer,
+    uint32_t capacity) {
+
+    if (!name || !buffer || strlen(name) >= Annotation::kNameMaxLength ||
+        capacity >= Annotation::kValueMaxSize) {
+        return nullptr;
+    }
+
+    static AnnotationList* list =
+        AnnotationList::Get() ? AnnotationList::Get() : AnnotationList::Register();
+    (void)list;
+
+    // Never freed, see crashpad_annotation_register
+    auto* stored_name = new std::string(name);
+    return new Annotation(Annotation::Type::kString, stored_name->c_str(), buffer);
+}
+
+void crashpad_string_annotation_set_size(crashpad_annotation_t annotation, uint32_t size) {
+    if (!annotation) {
+        return;
+    }
+    auto* string_annotation = static_cast<Annotation*>(annotation);
+    if (size == 0) {
+        string_annotation->Clear();
+    } else {
+        // Adds the annotation to the list the first time
+        string_annotation->SetSize(size);
+    }
+}
+
+static std::mutex g_extra_memory_ranges_mutex;
+static SimpleAddressRangeBag* g_extra_memory_ranges = nullptr;
+
+bool crashpad_add_extra_memory_range(const void* base, size_t size) {
+    if (size == 0) {
+        return false;
+    }
+    std::lock_guard<std::mutex> lock(g_extra_memory_ranges_mutex);
+
+    if (!g_extra_memory_ranges) {
+        // Read through CrashpadInfo for the life of the process
+        g_extra_memory_ranges = new SimpleAddressRangeBag();
+        CrashpadInfo::GetCrashpadInfo()->set_extra_memory_ranges(g_extra_memory_ranges);
+    }
+    return g_extra_memory_ranges->Insert(const_cast<void*>(base), size);
+}
+
+bool crashpad_remove_extra_memory_range(const void* base, size_t size) {
+    std::lock_guard<std::mutex> lock(g_extra_memory_ranges_mutex);
+
+    return g_extra_memory_ranges &&
+           g_extra_memory_ranges->Remove(const_cast<void*>(base), size);
+}
+
+int crashpad_client_last_start_error(int* os_error) {
+    if (os_error) {
+        *os_error = g_last_start_os_error;
+    }
+    return g_last_start_error;
+}
+
+#ifdef _WIN32
+bool crashpad_client_set_handler_ipc_pipe(
+    crashpad_client_t client,
+    const wchar_t* ipc_pipe) {
+    
+    auto* crashpad_client = static_cast<CrashpadClient*>(client);
+    return crashpad_client->SetHandlerIPCPipe(ipc_pipe);
+}
+
+size_t crashpad_client_get_handler_ipc_pipe(
+    crashpad_client_t client,
+    wchar_t* buffer,
+    size_t buffer_len) {
+
+    auto* crashpad_client = static_cast<CrashpadClient*>(client);
+    std::wstring ipc_pipe = crashpad_client->GetHandlerIPCPipe();
+    if (buffer && ipc_pipe.size() < buffer_len) {
+        std::copy(ipc_pipe.begin(), ipc_pipe.end(), buffer);
+        buffer[ipc_pipe.size()] = L'\0';
+    }
+    return ipc_pipe.size();
+}
+
+namespace {
+
+// Crashpad's own unhandled exception filter, captured when we wrap it
+LPTOP_LEVEL_EXCEPTION_FILTER g_crashpad_filter = nullptr;
+std::vector<DWORD> g_excluded_codes;
+PVOID g_vectored_handler = nullptr;
+
+bool IsExcludedException(DWORD code) {
+    return std::find(g_excluded_codes.begin(), g_excluded_codes.end(), code) !=
+           g_excluded_codes.end();
+}
+
+// Exceptions that can't be recovered from by a later handler, so reporting
+// them at first chance doesn't steal exceptions a runtime would handle
+bool IsFatalAtFirstChance(const EXCEPTION_RECORD* record) {
+    return record->ExceptionCode == EXCEPTION_STACK_OVERFLOW ||
+           record->ExceptionCode == 0xC0000374 ||  // STATUS_HEAP_CORRUPTION
+           (record->ExceptionFlags & EXCEPTION_NONCONTINUABLE) != 0;
+}
+
+LONG WINAPI FilteringUnhandledExceptionFilter(EXCEPTION_POINTERS* info) {
+    if (!g_crashpad_filter ||
+        IsExcludedException(info->ExceptionRecord->ExceptionCode)) {
+        return EXCEPTION_CONTINUE_SEARCH;
+    }
+    return g_crashpad_filter(info);
+}
+
+LONG CALLBACK FatalVectoredExceptionHandler(EXCEPTION_POINTERS* info) {
+    if (!g_crashpad_filter ||
+        IsExcludedException(info->ExceptionRecord->ExceptionCode) ||
+        !IsFatalAtFirstChance(info->ExceptionRecord)) {
+        return EXCEPTION_CONTINUE_SEARCH;
+    }
+    return g_crashpad_filter(info);
+}
+
+}  // namespace
+
+bool crashpad_client_set_exception_filter(
+    int mode,
+    const unsigned int* excluded_codes,
+    size_t excluded_codes_count) {
+
+    g_excluded_codes.clear();
+    if (excluded_codes != nullptr) {
+        g_excluded_codes.assign(excluded_codes, excluded_codes + excluded_codes_count);
+    }
+
+    // Only capture the previous filter the first time, so calling this again
+    // doesn't make our filter call itself
+    LPTOP_LEVEL_EXCEPTION_FILTER previous =
+        SetUnhandledExceptionFilter(FilteringUnhandledExceptionFilter);
+    if (previous != FilteringUnhandledExceptionFilter) {
+        g_crashpad_filter = previous;
+    }
+    if (!g_crashpad_filter) {
+        // StartHandler hasn't installed a filter
+        return false;
+    }
+
+    if (g_vectored_handler) {
+        RemoveVectoredExceptionHandler(g_vectored_handler);
+        g_vectored_handler = nullptr;
+    }
+    if (mode == CRASHPAD_EXCEPTION_FILTER_VECTORED_FIRST ||
+        mode == CRASHPAD_EXCEPTION_FILTER_VECTORED_LAST) {
+        ULONG first = mode == CRASHPAD_EXCEPTION_FILTER_VECTORED_FIRST ? 1 : 0;
+        g_vectored_handler =
+            AddVectoredExceptionHandler(first, FatalVectoredExceptionHandler);
+        return g_vectored_handler != nullptr;
+    }
+    return true;
+}
+
+namespace {
+
+// Job Object holding the handler. Never closed explicitly: the system closes
+// it when this process exits, which terminates the handler.
+HANDLE g_handler_job = nullptr;
+base::FilePath g_handler_database;
+DWORD g_handler_grace_ms = 0;
+
+bool HasPendingReports(const base::FilePath& database_path) {
+    std::unique_ptr<CrashReportDatabase> database =
+        CrashReportDatabase::InitializeWithoutCreating(database_path);
+    std::vector<CrashReportDatabase::Report> reports;
+    return database &&
+           database->GetPendingReports(&reports) == CrashReportDatabase::kNoError &&
+           !reports.empty();
+}
+
+// Registered with atexit, so it only runs on a normal exit. After a crash,
+// Crashpad terminates the process and the handler goes with it.
+void WaitForPendingUploads() {
+    constexpr DWORD kPollIntervalMs = 100;
+    ULONGLONG deadline = GetTickCount64() + g_handler_grace_ms;
+    while (HasPendingReports(g_handler_database) && GetTickCount64() < deadline) {
+        Sleep(kPollIntervalMs);
+    }
+}
+
+// Process ID of the handler serving pipe_name. CrashpadClient doesn't keep
+// the handler's process handle, so ask the pipe for its server. The handler
+// sees a client that disconnects without registering, which it ignores.
+DWORD HandlerProcessId(const std::wstring& pipe_name) {
+    HANDLE pipe = CreateFileW(pipe_name.c_str(),
+                              GENERIC_READ | GENERIC_WRITE,
+                              0,
+                              nullptr,
+                              OPEN_EXISTING,
+                              SECURITY_SQOS_PRESENT | SECURITY_IDENTIFICATION,
+                              nullptr);
+    if (pipe == INVALID_HANDLE_VALUE) {
+        return 0;
+    }
+    ULONG pid = 0;
+    if (!GetNamedPipeServerProcessId(pipe, &pid)) {
+        pid = 0;
+    }
+    CloseHandle(pipe);
+    return pid;
+}
+
+}  // namespace
+
+int crashpad_client_set_handler_kill_on_exit(
+    crashpad_client_t client,
+    const char* database_path,
+    unsigned int grace_ms) {
+
+    auto* crashpad_client = static_cast<CrashpadClient*>(client);
+    if (g_handler_job) {
+        // Already assigned; a job can't be left again
+        return 0;
+    }
+    // The handler may still be starting asynchronously
+    if (!crashpad_client->WaitForHandlerStart(INFINITE)) {
+        return ERROR_INVALID_STATE;
+    }
+
+    DWORD pid = HandlerProcessId(crashpad_client->GetHandlerIPCPipe());
+    if (pid == 0) {
+        return static_cast<int>(GetLastError());
+    }
+    HANDLE process = OpenProcess(PROCESS_SET_QUOTA | PROCESS_TERMINATE, FALSE, pid);
+    if (!process) {
+        return static_cast<int>(GetLastError());
+    }
+
+    HANDLE job = CreateJobObjectW(nullptr, nullptr);
+    JOBOBJECT_EXTENDED_LIMIT_INFORMATION limits = {};
+    limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+    if (!job ||
+        !SetInformationJobObject(job, JobObjectExtendedLimitInformation,
+                                 &limits, sizeof(limits)) ||
+        !AssignProcessToJobObject(job, process)) {
+        int error = static_cast<int>(GetLastError());
+        if (job) {
+            CloseHandle(job);
+        }
+        CloseHandle(process);
+        return error;
+    }
+    CloseHandle(process);
+    g_handler_job = job;
+
+    if (grace_ms > 0 && database_path) {
+        g_handler_database = base::FilePath(base::UTF8ToWide(database_path));
+        g_handler_grace_ms = grace_ms;
+        atexit(WaitForPendingUploads);
+    }
+    return 0;
+}
+
+int crashpad_client_stop_handler(crashpad_client_t client) {
+    auto* crashpad_client = static_cast<CrashpadClient*>(client);
+    std::wstring pipe_name = crashpad_client->GetHandlerIPCPipe();
+    if (pipe_name.empty()) {
+        return 0;
+    }
+    DWORD pid = HandlerProcessId(pipe_name);
+    if (pid == 0) {
+        // Gone already if the pipe doesn't exist
+        DWORD error = GetLastError();
+        return error == ERROR_FILE_NOT_FOUND ? 0 : static_cast<int>(error);
+    }
+    HANDLE process = OpenProcess(PROCESS_TERMINATE, FALSE, pid);
+    if (!process) {
+        return static_cast<int>(GetLastError());
+    }
+    int error = TerminateProcess(process, 0) ? 0 : static_cast<int>(GetLastError());
+    CloseHandle(process);
+    return error;
+}
+
+int crashpad_client_handler_status(crashpad_client_t client) {
+    auto* crashpad_client = static_cast<CrashpadClient*>(client);
+    std::wstring pipe_name = crashpad_client->GetHandlerIPCPipe();
+    if (pipe_name.empty()) {
+        return CRASHPAD_HANDLER_STATUS_UNKNOWN;
+    }
+    // The pipe goes away with the handler
+    if (HandlerProcessId(pipe_name) != 0) {
+        return CRASHPAD_HANDLER_STATUS_RUNNING;
+    }
+    return GetLastError() == ERROR_FILE_NOT_FOUND ? CRASHPAD_HANDLER_STATUS_EXITED
+                                                  : CRASHPAD_HANDLER_STATUS_UNKNOWN;
+}
+
+int crashpad_client_handler_pid(crashpad_client_t client) {
+    auto* crashpad_client = static_cast<CrashpadClient*>(client);
+    std::wstring pipe_name = crashpad_client->GetHandlerIPCPipe();
+    return pipe_name.empty() ? 0 : static_cast<int>(HandlerProcessId(pipe_name));
+}
+#endif
+
+#if defined(__linux__) || defined(__ANDROID__)
+void crashpad_client_set_unhandled_signals(
+    const int* signals,
+    size_t signals_count) {
+
+    std::set<int> unhandled;
+    if (signals != nullptr) {
+        unhandled.insert(signals, signals + signals_count);
+    }
+    CrashpadClient::SetUnhandledSignals(unhandled);
+}
+
+namespace {
+
+struct sigaction g_previous_actions[NSIG];
+bool g_previous_actions_captured = false;
+bool g_chain_before_dump = false;
+
+// Crash reserve, taken with exchange so concurrent crashes release it once.
+// Descriptors are stored plus one, so the zero-initialized slots are empty.
+constexpr size_t kMaxReservedFds = 64;
+std::atomic<void*> g_reserved_memory{nullptr};
+std::atomic<size_t> g_reserved_memory_size{0};
+std::atomic<int> g_reserved_fds[kMaxReservedFds];
+
+// Only synchronous faults are safe to hand over first: if the previous
+// handler returns, the faulting instruction is retried. Returning from an
+// abort() or raise() handler would end the process without a dump.
+bool IsChainableFault(int signo, const siginfo_t* siginfo) {
+    switch (signo) {
+        case SIGSEGV:
+        case SIGBUS:
+        case SIGFPE:
+        case SIGILL:
+        case SIGTRAP:
+            return siginfo->si_code > 0;
+        default:
+            return false;
+    }
+}
+
+bool ChainBeforeDumpHandler(int signo, siginfo_t* siginfo, ucontext_t* context) {
+    if (signo <= 0 || signo >= NSIG || !IsChainableFault(signo, siginfo)) {
+        return false;
+    }
+
+    const struct sigaction& action = g_previous_actions[signo];
+    if (action.sa_flags & SA_SIGINFO) {
+        if (action.sa_sigaction == nullptr) {
+            return false;
+        }
+        action.sa_sigaction(signo, siginfo, context);
+    } else {
+        if (action.sa_handler == SIG_DFL || action.sa_handler == SIG_IGN) {
+            return false;
+        }
+        action.sa_handler(signo);
+    }
+    // The previous handler returned, so it resolved the fault
+    return true;
+}
+
+// Async-signal-safe: only munmap and close
+void ReleaseCrashReserve() {
+    void* memory = g_reserved_memory.exchange(nullptr);
+    if (memory != nullptr) {
+        munmap(memory, g_reserved_memory_size.load());
+    }
+    for (auto& fd : g_reserved_fds) {
+        int reserved = fd.exchange(0);
+        if (reserved > 0) {
+            close(reserved - 1);
+        }
+    }
+}
+
+bool FirstChanceHandler(int signo, siginfo_t* siginfo, ucontext_t* context) {
+    if (g_chain_before_dump && ChainBeforeDumpHandler(signo, siginfo, context)) {
+        return true;
+    }
+    // A dump is about to be requested
+    ReleaseCrashReserve();
+    return false;
+}
+
+bool HasCrashReserve() {
+    if (g_reserved_memory.load() != nullptr) {
+        return true;
+    }
+    for (const auto& fd : g_reserved_fds) {
+        if (fd.load() > 0) {
+            return true;
+        }
+    }
+    return false;
+}
+
+void UpdateFirstChanceHandler() {
+    CrashpadClient::SetFirstChanceExceptionHandler(
+        g_chain_before_dump || HasCrashReserve() ? FirstChanceHandler : nullptr);
+}
+
+}  // namespace
+
+void crashpad_client_set_chain_before_dump(bool enabled) {
+    g_chain_before_dump = enabled;
+    if (!enabled) {
+        UpdateFirstChanceHandler();
+        return;
+    }
+
+    // Capture only once: after the first start these would be Crashpad's own
+    // handlers, and calling them from the first-chance handler would recurse
+    if (!g_previous_actions_captured) {
+        for (int signo = 1; signo < NSIG; signo++) {
+            if (sigaction(signo, nullptr, &g_previous_actions[signo]) != 0) {
+                g_previous_actions[signo].sa_handler = SIG_DFL;
+                g_previous_actions[signo].sa_flags = 0;
+            }
+        }
+        g_previous_actions_captured = true;
+    }
+    UpdateFirstChanceHandler();
+}
+
+int crashpad_client_set_crash_reserve(size_t memory_bytes, size_t file_descriptors) {
+    ReleaseCrashReserve();
+
+    int error = 0;
+    if (memory_bytes > 0) {
+        void* memory = mmap(nullptr, memory_bytes, PROT_READ | PROT_WRITE,
+                            MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+        if (memory == MAP_FAILED) {
+            error = errno;
+        } else {
+            // Touch every page so the memory is committed, not just mapped
+            memset(memory, 1, memory_bytes);
+            g_reserved_memory_size.store(memory_bytes);
+            g_reserved_memory.store(memory);
+        }
+    }
+    for (size_t i = 0; error == 0 && i < file_descriptors && i < kMaxReservedFds; i++) {
+        int fd = open("/dev/null", O_RDONLY | O_CLOEXEC);
+        if (fd < 0) {
+            error = errno;
+        } else {
+            g_reserved_fds[i].store(fd + 1);
+        }
+    }
+
+    if (error != 0) {
+        ReleaseCrashReserve();
+    }
+    UpdateFirstChanceHandler();
+    return error;
+}
+
+int crashpad_create_handler_socketpair(int* server_socket, int* client_socket) {
+    ScopedFileHandle server;
+    ScopedFileHandle client;
+    errno = 0;
+    if (!UnixCredentialSocket::CreateCredentialSocketpair(&server, &client)) {
+        return errno != 0 ? errno : EIO;
+    }
+    // Like all descriptors Rust creates, so they don't leak into children
+    if (fcntl(server.get(), F_SETFD, FD_CLOEXEC) != 0 ||
+        fcntl(client.get(), F_SETFD, FD_CLOEXEC) != 0) {
+        return errno;
+    }
+    *server_socket = server.release();
+    *client_socket = client.release();
+    return 0;
+}
+
+int crashpad_client_stop_handler(crashpad_client_t) {
+    int socket = -1;
+    pid_t pid = -1;
+    // Nothing to stop, or a handler started only at crash on Android
+    if (!CrashpadClient::GetHandlerSocket(&socket, &pid) || pid <= 0) {
+        return 0;
+    }
+    if (kill(pid, SIGTERM) != 0 && errno != ESRCH) {
+        return errno;
+    }
+    return 0;
+}
+
+int crashpad_client_handler_status(crashpad_client_t) {
+    int socket = -1;
+    pid_t pid = -1;
+    if (!CrashpadClient::GetHandlerSocket(&socket, &pid) || pid <= 0) {
+        return CRASHPAD_HANDLER_STATUS_UNKNOWN;
+    }
+    // EPERM: alive, but running as another user
+    if (kill(pid, 0) == 0 || errno == EPERM) {
+        return CRASHPAD_HANDLER_STATUS_RUNNING;
+    }
+    return errno == ESRCH ? CRASHPAD_HANDLER_STATUS_EXITED : CRASHPAD_HANDLER_STATUS_UNKNOWN;
+}
+
+int crashpad_client_handler_pid(crashpad_client_t) {
+    int socket = -1;
+    pid_t pid = -1;
+    if (!CrashpadClient::GetHandlerSocket(&socket, &pid) || pid <= 0) {
+        return 0;
+    }
+    return pid;
+}
+
+bool crashpad_client_get_handler_socket(int* socket, int* handler_pid) {
+    pid_t pid = -1;
+    if (!CrashpadClient::GetHandlerSocket(socket, &pid)) {
+        return false;
+    }
+    *handler_pid = pid;
+    return true;
+}
+
+bool crashpad_client_set_handler_socket(
+    crashpad_client_t client,
+    int socket,
+    int handler_pid) {
+
+    auto* crashpad_client = static_cast<CrashpadClient*>(client);
+    return crashpad_client->SetHandlerSocket(ScopedFileHandle(socket), handler_pid);
+}
+
+bool crashpad_client_start_handler_for_client(
+    const char* handler_path,
+    const char* database_path,
+    const char* metrics_path,
+    const char* url,
+    const char** annotations_keys,
+    const char** annotations_values,
+    size_t annotations_count,
+    const char** extra_arguments,
+    size_t extra_arguments_count,
+    int socket) {
+
+    return StartHandlerProcess(
+        handler_path, database_path, metrics_path, url,
+        annotations_keys, annotations_values, annotations_count,
+        extra_arguments, extra_arguments_count,
+        [socket](const base::FilePath& handler,
+                 const base::FilePath& database,
+                 const base::FilePath& metrics,
+                 const std::string& url,
+                 const std::map<std::string, std::string>& annotations,
+                 const std::vector<std::string>& arguments,
+                 bool) {
+            return CrashpadClient::StartHandlerForClient(
+                handler, database, metrics, url, annotations, arguments, socket);
+        });
+}
+#endif
+
+#if defined(__ANDROID__)
+namespace {
+
+// Handler arguments of the app_process based starts, which take no
+// handler executable and inherit nothing from this process at spawn time
+struct AndroidStartArguments {
+    std::unique_ptr<std::vector<std::string>> env;
+    base::FilePath database;
+    base::FilePath metrics;
+    std::string url;
+    std::map<std::string, std::string> annotations;
+    std::vector<std::string> arguments;
+};
+
+AndroidStartArguments MakeAndroidStartArguments(
+    const char** env,
+    size_t env_count,
+    const char* database_path,
+    const char* metrics_path,
+    const char* url,
+    const char** annotations_keys,
+    const char** annotations_values,
+    size_t annotations_count,
+    const char** extra_arguments,
+    size_t extra_arguments_count) {
+
+    AndroidStartArguments start;
+    if (env != nullptr) {
+        start.env.reset(new std::vector<std::string>(env, env + env_count));
+    }
+    start.database = base::FilePath(database_path);
+    start.metrics = base::FilePath(metrics_path);
+    start.url = url ? url : "";
+    for (size_t i = 0; i < annotations_count; i++) {
+        start.annotations[annotations_keys[i]] = annotations_values[i];
     }
-    
-    std::vector<std::string> arguments;
-    
-    // Add extra arguments from caller
     if (extra_arguments != nullptr) {
         for (size_t i = 0; i < extra_arguments_count; i++) {
             if (extra_arguments[i]) {
-                arguments.push_back(extra_arguments[i]);
+                start.arguments.push_back(extra_arguments[i]);
             }
         }
     }
-    
-    bool restartable = true;
-    // Linux doesn't support asynchronous start
-    #ifdef __linux__
-    bool asynchronous_start = false;
-    #else
-    bool asynchronous_start = true;  // Start asynchronously on other platforms
-    #endif
-    
-    return crashpad_client->StartHandler(
-        handler,
-        database,
-        metrics,
-        url_str,
-        annotations,
-        arguments,
-        restartable,
-        asynchronous_start
-    );
+    return start;
 }
 
-#ifdef _WIN32
-bool crashpad_client_set_handler_ipc_pipe(
-    crashpad_client_t client,
-    const wchar_t* ipc_pipe) {
-    
-    auto* crashpad_client = static_cast<CrashpadClient*>(client);
-    return crashpad_client->SetHandlerIPCPipe(ipc_pipe);
+}  // namespace
+
+bool crashpad_client_start_java_handler(
+    const char* class_name,
+    const char** env,
+    size_t env_count,
+    const char* database_path,
+    const char* metrics_path,
+    const char* url,
+    const char** annotations_keys,
+    const char** annotations_values,
+    size_t annotations_count,
+    const char** extra_arguments,
+    size_t extra_arguments_count,
+    int socket) {
+
+    AndroidStartArguments start = MakeAndroidStartArguments(
+        env, env_count, database_path, metrics_path, url,
+        annotations_keys, annotations_values, annotations_count,
+        extra_arguments, extra_arguments_count);
+
+    if (socket < 0) {
+        return CrashpadClient::StartJavaHandlerAtCrash(
+            class_name, start.env.get(), start.database, start.metrics,
+            start.url, start.annotations, start.arguments);
+    }
+    return CrashpadClient::StartJavaHandlerForClient(
+        class_name, start.env.get(), start.database, start.metrics,
+        start.url, start.annotations, start.arguments, socket);
+}
+
+bool crashpad_client_start_handler_with_linker(
+    const char* handler_trampoline,
+    const char* handler_library,
+    bool is_64_bit,
+    const char** env,
+    size_t env_count,
+    const char* database_path,
+    const char* metrics_path,
+    const char* url,
+    const char** annotations_keys,
+    const char** annotations_values,
+    size_t annotations_count,
+    const char** extra_arguments,
+    size_t extra_arguments_count,
+    int socket) {
+
+    AndroidStartArguments start = MakeAndroidStartArguments(
+        env, env_count, database_path, metrics_path, url,
+        annotations_keys, annotations_values, annotations_count,
+        extra_arguments, extra_arguments_count);
+
+    if (socket < 0) {
+        return CrashpadClient::StartHandlerWithLinkerAtCrash(
+            handler_trampoline, handler_library, is_64_bit, start.env.get(),
+            start.database, start.metrics, start.url, start.annotations,
+            start.arguments);
+    }
+    return CrashpadClient::StartHandlerWithLinkerForClient(
+        handler_trampoline, handler_library, is_64_bit, start.env.get(),
+        start.database, start.metrics, start.url, start.annotations,
+        start.arguments, socket);
 }
 #endif
 
@@ -125,6 +1228,88 @@ bool crashpad_client_use_system_default_handler(
     crashpad_client->UseSystemDefaultHandler();
     return true;  // This method returns void in Crashpad
 }
+
+#if !TARGET_OS_IOS
+int crashpad_client_stop_handler(crashpad_client_t client) {
+    // Releases the handler's exception port, after which it exits
+    static_cast<CrashpadClient*>(client)->UseSystemDefaultHandler();
+    return 0;
+}
+
+int crashpad_client_handler_status(crashpad_client_t) {
+    exception_mask_t masks[EXC_TYPES_COUNT];
+    mach_msg_type_number_t count = EXC_TYPES_COUNT;
+    mach_port_t ports[EXC_TYPES_COUNT];
+    exception_behavior_t behaviors[EXC_TYPES_COUNT];
+    thread_state_flavor_t flavors[EXC_TYPES_COUNT];
+    kern_return_t kr = task_get_exception_ports(
+        mach_task_self(), EXC_MASK_CRASH, masks, &count, ports, behaviors, flavors);
+    if (kr != KERN_SUCCESS || count == 0) {
+        return CRASHPAD_HANDLER_STATUS_UNKNOWN;
+    }
+
+    // The port dies with the handler's receive right
+    int status = CRASHPAD_HANDLER_STATUS_UNKNOWN;
+    if (ports[0] == MACH_PORT_DEAD) {
+        status = CRASHPAD_HANDLER_STATUS_EXITED;
+    } else if (MACH_PORT_VALID(ports[0])) {
+        status = CRASHPAD_HANDLER_STATUS_RUNNING;
+    }
+    for (mach_msg_type_number_t i = 0; i < count; i++) {
+        if (MACH_PORT_VALID(ports[i])) {
+            mach_port_deallocate(mach_task_self(), ports[i]);
+        }
+    }
+    return status;
+}
+
+int crashpad_client_handler_pid(crashpad_client_t) {
+    // The handler is only known by its exception port
+    return 0;
+}
+#endif
+#endif
+
+#if defined(__APPLE__) && !TARGET_OS_IOS
+bool crashpad_client_set_mach_exception_mask(unsigned int mask) {
+    // Exception types StartHandler registers the handler for
+    const exception_mask_t kCrashpadDefaultMask =
+        EXC_MASK_CRASH | EXC_MASK_RESOURCE | EXC_MASK_GUARD;
+
+    // Look up the port StartHandler installed for EXC_CRASH
+    exception_mask_t masks[EXC_TYPES_COUNT];
+    mach_msg_type_number_t count = EXC_TYPES_COUNT;
+    mach_port_t ports[EXC_TYPES_COUNT];
+    exception_behavior_t behaviors[EXC_TYPES_COUNT];
+    thread_state_flavor_t flavors[EXC_TYPES_COUNT];
+    kern_return_t kr = task_get_exception_ports(
+        mach_task_self(), EXC_MASK_CRASH, masks, &count, ports, behaviors, flavors);
+    if (kr != KERN_SUCCESS || count == 0 || !MACH_PORT_VALID(ports[0])) {
+        return false;
+    }
+
+    bool success = true;
+    if (mask != 0) {
+        success = task_set_exception_ports(
+            mach_task_self(), mask, ports[0], behaviors[0], flavors[0]) == KERN_SUCCESS;
+    }
+
+    // Leave the default types that were removed to the host (ReportCrash) or
+    // a debugger
+    exception_mask_t released = kCrashpadDefaultMask & ~mask;
+    if (success && released != 0) {
+        success = task_set_exception_ports(
+            mach_task_self(), released, MACH_PORT_NULL,
+            EXCEPTION_DEFAULT, THREAD_STATE_NONE) == KERN_SUCCESS;
+    }
+
+    for (mach_msg_type_number_t i = 0; i < count; i++) {
+        if (MACH_PORT_VALID(ports[i])) {
+            mach_port_deallocate(mach_task_self(), ports[i]);
+        }
+    }
+    return success;
+}
 #endif
 
 #if defined(__APPLE__) && defined(TARGET_OS_IOS) && TARGET_OS_IOS
@@ -165,6 +1350,46 @@ void crashpad_client_process_intermediate_dumps() {
 void crashpad_client_start_processing_pending_reports() {
     CrashpadClient::StartProcessingPendingReports();
 }
+
+namespace {
+    std::map<std::string, std::string> annotation_map(
+        const char** keys,
+        const char** values,
+        size_t count) {
+        std::map<std::string, std::string> annotations;
+        for (size_t i = 0; i < count; i++) {
+            annotations[keys[i]] = values[i];
+        }
+        return annotations;
+    }
+}
+
+void crashpad_client_process_intermediate_dumps_with_annotations(
+    const char** annotations_keys,
+    const char** annotations_values,
+    size_t annotations_count) {
+    CrashpadClient::ProcessIntermediateDumps(annotation_map(
+        annotations_keys, annotations_values, annotations_count));
+}
+
+void crashpad_client_process_intermediate_dump(
+    const char* file_path,
+    const char** annotations_keys,
+    const char** annotations_values,
+    size_t annotations_count) {
+    CrashpadClient::ProcessIntermediateDump(
+        base::FilePath(file_path),
+        annotation_map(
+            annotations_keys, annotations_values, annotations_count));
+}
+
+void crashpad_client_start_processing_pending_reports_with_behavior(
+    int upload_behavior) {
+    CrashpadClient::StartProcessingPendingReports(
+        upload_behavior == CRASHPAD_UPLOAD_IMMEDIATELY
+            ? CrashpadClient::UploadBehavior::kUploadImmediately
+            : CrashpadClient::UploadBehavior::kUploadWhenAppIsActive);
+}
 #endif
 
 // DumpWithoutCrash/SimulateCrash support
@@ -225,4 +1450,610 @@ void crashpad_dump_without_crash_with_context(void* context) {
     #error "Unsupported platform for dump without crash"
 #endif
 
+namespace {
+    class PureVirtualBase {
+    public:
+        // Calls Run() before the derived class has been constructed
+        PureVirtualBase() { Call(); }
+        virtual ~PureVirtualBase() = default;
+
+    private:
+        void Call() { Run(); }
+        virtual void Run() = 0;
+    };
+
+    class PureVirtualDerived : public PureVirtualBase {
+    private:
+        void Run() override {}
+    };
+}
+
+void crashpad_simulate_pure_virtual_call() {
+    PureVirtualDerived derived;
+}
+
+#if defined(_WIN32)
+uint64_t crashpad_current_thread_id() {
+    return GetCurrentThreadId();
+}
+
+int crashpad_dump_without_crash_of_thread(uint64_t thread_id) {
+    if (thread_id == GetCurrentThreadId()) {
+        crashpad_dump_without_crash();
+        return 0;
+    }
+
+    HANDLE thread = OpenThread(THREAD_SUSPEND_RESUME | THREAD_GET_CONTEXT,
+                               FALSE,
+                               static_cast<DWORD>(thread_id));
+    if (!thread) {
+        return static_cast<int>(GetLastError());
+    }
+
+    int result = 0;
+    if (SuspendThread(thread) == static_cast<DWORD>(-1)) {
+        result = static_cast<int>(GetLastError());
+    } else {
+        CONTEXT context = {};
+        context.ContextFlags = CONTEXT_ALL;
+        if (GetThreadContext(thread, &context)) {
+            // Keep the thread suspended so its stack matches the context
+            CrashpadClient::DumpWithoutCrash(context);
+        } else {
+            result = static_cast<int>(GetLastError());
+        }
+        ResumeThread(thread);
+    }
+    CloseHandle(thread);
+    return result;
+}
+#elif defined(__APPLE__) && !TARGET_OS_IOS
+uint64_t crashpad_current_thread_id() {
+    uint64_t thread_id = 0;
+    pthread_threadid_np(nullptr, &thread_id);
+    return thread_id;
+}
+
+namespace {
+    // Send right to the thread of this task with the given system-wide ID,
+    // MACH_PORT_NULL if there is none
+    thread_act_t find_thread(uint64_t thread_id) {
+        thread_act_array_t threads;
+        mach_msg_type_number_t count;
+        if (task_threads(mach_task_self(), &threads, &count) != KERN_SUCCESS) {
+            return MACH_PORT_NULL;
+        }
+
+        thread_act_t found = MACH_PORT_NULL;
+        for (mach_msg_type_number_t i = 0; i < count; ++i) {
+            thread_identifier_info_data_t info;
+            mach_msg_type_number_t info_count = THREAD_IDENTIFIER_INFO_COUNT;
+            if (found == MACH_PORT_NULL &&
+                thread_info(threads[i],
+                            THREAD_IDENTIFIER_INFO,
+                            reinterpret_cast<thread_info_t>(&info),
+                            &info_count) == KERN_SUCCESS &&
+                info.thread_id == thread_id) {
+                found = threads[i];
+            } else {
+                mach_port_deallocate(mach_task_self(), threads[i]);
+            }
+        }
+        vm_deallocate(mach_task_self(),
+                      reinterpret_cast<vm_address_t>(threads),
+                      count * sizeof(thread_act_t));
+        return found;
+    }
+}
+
+int crashpad_dump_without_crash_of_thread(uint64_t thread_id) {
+    if (thread_id == crashpad_current_thread_id()) {
+        crashpad_dump_without_crash();
+        return 0;
+    }
+
+    thread_act_t thread = find_thread(thread_id);
+    if (thread == MACH_PORT_NULL) {
+        return ESRCH;
+    }
+
+    int result = 0;
+    if (thread_suspend(thread) != KERN_SUCCESS) {
+        result = EIO;
+    } else {
+        NativeCPUContext context = {};
+#if defined(__x86_64__)
+        context.tsh.flavor = x86_THREAD_STATE64;
+        context.tsh.count = x86_THREAD_STATE64_COUNT;
+        mach_msg_type_number_t state_count = x86_THREAD_STATE64_COUNT;
+        kern_return_t kr = thread_get_state(
+            thread,
+            x86_THREAD_STATE64,
+            reinterpret_cast<thread_state_t>(&context.uts.ts64),
+            &state_count);
+#elif defined(__aarch64__)
+        context.ash.flavor = ARM_THREAD_STATE64;
+        context.ash.count = ARM_THREAD_STATE64_COUNT;
+        mach_msg_type_number_t state_count = ARM_THREAD_STATE64_COUNT;
+        kern_return_t kr = thread_get_state(
+            thread,
+            ARM_THREAD_STATE64,
+            reinterpret_cast<thread_state_t>(&context.ts_64),
+            &state_count);
+#else
+#error "Unsupported architecture for dump of another thread"
+#endif
+        if (kr == KERN_SUCCESS) {
+            // Keep the thread suspended so its stack matches the context
+            SimulateCrash(context);
+        } else {
+            result = EIO;
+        }
+        thread_resume(thread);
+    }
+    mach_port_deallocate(mach_task_self(), thread);
+    return result;
+}
+#endif
+
+int crashpad_utc_offset(int64_t unix_time) {
+    time_t time = static_cast<time_t>(unix_time);
+    struct tm local;
+#ifdef _WIN32
+    if (localtime_s(&local, &time) != 0) {
+        return 0;
+    }
+    // _mkgmtime reads the broken-down local time as if it were UTC
+    return static_cast<int>(_mkgmtime(&local) - time);
+#else
+    if (localtime_r(&time, &local) == nullptr) {
+        return 0;
+    }
+    return static_cast<int>(local.tm_gmtoff);
+#endif
+}
+
+bool crashpad_current_thread_stack(uint64_t* low, uint64_t* high, uint64_t* guard_size) {
+    if (!low || !high || !guard_size) {
+        return false;
+    }
+#if defined(_WIN32)
+    ULONG_PTR stack_low = 0;
+    ULONG_PTR stack_high = 0;
+    GetCurrentThreadStackLimits(&stack_low, &stack_high);
+    SYSTEM_INFO system_info;
+    GetSystemInfo(&system_info);
+    // The guard page, plus what SetThreadStackGuarantee reserved for
+    // handling the overflow
+    ULONG guarantee = 0;
+    SetThreadStackGuarantee(&guarantee);
+    *low = stack_low;
+    *high = stack_high;
+    *guard_size = system_info.dwPageSize + guarantee;
+    return stack_high > stack_low;
+#elif defined(__APPLE__)
+    pthread_t self = pthread_self();
+    // The address is the top of the stack, which grows down
+    uint64_t top = reinterpret_cast<uintptr_t>(pthread_get_stackaddr_np(self));
+    uint64_t size = pthread_get_stacksize_np(self);
+    *low = top - size;
+    *high = top;
+    // Threads get the default one-page guard, the main thread one the
+    // kernel maps below the stack
+    *guard_size = static_cast<uint64_t>(getpagesize());
+    return size > 0;
+#else
+    pthread_attr_t attr;
+    if (pthread_getattr_np(pthread_self(), &attr) != 0) {
+        return false;
+    }
+    void* address = nullptr;
+    size_t size = 0;
+    size_t guard = 0;
+    bool ok = pthread_attr_getstack(&attr, &address, &size) == 0 &&
+              pthread_attr_getguardsize(&attr, &guard) == 0;
+    pthread_attr_destroy(&attr);
+    if (!ok || size == 0) {
+        return false;
+    }
+    *low = reinterpret_cast<uintptr_t>(address);
+    *high = *low + size;
+    if (guard == 0 && getpid() == static_cast<pid_t>(syscall(SYS_gettid))) {
+        // The main thread's guard is the kernel's stack_guard_gap, 256
+        // pages unless changed on the kernel command line
+        guard = 256 * static_cast<size_t>(getpagesize());
+    }
+    *guard_size = guard;
+    return true;
+#endif
+}
+
+// Crash report database
+
+namespace {
+
+base::FilePath FilePathFromUTF8(const char* path) {
+#ifdef _WIN32
+    return base::FilePath(base::UTF8ToWide(path));
+#else
+    return base::FilePath(path);
+#endif
+}
+
+std::string FilePathToUTF8(const base::FilePath& path) {
+#ifdef _WIN32
+    return base::WideToUTF8(path.value());
+#else
+    return path.value();
+#endif
+}
+
+void VisitReport(const CrashReportDatabase::Report& report,
+                 crashpad_report_visitor_t visitor,
+                 void* context) {
+    std::string uuid = report.uuid.ToString();
+    std::string file_path = FilePathToUTF8(report.file_path);
+
+    crashpad_report_t c_report = {};
+    c_report.uuid = uuid.c_str();
+    c_report.file_path = file_path.c_str();
+    c_report.id = report.id.c_str();
+    c_report.creation_time = report.creation_time;
+    c_report.uploaded = report.uploaded;
+    c_report.last_upload_attempt_time = report.last_upload_attempt_time;
+    c_report.upload_attempts = report.upload_attempts;
+    c_report.upload_explicitly_requested = report.upload_explicitly_requested;
+    c_report.total_size = report.total_size;
+    visitor(context, &c_report);
+}
+
+bool ParseUUID(const char* string, UUID* uuid) {
+    return string && uuid->InitializeFromString(std::string(string));
+}
+
+// Never prunes the reports in keep, whatever the wrapped condition says. The
+// wrapped condition still sees them, so a size limit counts their size.
+class KeepReportsPruneCondition final : public PruneCondition {
+public:
+    KeepReportsPruneCondition(PruneCondition* condition, std::set<std::string> keep)
+        : condition_(condition), keep_(std::move(keep)) {}
+
+    bool ShouldPruneReport(const CrashReportDatabase::Report& report) override {
+        bool prune = condition_->ShouldPruneReport(report);
+        return prune && keep_.count(report.uuid.ToString()) == 0;
+    }
+
+private:
+    PruneCondition* condition_;
+    std::set<std::string> keep_;
+};
+
+}  // namespace
+
+crashpad_database_t crashpad_database_open(const char* path, bool create) {
+    base::FilePath database_path = FilePathFromUTF8(path);
+    std::unique_ptr<CrashReportDatabase> database =
+        create ? CrashReportDatabase::Initialize(database_path)
+               : CrashReportDatabase::InitializeWithoutCreating(database_path);
+    return database.release();
+}
+
+void crashpad_database_close(crashpad_database_t database) {
+    delete static_cast<CrashReportDatabase*>(database);
+}
+
+int crashpad_database_get_reports(
+    crashpad_database_t database,
+    bool completed,
+    crashpad_report_visitor_t visitor,
+    void* context) {
+    auto* db = static_cast<CrashReportDatabase*>(database);
+    std::vector<CrashReportDatabase::Report> reports;
+    CrashReportDatabase::OperationStatus status =
+        completed ? db->GetCompletedReports(&reports) : db->GetPendingReports(&reports);
+    if (status != CrashReportDatabase::kNoError) {
+        return status;
+    }
+    for (const auto& report : reports) {
+        VisitReport(report, visitor, context);
+    }
+    return CrashReportDatabase::kNoError;
+}
+
+int crashpad_database_look_up_report(
+    crashpad_database_t database,
+    const char* uuid,
+    crashpad_report_visitor_t visitor,
+    void* context) {
+    UUID report_uuid;
+    if (!ParseUUID(uuid, &report_uuid)) {
+        return CrashReportDatabase::kReportNotFound;
+    }
+    CrashReportDatabase::Report report;
+    CrashReportDatabase::OperationStatus status =
+        static_cast<CrashReportDatabase*>(database)->LookUpCrashReport(report_uuid, &report);
+    if (status == CrashReportDatabase::kNoError) {
+        VisitReport(report, visitor, context);
+    }
+    return status;
+}
+
+int crashpad_database_delete_report(crashpad_database_t database, const char* uuid) {
+    UUID report_uuid;
+    if (!ParseUUID(uuid, &report_uuid)) {
+        return CrashReportDatabase::kReportNotFound;
+    }
+    return static_cast<CrashReportDatabase*>(database)->DeleteReport(report_uuid);
+}
+
+int crashpad_database_request_upload(crashpad_database_t database, const char* uuid) {
+    UUID report_uuid;
+    if (!ParseUUID(uuid, &report_uuid)) {
+        return CrashReportDatabase::kReportNotFound;
+    }
+    return static_cast<CrashReportDatabase*>(database)->RequestUpload(report_uuid);
+}
+
+bool crashpad_database_get_uploads_enabled(crashpad_database_t database, bool* enabled) {
+    Settings* settings = static_cast<CrashReportDatabase*>(database)->GetSettings();
+    return settings && settings->GetUploadsEnabled(enabled);
+}
+
+bool crashpad_database_set_uploads_enabled(crashpad_database_t database, bool enabled) {
+    Settings* settings = static_cast<CrashReportDatabase*>(database)->GetSettings();
+    return settings && settings->SetUploadsEnabled(enabled);
+}
+
+bool crashpad_database_get_client_id(crashpad_database_t database, char* client_id) {
+    Settings* settings = static_cast<CrashReportDatabase*>(database)->GetSettings();
+    UUID uuid;
+    if (!settings || !settings->GetClientID(&uuid)) {
+        return false;
+    }
+    std::string string = uuid.ToString();
+    string.copy(client_id, 36);
+    client_id[36] = '\0';
+    return true;
+}
+
+bool crashpad_database_get_last_upload_attempt_time(crashpad_database_t database, int64_t* time) {
+    Settings* settings = static_cast<CrashReportDatabase*>(database)->GetSettings();
+    time_t last = 0;
+    if (!settings || !settings->GetLastUploadAttemptTime(&last)) {
+        return false;
+    }
+    *time = static_cast<int64_t>(last);
+    return true;
+}
+
+int crashpad_database_skip_upload(crashpad_database_t database, const char* uuid) {
+    UUID report_uuid;
+    if (!ParseUUID(uuid, &report_uuid)) {
+        return CrashReportDatabase::kReportNotFound;
+    }
+    return static_cast<CrashReportDatabase*>(database)->SkipReportUpload(
+        report_uuid, Metrics::CrashSkippedReason::kUploadThrottled);
+}
+
+int crashpad_database_record_upload(
+    crashpad_database_t database,
+    const char* uuid,
+    bool uploaded,
+    const char* id) {
+    UUID report_uuid;
+    if (!ParseUUID(uuid, &report_uuid)) {
+        return CrashReportDatabase::kReportNotFound;
+    }
+    auto* db = static_cast<CrashReportDatabase*>(database);
+    std::unique_ptr<const CrashReportDatabase::UploadReport> report;
+    CrashReportDatabase::OperationStatus status =
+        db->GetReportForUploading(report_uuid, &report);
+    if (status != CrashReportDatabase::kNoError || !uploaded) {
+        // Dropping the report records a failed attempt
+        return status;
+    }
+    return db->RecordUploadComplete(std::move(report), id ? id : "");
+}
+
+int crashpad_database_add_report(
+    crashpad_database_t database,
+    const uint8_t* data,
+    size_t size,
+    char* uuid) {
+    auto* db = static_cast<CrashReportDatabase*>(database);
+    std::unique_ptr<CrashReportDatabase::NewReport> new_report;
+    CrashReportDatabase::OperationStatus status = db->PrepareNewCrashReport(&new_report);
+    if (status != CrashReportDatabase::kNoError) {
+        return status;
+    }
+    if (!new_report->Writer()->Write(data, size)) {
+        // Dropping new_report removes the partial file
+        return CrashReportDatabase::kFileSystemError;
+    }
+    UUID report_uuid;
+    status = db->FinishedWritingCrashReport(std::move(new_report), &report_uuid);
+    if (status == CrashReportDatabase::kNoError) {
+        std::string string = report_uuid.ToString();
+        string.copy(uuid, 36);
+        uuid[36] = '\0';
+    }
+    return status;
+}
+
+size_t crashpad_database_prune(
+    crashpad_database_t database,
+    int max_age_days,
+    uint64_t max_size_kb,
+    bool require_both) {
+    return crashpad_database_prune_except(
+        database, max_age_days, max_size_kb, require_both, nullptr, 0);
+}
+
+size_t crashpad_database_prune_except(
+    crashpad_database_t database,
+    int max_age_days,
+    uint64_t max_size_kb,
+    bool require_both,
+    const char* const* keep_uuids,
+    size_t keep_count) {
+    std::unique_ptr<PruneCondition> age;
+    if (max_age_days > 0) {
+        age = std::make_unique<AgePruneCondition>(max_age_days);
+    }
+    std::unique_ptr<PruneCondition> size;
+    if (max_size_kb > 0) {
+        size = std::make_unique<DatabaseSizePruneCondition>(static_cast<size_t>(max_size_kb));
+    }
+
+    std::unique_ptr<PruneCondition> condition;
+    if (age && size) {
+        // BinaryPruneCondition takes ownership of both conditions
+        condition = std::make_unique<BinaryPruneCondition>(
+            require_both ? BinaryPruneCondition::AND : BinaryPruneCondition::OR,
+            age.release(),
+            size.release());
+    } else {
+        condition = age ? std::move(age) : std::move(size);
+    }
+    if (!condition) {
+        return 0;
+    }
+
+    std::set<std::string> keep;
+    for (size_t i = 0; i < keep_count; ++i) {
+        UUID uuid;
+        if (ParseUUID(keep_uuids[i], &uuid)) {
+            keep.insert(uuid.ToString());
+        }
+    }
+    KeepReportsPruneCondition keep_condition(condition.get(), std::move(keep));
+    return PruneCrashReportDatabase(static_cast<CrashReportDatabase*>(database), &keep_condition);
+}
+
+#ifdef CRASHPAD_RS_HANDLER_LINKED
+} // extern "C"
+
+namespace {
+
+// Contents of a user stream, produced before the dump is written
+class BufferStreamDataSource final : public MinidumpUserExtensionStreamDataSource {
+public:
+    BufferStreamDataSource(uint32_t stream_type, std::vector<uint8_t> data)
+        : MinidumpUserExtensionStreamDataSource(stream_type), data_(std::move(data)) {}
+
+    size_t StreamDataSize() override { return data_.size(); }
+
+    bool ReadStreamData(Delegate* delegate) override {
+        return delegate->ExtensionStreamDataSourceRead(data_.data(), data_.size());
+    }
+
+private:
+    std::vector<uint8_t> data_;
+};
+
+// A user stream whose contents come from a callback
+class CallbackStreamSource final : public UserStreamDataSource {
+public:
+    CallbackStreamSource(uint32_t stream_type, crashpad_user_stream_fn produce, void* context)
+        : stream_type_(stream_type), produce_(produce), context_(context) {}
+
+    std::unique_ptr<MinidumpUserExtensionStreamDataSource> ProduceStreamData(
+        ProcessSnapshot* process_snapshot) override {
+        std::vector<uint8_t> data;
+        if (!produce_(context_, process_snapshot, &data)) {
+            return nullptr;
+        }
+        return std::make_unique<BufferStreamDataSource>(stream_type_, std::move(data));
+    }
+
+private:
+    uint32_t stream_type_;
+    crashpad_user_stream_fn produce_;
+    void* context_;
+};
+
+std::mutex g_user_streams_mutex;
+UserStreamDataSources g_user_streams;
+
+} // namespace
+
+extern "C" {
+
+int crashpad_handler_main(int argc, char** argv) {
+    // Streams are only added before the handler starts
+    return HandlerMain(argc, argv, &g_user_streams);
+}
+
+void crashpad_handler_add_user_stream(
+    uint32_t stream_type,
+    crashpad_user_stream_fn produce,
+    void* context) {
+
+    std::lock_guard<std::mutex> lock(g_user_streams_mutex);
+    g_user_streams.push_back(
+        std::make_unique<CallbackStreamSource>(stream_type, produce, context));
+}
+
+void crashpad_stream_buffer_append(
+    crashpad_stream_buffer_t buffer,
+    const void* data,
+    size_t size) {
+
+    auto* bytes = static_cast<std::vector<uint8_t>*>(buffer);
+    const auto* begin = static_cast<const uint8_t*>(data);
+    bytes->insert(bytes->end(), begin, begin + size);
+}
+
+uint64_t crashpad_process_snapshot_pid(crashpad_process_snapshot_t process) {
+    return static_cast<const ProcessSnapshot*>(process)->ProcessID();
+}
+
+bool crashpad_process_snapshot_read_memory(
+    crashpad_process_snapshot_t process,
+    uint64_t address,
+    size_t size,
+    void* buffer) {
+
+    const ProcessMemory* memory = static_cast<const ProcessSnapshot*>(process)->Memory();
+    return memory && memory->Read(address, size, buffer);
+}
+
+bool crashpad_process_snapshot_annotation(
+    crashpad_process_snapshot_t process,
+    const char* key,
+    char* buffer,
+    size_t buffer_len,
+    size_t* value_len) {
+
+    const auto* snapshot = static_cast<const ProcessSnapshot*>(process);
+    const std::string* value = nullptr;
+    const auto& process_annotations = snapshot->AnnotationsSimpleMap();
+    auto it = process_annotations.find(key);
+    if (it != process_annotations.end()) {
+        value = &it->second;
+    }
+    std::map<std::string, std::string> module_annotations;
+    for (const ModuleSnapshot* module : snapshot->Modules()) {
+        if (value) {
+            break;
+        }
+        module_annotations = module->AnnotationsSimpleMap();
+        it = module_annotations.find(key);
+        if (it != module_annotations.end()) {
+            value = &it->second;
+        }
+    }
+    if (!value) {
+        return false;
+    }
+
+    *value_len = value->size();
+    if (buffer && value->size() < buffer_len) {
+        memcpy(buffer, value->data(), value->size());
+        buffer[value->size()] = '\0';
+    }
+    return true;
+}
+#endif
+
 } // extern "C"
\ No newline at end of file
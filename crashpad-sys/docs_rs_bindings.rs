@@ -0,0 +1,307 @@
+// Bindings for docs.rs builds, which can't build Crashpad.
+//
+// Declarations of every platform's functions in wrapper.h, with the types
+// bindgen gives them (`wchar_t` as on Windows), so the documentation of
+// crashpad-rs-sys and crashpad-rs builds on any host. Keep it in sync with
+// wrapper.h; CI checks it with `DOCS_RS=1 cargo check`.
+
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+
+pub const CRASHPAD_SIMPLE_DICTIONARY_ENTRIES: u32 = 64;
+pub const CRASHPAD_SIMPLE_DICTIONARY_KEY_SIZE: u32 = 256;
+pub const CRASHPAD_SIMPLE_DICTIONARY_VALUE_SIZE: u32 = 256;
+pub const CRASHPAD_ANNOTATION_NAME_MAX_LENGTH: u32 = 256;
+pub const CRASHPAD_ANNOTATION_VALUE_MAX_SIZE: u32 = 20480;
+pub const CRASHPAD_EXTRA_MEMORY_RANGES: u32 = 64;
+pub const CRASHPAD_START_ERROR_NONE: u32 = 0;
+pub const CRASHPAD_START_ERROR_HANDLER_NOT_FOUND: u32 = 1;
+pub const CRASHPAD_START_ERROR_SETUP_FAILED: u32 = 2;
+pub const CRASHPAD_START_ERROR_SPAWN_FAILED: u32 = 3;
+pub const CRASHPAD_HANDLER_STATUS_UNKNOWN: u32 = 0;
+pub const CRASHPAD_HANDLER_STATUS_RUNNING: u32 = 1;
+pub const CRASHPAD_HANDLER_STATUS_EXITED: u32 = 2;
+pub const CRASHPAD_EXCEPTION_FILTER_UNHANDLED: u32 = 0;
+pub const CRASHPAD_EXCEPTION_FILTER_VECTORED_FIRST: u32 = 1;
+pub const CRASHPAD_EXCEPTION_FILTER_VECTORED_LAST: u32 = 2;
+pub const CRASHPAD_UPLOAD_WHEN_APP_IS_ACTIVE: u32 = 1;
+pub const CRASHPAD_UPLOAD_IMMEDIATELY: u32 = 2;
+pub const CRASHPAD_DATABASE_NO_ERROR: u32 = 0;
+pub const CRASHPAD_DATABASE_REPORT_NOT_FOUND: u32 = 1;
+pub const CRASHPAD_DATABASE_FILE_SYSTEM_ERROR: u32 = 2;
+pub const CRASHPAD_DATABASE_DATABASE_ERROR: u32 = 3;
+pub const CRASHPAD_DATABASE_BUSY_ERROR: u32 = 4;
+pub const CRASHPAD_DATABASE_CANNOT_REQUEST_UPLOAD: u32 = 5;
+pub type crashpad_client_t = *mut c_void;
+pub type crashpad_annotation_t = *mut c_void;
+pub type crashpad_database_t = *mut c_void;
+pub type crashpad_report_visitor_t = ::std::option::Option<
+    unsafe extern "C" fn(context: *mut c_void, report: *const crashpad_report_t),
+>;
+pub type crashpad_process_snapshot_t = *const c_void;
+pub type crashpad_stream_buffer_t = *mut c_void;
+pub type crashpad_user_stream_fn = ::std::option::Option<
+    unsafe extern "C" fn(
+        context: *mut c_void,
+        process: crashpad_process_snapshot_t,
+        buffer: crashpad_stream_buffer_t,
+    ) -> bool,
+>;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct crashpad_report {
+    pub uuid: *const c_char,
+    pub file_path: *const c_char,
+    pub id: *const c_char,
+    pub creation_time: i64,
+    pub uploaded: bool,
+    pub last_upload_attempt_time: i64,
+    pub upload_attempts: c_int,
+    pub upload_explicitly_requested: bool,
+    pub total_size: u64,
+}
+pub type crashpad_report_t = crashpad_report;
+extern "C" {
+    pub fn crashpad_client_new() -> crashpad_client_t;
+    pub fn crashpad_client_delete(client: crashpad_client_t);
+    pub fn crashpad_client_start_handler(
+        client: crashpad_client_t,
+        handler_path: *const c_char,
+        database_path: *const c_char,
+        metrics_path: *const c_char,
+        url: *const c_char,
+        annotations_keys: *mut *const c_char,
+        annotations_values: *mut *const c_char,
+        annotations_count: usize,
+        extra_arguments: *mut *const c_char,
+        extra_arguments_count: usize,
+    ) -> bool;
+    pub fn crashpad_set_module_annotation(key: *const c_char, value: *const c_char);
+    pub fn crashpad_annotation_register(
+        name: *const c_char,
+        user_type: u16,
+        value: *mut c_void,
+        size: u32,
+    ) -> bool;
+    pub fn crashpad_string_annotation_register(
+        name: *const c_char,
+        buffer: *mut c_void,
+        capacity: u32,
+    ) -> crashpad_annotation_t;
+    pub fn crashpad_string_annotation_set_size(annotation: crashpad_annotation_t, size: u32);
+    pub fn crashpad_add_extra_memory_range(base: *const c_void, size: usize) -> bool;
+    pub fn crashpad_remove_extra_memory_range(base: *const c_void, size: usize) -> bool;
+    pub fn crashpad_client_last_start_error(os_error: *mut c_int) -> c_int;
+    pub fn crashpad_client_set_handler_log(path: *const c_char);
+    pub fn crashpad_client_set_handler_working_directory(path: *const c_char);
+    pub fn crashpad_client_set_handler_umask(mask: c_int);
+    pub fn crashpad_client_stop_handler(client: crashpad_client_t) -> c_int;
+    pub fn crashpad_client_handler_status(client: crashpad_client_t) -> c_int;
+    pub fn crashpad_client_handler_pid(client: crashpad_client_t) -> c_int;
+    pub fn crashpad_client_set_handler_ipc_pipe(
+        client: crashpad_client_t,
+        ipc_pipe: *const u16,
+    ) -> bool;
+    pub fn crashpad_client_get_handler_ipc_pipe(
+        client: crashpad_client_t,
+        buffer: *mut u16,
+        buffer_len: usize,
+    ) -> usize;
+    pub fn crashpad_client_set_exception_filter(
+        mode: c_int,
+        excluded_codes: *const c_uint,
+        excluded_codes_count: usize,
+    ) -> bool;
+    pub fn crashpad_client_set_handler_kill_on_exit(
+        client: crashpad_client_t,
+        database_path: *const c_char,
+        grace_ms: c_uint,
+    ) -> c_int;
+    pub fn crashpad_client_set_unhandled_signals(signals: *const c_int, signals_count: usize);
+    pub fn crashpad_client_set_chain_before_dump(enabled: bool);
+    pub fn crashpad_client_set_crash_reserve(memory_bytes: usize, file_descriptors: usize)
+        -> c_int;
+    pub fn crashpad_create_handler_socketpair(
+        server_socket: *mut c_int,
+        client_socket: *mut c_int,
+    ) -> c_int;
+    pub fn crashpad_client_get_handler_socket(socket: *mut c_int, handler_pid: *mut c_int) -> bool;
+    pub fn crashpad_client_set_handler_socket(
+        client: crashpad_client_t,
+        socket: c_int,
+        handler_pid: c_int,
+    ) -> bool;
+    pub fn crashpad_client_start_handler_for_client(
+        handler_path: *const c_char,
+        database_path: *const c_char,
+        metrics_path: *const c_char,
+        url: *const c_char,
+        annotations_keys: *mut *const c_char,
+        annotations_values: *mut *const c_char,
+        annotations_count: usize,
+        extra_arguments: *mut *const c_char,
+        extra_arguments_count: usize,
+        socket: c_int,
+    ) -> bool;
+    pub fn crashpad_client_start_java_handler(
+        class_name: *const c_char,
+        env: *mut *const c_char,
+        env_count: usize,
+        database_path: *const c_char,
+        metrics_path: *const c_char,
+        url: *const c_char,
+        annotations_keys: *mut *const c_char,
+        annotations_values: *mut *const c_char,
+        annotations_count: usize,
+        extra_arguments: *mut *const c_char,
+        extra_arguments_count: usize,
+        socket: c_int,
+    ) -> bool;
+    pub fn crashpad_client_start_handler_with_linker(
+        handler_trampoline: *const c_char,
+        handler_library: *const c_char,
+        is_64_bit: bool,
+        env: *mut *const c_char,
+        env_count: usize,
+        database_path: *const c_char,
+        metrics_path: *const c_char,
+        url: *const c_char,
+        annotations_keys: *mut *const c_char,
+        annotations_values: *mut *const c_char,
+        annotations_count: usize,
+        extra_arguments: *mut *const c_char,
+        extra_arguments_count: usize,
+        socket: c_int,
+    ) -> bool;
+    pub fn crashpad_client_set_handler_mach_service(
+        client: crashpad_client_t,
+        service_name: *const c_char,
+    ) -> bool;
+    pub fn crashpad_client_use_system_default_handler(client: crashpad_client_t) -> bool;
+    pub fn crashpad_client_set_mach_exception_mask(mask: c_uint) -> bool;
+    pub fn crashpad_client_start_in_process_handler(
+        client: crashpad_client_t,
+        database_path: *const c_char,
+        url: *const c_char,
+        annotations_keys: *mut *const c_char,
+        annotations_values: *mut *const c_char,
+        annotations_count: usize,
+    ) -> bool;
+    pub fn crashpad_client_process_intermediate_dumps();
+    pub fn crashpad_client_start_processing_pending_reports();
+    pub fn crashpad_client_process_intermediate_dumps_with_annotations(
+        annotations_keys: *mut *const c_char,
+        annotations_values: *mut *const c_char,
+        annotations_count: usize,
+    );
+    pub fn crashpad_client_process_intermediate_dump(
+        file_path: *const c_char,
+        annotations_keys: *mut *const c_char,
+        annotations_values: *mut *const c_char,
+        annotations_count: usize,
+    );
+    pub fn crashpad_client_start_processing_pending_reports_with_behavior(upload_behavior: c_int);
+    pub fn crashpad_dump_without_crash();
+    pub fn crashpad_dump_without_crash_with_context(context: *mut c_void);
+    pub fn crashpad_simulate_pure_virtual_call();
+    pub fn crashpad_dump_without_crash_of_thread(thread_id: u64) -> c_int;
+    pub fn crashpad_current_thread_id() -> u64;
+    pub fn crashpad_utc_offset(unix_time: i64) -> c_int;
+    pub fn crashpad_current_thread_stack(
+        low: *mut u64,
+        high: *mut u64,
+        guard_size: *mut u64,
+    ) -> bool;
+    pub fn crashpad_database_open(path: *const c_char, create: bool) -> crashpad_database_t;
+    pub fn crashpad_database_close(database: crashpad_database_t);
+    pub fn crashpad_database_get_reports(
+        database: crashpad_database_t,
+        completed: bool,
+        visitor: crashpad_report_visitor_t,
+        context: *mut c_void,
+    ) -> c_int;
+    pub fn crashpad_database_look_up_report(
+        database: crashpad_database_t,
+        uuid: *const c_char,
+        visitor: crashpad_report_visitor_t,
+        context: *mut c_void,
+    ) -> c_int;
+    pub fn crashpad_database_delete_report(
+        database: crashpad_database_t,
+        uuid: *const c_char,
+    ) -> c_int;
+    pub fn crashpad_database_request_upload(
+        database: crashpad_database_t,
+        uuid: *const c_char,
+    ) -> c_int;
+    pub fn crashpad_database_get_uploads_enabled(
+        database: crashpad_database_t,
+        enabled: *mut bool,
+    ) -> bool;
+    pub fn crashpad_database_set_uploads_enabled(
+        database: crashpad_database_t,
+        enabled: bool,
+    ) -> bool;
+    pub fn crashpad_database_get_client_id(
+        database: crashpad_database_t,
+        client_id: *mut c_char,
+    ) -> bool;
+    pub fn crashpad_database_get_last_upload_attempt_time(
+        database: crashpad_database_t,
+        time: *mut i64,
+    ) -> bool;
+    pub fn crashpad_database_skip_upload(
+        database: crashpad_database_t,
+        uuid: *const c_char,
+    ) -> c_int;
+    pub fn crashpad_database_record_upload(
+        database: crashpad_database_t,
+        uuid: *const c_char,
+        uploaded: bool,
+        id: *const c_char,
+    ) -> c_int;
+    pub fn crashpad_database_add_report(
+        database: crashpad_database_t,
+        data: *const u8,
+        size: usize,
+        uuid: *mut c_char,
+    ) -> c_int;
+    pub fn crashpad_database_prune(
+        database: crashpad_database_t,
+        max_age_days: c_int,
+        max_size_kb: u64,
+        require_both: bool,
+    ) -> usize;
+    pub fn crashpad_database_prune_except(
+        database: crashpad_database_t,
+        max_age_days: c_int,
+        max_size_kb: u64,
+        require_both: bool,
+        keep_uuids: *const *const c_char,
+        keep_count: usize,
+    ) -> usize;
+    pub fn crashpad_handler_main(argc: c_int, argv: *mut *mut c_char) -> c_int;
+    pub fn crashpad_handler_add_user_stream(
+        stream_type: u32,
+        produce: crashpad_user_stream_fn,
+        context: *mut c_void,
+    );
+    pub fn crashpad_stream_buffer_append(
+        buffer: crashpad_stream_buffer_t,
+        data: *const c_void,
+        size: usize,
+    );
+    pub fn crashpad_process_snapshot_pid(process: crashpad_process_snapshot_t) -> u64;
+    pub fn crashpad_process_snapshot_read_memory(
+        process: crashpad_process_snapshot_t,
+        address: u64,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> bool;
+    pub fn crashpad_process_snapshot_annotation(
+        process: crashpad_process_snapshot_t,
+        key: *const c_char,
+        buffer: *mut c_char,
+        buffer_len: usize,
+        value_len: *mut usize,
+    ) -> bool;
+}
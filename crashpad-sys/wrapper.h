@@ -1,10 +1,14 @@
 // Wrapper header for bindgen to generate Rust FFI bindings
+//
+// docs.rs builds use docs_rs_bindings.rs instead; add new declarations there
+// too (checked in CI with `DOCS_RS=1 cargo check`).
 
 #ifndef CRASHPAD_WRAPPER_H
 #define CRASHPAD_WRAPPER_H
 
 #include <stdbool.h>
 #include <stddef.h>
+#include <stdint.h>
 
 #ifdef __cplusplus
 extern "C" {
@@ -32,11 +36,265 @@ bool crashpad_client_start_handler(
     const char** extra_arguments,
     size_t extra_arguments_count);
 
+// Capacity of Crashpad's SimpleStringDictionary, which holds the simple
+// annotations of a module: number of entries, and key and value sizes
+// including the NUL terminator. Checked against the vendored Crashpad
+// headers when the wrapper is compiled.
+#define CRASHPAD_SIMPLE_DICTIONARY_ENTRIES 64
+#define CRASHPAD_SIMPLE_DICTIONARY_KEY_SIZE 256
+#define CRASHPAD_SIMPLE_DICTIONARY_VALUE_SIZE 256
+
+// Set the simple annotation key of this module to value, or remove it if
+// value is NULL. Read when a dump is written, so annotations can change
+// after the handler started; process annotations given at start win over
+// module annotations with the same key. Thread-safe.
+void crashpad_set_module_annotation(const char* key, const char* value);
+
+// Limits of annotations registered with crashpad_annotation_register: name
+// length including the NUL terminator, and value size (exclusive). Checked
+// against the vendored Crashpad headers when the wrapper is compiled.
+#define CRASHPAD_ANNOTATION_NAME_MAX_LENGTH 256
+#define CRASHPAD_ANNOTATION_VALUE_MAX_SIZE 20480
+
+// Register an annotation of the user-defined type user_type (below 0x8000)
+// whose value is the size bytes at value. The memory is read whenever a dump
+// is written, so it has to stay valid for the life of the process; there is
+// no way to unregister. Returns false if the name or size is too long.
+bool crashpad_annotation_register(
+    const char* name,
+    uint16_t user_type,
+    void* value,
+    uint32_t size);
+
+// Opaque handle of a string annotation
+typedef void* crashpad_annotation_t;
+
+// Register a string annotation named name whose value is kept in the
+// capacity bytes at buffer. Like crashpad_annotation_register, the name and
+// buffer have to stay valid for the life of the process. The annotation is
+// left out of dumps until crashpad_string_annotation_set_size gives it a
+// size. Returns NULL if the name or capacity is too long.
+crashpad_annotation_t crashpad_string_annotation_register(
+    const char* name,
+    void* buffer,
+    uint32_t capacity);
+
+// Set the length of the value in the annotation's buffer, at most its
+// capacity; 0 leaves the annotation out of dumps.
+void crashpad_string_annotation_set_size(crashpad_annotation_t annotation, uint32_t size);
+
+// Capacity of Crashpad's SimpleAddressRangeBag, which holds the extra
+// memory ranges of a module. Checked against the vendored Crashpad headers
+// when the wrapper is compiled.
+#define CRASHPAD_EXTRA_MEMORY_RANGES 64
+
+// Include the size bytes at base in every dump written after this call, in
+// addition to the memory Crashpad captures anyway. The handler reads the
+// range out of process, so it doesn't have to stay valid, but dumps contain
+// whatever is mapped there. Returns false if size is 0 or all
+// CRASHPAD_EXTRA_MEMORY_RANGES slots are taken. Thread-safe.
+bool crashpad_add_extra_memory_range(const void* base, size_t size);
+
+// Stop including a range added with crashpad_add_extra_memory_range with the
+// same base and size. Returns false if there is no such range. Thread-safe.
+bool crashpad_remove_extra_memory_range(const void* base, size_t size);
+
+// Reasons crashpad_client_start_handler can fail
+#define CRASHPAD_START_ERROR_NONE 0
+// The handler executable doesn't exist
+#define CRASHPAD_START_ERROR_HANDLER_NOT_FOUND 1
+// The handler log or working directory could not be set up
+#define CRASHPAD_START_ERROR_SETUP_FAILED 2
+// Crashpad could not start the handler or connect to it
+#define CRASHPAD_START_ERROR_SPAWN_FAILED 3
+
+// Reason the last crashpad_client_start_handler call on this thread failed,
+// a CRASHPAD_START_ERROR_* value. The OS error code (errno, or GetLastError()
+// on Windows) is stored in *os_error if not NULL, 0 if unknown.
+int crashpad_client_last_start_error(int* os_error);
+
+// Send the stderr of handlers started after this call to the file at path
+// (appended to), or inherit this process's stderr again if path is NULL.
+// Not available on iOS, which has no handler process.
+void crashpad_client_set_handler_log(const char* path);
+
+// Start handlers started after this call in the directory at path, or in
+// this process's working directory again if path is NULL.
+// Not available on iOS, which has no handler process.
+void crashpad_client_set_handler_working_directory(const char* path);
+
+// Give handlers started after this call the file mode creation mask mask,
+// or this process's umask again if mask is negative. Ignored on Windows.
+// Not available on iOS, which has no handler process.
+void crashpad_client_set_handler_umask(int mask);
+
+// Stop the handler started by client. On macOS, crashes go to the system
+// crash reporter again and the handler exits once it has no clients left.
+// On Linux, Android and Windows, Crashpad's crash handlers can't be removed:
+// the handler process is terminated, and crashes until the next start are
+// not reported. Returns 0 on success or if no handler is running, otherwise
+// an errno value (GetLastError() on Windows).
+// Not available on iOS, which has no handler process.
+int crashpad_client_stop_handler(crashpad_client_t client);
+
+// States of the handler started by a client
+#define CRASHPAD_HANDLER_STATUS_UNKNOWN 0
+#define CRASHPAD_HANDLER_STATUS_RUNNING 1
+#define CRASHPAD_HANDLER_STATUS_EXITED 2
+
+// Whether the handler started by client still runs, a
+// CRASHPAD_HANDLER_STATUS_* value. Checks the handler's process ID on Linux
+// and Android, whether its pipe still exists on Windows, and whether the
+// EXC_CRASH exception port is dead on macOS. Unknown if no handler was
+// started, or for a handler Android starts only at crash.
+// Not available on iOS, which has no handler process.
+int crashpad_client_handler_status(crashpad_client_t client);
+
+// Process ID of the handler started by client, 0 if unknown: on macOS,
+// which doesn't expose it, before an asynchronous start on Windows has
+// completed, and for a handler Android starts only at crash.
+// Not available on iOS, which has no handler process.
+int crashpad_client_handler_pid(crashpad_client_t client);
+
 // Set handler IPC pipe (for Windows)
 #ifdef _WIN32
 bool crashpad_client_set_handler_ipc_pipe(
     crashpad_client_t client,
     const wchar_t* ipc_pipe);
+
+// Copy the name of the pipe clients use to reach the handler started by, or
+// registered with, client to buffer (NUL-terminated) if it has room for
+// buffer_len wide characters. Returns the length of the name without the
+// terminator, 0 if there is no handler yet.
+size_t crashpad_client_get_handler_ipc_pipe(
+    crashpad_client_t client,
+    wchar_t* buffer,
+    size_t buffer_len);
+
+// How Crashpad's exception filter is registered (Windows)
+#define CRASHPAD_EXCEPTION_FILTER_UNHANDLED 0
+#define CRASHPAD_EXCEPTION_FILTER_VECTORED_FIRST 1
+#define CRASHPAD_EXCEPTION_FILTER_VECTORED_LAST 2
+
+// Wrap the unhandled exception filter installed by StartHandler so that
+// excluded exception codes (e.g. CLR managed exceptions) are passed on, and
+// optionally also register a vectored handler for fatal exceptions.
+// Must be called after crashpad_client_start_handler.
+bool crashpad_client_set_exception_filter(
+    int mode,
+    const unsigned int* excluded_codes,
+    size_t excluded_codes_count);
+
+// Assign the handler started by crashpad_client_start_handler to a Job
+// Object that terminates it when this process exits. On a normal exit
+// (exit() or returning from main), the exit waits up to grace_ms for the
+// database at database_path to have no pending reports first; 0 doesn't
+// wait. Returns 0 on success, otherwise GetLastError().
+// Must be called after crashpad_client_start_handler.
+int crashpad_client_set_handler_kill_on_exit(
+    crashpad_client_t client,
+    const char* database_path,
+    unsigned int grace_ms);
+#endif
+
+// Signals Crashpad should not install handlers for (Linux/Android).
+// Must be called before crashpad_client_start_handler.
+#if defined(__linux__) || defined(__ANDROID__)
+void crashpad_client_set_unhandled_signals(
+    const int* signals,
+    size_t signals_count);
+
+// Call the signal handlers installed before Crashpad's first for fault
+// signals, and only write a dump if they return without handling the fault
+// by resuming. Must be called before crashpad_client_start_handler.
+void crashpad_client_set_chain_before_dump(bool enabled);
+
+// Reserve memory and file descriptors that are released when a crash signal
+// arrives, before the dump is requested, so it can succeed when the process
+// ran out of them. Replaces an earlier reservation; zero for both releases
+// it. Returns 0 on success, otherwise the errno of the failed allocation.
+int crashpad_client_set_crash_reserve(size_t memory_bytes, size_t file_descriptors);
+
+// Create a connected socket pair for a handler and its clients, with
+// credential passing enabled. The server end goes to
+// crashpad_client_start_handler_for_client, the client end (or duplicates of
+// it) to crashpad_client_set_handler_socket. Returns 0 on success, otherwise
+// an errno value.
+int crashpad_create_handler_socketpair(int* server_socket, int* client_socket);
+
+// The socket clients use to reach the handler started by
+// crashpad_client_start_handler, and the handler's process ID. The socket
+// stays owned by Crashpad. Returns false if no handler was started.
+bool crashpad_client_get_handler_socket(int* socket, int* handler_pid);
+
+// Request dumps from an already running handler over socket instead of
+// starting one, taking ownership of socket. handler_pid is the handler's
+// process ID, allowed to ptrace this process; 0 if that isn't necessary,
+// -1 to ask the handler over the socket.
+bool crashpad_client_set_handler_socket(
+    crashpad_client_t client,
+    int socket,
+    int handler_pid);
+
+// Start a handler for another process, serving clients of socket (the
+// server end of crashpad_create_handler_socketpair). This process doesn't
+// become a client. socket isn't closed.
+// Failures are reported like crashpad_client_start_handler.
+bool crashpad_client_start_handler_for_client(
+    const char* handler_path,
+    const char* database_path,
+    const char* metrics_path,
+    const char* url,
+    const char** annotations_keys,
+    const char** annotations_values,
+    size_t annotations_count,
+    const char** extra_arguments,
+    size_t extra_arguments_count,
+    int socket);
+#endif
+
+#if defined(__ANDROID__)
+// Start the handler with app_process as the Java class class_name, whose
+// main calls CrashpadHandlerMain. For apps that can't execute files from
+// their data directory. env holds var=value entries for app_process, or is
+// NULL to use this process's environment.
+//
+// If socket is -1, this process becomes a client and the handler is only
+// started when it crashes (StartJavaHandlerAtCrash). Otherwise the handler
+// is started now, serving clients of socket (StartJavaHandlerForClient).
+bool crashpad_client_start_java_handler(
+    const char* class_name,
+    const char** env,
+    size_t env_count,
+    const char* database_path,
+    const char* metrics_path,
+    const char* url,
+    const char** annotations_keys,
+    const char** annotations_values,
+    size_t annotations_count,
+    const char** extra_arguments,
+    size_t extra_arguments_count,
+    int socket);
+
+// Like crashpad_client_start_java_handler, but starts the handler by running
+// the trampoline library handler_trampoline with the system linker, which
+// loads handler_library and calls its CrashpadHandlerMain
+// (StartHandlerWithLinkerAtCrash/StartHandlerWithLinkerForClient).
+bool crashpad_client_start_handler_with_linker(
+    const char* handler_trampoline,
+    const char* handler_library,
+    bool is_64_bit,
+    const char** env,
+    size_t env_count,
+    const char* database_path,
+    const char* metrics_path,
+    const char* url,
+    const char** annotations_keys,
+    const char** annotations_values,
+    size_t annotations_count,
+    const char** extra_arguments,
+    size_t extra_arguments_count,
+    int socket);
 #endif
 
 // Platform-specific functions for macOS/iOS
@@ -50,6 +308,13 @@ bool crashpad_client_set_handler_mach_service(
 bool crashpad_client_use_system_default_handler(
     crashpad_client_t client);
 
+#if !(defined(TARGET_OS_IOS) && TARGET_OS_IOS)
+// Change which Mach exception types (EXC_MASK_* bits) are sent to the handler.
+// Types Crashpad claimed by default that are not in the mask are released.
+// Must be called after crashpad_client_start_handler.
+bool crashpad_client_set_mach_exception_mask(unsigned int mask);
+#endif
+
 // iOS-specific in-process handler functions
 #if defined(TARGET_OS_IOS) && TARGET_OS_IOS
 bool crashpad_client_start_in_process_handler(
@@ -63,6 +328,27 @@ bool crashpad_client_start_in_process_handler(
 void crashpad_client_process_intermediate_dumps();
 
 void crashpad_client_start_processing_pending_reports();
+
+// Process the intermediate dumps of previous sessions, adding the given
+// annotations to the resulting minidumps
+void crashpad_client_process_intermediate_dumps_with_annotations(
+    const char** annotations_keys,
+    const char** annotations_values,
+    size_t annotations_count);
+
+// Process a single intermediate dump file
+void crashpad_client_process_intermediate_dump(
+    const char* file_path,
+    const char** annotations_keys,
+    const char** annotations_values,
+    size_t annotations_count);
+
+// CrashpadClient::UploadBehavior
+#define CRASHPAD_UPLOAD_WHEN_APP_IS_ACTIVE 1
+#define CRASHPAD_UPLOAD_IMMEDIATELY 2
+
+void crashpad_client_start_processing_pending_reports_with_behavior(
+    int upload_behavior);
 #endif
 #endif
 
@@ -76,6 +362,215 @@ void crashpad_dump_without_crash();
 // On other platforms: context should be a pointer to NativeCPUContext
 void crashpad_dump_without_crash_with_context(void* context);
 
+// Call a pure virtual function from a constructor, which terminates the
+// process through the C++ runtime's pure virtual call handler. Used by the
+// QA crash simulation; only returns if the runtime's handler does.
+void crashpad_simulate_pure_virtual_call();
+
+#if defined(_WIN32) || \
+    (defined(__APPLE__) && !(defined(TARGET_OS_IOS) && TARGET_OS_IOS))
+// Capture a dump whose exception context is the register state of another
+// thread of this process. The thread is suspended while the dump is written.
+// thread_id is a Win32 thread ID on Windows and a pthread_threadid_np() ID on
+// macOS. Returns 0 on success, otherwise GetLastError() on Windows or an errno
+// value on macOS (ESRCH if there is no such thread).
+int crashpad_dump_without_crash_of_thread(uint64_t thread_id);
+
+// ID of the calling thread in the form taken by
+// crashpad_dump_without_crash_of_thread
+uint64_t crashpad_current_thread_id();
+#endif
+
+// Offset of local time from UTC in seconds at the given Unix time, with the
+// process's timezone settings (TZ, the system timezone). East of UTC is
+// positive.
+int crashpad_utc_offset(int64_t unix_time);
+
+// Stack of the calling thread: the lowest and one past the highest address,
+// and the size of the guard region below it (0 if there is none). Returns
+// false if the bounds can't be determined.
+bool crashpad_current_thread_stack(uint64_t* low, uint64_t* high, uint64_t* guard_size);
+
+// Crash report database
+
+// Opaque handle for CrashReportDatabase
+typedef void* crashpad_database_t;
+
+// Status codes of database operations (CrashReportDatabase::OperationStatus)
+#define CRASHPAD_DATABASE_NO_ERROR 0
+#define CRASHPAD_DATABASE_REPORT_NOT_FOUND 1
+#define CRASHPAD_DATABASE_FILE_SYSTEM_ERROR 2
+#define CRASHPAD_DATABASE_DATABASE_ERROR 3
+#define CRASHPAD_DATABASE_BUSY_ERROR 4
+#define CRASHPAD_DATABASE_CANNOT_REQUEST_UPLOAD 5
+
+// A report in the database. Strings are UTF-8 and only valid for the
+// duration of the visitor call they are passed to.
+typedef struct crashpad_report {
+    const char* uuid;
+    const char* file_path;
+    // ID assigned by the server, empty if not uploaded
+    const char* id;
+    // Seconds since the Unix epoch, 0 if never
+    int64_t creation_time;
+    bool uploaded;
+    int64_t last_upload_attempt_time;
+    int upload_attempts;
+    bool upload_explicitly_requested;
+    uint64_t total_size;
+} crashpad_report_t;
+
+typedef void (*crashpad_report_visitor_t)(void* context, const crashpad_report_t* report);
+
+// Open the database at path, creating it if create is true.
+// Returns NULL on failure.
+crashpad_database_t crashpad_database_open(const char* path, bool create);
+
+void crashpad_database_close(crashpad_database_t database);
+
+// Call visitor for each pending (or completed) report.
+// Returns a CRASHPAD_DATABASE_* status.
+int crashpad_database_get_reports(
+    crashpad_database_t database,
+    bool completed,
+    crashpad_report_visitor_t visitor,
+    void* context);
+
+// Call visitor with the report with the given UUID.
+// Returns a CRASHPAD_DATABASE_* status.
+int crashpad_database_look_up_report(
+    crashpad_database_t database,
+    const char* uuid,
+    crashpad_report_visitor_t visitor,
+    void* context);
+
+// Returns a CRASHPAD_DATABASE_* status.
+int crashpad_database_delete_report(crashpad_database_t database, const char* uuid);
+
+// Mark a report for upload, even if it was already completed.
+// Returns a CRASHPAD_DATABASE_* status.
+int crashpad_database_request_upload(crashpad_database_t database, const char* uuid);
+
+// Read the database's upload consent setting into *enabled.
+// Returns false if the settings can't be read.
+bool crashpad_database_get_uploads_enabled(crashpad_database_t database, bool* enabled);
+
+// Change the database's upload consent setting. The handler reads it before
+// every upload. Returns false if the settings can't be written.
+bool crashpad_database_set_uploads_enabled(crashpad_database_t database, bool enabled);
+
+// Write the database's client ID, the UUID the handler sends with every
+// report, to client_id (37 bytes, NUL-terminated). Returns false if the
+// settings can't be read.
+bool crashpad_database_get_client_id(crashpad_database_t database, char* client_id);
+
+// Read the time of the handler's last upload attempt, in seconds since the
+// Unix epoch or 0 for never, into *time. Returns false if the settings can't
+// be read.
+bool crashpad_database_get_last_upload_attempt_time(crashpad_database_t database, int64_t* time);
+
+// Complete a pending report without uploading it, recorded as throttled.
+// It can still be uploaded with crashpad_database_request_upload.
+// Returns a CRASHPAD_DATABASE_* status.
+int crashpad_database_skip_upload(crashpad_database_t database, const char* uuid);
+
+// Record an upload of a pending report made outside the handler. If
+// uploaded, the report is completed with the server's report ID id;
+// otherwise a failed attempt is recorded and the report stays pending.
+// Returns a CRASHPAD_DATABASE_* status.
+int crashpad_database_record_upload(
+    crashpad_database_t database,
+    const char* uuid,
+    bool uploaded,
+    const char* id);
+
+// Add a pending report with the given minidump contents. On success, the
+// new report's UUID is written to uuid (37 bytes, NUL-terminated).
+// Returns a CRASHPAD_DATABASE_* status.
+int crashpad_database_add_report(
+    crashpad_database_t database,
+    const uint8_t* data,
+    size_t size,
+    char* uuid);
+
+// Delete reports (pending and completed) that match a prune condition, with Crashpad's
+// PruneCrashReportDatabase. A report matches if it is older than
+// max_age_days, or if it falls beyond max_size_kb of reports kept newest
+// first; 0 disables a limit. With require_both, a report has to match both
+// limits. Returns the number of reports deleted.
+size_t crashpad_database_prune(
+    crashpad_database_t database,
+    int max_age_days,
+    uint64_t max_size_kb,
+    bool require_both);
+
+// crashpad_database_prune, keeping the keep_count reports whose UUIDs are in
+// keep_uuids whatever the condition. Invalid UUIDs are ignored.
+size_t crashpad_database_prune_except(
+    crashpad_database_t database,
+    int max_age_days,
+    uint64_t max_size_kb,
+    bool require_both,
+    const char* const* keep_uuids,
+    size_t keep_count);
+
+// Run crashpad_handler's main function in this process with the given
+// command line (argv[0] is the program name) and return its exit code once
+// the handler shuts down. Only defined when the library is built with the
+// handler-linked feature.
+int crashpad_handler_main(int argc, char** argv);
+
+// Process being dumped, passed to user stream callbacks
+typedef const void* crashpad_process_snapshot_t;
+
+// Contents of a user stream, filled by a user stream callback
+typedef void* crashpad_stream_buffer_t;
+
+// Produce the contents of a user stream for a dump of process by appending
+// them to buffer. Return false to leave the stream out of this dump. Runs in
+// the handler process, on the thread writing the dump.
+typedef bool (*crashpad_user_stream_fn)(
+    void* context,
+    crashpad_process_snapshot_t process,
+    crashpad_stream_buffer_t buffer);
+
+// Add a stream of type stream_type produced by produce to every dump written
+// by the handler, before calling crashpad_handler_main. context is passed to
+// produce and never freed. This and the functions below are only defined
+// when the library is built with the handler-linked feature.
+void crashpad_handler_add_user_stream(
+    uint32_t stream_type,
+    crashpad_user_stream_fn produce,
+    void* context);
+
+// Append the size bytes at data to a user stream
+void crashpad_stream_buffer_append(
+    crashpad_stream_buffer_t buffer,
+    const void* data,
+    size_t size);
+
+// Process ID of the process being dumped
+uint64_t crashpad_process_snapshot_pid(crashpad_process_snapshot_t process);
+
+// Copy the size bytes at address in the process being dumped to buffer.
+// Returns false if any of them can't be read.
+bool crashpad_process_snapshot_read_memory(
+    crashpad_process_snapshot_t process,
+    uint64_t address,
+    size_t size,
+    void* buffer);
+
+// Look up the annotation key of the process being dumped: its process
+// annotations, then the simple annotations of its modules. Returns false if
+// there is none. Otherwise stores the length of the value in *value_len and
+// copies it to buffer (NUL-terminated) if it has room for buffer_len bytes.
+bool crashpad_process_snapshot_annotation(
+    crashpad_process_snapshot_t process,
+    const char* key,
+    char* buffer,
+    size_t buffer_len,
+    size_t* value_len);
+
 #ifdef __cplusplus
 }
 #endif
@@ -0,0 +1,337 @@
+//! Test utilities for verifying minidumps written by crashpad-rs
+//!
+//! [`expect_dump`] waits for a minidump to appear in a Crashpad database,
+//! parses it and checks its contents, so tests verify not just that a file
+//! was written but that it is a valid dump with the expected annotations and
+//! exception.
+//!
+//! ```no_run
+//! use crashpad_test_support::{expect_dump, ExceptionKind};
+//! use std::time::Duration;
+//!
+//! // ... start Crashpad with database "./crashpad_db" and crash a child ...
+//! let dump = expect_dump("./crashpad_db")
+//!     .with_annotation("version", "1.0.0")
+//!     .with_exception_kind(ExceptionKind::Segv)
+//!     .within(Duration::from_secs(10));
+//! println!("verified {}", dump.path.display());
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use minidump::system_info::Os;
+use minidump::{
+    Minidump, MinidumpAnnotation, MinidumpCrashpadInfo, MinidumpException, MinidumpSystemInfo,
+    MinidumpThreadList,
+};
+
+/// How often the database is scanned while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Kind of crash recorded in a dump's exception stream.
+///
+/// Matched against the platform-specific exception code of the dump's OS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExceptionKind {
+    /// Invalid memory access (`SIGSEGV`, `EXCEPTION_ACCESS_VIOLATION`, `EXC_BAD_ACCESS`)
+    Segv,
+    /// Bus error (`SIGBUS`); matches access violations on Windows and macOS
+    Bus,
+    /// `abort()` (`SIGABRT`, `STATUS_FATAL_APP_EXIT`, `EXC_CRASH`)
+    Abort,
+    /// Illegal instruction (`SIGILL`, `EXCEPTION_ILLEGAL_INSTRUCTION`, `EXC_BAD_INSTRUCTION`)
+    IllegalInstruction,
+    /// Arithmetic error (`SIGFPE`, `EXCEPTION_INT_DIVIDE_BY_ZERO`, `EXC_ARITHMETIC`)
+    FloatingPoint,
+    /// Breakpoint or trap (`SIGTRAP`, `EXCEPTION_BREAKPOINT`, `EXC_BREAKPOINT`)
+    Breakpoint,
+}
+
+impl ExceptionKind {
+    /// Exception codes that count as this kind in a dump from `os`.
+    fn codes(self, os: Os) -> &'static [u32] {
+        match os {
+            Os::Windows => match self {
+                ExceptionKind::Segv | ExceptionKind::Bus => &[0xC000_0005],
+                ExceptionKind::Abort => &[0x4000_0015, 0xC000_0409],
+                ExceptionKind::IllegalInstruction => &[0xC000_001D],
+                ExceptionKind::FloatingPoint => &[0xC000_0094, 0xC000_008E],
+                ExceptionKind::Breakpoint => &[0x8000_0003],
+            },
+            Os::MacOs | Os::Ios => match self {
+                ExceptionKind::Segv | ExceptionKind::Bus => &[1],
+                ExceptionKind::Abort => &[10],
+                ExceptionKind::IllegalInstruction => &[2],
+                ExceptionKind::FloatingPoint => &[3],
+                ExceptionKind::Breakpoint => &[6],
+            },
+            // Linux and Android record the signal number
+            _ => match self {
+                ExceptionKind::Segv => &[11],
+                ExceptionKind::Bus => &[7],
+                ExceptionKind::Abort => &[6],
+                ExceptionKind::IllegalInstruction => &[4],
+                ExceptionKind::FloatingPoint => &[8],
+                ExceptionKind::Breakpoint => &[5],
+            },
+        }
+    }
+}
+
+/// Start waiting for a dump in the Crashpad database at `database`.
+///
+/// Dumps already in the database when this is called are ignored.
+pub fn expect_dump<P: AsRef<Path>>(database: P) -> DumpExpectation {
+    let database = database.as_ref().to_path_buf();
+    let existing = dump_files(&database);
+    DumpExpectation {
+        database,
+        existing,
+        annotations: Vec::new(),
+        exception_kind: None,
+    }
+}
+
+/// Expected contents of a dump, built by [`expect_dump`].
+#[derive(Debug)]
+pub struct DumpExpectation {
+    database: PathBuf,
+    existing: HashSet<PathBuf>,
+    annotations: Vec<(String, String)>,
+    exception_kind: Option<ExceptionKind>,
+}
+
+impl DumpExpectation {
+    /// Require an annotation with this value
+    ///
+    /// Process annotations (passed when starting the handler) and module
+    /// annotations are both searched.
+    pub fn with_annotation(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.annotations.push((key.into(), value.into()));
+        self
+    }
+
+    /// Require the dump's exception to be of this kind
+    pub fn with_exception_kind(mut self, kind: ExceptionKind) -> Self {
+        self.exception_kind = Some(kind);
+        self
+    }
+
+    /// Wait up to `timeout` for a matching dump and return it
+    ///
+    /// # Panics
+    /// If no new dump matching the expectations appears in time. The message
+    /// explains why the dumps that did appear didn't match.
+    pub fn within(self, timeout: Duration) -> VerifiedDump {
+        match self.try_within(timeout) {
+            Ok(dump) => dump,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    /// Like [`within`](Self::within), but returns an error instead of panicking
+    pub fn try_within(self, timeout: Duration) -> Result<VerifiedDump, DumpError> {
+        let deadline = Instant::now() + timeout;
+        let mut mismatches = BTreeMap::new();
+
+        loop {
+            let mut candidates: Vec<_> = dump_files(&self.database)
+                .into_iter()
+                .filter(|path| !self.existing.contains(path))
+                .collect();
+            candidates.sort();
+
+            for path in candidates {
+                // Dumps still being written fail to parse; retry on the next scan
+                match self.check(&path) {
+                    Ok(dump) => return Ok(dump),
+                    Err(reason) => {
+                        mismatches.insert(path, reason);
+                    }
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(DumpError {
+                    database: self.database,
+                    timeout,
+                    mismatches: mismatches.into_iter().collect(),
+                });
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn check(&self, path: &Path) -> Result<VerifiedDump, String> {
+        let dump = VerifiedDump::read(path)?;
+
+        for (key, value) in &self.annotations {
+            match dump.annotations.get(key) {
+                Some(actual) if actual == value => {}
+                Some(actual) => {
+                    return Err(format!(
+                        "annotation '{key}' is '{actual}', expected '{value}'"
+                    ))
+                }
+                None => return Err(format!("annotation '{key}' is missing")),
+            }
+        }
+
+        if let Some(kind) = self.exception_kind {
+            let code = dump
+                .exception_code
+                .ok_or_else(|| "dump has no exception stream".to_string())?;
+            if !kind.codes(dump.os).contains(&code) {
+                return Err(format!(
+                    "exception code {code:#x} is not {kind:?} on {:?}",
+                    dump.os
+                ));
+            }
+        }
+
+        Ok(dump)
+    }
+}
+
+/// A parsed dump that met all expectations.
+#[derive(Debug, Clone)]
+pub struct VerifiedDump {
+    pub path: PathBuf,
+    /// Process and module annotations
+    pub annotations: BTreeMap<String, String>,
+    /// Raw exception code, `None` if the dump has no exception stream
+    pub exception_code: Option<u32>,
+    /// Operating system the dump was written on
+    pub os: Os,
+    pub thread_count: usize,
+}
+
+impl VerifiedDump {
+    fn read(path: &Path) -> Result<Self, String> {
+        let dump = Minidump::read_path(path).map_err(|e| format!("not a valid minidump: {e}"))?;
+
+        let system_info = dump
+            .get_stream::<MinidumpSystemInfo>()
+            .map_err(|e| format!("no system info: {e}"))?;
+        let threads = dump
+            .get_stream::<MinidumpThreadList>()
+            .map_err(|e| format!("no thread list: {e}"))?;
+        if threads.threads.is_empty() {
+            return Err("thread list is empty".to_string());
+        }
+
+        let exception_code = dump
+            .get_stream::<MinidumpException>()
+            .ok()
+            .map(|exception| exception.raw.exception_record.exception_code);
+
+        let mut annotations = BTreeMap::new();
+        if let Ok(crashpad_info) = dump.get_stream::<MinidumpCrashpadInfo>() {
+            annotations.extend(crashpad_info.simple_annotations.clone());
+            for module in &crashpad_info.module_list {
+                annotations.extend(module.simple_annotations.clone());
+                for (key, annotation) in &module.annotation_objects {
+                    if let MinidumpAnnotation::String(value) = annotation {
+                        annotations.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            annotations,
+            exception_code,
+            os: system_info.os,
+            thread_count: threads.threads.len(),
+        })
+    }
+}
+
+/// No dump matching the expectations appeared in time.
+#[derive(Debug)]
+pub struct DumpError {
+    pub database: PathBuf,
+    pub timeout: Duration,
+    /// Dumps that appeared, with the reason each didn't match
+    pub mismatches: Vec<(PathBuf, String)>,
+}
+
+impl fmt::Display for DumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "No matching dump in {} within {:?}",
+            self.database.display(),
+            self.timeout
+        )?;
+        if self.mismatches.is_empty() {
+            write!(f, " (no new dumps were written)")?;
+        }
+        for (path, reason) in &self.mismatches {
+            write!(f, "\n  {}: {reason}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DumpError {}
+
+/// All `.dmp` files in the database.
+///
+/// Crashpad keeps dumps in `new`, `pending` and `completed` on most platforms
+/// and in `reports` on Windows, so the whole tree is searched.
+pub fn dump_files(database: &Path) -> HashSet<PathBuf> {
+    let mut dumps = HashSet::new();
+    let mut dirs = vec![database.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "dmp") {
+                dumps.insert(path);
+            }
+        }
+    }
+    dumps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exception_codes_per_os() {
+        assert_eq!(ExceptionKind::Segv.codes(Os::Linux), &[11]);
+        assert_eq!(ExceptionKind::Segv.codes(Os::Windows), &[0xC000_0005]);
+        assert_eq!(ExceptionKind::Segv.codes(Os::MacOs), &[1]);
+        assert_eq!(ExceptionKind::Abort.codes(Os::Android), &[6]);
+    }
+
+    #[test]
+    fn test_ignores_existing_and_invalid_dumps() {
+        let temp_dir = std::env::temp_dir().join(format!("expect-dump-{}", std::process::id()));
+        let pending = temp_dir.join("pending");
+        std::fs::create_dir_all(&pending).unwrap();
+        std::fs::write(pending.join("old.dmp"), b"old").unwrap();
+
+        let expectation = expect_dump(&temp_dir);
+        std::fs::write(pending.join("new.dmp"), b"not a minidump").unwrap();
+
+        let error = expectation
+            .try_within(Duration::from_millis(10))
+            .unwrap_err();
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+
+        assert_eq!(error.mismatches.len(), 1);
+        assert!(error.mismatches[0].0.ends_with("new.dmp"));
+        assert!(error.mismatches[0].1.contains("not a valid minidump"));
+    }
+}
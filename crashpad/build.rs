@@ -2,10 +2,13 @@ use std::env;
 
 /// Build script for the crashpad crate.
 ///
-/// This script only validates feature flags to ensure proper configuration.
+/// This script validates feature flags and the version of crashpad-rs-sys.
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-env-changed=DEP_CRASHPAD_HANDLER");
+    println!("cargo:rerun-if-env-changed=DEP_CRASHPAD_VERSION");
+
+    check_sys_version(env::var("DEP_CRASHPAD_VERSION").ok().as_deref());
 
     // Check feature flags
     let bundled = env::var("CARGO_FEATURE_BUNDLED").is_ok();
@@ -21,5 +24,30 @@ fn main() {
     // as DEP_CRASHPAD_RS_HANDLER.
     if let Ok(handler) = env::var("DEP_CRASHPAD_HANDLER") {
         println!("cargo:handler={}", handler);
+        // Also record it for `CrashpadConfig::with_bundled_handler()`
+        println!("cargo:rustc-env=CRASHPAD_HANDLER_PATH={}", handler);
+    }
+}
+
+/// Fail the build if crashpad-rs-sys isn't the release this crate was
+/// written against.
+///
+/// Both crates are released together and the bindings follow the wrapper's
+/// C API, which changes between releases; a dependency graph that resolves
+/// them to different versions would otherwise link against functions that
+/// are missing or have another signature.
+fn check_sys_version(sys_version: Option<&str>) {
+    let version = env!("CARGO_PKG_VERSION");
+    match sys_version {
+        Some(sys_version) if sys_version == version => {}
+        Some(sys_version) => panic!(
+            "crashpad-rs {version} requires crashpad-rs-sys {version}, found {sys_version}. \
+             Use matching versions, e.g. with `cargo update -p crashpad-rs-sys --precise {version}`"
+        ),
+        None => panic!(
+            "crashpad-rs {version} requires crashpad-rs-sys {version}, found an older release \
+             without version metadata. Use matching versions, e.g. with \
+             `cargo update -p crashpad-rs-sys --precise {version}`"
+        ),
     }
 }
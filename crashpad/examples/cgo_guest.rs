@@ -0,0 +1,52 @@
+//! Rust library embedded in a Go program through cgo
+//!
+//! Built as a static library and linked into `tests/cgo_host/main.go` by
+//! `tests/cgo_host_test.rs`.
+
+use crashpad_rs::{Coexistence, CrashpadClient, CrashpadConfig};
+use std::ffi::{c_char, c_int, CStr};
+use std::sync::OnceLock;
+
+static CLIENT: OnceLock<CrashpadClient> = OnceLock::new();
+
+/// Start Crashpad with the Go preset. Returns 0 on success.
+///
+/// # Safety
+/// Both arguments must be valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn crashpad_guest_start(
+    handler_path: *const c_char,
+    database_path: *const c_char,
+) -> c_int {
+    let handler_path = CStr::from_ptr(handler_path).to_string_lossy().into_owned();
+    let database_path = CStr::from_ptr(database_path).to_string_lossy().into_owned();
+
+    let config = CrashpadConfig::builder()
+        .handler_path(handler_path)
+        .database_path(database_path)
+        .coexistence(Coexistence::GoCgo)
+        .build();
+
+    let result = CrashpadClient::new().and_then(|client| {
+        client.start_with_config(&config, &Default::default())?;
+        Ok(client)
+    });
+    match result {
+        Ok(client) => {
+            let _ = CLIENT.set(client);
+            0
+        }
+        Err(e) => {
+            eprintln!("crashpad_guest_start failed: {e}");
+            -1
+        }
+    }
+}
+
+/// Crash in Rust code called from Go.
+#[no_mangle]
+pub extern "C" fn crashpad_guest_crash() {
+    unsafe {
+        std::ptr::null_mut::<i32>().write_volatile(42);
+    }
+}
@@ -0,0 +1,23 @@
+//! Print a minidump as JSON, see the `export` module
+//!
+//! Usage: `cargo run --example dump2json -- <minidump>`
+
+use std::process;
+
+fn main() {
+    let Some(path) = std::env::args_os().nth(1) else {
+        eprintln!("Usage: dump2json <minidump>");
+        process::exit(2);
+    };
+
+    match crashpad_rs::export::minidump_to_json(&path) {
+        Ok(document) => println!(
+            "{}",
+            serde_json::to_string_pretty(&document).expect("a JSON value serializes")
+        ),
+        Err(e) => {
+            eprintln!("{}: {e}", path.to_string_lossy());
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! The last changes of runtime annotations, included in every dump.
+//!
+//! When a crash follows a change of state, such as a renderer switching
+//! from Vulkan to GL, the annotations only show the state after it. With
+//! the audit trail enabled by [`init`], every change of a
+//! [`LargeAnnotation`](crate::LargeAnnotation) is recorded with its key,
+//! old and new value and time, so the transition itself is in the report.
+//! [`Minidump::annotation_changes`](crate::minidump::Minidump::annotation_changes)
+//! reads them back.
+//!
+//! ```rust,no_run
+//! use crashpad_rs::{annotation_audit, LargeAnnotation};
+//!
+//! // Room for the last 32 changes of up to 100 bytes
+//! annotation_audit::init(32, 100)?;
+//!
+//! let renderer = LargeAnnotation::register("renderer", 64)?;
+//! renderer.set("vulkan");
+//! renderer.set("gl");
+//! # Ok::<(), crashpad_rs::CrashpadError>(())
+//! ```
+//!
+//! Changes of the annotations the crate sets itself, like the
+//! [crash category hint](crate::set_crash_category_hint), are recorded
+//! too.
+//!
+//! The trail is a [`ValueHistory`] under the key [`KEY`], each value the
+//! key, the old and the new value separated by NUL bytes. Values pass the
+//! [redaction rules](crate::CrashpadConfigBuilder::redaction) of the
+//! started client under the key of their annotation.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::ptr;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crashpad_rs_sys::crashpad_set_module_annotation;
+
+use crate::history::{self, ValueChange};
+use crate::{CrashpadError, Result, ValueHistory};
+
+/// Key of the value history holding the changes
+pub const KEY: &str = "annotation_changes";
+
+/// The trail and its size, once enabled
+static TRAIL: Mutex<Option<(&'static ValueHistory, usize, usize)>> = Mutex::new(None);
+
+/// Values of the module annotations set with [`set_module_annotation`],
+/// which Crashpad can't read back
+static MODULE_VALUES: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+/// A change of an annotation recorded by the audit trail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnotationChange {
+    pub time: SystemTime,
+    pub key: String,
+    /// Value before the change, empty if the annotation wasn't set
+    pub old: String,
+    /// Value after the change, empty if the annotation was cleared
+    pub new: String,
+}
+
+/// Record the last `capacity` annotation changes from now on, each cut to
+/// `max_change_len` bytes of key, old and new value together
+///
+/// Long values are cut so that the old and the new value each keep their
+/// share of the bytes left after the key: half each, or more for one if
+/// the other is shorter.
+///
+/// The trail lives for the rest of the process. Changes made before the
+/// first call aren't recorded.
+///
+/// # Errors
+///
+/// [`CrashpadError::InvalidConfiguration`] if the trail exists already
+/// with another size, or can't be registered, see
+/// [`ValueHistory::register`].
+pub fn init(capacity: usize, max_change_len: usize) -> Result<()> {
+    let mut trail = TRAIL.lock().unwrap_or_else(|e| e.into_inner());
+    match *trail {
+        Some((_, existing_capacity, existing_len))
+            if existing_capacity == capacity && existing_len == max_change_len =>
+        {
+            Ok(())
+        }
+        Some(_) => Err(CrashpadError::InvalidConfiguration(
+            "Annotation audit trail was initialized with another size already".to_string(),
+        )),
+        None => {
+            let history = ValueHistory::register(KEY, capacity, max_change_len)?;
+            *trail = Some((history, capacity, max_change_len));
+            Ok(())
+        }
+    }
+}
+
+/// Whether changes are recorded
+pub(crate) fn is_enabled() -> bool {
+    TRAIL.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// Record a change of annotation `key` from `old` to `new`, if the trail is
+/// enabled and the value changed
+pub(crate) fn record(key: &str, old: &str, new: &str) {
+    let Some((trail, _, max_change_len)) = *TRAIL.lock().unwrap_or_else(|e| e.into_inner()) else {
+        return;
+    };
+    if old == new {
+        return;
+    }
+    let (old, new) = crate::redaction::with_active(|rules| match rules {
+        Some(rules) => (rules.redact(key, old), rules.redact(key, new)),
+        None => (Cow::Borrowed(old), Cow::Borrowed(new)),
+    });
+    trail.record(encode(key, &old, &new, max_change_len));
+}
+
+/// Set module annotation `key` to `value`, or remove it with `None`, and
+/// record the change
+///
+/// For annotations the crate sets itself through
+/// `crashpad_set_module_annotation`; the values are kept here to know the
+/// old one. Values with NUL bytes remove the annotation.
+pub(crate) fn set_module_annotation(key: &str, value: Option<&str>) {
+    let value = value.filter(|value| !value.contains('\0'));
+    let Ok(key_c) = CString::new(key) else {
+        return;
+    };
+    let value_c = value.map(|value| CString::new(value).expect("no NUL bytes"));
+
+    let mut values = MODULE_VALUES.lock().unwrap_or_else(|e| e.into_inner());
+    let values = values.get_or_insert_with(HashMap::new);
+    let value_ptr = value_c.as_ref().map_or(ptr::null(), |value| value.as_ptr());
+    unsafe { crashpad_set_module_annotation(key_c.as_ptr(), value_ptr) };
+    let old = match value {
+        Some(value) => values.insert(key.to_string(), value.to_string()),
+        None => values.remove(key),
+    };
+    record(
+        key,
+        old.as_deref().unwrap_or_default(),
+        value.unwrap_or_default(),
+    );
+}
+
+/// The change of `key` from `old` to `new` as recorded, the values cut to
+/// their shares of `max_len` bytes
+fn encode(key: &str, old: &str, new: &str, max_len: usize) -> String {
+    // The key and the two separators come first
+    let budget = max_len.saturating_sub(key.len() + 2);
+    let half = budget / 2;
+    let (old_len, new_len) = if old.len() <= half {
+        (old.len(), budget - old.len())
+    } else if new.len() <= budget - half {
+        (budget - new.len(), new.len())
+    } else {
+        (half, budget - half)
+    };
+    let old = history::truncate(old, old_len);
+    let new = history::truncate(new, new_len);
+    format!("{key}\0{old}\0{new}")
+}
+
+/// The change recorded as `change`; parts cut off by truncation are empty
+pub(crate) fn decode(change: &ValueChange) -> AnnotationChange {
+    let mut parts = change.value.splitn(3, '\0');
+    let mut part = || parts.next().unwrap_or_default().to_string();
+    AnnotationChange {
+        time: change.time,
+        key: part(),
+        old: part(),
+        new: part(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("k", "old", "new", 100), "k\0old\0new");
+
+        // A long old value doesn't push the new one out
+        let long = "o".repeat(100);
+        assert_eq!(encode("k", &long, "new", 13), "k\0ooooooo\0new");
+        assert_eq!(encode("k", "", &long, 13), "k\0\0oooooooooo");
+        assert_eq!(encode("k", &long, &long, 13), "k\0ooooo\0ooooo");
+        // Cut at character boundaries
+        assert_eq!(encode("k", "éé", "éé", 9), "k\0é\0é");
+        // No room for values
+        assert_eq!(encode("key", "old", "new", 2), "key\0\0");
+    }
+
+    #[test]
+    fn test_decode() {
+        let time = UNIX_EPOCH + Duration::from_millis(1500);
+        let change = |value: &str| ValueChange {
+            time,
+            value: value.to_string(),
+        };
+        assert_eq!(
+            decode(&change("renderer\0vulkan\0gl")),
+            AnnotationChange {
+                time,
+                key: "renderer".to_string(),
+                old: "vulkan".to_string(),
+                new: "gl".to_string(),
+            }
+        );
+
+        // Cut while recording the old value
+        let truncated = decode(&change("renderer\0vul"));
+        assert_eq!(truncated.old, "vul");
+        assert_eq!(truncated.new, "");
+    }
+}
@@ -0,0 +1,343 @@
+//! Export of dumps to a directory scraped by cluster-level collectors.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use crate::{file_name, timestamp};
+use crate::{CrashReport, CrashReportDatabase, Result};
+
+/// Default [`ArtifactExport::file_name_template`].
+const DEFAULT_TEMPLATE: &str = "{timestamp}-{uuid}";
+
+/// Extension of the metadata file exported next to each dump.
+const METADATA_EXTENSION: &str = "json";
+
+/// Where and how [`ArtifactExporter`] exports dumps.
+///
+/// Each report is exported as `<name>.dmp` with a `<name>.json` metadata
+/// file next to it, holding the report's UUID, creation time (RFC 3339
+/// UTC), size, upload state and annotations. Point the directory at a
+/// volume that outlives the pod, e.g. a PVC or a hostPath scraped by a
+/// node agent.
+///
+/// # Example
+///
+/// ```
+/// use crashpad_rs::ArtifactExport;
+///
+/// let export = ArtifactExport::new("/var/crash-artifacts")
+///     .file_name_template("{timestamp}-{version}-{uuid}")
+///     .termination_log("/dev/termination-log");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactExport {
+    directory: PathBuf,
+    template: String,
+    link: bool,
+    termination_log: Option<PathBuf>,
+}
+
+impl ArtifactExport {
+    /// Export to `directory`, which is created if needed
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        Self {
+            directory: directory.into(),
+            template: DEFAULT_TEMPLATE.to_string(),
+            link: false,
+            termination_log: None,
+        }
+    }
+
+    /// Name of the exported files, without extension
+    ///
+    /// See [`CrashReport::file_name`] for the placeholders. If the name is
+    /// already taken by another report, `-<uuid>` is appended.
+    ///
+    /// # Default
+    ///
+    /// `{timestamp}-{uuid}`
+    pub fn file_name_template<S: Into<String>>(mut self, template: S) -> Self {
+        self.template = template.into();
+        self
+    }
+
+    /// Hard link dumps instead of copying them
+    ///
+    /// Falls back to a copy when the directory is on another file system,
+    /// as an `emptyDir` or PVC mount usually is. A link shares the file with
+    /// the database, so it survives pruning but not changes to the dump.
+    ///
+    /// # Default
+    ///
+    /// `false`
+    pub fn link(mut self, link: bool) -> Self {
+        self.link = link;
+        self
+    }
+
+    /// Write a message about the last exported report to `path`
+    ///
+    /// Kubernetes shows the contents of the container's termination message
+    /// file (`/dev/termination-log` unless `terminationMessagePath` says
+    /// otherwise) in the pod status when the container terminates, so
+    /// `kubectl describe pod` points at the exported dump.
+    pub fn termination_log<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.termination_log = Some(path.into());
+        self
+    }
+
+    /// Export the reports of `database` that aren't in the directory yet
+    ///
+    /// Returns the paths of the exported dumps. Reports already exported,
+    /// by this or an earlier process, are recognized by the UUID in their
+    /// metadata file.
+    pub fn export_reports(&self, database: &CrashReportDatabase) -> Result<Vec<PathBuf>> {
+        let mut exported = self.exported_uuids()?;
+        self.export_new(database, &mut exported)
+    }
+
+    fn export_new(
+        &self,
+        database: &CrashReportDatabase,
+        exported: &mut HashSet<String>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut reports = database.pending_reports()?;
+        reports.extend(database.completed_reports()?);
+        reports.retain(|report| !exported.contains(&report.uuid));
+        reports.sort_by_key(|report| report.creation_time);
+
+        let mut paths = Vec::new();
+        for report in reports {
+            // The handler may still be writing it, or the database pruned it
+            if !report.file_path.exists() {
+                continue;
+            }
+            let path = self.export(&report)?;
+            exported.insert(report.uuid.clone());
+            if let Some(log) = &self.termination_log {
+                let message = format!(
+                    "Crash report {} exported to {}\n",
+                    report.uuid,
+                    path.display()
+                );
+                std::fs::write(log, message)?;
+            }
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Export one report, returning the path of the exported dump
+    fn export(&self, report: &CrashReport) -> Result<PathBuf> {
+        std::fs::create_dir_all(&self.directory)?;
+        // A dump without readable annotations is still worth exporting
+        let annotations = report.annotations().unwrap_or_default();
+
+        let mut name = file_name::render(&self.template, report, &annotations);
+        if self.directory.join(format!("{name}.dmp")).exists() {
+            name = format!("{name}-{}", report.uuid);
+        }
+        let dump = self.directory.join(format!("{name}.dmp"));
+
+        if !self.link || std::fs::hard_link(&report.file_path, &dump).is_err() {
+            // Through a temporary file, so collectors never pick up a
+            // partial dump
+            let temporary = dump.with_extension("dmp.tmp");
+            std::fs::copy(&report.file_path, &temporary)?;
+            std::fs::rename(&temporary, &dump)?;
+        }
+
+        // Written last: its presence marks the report as exported
+        let metadata = serde_json::json!({
+            "uuid": report.uuid,
+            "creation_time": timestamp::rfc3339(report.creation_time),
+            "exported_time": timestamp::rfc3339(SystemTime::now()),
+            "dump": dump.file_name().map(|name| name.to_string_lossy()),
+            "database_path": report.file_path,
+            "total_size": report.total_size,
+            "uploaded": report.uploaded,
+            "server_id": report.id,
+            "annotations": annotations,
+        });
+        let metadata_path = dump.with_extension(METADATA_EXTENSION);
+        let temporary = dump.with_extension("json.tmp");
+        std::fs::write(
+            &temporary,
+            serde_json::to_vec_pretty(&metadata).map_err(std::io::Error::from)?,
+        )?;
+        std::fs::rename(&temporary, &metadata_path)?;
+
+        Ok(dump)
+    }
+
+    /// UUIDs of the reports with a metadata file in the directory
+    fn exported_uuids(&self) -> Result<HashSet<String>> {
+        let entries = match std::fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashSet::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext == METADATA_EXTENSION)
+            })
+            .filter_map(|path| std::fs::read(path).ok())
+            .filter_map(|json| serde_json::from_slice::<serde_json::Value>(&json).ok())
+            .filter_map(|metadata| metadata["uuid"].as_str().map(str::to_string))
+            .collect())
+    }
+}
+
+/// Exports new dumps on a background thread.
+///
+/// Checks the database once per interval and exports every report that
+/// isn't in the artifacts directory yet, including the ones written before
+/// the exporter started. Run it in the application to export the dump of
+/// the previous crash when the container restarts, or in a sidecar
+/// container that shares the database volume to export dumps as soon as
+/// the handler finishes them. The thread stops when the exporter is
+/// dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use crashpad_rs::{ArtifactExport, ArtifactExporter};
+/// use std::time::Duration;
+///
+/// let _exporter = ArtifactExporter::start(
+///     "./crashpad_db",
+///     ArtifactExport::new("/var/crash-artifacts").termination_log("/dev/termination-log"),
+///     Duration::from_secs(5),
+/// )?;
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+pub struct ArtifactExporter {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ArtifactExporter {
+    /// Export the reports of the database at `database_path` every
+    /// `interval`
+    pub fn start<P: AsRef<Path>>(
+        database_path: P,
+        export: ArtifactExport,
+        interval: Duration,
+    ) -> Result<Self> {
+        let database = CrashReportDatabase::open(database_path)?;
+        let mut exported = export.exported_uuids()?;
+
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stop = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("crashpad-artifact-exporter".to_string())
+            .spawn(move || {
+                let (stopped, condvar) = &*thread_stop;
+                loop {
+                    // Try again on the next tick, e.g. while the volume is full
+                    let _ = export.export_new(&database, &mut exported);
+
+                    let guard = stopped.lock().unwrap_or_else(|e| e.into_inner());
+                    let (guard, _) = condvar
+                        .wait_timeout_while(guard, interval, |stopped| !*stopped)
+                        .unwrap_or_else(|e| e.into_inner());
+                    if *guard {
+                        return;
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for ArtifactExporter {
+    fn drop(&mut self) {
+        let (stopped, condvar) = &*self.stop;
+        *stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for ArtifactExporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArtifactExporter").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn report(file_path: PathBuf) -> CrashReport {
+        CrashReport {
+            uuid: "00112233-4455-6677-8899-aabbccddeeff".to_string(),
+            file_path,
+            id: None,
+            creation_time: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            uploaded: false,
+            last_upload_attempt_time: None,
+            upload_attempts: 0,
+            upload_explicitly_requested: false,
+            total_size: 4,
+        }
+    }
+
+    #[test]
+    fn test_export() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dump_path = temp_dir.path().join("report.dmp");
+        std::fs::write(&dump_path, b"MDMP").unwrap();
+        // Annotations come from the sidecar, the dump isn't parsed
+        std::fs::write(
+            temp_dir.path().join("report.annotations.json"),
+            br#"{"version":"2.0"}"#,
+        )
+        .unwrap();
+        let artifacts = temp_dir.path().join("artifacts");
+        let log = temp_dir.path().join("termination-log");
+        let export = ArtifactExport::new(&artifacts)
+            .file_name_template("crash-{version}")
+            .termination_log(&log);
+        let report = report(dump_path);
+
+        let dump = export.export(&report).unwrap();
+        assert_eq!(dump, artifacts.join("crash-2.0.dmp"));
+        assert_eq!(std::fs::read(&dump).unwrap(), b"MDMP");
+
+        let metadata: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(artifacts.join("crash-2.0.json")).unwrap())
+                .unwrap();
+        assert_eq!(metadata["uuid"], report.uuid.as_str());
+        assert_eq!(metadata["creation_time"], "2023-11-14T22:13:20Z");
+        assert_eq!(metadata["annotations"]["version"], "2.0");
+        assert_eq!(
+            export.exported_uuids().unwrap(),
+            HashSet::from([report.uuid.clone()])
+        );
+
+        // A name collision keeps both
+        let other = CrashReport {
+            uuid: "ffeeddcc-bbaa-9988-7766-554433221100".to_string(),
+            ..report
+        };
+        assert_eq!(
+            export.export(&other).unwrap(),
+            artifacts.join(format!("crash-2.0-{}.dmp", other.uuid))
+        );
+        assert!(!log.exists());
+    }
+}
@@ -0,0 +1,170 @@
+//! Async wrappers for the blocking parts of the API (`tokio` feature).
+//!
+//! Starting the handler spawns a process and prepares the database, and
+//! listing reports scans the database directory; both block. Called from
+//! an async task, they would stall the other tasks of its worker thread.
+//! These wrappers run them on Tokio's blocking thread pool instead.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+
+use crate::{
+    CrashReport, CrashReportDatabase, CrashpadClient, CrashpadConfig, CrashpadError, Result,
+    UploadEvent, UploadMonitor,
+};
+
+/// Run `f` on the blocking thread pool
+async fn blocking<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(_) => Err(CrashpadError::IoError(io::Error::new(
+            io::ErrorKind::Interrupted,
+            "the runtime shut down",
+        ))),
+    }
+}
+
+impl CrashpadClient {
+    /// Like [`start_with_config`](Self::start_with_config), without blocking
+    /// the async runtime
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use crashpad_rs::{CrashpadClient, CrashpadConfig};
+    /// use std::sync::Arc;
+    ///
+    /// # async fn run() -> crashpad_rs::Result<()> {
+    /// let client = Arc::new(CrashpadClient::new()?);
+    /// client
+    ///     .start_with_config_async(&CrashpadConfig::default(), &Default::default())
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn start_with_config_async(
+        self: &Arc<Self>,
+        config: &CrashpadConfig,
+        annotations: &HashMap<String, String>,
+    ) -> Result<()> {
+        let client = self.clone();
+        let config = config.clone();
+        let annotations = annotations.clone();
+        blocking(move || client.start_with_config(&config, &annotations)).await
+    }
+}
+
+impl CrashReportDatabase {
+    /// [`pending_reports`](Self::pending_reports) of the database at
+    /// `path`, without blocking the async runtime
+    pub async fn pending_reports_async<P: AsRef<Path>>(path: P) -> Result<Vec<CrashReport>> {
+        let path = path.as_ref().to_path_buf();
+        blocking(move || Self::open(path)?.pending_reports()).await
+    }
+
+    /// [`completed_reports`](Self::completed_reports) of the database at
+    /// `path`, without blocking the async runtime
+    pub async fn completed_reports_async<P: AsRef<Path>>(path: P) -> Result<Vec<CrashReport>> {
+        let path = path.as_ref().to_path_buf();
+        blocking(move || Self::open(path)?.completed_reports()).await
+    }
+}
+
+/// Upload events of a database, received with [`next`](Self::next).
+///
+/// Created by [`UploadMonitor::subscribe`]. Monitoring stops when it is
+/// dropped.
+#[derive(Debug)]
+pub struct UploadEvents {
+    receiver: mpsc::UnboundedReceiver<UploadEvent>,
+    _monitor: UploadMonitor,
+}
+
+impl UploadEvents {
+    /// The next event, waiting for the handler to finish with a report
+    pub async fn next(&mut self) -> Option<UploadEvent> {
+        self.receiver.recv().await
+    }
+}
+
+impl UploadMonitor {
+    /// Monitor the database at `database_path` like [`start`](Self::start),
+    /// receiving the events asynchronously
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use crashpad_rs::{UploadEvent, UploadMonitor};
+    ///
+    /// # async fn run() -> crashpad_rs::Result<()> {
+    /// let mut events = UploadMonitor::subscribe("./crashpad_db").await?;
+    /// while let Some(event) = events.next().await {
+    ///     if let UploadEvent::Uploaded { id, .. } = event {
+    ///         println!("Crash report sent ({id})");
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn subscribe<P: AsRef<Path>>(database_path: P) -> Result<UploadEvents> {
+        let database_path = database_path.as_ref().to_path_buf();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let monitor = blocking(move || {
+            UploadMonitor::start(database_path, move |event| {
+                let _ = sender.send(event);
+            })
+        })
+        .await?;
+        Ok(UploadEvents {
+            receiver,
+            _monitor: monitor,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use tempfile::TempDir;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_reports_async() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("crashpad_db");
+        block_on(async {
+            assert!(CrashReportDatabase::pending_reports_async(&path)
+                .await
+                .unwrap()
+                .is_empty());
+            assert!(CrashReportDatabase::completed_reports_async(&path)
+                .await
+                .unwrap()
+                .is_empty());
+        });
+    }
+
+    #[test]
+    fn test_blocking_error() {
+        let result: Result<()> = block_on(blocking(|| {
+            Err(CrashpadError::ReportNotFound("uuid".to_string()))
+        }));
+        assert!(matches!(result, Err(CrashpadError::ReportNotFound(_))));
+    }
+}
@@ -0,0 +1,146 @@
+//! iOS/tvOS/watchOS backend.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crashpad_rs_sys::crashpad_client_t;
+
+use super::{path_to_cstring, url_to_cstring, AnnotationArrays, PlatformBackend, StartRequest};
+use crate::{CrashpadError, Result};
+
+/// Directory of the database holding intermediate dumps not yet converted
+/// to minidumps
+const INTERMEDIATE_DUMP_DIR: &str = "pending-serialized-ios-dump";
+
+/// When the in-process handler uploads pending reports.
+///
+/// See [`CrashpadClient::start_processing_pending_reports`](crate::CrashpadClient::start_processing_pending_reports).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum UploadBehavior {
+    /// Only while the app is in the foreground (Crashpad's default)
+    #[default]
+    WhenAppIsActive,
+    /// Right away, also in the background
+    Immediately,
+}
+
+/// Runs the in-process handler.
+///
+/// The handler path is unused and handler arguments are ignored, as the
+/// in-process handler has hardcoded settings. This may change in future
+/// Crashpad versions. See https://crashpad.chromium.org/bug/23
+#[derive(Debug, Default)]
+pub(crate) struct IosBackend;
+
+impl PlatformBackend for IosBackend {
+    fn start_handler(&self, client: crashpad_client_t, request: &StartRequest<'_>) -> Result<()> {
+        let database_path_c = path_to_cstring(request.database_path)?;
+        let _metrics_path_c = path_to_cstring(request.metrics_path)?;
+        let url_c = url_to_cstring(request.url)?;
+        let annotations = AnnotationArrays::new(request.annotations)?;
+
+        let success = unsafe {
+            crashpad_rs_sys::crashpad_client_start_in_process_handler(
+                client,
+                database_path_c.as_ptr(),
+                url_c.as_ref().map_or(std::ptr::null(), |u| u.as_ptr()),
+                annotations.keys.as_ptr(),
+                annotations.values.as_ptr(),
+                annotations.len(),
+            )
+        };
+
+        if !success {
+            return Err(CrashpadError::InitializationFailed);
+        }
+
+        // Start processing pending reports first
+        self.start_processing_pending_reports();
+
+        // Then process any intermediate dumps from previous sessions
+        // This needs to be called after StartProcessingPendingReports
+        self.process_intermediate_dumps();
+        Ok(())
+    }
+}
+
+impl IosBackend {
+    pub(crate) fn start_processing_pending_reports(&self) {
+        unsafe {
+            crashpad_rs_sys::crashpad_client_start_processing_pending_reports();
+        }
+    }
+
+    pub(crate) fn start_processing_pending_reports_with(&self, behavior: UploadBehavior) {
+        let behavior = match behavior {
+            UploadBehavior::WhenAppIsActive => crashpad_rs_sys::CRASHPAD_UPLOAD_WHEN_APP_IS_ACTIVE,
+            UploadBehavior::Immediately => crashpad_rs_sys::CRASHPAD_UPLOAD_IMMEDIATELY,
+        };
+        unsafe {
+            crashpad_rs_sys::crashpad_client_start_processing_pending_reports_with_behavior(
+                behavior as i32,
+            );
+        }
+    }
+
+    pub(crate) fn process_intermediate_dumps(&self) {
+        unsafe {
+            crashpad_rs_sys::crashpad_client_process_intermediate_dumps();
+        }
+    }
+
+    pub(crate) fn process_intermediate_dumps_with_annotations(
+        &self,
+        annotations: &HashMap<String, String>,
+    ) -> Result<()> {
+        let annotations = AnnotationArrays::new(annotations)?;
+        unsafe {
+            crashpad_rs_sys::crashpad_client_process_intermediate_dumps_with_annotations(
+                annotations.keys.as_ptr(),
+                annotations.values.as_ptr(),
+                annotations.len(),
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn process_intermediate_dump(
+        &self,
+        path: &Path,
+        annotations: &HashMap<String, String>,
+    ) -> Result<()> {
+        let path_c = path_to_cstring(path)?;
+        let annotations = AnnotationArrays::new(annotations)?;
+        unsafe {
+            crashpad_rs_sys::crashpad_client_process_intermediate_dump(
+                path_c.as_ptr(),
+                annotations.keys.as_ptr(),
+                annotations.values.as_ptr(),
+                annotations.len(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Intermediate dumps waiting in the database at `database_path`, oldest
+/// first.
+pub(crate) fn pending_intermediate_dumps(database_path: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(database_path.join(INTERMEDIATE_DUMP_DIR)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut dumps = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            let modified = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
+            dumps.push((modified, entry.path()));
+        }
+    }
+    dumps.sort();
+    Ok(dumps.into_iter().map(|(_, path)| path).collect())
+}
@@ -0,0 +1,314 @@
+//! Linux and Android backend.
+
+#[cfg(target_os = "android")]
+use std::ffi::CString;
+use std::os::fd::{AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd};
+use std::os::raw::c_int;
+
+use crashpad_rs_sys::*;
+
+use super::{spawn_handler, start_external_handler, PlatformBackend, StartRequest};
+#[cfg(target_os = "android")]
+use super::{CStringArray, HandlerCommand};
+use crate::coexistence::PlatformOptions;
+use crate::signals;
+#[cfg(target_os = "android")]
+use crate::AndroidHandlerLaunch;
+use crate::{installed_signal_handlers, CrashpadError, Result, SignalChaining, CRASH_SIGNALS};
+
+/// Spawns `crashpad_handler` (`libcrashpad_handler.so` on Android) and
+/// installs the signal handlers.
+#[derive(Debug, Default)]
+pub(crate) struct LinuxBackend;
+
+impl PlatformBackend for LinuxBackend {
+    fn start_handler(&self, client: crashpad_client_t, request: &StartRequest<'_>) -> Result<()> {
+        let options = request.platform_options;
+        let signals = handled_signals(options)?;
+
+        let before = installed_signal_handlers(&signals);
+        if options.require_runtime_handlers
+            && installed_signal_handlers(&[libc::SIGSEGV]).is_empty()
+        {
+            return Err(CrashpadError::InvalidConfiguration(
+                "No SIGSEGV handler installed yet; start Crashpad after the host runtime \
+                 has initialized"
+                    .to_string(),
+            ));
+        }
+        if options.reject_signal_conflicts {
+            if let Some(existing) = before.first() {
+                return Err(CrashpadError::SignalHandlerConflict {
+                    signal: existing.signal,
+                    handler: existing.to_string(),
+                });
+            }
+        }
+
+        // Always set, the unhandled set persists across restarts
+        let unhandled: Vec<i32> = CRASH_SIGNALS
+            .iter()
+            .copied()
+            .filter(|signal| !signals.contains(signal))
+            .collect();
+        unsafe {
+            crashpad_client_set_unhandled_signals(unhandled.as_ptr(), unhandled.len());
+            crashpad_client_set_chain_before_dump(
+                options.signal_chaining == SignalChaining::BeforeDump,
+            );
+        }
+        // Also releases the reservation of an earlier start when unset
+        match unsafe {
+            crashpad_client_set_crash_reserve(
+                options.crash_reserve_memory,
+                options.crash_reserve_file_descriptors,
+            )
+        } {
+            0 => {}
+            code => return Err(std::io::Error::from_raw_os_error(code).into()),
+        }
+
+        if !start_android_handler(request, None)? {
+            #[cfg(target_os = "linux")]
+            if let Some((socket, pid)) = crate::fork_server::spawn_handler(request)? {
+                self.set_handler_socket(client, socket, Some(pid))?;
+                signals::record_start(before, installed_signal_handlers(&signals));
+                return Ok(());
+            }
+            start_external_handler(client, request)?;
+        }
+
+        signals::record_start(before, installed_signal_handlers(&signals));
+        Ok(())
+    }
+}
+
+impl LinuxBackend {
+    /// A duplicate of the socket clients use to reach the started handler,
+    /// and the handler's process ID.
+    pub(crate) fn handler_socket(&self) -> Result<(OwnedFd, u32)> {
+        let mut socket: c_int = -1;
+        let mut pid: c_int = -1;
+        if !unsafe { crashpad_client_get_handler_socket(&mut socket, &mut pid) } {
+            return Err(CrashpadError::InvalidConfiguration(
+                "No handler was started by this process".to_string(),
+            ));
+        }
+        // SAFETY: Crashpad keeps the socket open for the life of the process
+        let socket = unsafe { BorrowedFd::borrow_raw(socket) }.try_clone_to_owned()?;
+        Ok((socket, pid.max(0) as u32))
+    }
+
+    pub(crate) fn set_handler_socket(
+        &self,
+        client: crashpad_client_t,
+        socket: OwnedFd,
+        handler_pid: Option<u32>,
+    ) -> Result<()> {
+        let pid = match handler_pid {
+            Some(pid) => c_int::try_from(pid).map_err(|_| {
+                CrashpadError::InvalidConfiguration(format!("Invalid handler PID {pid}"))
+            })?,
+            None => -1,
+        };
+        let success =
+            unsafe { crashpad_client_set_handler_socket(client, socket.into_raw_fd(), pid) };
+
+        if success {
+            Ok(())
+        } else {
+            Err(CrashpadError::IpcRegistrationFailed {
+                endpoint: "socket".to_string(),
+                os_error: None,
+            })
+        }
+    }
+
+    pub(crate) fn start_handler_for_client(
+        &self,
+        request: &StartRequest<'_>,
+        socket: BorrowedFd<'_>,
+    ) -> Result<()> {
+        if start_android_handler(request, Some(socket))? {
+            return Ok(());
+        }
+        spawn_handler(request, |command| unsafe {
+            crashpad_client_start_handler_for_client(
+                command.handler_path.as_ptr(),
+                command.database_path.as_ptr(),
+                command.metrics_path.as_ptr(),
+                command.url_ptr(),
+                command.annotations.keys.as_ptr(),
+                command.annotations.values.as_ptr(),
+                command.annotations.len(),
+                command.arguments_ptr(),
+                command.arguments.len(),
+                socket.as_raw_fd(),
+            )
+        })
+    }
+}
+
+/// Start the handler of `request` through `app_process` or the linker, as
+/// selected by its [`AndroidHandlerLaunch`].
+///
+/// Without `socket`, the handler is started when this process crashes.
+/// Returns `false` for [`AndroidHandlerLaunch::Executable`], which is
+/// started like on Linux.
+#[cfg(target_os = "android")]
+fn start_android_handler(
+    request: &StartRequest<'_>,
+    socket: Option<BorrowedFd<'_>>,
+) -> Result<bool> {
+    let command = HandlerCommand::new(request)?;
+    let socket = socket.map_or(-1, |socket| socket.as_raw_fd());
+    let env_array = |env: &Option<Vec<String>>| {
+        env.as_ref()
+            .map(|env| {
+                CStringArray::new(
+                    env.iter().map(String::as_str),
+                    "Invalid environment variable",
+                )
+            })
+            .transpose()
+    };
+    let env_ptr =
+        |env: &Option<CStringArray>| env.as_ref().map_or(std::ptr::null_mut(), |e| e.as_ptr());
+    let env_len = |env: &Option<CStringArray>| env.as_ref().map_or(0, |e| e.len());
+
+    let (success, launched) = match &request.platform_options.android_handler_launch {
+        AndroidHandlerLaunch::Executable => return Ok(false),
+        AndroidHandlerLaunch::Java { class_name, env } => {
+            let class_name_c = CString::new(class_name.as_str()).map_err(|_| {
+                CrashpadError::InvalidConfiguration("Invalid class name".to_string())
+            })?;
+            let env = env_array(env)?;
+            let success = unsafe {
+                crashpad_client_start_java_handler(
+                    class_name_c.as_ptr(),
+                    env_ptr(&env),
+                    env_len(&env),
+                    command.database_path.as_ptr(),
+                    command.metrics_path.as_ptr(),
+                    command.url_ptr(),
+                    command.annotations.keys.as_ptr(),
+                    command.annotations.values.as_ptr(),
+                    command.annotations.len(),
+                    command.arguments_ptr(),
+                    command.arguments.len(),
+                    socket,
+                )
+            };
+            (success, class_name)
+        }
+        AndroidHandlerLaunch::Linker {
+            trampoline,
+            library,
+            env,
+        } => {
+            let invalid =
+                || CrashpadError::InvalidConfiguration("Invalid library name".to_string());
+            let trampoline_c = CString::new(trampoline.as_str()).map_err(|_| invalid())?;
+            let library_c = CString::new(library.as_str()).map_err(|_| invalid())?;
+            let env = env_array(env)?;
+            let success = unsafe {
+                crashpad_client_start_handler_with_linker(
+                    trampoline_c.as_ptr(),
+                    library_c.as_ptr(),
+                    cfg!(target_pointer_width = "64"),
+                    env_ptr(&env),
+                    env_len(&env),
+                    command.database_path.as_ptr(),
+                    command.metrics_path.as_ptr(),
+                    command.url_ptr(),
+                    command.annotations.keys.as_ptr(),
+                    command.annotations.values.as_ptr(),
+                    command.annotations.len(),
+                    command.arguments_ptr(),
+                    command.arguments.len(),
+                    socket,
+                )
+            };
+            (success, library)
+        }
+    };
+
+    if success {
+        Ok(true)
+    } else {
+        Err(CrashpadError::HandlerSpawnFailed {
+            path: launched.into(),
+            os_error: None,
+            trace: Vec::new(),
+        })
+    }
+}
+
+/// Linux has a single way of starting the handler.
+#[cfg(not(target_os = "android"))]
+fn start_android_handler(
+    _request: &StartRequest<'_>,
+    _socket: Option<BorrowedFd<'_>>,
+) -> Result<bool> {
+    Ok(false)
+}
+
+/// A connected socket pair, server end first, with credential passing
+/// enabled as the handler requires.
+pub(crate) fn handler_socket_pair() -> Result<(OwnedFd, OwnedFd)> {
+    let (mut server, mut client): (c_int, c_int) = (-1, -1);
+    match unsafe { crashpad_create_handler_socketpair(&mut server, &mut client) } {
+        // SAFETY: Both descriptors were just created and are owned by no one
+        0 => Ok(unsafe { (OwnedFd::from_raw_fd(server), OwnedFd::from_raw_fd(client)) }),
+        code => Err(std::io::Error::from_raw_os_error(code).into()),
+    }
+}
+
+/// Signals Crashpad should install handlers for.
+fn handled_signals(options: &PlatformOptions) -> Result<Vec<i32>> {
+    let selected = options.crash_signals.as_deref().unwrap_or(CRASH_SIGNALS);
+    if let Some(signal) = selected.iter().find(|s| !CRASH_SIGNALS.contains(s)) {
+        return Err(CrashpadError::InvalidConfiguration(format!(
+            "Signal {signal} is not a crash signal Crashpad can handle"
+        )));
+    }
+
+    Ok(selected
+        .iter()
+        .copied()
+        .filter(|signal| !options.excluded_signals.contains(signal))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handled_signals() {
+        let options = PlatformOptions::default();
+        assert_eq!(handled_signals(&options).unwrap(), CRASH_SIGNALS);
+
+        let options = PlatformOptions {
+            excluded_signals: vec![libc::SIGABRT],
+            ..Default::default()
+        };
+        assert!(!handled_signals(&options).unwrap().contains(&libc::SIGABRT));
+
+        let options = PlatformOptions {
+            crash_signals: Some(vec![libc::SIGSEGV, libc::SIGSYS]),
+            excluded_signals: vec![libc::SIGSEGV],
+            ..Default::default()
+        };
+        assert_eq!(handled_signals(&options).unwrap(), vec![libc::SIGSYS]);
+
+        let options = PlatformOptions {
+            crash_signals: Some(vec![libc::SIGUSR1]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            handled_signals(&options),
+            Err(CrashpadError::InvalidConfiguration(_))
+        ));
+    }
+}
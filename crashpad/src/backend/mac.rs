@@ -0,0 +1,41 @@
+//! macOS backend.
+
+use crashpad_rs_sys::*;
+
+use super::{start_external_handler, PlatformBackend, StartRequest};
+use crate::{CrashpadError, Result};
+
+/// Spawns `crashpad_handler` and registers the Mach exception port.
+#[derive(Debug, Default)]
+pub(crate) struct MacBackend;
+
+impl PlatformBackend for MacBackend {
+    fn start_handler(&self, client: crashpad_client_t, request: &StartRequest<'_>) -> Result<()> {
+        start_external_handler(client, request)?;
+
+        // StartHandler registers the default mask, so it can only be changed now
+        let mask = request.platform_options.mach_exception_mask;
+        if request.platform_options.customizes_mach_exception_mask() {
+            let success = unsafe { crashpad_client_set_mach_exception_mask(mask.bits()) };
+            if !success {
+                return Err(CrashpadError::InitializationFailed);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MacBackend {
+    pub(crate) fn use_system_default_handler(&self, client: crashpad_client_t) -> Result<()> {
+        let success = unsafe { crashpad_client_use_system_default_handler(client) };
+
+        if success {
+            Ok(())
+        } else {
+            Err(CrashpadError::IpcRegistrationFailed {
+                endpoint: "system crash reporter".to_string(),
+                os_error: None,
+            })
+        }
+    }
+}
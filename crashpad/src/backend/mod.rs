@@ -0,0 +1,466 @@
+//! Platform backends for the Crashpad client.
+//!
+//! Every platform-specific FFI call goes through a [`PlatformBackend`]
+//! implementation. The backend for the current target is selected at compile
+//! time as [`NativeBackend`], which keeps `client.rs` free of `cfg` blocks and
+//! lets platform-agnostic logic be tested against [`stub::StubBackend`].
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::path::Path;
+
+use crashpad_rs_sys::crashpad_client_t;
+
+use crate::coexistence::PlatformOptions;
+use crate::{CrashpadError, Result};
+
+#[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
+mod ios;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod linux;
+#[cfg(target_os = "macos")]
+mod mac;
+#[cfg(test)]
+pub(crate) mod stub;
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
+pub(crate) use ios::IosBackend as NativeBackend;
+#[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
+pub(crate) use ios::{pending_intermediate_dumps, UploadBehavior};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) use linux::handler_socket_pair;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) use linux::LinuxBackend as NativeBackend;
+#[cfg(target_os = "macos")]
+pub(crate) use mac::MacBackend as NativeBackend;
+#[cfg(target_os = "windows")]
+pub(crate) use windows::WindowsBackend as NativeBackend;
+
+/// Everything a backend needs to start the handler.
+pub(crate) struct StartRequest<'a> {
+    /// Handler executable (empty for in-process handlers)
+    pub handler_path: &'a Path,
+    pub database_path: &'a Path,
+    pub metrics_path: &'a Path,
+    pub url: Option<&'a str>,
+    pub annotations: &'a HashMap<String, String>,
+    pub handler_arguments: &'a [String],
+    /// File the handler's stderr is redirected to
+    #[cfg_attr(
+        any(target_os = "ios", target_os = "tvos", target_os = "watchos"),
+        allow(dead_code)
+    )]
+    pub handler_log: Option<&'a Path>,
+    #[cfg_attr(
+        any(target_os = "ios", target_os = "tvos", target_os = "watchos"),
+        allow(dead_code)
+    )]
+    pub handler_working_directory: Option<&'a Path>,
+    #[cfg_attr(
+        any(target_os = "ios", target_os = "tvos", target_os = "watchos"),
+        allow(dead_code)
+    )]
+    pub handler_umask: Option<u32>,
+    #[cfg_attr(
+        any(target_os = "ios", target_os = "tvos", target_os = "watchos"),
+        allow(dead_code)
+    )]
+    pub platform_options: &'a PlatformOptions,
+}
+
+impl StartRequest<'_> {
+    /// The handler's command line before Crashpad adds its connection
+    /// arguments: the handler arguments, then the database, metrics
+    /// directory, URL and annotations, like Crashpad's `BuildHandlerArgs`
+    pub(crate) fn command_line(&self) -> Vec<String> {
+        let mut command_line = vec![self.handler_path.display().to_string()];
+        command_line.extend(self.handler_arguments.iter().cloned());
+        if !self.database_path.as_os_str().is_empty() {
+            command_line.push(format!("--database={}", self.database_path.display()));
+        }
+        if !self.metrics_path.as_os_str().is_empty() {
+            command_line.push(format!("--metrics-dir={}", self.metrics_path.display()));
+        }
+        if let Some(url) = self.url.filter(|url| !url.is_empty()) {
+            command_line.push(format!("--url={url}"));
+        }
+        // Crashpad passes them as a std::map, sorted by key
+        let mut annotations: Vec<_> = self.annotations.iter().collect();
+        annotations.sort();
+        for (key, value) in annotations {
+            command_line.push(format!("--annotation={key}={value}"));
+        }
+        command_line
+    }
+}
+
+/// Platform-specific operations of the Crashpad client.
+pub(crate) trait PlatformBackend {
+    /// Start the handler for `client`.
+    fn start_handler(&self, client: crashpad_client_t, request: &StartRequest<'_>) -> Result<()>;
+
+    /// Change the upload setting of the database at `database_path`.
+    fn set_uploads_enabled(&self, database_path: &Path, enabled: bool) -> Result<()> {
+        crate::CrashReportDatabase::open(database_path)?.set_uploads_enabled(enabled)
+    }
+
+    /// Stop the handler started for `client`, see
+    /// [`CrashpadClient::stop_handler`](crate::CrashpadClient::stop_handler).
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn stop_handler(&self, client: crashpad_client_t) -> Result<()> {
+        match unsafe { crashpad_rs_sys::crashpad_client_stop_handler(client) } {
+            0 => Ok(()),
+            code => Err(std::io::Error::from_raw_os_error(code).into()),
+        }
+    }
+
+    /// Whether the handler started for `client` still runs.
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn handler_status(&self, client: crashpad_client_t) -> crate::HandlerStatus {
+        match unsafe { crashpad_rs_sys::crashpad_client_handler_status(client) } as u32 {
+            crashpad_rs_sys::CRASHPAD_HANDLER_STATUS_RUNNING => crate::HandlerStatus::Running,
+            crashpad_rs_sys::CRASHPAD_HANDLER_STATUS_EXITED => crate::HandlerStatus::Exited,
+            _ => crate::HandlerStatus::Unknown,
+        }
+    }
+
+    /// Process ID of the handler started for `client`, if known.
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn handler_pid(&self, client: crashpad_client_t) -> Option<u32> {
+        let pid = unsafe { crashpad_rs_sys::crashpad_client_handler_pid(client) };
+        (pid > 0).then_some(pid as u32)
+    }
+
+    /// Capture a dump of the calling thread without crashing.
+    fn dump_without_crash(&self) {
+        unsafe {
+            crashpad_rs_sys::crashpad_dump_without_crash();
+        }
+    }
+
+    /// Capture a dump with the context of another thread without crashing.
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    fn dump_without_crash_of_thread(&self, thread_id: u64) -> Result<()> {
+        match unsafe { crashpad_rs_sys::crashpad_dump_without_crash_of_thread(thread_id) } {
+            0 => Ok(()),
+            code => Err(std::io::Error::from_raw_os_error(code).into()),
+        }
+    }
+}
+
+/// Start an external handler process (all platforms except iOS).
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+fn start_external_handler(client: crashpad_client_t, request: &StartRequest<'_>) -> Result<()> {
+    spawn_handler(request, |command| unsafe {
+        crashpad_rs_sys::crashpad_client_start_handler(
+            client,
+            command.handler_path.as_ptr(),
+            command.database_path.as_ptr(),
+            command.metrics_path.as_ptr(),
+            command.url_ptr(),
+            command.annotations.keys.as_ptr(),
+            command.annotations.values.as_ptr(),
+            command.annotations.len(),
+            command.arguments_ptr(),
+            command.arguments.len(),
+        )
+    })
+}
+
+/// The arguments of a [`StartRequest`] as C strings.
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+struct HandlerCommand {
+    handler_path: CString,
+    database_path: CString,
+    metrics_path: CString,
+    url: Option<CString>,
+    annotations: AnnotationArrays,
+    arguments: CStringArray,
+}
+
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+impl HandlerCommand {
+    fn new(request: &StartRequest<'_>) -> Result<Self> {
+        Ok(Self {
+            handler_path: path_to_cstring(request.handler_path)?,
+            database_path: path_to_cstring(request.database_path)?,
+            metrics_path: path_to_cstring(request.metrics_path)?,
+            url: url_to_cstring(request.url)?,
+            annotations: AnnotationArrays::new(request.annotations)?,
+            arguments: CStringArray::new(
+                request.handler_arguments.iter().map(String::as_str),
+                "Handler argument contains null byte",
+            )?,
+        })
+    }
+
+    fn url_ptr(&self) -> *const c_char {
+        self.url.as_ref().map_or(std::ptr::null(), |u| u.as_ptr())
+    }
+
+    fn arguments_ptr(&self) -> *mut *const c_char {
+        if self.arguments.is_empty() {
+            std::ptr::null_mut()
+        } else {
+            self.arguments.as_ptr()
+        }
+    }
+}
+
+/// Spawn the handler of `request` with `start`, which calls one of the
+/// wrapper's start functions and returns its result.
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+fn spawn_handler(
+    request: &StartRequest<'_>,
+    start: impl FnOnce(&HandlerCommand) -> bool,
+) -> Result<()> {
+    let command = HandlerCommand::new(request)?;
+    let handler_log_c = request.handler_log.map(path_to_cstring).transpose()?;
+    let working_directory_c = request
+        .handler_working_directory
+        .map(path_to_cstring)
+        .transpose()?;
+
+    crate::failpoints::fail_point!(crate::failpoints::HANDLER_START, |_| {
+        Err(CrashpadError::HandlerSpawnFailed {
+            path: request.handler_path.to_path_buf(),
+            os_error: None,
+            trace: Vec::new(),
+        })
+    });
+    unsafe {
+        // Always set, so a restart without a log stops redirecting
+        crashpad_rs_sys::crashpad_client_set_handler_log(
+            handler_log_c
+                .as_ref()
+                .map_or(std::ptr::null(), |p| p.as_ptr()),
+        );
+        crashpad_rs_sys::crashpad_client_set_handler_working_directory(
+            working_directory_c
+                .as_ref()
+                .map_or(std::ptr::null(), |p| p.as_ptr()),
+        );
+        crashpad_rs_sys::crashpad_client_set_handler_umask(
+            request
+                .handler_umask
+                .map_or(-1, |mask| mask as std::os::raw::c_int),
+        );
+    }
+
+    if start(&command) {
+        Ok(())
+    } else {
+        let mut os_error = 0;
+        let reason = unsafe { crashpad_rs_sys::crashpad_client_last_start_error(&mut os_error) };
+        Err(start_error(reason, os_error, request.handler_path))
+    }
+}
+
+/// Convert a `CRASHPAD_START_ERROR_*` reason into an error.
+#[cfg_attr(
+    any(target_os = "ios", target_os = "tvos", target_os = "watchos"),
+    allow(dead_code)
+)]
+fn start_error(reason: c_int, os_error: c_int, handler_path: &Path) -> CrashpadError {
+    let path = handler_path.to_path_buf();
+    let os_error = (os_error != 0).then_some(os_error);
+    match reason as u32 {
+        crashpad_rs_sys::CRASHPAD_START_ERROR_HANDLER_NOT_FOUND => CrashpadError::HandlerNotFound {
+            path,
+            trace: Vec::new(),
+        },
+        crashpad_rs_sys::CRASHPAD_START_ERROR_SETUP_FAILED => match os_error {
+            Some(code) => std::io::Error::from_raw_os_error(code).into(),
+            None => std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "failed to set up the handler's log or working directory",
+            )
+            .into(),
+        },
+        _ => CrashpadError::HandlerSpawnFailed {
+            path,
+            os_error,
+            trace: Vec::new(),
+        },
+    }
+}
+
+/// Register with an already running handler's Mach service (macOS/iOS).
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub(crate) fn set_handler_mach_service(
+    client: crashpad_client_t,
+    service_name: &str,
+) -> Result<()> {
+    let service_name_c = CString::new(service_name)
+        .map_err(|_| CrashpadError::InvalidConfiguration("Invalid service name".to_string()))?;
+
+    let success = unsafe {
+        crashpad_rs_sys::crashpad_client_set_handler_mach_service(client, service_name_c.as_ptr())
+    };
+
+    if success {
+        Ok(())
+    } else {
+        Err(CrashpadError::IpcRegistrationFailed {
+            endpoint: service_name.to_string(),
+            os_error: None,
+        })
+    }
+}
+
+/// Owned C strings together with the pointer array passed across FFI.
+///
+/// The pointers stay valid as long as this value is alive, since moving a
+/// `Vec<CString>` doesn't move the string buffers.
+pub(crate) struct CStringArray {
+    _strings: Vec<CString>,
+    ptrs: Vec<*const c_char>,
+}
+
+impl CStringArray {
+    pub(crate) fn new<'a, I>(items: I, error: &str) -> Result<Self>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let strings = items
+            .into_iter()
+            .map(|s| {
+                CString::new(s).map_err(|_| CrashpadError::InvalidConfiguration(error.to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let ptrs = strings.iter().map(|s| s.as_ptr()).collect();
+        Ok(Self {
+            _strings: strings,
+            ptrs,
+        })
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut *const c_char {
+        self.ptrs.as_ptr() as *mut *const c_char
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.ptrs.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.ptrs.is_empty()
+    }
+}
+
+/// Annotation keys and values as parallel C string arrays.
+pub(crate) struct AnnotationArrays {
+    pub keys: CStringArray,
+    pub values: CStringArray,
+}
+
+impl AnnotationArrays {
+    pub(crate) fn new(annotations: &HashMap<String, String>) -> Result<Self> {
+        Ok(Self {
+            keys: CStringArray::new(
+                annotations.keys().map(String::as_str),
+                "Invalid annotation key",
+            )?,
+            values: CStringArray::new(
+                annotations.values().map(String::as_str),
+                "Invalid annotation value",
+            )?,
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.keys.len()
+    }
+}
+
+pub(crate) fn path_to_cstring(path: &Path) -> Result<CString> {
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| CrashpadError::InvalidConfiguration("Invalid path".to_string()))?;
+    CString::new(path_str)
+        .map_err(|_| CrashpadError::InvalidConfiguration("Path contains null byte".to_string()))
+}
+
+pub(crate) fn url_to_cstring(url: Option<&str>) -> Result<Option<CString>> {
+    url.map(|u| {
+        CString::new(u).map_err(|_| CrashpadError::InvalidConfiguration("Invalid URL".to_string()))
+    })
+    .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line() {
+        let annotations = HashMap::from([
+            ("version".to_string(), "1.0".to_string()),
+            ("channel".to_string(), "beta".to_string()),
+        ]);
+        let arguments = ["--no-rate-limit".to_string()];
+        let request = StartRequest {
+            handler_path: Path::new("/opt/app/crashpad_handler"),
+            database_path: Path::new("/var/crash"),
+            metrics_path: Path::new(""),
+            url: Some("https://crashes.example.com"),
+            annotations: &annotations,
+            handler_arguments: &arguments,
+            handler_log: None,
+            handler_working_directory: None,
+            handler_umask: None,
+            platform_options: &PlatformOptions::default(),
+        };
+        assert_eq!(
+            request.command_line(),
+            vec![
+                "/opt/app/crashpad_handler",
+                "--no-rate-limit",
+                "--database=/var/crash",
+                "--url=https://crashes.example.com",
+                "--annotation=channel=beta",
+                "--annotation=version=1.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_start_error() {
+        let path = Path::new("/opt/app/crashpad_handler");
+
+        assert!(matches!(
+            start_error(crashpad_rs_sys::CRASHPAD_START_ERROR_HANDLER_NOT_FOUND as c_int, 2, path),
+            CrashpadError::HandlerNotFound { path: p, .. } if p == path
+        ));
+        assert!(matches!(
+            start_error(crashpad_rs_sys::CRASHPAD_START_ERROR_SETUP_FAILED as c_int, 13, path),
+            CrashpadError::IoError(e) if e.raw_os_error() == Some(13)
+        ));
+
+        let error = start_error(
+            crashpad_rs_sys::CRASHPAD_START_ERROR_SPAWN_FAILED as c_int,
+            0,
+            path,
+        );
+        assert!(matches!(
+            error,
+            CrashpadError::HandlerSpawnFailed { os_error: None, .. }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Failed to spawn handler /opt/app/crashpad_handler"
+        );
+
+        let error = start_error(
+            crashpad_rs_sys::CRASHPAD_START_ERROR_SPAWN_FAILED as c_int,
+            2,
+            path,
+        );
+        assert!(error
+            .to_string()
+            .starts_with("Failed to spawn handler /opt/app/crashpad_handler: "));
+    }
+}
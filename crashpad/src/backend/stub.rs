@@ -0,0 +1,108 @@
+//! Fake backend for testing platform-agnostic client logic.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crashpad_rs_sys::crashpad_client_t;
+
+use super::{PlatformBackend, StartRequest};
+use crate::coexistence::PlatformOptions;
+use crate::{CrashpadError, Result};
+
+/// Owned copy of a [`StartRequest`] seen by [`StubBackend`].
+#[derive(Debug, Clone)]
+pub(crate) struct RecordedStart {
+    pub handler_path: PathBuf,
+    pub database_path: PathBuf,
+    pub metrics_path: PathBuf,
+    pub url: Option<String>,
+    pub annotations: HashMap<String, String>,
+    pub handler_arguments: Vec<String>,
+    pub handler_log: Option<PathBuf>,
+    pub handler_working_directory: Option<PathBuf>,
+    pub handler_umask: Option<u32>,
+    pub platform_options: PlatformOptions,
+}
+
+/// Records every call instead of touching Crashpad.
+#[derive(Debug, Default)]
+pub(crate) struct StubBackend {
+    pub starts: RefCell<Vec<RecordedStart>>,
+    pub dumps: RefCell<usize>,
+    /// Number of `stop_handler` calls
+    pub stops: RefCell<usize>,
+    /// Status returned by `handler_status`, `Running` if unset
+    pub handler_status: RefCell<Option<crate::HandlerStatus>>,
+    /// Process ID returned by `handler_pid`
+    pub handler_pid: Option<u32>,
+    /// Thread of every `dump_without_crash_of_thread` call
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    pub thread_dumps: RefCell<Vec<u64>>,
+    /// Database path and value of every `set_uploads_enabled` call
+    pub uploads_enabled: RefCell<Vec<(PathBuf, bool)>>,
+    /// Make `start_handler` fail
+    pub fail_start: bool,
+}
+
+impl PlatformBackend for StubBackend {
+    fn start_handler(&self, _client: crashpad_client_t, request: &StartRequest<'_>) -> Result<()> {
+        self.starts.borrow_mut().push(RecordedStart {
+            handler_path: request.handler_path.to_path_buf(),
+            database_path: request.database_path.to_path_buf(),
+            metrics_path: request.metrics_path.to_path_buf(),
+            url: request.url.map(str::to_string),
+            annotations: request.annotations.clone(),
+            handler_arguments: request.handler_arguments.to_vec(),
+            handler_log: request.handler_log.map(PathBuf::from),
+            handler_working_directory: request.handler_working_directory.map(PathBuf::from),
+            handler_umask: request.handler_umask,
+            platform_options: request.platform_options.clone(),
+        });
+
+        if self.fail_start {
+            Err(CrashpadError::HandlerSpawnFailed {
+                path: request.handler_path.to_path_buf(),
+                os_error: None,
+                trace: Vec::new(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn set_uploads_enabled(&self, database_path: &Path, enabled: bool) -> Result<()> {
+        self.uploads_enabled
+            .borrow_mut()
+            .push((database_path.to_path_buf(), enabled));
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn stop_handler(&self, _client: crashpad_client_t) -> Result<()> {
+        *self.stops.borrow_mut() += 1;
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn handler_status(&self, _client: crashpad_client_t) -> crate::HandlerStatus {
+        self.handler_status
+            .borrow()
+            .unwrap_or(crate::HandlerStatus::Running)
+    }
+
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn handler_pid(&self, _client: crashpad_client_t) -> Option<u32> {
+        self.handler_pid
+    }
+
+    fn dump_without_crash(&self) {
+        *self.dumps.borrow_mut() += 1;
+    }
+
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    fn dump_without_crash_of_thread(&self, thread_id: u64) -> Result<()> {
+        self.thread_dumps.borrow_mut().push(thread_id);
+        Ok(())
+    }
+}
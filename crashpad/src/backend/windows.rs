@@ -0,0 +1,109 @@
+//! Windows backend.
+
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+use std::time::Duration;
+
+use crashpad_rs_sys::*;
+
+use super::{path_to_cstring, start_external_handler, PlatformBackend, StartRequest};
+use crate::coexistence::PlatformOptions;
+use crate::{CrashpadError, ExceptionFilterMode, HandlerLifetime, Result};
+
+/// Spawns `crashpad_handler.exe` and registers the unhandled exception filter.
+#[derive(Debug, Default)]
+pub(crate) struct WindowsBackend;
+
+impl PlatformBackend for WindowsBackend {
+    fn start_handler(&self, client: crashpad_client_t, request: &StartRequest<'_>) -> Result<()> {
+        start_external_handler(client, request)?;
+
+        // StartHandler installs Crashpad's filter, so it can only be adjusted now
+        if request.platform_options.customizes_exception_filter() {
+            set_exception_filter(request.platform_options)?;
+        }
+
+        let grace = match request.platform_options.handler_lifetime {
+            HandlerLifetime::Independent => None,
+            HandlerLifetime::KillWithApp => Some(Duration::ZERO),
+            HandlerLifetime::GracePeriod(grace) => Some(grace),
+        };
+        if let Some(grace) = grace {
+            set_handler_kill_on_exit(client, request.database_path, grace)?;
+        }
+        Ok(())
+    }
+}
+
+fn set_handler_kill_on_exit(
+    client: crashpad_client_t,
+    database_path: &Path,
+    grace: Duration,
+) -> Result<()> {
+    let database_path_c = path_to_cstring(database_path)?;
+    let grace_ms = grace.as_millis().min(u32::MAX as u128) as u32;
+
+    match unsafe {
+        crashpad_client_set_handler_kill_on_exit(client, database_path_c.as_ptr(), grace_ms)
+    } {
+        0 => Ok(()),
+        code => Err(std::io::Error::from_raw_os_error(code).into()),
+    }
+}
+
+fn set_exception_filter(options: &PlatformOptions) -> Result<()> {
+    let mode = match options.exception_filter {
+        ExceptionFilterMode::UnhandledFilter => CRASHPAD_EXCEPTION_FILTER_UNHANDLED,
+        ExceptionFilterMode::VectoredFirst => CRASHPAD_EXCEPTION_FILTER_VECTORED_FIRST,
+        ExceptionFilterMode::VectoredLast => CRASHPAD_EXCEPTION_FILTER_VECTORED_LAST,
+    };
+    let codes = &options.excluded_exception_codes;
+
+    let success =
+        unsafe { crashpad_client_set_exception_filter(mode as _, codes.as_ptr(), codes.len()) };
+
+    if success {
+        Ok(())
+    } else {
+        Err(CrashpadError::InitializationFailed)
+    }
+}
+
+impl WindowsBackend {
+    /// Name of the pipe the handler of `client` listens on.
+    pub(crate) fn handler_ipc_pipe(&self, client: crashpad_client_t) -> Result<String> {
+        let len = unsafe { crashpad_client_get_handler_ipc_pipe(client, std::ptr::null_mut(), 0) };
+        if len == 0 {
+            return Err(CrashpadError::InvalidConfiguration(
+                "No handler was started or registered by this client".to_string(),
+            ));
+        }
+
+        let mut buffer = vec![0u16; len + 1];
+        let len = unsafe {
+            crashpad_client_get_handler_ipc_pipe(client, buffer.as_mut_ptr(), buffer.len())
+        };
+        buffer.truncate(len);
+        Ok(String::from_utf16_lossy(&buffer))
+    }
+
+    pub(crate) fn set_handler_ipc_pipe(
+        &self,
+        client: crashpad_client_t,
+        ipc_pipe: &str,
+    ) -> Result<()> {
+        let wide: Vec<u16> = OsStr::new(ipc_pipe).encode_wide().chain(Some(0)).collect();
+
+        let success = unsafe { crashpad_client_set_handler_ipc_pipe(client, wide.as_ptr()) };
+
+        if success {
+            Ok(())
+        } else {
+            Err(CrashpadError::IpcRegistrationFailed {
+                endpoint: ipc_pipe.to_string(),
+                os_error: None,
+            })
+        }
+    }
+}
@@ -0,0 +1,530 @@
+//! Crashpad smoke test
+//!
+//! Command-line tool for checking that Crashpad works on a machine: starts the
+//! handler, optionally captures a dump or crashes, and reports what happened.
+//! With `--format json` the report is a single JSON object on stdout, for CI
+//! health checks and on-site diagnostics.
+//!
+//! Install with `cargo install crashpad-rs --bin crashpad-smoketest`.
+
+// Android standalone executables need special handling
+// Due to -nodefaultlibs flag, we need to provide our own pthread_atfork
+#[cfg(target_os = "android")]
+#[no_mangle]
+pub extern "C" fn pthread_atfork(
+    _prepare: Option<extern "C" fn()>,
+    _parent: Option<extern "C" fn()>,
+    _child: Option<extern "C" fn()>,
+) -> i32 {
+    // Dummy implementation for testing
+    // In a real app, this would be provided by the Android runtime
+    0
+}
+
+use crashpad_rs::{CrashpadClient, CrashpadConfig};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+
+// Exit codes for different scenarios
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_INIT_FAILED: i32 = 1;
+const EXIT_HANDLER_FAILED: i32 = 2;
+const EXIT_TEST_FAILED: i32 = 3;
+const EXIT_USAGE: i32 = 64;
+
+/// How long to wait for the handler to write a requested dump
+const DUMP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Status,
+    Dump,
+    Crash,
+    Test,
+}
+
+impl Command {
+    fn name(self) -> &'static str {
+        match self {
+            Command::Status => "status",
+            Command::Dump => "dump",
+            Command::Crash => "crash",
+            Command::Test => "test",
+        }
+    }
+}
+
+struct Options {
+    command: Command,
+    format: Format,
+    handler: Option<PathBuf>,
+    database: Option<PathBuf>,
+}
+
+/// Everything the smoke test found out, printed at the end.
+#[derive(Default)]
+struct Report {
+    command: &'static str,
+    handler_path: Option<PathBuf>,
+    database_path: PathBuf,
+    handler_started: bool,
+    start_ms: Option<f64>,
+    dump_created: Option<bool>,
+    dump_path: Option<PathBuf>,
+    dump_ms: Option<f64>,
+    error: Option<String>,
+}
+
+impl Report {
+    fn to_json(&self) -> String {
+        let path = |p: &Option<PathBuf>| {
+            p.as_ref().map_or("null".to_string(), |p| {
+                json_string(&p.display().to_string())
+            })
+        };
+        let number = |n: Option<f64>| n.map_or("null".to_string(), |n| format!("{n:.1}"));
+
+        let mut json = String::from("{");
+        let _ = write!(json, "\"command\":{}", json_string(self.command));
+        let _ = write!(json, ",\"platform\":{}", json_string(&platform()));
+        let _ = write!(json, ",\"handler_path\":{}", path(&self.handler_path));
+        let _ = write!(
+            json,
+            ",\"database_path\":{}",
+            json_string(&self.database_path.display().to_string())
+        );
+        let _ = write!(json, ",\"handler_started\":{}", self.handler_started);
+        let _ = write!(
+            json,
+            ",\"dump_created\":{}",
+            self.dump_created
+                .map_or("null".to_string(), |c| c.to_string())
+        );
+        let _ = write!(json, ",\"dump_path\":{}", path(&self.dump_path));
+        let _ = write!(
+            json,
+            ",\"timing_ms\":{{\"start_handler\":{},\"dump\":{}}}",
+            number(self.start_ms),
+            number(self.dump_ms)
+        );
+        let _ = write!(
+            json,
+            ",\"error\":{}",
+            self.error
+                .as_deref()
+                .map_or("null".to_string(), json_string)
+        );
+        json.push('}');
+        json
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn platform() -> String {
+    format!("{}-{}", env::consts::OS, env::consts::ARCH)
+}
+
+fn print_usage(program: &str) {
+    println!("Usage: {program} [COMMAND] [OPTIONS]");
+    println!("\nCommands:");
+    println!("  status   Start the handler and report (default)");
+    println!("  dump     Capture a dump without crashing");
+    println!("  crash    Trigger a crash to test handler");
+    println!("  test     Run automated tests with TAP output");
+    println!("\nOptions:");
+    println!("  --format <text|json>   Output format (default: text)");
+    println!("  --handler <PATH>       Path to crashpad_handler");
+    println!("  --database <PATH>      Crash database directory");
+    println!("  -h, --help             Show this help message");
+    println!("\nEnvironment variables:");
+    println!("  CRASHPAD_HANDLER   Path to crashpad_handler executable");
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        command: Command::Status,
+        format: Format::Text,
+        handler: None,
+        database: None,
+    };
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("{name} requires a value"))
+        };
+        match arg.as_str() {
+            "status" => options.command = Command::Status,
+            "dump" => options.command = Command::Dump,
+            "crash" => options.command = Command::Crash,
+            "test" => options.command = Command::Test,
+            "--format" => {
+                options.format = match value("--format")?.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    other => return Err(format!("Unknown format '{other}'")),
+                }
+            }
+            "--handler" => options.handler = Some(value("--handler")?.into()),
+            "--database" => options.database = Some(value("--database")?.into()),
+            "-h" | "--help" => {
+                print_usage(&args[0]);
+                process::exit(EXIT_SUCCESS);
+            }
+            other => return Err(format!("Unknown argument '{other}'")),
+        }
+    }
+    Ok(options)
+}
+
+fn handler_name() -> &'static str {
+    if cfg!(target_os = "android") {
+        // On Android, handler needs lib prefix and .so extension for APK
+        "libcrashpad_handler.so"
+    } else if cfg!(windows) {
+        "crashpad_handler.exe"
+    } else {
+        "crashpad_handler"
+    }
+}
+
+/// Find the handler: explicit path, `CRASHPAD_HANDLER`, next to this binary
+/// (or its parent when run from `target/<profile>/examples`), current directory.
+fn find_handler(explicit: Option<PathBuf>, exe_dir: &Path) -> Option<PathBuf> {
+    if explicit.is_some() {
+        return explicit;
+    }
+
+    let mut candidates = Vec::new();
+    if let Some(path) = env::var_os("CRASHPAD_HANDLER") {
+        candidates.push(PathBuf::from(path));
+    }
+    candidates.push(exe_dir.join(handler_name()));
+    if let Some(parent) = exe_dir.parent() {
+        candidates.push(parent.join(handler_name()));
+    }
+    candidates.push(PathBuf::from(handler_name()));
+
+    candidates.into_iter().find(|p| p.exists())
+}
+
+/// All minidump files currently in the database.
+fn dump_files(database: &Path) -> HashSet<PathBuf> {
+    let mut dumps = HashSet::new();
+    let mut dirs = vec![database.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "dmp") {
+                dumps.insert(path);
+            }
+        }
+    }
+    dumps
+}
+
+/// Wait for a dump that wasn't in `before` to appear.
+fn wait_for_new_dump(database: &Path, before: &HashSet<PathBuf>) -> Option<PathBuf> {
+    let deadline = Instant::now() + DUMP_TIMEOUT;
+    loop {
+        if let Some(path) = dump_files(database)
+            .into_iter()
+            .find(|p| !before.contains(p))
+        {
+            return Some(path);
+        }
+        if Instant::now() >= deadline {
+            return None;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+fn crash() -> ! {
+    // Windows needs a different approach to trigger SEH exceptions
+    // that Crashpad can capture
+    #[cfg(windows)]
+    // SAFETY: This intentionally triggers a crash for testing purposes.
+    // The inline assembly causes an access violation that will be caught
+    // by Windows SEH and handled by Crashpad.
+    unsafe {
+        // Use inline assembly to bypass Rust's UB checks
+        // This directly dereferences an invalid address, causing an access violation
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+        {
+            std::arch::asm!("mov dword ptr [0x7], 42", options(nostack));
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            let invalid_ptr = 7 as *mut i32;
+            core::ptr::write_volatile(invalid_ptr, 42);
+        }
+    }
+
+    #[cfg(not(windows))]
+    // SAFETY: This intentionally triggers a crash for testing purposes.
+    // The null pointer dereference will cause a segmentation fault
+    // that will be caught by the signal handler and processed by Crashpad.
+    unsafe {
+        let null_ptr: *const i32 = std::ptr::null();
+        println!(
+            "About to crash with value: {}",
+            std::ptr::read_volatile(null_ptr)
+        );
+    }
+
+    process::abort()
+}
+
+fn finish(report: &Report, format: Format, code: i32) -> ! {
+    if format == Format::Json {
+        println!("{}", report.to_json());
+    }
+    process::exit(code)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{e}");
+            print_usage(&args[0]);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    let text = options.format == Format::Text;
+
+    if text {
+        println!("Crashpad Smoke Test");
+        println!("===================\n");
+    }
+
+    let exe_dir = env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("."));
+    let database_path = options
+        .database
+        .clone()
+        .unwrap_or_else(|| exe_dir.join("crashpad_database"));
+
+    let mut report = Report {
+        command: options.command.name(),
+        handler_path: find_handler(options.handler.clone(), &exe_dir),
+        database_path: database_path.clone(),
+        ..Default::default()
+    };
+
+    // Create a new Crashpad client
+    let client = match CrashpadClient::new() {
+        Ok(c) => {
+            if text {
+                println!("✓ Created Crashpad client");
+            }
+            c
+        }
+        Err(e) => {
+            if text {
+                eprintln!("✗ Failed to create Crashpad client: {e}");
+            }
+            report.error = Some(e.to_string());
+            finish(&report, options.format, EXIT_INIT_FAILED);
+        }
+    };
+
+    let mut builder = CrashpadConfig::builder()
+        .database_path(&database_path)
+        .metrics_path(exe_dir.join("crashpad_metrics"));
+    if let Some(handler) = &report.handler_path {
+        builder = builder.handler_path(handler);
+    }
+    let config = builder.build();
+
+    if text {
+        match &report.handler_path {
+            Some(handler) => println!("Using handler at: {}", handler.display()),
+            None => println!("No handler found, using the default search"),
+        }
+    }
+
+    // Create annotations (metadata that will be included with crash reports)
+    let mut annotations = HashMap::new();
+    annotations.insert("version".to_string(), env!("CARGO_PKG_VERSION").to_string());
+    annotations.insert("tool".to_string(), "crashpad-smoketest".to_string());
+    annotations.insert("platform".to_string(), platform());
+    annotations.insert("test_type".to_string(), options.command.name().to_string());
+
+    // Start the handler
+    if text {
+        println!("Attempting to start handler...");
+    }
+    let start = Instant::now();
+    let result = client.start_with_config(&config, &annotations);
+    report.start_ms = Some(elapsed_ms(start));
+    match result {
+        Ok(()) => {
+            report.handler_started = true;
+            if text {
+                println!("✓ Handler started successfully");
+                println!("Crashes will be saved to: {}", database_path.display());
+            }
+        }
+        Err(e) => {
+            if text {
+                eprintln!("✗ Failed to start handler: {e}");
+                eprintln!("\nTips:");
+                eprintln!("- Pass --handler or set CRASHPAD_HANDLER to the handler path");
+                eprintln!("- Or put crashpad_handler in the same directory as this executable");
+            }
+            report.error = Some(e.to_string());
+            finish(&report, options.format, EXIT_HANDLER_FAILED);
+        }
+    }
+
+    match options.command {
+        Command::Status => {
+            if text {
+                println!("\nCrashpad is monitoring this process for crashes.");
+                println!("Run with --help to see the available commands.");
+            }
+            finish(&report, options.format, EXIT_SUCCESS);
+        }
+        Command::Dump => {
+            if text {
+                println!("\nCapturing dump without crash...");
+            }
+            let before = dump_files(&database_path);
+            let start = Instant::now();
+            client.dump_without_crash();
+            report.dump_path = wait_for_new_dump(&database_path, &before);
+            report.dump_ms = Some(elapsed_ms(start));
+            report.dump_created = Some(report.dump_path.is_some());
+
+            match &report.dump_path {
+                Some(path) => {
+                    if text {
+                        println!("✓ Dump captured successfully without crashing the process");
+                        println!("  {}", path.display());
+                    }
+                    finish(&report, options.format, EXIT_SUCCESS);
+                }
+                None => {
+                    if text {
+                        eprintln!("✗ No dump appeared within {DUMP_TIMEOUT:?}");
+                    }
+                    report.error = Some("No dump written".to_string());
+                    finish(&report, options.format, EXIT_TEST_FAILED);
+                }
+            }
+        }
+        Command::Crash => {
+            // Report before crashing, there is no chance afterwards
+            if options.format == Format::Json {
+                println!("{}", report.to_json());
+            } else {
+                println!("\nTriggering crash now...");
+            }
+            crash();
+        }
+        Command::Test => {
+            // Automated test mode with TAP output
+            println!("\n# TAP version 13");
+            println!("1..3");
+            println!("ok 1 - Crashpad client created");
+            println!("ok 2 - Handler started successfully");
+
+            if database_path.exists() || std::fs::create_dir_all(&database_path).is_ok() {
+                println!("ok 3 - Database directory accessible");
+                println!("\n# All tests passed");
+                process::exit(EXIT_SUCCESS);
+            } else {
+                println!("not ok 3 - Database directory not accessible");
+                process::exit(EXIT_TEST_FAILED);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_string_escaping() {
+        assert_eq!(json_string("C:\\crash \"db\"\n"), r#""C:\\crash \"db\"\n""#);
+        assert_eq!(json_string("\u{1}"), r#""\u0001""#);
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args: Vec<String> = ["smoketest", "dump", "--format", "json", "--database", "db"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let options = parse_args(&args).unwrap();
+        assert_eq!(options.command, Command::Dump);
+        assert_eq!(options.format, Format::Json);
+        assert_eq!(options.database, Some(PathBuf::from("db")));
+
+        let args = vec!["smoketest".to_string(), "--format".to_string()];
+        assert!(parse_args(&args).is_err());
+    }
+
+    #[test]
+    fn test_report_json() {
+        let report = Report {
+            command: "dump",
+            database_path: PathBuf::from("db"),
+            handler_started: true,
+            dump_created: Some(false),
+            ..Default::default()
+        };
+        let json = report.to_json();
+        assert!(json.starts_with("{\"command\":\"dump\""));
+        assert!(json.contains("\"handler_path\":null"));
+        assert!(json.contains("\"handler_started\":true"));
+        assert!(json.contains("\"dump_created\":false"));
+        assert!(json.contains("\"timing_ms\":{\"start_handler\":null,\"dump\":null}"));
+    }
+}
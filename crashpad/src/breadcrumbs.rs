@@ -0,0 +1,352 @@
+//! The last events of the application, included in every dump.
+//!
+//! [`breadcrumb!`](crate::breadcrumb) records a timestamped message in a
+//! ring buffer that is registered as extra dump memory, so a crash report
+//! shows what the application did right before it crashed.
+//! [`Minidump::breadcrumbs`](crate::minidump::Minidump::breadcrumbs) reads
+//! them back.
+//!
+//! ```rust,no_run
+//! // Optional, before the first breadcrumb: room for 256 events
+//! crashpad_rs::breadcrumbs::init(256, 200)?;
+//!
+//! let path = "/etc/app.toml";
+//! crashpad_rs::breadcrumb!("opened file {}", path);
+//! # Ok::<(), crashpad_rs::CrashpadError>(())
+//! ```
+//!
+//! Messages pass the [redaction rules](crate::CrashpadConfigBuilder::redaction)
+//! of the started client as values of the key [`REDACTION_KEY`], so value
+//! patterns scrub secrets from them, and a key glob matching `breadcrumb`
+//! drops their text entirely. Breadcrumbs recorded before the client
+//! started aren't redacted.
+//!
+//! Recording never blocks: writers claim a slot with an atomic counter, and
+//! a slot being written when the dump is taken is left out. The buffer is an
+//! array of little-endian `u64`: a header of the magic [`MAGIC`], the
+//! capacity, the maximum message length and the number of events recorded,
+//! followed by `capacity` slots of a sequence number (the
+//! event's index plus one, 0 while it is written), a time in milliseconds
+//! since the Unix epoch, a length and the message padded to whole words.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crashpad_rs_sys::crashpad_add_extra_memory_range;
+
+use crate::history::truncate;
+use crate::{CrashpadError, Result};
+
+/// First word of the buffer, `CPRSBRC1`
+pub const MAGIC: u64 = u64::from_le_bytes(*b"CPRSBRC1");
+
+/// Key the redaction rules see breadcrumb messages under
+pub const REDACTION_KEY: &str = "breadcrumb";
+
+/// Events kept if [`init`] isn't called
+pub const DEFAULT_CAPACITY: usize = 64;
+
+/// Longest message kept if [`init`] isn't called, in bytes
+pub const DEFAULT_MAX_MESSAGE_LEN: usize = 128;
+
+/// Largest buffer [`init`] allocates, in bytes
+const MAX_BUFFER_SIZE: usize = 1024 * 1024;
+
+const HEADER_WORDS: usize = 4;
+
+/// Sequence number, time and length in front of each message
+const SLOT_HEADER_WORDS: usize = 3;
+
+static RING: OnceLock<Ring> = OnceLock::new();
+
+/// An event recorded with [`breadcrumb!`](crate::breadcrumb).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breadcrumb {
+    pub time: SystemTime,
+    pub message: String,
+}
+
+/// Record an event in the breadcrumb ring buffer.
+///
+/// Takes [`format!`] arguments. Messages longer than the maximum length are
+/// truncated at a character boundary; once the buffer is full, each event
+/// replaces the oldest one.
+///
+/// # Example
+/// ```rust,no_run
+/// let path = "/etc/app.toml";
+/// crashpad_rs::breadcrumb!("opened file {}", path);
+/// ```
+#[macro_export]
+macro_rules! breadcrumb {
+    ($($arg:tt)*) => {
+        $crate::breadcrumbs::record_fmt(::std::format_args!($($arg)*))
+    };
+}
+
+/// Allocate the ring buffer with room for `capacity` messages of up to
+/// `max_message_len` bytes, and include it in every dump
+///
+/// Call it before the first breadcrumb; otherwise the first breadcrumb
+/// allocates [`DEFAULT_CAPACITY`] messages of up to
+/// [`DEFAULT_MAX_MESSAGE_LEN`] bytes. The buffer lives for the rest of the
+/// process and takes one of the
+/// [`MAX_MEMORY_RANGES`](crate::limits::MAX_MEMORY_RANGES) extra memory
+/// ranges.
+///
+/// # Errors
+///
+/// [`CrashpadError::InvalidConfiguration`] if the buffer exists already
+/// with another size, `capacity` is zero, the buffer would be larger than
+/// 1 MiB, or no memory range is left.
+pub fn init(capacity: usize, max_message_len: usize) -> Result<()> {
+    let words = buffer_words(capacity, max_message_len)
+        .filter(|&words| words * 8 <= MAX_BUFFER_SIZE && capacity > 0);
+    let Some(words) = words else {
+        return Err(CrashpadError::InvalidConfiguration(format!(
+            "Breadcrumbs of {capacity} messages of {max_message_len} bytes don't fit in \
+             {MAX_BUFFER_SIZE} bytes"
+        )));
+    };
+
+    let mut registered = None;
+    let ring = RING.get_or_init(|| {
+        let ring = Ring::new(words, capacity, max_message_len);
+        registered = Some(ring.register());
+        ring
+    });
+    if let Some(registered) = registered {
+        return registered;
+    }
+    if ring.capacity != capacity || ring.max_message_len != max_message_len {
+        return Err(CrashpadError::InvalidConfiguration(
+            "Breadcrumbs were initialized with another size already".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Record `message`, see [`breadcrumb!`](crate::breadcrumb)
+pub fn record(message: &str) {
+    let message = crate::redaction::with_active(|rules| match rules {
+        Some(rules) => rules.redact(REDACTION_KEY, message),
+        None => Cow::Borrowed(message),
+    });
+    ring().push(SystemTime::now(), &message);
+}
+
+#[doc(hidden)]
+pub fn record_fmt(args: fmt::Arguments<'_>) {
+    match args.as_str() {
+        Some(message) => record(message),
+        None => record(&args.to_string()),
+    }
+}
+
+fn ring() -> &'static Ring {
+    RING.get_or_init(|| {
+        let words =
+            buffer_words(DEFAULT_CAPACITY, DEFAULT_MAX_MESSAGE_LEN).expect("default size fits");
+        let ring = Ring::new(words, DEFAULT_CAPACITY, DEFAULT_MAX_MESSAGE_LEN);
+        // Without a memory range the breadcrumbs are missing from dumps,
+        // like any other extra memory that didn't fit
+        let _ = ring.register();
+        ring
+    })
+}
+
+/// Words of a buffer for `capacity` messages of up to `max_message_len`
+/// bytes
+fn buffer_words(capacity: usize, max_message_len: usize) -> Option<usize> {
+    message_words(max_message_len)
+        .checked_add(SLOT_HEADER_WORDS)?
+        .checked_mul(capacity)?
+        .checked_add(HEADER_WORDS)
+}
+
+/// Words a message of up to `max_message_len` bytes is padded to
+fn message_words(max_message_len: usize) -> usize {
+    max_message_len / 8 + usize::from(max_message_len % 8 != 0)
+}
+
+/// The ring buffer, in the format described in the module docs.
+#[derive(Debug)]
+struct Ring {
+    words: &'static [AtomicU64],
+    capacity: usize,
+    max_message_len: usize,
+}
+
+impl Ring {
+    fn new(words: usize, capacity: usize, max_message_len: usize) -> Self {
+        // Crashpad reads the buffer until the process exits
+        let words: &'static [AtomicU64] =
+            Box::leak((0..words).map(|_| AtomicU64::new(0)).collect());
+        words[0].store(MAGIC.to_le(), Ordering::Relaxed);
+        words[1].store((capacity as u64).to_le(), Ordering::Relaxed);
+        words[2].store((max_message_len as u64).to_le(), Ordering::Relaxed);
+        Self {
+            words,
+            capacity,
+            max_message_len,
+        }
+    }
+
+    /// Include the buffer in dumps
+    fn register(&self) -> Result<()> {
+        let size = self.words.len() * 8;
+        if !unsafe { crashpad_add_extra_memory_range(self.words.as_ptr().cast(), size) } {
+            return Err(CrashpadError::InvalidConfiguration(format!(
+                "More than {} memory ranges",
+                crate::limits::MAX_MEMORY_RANGES
+            )));
+        }
+        Ok(())
+    }
+
+    fn push(&self, time: SystemTime, message: &str) {
+        let message = truncate(message, self.max_message_len);
+        let millis = time
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+
+        // Only the decoder needs the byte order; the count isn't decoded
+        let index = self.words[3].fetch_add(1, Ordering::Relaxed);
+        let slot_words = SLOT_HEADER_WORDS + message_words(self.max_message_len);
+        let start = HEADER_WORDS + (index % self.capacity as u64) as usize * slot_words;
+        let slot = &self.words[start..start + slot_words];
+
+        // Left out of dumps until the sequence number is set again
+        slot[0].store(0, Ordering::Release);
+        slot[1].store(millis.to_le(), Ordering::Relaxed);
+        slot[2].store((message.len() as u64).to_le(), Ordering::Relaxed);
+        for (word, chunk) in slot[3..].iter().zip(message.as_bytes().chunks(8)) {
+            let mut bytes = [0u8; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            word.store(u64::from_ne_bytes(bytes), Ordering::Relaxed);
+        }
+        slot[0].store((index + 1).to_le(), Ordering::Release);
+    }
+}
+
+/// Events of a buffer read from a dump, oldest first. `None` if `bytes`
+/// doesn't start with a valid buffer.
+pub(crate) fn decode(bytes: &[u8]) -> Option<Vec<Breadcrumb>> {
+    let word = |index: usize| -> Option<u64> {
+        let offset = index.checked_mul(8)?;
+        Some(u64::from_le_bytes(
+            bytes.get(offset..offset + 8)?.try_into().ok()?,
+        ))
+    };
+    if word(0)? != MAGIC {
+        return None;
+    }
+    let capacity = usize::try_from(word(1)?).ok()?;
+    let max_message_len = usize::try_from(word(2)?).ok()?;
+    let words = buffer_words(capacity, max_message_len)?;
+    if capacity == 0 || bytes.len() / 8 < words {
+        return None;
+    }
+
+    let slot_words = SLOT_HEADER_WORDS + message_words(max_message_len);
+    let mut events: Vec<(u64, Breadcrumb)> = (0..capacity)
+        .filter_map(|slot| {
+            let start = HEADER_WORDS + slot * slot_words;
+            let sequence = word(start)?;
+            let len = usize::try_from(word(start + 2)?).ok()?;
+            if sequence == 0 || len > max_message_len {
+                return None;
+            }
+            let message = &bytes[(start + 3) * 8..(start + 3) * 8 + len];
+            Some((
+                sequence,
+                Breadcrumb {
+                    time: UNIX_EPOCH + Duration::from_millis(word(start + 1)?),
+                    message: String::from_utf8_lossy(message).into_owned(),
+                },
+            ))
+        })
+        .collect();
+    events.sort_by_key(|(sequence, _)| *sequence);
+    Some(events.into_iter().map(|(_, event)| event).collect())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Bytes of a buffer for `capacity` messages of up to `max_message_len`
+    /// bytes holding `messages`, recorded at 1.5 s after the epoch
+    pub(crate) fn buffer(capacity: usize, max_message_len: usize, messages: &[&str]) -> Vec<u8> {
+        let words = buffer_words(capacity, max_message_len).unwrap();
+        let ring = Ring::new(words, capacity, max_message_len);
+        for message in messages {
+            ring.push(UNIX_EPOCH + Duration::from_millis(1500), message);
+        }
+        ring.words
+            .iter()
+            .flat_map(|word| word.load(Ordering::Relaxed).to_ne_bytes())
+            .collect()
+    }
+
+    /// Messages in the process's buffer, oldest first
+    #[cfg(feature = "regex")]
+    pub(crate) fn recorded() -> Vec<String> {
+        let bytes: Vec<u8> = ring()
+            .words
+            .iter()
+            .flat_map(|word| word.load(Ordering::Relaxed).to_ne_bytes())
+            .collect();
+        decode(&bytes)
+            .unwrap()
+            .into_iter()
+            .map(|event| event.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_ring_wraps() {
+        let bytes = buffer(2, 4, &["first", "two", "three"]);
+        let events = decode(&bytes).unwrap();
+        let messages: Vec<_> = events.iter().map(|event| event.message.as_str()).collect();
+        assert_eq!(messages, ["two", "thre"]);
+        assert_eq!(events[0].time, UNIX_EPOCH + Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert_eq!(decode(&[]), None);
+        assert_eq!(decode(&[0; 64]), None);
+        // Cut off before the last slot
+        let bytes = buffer(2, 8, &["a"]);
+        assert_eq!(decode(&bytes[..bytes.len() - 8]), None);
+        assert_eq!(decode(&buffer(2, 8, &[])), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_init() {
+        assert!(init(0, 16).is_err());
+        assert!(init(1, MAX_BUFFER_SIZE).is_err());
+
+        crate::breadcrumb!("value {}", 1);
+        // The first breadcrumb allocated the default buffer
+        assert!(init(8, 16).is_err());
+        assert!(init(DEFAULT_CAPACITY, DEFAULT_MAX_MESSAGE_LEN).is_ok());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_record_redacts() {
+        // Other tests activate the same rules
+        crate::redaction::set_active(Some(&crate::RedactionRules::default()));
+
+        crate::breadcrumb!("sent {} to the API", "Bearer s3cr3t-t0ken");
+
+        let messages = recorded();
+        assert!(messages.iter().any(|m| m == "sent [REDACTED] to the API"));
+        assert!(!messages.iter().any(|m| m.contains("s3cr3t-t0ken")));
+    }
+}
@@ -0,0 +1,139 @@
+//! Breakpad-compatible upload field names.
+
+use std::collections::HashMap;
+
+/// Form field the minidump is uploaded in.
+///
+/// Crashpad uses the same name as Breakpad, so only annotation fields need
+/// mapping.
+pub const MINIDUMP_FIELD: &str = "upload_file_minidump";
+
+/// Renames annotation keys to the form field names a Breakpad-era server
+/// expects.
+///
+/// Uploads are `multipart/form-data` with one field per annotation, so a
+/// server that reads e.g. `prod` and `ver` can ingest Crashpad reports once
+/// the annotations use those names. Keys without a mapping are sent as is.
+///
+/// # Example
+///
+/// ```
+/// use crashpad_rs::BreakpadFields;
+/// use std::collections::HashMap;
+///
+/// let fields = BreakpadFields::default().map("channel", "release_channel");
+/// let annotations = HashMap::from([
+///     ("product".to_string(), "MyApp".to_string()),
+///     ("channel".to_string(), "beta".to_string()),
+/// ]);
+///
+/// let mapped = fields.apply(&annotations);
+/// assert_eq!(mapped["prod"], "MyApp");
+/// assert_eq!(mapped["release_channel"], "beta");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakpadFields {
+    mapping: Vec<(String, String)>,
+}
+
+impl Default for BreakpadFields {
+    /// Field names of Breakpad's `crash_report_sender` and the Google crash
+    /// server (`prod`, `ver`, `plat`, `ptype`)
+    fn default() -> Self {
+        Self::new()
+            .map("product", "prod")
+            .map("version", "ver")
+            .map("platform", "plat")
+            .map("process_type", "ptype")
+    }
+}
+
+impl BreakpadFields {
+    /// Create a mapping that renames nothing
+    pub fn new() -> Self {
+        Self {
+            mapping: Vec::new(),
+        }
+    }
+
+    /// Field names expected by Mozilla's Socorro collector
+    pub fn socorro() -> Self {
+        Self::new()
+            .map("product", "ProductName")
+            .map("version", "Version")
+            .map("build_id", "BuildID")
+            .map("release_channel", "ReleaseChannel")
+    }
+
+    /// Send annotation `key` as form field `field`
+    ///
+    /// A later mapping for the same key replaces the earlier one.
+    pub fn map<K: Into<String>, F: Into<String>>(mut self, key: K, field: F) -> Self {
+        let key = key.into();
+        self.mapping.retain(|(existing, _)| *existing != key);
+        self.mapping.push((key, field.into()));
+        self
+    }
+
+    /// Form field name for annotation `key`
+    pub fn field_name<'a>(&'a self, key: &'a str) -> &'a str {
+        self.mapping
+            .iter()
+            .find(|(from, _)| from == key)
+            .map_or(key, |(_, field)| field)
+    }
+
+    /// Rename the keys of `annotations`
+    ///
+    /// If an annotation already uses a mapped field name, the renamed
+    /// annotation wins.
+    pub fn apply(&self, annotations: &HashMap<String, String>) -> HashMap<String, String> {
+        let mut mapped: HashMap<String, String> = annotations
+            .iter()
+            .filter(|(key, _)| self.field_name(key) == key.as_str())
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        for (key, value) in annotations {
+            let field = self.field_name(key);
+            if field != key {
+                mapped.insert(field.to_string(), value.clone());
+            }
+        }
+        mapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_fields() {
+        let fields = BreakpadFields::default();
+        assert_eq!(fields.field_name("product"), "prod");
+        assert_eq!(fields.field_name("version"), "ver");
+        assert_eq!(fields.field_name("user_id"), "user_id");
+    }
+
+    #[test]
+    fn test_map_replaces_existing() {
+        let fields = BreakpadFields::socorro().map("version", "AppVersion");
+        assert_eq!(fields.field_name("version"), "AppVersion");
+        assert_eq!(fields.field_name("product"), "ProductName");
+    }
+
+    #[test]
+    fn test_apply_prefers_renamed() {
+        let annotations = HashMap::from([
+            ("version".to_string(), "2.0.0".to_string()),
+            ("ver".to_string(), "stale".to_string()),
+            ("build".to_string(), "42".to_string()),
+        ]);
+
+        let mapped = BreakpadFields::default().apply(&annotations);
+
+        assert_eq!(mapped.len(), 2);
+        assert_eq!(mapped["ver"], "2.0.0");
+        assert_eq!(mapped["build"], "42");
+    }
+}
@@ -0,0 +1,471 @@
+//! Support bundles: reports packaged into a single `.tar.zst` file.
+//!
+//! A bundle holds a `manifest.json` with the metadata and annotations of
+//! each report, followed by its minidump and attachments:
+//!
+//! ```text
+//! manifest.json
+//! reports/<uuid>/minidump.dmp
+//! reports/<uuid>/attachments/<name>
+//! ```
+
+use std::collections::HashMap;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::database::write_sidecar;
+use crate::fs::{Fs, StdFs};
+use crate::janitor::ATTACHMENTS_DIR;
+use crate::timestamp;
+use crate::{CrashReport, CrashReportDatabase, CrashpadError, Result};
+
+/// Version of the bundle layout, bumped on incompatible changes.
+const FORMAT_VERSION: u64 = 1;
+
+const MANIFEST: &str = "manifest.json";
+const REPORTS_DIR: &str = "reports";
+const MINIDUMP: &str = "minidump.dmp";
+
+/// A report added to a database by [`CrashReportDatabase::import_bundle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedReport {
+    /// UUID of the report in the database it was exported from
+    pub original_uuid: String,
+    /// When the report was created in the database it was exported from
+    pub original_creation_time: SystemTime,
+    /// ID assigned by the server, if the report had been uploaded
+    pub server_id: Option<String>,
+    /// The report in this database, under a new UUID
+    pub report: CrashReport,
+}
+
+/// A report read from a bundle.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct BundledReport {
+    uuid: String,
+    creation_time: Option<SystemTime>,
+    server_id: Option<String>,
+    annotations: HashMap<String, String>,
+    minidump: Vec<u8>,
+    attachments: Vec<(String, Vec<u8>)>,
+}
+
+impl CrashReportDatabase {
+    /// Package reports into a support bundle at `path`
+    ///
+    /// Writes a zstd-compressed tar file with the minidumps, attachments,
+    /// annotations and upload state of the reports with the given UUIDs,
+    /// pending or completed. A user can send the single file to support,
+    /// where [`import_bundle`](Self::import_bundle) reads it back. The file
+    /// is written through a temporary file, so `path` never holds a partial
+    /// bundle.
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::ReportNotFound`] if a UUID isn't in the database,
+    /// [`CrashpadError::IoError`] if a file can't be read or written.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use crashpad_rs::CrashReportDatabase;
+    ///
+    /// let database = CrashReportDatabase::open("./crashpad_db")?;
+    /// let uuids: Vec<_> = database
+    ///     .completed_reports()?
+    ///     .into_iter()
+    ///     .map(|report| report.uuid)
+    ///     .collect();
+    /// database.export_bundle(&uuids, "crash-reports.tar.zst")?;
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    pub fn export_bundle<I, S, P>(&self, uuids: I, path: P) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+        P: AsRef<Path>,
+    {
+        let reports = uuids
+            .into_iter()
+            .map(|uuid| self.report(uuid.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        export_reports(&StdFs, &reports, path.as_ref())
+    }
+
+    /// Add the reports of a support bundle to this database
+    ///
+    /// Reads a bundle written by [`export_bundle`](Self::export_bundle).
+    /// Each report gets a new UUID; the original one is returned with it.
+    /// Imported reports are completed without uploading, so importing a
+    /// customer's reports doesn't send them to the server again. Use
+    /// [`request_upload`](Self::request_upload) to upload one anyway.
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if the file isn't a bundle
+    /// or was written by a newer, incompatible version of this crate.
+    /// Reports imported before an error are kept.
+    pub fn import_bundle<P: AsRef<Path>>(&self, path: P) -> Result<Vec<ImportedReport>> {
+        let fs = StdFs;
+        let file = BufReader::new(fs.open(path.as_ref())?);
+        let mut imported = Vec::new();
+        for bundled in read_bundle(file)? {
+            let uuid = self.add_report(&bundled.minidump)?;
+            self.skip_upload(&uuid)?;
+            let report = self.report(&uuid)?;
+            store_files(&fs, &report, &bundled)?;
+
+            imported.push(ImportedReport {
+                original_uuid: bundled.uuid,
+                original_creation_time: bundled.creation_time.unwrap_or(UNIX_EPOCH),
+                server_id: bundled.server_id,
+                report,
+            });
+        }
+        Ok(imported)
+    }
+}
+
+/// Write `reports` as a bundle to `path` through a temporary file.
+fn export_reports(fs: &impl Fs, reports: &[CrashReport], path: &Path) -> Result<()> {
+    let temporary = path.with_extension("tmp");
+    let file = BufWriter::new(fs.create(&temporary)?);
+    let result = write_bundle(fs, file, reports).and_then(|mut file| Ok(file.flush()?));
+    if let Err(e) = result {
+        let _ = fs.remove_file(&temporary);
+        return Err(e);
+    }
+    fs.rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Write the annotation sidecar and attachments of `bundled`, imported as
+/// `report`.
+fn store_files(fs: &impl Fs, report: &CrashReport, bundled: &BundledReport) -> Result<()> {
+    write_sidecar(fs, &report.annotations_path(), &bundled.annotations)?;
+    if !bundled.attachments.is_empty() {
+        let directory = attachments_dir(report).ok_or_else(|| {
+            CrashpadError::DatabaseError("Unexpected report location".to_string())
+        })?;
+        fs.create_dir_all(&directory)?;
+        for (name, data) in &bundled.attachments {
+            fs.write(&directory.join(name), data)?;
+        }
+    }
+    Ok(())
+}
+
+/// Directory of the attachments of `report`: `attachments/<uuid>` in the
+/// database, next to the directory holding the minidump.
+fn attachments_dir(report: &CrashReport) -> Option<PathBuf> {
+    let database = report.file_path.parent()?.parent()?;
+    Some(database.join(ATTACHMENTS_DIR).join(&report.uuid))
+}
+
+/// Files in the attachment directory of `report`, sorted by name.
+fn attachment_files(fs: &impl Fs, report: &CrashReport) -> Result<Vec<PathBuf>> {
+    let Some(directory) = attachments_dir(report) else {
+        return Ok(Vec::new());
+    };
+    match fs.files(&directory) {
+        Ok(files) => Ok(files),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Write `reports` as a bundle to `out`, returning `out` once the
+/// compressed stream is complete.
+fn write_bundle<W: Write>(fs: &impl Fs, out: W, reports: &[CrashReport]) -> Result<W> {
+    let mut attachments = Vec::new();
+    let mut entries = Vec::new();
+    for report in reports {
+        let files = attachment_files(fs, report)?;
+        let names: Vec<_> = files
+            .iter()
+            .filter_map(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect();
+        entries.push(serde_json::json!({
+            "uuid": report.uuid,
+            "creation_time": timestamp::rfc3339(report.creation_time),
+            "uploaded": report.uploaded,
+            "server_id": report.id,
+            "upload_attempts": report.upload_attempts,
+            // A dump without readable annotations is still worth sending
+            "annotations": report.annotations_in(fs).unwrap_or_default(),
+            "attachments": names,
+        }));
+        attachments.push(files);
+    }
+    let manifest = serde_json::json!({
+        "format_version": FORMAT_VERSION,
+        "created": timestamp::rfc3339(SystemTime::now()),
+        "reports": entries,
+    });
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(std::io::Error::from)?;
+
+    let mut tar = tar::Builder::new(zstd::Encoder::new(out, 0)?);
+    append_file(&mut tar, Path::new(MANIFEST), &manifest)?;
+
+    for (report, files) in reports.iter().zip(attachments) {
+        let directory = Path::new(REPORTS_DIR).join(&report.uuid);
+        append_file(
+            &mut tar,
+            &directory.join(MINIDUMP),
+            &fs.read(&report.file_path)?,
+        )?;
+        for file in files {
+            if let Some(name) = file.file_name() {
+                let path = directory.join("attachments").join(name);
+                append_file(&mut tar, &path, &fs.read(&file)?)?;
+            }
+        }
+    }
+    Ok(tar.into_inner()?.finish()?)
+}
+
+/// Add a file holding `data` at `path` to `tar`.
+fn append_file<W: Write>(tar: &mut tar::Builder<W>, path: &Path, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    );
+    header.set_cksum();
+    tar.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+/// Read the reports of the bundle in `input`.
+fn read_bundle<R: Read>(input: R) -> Result<Vec<BundledReport>> {
+    let invalid =
+        |message: &str| CrashpadError::InvalidConfiguration(format!("Invalid bundle: {message}"));
+
+    let mut archive = tar::Archive::new(zstd::Decoder::new(input)?);
+    let mut reports: Vec<BundledReport> = Vec::new();
+    let mut index = HashMap::new();
+    let mut has_manifest = false;
+    for (position, entry) in archive.entries()?.enumerate() {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+
+        if position == 0 {
+            if path != Path::new(MANIFEST) {
+                return Err(invalid("missing manifest"));
+            }
+            reports = parse_manifest(&data).ok_or_else(|| invalid("malformed manifest"))?;
+            index = reports
+                .iter()
+                .enumerate()
+                .map(|(i, report)| (report.uuid.clone(), i))
+                .collect();
+            has_manifest = true;
+            continue;
+        }
+
+        let (uuid, file) = report_file(&path).ok_or_else(|| invalid("unexpected entry"))?;
+        let report = index
+            .get(uuid.as_str())
+            .map(|&i| &mut reports[i])
+            .ok_or_else(|| invalid("entry of a report not in the manifest"))?;
+        match file {
+            None => report.minidump = data,
+            Some(name) => report.attachments.push((name, data)),
+        }
+    }
+
+    if !has_manifest {
+        return Err(invalid("missing manifest"));
+    }
+    if reports.iter().any(|report| report.minidump.is_empty()) {
+        return Err(invalid("report without minidump"));
+    }
+    Ok(reports)
+}
+
+/// Reports listed in a manifest, `None` if it is malformed or of an
+/// unsupported format version.
+fn parse_manifest(json: &[u8]) -> Option<Vec<BundledReport>> {
+    let manifest: serde_json::Value = serde_json::from_slice(json).ok()?;
+    if manifest["format_version"].as_u64()? > FORMAT_VERSION {
+        return None;
+    }
+    manifest["reports"]
+        .as_array()?
+        .iter()
+        .map(|entry| {
+            let uuid = entry["uuid"].as_str()?;
+            // Used as a path component of the other entries
+            if !is_file_name(uuid) {
+                return None;
+            }
+            Some(BundledReport {
+                uuid: uuid.to_string(),
+                creation_time: entry["creation_time"]
+                    .as_str()
+                    .and_then(timestamp::parse_rfc3339),
+                server_id: entry["server_id"].as_str().map(str::to_string),
+                annotations: serde_json::from_value(entry["annotations"].clone())
+                    .unwrap_or_default(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// UUID and attachment name of a report entry, `None` as the name for the
+/// minidump. `None` for any other path, so that a bundle can't write
+/// outside the attachment directory.
+fn report_file(path: &Path) -> Option<(String, Option<String>)> {
+    let components: Vec<_> = path
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => name.to_str(),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    match components.as_slice() {
+        [REPORTS_DIR, uuid, MINIDUMP] => Some((uuid.to_string(), None)),
+        [REPORTS_DIR, uuid, "attachments", name] if is_file_name(name) => {
+            Some((uuid.to_string(), Some(name.to_string())))
+        }
+        _ => None,
+    }
+}
+
+/// Whether `name` is a single, plain path component.
+fn is_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', ':'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::memory::MemoryFs;
+    use std::time::Duration;
+
+    const UUID: &str = "00112233-4455-6677-8899-aabbccddeeff";
+
+    fn report(file_path: PathBuf) -> CrashReport {
+        CrashReport {
+            uuid: UUID.to_string(),
+            file_path,
+            id: Some("server-1".to_string()),
+            creation_time: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            uploaded: true,
+            last_upload_attempt_time: None,
+            upload_attempts: 1,
+            upload_explicitly_requested: false,
+            total_size: 4,
+        }
+    }
+
+    fn dump_path() -> PathBuf {
+        Path::new("db")
+            .join("completed")
+            .join(format!("{UUID}.dmp"))
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let dump_path = dump_path();
+        let fs = MemoryFs::default()
+            .with_file(&dump_path, b"MDMP")
+            .with_file(
+                dump_path.with_extension(crate::database::ANNOTATIONS_EXTENSION),
+                br#"{"version":"2.0"}"#,
+            )
+            .with_file(
+                Path::new("db")
+                    .join("attachments")
+                    .join(UUID)
+                    .join("log.txt"),
+                b"last words",
+            );
+
+        export_reports(&fs, &[report(dump_path)], Path::new("bundle.tar.zst")).unwrap();
+        assert!(!fs.exists(Path::new("bundle.tmp")));
+        let bundle = fs.read(Path::new("bundle.tar.zst")).unwrap();
+        let reports = read_bundle(bundle.as_slice()).unwrap();
+
+        assert_eq!(
+            reports,
+            vec![BundledReport {
+                uuid: UUID.to_string(),
+                creation_time: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+                server_id: Some("server-1".to_string()),
+                annotations: HashMap::from([("version".to_string(), "2.0".to_string())]),
+                minidump: b"MDMP".to_vec(),
+                attachments: vec![("log.txt".to_string(), b"last words".to_vec())],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_export_missing_dump() {
+        let fs = MemoryFs::default().with_file("db/completed/other.dmp", b"MDMP");
+        let path = Path::new("db/bundle.tar.zst");
+        assert!(export_reports(&fs, &[report(dump_path())], path).is_err());
+        assert!(!fs.exists(path));
+        assert!(!fs.exists(&path.with_extension("tmp")));
+    }
+
+    #[test]
+    fn test_store_files() {
+        let dump_path = dump_path();
+        let fs = MemoryFs::default().with_file(&dump_path, b"MDMP");
+        let bundled = BundledReport {
+            annotations: HashMap::from([("version".to_string(), "2.0".to_string())]),
+            attachments: vec![("log.txt".to_string(), b"last words".to_vec())],
+            ..Default::default()
+        };
+
+        let report = report(dump_path);
+        store_files(&fs, &report, &bundled).unwrap();
+        assert_eq!(report.annotations_in(&fs).unwrap()["version"], "2.0");
+        assert_eq!(
+            fs.read(
+                &Path::new("db")
+                    .join("attachments")
+                    .join(UUID)
+                    .join("log.txt")
+            )
+            .unwrap(),
+            b"last words"
+        );
+    }
+
+    #[test]
+    fn test_report_file() {
+        assert_eq!(
+            report_file(Path::new("reports/abc/minidump.dmp")),
+            Some(("abc".to_string(), None))
+        );
+        assert_eq!(
+            report_file(Path::new("reports/abc/attachments/log.txt")),
+            Some(("abc".to_string(), Some("log.txt".to_string())))
+        );
+        assert_eq!(report_file(Path::new("reports/../minidump.dmp")), None);
+        assert_eq!(report_file(Path::new("/reports/abc/minidump.dmp")), None);
+        assert_eq!(report_file(Path::new("reports/abc/other.dmp")), None);
+    }
+
+    #[test]
+    fn test_read_bundle_rejects_invalid_input() {
+        assert!(read_bundle(&b"not a bundle"[..]).is_err());
+
+        // Newer format version
+        let manifest = br#"{"format_version": 2, "reports": []}"#;
+        assert!(parse_manifest(manifest).is_none());
+        let manifest = br#"{"format_version": 1, "reports": [{"uuid": ".."}]}"#;
+        assert!(parse_manifest(manifest).is_none());
+    }
+}
@@ -0,0 +1,317 @@
+//! Coalescing of crash bursts from crash-looping processes.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::Clock;
+use crate::{minidump, timestamp};
+use crate::{CrashReport, CrashReportDatabase, Result};
+
+/// Annotations of a summary report, see [`BurstSummary`].
+const BURST_COUNT: &str = "burst_count";
+const BURST_FIRST: &str = "burst_first";
+const BURST_LAST: &str = "burst_last";
+#[cfg(feature = "serde")]
+const BURST_FINGERPRINTS: &str = "burst_fingerprints";
+
+/// Fingerprint of reports without an exception or a readable minidump.
+const UNKNOWN_FINGERPRINT: &str = "unknown";
+
+/// Result of the coalescing run by the last start, see [`last_burst`].
+static LAST_BURST: Mutex<Option<BurstSummary>> = Mutex::new(None);
+
+/// When [`CrashReportDatabase::coalesce_burst`] considers reports a burst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BurstPolicy {
+    max_reports: usize,
+    window: Duration,
+}
+
+impl BurstPolicy {
+    /// Upload at most `max_reports` of the reports created within `window`
+    /// and summarize the rest
+    pub fn new(max_reports: usize, window: Duration) -> Self {
+        Self {
+            max_reports,
+            window,
+        }
+    }
+}
+
+/// A burst of reports folded into one summary report.
+///
+/// The summary report is a minidump without threads or memory. It has the
+/// annotations of the newest report of the burst, plus:
+/// - `burst_count`: number of reports summarized
+/// - `burst_first`, `burst_last`: creation times of the first and last of
+///   them, RFC 3339 UTC
+/// - `burst_fingerprints`: JSON object of fingerprints (exception code and
+///   module offset, e.g. `0x0000000b@app+0x1a2b`) to their number of
+///   reports, with the `serde` feature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BurstSummary {
+    /// Reports summarized, including those of earlier summaries that were
+    /// still pending
+    pub count: usize,
+    pub first: SystemTime,
+    pub last: SystemTime,
+    /// Number of reports per fingerprint, `unknown` for reports without an
+    /// exception
+    pub fingerprints: BTreeMap<String, usize>,
+    /// UUIDs of the reports this run completed without uploading them
+    pub deferred: Vec<String>,
+    /// UUID of the summary report
+    pub report_uuid: String,
+}
+
+/// Result of the coalescing done when the handler was last started with
+/// [`CrashpadConfigBuilder::crash_burst_limit`](crate::CrashpadConfigBuilder::crash_burst_limit).
+///
+/// `None` if there was no burst.
+pub fn last_burst() -> Option<BurstSummary> {
+    LAST_BURST.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub(crate) fn record_burst(summary: Option<BurstSummary>) {
+    *LAST_BURST.lock().unwrap_or_else(|e| e.into_inner()) = summary;
+}
+
+/// Counts of a burst, accumulated from reports and earlier summaries.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Burst {
+    count: usize,
+    first: Option<SystemTime>,
+    last: Option<SystemTime>,
+    fingerprints: BTreeMap<String, usize>,
+}
+
+impl Burst {
+    fn add(&mut self, count: usize, first: SystemTime, last: SystemTime) {
+        self.count += count;
+        self.first = Some(self.first.map_or(first, |time| time.min(first)));
+        self.last = Some(self.last.map_or(last, |time| time.max(last)));
+    }
+
+    fn add_report(&mut self, report: &CrashReport, fingerprint: String) {
+        self.add(1, report.creation_time, report.creation_time);
+        *self.fingerprints.entry(fingerprint).or_default() += 1;
+    }
+
+    /// Add the counts of an earlier summary report's annotations
+    fn merge(&mut self, annotations: &HashMap<String, String>) {
+        let time = |key| {
+            annotations
+                .get(key)
+                .and_then(|value: &String| timestamp::parse_rfc3339(value))
+        };
+        let count = annotations
+            .get(BURST_COUNT)
+            .and_then(|count| count.parse().ok());
+        if let (Some(count), Some(first), Some(last)) = (count, time(BURST_FIRST), time(BURST_LAST))
+        {
+            self.add(count, first, last);
+        }
+        #[cfg(feature = "serde")]
+        let fingerprints: BTreeMap<String, usize> = annotations
+            .get(BURST_FINGERPRINTS)
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or_default();
+        #[cfg(feature = "serde")]
+        for (fingerprint, count) in fingerprints {
+            *self.fingerprints.entry(fingerprint).or_default() += count;
+        }
+    }
+
+    fn annotations(&self) -> Vec<(&'static str, String)> {
+        let time = |time: Option<SystemTime>| time.map(timestamp::rfc3339).unwrap_or_default();
+        vec![
+            (BURST_COUNT, self.count.to_string()),
+            (BURST_FIRST, time(self.first)),
+            (BURST_LAST, time(self.last)),
+            #[cfg(feature = "serde")]
+            (
+                BURST_FINGERPRINTS,
+                serde_json::to_string(&self.fingerprints).unwrap_or_default(),
+            ),
+        ]
+    }
+}
+
+fn is_summary(annotations: &HashMap<String, String>) -> bool {
+    annotations.contains_key(BURST_COUNT)
+}
+
+/// Reports of `reports` beyond the first `max_reports` created within the
+/// window before `now`, oldest first.
+fn excess<T>(
+    mut reports: Vec<(CrashReport, T)>,
+    policy: &BurstPolicy,
+    now: SystemTime,
+) -> Vec<(CrashReport, T)> {
+    let since = now
+        .checked_sub(policy.window)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    reports.retain(|(report, _)| report.creation_time >= since);
+    reports.sort_by_key(|(report, _)| report.creation_time);
+    reports.split_off(policy.max_reports.min(reports.len()))
+}
+
+/// See [`CrashReportDatabase::coalesce_burst`].
+pub(crate) fn coalesce(
+    database: &CrashReportDatabase,
+    policy: &BurstPolicy,
+    clock: &impl Clock,
+) -> Result<Option<BurstSummary>> {
+    let now = clock.now();
+    let mut crashes = Vec::new();
+    let mut summaries = Vec::new();
+    for report in database.pending_reports()? {
+        let annotations = report.annotations().unwrap_or_default();
+        if is_summary(&annotations) {
+            summaries.push((report, annotations));
+        } else {
+            crashes.push((report, annotations));
+        }
+    }
+
+    let excess = excess(crashes, policy, now);
+    let mut burst = Burst::default();
+    let mut deferred = Vec::new();
+    for (report, _) in &excess {
+        // Skip reports a running handler is uploading right now
+        if database.skip_upload(&report.uuid).is_err() {
+            continue;
+        }
+        let fingerprint = minidump::read_fingerprint(&report.file_path)
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| UNKNOWN_FINGERPRINT.to_string());
+        burst.add_report(report, fingerprint);
+        deferred.push(report.uuid.clone());
+    }
+    let Some((_, newest)) = excess.last().filter(|_| !deferred.is_empty()) else {
+        return Ok(None);
+    };
+
+    for (_, annotations) in &summaries {
+        burst.merge(annotations);
+    }
+    let mut annotations: Vec<(&str, &str)> = newest
+        .iter()
+        .filter(|(key, _)| !key.starts_with("burst_"))
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+    let burst_annotations = burst.annotations();
+    annotations.extend(
+        burst_annotations
+            .iter()
+            .map(|(key, value)| (*key, value.as_str())),
+    );
+    let report_uuid = database.add_report(&minidump::annotations_dump(&annotations))?;
+
+    // Replaced by the new summary
+    for (report, _) in &summaries {
+        let _ = database.delete_report(&report.uuid);
+    }
+
+    Ok(Some(BurstSummary {
+        count: burst.count,
+        first: burst.first.unwrap_or(now),
+        last: burst.last.unwrap_or(now),
+        fingerprints: burst.fingerprints,
+        deferred,
+        report_uuid,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::UNIX_EPOCH;
+
+    fn report(uuid: &str, seconds: u64) -> (CrashReport, ()) {
+        let report = CrashReport {
+            uuid: uuid.to_string(),
+            file_path: PathBuf::from(format!("{uuid}.dmp")),
+            id: None,
+            creation_time: UNIX_EPOCH + Duration::from_secs(seconds),
+            uploaded: false,
+            last_upload_attempt_time: None,
+            upload_attempts: 0,
+            upload_explicitly_requested: false,
+            total_size: 0,
+        };
+        (report, ())
+    }
+
+    #[test]
+    fn test_excess() {
+        let policy = BurstPolicy::new(2, Duration::from_secs(60));
+        let now = UNIX_EPOCH + Duration::from_secs(1000);
+        let reports = vec![
+            report("d", 990),
+            report("old", 100),
+            report("b", 960),
+            report("a", 950),
+            report("c", 970),
+        ];
+
+        let uuids: Vec<String> = excess(reports, &policy, now)
+            .into_iter()
+            .map(|(report, _)| report.uuid)
+            .collect();
+        assert_eq!(uuids, vec!["c", "d"]);
+
+        let within_limit = vec![report("a", 950), report("b", 960)];
+        assert!(excess(within_limit, &policy, now).is_empty());
+    }
+
+    #[test]
+    fn test_burst_merge() {
+        let mut earlier = Burst::default();
+        earlier.add_report(
+            &report("a", 1_700_000_000).0,
+            "0x0000000b@app+0x10".to_string(),
+        );
+        earlier.add_report(
+            &report("b", 1_700_000_050).0,
+            UNKNOWN_FINGERPRINT.to_string(),
+        );
+        let annotations: HashMap<String, String> = earlier
+            .annotations()
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+        assert!(is_summary(&annotations));
+        assert_eq!(annotations[BURST_FIRST], "2023-11-14T22:13:20Z");
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            annotations[BURST_FINGERPRINTS],
+            r#"{"0x0000000b@app+0x10":1,"unknown":1}"#
+        );
+
+        let mut burst = Burst::default();
+        burst.add_report(
+            &report("c", 1_700_000_100).0,
+            "0x0000000b@app+0x10".to_string(),
+        );
+        burst.merge(&annotations);
+
+        assert_eq!(burst.count, 3);
+        assert_eq!(
+            burst.first,
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(
+            burst.last,
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_100))
+        );
+        #[cfg(feature = "serde")]
+        {
+            assert_eq!(burst.fingerprints["0x0000000b@app+0x10"], 2);
+            assert_eq!(burst.fingerprints[UNKNOWN_FINGERPRINT], 1);
+        }
+    }
+}
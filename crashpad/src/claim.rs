@@ -0,0 +1,473 @@
+//! Claims on reports, for coordinating with external harvesters.
+
+#[cfg(feature = "serde")]
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "serde")]
+use std::time::SystemTime;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::clock::{Clock, IdGen};
+use crate::fs::Fs;
+
+/// Directory of the claim files in the database.
+pub(crate) const CLAIMS_DIR: &str = "claims";
+
+/// How long a claim lock is held at most; older locks were left by an
+/// agent that died while changing the claim
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a busy claim lock is tried, a millisecond apart
+const LOCK_ATTEMPTS: u32 = 100;
+
+/// A live claim on a report.
+///
+/// An agent collecting dumps from the database, like one shipping them out
+/// of band, and the application's own upload and prune code can work on the
+/// same report at once. Before processing a report, each claims it with an
+/// owner ID and an expiry: [`CrashReportDatabase::claim`] fails while
+/// another owner's claim is live, [`CrashReportDatabase::prune`] keeps
+/// claimed reports, and [`CrashReportDatabase::upload_pending_blocking`]
+/// doesn't request uploads of them. Claims expire, so an agent that died
+/// doesn't hold its reports forever.
+///
+/// A claim is the file `claims/<uuid>.json` in the database, holding the
+/// owner and the expiry in seconds since the Unix epoch, e.g.
+/// `{"owner":"harvester-1","expires":1714563000}`. Agents in other
+/// languages can take part: create the file so that it never replaces
+/// another one (e.g. as a hard link to a temporary file). An existing claim
+/// file, expired or their own, is only changed while holding the lock
+/// `claims/<uuid>.lock`, created the same way and holding the seconds
+/// since the Unix epoch after which it may be broken: with the lock, read
+/// the claim again, and if it's still the one to replace, rename a new file
+/// over it, or delete it when done. Then delete the lock. Claims are
+/// advisory; Crashpad's handler doesn't know about them.
+///
+/// Claims need the `serde` feature. Without it, the database doesn't read
+/// claim files, so [`CrashReportDatabase::prune`] and the uploads don't
+/// honor claims of external agents either.
+///
+/// [`CrashReportDatabase::claim`]: crate::CrashReportDatabase::claim
+/// [`CrashReportDatabase::prune`]: crate::CrashReportDatabase::prune
+/// [`CrashReportDatabase::upload_pending_blocking`]: crate::CrashReportDatabase::upload_pending_blocking
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportClaim {
+    /// ID of the agent holding the claim
+    pub owner: String,
+    /// When the claim lapses, to the second
+    pub expires: SystemTime,
+}
+
+#[cfg(feature = "serde")]
+impl ReportClaim {
+    fn parse(json: &[u8]) -> Option<Self> {
+        let value: serde_json::Value = serde_json::from_slice(json).ok()?;
+        Some(Self {
+            owner: value.get("owner")?.as_str()?.to_string(),
+            expires: UNIX_EPOCH + Duration::from_secs(value.get("expires")?.as_u64()?),
+        })
+    }
+
+    fn to_json(&self) -> Vec<u8> {
+        let expires = self
+            .expires
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        serde_json::json!({ "owner": self.owner, "expires": expires })
+            .to_string()
+            .into_bytes()
+    }
+
+    fn is_live(&self, now: SystemTime) -> bool {
+        self.expires > now
+    }
+}
+
+/// Path of the claim file of report `uuid` in `database`
+fn claim_path(database: &Path, uuid: &str) -> PathBuf {
+    database.join(CLAIMS_DIR).join(format!("{uuid}.json"))
+}
+
+/// The lock on the claim file of report `uuid`, removed when dropped.
+struct ClaimLock<'a, F: Fs> {
+    fs: &'a F,
+    path: PathBuf,
+}
+
+impl<'a, F: Fs> ClaimLock<'a, F> {
+    /// Take the lock on the claim of report `uuid`, waiting while another
+    /// agent holds it. `None` if it's still held after [`LOCK_ATTEMPTS`].
+    fn acquire(
+        fs: &'a F,
+        database: &Path,
+        uuid: &str,
+        clock: &impl Clock,
+        ids: &impl IdGen,
+    ) -> io::Result<Option<Self>> {
+        let path = database.join(CLAIMS_DIR).join(format!("{uuid}.lock"));
+        let now = clock.now();
+        let expires = (now + LOCK_TIMEOUT)
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        fs.create_dir_all(&database.join(CLAIMS_DIR))?;
+        for attempt in 0..LOCK_ATTEMPTS {
+            if create_new(fs, ids, &path, expires.to_string().as_bytes())? {
+                return Ok(Some(Self { fs, path }));
+            }
+            let stale = match fs.read(&path) {
+                Ok(lock) => String::from_utf8_lossy(&lock)
+                    .trim()
+                    .parse::<u64>()
+                    .map_or(true, |expires| {
+                        UNIX_EPOCH + Duration::from_secs(expires) <= now
+                    }),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+                Err(e) => return Err(e),
+            };
+            if stale {
+                let _ = fs.remove_file(&path);
+            } else if attempt + 1 < LOCK_ATTEMPTS {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<F: Fs> Drop for ClaimLock<'_, F> {
+    fn drop(&mut self) {
+        let _ = self.fs.remove_file(&self.path);
+    }
+}
+
+/// The error of changing a claim whose lock stays busy
+fn lock_busy(uuid: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::WouldBlock,
+        format!("Claim on report {uuid} is being changed by another agent"),
+    )
+}
+
+/// Claim report `uuid` for `owner` for `ttl` from now. `false` if another
+/// owner holds a live claim or is taking the claim over; the owner's own
+/// claim is renewed.
+#[cfg(feature = "serde")]
+pub(crate) fn claim(
+    fs: &impl Fs,
+    database: &Path,
+    uuid: &str,
+    owner: &str,
+    ttl: Duration,
+    clock: &impl Clock,
+    ids: &impl IdGen,
+) -> io::Result<bool> {
+    let path = claim_path(database, uuid);
+    let now = clock.now();
+    let new = ReportClaim {
+        owner: owner.to_string(),
+        expires: now + ttl,
+    };
+
+    match read_claim(fs, &path)? {
+        Some(held) if held.is_live(now) && held.owner != owner => Ok(false),
+        // Renew, or replace an expired or unreadable claim
+        Some(held) => replace(fs, database, uuid, &held, &new, clock, ids),
+        None => {
+            fs.create_dir_all(&database.join(CLAIMS_DIR))?;
+            create_new(fs, ids, &path, &new.to_json())
+        }
+    }
+}
+
+/// Replace the claim on report `uuid` by `new` if it's still `held`, as
+/// read before, so that of two agents taking over the same expired claim
+/// only one succeeds
+#[cfg(feature = "serde")]
+fn replace(
+    fs: &impl Fs,
+    database: &Path,
+    uuid: &str,
+    held: &ReportClaim,
+    new: &ReportClaim,
+    clock: &impl Clock,
+    ids: &impl IdGen,
+) -> io::Result<bool> {
+    let Some(_lock) = ClaimLock::acquire(fs, database, uuid, clock, ids)? else {
+        return Ok(false);
+    };
+    let path = claim_path(database, uuid);
+    if read_claim(fs, &path)?.as_ref() != Some(held) {
+        return Ok(false);
+    }
+    let temporary = temporary_path(ids, &path);
+    fs.write(&temporary, &new.to_json())?;
+    let renamed = fs.rename(&temporary, &path);
+    if renamed.is_err() {
+        let _ = fs.remove_file(&temporary);
+    }
+    renamed.map(|()| true)
+}
+
+/// A unique temporary file next to `path`
+fn temporary_path(ids: &impl IdGen, path: &Path) -> PathBuf {
+    path.with_extension(format!("{}.tmp", ids.new_id()))
+}
+
+/// Write `data` to `path` unless it exists, so that of two agents claiming
+/// at once only one succeeds
+fn create_new(fs: &impl Fs, ids: &impl IdGen, path: &Path, data: &[u8]) -> io::Result<bool> {
+    let temporary = temporary_path(ids, path);
+    fs.write(&temporary, data)?;
+    let linked = fs.hard_link(&temporary, path);
+    let _ = fs.remove_file(&temporary);
+    match linked {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Release `owner`'s claim on report `uuid`. Claims of other owners are
+/// left alone.
+#[cfg(feature = "serde")]
+pub(crate) fn unclaim(
+    fs: &impl Fs,
+    database: &Path,
+    uuid: &str,
+    owner: &str,
+    clock: &impl Clock,
+    ids: &impl IdGen,
+) -> io::Result<()> {
+    let path = claim_path(database, uuid);
+    if read_claim(fs, &path)?.is_none() {
+        return Ok(());
+    }
+    let Some(_lock) = ClaimLock::acquire(fs, database, uuid, clock, ids)? else {
+        return Err(lock_busy(uuid));
+    };
+    match read_claim(fs, &path)? {
+        Some(held) if held.owner == owner => match fs.remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Remove the claim on report `uuid`, whoever holds it
+pub(crate) fn remove_claim(
+    fs: &impl Fs,
+    database: &Path,
+    uuid: &str,
+    clock: &impl Clock,
+    ids: &impl IdGen,
+) -> io::Result<()> {
+    let path = claim_path(database, uuid);
+    if !fs.exists(&path) {
+        return Ok(());
+    }
+    let Some(_lock) = ClaimLock::acquire(fs, database, uuid, clock, ids)? else {
+        return Err(lock_busy(uuid));
+    };
+    match fs.remove_file(&path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// The live claim on report `uuid`, if any
+#[cfg(feature = "serde")]
+pub(crate) fn live_claim(
+    fs: &impl Fs,
+    database: &Path,
+    uuid: &str,
+    clock: &impl Clock,
+) -> io::Result<Option<ReportClaim>> {
+    let now = clock.now();
+    Ok(read_claim(fs, &claim_path(database, uuid))?.filter(|claim| claim.is_live(now)))
+}
+
+/// UUIDs of the reports with a live claim
+#[cfg(feature = "serde")]
+pub(crate) fn claimed_reports(
+    fs: &impl Fs,
+    database: &Path,
+    clock: &impl Clock,
+) -> io::Result<Vec<String>> {
+    let now = clock.now();
+    let files = match fs.files(&database.join(CLAIMS_DIR)) {
+        Ok(files) => files,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut claimed = Vec::new();
+    for file in files {
+        if file.extension() != Some(OsStr::new("json")) {
+            continue;
+        }
+        let Some(uuid) = file.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        if read_claim(fs, &file)?.is_some_and(|claim| claim.is_live(now)) {
+            claimed.push(uuid.to_string());
+        }
+    }
+    Ok(claimed)
+}
+
+/// UUIDs of the reports with a live claim: none, as claims aren't read
+/// without the `serde` feature
+#[cfg(not(feature = "serde"))]
+pub(crate) fn claimed_reports(
+    _fs: &impl Fs,
+    _database: &Path,
+    _clock: &impl Clock,
+) -> io::Result<Vec<String>> {
+    Ok(Vec::new())
+}
+
+/// The claim at `path`. An unreadable claim, e.g. written by a broken
+/// agent, counts as expired.
+#[cfg(feature = "serde")]
+fn read_claim(fs: &impl Fs, path: &Path) -> io::Result<Option<ReportClaim>> {
+    match fs.read(path) {
+        Ok(json) => Ok(Some(ReportClaim::parse(&json).unwrap_or(ReportClaim {
+            owner: String::new(),
+            expires: UNIX_EPOCH,
+        }))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::clock::manual::{ManualClock, SequentialIds};
+    use crate::fs::memory::MemoryFs;
+
+    const UUID: &str = "6d6b0ce1-8f2a-4e4b-9a3b-1e0c5f3a7d21";
+
+    fn clock() -> ManualClock {
+        ManualClock::at(UNIX_EPOCH + Duration::from_secs(1_000))
+    }
+
+    #[test]
+    fn test_claim() {
+        let fs = MemoryFs::default();
+        let (clock, ids) = (clock(), SequentialIds::default());
+        let database = Path::new("db");
+        let ttl = Duration::from_secs(60);
+
+        assert!(claim(&fs, database, UUID, "harvester", ttl, &clock, &ids).unwrap());
+        assert!(!claim(&fs, database, UUID, "app", ttl, &clock, &ids).unwrap());
+        // Renewed by its owner
+        clock.advance(ttl / 2);
+        assert!(claim(&fs, database, UUID, "harvester", ttl, &clock, &ids).unwrap());
+        assert_eq!(
+            live_claim(&fs, database, UUID, &clock).unwrap(),
+            Some(ReportClaim {
+                owner: "harvester".to_string(),
+                expires: clock.now() + ttl,
+            })
+        );
+        assert_eq!(
+            claimed_reports(&fs, database, &clock).unwrap(),
+            vec![UUID.to_string()]
+        );
+        // No temporary files left
+        assert_eq!(fs.files(&database.join(CLAIMS_DIR)).unwrap().len(), 1);
+
+        // Taken over once expired
+        clock.advance(ttl);
+        assert_eq!(live_claim(&fs, database, UUID, &clock).unwrap(), None);
+        assert!(claim(&fs, database, UUID, "app", ttl, &clock, &ids).unwrap());
+    }
+
+    #[test]
+    fn test_unclaim() {
+        let fs = MemoryFs::default();
+        let (clock, ids) = (clock(), SequentialIds::default());
+        let database = Path::new("db");
+        let ttl = Duration::from_secs(60);
+        claim(&fs, database, UUID, "harvester", ttl, &clock, &ids).unwrap();
+
+        // Only by the owner
+        unclaim(&fs, database, UUID, "app", &clock, &ids).unwrap();
+        assert!(live_claim(&fs, database, UUID, &clock).unwrap().is_some());
+        unclaim(&fs, database, UUID, "harvester", &clock, &ids).unwrap();
+        assert!(claimed_reports(&fs, database, &clock).unwrap().is_empty());
+        unclaim(&fs, database, UUID, "harvester", &clock, &ids).unwrap();
+        remove_claim(&fs, database, UUID, &clock, &ids).unwrap();
+        // No lock left
+        assert!(fs.files(&database.join(CLAIMS_DIR)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_takeover_of_expired_claim() {
+        let fs = MemoryFs::default();
+        let (clock, ids) = (clock(), SequentialIds::default());
+        let database = Path::new("db");
+        let ttl = Duration::from_secs(60);
+        assert!(claim(&fs, database, UUID, "dead", ttl, &clock, &ids).unwrap());
+
+        // Both agents read the expired claim before either takes it over
+        clock.advance(2 * ttl);
+        let expired = read_claim(&fs, &claim_path(database, UUID))
+            .unwrap()
+            .unwrap();
+        let claim_of = |owner: &str| ReportClaim {
+            owner: owner.to_string(),
+            expires: clock.now() + ttl,
+        };
+        assert!(replace(&fs, database, UUID, &expired, &claim_of("a"), &clock, &ids).unwrap());
+        assert!(!replace(&fs, database, UUID, &expired, &claim_of("b"), &clock, &ids).unwrap());
+        assert_eq!(
+            live_claim(&fs, database, UUID, &clock).unwrap(),
+            Some(claim_of("a"))
+        );
+        assert_eq!(fs.files(&database.join(CLAIMS_DIR)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_claim_lock() {
+        let fs = MemoryFs::default();
+        let (clock, ids) = (clock(), SequentialIds::default());
+        let database = Path::new("db");
+        let ttl = Duration::from_secs(60);
+        assert!(claim(&fs, database, UUID, "dead", ttl, &clock, &ids).unwrap());
+        clock.advance(2 * ttl);
+
+        // Another agent is taking the expired claim over
+        let lock = ClaimLock::acquire(&fs, database, UUID, &clock, &ids)
+            .unwrap()
+            .unwrap();
+        assert!(!claim(&fs, database, UUID, "app", ttl, &clock, &ids).unwrap());
+        assert_eq!(
+            remove_claim(&fs, database, UUID, &clock, &ids)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::WouldBlock
+        );
+        drop(lock);
+        assert!(claim(&fs, database, UUID, "app", ttl, &clock, &ids).unwrap());
+
+        // A lock left by a dead agent is broken once it times out
+        std::mem::forget(ClaimLock::acquire(&fs, database, UUID, &clock, &ids).unwrap());
+        clock.advance(LOCK_TIMEOUT);
+        assert!(claim(&fs, database, UUID, "app", ttl, &clock, &ids).unwrap());
+    }
+
+    #[test]
+    fn test_unreadable_claim() {
+        let path = format!("db/claims/{UUID}.json");
+        let fs = MemoryFs::default().with_file(path, "{");
+        let (clock, ids) = (clock(), SequentialIds::default());
+        let database = Path::new("db");
+        assert!(claimed_reports(&fs, database, &clock).unwrap().is_empty());
+        let ttl = Duration::from_secs(60);
+        assert!(claim(&fs, database, UUID, "app", ttl, &clock, &ids).unwrap());
+    }
+}
@@ -1,9 +1,17 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::ffi::CString;
-use std::path::Path;
-use std::ptr;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::os::fd::{AsFd, OwnedFd};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
 
-use crate::{CrashpadConfig, CrashpadError, Result};
+use crate::backend::{NativeBackend, PlatformBackend, StartRequest};
+use crate::coexistence::PlatformOptions;
+use crate::fs::{Fs, StdFs};
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+use crate::HandlerStatus;
+use crate::{CrashpadConfig, CrashpadError, HandlerInfo, Result};
 
 // Import FFI bindings
 use crashpad_rs_sys::*;
@@ -11,6 +19,15 @@ use crashpad_rs_sys::*;
 /// A Crashpad client that can be used to capture and report crashes.
 pub struct CrashpadClient {
     handle: crashpad_client_t,
+    backend: NativeBackend,
+    /// Database of the handler started by this client
+    database_path: Mutex<Option<PathBuf>>,
+    /// How the handler started by this client was started
+    #[cfg_attr(
+        any(target_os = "ios", target_os = "tvos", target_os = "watchos"),
+        allow(dead_code)
+    )]
+    handler_info: Mutex<Option<HandlerInfo>>,
 }
 
 impl CrashpadClient {
@@ -20,7 +37,12 @@ impl CrashpadClient {
         if handle.is_null() {
             return Err(CrashpadError::InitializationFailed);
         }
-        Ok(CrashpadClient { handle })
+        Ok(CrashpadClient {
+            handle,
+            backend: NativeBackend,
+            database_path: Mutex::new(None),
+            handler_info: Mutex::new(None),
+        })
     }
 
     /// Starts the Crashpad handler with a configuration.
@@ -29,58 +51,10 @@ impl CrashpadClient {
         config: &CrashpadConfig,
         annotations: &HashMap<String, String>,
     ) -> Result<()> {
-        // iOS/tvOS/watchOS use in-process handler
-        #[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
-        {
-            // Get paths
-            let database_path = config.database_path();
-            let metrics_path = config.metrics_path();
-            let url = config.url();
-
-            // Ensure directories exist
-            if let Some(parent) = database_path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
-            if let Some(parent) = metrics_path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
-
-            // Note: handler_arguments are ignored on iOS as the in-process handler
-            // has hardcoded settings. This may change in future Crashpad versions.
-            // See https://crashpad.chromium.org/bug/23
-
-            // For iOS, start in-process handler
-            self.start_in_process_handler(database_path, metrics_path, url, annotations)
-        }
-
-        #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
-        {
-            // Get handler path (with fallback to same directory)
-            let handler_path = config.handler_path()?;
-
-            // Get paths
-            let database_path = config.database_path();
-            let metrics_path = config.metrics_path();
-            let url = config.url();
-            let handler_arguments = config.handler_arguments();
-
-            // Ensure directories exist
-            if let Some(parent) = database_path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
-            if let Some(parent) = metrics_path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
-
-            self.start_handler_with_arguments(
-                &handler_path,
-                database_path,
-                metrics_path,
-                url,
-                annotations,
-                handler_arguments,
-            )
-        }
+        let info = start_with_backend(&self.backend, self.handle, config, annotations)?;
+        self.set_started(config.database_path(), info);
+        crate::redaction::set_active(config.redaction());
+        Ok(())
     }
 
     /// Starts the Crashpad handler process.
@@ -100,218 +74,376 @@ impl CrashpadClient {
         annotations: &HashMap<String, String>,
     ) -> Result<()> {
         // Call with empty handler arguments for backward compatibility
-        self.start_handler_with_arguments(
+        let request = StartRequest {
             handler_path,
             database_path,
             metrics_path,
             url,
             annotations,
-            &[],
-        )
+            handler_arguments: &[],
+            handler_log: None,
+            handler_working_directory: None,
+            handler_umask: None,
+            platform_options: &PlatformOptions::default(),
+        };
+        self.backend.start_handler(self.handle, &request)?;
+        let info = spawned(&self.backend, self.handle, request.command_line());
+        self.set_started(database_path, info);
+        Ok(())
     }
 
-    /// Starts the Crashpad handler process with custom arguments.
+    /// Stop the handler started by this client
     ///
-    /// # Arguments
-    /// * `handler_path` - Path to the Crashpad handler executable
-    /// * `database_path` - Path where crash dumps will be stored
-    /// * `metrics_path` - Path for metrics data (can be empty)
-    /// * `url` - URL to upload crash reports to (can be None for local-only)
-    /// * `annotations` - Key-value pairs to include with crash reports
-    /// * `handler_arguments` - Additional command-line arguments for the handler process
-    fn start_handler_with_arguments(
+    /// Crashes after this call aren't reported until a handler is started
+    /// again, e.g. with [`restart_handler`](Self::restart_handler).
+    ///
+    /// # Platform Behavior
+    /// - **macOS**: Crashes go to the system crash reporter again; the
+    ///   handler exits once it has no clients left
+    /// - **Linux/Android/Windows**: Crashpad's crash handlers can't be
+    ///   removed, so the handler process is terminated instead. Child
+    ///   processes registered with it stop being reported too. A handler
+    ///   that Android starts only at crash can't be stopped.
+    /// - **iOS**: Not available, the handler runs in process
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if no handler was started by
+    /// this client, [`CrashpadError::IoError`] if the handler couldn't be
+    /// terminated.
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    pub fn stop_handler(&self) -> Result<()> {
+        self.database_path()?;
+        self.backend.stop_handler(self.handle)?;
+        *self.database_path.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        *self.handler_info.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        Ok(())
+    }
+
+    /// Stop the running handler and start a new one with `config`
+    ///
+    /// Applies configuration changes, like a new upload URL or new
+    /// annotations, without restarting the application. Crashes while the
+    /// new handler starts aren't reported. Starts the handler if none is
+    /// running.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use crashpad_rs::{CrashpadClient, CrashpadConfig};
+    /// # use std::collections::HashMap;
+    /// # let client = CrashpadClient::new()?;
+    /// let config = CrashpadConfig::builder()
+    ///     .url("https://crashes.example.com/v2/submit")
+    ///     .build();
+    /// let annotations = HashMap::from([("channel".to_string(), "beta".to_string())]);
+    /// client.restart_handler(&config, &annotations)?;
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    ///
+    /// # Platform Behavior
+    /// - **macOS/Linux/Android**: See [`stop_handler`](Self::stop_handler)
+    /// - **Windows**: Not supported, Crashpad registers a process with one
+    ///   handler only; fails without stopping the handler
+    /// - **iOS**: Not available, the handler runs in process
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] on Windows, otherwise the
+    /// errors of [`stop_handler`](Self::stop_handler) and
+    /// [`start_with_config`](Self::start_with_config).
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    pub fn restart_handler(
         &self,
-        handler_path: &Path,
-        database_path: &Path,
-        metrics_path: &Path,
-        url: Option<&str>,
+        config: &CrashpadConfig,
         annotations: &HashMap<String, String>,
-        handler_arguments: &[String],
     ) -> Result<()> {
-        // Convert paths to C strings
-        let handler_path_c = path_to_cstring(handler_path)?;
-        let database_path_c = path_to_cstring(database_path)?;
-        let metrics_path_c = path_to_cstring(metrics_path)?;
-
-        let url_c = match url {
-            Some(u) => Some(
-                CString::new(u)
-                    .map_err(|_| CrashpadError::InvalidConfiguration("Invalid URL".to_string()))?,
-            ),
-            None => None,
-        };
-
-        // Convert annotations to C-compatible arrays
-        let mut keys: Vec<CString> = Vec::new();
-        let mut values: Vec<CString> = Vec::new();
-
-        for (k, v) in annotations {
-            keys.push(CString::new(k.as_str()).map_err(|_| {
-                CrashpadError::InvalidConfiguration("Invalid annotation key".to_string())
-            })?);
-            values.push(CString::new(v.as_str()).map_err(|_| {
-                CrashpadError::InvalidConfiguration("Invalid annotation value".to_string())
-            })?);
+        if cfg!(target_os = "windows") {
+            return Err(CrashpadError::InvalidConfiguration(
+                "Crashpad can't register with a second handler on Windows".to_string(),
+            ));
         }
+        let started = self.database_path().is_ok();
+        let info = restart_with_backend(&self.backend, self.handle, started, config, annotations)?;
+        self.set_started(config.database_path(), info);
+        crate::redaction::set_active(config.redaction());
+        Ok(())
+    }
 
-        // Convert to raw pointers
-        let keys_ptrs: Vec<*const std::os::raw::c_char> = keys.iter().map(|k| k.as_ptr()).collect();
-        let values_ptrs: Vec<*const std::os::raw::c_char> =
-            values.iter().map(|v| v.as_ptr()).collect();
-
-        // Convert handler arguments to C strings
-        let handler_args: Vec<CString> = handler_arguments
-            .iter()
-            .map(|arg| {
-                CString::new(arg.as_str()).map_err(|_| {
-                    CrashpadError::InvalidConfiguration(
-                        "Handler argument contains null byte".to_string(),
-                    )
-                })
-            })
-            .collect::<Result<Vec<_>>>()?;
-
-        let handler_args_ptrs: Vec<*const std::os::raw::c_char> =
-            handler_args.iter().map(|arg| arg.as_ptr()).collect();
-
-        let success = unsafe {
-            crashpad_client_start_handler(
-                self.handle,
-                handler_path_c.as_ptr(),
-                database_path_c.as_ptr(),
-                metrics_path_c.as_ptr(),
-                url_c.as_ref().map_or(ptr::null(), |u| u.as_ptr()),
-                keys_ptrs.as_ptr() as *mut *const std::os::raw::c_char,
-                values_ptrs.as_ptr() as *mut *const std::os::raw::c_char,
-                annotations.len(),
-                if handler_args_ptrs.is_empty() {
-                    ptr::null_mut()
-                } else {
-                    handler_args_ptrs.as_ptr() as *mut *const std::os::raw::c_char
-                },
-                handler_args_ptrs.len(),
-            )
-        };
+    /// Whether the handler started by this client still runs
+    ///
+    /// Crashes aren't reported while the handler is gone, e.g. after it was
+    /// killed by the OOM killer or by a user. See [`HandlerWatchdog`] to
+    /// restart it automatically.
+    ///
+    /// # Platform Behavior
+    /// - **Linux/Android**: Checks the handler's process ID, known since it
+    ///   was started. [`HandlerStatus::Unknown`] for a handler that Android
+    ///   starts only at crash.
+    /// - **Windows**: Checks whether the handler's pipe still exists
+    /// - **macOS**: Checks whether the handler's exception port is dead.
+    ///   Crashpad restarts a handler that exits on its own, so it rarely
+    ///   stays gone.
+    /// - **iOS**: Not available, the handler runs in process
+    ///
+    /// [`HandlerStatus::Unknown`] if no handler was started by this client,
+    /// or after [`stop_handler`](Self::stop_handler).
+    ///
+    /// [`HandlerWatchdog`]: crate::HandlerWatchdog
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    pub fn handler_status(&self) -> HandlerStatus {
+        let started = self.database_path().is_ok();
+        status_with_backend(&self.backend, self.handle, started)
+    }
 
-        if success {
-            Ok(())
-        } else {
-            Err(CrashpadError::HandlerStartFailed)
+    /// Process ID, command line and start time of the handler started by
+    /// this client
+    ///
+    /// For debugging a handler that started but writes no dumps: check that
+    /// the process still runs, and which database and URL it was given.
+    /// `None` if no handler was started by this client, or after
+    /// [`stop_handler`](Self::stop_handler).
+    ///
+    /// # Platform Behavior
+    /// - **Linux/Android**: The process ID is known from the start. A
+    ///   handler that Android starts only at crash has none, and its actual
+    ///   command line runs it through `app_process` or the linker.
+    /// - **Windows**: The process ID is known once the handler's pipe is up,
+    ///   as the handler starts asynchronously
+    /// - **macOS**: The process ID isn't known
+    /// - **iOS**: Not available, the handler runs in process
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    pub fn handler_info(&self) -> Option<HandlerInfo> {
+        let mut info = self.handler_info.lock().unwrap_or_else(|e| e.into_inner());
+        let info = info.as_mut()?;
+        if info.pid.is_none() {
+            info.pid = self.backend.handler_pid(self.handle);
         }
+        Some(info.clone())
     }
 
-    /// Starts the in-process handler (iOS/tvOS/watchOS only).
-    #[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
-    fn start_in_process_handler(
-        &self,
-        database_path: &Path,
-        metrics_path: &Path,
-        url: Option<&str>,
-        annotations: &HashMap<String, String>,
-    ) -> Result<()> {
-        // Convert paths to C strings
-        let database_path_c = path_to_cstring(database_path)?;
-        let _metrics_path_c = path_to_cstring(metrics_path)?;
-
-        let url_c = match url {
-            Some(u) => Some(
-                CString::new(u)
-                    .map_err(|_| CrashpadError::InvalidConfiguration("Invalid URL".to_string()))?,
-            ),
-            None => None,
-        };
+    fn set_started(&self, database_path: &Path, info: HandlerInfo) {
+        self.set_database_path(database_path);
+        *self.handler_info.lock().unwrap_or_else(|e| e.into_inner()) = Some(info);
+    }
 
-        // Convert annotations to C-compatible arrays
-        let mut keys: Vec<CString> = Vec::new();
-        let mut values: Vec<CString> = Vec::new();
-
-        for (k, v) in annotations {
-            keys.push(CString::new(k.as_str()).map_err(|_| {
-                CrashpadError::InvalidConfiguration("Invalid annotation key".to_string())
-            })?);
-            values.push(CString::new(v.as_str()).map_err(|_| {
-                CrashpadError::InvalidConfiguration("Invalid annotation value".to_string())
-            })?);
-        }
+    fn set_database_path(&self, database_path: &Path) {
+        *self.database_path.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some(database_path.to_path_buf());
+    }
 
-        // Convert to raw pointers
-        let keys_ptrs: Vec<*const std::os::raw::c_char> = keys.iter().map(|k| k.as_ptr()).collect();
-        let values_ptrs: Vec<*const std::os::raw::c_char> =
-            values.iter().map(|v| v.as_ptr()).collect();
-
-        // For iOS, we start the in-process handler
-        let success = unsafe {
-            crashpad_rs_sys::crashpad_client_start_in_process_handler(
-                self.handle,
-                database_path_c.as_ptr(),
-                url_c.as_ref().map_or(ptr::null(), |u| u.as_ptr()),
-                keys_ptrs.as_ptr() as *mut *const std::os::raw::c_char,
-                values_ptrs.as_ptr() as *mut *const std::os::raw::c_char,
-                annotations.len(),
-            )
-        };
+    /// Database of the handler started by this client
+    fn database_path(&self) -> Result<PathBuf> {
+        self.database_path
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or_else(|| {
+                CrashpadError::InvalidConfiguration(
+                    "No handler was started by this client".to_string(),
+                )
+            })
+    }
 
-        if success {
-            // Start processing pending reports first
-            unsafe {
-                crashpad_rs_sys::crashpad_client_start_processing_pending_reports();
-            }
+    /// Record the user's decision to send crash reports or not
+    ///
+    /// For consent workflows, e.g. under GDPR: the decision is the upload
+    /// setting of the database, which starts off for a new database, so
+    /// nothing is uploaded until the user agrees. It is kept across runs and
+    /// takes effect for the next upload of the running handler; withdrawing
+    /// consent keeps the reports, to be sent if the user agrees later.
+    /// Don't set [`uploads_enabled`](crate::CrashpadConfigBuilder::uploads_enabled)
+    /// in the config as well, it would override the decision at each start.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use crashpad_rs::{CrashpadClient, CrashpadConfig};
+    /// # let client = CrashpadClient::new()?;
+    /// # let config = CrashpadConfig::builder().database_path("./crashes").build();
+    /// client.start_with_config(&config, &Default::default())?;
+    /// if !client.upload_consent()? {
+    ///     let pending = client.pending_report_count()?;
+    ///     // "3 reports are waiting to be sent. Send them?"
+    ///     # let agreed = pending > 0;
+    ///     client.set_upload_consent(agreed)?;
+    /// }
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if no handler was started by
+    /// this client, or the error of writing the setting.
+    pub fn set_upload_consent(&self, consent: bool) -> Result<()> {
+        self.backend
+            .set_uploads_enabled(&self.database_path()?, consent)
+    }
 
-            // Then process any intermediate dumps from previous sessions
-            // This needs to be called after StartProcessingPendingReports
-            unsafe {
-                crashpad_rs_sys::crashpad_client_process_intermediate_dumps();
-            }
-            Ok(())
-        } else {
-            Err(CrashpadError::HandlerStartFailed)
-        }
+    /// Whether the user agreed to send crash reports, see
+    /// [`set_upload_consent`](Self::set_upload_consent)
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if no handler was started by
+    /// this client, or the error of reading the setting.
+    pub fn upload_consent(&self) -> Result<bool> {
+        crate::CrashReportDatabase::open(self.database_path()?)?.uploads_enabled()
+    }
+
+    /// The client ID the handler sends with every report, see
+    /// [`CrashReportDatabase::client_id`](crate::CrashReportDatabase::client_id)
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if no handler was started by
+    /// this client, or the error of reading the settings.
+    pub fn client_id(&self) -> Result<String> {
+        crate::CrashReportDatabase::open(self.database_path()?)?.client_id()
+    }
+
+    /// Number of reports waiting to be uploaded, for a consent dialog
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if no handler was started by
+    /// this client, or the error of reading the database.
+    pub fn pending_report_count(&self) -> Result<usize> {
+        let database = crate::CrashReportDatabase::open(self.database_path()?)?;
+        Ok(database.pending_reports()?.len())
     }
 
     /// Sets the handler IPC pipe (Windows only).
     #[cfg(target_os = "windows")]
     pub fn set_handler_ipc_pipe(&self, ipc_pipe: &str) -> Result<()> {
-        use std::ffi::OsStr;
-        use std::os::windows::ffi::OsStrExt;
-
-        let wide: Vec<u16> = OsStr::new(ipc_pipe).encode_wide().chain(Some(0)).collect();
-
-        let success = unsafe { crashpad_client_set_handler_ipc_pipe(self.handle, wide.as_ptr()) };
+        self.backend.set_handler_ipc_pipe(self.handle, ipc_pipe)
+    }
 
-        if success {
-            Ok(())
-        } else {
-            Err(CrashpadError::HandlerStartFailed)
-        }
+    /// The pipe the handler started by this client listens on (Windows only).
+    ///
+    /// Pass it to child processes so they report crashes to the same handler
+    /// with [`set_handler_ipc_pipe`](Self::set_handler_ipc_pipe) instead of
+    /// starting their own. Also returns the pipe set with
+    /// `set_handler_ipc_pipe`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use crashpad_rs::{CrashpadClient, CrashpadConfig};
+    ///
+    /// let client = CrashpadClient::new()?;
+    /// let config = CrashpadConfig::builder().database_path("./crashes").build();
+    /// client.start_with_config(&config, &Default::default())?;
+    ///
+    /// let pipe = client.get_handler_ipc_pipe()?;
+    /// std::process::Command::new("child.exe")
+    ///     .env("CRASHPAD_PIPE", pipe)
+    ///     .spawn()?;
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if no handler was started or
+    /// registered.
+    #[cfg(target_os = "windows")]
+    pub fn get_handler_ipc_pipe(&self) -> Result<String> {
+        self.backend.handler_ipc_pipe(self.handle)
     }
 
     /// Sets the handler Mach service (macOS/iOS only).
     #[cfg(any(target_os = "macos", target_os = "ios"))]
     pub fn set_handler_mach_service(&self, service_name: &str) -> Result<()> {
-        let service_name_c = CString::new(service_name)
-            .map_err(|_| CrashpadError::InvalidConfiguration("Invalid service name".to_string()))?;
-
-        let success = unsafe {
-            crashpad_client_set_handler_mach_service(self.handle, service_name_c.as_ptr())
-        };
-
-        if success {
-            Ok(())
-        } else {
-            Err(CrashpadError::HandlerStartFailed)
-        }
+        crate::backend::set_handler_mach_service(self.handle, service_name)
     }
 
     /// Use system default crash handler (macOS only).
     #[cfg(target_os = "macos")]
     pub fn use_system_default_handler(&self) -> Result<()> {
-        let success = unsafe { crashpad_client_use_system_default_handler(self.handle) };
+        self.backend.use_system_default_handler(self.handle)
+    }
 
-        if success {
-            Ok(())
-        } else {
-            Err(CrashpadError::HandlerStartFailed)
-        }
+    /// Create a socket pair connecting a handler to its clients (Linux/Android only).
+    ///
+    /// Returns the server end, for
+    /// [`start_handler_for_client`](Self::start_handler_for_client), and the
+    /// client end, for [`set_handler_socket`](Self::set_handler_socket).
+    /// Credential passing is enabled, so one client end can be shared by any
+    /// number of processes; the handler tells them apart by process ID.
+    ///
+    /// Both ends are close-on-exec. Clear `FD_CLOEXEC` on the client end (or
+    /// `dup2` it) in children that `exec`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn handler_socket_pair() -> Result<(OwnedFd, OwnedFd)> {
+        crate::backend::handler_socket_pair()
+    }
+
+    /// Start a handler that serves other processes (Linux/Android only).
+    ///
+    /// Like [`start_with_config`](Self::start_with_config), but the handler
+    /// serves the clients of `server_socket` (from
+    /// [`handler_socket_pair`](Self::handler_socket_pair)) instead of this
+    /// process, which installs no signal handlers. A daemon starts one
+    /// handler this way and hands the client end to its sandboxed children,
+    /// which register with [`set_handler_socket`](Self::set_handler_socket)
+    /// without spawning anything.
+    ///
+    /// `server_socket` is closed once the handler has its own copy.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use crashpad_rs::{CrashpadClient, CrashpadConfig};
+    ///
+    /// let config = CrashpadConfig::builder().database_path("./crashes").build();
+    /// let (server, client) = CrashpadClient::handler_socket_pair()?;
+    /// CrashpadClient::start_handler_for_client(&config, &Default::default(), server)?;
+    /// // Pass `client` to the child processes
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn start_handler_for_client(
+        config: &CrashpadConfig,
+        annotations: &HashMap<String, String>,
+        server_socket: OwnedFd,
+    ) -> Result<()> {
+        let backend = NativeBackend;
+        with_start_request(&backend, config, annotations, |request| {
+            backend.start_handler_for_client(request, server_socket.as_fd())
+        })
+    }
+
+    /// The socket the handler started by this client serves, and the
+    /// handler's process ID (Linux/Android only).
+    ///
+    /// Pass both to child processes so they report crashes to the same
+    /// handler with [`set_handler_socket`](Self::set_handler_socket). The
+    /// socket is a duplicate, close-on-exec like those of
+    /// [`handler_socket_pair`](Self::handler_socket_pair).
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if no handler was started.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn handler_socket(&self) -> Result<(OwnedFd, u32)> {
+        self.backend.handler_socket()
+    }
+
+    /// Report crashes to an already running handler over `socket` (Linux/Android only).
+    ///
+    /// Registers this process with the handler of another process instead
+    /// of starting one: installs the signal handlers, which request dumps
+    /// over the socket, and takes ownership of `socket`. No process is
+    /// spawned, so this works in sandboxes that forbid `fork`/`exec`.
+    ///
+    /// `handler_pid` is the handler's process ID, which is allowed to
+    /// `ptrace` this process under Yama. `None` asks the handler for it over
+    /// the socket; `Some(0)` skips the permission.
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::IpcRegistrationFailed`] if the signal handlers
+    /// couldn't be installed.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_handler_socket(&self, socket: OwnedFd, handler_pid: Option<u32>) -> Result<()> {
+        self.backend
+            .set_handler_socket(self.handle, socket, handler_pid)
     }
 
     /// Process intermediate dumps (iOS only).
@@ -319,11 +451,72 @@ impl CrashpadClient {
     /// Converts intermediate dumps to minidumps. This should be called:
     /// - On app startup to process crashes from previous sessions
     /// - After StartProcessingPendingReports has been called
+    ///
+    /// [`start_with_config`](Self::start_with_config) already does both.
     #[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
     pub fn process_intermediate_dumps(&self) {
-        unsafe {
-            crashpad_rs_sys::crashpad_client_process_intermediate_dumps();
-        }
+        self.backend.process_intermediate_dumps();
+    }
+
+    /// Process intermediate dumps, adding `annotations` to the minidumps
+    /// (iOS only).
+    ///
+    /// Like [`process_intermediate_dumps`](Self::process_intermediate_dumps),
+    /// for annotations that are only known after startup, such as the
+    /// previous session's state.
+    #[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
+    pub fn process_intermediate_dumps_with_annotations(
+        &self,
+        annotations: &HashMap<String, String>,
+    ) -> Result<()> {
+        self.backend
+            .process_intermediate_dumps_with_annotations(annotations)
+    }
+
+    /// Process a single intermediate dump file (iOS only).
+    ///
+    /// Use it with [`pending_intermediate_dumps`](Self::pending_intermediate_dumps)
+    /// to limit how many dumps are converted at a time, e.g. to keep app
+    /// startup short:
+    ///
+    /// ```no_run
+    /// # use crashpad_rs::CrashpadClient;
+    /// # let client = CrashpadClient::new()?;
+    /// for dump in CrashpadClient::pending_intermediate_dumps("./crashpad_db")?
+    ///     .into_iter()
+    ///     .take(2)
+    /// {
+    ///     client.process_intermediate_dump(&dump, &Default::default())?;
+    /// }
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    #[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
+    pub fn process_intermediate_dump<P: AsRef<Path>>(
+        &self,
+        path: P,
+        annotations: &HashMap<String, String>,
+    ) -> Result<()> {
+        self.backend
+            .process_intermediate_dump(path.as_ref(), annotations)
+    }
+
+    /// Intermediate dumps in the database at `database_path` that haven't
+    /// been processed yet, oldest first (iOS only).
+    #[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
+    pub fn pending_intermediate_dumps<P: AsRef<Path>>(
+        database_path: P,
+    ) -> Result<Vec<std::path::PathBuf>> {
+        crate::backend::pending_intermediate_dumps(database_path.as_ref())
+    }
+
+    /// Start uploading pending reports (iOS only).
+    ///
+    /// [`start_with_config`](Self::start_with_config) starts uploads with
+    /// [`UploadBehavior::WhenAppIsActive`](crate::UploadBehavior::WhenAppIsActive).
+    /// Call this to upload right away instead, e.g. from a background task.
+    #[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
+    pub fn start_processing_pending_reports(&self, behavior: crate::UploadBehavior) {
+        self.backend.start_processing_pending_reports_with(behavior);
     }
 
     /// Capture a crash dump without actually crashing the process.
@@ -356,12 +549,151 @@ impl CrashpadClient {
     /// A handler must have been installed before calling this method.
     /// The captured context will be from the point where this function is called.
     pub fn dump_without_crash(&self) {
-        unsafe {
-            crashpad_rs_sys::crashpad_dump_without_crash();
+        self.backend.dump_without_crash();
+    }
+
+    /// Capture a dump without crashing, keeping clear of real-time threads
+    ///
+    /// Like [`dump_without_crash`](Self::dump_without_crash), but first
+    /// waits, up to the time box of `capture`, until no thread registered
+    /// with [`realtime::register_thread`](crate::realtime::register_thread)
+    /// is inside its callback, and annotates the dump with the threads'
+    /// state. See the [`realtime`](crate::realtime) module.
+    ///
+    /// Returns how long the snapshot waited and kept the process suspended.
+    pub fn dump_without_crash_realtime(
+        &self,
+        capture: &crate::realtime::RealtimeCapture,
+    ) -> crate::realtime::SnapshotTiming {
+        crate::realtime::snapshot(capture, || self.backend.dump_without_crash())
+    }
+
+    /// Capture a crash dump of another thread without crashing the process.
+    ///
+    /// Like [`dump_without_crash`](Self::dump_without_crash), but the dump's
+    /// exception context is the register state of the thread `thread_id`
+    /// instead of the calling thread. Use it from a watchdog to see where a
+    /// hung thread is stuck. Obtain the ID with [`current_thread_id`] on the
+    /// thread to watch.
+    ///
+    /// The target thread is suspended while the dump is written, so that its
+    /// stack matches the captured context. Passing the calling thread's ID
+    /// behaves like `dump_without_crash`.
+    ///
+    /// # Platform Behavior
+    ///
+    /// - **Windows**: The context is read with `GetThreadContext`
+    /// - **macOS**: The context is read with `thread_get_state` and the
+    ///   dump is written via `SimulateCrash`
+    ///
+    /// The exception stream names the calling thread; its context is the
+    /// target thread's. The stacks of all threads are captured as usual.
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::IoError`] with the OS error if the thread doesn't
+    /// exist or can't be suspended.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use crashpad_rs::CrashpadClient;
+    /// # use std::sync::Arc;
+    /// # let client = Arc::new(CrashpadClient::new().unwrap());
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    /// std::thread::spawn(move || {
+    ///     tx.send(crashpad_rs::current_thread_id()).unwrap();
+    ///     // ... work that may hang ...
+    /// });
+    ///
+    /// let worker = rx.recv().unwrap();
+    /// // Later, when the worker missed its heartbeat:
+    /// client.dump_without_crash_of_thread(worker)?;
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    pub fn dump_without_crash_of_thread(&self, thread_id: u64) -> Result<()> {
+        self.backend.dump_without_crash_of_thread(thread_id)
+    }
+
+    /// Include `len` bytes at `ptr` in every dump written from now on.
+    ///
+    /// Crashpad captures the stacks of all threads and memory they point to;
+    /// other data, like a ring buffer of recent log lines, is only in a dump
+    /// if registered here. The handler reads the range when it writes a
+    /// dump, so it sees the contents at the time of the crash.
+    ///
+    /// This is safe to call with any pointer: the range is read from outside
+    /// the process and unreadable ranges are skipped. But if it is freed
+    /// while registered, dumps contain whatever is allocated there next; use
+    /// [`add_memory_slice`](Self::add_memory_slice) for buffers that live for
+    /// the rest of the process, or remove the range first with
+    /// [`remove_memory_range`](Self::remove_memory_range).
+    ///
+    /// The ranges belong to the process rather than this client, and apply
+    /// to dumps of all clients.
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if `len` is 0, or
+    /// [`limits::MAX_MEMORY_RANGES`](crate::limits::MAX_MEMORY_RANGES) ranges
+    /// are registered already.
+    pub fn add_memory_range(&self, ptr: *const u8, len: usize) -> Result<()> {
+        if len == 0 {
+            return Err(CrashpadError::InvalidConfiguration(
+                "Empty memory range".to_string(),
+            ));
         }
+        if !unsafe { crashpad_add_extra_memory_range(ptr.cast(), len) } {
+            return Err(CrashpadError::InvalidConfiguration(format!(
+                "More than {} memory ranges",
+                crate::limits::MAX_MEMORY_RANGES
+            )));
+        }
+        Ok(())
+    }
+
+    /// Include `bytes` in every dump written from now on.
+    ///
+    /// Like [`add_memory_range`](Self::add_memory_range), for a buffer that
+    /// lives for the rest of the process.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use crashpad_rs::CrashpadClient;
+    /// # let client = CrashpadClient::new().unwrap();
+    /// // Recent log lines, written by the application's logger
+    /// let log_ring: &'static mut [u8] = Box::leak(vec![0u8; 16 * 1024].into_boxed_slice());
+    /// client.add_memory_slice(log_ring)?;
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    pub fn add_memory_slice(&self, bytes: &'static [u8]) -> Result<()> {
+        self.add_memory_range(bytes.as_ptr(), bytes.len())
+    }
+
+    /// Stop including a range added with
+    /// [`add_memory_range`](Self::add_memory_range) with the same `ptr` and
+    /// `len`
+    ///
+    /// Returns `false` if no such range was registered.
+    pub fn remove_memory_range(&self, ptr: *const u8, len: usize) -> bool {
+        unsafe { crashpad_remove_extra_memory_range(ptr.cast(), len) }
     }
 }
 
+/// ID of the calling thread, for
+/// [`CrashpadClient::dump_without_crash_of_thread`]
+///
+/// # Platform Behavior
+///
+/// - **Windows**: `GetCurrentThreadId()`
+/// - **macOS**: `pthread_threadid_np()`, the ID shown in crash reports
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+pub fn current_thread_id() -> u64 {
+    unsafe { crashpad_current_thread_id() }
+}
+
 impl Drop for CrashpadClient {
     fn drop(&mut self) {
         unsafe {
@@ -374,10 +706,676 @@ impl Drop for CrashpadClient {
 unsafe impl Send for CrashpadClient {}
 unsafe impl Sync for CrashpadClient {}
 
-fn path_to_cstring(path: &Path) -> Result<CString> {
-    let path_str = path
-        .to_str()
-        .ok_or_else(|| CrashpadError::InvalidConfiguration("Invalid path".to_string()))?;
-    CString::new(path_str)
-        .map_err(|_| CrashpadError::InvalidConfiguration("Path contains null byte".to_string()))
+/// Platform-agnostic part of [`CrashpadClient::start_with_config`].
+fn start_with_backend<B: PlatformBackend>(
+    backend: &B,
+    handle: crashpad_client_t,
+    config: &CrashpadConfig,
+    annotations: &HashMap<String, String>,
+) -> Result<HandlerInfo> {
+    let mut command_line = Vec::new();
+    with_start_request(backend, config, annotations, |request| {
+        backend.start_handler(handle, request)?;
+        command_line = request.command_line();
+        Ok(())
+    })?;
+    Ok(spawned(backend, handle, command_line))
+}
+
+/// [`HandlerInfo`] of a handler just started with `command_line`
+fn spawned<B: PlatformBackend>(
+    backend: &B,
+    handle: crashpad_client_t,
+    command_line: Vec<String>,
+) -> HandlerInfo {
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    let pid = backend.handler_pid(handle);
+    #[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
+    let pid = {
+        let _ = (backend, handle);
+        None
+    };
+    HandlerInfo {
+        pid,
+        command_line,
+        spawned_at: SystemTime::now(),
+    }
+}
+
+/// Platform-agnostic part of [`CrashpadClient::restart_handler`]: stop the
+/// handler if one was `started`, then start one with `config`.
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+fn restart_with_backend<B: PlatformBackend>(
+    backend: &B,
+    handle: crashpad_client_t,
+    started: bool,
+    config: &CrashpadConfig,
+    annotations: &HashMap<String, String>,
+) -> Result<HandlerInfo> {
+    if started {
+        backend.stop_handler(handle)?;
+    }
+    start_with_backend(backend, handle, config, annotations)
+}
+
+/// Platform-agnostic part of [`CrashpadClient::handler_status`]: only a
+/// handler that was `started` is checked.
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+fn status_with_backend<B: PlatformBackend>(
+    backend: &B,
+    handle: crashpad_client_t,
+    started: bool,
+) -> HandlerStatus {
+    if started {
+        backend.handler_status(handle)
+    } else {
+        HandlerStatus::Unknown
+    }
+}
+
+/// Verify the handler, complete the annotations and prepare the database
+/// of `config`, then start the handler with `start`.
+pub(crate) fn with_start_request<B: PlatformBackend>(
+    backend: &B,
+    config: &CrashpadConfig,
+    annotations: &HashMap<String, String>,
+    start: impl FnOnce(&StartRequest<'_>) -> Result<()>,
+) -> Result<()> {
+    let mut startup = crate::startup::Startup::new(config.startup_budget());
+
+    // Get handler path (with fallback to same directory).
+    // Empty on iOS/tvOS/watchOS, which use the in-process handler.
+    let handler_path = config.handler_path()?;
+
+    // Refuse to execute a handler that fails verification
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    crate::verify_handler(&handler_path, config.handler_verification())?;
+
+    // Record which handler produced the reports, if requested
+    // (not available on iOS/tvOS/watchOS, which have no handler executable,
+    // or for a linked handler, which would run the app with --version).
+    // Running the handler takes a while, so over budget the version is set
+    // as a module annotation later.
+    let mut annotations = Cow::Borrowed(annotations);
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    if config.handler_version_annotation() && !config.uses_linked_handler() {
+        if !startup.over_budget() {
+            if let Ok(version) = crate::handler_version(&handler_path) {
+                annotations
+                    .to_mut()
+                    .insert("crashpad_handler_version".to_string(), version.to_string());
+            }
+        } else {
+            let handler_path = handler_path.clone();
+            let redaction = config.redaction().cloned();
+            let breakpad_fields = config.breakpad_fields().cloned();
+            startup.defer(crate::DeferredTask::HandlerVersion, move || {
+                if let Ok(version) = crate::handler_version(&handler_path) {
+                    crate::lazy_annotation::set(
+                        "crashpad_handler_version",
+                        &version.to_string(),
+                        redaction.as_ref(),
+                        breakpad_fields.as_ref(),
+                    );
+                }
+            });
+        }
+    }
+
+    if config.diagnostics_annotation() {
+        let codes: Vec<_> = crate::startup_diagnostics()
+            .into_iter()
+            .map(|diagnostic| diagnostic.code)
+            .collect();
+        if !codes.is_empty() {
+            annotations
+                .to_mut()
+                .insert("crashpad_diagnostics".to_string(), codes.join(","));
+        }
+    }
+
+    if config.container_annotations() {
+        for (key, value) in crate::container_annotations() {
+            annotations.to_mut().entry(key).or_insert(value);
+        }
+    }
+
+    if config.timezone_annotations() {
+        for (key, value) in crate::timezone_annotations() {
+            annotations.to_mut().entry(key).or_insert(value);
+        }
+    }
+
+    if config.stack_annotations() {
+        for (key, value) in crate::stack_annotations() {
+            annotations.to_mut().entry(key).or_insert(value);
+        }
+    }
+
+    #[cfg(feature = "machine-id")]
+    if config.machine_id_annotation() {
+        let machine_id = crate::machine_id::raw_machine_id().and_then(|raw| {
+            crate::machine_id::machine_id(&StdFs, config.database_path(), &raw)
+                .ok()
+                .flatten()
+        });
+        if let Some(machine_id) = machine_id {
+            annotations
+                .to_mut()
+                .entry(crate::machine_id::ANNOTATION.to_string())
+                .or_insert(machine_id);
+        }
+    }
+
+    // Redact after all annotations are added so none bypasses the rules,
+    // but before renaming so rules match the keys the application uses
+    if let Some(rules) = config.redaction() {
+        annotations = Cow::Owned(rules.redact_annotations(&annotations));
+    }
+    if let Some(fields) = config.breakpad_fields() {
+        annotations = Cow::Owned(fields.apply(&annotations));
+    }
+
+    startup.end_phase(|timing| &mut timing.handler_resolution);
+
+    // Get paths
+    let database_path = config.database_path();
+    let metrics_path = config.metrics_path();
+
+    // Ensure directories exist
+    crate::failpoints::fail_point!(crate::failpoints::DATABASE_CREATE, |msg: Option<String>| {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            msg.unwrap_or_else(|| "injected database failure".to_string()),
+        )
+        .into())
+    });
+    create_parent_dirs(&StdFs, &[database_path, metrics_path])?;
+
+    if let Some(client_id) = config.client_id() {
+        let uuid = crate::settings::parse_uuid(client_id).ok_or_else(|| {
+            CrashpadError::InvalidConfiguration(format!("Client ID {client_id} isn't a UUID"))
+        })?;
+        crate::settings::write_client_id(&StdFs, database_path, &uuid)?;
+    }
+
+    if let Some(enabled) = config.uploads_enabled() {
+        backend.set_uploads_enabled(database_path, enabled)?;
+    }
+
+    startup.end_phase(|timing| &mut timing.settings_io);
+
+    if let Some(max_age) = config.janitor_max_age() {
+        let database_path = database_path.to_path_buf();
+        let janitor = move || {
+            crate::janitor::record_cleanup(crate::clean_database(&database_path, max_age));
+        };
+        if startup.over_budget() {
+            startup.defer(crate::DeferredTask::Janitor, janitor);
+        } else {
+            janitor();
+        }
+    }
+
+    // Before the new handler rotates the metrics of the previous one
+    let _ = crate::dropped::journal_previous_run(database_path, metrics_path);
+
+    if let Some(policy) = config.expiry_policy().copied() {
+        let database_path = database_path.to_path_buf();
+        let expire = move || {
+            let _ = crate::CrashReportDatabase::open(&database_path)
+                .and_then(|database| database.expire_stale_reports(&policy));
+        };
+        if startup.over_budget() {
+            startup.defer(crate::DeferredTask::ReportExpiry, expire);
+        } else {
+            expire();
+        }
+    }
+
+    if let Some(policy) = config.burst_policy().copied() {
+        let database_path = database_path.to_path_buf();
+        let coalesce = move || {
+            let summary = crate::CrashReportDatabase::open(&database_path)
+                .and_then(|database| database.coalesce_burst(&policy));
+            crate::burst::record_burst(summary.ok().flatten());
+        };
+        if startup.over_budget() {
+            startup.defer(crate::DeferredTask::BurstCoalescing, coalesce);
+        } else {
+            coalesce();
+        }
+    }
+
+    startup.end_phase(|timing| &mut timing.maintenance);
+
+    // Start a new log file if the old one grew too large
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    let handler_log = match config.handler_log() {
+        Some(log) => {
+            let path = log.path(database_path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            log.rotate(&path)?;
+            Some(path)
+        }
+        None => None,
+    };
+    #[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
+    let handler_log: Option<std::path::PathBuf> = None;
+
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    if let Some(dir) = config.handler_working_directory() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    startup.end_phase(|timing| &mut timing.settings_io);
+
+    start(&StartRequest {
+        handler_path: &handler_path,
+        database_path,
+        metrics_path,
+        url: config.url(),
+        annotations: &annotations,
+        handler_arguments: config.handler_arguments(),
+        handler_log: handler_log.as_deref(),
+        handler_working_directory: config.handler_working_directory(),
+        handler_umask: config.handler_umask(),
+        platform_options: config.platform_options(),
+    })
+    .map_err(|error| with_handler_trace(error, config))?;
+    startup.end_phase(|timing| &mut timing.spawn);
+    startup.finish();
+
+    crate::lazy_annotation::spawn(
+        config.lazy_annotations(),
+        &annotations,
+        config.redaction(),
+        config.breakpad_fields(),
+    );
+    Ok(())
+}
+
+/// Add how the handler of `config` was found to a failure to start it.
+fn with_handler_trace(error: CrashpadError, config: &CrashpadConfig) -> CrashpadError {
+    match error {
+        CrashpadError::HandlerNotFound { path, trace } if trace.is_empty() => {
+            CrashpadError::HandlerNotFound {
+                path,
+                trace: config.resolve_handler_with_trace().candidates,
+            }
+        }
+        CrashpadError::HandlerSpawnFailed {
+            path,
+            os_error,
+            trace,
+        } if trace.is_empty() => CrashpadError::HandlerSpawnFailed {
+            path,
+            os_error,
+            trace: config.resolve_handler_with_trace().candidates,
+        },
+        error => error,
+    }
+}
+
+/// Create the directories `paths` are in.
+fn create_parent_dirs(fs: &impl Fs, paths: &[&Path]) -> std::io::Result<()> {
+    for parent in paths.iter().filter_map(|path| path.parent()) {
+        fs.create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::stub::StubBackend;
+    use tempfile::TempDir;
+
+    fn test_config(temp_dir: &TempDir) -> CrashpadConfig {
+        CrashpadConfig::builder()
+            .handler_path("/path/to/crashpad_handler")
+            .database_path(temp_dir.path().join("app").join("crashpad_db"))
+            .metrics_path(temp_dir.path().join("app").join("crashpad_metrics"))
+            .url("https://crashes.example.com/submit")
+            .rate_limit(false)
+            .coexist_with_clr()
+            .build()
+    }
+
+    #[test]
+    fn test_memory_ranges() {
+        static BUFFER: [u8; 64] = [0; 64];
+        let client = CrashpadClient::new().unwrap();
+
+        assert!(client.add_memory_range(BUFFER.as_ptr(), 0).is_err());
+        client.add_memory_slice(&BUFFER).unwrap();
+        assert!(client.remove_memory_range(BUFFER.as_ptr(), BUFFER.len()));
+        assert!(!client.remove_memory_range(BUFFER.as_ptr(), BUFFER.len()));
+    }
+
+    #[test]
+    fn test_upload_consent() {
+        let temp_dir = TempDir::new().unwrap();
+        let client = CrashpadClient::new().unwrap();
+        assert!(matches!(
+            client.set_upload_consent(true),
+            Err(CrashpadError::InvalidConfiguration(_))
+        ));
+
+        client.set_database_path(&temp_dir.path().join("crashpad_db"));
+        assert!(!client.upload_consent().unwrap());
+        assert_eq!(client.pending_report_count().unwrap(), 0);
+        client.set_upload_consent(true).unwrap();
+        assert!(client.upload_consent().unwrap());
+    }
+
+    #[test]
+    fn test_create_parent_dirs() {
+        let fs = crate::fs::memory::MemoryFs::default();
+        create_parent_dirs(&fs, &[Path::new("app/crashpad_db"), Path::new("metrics")]).unwrap();
+        assert!(fs.exists(Path::new("app")));
+        assert!(!fs.exists(Path::new("app/crashpad_db")));
+    }
+
+    #[test]
+    fn test_start_with_backend_redacts_annotations() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = CrashpadConfig::builder()
+            .handler_path("/path/to/crashpad_handler")
+            .database_path(temp_dir.path().join("crashpad_db"))
+            .redaction(crate::RedactionRules::default())
+            .build();
+        let annotations = HashMap::from([
+            ("version".to_string(), "1.0.0".to_string()),
+            ("auth_token".to_string(), "abc123".to_string()),
+        ]);
+
+        let backend = StubBackend::default();
+        start_with_backend(&backend, std::ptr::null_mut(), &config, &annotations).unwrap();
+
+        let starts = backend.starts.borrow();
+        assert_eq!(starts[0].annotations["version"], "1.0.0");
+        assert_eq!(starts[0].annotations["auth_token"], crate::REDACTED);
+    }
+
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    #[test]
+    fn test_start_with_backend_returns_handler_info() {
+        let temp_dir = TempDir::new().unwrap();
+        let database_path = temp_dir.path().join("crashpad_db");
+        let backend = StubBackend {
+            handler_pid: Some(4242),
+            ..Default::default()
+        };
+        let config = CrashpadConfig::builder()
+            .handler_path("/path/to/crashpad_handler")
+            .database_path(&database_path)
+            .url("https://crashes.example.com/v2")
+            .build();
+
+        let before = SystemTime::now();
+        let info =
+            start_with_backend(&backend, std::ptr::null_mut(), &config, &HashMap::new()).unwrap();
+        assert_eq!(info.pid, Some(4242));
+        assert!(info.spawned_at >= before);
+        assert_eq!(info.command_line[0], "/path/to/crashpad_handler");
+        assert!(info
+            .command_line
+            .contains(&format!("--database={}", database_path.display())));
+        assert!(info
+            .command_line
+            .contains(&"--url=https://crashes.example.com/v2".to_string()));
+    }
+
+    #[test]
+    fn test_start_with_backend_sets_uploads_enabled() {
+        let temp_dir = TempDir::new().unwrap();
+        let database_path = temp_dir.path().join("crashpad_db");
+        let backend = StubBackend::default();
+
+        let config = CrashpadConfig::builder()
+            .handler_path("/path/to/crashpad_handler")
+            .database_path(&database_path)
+            .build();
+        start_with_backend(&backend, std::ptr::null_mut(), &config, &HashMap::new()).unwrap();
+        assert!(backend.uploads_enabled.borrow().is_empty());
+
+        let config = CrashpadConfig::builder()
+            .handler_path("/path/to/crashpad_handler")
+            .database_path(&database_path)
+            .uploads_enabled(true)
+            .build();
+        start_with_backend(&backend, std::ptr::null_mut(), &config, &HashMap::new()).unwrap();
+        assert_eq!(
+            *backend.uploads_enabled.borrow(),
+            vec![(database_path.clone(), true)]
+        );
+    }
+
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    #[test]
+    fn test_restart_with_backend() {
+        let temp_dir = TempDir::new().unwrap();
+        let backend = StubBackend::default();
+        let config = CrashpadConfig::builder()
+            .handler_path("/path/to/crashpad_handler")
+            .database_path(temp_dir.path().join("crashpad_db"))
+            .url("https://crashes.example.com/v2")
+            .build();
+
+        restart_with_backend(
+            &backend,
+            std::ptr::null_mut(),
+            false,
+            &config,
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(*backend.stops.borrow(), 0);
+        restart_with_backend(
+            &backend,
+            std::ptr::null_mut(),
+            true,
+            &config,
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(*backend.stops.borrow(), 1);
+        let starts = backend.starts.borrow();
+        assert_eq!(starts.len(), 2);
+        assert_eq!(
+            starts[1].url.as_deref(),
+            Some("https://crashes.example.com/v2")
+        );
+    }
+
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    #[test]
+    fn test_status_with_backend() {
+        let backend = StubBackend::default();
+        assert_eq!(
+            status_with_backend(&backend, std::ptr::null_mut(), true),
+            HandlerStatus::Running
+        );
+
+        *backend.handler_status.borrow_mut() = Some(HandlerStatus::Exited);
+        assert_eq!(
+            status_with_backend(&backend, std::ptr::null_mut(), true),
+            HandlerStatus::Exited
+        );
+        // A stopped handler isn't reported as exited
+        assert_eq!(
+            status_with_backend(&backend, std::ptr::null_mut(), false),
+            HandlerStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn test_start_with_backend_writes_client_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let database_path = temp_dir.path().join("crashpad_db");
+        let backend = StubBackend::default();
+
+        let config = CrashpadConfig::builder()
+            .handler_path("/path/to/crashpad_handler")
+            .database_path(&database_path)
+            .client_id("not-a-uuid")
+            .build();
+        assert!(matches!(
+            start_with_backend(&backend, std::ptr::null_mut(), &config, &HashMap::new()),
+            Err(CrashpadError::InvalidConfiguration(_))
+        ));
+
+        let config = CrashpadConfig::builder()
+            .handler_path("/path/to/crashpad_handler")
+            .database_path(&database_path)
+            .client_id("00112233-4455-6677-8899-aabbccddeeff")
+            .build();
+        start_with_backend(&backend, std::ptr::null_mut(), &config, &HashMap::new()).unwrap();
+        assert!(database_path.join("settings.dat").exists());
+    }
+
+    #[test]
+    fn test_start_with_backend_runs_janitor() {
+        let temp_dir = TempDir::new().unwrap();
+        let database_path = temp_dir.path().join("crashpad_db");
+        let config = CrashpadConfig::builder()
+            .handler_path("/path/to/crashpad_handler")
+            .database_path(&database_path)
+            .database_janitor(std::time::Duration::from_secs(3600))
+            .build();
+
+        let backend = StubBackend::default();
+        start_with_backend(&backend, std::ptr::null_mut(), &config, &HashMap::new()).unwrap();
+
+        assert_eq!(crate::last_cleanup(), Some(crate::CleanupReport::default()));
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn test_start_with_backend_rotates_handler_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let database_path = temp_dir.path().join("crashpad_db");
+        let config = CrashpadConfig::builder()
+            .handler_path("/path/to/crashpad_handler")
+            .database_path(&database_path)
+            .handler_log(crate::HandlerLog::in_database().max_size(1))
+            .build();
+        let log_path = database_path.join("crashpad_handler.log");
+        std::fs::create_dir_all(&database_path).unwrap();
+        std::fs::write(&log_path, "previous run").unwrap();
+
+        let backend = StubBackend::default();
+        start_with_backend(&backend, std::ptr::null_mut(), &config, &HashMap::new()).unwrap();
+
+        assert_eq!(
+            backend.starts.borrow()[0].handler_log.as_deref(),
+            Some(log_path.as_path())
+        );
+        assert!(!log_path.exists());
+        assert!(database_path.join("crashpad_handler.log.1").exists());
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn test_start_with_backend_passes_process_state() {
+        let temp_dir = TempDir::new().unwrap();
+        let working_directory = temp_dir.path().join("handler_cwd");
+        let config = CrashpadConfig::builder()
+            .handler_path("/path/to/crashpad_handler")
+            .database_path(temp_dir.path().join("crashpad_db"))
+            .handler_working_directory(&working_directory)
+            .handler_umask(0o1027)
+            .build();
+
+        let backend = StubBackend::default();
+        start_with_backend(&backend, std::ptr::null_mut(), &config, &HashMap::new()).unwrap();
+
+        let starts = backend.starts.borrow();
+        assert_eq!(
+            starts[0].handler_working_directory.as_deref(),
+            Some(working_directory.as_path())
+        );
+        assert_eq!(starts[0].handler_umask, Some(0o027));
+        assert!(working_directory.is_dir());
+    }
+
+    #[test]
+    fn test_start_with_backend_maps_breakpad_fields() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = CrashpadConfig::builder()
+            .handler_path("/path/to/crashpad_handler")
+            .database_path(temp_dir.path().join("crashpad_db"))
+            .breakpad_fields(crate::BreakpadFields::default())
+            .build();
+        let annotations = HashMap::from([("product".to_string(), "MyApp".to_string())]);
+
+        let backend = StubBackend::default();
+        start_with_backend(&backend, std::ptr::null_mut(), &config, &annotations).unwrap();
+
+        let starts = backend.starts.borrow();
+        assert_eq!(
+            starts[0].annotations,
+            HashMap::from([("prod".to_string(), "MyApp".to_string())])
+        );
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn test_start_with_backend_forwards_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+        let mut annotations = HashMap::new();
+        annotations.insert("version".to_string(), "1.0.0".to_string());
+
+        let backend = StubBackend::default();
+        start_with_backend(&backend, std::ptr::null_mut(), &config, &annotations).unwrap();
+
+        let starts = backend.starts.borrow();
+        assert_eq!(starts.len(), 1);
+        assert_eq!(
+            starts[0].handler_path,
+            Path::new("/path/to/crashpad_handler")
+        );
+        assert_eq!(starts[0].database_path, config.database_path());
+        assert_eq!(starts[0].metrics_path, config.metrics_path());
+        assert_eq!(
+            starts[0].url.as_deref(),
+            Some("https://crashes.example.com/submit")
+        );
+        assert_eq!(starts[0].annotations, annotations);
+        assert_eq!(starts[0].handler_arguments, vec!["--no-rate-limit"]);
+        assert_eq!(&starts[0].platform_options, config.platform_options());
+    }
+
+    #[test]
+    fn test_start_with_backend_creates_parent_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+
+        let backend = StubBackend::default();
+        start_with_backend(&backend, std::ptr::null_mut(), &config, &HashMap::new()).unwrap();
+
+        assert!(temp_dir.path().join("app").is_dir());
+    }
+
+    #[test]
+    fn test_start_with_backend_propagates_failure() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(&temp_dir);
+
+        let backend = StubBackend {
+            fail_start: true,
+            ..Default::default()
+        };
+        let result = start_with_backend(&backend, std::ptr::null_mut(), &config, &HashMap::new());
+
+        assert!(matches!(
+            result,
+            Err(CrashpadError::HandlerSpawnFailed { os_error: None, ref trace, .. })
+                if trace[0].source == crate::HandlerSource::Config
+        ));
+    }
 }
@@ -0,0 +1,104 @@
+//! Time and ID sources of the database and session code.
+//!
+//! Code that depends on the current time, like upload backoff, claim and
+//! report expiry, quotas and cleanup by age, reads it from a [`Clock`], and
+//! code that names temporary files or multipart boundaries takes the
+//! random part from an [`IdGen`]. The public API passes [`SystemClock`] and
+//! [`RandomIds`], like it passes [`StdFs`](crate::fs::StdFs); tests pass
+//! [`manual::ManualClock`] and [`manual::SequentialIds`], so they can move
+//! time forward instead of sleeping.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::SystemTime;
+
+/// The source of the current time.
+pub(crate) trait Clock {
+    fn now(&self) -> SystemTime;
+}
+
+/// The system's wall clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// The source of unique IDs, for names that other processes must not
+/// reuse.
+pub(crate) trait IdGen {
+    /// A new ID of ASCII letters and digits
+    fn new_id(&self) -> String;
+}
+
+/// Random IDs of 32 hex digits, from the OS-seeded keys of `RandomState`.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RandomIds;
+
+impl IdGen for RandomIds {
+    fn new_id(&self) -> String {
+        let random = |i: u64| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(i);
+            hasher.finish()
+        };
+        format!("{:016x}{:016x}", random(0), random(1))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod manual {
+    use std::cell::Cell;
+    use std::time::{Duration, SystemTime};
+
+    use super::{Clock, IdGen};
+
+    /// A clock that only moves when told to.
+    #[derive(Debug)]
+    pub(crate) struct ManualClock(Cell<SystemTime>);
+
+    impl ManualClock {
+        pub fn at(now: SystemTime) -> Self {
+            Self(Cell::new(now))
+        }
+
+        pub fn advance(&self, by: Duration) {
+            self.0.set(self.0.get() + by);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> SystemTime {
+            self.0.get()
+        }
+    }
+
+    /// IDs counting up from `id0`.
+    #[derive(Debug, Default)]
+    pub(crate) struct SequentialIds(Cell<u64>);
+
+    impl IdGen for SequentialIds {
+        fn new_id(&self) -> String {
+            let id = self.0.get();
+            self.0.set(id + 1);
+            format!("id{id}")
+        }
+    }
+
+    #[test]
+    fn test_manual_sources() {
+        let clock = ManualClock::at(SystemTime::UNIX_EPOCH);
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(
+            clock.now(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(60)
+        );
+
+        let ids = SequentialIds::default();
+        assert_eq!(ids.new_id(), "id0");
+        assert_eq!(ids.new_id(), "id1");
+    }
+}
@@ -0,0 +1,300 @@
+//! Coexistence with other crash and exception handlers in the same process.
+//!
+//! Crashpad installs process-wide handlers. When the process also hosts a
+//! managed runtime, those handlers can intercept exceptions the runtime
+//! expects to handle itself. The options here control how Crashpad's handlers
+//! are registered so both can work side by side.
+
+/// Exception code the CLR raises for managed exceptions (`0xE0434352`, "CCR").
+pub const CLR_EXCEPTION_CODE: u32 = 0xE043_4352;
+
+/// Exception code raised by the CLR's COM+ interop layer (`0xE0434F4D`, "COM").
+pub const CLR_COM_EXCEPTION_CODE: u32 = 0xE043_4F4D;
+
+/// Exception codes used by the .NET runtime for managed exceptions.
+///
+/// These should be left to the CLR: it handles them itself, and when one is
+/// truly unhandled the CLR reports it through its own mechanisms.
+pub const CLR_EXCEPTION_CODES: &[u32] = &[CLR_EXCEPTION_CODE, CLR_COM_EXCEPTION_CODE];
+
+/// How Crashpad's exception handler is registered on Windows.
+///
+/// Crashpad normally installs an unhandled exception filter, which only runs
+/// once no frame handled the exception. Runtimes such as the CLR replace that
+/// filter or terminate the process before it runs for some failures (stack
+/// overflow, heap corruption), so those crashes would go unreported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ExceptionFilterMode {
+    /// Only Crashpad's unhandled exception filter
+    #[default]
+    UnhandledFilter,
+
+    /// Additionally register a vectored exception handler at the front of the
+    /// chain
+    ///
+    /// The vectored handler only reports exceptions that can't be recovered
+    /// from (stack overflow, heap corruption, non-continuable exceptions), so
+    /// first-chance exceptions other handlers would catch are left alone.
+    VectoredFirst,
+
+    /// Like [`VectoredFirst`](Self::VectoredFirst), but registered at the back
+    /// of the vectored handler chain
+    VectoredLast,
+}
+
+/// Set of Mach exception types sent to the handler on macOS.
+///
+/// Combine types with `|`:
+///
+/// ```rust
+/// # use crashpad_rs::MachExceptionMask;
+/// // Don't produce dumps for resource limit violations
+/// let mask = MachExceptionMask::CRASH | MachExceptionMask::GUARD;
+/// assert!(!mask.contains(MachExceptionMask::RESOURCE));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MachExceptionMask(u32);
+
+impl MachExceptionMask {
+    /// `EXC_BAD_ACCESS`, caught at first chance
+    ///
+    /// Taken before signal handlers run, so runtimes that recover from
+    /// `SIGSEGV`/`SIGBUS` themselves stop working. Crashes are already
+    /// reported through `EXC_CRASH` without it.
+    pub const BAD_ACCESS: Self = Self(1 << 1);
+
+    /// `EXC_CRASH`, raised when the process is about to terminate abnormally
+    pub const CRASH: Self = Self(1 << 10);
+
+    /// `EXC_RESOURCE`, resource limit violations (CPU, wakeups, memory)
+    pub const RESOURCE: Self = Self(1 << 11);
+
+    /// `EXC_GUARD`, guarded resource violations
+    pub const GUARD: Self = Self(1 << 12);
+
+    /// The types Crashpad registers for by default
+    pub const DEFAULT: Self = Self(Self::CRASH.0 | Self::RESOURCE.0 | Self::GUARD.0);
+
+    /// No exception types
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// The raw `EXC_MASK_*` bits
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether all types in `other` are in this mask
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl Default for MachExceptionMask {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl std::ops::BitOr for MachExceptionMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for MachExceptionMask {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// When the signal handler installed before Crashpad's runs.
+///
+/// Crashpad replaces existing handlers for the crash signals. Runtimes such
+/// as the JVM, Go (cgo hosts) and Mono handle some faults themselves and need
+/// to see them first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum SignalChaining {
+    /// Crashpad writes a dump, then forwards the signal to the previous handler
+    #[default]
+    AfterDump,
+
+    /// The previous handler runs first for synchronous faults (`SIGSEGV`,
+    /// `SIGBUS`, `SIGFPE`, `SIGILL`, `SIGTRAP`)
+    ///
+    /// If it returns, the fault is considered handled and no dump is written.
+    /// Faults the runtime treats as fatal usually end in `abort()`, which
+    /// Crashpad still reports. Other signals behave as in
+    /// [`AfterDump`](Self::AfterDump).
+    BeforeDump,
+}
+
+/// Presets for running inside a process that hosts another runtime.
+///
+/// Applied with `CrashpadConfigBuilder::coexistence`. Options set on the
+/// builder afterwards override the preset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Coexistence {
+    /// Loaded into a JVM through JNI (HotSpot, OpenJ9)
+    ///
+    /// The JVM uses `SIGSEGV`, `SIGBUS`, `SIGFPE` and `SIGILL` for safepoints,
+    /// implicit null checks and stack banging, and `SIGQUIT` for thread dumps.
+    /// The preset:
+    ///
+    /// - lets the JVM's handlers see faults first ([`SignalChaining::BeforeDump`]),
+    ///   so only faults the JVM considers fatal produce a dump (through the
+    ///   JVM's `abort()`)
+    /// - leaves `SIGQUIT` to the JVM so `jstack`/`kill -3` keep working
+    ///
+    /// Start Crashpad after the JVM, e.g. from `JNI_OnLoad`. If Crashpad has to
+    /// start first, the JVM replaces its handlers unless `libjsig` is preloaded.
+    /// On Windows and macOS the JVM's exception handling doesn't conflict with
+    /// Crashpad and the preset changes nothing.
+    Jvm,
+
+    /// Loaded into a Go program through cgo
+    ///
+    /// Go doesn't use fault signals for normal operation, so Crashpad keeps
+    /// handling them first and forwards to Go's handler after writing the dump
+    /// ([`SignalChaining::AfterDump`]); nil dereferences in Go code still
+    /// become recoverable panics. The preset:
+    ///
+    /// - leaves `SIGQUIT` to Go so goroutine dumps keep working
+    /// - requires the Go runtime's handlers to be installed when Crashpad
+    ///   starts, and fails with [`CrashpadError::InvalidConfiguration`](crate::CrashpadError::InvalidConfiguration)
+    ///   otherwise
+    ///
+    /// Start Crashpad from a function called by Go (never from a library
+    /// constructor, which may run before the Go runtime is initialized). Go
+    /// panics that aren't caused by a signal exit the process without a dump.
+    GoCgo,
+}
+
+impl Coexistence {
+    pub(crate) fn apply(self, options: &mut PlatformOptions) {
+        match self {
+            Coexistence::Jvm => {
+                options.signal_chaining = SignalChaining::BeforeDump;
+                options.excluded_signals.push(SIGQUIT);
+            }
+            Coexistence::GoCgo => {
+                options.signal_chaining = SignalChaining::AfterDump;
+                options.excluded_signals.push(SIGQUIT);
+                options.require_runtime_handlers = true;
+            }
+        }
+    }
+}
+
+/// `SIGQUIT` has the same number on every platform with signals.
+const SIGQUIT: i32 = 3;
+
+/// Platform-specific options for how Crashpad's handlers are installed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct PlatformOptions {
+    pub exception_filter: ExceptionFilterMode,
+    /// Exception codes Crashpad passes on instead of reporting (Windows)
+    pub excluded_exception_codes: Vec<u32>,
+    /// Mach exception types sent to the handler (macOS)
+    pub mach_exception_mask: MachExceptionMask,
+    /// Signals to install handlers for, `None` for Crashpad's defaults (Linux/Android)
+    pub crash_signals: Option<Vec<i32>>,
+    /// Signals removed from `crash_signals` (Linux/Android)
+    pub excluded_signals: Vec<i32>,
+    /// Fail to start if another handler owns one of the signals (Linux/Android)
+    pub reject_signal_conflicts: bool,
+    /// When previous signal handlers run (Linux/Android)
+    pub signal_chaining: SignalChaining,
+    /// Fail to start unless a host runtime already handles `SIGSEGV` (Linux/Android)
+    pub require_runtime_handlers: bool,
+    /// Bytes of memory released when a crash signal arrives (Linux/Android)
+    pub crash_reserve_memory: usize,
+    /// File descriptors closed when a crash signal arrives (Linux/Android)
+    pub crash_reserve_file_descriptors: usize,
+    /// How long the handler may outlive the app (Windows)
+    pub handler_lifetime: crate::HandlerLifetime,
+    /// How the handler is started (Android)
+    pub android_handler_launch: crate::AndroidHandlerLaunch,
+}
+
+impl PlatformOptions {
+    /// Whether Crashpad's default Windows exception filter needs adjusting.
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    pub(crate) fn customizes_exception_filter(&self) -> bool {
+        self.exception_filter != ExceptionFilterMode::UnhandledFilter
+            || !self.excluded_exception_codes.is_empty()
+    }
+
+    /// Whether the Mach exception ports differ from Crashpad's defaults.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub(crate) fn customizes_mach_exception_mask(&self) -> bool {
+        self.mach_exception_mask != MachExceptionMask::DEFAULT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_options_keep_crashpad_behavior() {
+        let options = PlatformOptions::default();
+        assert!(!options.customizes_exception_filter());
+        assert!(!options.customizes_mach_exception_mask());
+    }
+
+    #[test]
+    fn test_customized_options() {
+        let options = PlatformOptions {
+            excluded_exception_codes: CLR_EXCEPTION_CODES.to_vec(),
+            ..Default::default()
+        };
+        assert!(options.customizes_exception_filter());
+
+        let options = PlatformOptions {
+            exception_filter: ExceptionFilterMode::VectoredFirst,
+            ..Default::default()
+        };
+        assert!(options.customizes_exception_filter());
+    }
+
+    #[test]
+    fn test_mach_exception_mask() {
+        let mut mask = MachExceptionMask::CRASH | MachExceptionMask::GUARD;
+        assert!(mask.contains(MachExceptionMask::CRASH));
+        assert!(!mask.contains(MachExceptionMask::RESOURCE));
+        assert!(MachExceptionMask::DEFAULT.contains(mask));
+
+        mask |= MachExceptionMask::BAD_ACCESS;
+        assert_eq!(mask.bits(), (1 << 1) | (1 << 10) | (1 << 12));
+        assert_eq!(MachExceptionMask::empty().bits(), 0);
+    }
+
+    #[test]
+    fn test_jvm_preset() {
+        let mut options = PlatformOptions::default();
+        Coexistence::Jvm.apply(&mut options);
+
+        assert_eq!(options.signal_chaining, SignalChaining::BeforeDump);
+        assert_eq!(options.excluded_signals, vec![SIGQUIT]);
+        // Nothing changes on Windows or macOS
+        assert!(!options.customizes_exception_filter());
+        assert!(!options.customizes_mach_exception_mask());
+    }
+
+    #[test]
+    fn test_go_cgo_preset() {
+        let mut options = PlatformOptions::default();
+        Coexistence::GoCgo.apply(&mut options);
+
+        assert_eq!(options.signal_chaining, SignalChaining::AfterDump);
+        assert_eq!(options.excluded_signals, vec![SIGQUIT]);
+        assert!(options.require_runtime_handlers);
+        assert!(!options.customizes_exception_filter());
+        assert!(!options.customizes_mach_exception_mask());
+    }
+}
@@ -1,17 +1,87 @@
-#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+use crate::coexistence::PlatformOptions;
+use crate::lazy_annotation::LazyAnnotation;
 use crate::CrashpadError;
-use crate::Result;
+use crate::{
+    AndroidHandlerLaunch, BreakpadFields, BurstPolicy, Coexistence, ExceptionFilterMode,
+    ExpiryPolicy, HandlerLifetime, HandlerLog, HandlerResolution, HandlerVerification,
+    MachExceptionMask, RedactionRules, Result, SignalChaining,
+};
 use std::env;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Handler path recorded at compile time from the crashpad-rs-sys build.
+///
+/// Set by this crate's build script from `DEP_CRASHPAD_HANDLER`.
+const BUNDLED_HANDLER_PATH: Option<&str> = option_env!("CRASHPAD_HANDLER_PATH");
+
+/// File name of the handler searched for
+fn handler_file_name() -> &'static str {
+    if cfg!(target_os = "android") {
+        "libcrashpad_handler.so"
+    } else if cfg!(windows) {
+        "crashpad_handler.exe"
+    } else {
+        "crashpad_handler"
+    }
+}
+
+#[cfg(feature = "serde")]
+mod file;
 
 /// Configuration for Crashpad client
+///
+/// With the `serde` feature, the configuration can also be read from a file,
+/// see [`from_file`](Self::from_file).
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "file::ConfigFile", into = "file::ConfigFile")
+)]
 pub struct CrashpadConfig {
     handler_path: PathBuf,
     database_path: PathBuf,
     metrics_path: PathBuf,
     url: Option<String>,
+    uploads_enabled: Option<bool>,
+    client_id: Option<String>,
+    failover_urls: Vec<String>,
+    http_headers: HttpHeaders,
     handler_arguments: Vec<String>,
+    handler_version_annotation: bool,
+    diagnostics_annotation: bool,
+    container_annotations: bool,
+    timezone_annotations: bool,
+    stack_annotations: bool,
+    #[cfg(feature = "machine-id")]
+    machine_id_annotation: bool,
+    handler_verification: HandlerVerification,
+    handler_log: Option<HandlerLog>,
+    handler_working_directory: Option<PathBuf>,
+    handler_umask: Option<u32>,
+    janitor_max_age: Option<Duration>,
+    burst_policy: Option<BurstPolicy>,
+    expiry_policy: Option<ExpiryPolicy>,
+    platform_options: PlatformOptions,
+    redaction: Option<RedactionRules>,
+    breakpad_fields: Option<BreakpadFields>,
+    lazy_annotations: Vec<LazyAnnotation>,
+    startup_budget: Option<Duration>,
+}
+
+/// Upload headers, whose values are left out of `Debug` output as they
+/// usually are credentials
+#[derive(Clone, Default)]
+struct HttpHeaders(Vec<(String, String)>);
+
+impl std::fmt::Debug for HttpHeaders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(name, _)| (name, "<redacted>")))
+            .finish()
+    }
 }
 
 impl Default for CrashpadConfig {
@@ -26,7 +96,30 @@ impl Default for CrashpadConfig {
             database_path: exe_dir.join("crashpad_db"),
             metrics_path: exe_dir.join("crashpad_metrics"),
             url: None,
+            uploads_enabled: None,
+            client_id: None,
+            failover_urls: Vec::new(),
+            http_headers: HttpHeaders::default(),
             handler_arguments: Vec::new(),
+            handler_version_annotation: false,
+            diagnostics_annotation: false,
+            container_annotations: false,
+            timezone_annotations: false,
+            stack_annotations: false,
+            #[cfg(feature = "machine-id")]
+            machine_id_annotation: false,
+            handler_verification: HandlerVerification::None,
+            handler_log: None,
+            handler_working_directory: None,
+            handler_umask: None,
+            janitor_max_age: None,
+            burst_policy: None,
+            expiry_policy: None,
+            platform_options: PlatformOptions::default(),
+            redaction: None,
+            breakpad_fields: None,
+            lazy_annotations: Vec::new(),
+            startup_budget: None,
         }
     }
 }
@@ -42,6 +135,47 @@ impl CrashpadConfig {
         CrashpadConfigBuilder::default()
     }
 
+    /// Read the configuration from a TOML or JSON file
+    ///
+    /// The format is chosen by the file extension (`.toml` or `.json`). Every
+    /// field is optional and keeps its default when missing; unknown fields
+    /// are rejected so that typos don't go unnoticed:
+    ///
+    /// ```toml
+    /// handler_path = "/opt/myapp/crashpad_handler"
+    /// database_path = "/var/crash/myapp"
+    /// url = "https://crashes.example.com/submit"
+    /// failover_urls = ["https://eu.crashes.example.com/submit"]
+    /// uploads_enabled = true
+    /// handler_arguments = ["--no-rate-limit"]
+    /// handler_umask = 0o027
+    /// database_janitor_secs = 604800
+    ///
+    /// [handler_log]
+    /// max_size = 1048576
+    /// keep = 3
+    /// ```
+    ///
+    /// The remaining fields are `metrics_path`, `handler_version_annotation`,
+    /// `diagnostics_annotation`, `container_annotations`,
+    /// `timezone_annotations`, `machine_id_annotation` (with the
+    /// `machine-id` feature) and `handler_working_directory`. Platform
+    /// options, handler verification, redaction rules, Breakpad fields and
+    /// HTTP headers are code-only; set them on the builder.
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::IoError`](crate::CrashpadError::IoError) if the file
+    /// can't be read, and
+    /// [`CrashpadError::InvalidConfiguration`](crate::CrashpadError::InvalidConfiguration)
+    /// for an unknown extension or invalid contents.
+    #[cfg(feature = "serde")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        file::parse(path, &contents)
+    }
+
     /// Set the database path
     pub fn with_database_path<P: AsRef<Path>>(mut self, path: P) -> Self {
         self.database_path = path.as_ref().to_path_buf();
@@ -60,73 +194,173 @@ impl CrashpadConfig {
         self
     }
 
-    /// Get the handler path
+    /// Use the handler bundled at build time
+    ///
+    /// Uses the handler path recorded at compile time (`CRASHPAD_HANDLER_PATH`)
+    /// if that file still exists. Otherwise the runtime search applies:
+    /// `CRASHPAD_HANDLER` env, executable directory, current directory.
+    /// A handler path that was already set explicitly is kept.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use crashpad_rs::CrashpadConfig;
+    /// let config = CrashpadConfig::new()
+    ///     .with_bundled_handler()
+    ///     .with_database_path("./crashes");
+    /// ```
+    pub fn with_bundled_handler(self) -> Self {
+        self.with_bundled_handler_from(None)
+    }
+
+    /// Same as [`with_bundled_handler`](Self::with_bundled_handler), but tries
+    /// `preferred` first (e.g. the calling crate's own `CRASHPAD_HANDLER_PATH`).
+    pub(crate) fn with_bundled_handler_from(mut self, preferred: Option<&str>) -> Self {
+        if !self.handler_path.as_os_str().is_empty() {
+            return self;
+        }
+
+        if let Some(path) = [preferred, BUNDLED_HANDLER_PATH]
+            .into_iter()
+            .flatten()
+            .map(PathBuf::from)
+            .find(|p| p.exists())
+        {
+            self.handler_path = path;
+        }
+        self
+    }
+
+    /// Find the handler, recording every path considered
     ///
     /// Search order:
-    /// 1. Path specified in config (if provided)
+    /// 1. Path specified in config (if provided), used even if it doesn't
+    ///    exist so that starting reports it
     /// 2. CRASHPAD_HANDLER environment variable
     /// 3. Same directory as the executable
     /// 4. Current working directory
-    pub(crate) fn handler_path(&self) -> Result<PathBuf> {
+    ///
+    /// When the search picks an unexpected binary, the candidates show why.
+    /// [`HandlerNotFound`](CrashpadError::HandlerNotFound) and
+    /// [`HandlerSpawnFailed`](CrashpadError::HandlerSpawnFailed) errors of
+    /// `start_with_config` carry them as well.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use crashpad_rs::CrashpadConfig;
+    /// let resolution = CrashpadConfig::default().resolve_handler_with_trace();
+    /// for candidate in &resolution.candidates {
+    ///     println!("{candidate}");
+    /// }
+    /// ```
+    ///
+    /// # Platform Behavior
+    /// - **iOS**: Always an empty path without candidates, the handler runs
+    ///   in process
+    /// - **Android**: The same, unless the handler is started as an
+    ///   [`Executable`](AndroidHandlerLaunch::Executable)
+    pub fn resolve_handler_with_trace(&self) -> HandlerResolution {
         // iOS/tvOS/watchOS use in-process handler, no external handler needed
         #[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
         {
-            // Return empty path for iOS - it's handled in-process
-            return Ok(PathBuf::new());
+            return HandlerResolution {
+                path: Some(PathBuf::new()),
+                candidates: Vec::new(),
+            };
         }
 
         #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
         {
-            // Determine handler filename based on platform
-            let handler_name = if cfg!(target_os = "android") {
-                "libcrashpad_handler.so"
-            } else if cfg!(windows) {
-                "crashpad_handler.exe"
-            } else {
-                "crashpad_handler"
-            };
+            use crate::{HandlerCandidate, HandlerSource};
 
-            // 1. Check if path was explicitly set in config
-            if !self.handler_path.as_os_str().is_empty() {
-                let path = &self.handler_path;
-                if path.exists() {
-                    return Ok(path.clone());
-                }
-                // If explicitly set but doesn't exist, still return it
-                // (let the caller handle the error for better diagnostics)
-                return Ok(path.clone());
+            // Started through app_process or the linker, no file to execute
+            #[cfg(target_os = "android")]
+            if self.platform_options.android_handler_launch != AndroidHandlerLaunch::Executable {
+                return HandlerResolution {
+                    path: Some(PathBuf::new()),
+                    candidates: Vec::new(),
+                };
             }
 
-            // 2. Check CRASHPAD_HANDLER environment variable
-            if let Ok(env_path) = env::var("CRASHPAD_HANDLER") {
-                let path = PathBuf::from(env_path);
-                if path.exists() {
-                    return Ok(path);
-                }
+            // 1. Check if path was explicitly set in config.
+            // If it doesn't exist, still return it (let the caller handle
+            // the error for better diagnostics)
+            let path = &self.handler_path;
+            if !path.as_os_str().is_empty() {
+                let reason = if path.exists() {
+                    "set explicitly"
+                } else {
+                    "set explicitly, doesn't exist"
+                };
+                return HandlerResolution {
+                    path: Some(path.clone()),
+                    candidates: vec![HandlerCandidate::new(
+                        HandlerSource::Config,
+                        path,
+                        true,
+                        reason,
+                    )],
+                };
             }
 
-            // 3. Check same directory as executable
-            if let Ok(exe_path) = env::current_exe() {
-                if let Some(exe_dir) = exe_path.parent() {
-                    let handler_path = exe_dir.join(handler_name);
-                    if handler_path.exists() {
-                        return Ok(handler_path);
-                    }
+            // 2.-4. Look for the handler's file name
+            let exe_dir = env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(Path::to_path_buf));
+            let searched = [
+                (
+                    HandlerSource::Environment,
+                    env::var_os("CRASHPAD_HANDLER").map(PathBuf::from),
+                    "not set",
+                ),
+                (
+                    HandlerSource::ExecutableDirectory,
+                    exe_dir.map(|dir| dir.join(handler_file_name())),
+                    "executable path unknown",
+                ),
+                (
+                    HandlerSource::WorkingDirectory,
+                    Some(PathBuf::from(handler_file_name())),
+                    "",
+                ),
+            ];
+
+            let mut candidates = Vec::new();
+            for (source, path, unknown) in searched {
+                let Some(path) = path else {
+                    candidates.push(HandlerCandidate::new(
+                        source,
+                        PathBuf::new(),
+                        false,
+                        unknown,
+                    ));
+                    continue;
+                };
+                if path.exists() {
+                    candidates.push(HandlerCandidate::new(source, &path, true, "exists"));
+                    return HandlerResolution {
+                        path: Some(path),
+                        candidates,
+                    };
                 }
+                candidates.push(HandlerCandidate::new(source, path, false, "doesn't exist"));
             }
-
-            // 4. Check current working directory
-            let cwd_handler = PathBuf::from(handler_name);
-            if cwd_handler.exists() {
-                return Ok(cwd_handler);
+            HandlerResolution {
+                path: None,
+                candidates,
             }
+        }
+    }
 
-            Err(CrashpadError::InvalidConfiguration(
-                format!(
-                    "Handler '{handler_name}' not found. Searched: config path, CRASHPAD_HANDLER env, executable directory, current directory"
-                )
+    /// The handler path, see [`resolve_handler_with_trace`](Self::resolve_handler_with_trace)
+    pub(crate) fn handler_path(&self) -> Result<PathBuf> {
+        let resolution = self.resolve_handler_with_trace();
+        resolution.path.ok_or_else(|| {
+            CrashpadError::InvalidConfiguration(format!(
+                "Handler '{}' not found{}",
+                handler_file_name(),
+                crate::handler::trace_suffix(&resolution.candidates)
             ))
-        }
+        })
     }
 
     pub(crate) fn database_path(&self) -> &Path {
@@ -141,109 +375,1032 @@ impl CrashpadConfig {
         self.url.as_deref()
     }
 
-    pub(crate) fn handler_arguments(&self) -> &[String] {
-        &self.handler_arguments
+    pub(crate) fn uploads_enabled(&self) -> Option<bool> {
+        self.uploads_enabled
+    }
+
+    pub(crate) fn client_id(&self) -> Option<&str> {
+        self.client_id.as_deref()
+    }
+
+    /// Upload URLs in the order they should be tried, primary first
+    ///
+    /// The [`url`](CrashpadConfigBuilder::url) followed by the fallbacks of
+    /// [`url_failover`](CrashpadConfigBuilder::url_failover), for uploads
+    /// from Rust with the `upload` feature's `send_report` and
+    /// `send_pending`.
+    pub fn upload_urls(&self) -> impl Iterator<Item = &str> {
+        self.url
+            .iter()
+            .chain(&self.failover_urls)
+            .map(String::as_str)
+    }
+
+    /// Headers added to upload requests, in the order they were set
+    ///
+    /// The [`http_header`](CrashpadConfigBuilder::http_header)s, for uploads
+    /// from Rust with the `upload` feature's `send_report` and
+    /// `send_pending`.
+    pub fn http_headers(&self) -> Vec<(&str, &str)> {
+        self.http_headers
+            .0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    }
+
+    pub(crate) fn handler_arguments(&self) -> &[String] {
+        &self.handler_arguments
+    }
+
+    pub(crate) fn handler_version_annotation(&self) -> bool {
+        self.handler_version_annotation
+    }
+
+    /// Whether the handler is this executable (`handler-linked` feature)
+    pub(crate) fn uses_linked_handler(&self) -> bool {
+        #[cfg(feature = "handler-linked")]
+        return self
+            .handler_arguments
+            .iter()
+            .any(|arg| arg == crate::LINKED_HANDLER_ARGUMENT);
+
+        #[cfg(not(feature = "handler-linked"))]
+        false
+    }
+
+    pub(crate) fn diagnostics_annotation(&self) -> bool {
+        self.diagnostics_annotation
+    }
+
+    pub(crate) fn container_annotations(&self) -> bool {
+        self.container_annotations
+    }
+
+    pub(crate) fn timezone_annotations(&self) -> bool {
+        self.timezone_annotations
+    }
+
+    pub(crate) fn stack_annotations(&self) -> bool {
+        self.stack_annotations
+    }
+
+    #[cfg(feature = "machine-id")]
+    pub(crate) fn machine_id_annotation(&self) -> bool {
+        self.machine_id_annotation
+    }
+
+    pub(crate) fn handler_verification(&self) -> &HandlerVerification {
+        &self.handler_verification
+    }
+
+    pub(crate) fn handler_log(&self) -> Option<&HandlerLog> {
+        self.handler_log.as_ref()
+    }
+
+    pub(crate) fn handler_working_directory(&self) -> Option<&Path> {
+        self.handler_working_directory.as_deref()
+    }
+
+    pub(crate) fn handler_umask(&self) -> Option<u32> {
+        self.handler_umask
+    }
+
+    pub(crate) fn janitor_max_age(&self) -> Option<Duration> {
+        self.janitor_max_age
+    }
+
+    pub(crate) fn burst_policy(&self) -> Option<&BurstPolicy> {
+        self.burst_policy.as_ref()
+    }
+
+    pub(crate) fn expiry_policy(&self) -> Option<&ExpiryPolicy> {
+        self.expiry_policy.as_ref()
+    }
+
+    pub(crate) fn platform_options(&self) -> &PlatformOptions {
+        &self.platform_options
+    }
+
+    pub(crate) fn redaction(&self) -> Option<&RedactionRules> {
+        self.redaction.as_ref()
+    }
+
+    pub(crate) fn breakpad_fields(&self) -> Option<&BreakpadFields> {
+        self.breakpad_fields.as_ref()
+    }
+
+    pub(crate) fn lazy_annotations(&self) -> &[LazyAnnotation] {
+        &self.lazy_annotations
+    }
+
+    pub(crate) fn startup_budget(&self) -> Option<Duration> {
+        self.startup_budget
+    }
+}
+
+/// Builder for CrashpadConfig
+#[derive(Default)]
+pub struct CrashpadConfigBuilder {
+    config: CrashpadConfig,
+}
+
+impl CrashpadConfigBuilder {
+    /// Set the handler path
+    pub fn handler_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.config.handler_path = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Use the handler bundled at build time
+    ///
+    /// See [`CrashpadConfig::with_bundled_handler`].
+    pub fn bundled_handler(mut self) -> Self {
+        self.config = self.config.with_bundled_handler();
+        self
+    }
+
+    /// Set the database path
+    pub fn database_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.config.database_path = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Set the metrics path
+    pub fn metrics_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.config.metrics_path = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Set the upload URL
+    ///
+    /// # Platform Behavior
+    /// With the `minimal-handler` feature, the handler on Linux and Android
+    /// is built without TLS and only uploads to `http://` URLs.
+    pub fn url<S: Into<String>>(mut self, url: S) -> Self {
+        self.config.url = Some(url.into());
+        self
+    }
+
+    /// Set several upload URLs, tried in order
+    ///
+    /// The first URL is the primary, equivalent to [`url`](Self::url). The
+    /// others are fallbacks for when the primary endpoint is unreachable,
+    /// each with its own retry backoff.
+    ///
+    /// # Platform Behavior
+    /// `crashpad_handler` accepts a single URL, so reports it uploads on its
+    /// own only go to the primary. Fallbacks apply to uploads performed by
+    /// this crate: pass [`CrashpadConfig::upload_urls`] to the `upload`
+    /// feature's `send_report` or `send_pending`.
+    ///
+    /// # Default
+    /// No fallbacks. An empty list clears the upload URL.
+    pub fn url_failover<I, S>(mut self, urls: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut urls = urls.into_iter().map(Into::into);
+        self.config.url = urls.next();
+        self.config.failover_urls = urls.collect();
+        self
+    }
+
+    /// Add a header to upload requests, e.g. `Authorization` with a bearer
+    /// token or an API key header
+    ///
+    /// Credentials in a header stay out of the URL, and so out of server
+    /// logs and the handler's command line. Setting a header again replaces
+    /// its value; names are case-insensitive. The configuration's `Debug`
+    /// output shows header names only.
+    ///
+    /// # Platform Behavior
+    /// `crashpad_handler` has no option for extra headers, so reports it
+    /// uploads on its own are sent without them. Headers apply to uploads
+    /// performed by this crate: pass [`CrashpadConfig::http_headers`] to the
+    /// `upload` feature's `send_report` or `send_pending`, with the
+    /// handler's uploads disabled.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use crashpad_rs::CrashpadConfig;
+    ///
+    /// let token = std::env::var("CRASH_TOKEN").unwrap_or_default();
+    /// let config = CrashpadConfig::builder()
+    ///     .url("https://crashes.example.com/submit")
+    ///     .http_header("Authorization", format!("Bearer {token}"))
+    ///     .build();
+    /// ```
+    ///
+    /// # Default
+    /// No headers
+    pub fn http_header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let (key, value) = (key.into(), value.into());
+        let headers = &mut self.config.http_headers.0;
+        headers.retain(|(name, _)| !name.eq_ignore_ascii_case(&key));
+        headers.push((key, value));
+        self
+    }
+
+    /// Enable or disable uploads in the database settings
+    ///
+    /// Crashpad only uploads reports when the database's upload setting is
+    /// on, and new databases start with it off: a [`url`](Self::url) alone
+    /// uploads nothing. The setting is stored in the database and kept
+    /// across runs, so this is also how a user's choice to opt out is
+    /// applied. See also [`CrashReportDatabase::set_uploads_enabled`]
+    /// to change it while the handler runs.
+    ///
+    /// # Platform Behavior
+    /// All platforms: written to the database before the handler starts
+    ///
+    /// # Default
+    /// `None` - The stored setting is left as is (off for a new database)
+    ///
+    /// [`CrashReportDatabase::set_uploads_enabled`]: crate::CrashReportDatabase::set_uploads_enabled
+    pub fn uploads_enabled(mut self, enabled: bool) -> Self {
+        self.config.uploads_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the client ID the handler sends with every report
+    ///
+    /// Crashpad creates a random UUID as the client ID of a new database.
+    /// Setting it to an existing installation ID, e.g. of an analytics SDK,
+    /// lets reports be matched with it. `client_id` must be a UUID in its
+    /// canonical form, e.g. `00112233-4455-6677-8899-aabbccddeeff`. Read
+    /// it back with [`CrashpadClient::client_id`](crate::CrashpadClient::client_id).
+    ///
+    /// # Platform Behavior
+    /// All platforms: written to the database before the handler starts;
+    /// `start_with_config` fails with
+    /// [`CrashpadError::InvalidConfiguration`](crate::CrashpadError::InvalidConfiguration)
+    /// if `client_id` isn't a UUID
+    ///
+    /// # Default
+    /// `None` - The stored client ID is left as is
+    pub fn client_id(mut self, client_id: impl Into<String>) -> Self {
+        self.config.client_id = Some(client_id.into());
+        self
+    }
+
+    /// Control upload rate limiting
+    ///
+    /// Limits crash report uploads to one per hour when enabled.
+    ///
+    /// # Platform Behavior
+    /// - **Desktop/Linux/Android**: Passed as handler process argument
+    /// - **iOS/tvOS/watchOS**: Currently ignored (hardcoded to false in Crashpad)
+    ///
+    /// # Default
+    /// `true` - Rate limiting enabled
+    pub fn rate_limit(mut self, enabled: bool) -> Self {
+        if !enabled {
+            self.config
+                .handler_arguments
+                .push("--no-rate-limit".to_string());
+        }
+        self
+    }
+
+    /// Control gzip compression for uploads
+    ///
+    /// # Platform Behavior
+    /// - **Desktop/Linux/Android**: Passed as handler process argument
+    /// - **iOS/tvOS/watchOS**: Currently ignored (hardcoded to true in Crashpad)
+    ///
+    /// # Default
+    /// `true` - Gzip compression enabled
+    pub fn upload_gzip(mut self, enabled: bool) -> Self {
+        if !enabled {
+            self.config
+                .handler_arguments
+                .push("--no-upload-gzip".to_string());
+        }
+        self
+    }
+
+    /// Control periodic database maintenance tasks
+    ///
+    /// # Platform Behavior
+    /// - **Desktop/Linux/Android**: Passed as handler process argument
+    /// - **iOS/tvOS/watchOS**: Currently ignored (uses internal pruning thread)
+    ///
+    /// # Default
+    /// `true` - Periodic tasks enabled
+    pub fn periodic_tasks(mut self, enabled: bool) -> Self {
+        if !enabled {
+            self.config
+                .handler_arguments
+                .push("--no-periodic-tasks".to_string());
+        }
+        self
+    }
+
+    /// Control client identification via URL
+    ///
+    /// # Platform Behavior
+    /// - **Desktop/Linux/Android**: Passed as handler process argument
+    /// - **iOS/tvOS/watchOS**: Currently ignored (hardcoded to true in Crashpad)
+    ///
+    /// # Default
+    /// `true` - Client identification enabled
+    pub fn identify_client_via_url(mut self, enabled: bool) -> Self {
+        if !enabled {
+            self.config
+                .handler_arguments
+                .push("--no-identify-client-via-url".to_string());
+        }
+        self
+    }
+
+    /// Record the handler version as an annotation at startup
+    ///
+    /// Runs the handler with `--version` before starting it and adds the result
+    /// as the `crashpad_handler_version` annotation, so every report shows which
+    /// handler produced it. A handler that doesn't report a version is still
+    /// started.
+    ///
+    /// # Platform Behavior
+    /// - **Desktop/Linux/Android**: Queries the handler executable
+    /// - **iOS/tvOS/watchOS**: Ignored (in-process handler)
+    ///
+    /// # Default
+    /// `false` - Handler version is not queried
+    pub fn handler_version_annotation(mut self, enabled: bool) -> Self {
+        self.config.handler_version_annotation = enabled;
+        self
+    }
+
+    /// Add a `crashpad_diagnostics` annotation listing problems found by
+    /// [`startup_diagnostics`](crate::startup_diagnostics)
+    ///
+    /// The value is a comma-separated list of diagnostic codes, e.g.
+    /// `ptrace_restricted,stack_unlimited`, so dumps from misconfigured
+    /// machines can be told apart. Nothing is added when no problem is found.
+    ///
+    /// # Platform Behavior
+    /// - **Linux/Android**: Checks kernel and resource limit settings
+    /// - **Other platforms**: Ignored (no checks)
+    ///
+    /// # Default
+    /// `false` - Diagnostics are not run
+    pub fn diagnostics_annotation(mut self, enabled: bool) -> Self {
+        self.config.diagnostics_annotation = enabled;
+        self
+    }
+
+    /// Add annotations identifying the container and Kubernetes pod
+    ///
+    /// Adds the [`container_annotations`](crate::container_annotations)
+    /// (`container_id`, `k8s_pod_name`, ...) when the process runs in a
+    /// container; nothing is added otherwise. Annotations passed at start
+    /// take precedence.
+    ///
+    /// # Platform Behavior
+    /// - **Linux**: Reads cgroups, mounts, `/etc/hostname` and environment
+    ///   variables
+    /// - **Other platforms**: Reads environment variables only
+    ///
+    /// # Default
+    /// `false` - No container annotations
+    pub fn container_annotations(mut self, enabled: bool) -> Self {
+        self.config.container_annotations = enabled;
+        self
+    }
+
+    /// Add annotations describing the process's timezone
+    ///
+    /// Adds the [`timezone_annotations`](crate::timezone_annotations):
+    /// `utc_offset` (e.g. `+09:00`) and `timezone` (e.g. `Asia/Seoul`) when
+    /// its name can be determined. Report timestamps are always UTC; these
+    /// tell which local time the user saw. Annotations passed at start take
+    /// precedence.
+    ///
+    /// # Platform Behavior
+    /// - **Linux/Android/macOS**: The name comes from `TZ`, the
+    ///   `/etc/localtime` link or `/etc/timezone`
+    /// - **Windows**: The name comes from `TZ` only
+    ///
+    /// # Default
+    /// `false` - No timezone annotations
+    pub fn timezone_annotations(mut self, enabled: bool) -> Self {
+        self.config.timezone_annotations = enabled;
+        self
+    }
+
+    /// Add annotations describing the stack of the thread starting Crashpad
+    ///
+    /// Adds the [`stack_annotations`](crate::stack_annotations) of the
+    /// calling thread, usually `main`: `stack_main` (the address range),
+    /// `stack_main_size` and `stack_main_guard`. Reports of crashes that hit
+    /// a recorded guard region get `probable_stack_overflow=true`. Record
+    /// other threads with [`annotate_thread_stack`](crate::annotate_thread_stack).
+    ///
+    /// # Platform Behavior
+    /// - **Linux/Android**: The main thread's guard is the kernel's default
+    ///   stack guard gap of 256 pages
+    /// - **macOS/iOS**: The guard is reported as one page
+    /// - **Windows**: The guard includes the thread's stack guarantee
+    ///
+    /// # Default
+    /// `false` - No stack annotations
+    pub fn stack_annotations(mut self, enabled: bool) -> Self {
+        self.config.stack_annotations = enabled;
+        self
+    }
+
+    /// Add a hashed `machine_id` annotation to group crashes by device
+    ///
+    /// The identifier is a hash of the OS's machine ID with a random salt
+    /// kept in the database, so the server can tell which reports come from
+    /// the same device without learning a hardware identifier. Rotate or
+    /// clear it with
+    /// [`CrashReportDatabase::rotate_machine_id`](crate::CrashReportDatabase::rotate_machine_id)
+    /// and
+    /// [`CrashReportDatabase::clear_machine_id`](crate::CrashReportDatabase::clear_machine_id).
+    /// Annotations passed at start take precedence.
+    ///
+    /// # Platform Behavior
+    /// - **Linux**: Hashes `/etc/machine-id`
+    /// - **macOS**: Hashes the `IOPlatformUUID`
+    /// - **Windows**: Hashes the `MachineGuid` of the registry
+    /// - **Android/iOS**: Apps can't read a machine ID; nothing is added
+    ///
+    /// # Default
+    /// `false` - No machine identifier
+    ///
+    /// Needs the `machine-id` feature.
+    #[cfg(feature = "machine-id")]
+    pub fn machine_id_annotation(mut self, enabled: bool) -> Self {
+        self.config.machine_id_annotation = enabled;
+        self
+    }
+
+    /// Verify the handler binary before it is executed
+    ///
+    /// The handler is refused with [`CrashpadError::HandlerIntegrityError`]
+    /// if it is unsigned or has been modified.
+    ///
+    /// # Platform Behavior
+    /// - **Windows**: Authenticode or minisign
+    /// - **macOS**: codesign or minisign
+    /// - **Linux/Android**: minisign (`minisign` feature)
+    /// - **iOS/tvOS/watchOS**: Ignored (in-process handler)
+    ///
+    /// # Default
+    /// [`HandlerVerification::None`] - Handler is not verified
+    pub fn verify_handler(mut self, verification: HandlerVerification) -> Self {
+        self.config.handler_verification = verification;
+        self
+    }
+
+    /// Write the handler's log to a file instead of the app's stderr
+    ///
+    /// See [`HandlerLog`]; `HandlerLog::in_database()` keeps the log next to
+    /// the reports, where it helps debug failed uploads in the field.
+    ///
+    /// # Platform Behavior
+    /// - **Windows**: The handler is started synchronously, with the app's
+    ///   stderr handle pointed at the log for the duration of the spawn, as
+    ///   Crashpad passes the handler the app's standard handles. Since other
+    ///   threads would write to the log meanwhile, starting fails with an
+    ///   I/O error (`ERROR_BUSY`) unless the starting thread is the app's
+    ///   only one.
+    /// - **macOS/Linux/Android**: The handler is started through `/bin/sh`
+    ///   (`/system/bin/sh` on Android), which opens the log as its stderr
+    ///   and then executes the handler in its place. The app's stderr isn't
+    ///   touched.
+    /// - **iOS/tvOS/watchOS**: Ignored (in-process handler)
+    ///
+    /// # Default
+    /// `None` - The handler inherits the app's stderr
+    pub fn handler_log(mut self, log: HandlerLog) -> Self {
+        self.config.handler_log = Some(log);
+        self
+    }
+
+    /// Start the handler in `path` instead of the app's working directory
+    ///
+    /// The handler inherits the working directory at the time it is
+    /// started. For an app that has changed into a temporary directory,
+    /// that directory stays busy (and can't be unmounted or removed) for as
+    /// long as the handler runs. The directory is created if needed.
+    ///
+    /// # Platform Behavior
+    /// - **Windows**: The handler is started synchronously, with the app's
+    ///   working directory changed for the duration of the spawn, as Windows
+    ///   can't change it in the handler. Since other threads would resolve
+    ///   relative paths against the handler's directory meanwhile, starting
+    ///   fails with an I/O error (`ERROR_BUSY`) unless the starting thread is
+    ///   the app's only one.
+    /// - **macOS/Linux/Android**: The handler is started through `/bin/sh`
+    ///   (`/system/bin/sh` on Android), which changes into the directory and
+    ///   then executes the handler in its place. The app's working directory
+    ///   isn't touched.
+    /// - **iOS/tvOS/watchOS**: Ignored (in-process handler)
+    ///
+    /// # Default
+    /// `None` - The handler inherits the app's working directory
+    pub fn handler_working_directory<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.config.handler_working_directory = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set the umask of the handler process
+    ///
+    /// Controls the permissions of directories and files the handler
+    /// creates in the database, e.g. `0o027` to let a harvester running in
+    /// the app's group traverse it. Only the low 9 bits are used.
+    ///
+    /// Crashpad creates report files readable by their owner only,
+    /// whatever the umask, so a harvester running as another user still
+    /// can't read the dumps themselves.
+    ///
+    /// # Platform Behavior
+    /// - **Windows**: Ignored (no umask)
+    /// - **macOS/Linux/Android**: The handler is started through `/bin/sh`
+    ///   (`/system/bin/sh` on Android), which sets the umask and then
+    ///   executes the handler in its place. The app's umask isn't touched.
+    /// - **iOS/tvOS/watchOS**: Ignored (in-process handler)
+    ///
+    /// # Default
+    /// `None` - The handler inherits the app's umask
+    pub fn handler_umask(mut self, mask: u32) -> Self {
+        self.config.handler_umask = Some(mask & 0o777);
+        self
+    }
+
+    /// Clean up after interrupted dumps before the handler starts
+    ///
+    /// Runs [`clean_database`](crate::clean_database) on the database,
+    /// removing temporary files, stale locks and orphaned attachments older
+    /// than `max_age`. The result is available from
+    /// [`last_cleanup`](crate::last_cleanup).
+    ///
+    /// # Platform Behavior
+    /// - **All platforms**: Runs in `start_with_config`, before the handler
+    ///   is started
+    ///
+    /// # Default
+    /// `None` - Left to the handler's periodic tasks
+    pub fn database_janitor(mut self, max_age: Duration) -> Self {
+        self.config.janitor_max_age = Some(max_age);
+        self
+    }
+
+    /// Summarize crash bursts before the handler starts
+    ///
+    /// Runs [`CrashReportDatabase::coalesce_burst`](crate::CrashReportDatabase::coalesce_burst)
+    /// with `policy`, so a crash-looping service uploads a few reports and
+    /// one summary of the rest instead of every report. The result is
+    /// available from [`last_burst`](crate::last_burst).
+    ///
+    /// # Platform Behavior
+    /// - **All platforms**: Runs in `start_with_config`, before the handler
+    ///   is started. Failures are ignored.
+    ///
+    /// # Default
+    /// `None` - Every report is uploaded, subject to the handler's rate limit
+    pub fn crash_burst_limit(mut self, policy: BurstPolicy) -> Self {
+        self.config.burst_policy = Some(policy);
+        self
+    }
+
+    /// Give up on old pending reports before the handler starts
+    ///
+    /// Runs [`CrashReportDatabase::expire_stale_reports`](crate::CrashReportDatabase::expire_stale_reports)
+    /// with `policy`, so a machine that was offline for months doesn't
+    /// upload its old reports all at once on reconnect. Runs before
+    /// [burst coalescing](Self::crash_burst_limit).
+    ///
+    /// # Platform Behavior
+    /// - **All platforms**: Runs in `start_with_config`, before the handler
+    ///   is started. Failures are ignored.
+    ///
+    /// # Default
+    /// `None` - Pending reports are uploaded however old they are
+    pub fn expire_pending_reports(mut self, policy: ExpiryPolicy) -> Self {
+        self.config.expiry_policy = Some(policy);
+        self
+    }
+
+    /// Choose how the exception handler is registered
+    ///
+    /// See [`ExceptionFilterMode`]. A vectored handler catches fatal
+    /// exceptions that a hosted runtime would otherwise terminate the process
+    /// on before Crashpad's unhandled exception filter runs.
+    ///
+    /// # Platform Behavior
+    /// - **Windows**: Applied after the handler has started
+    /// - **Other platforms**: Ignored
+    ///
+    /// # Default
+    /// [`ExceptionFilterMode::UnhandledFilter`] - Crashpad's default registration
+    pub fn exception_filter(mut self, mode: ExceptionFilterMode) -> Self {
+        self.config.platform_options.exception_filter = mode;
+        self
+    }
+
+    /// Don't report exceptions with these codes
+    ///
+    /// Excluded exceptions are passed on to the next handler as if Crashpad
+    /// wasn't installed. Use this for exceptions another runtime in the process
+    /// raises and handles itself.
+    ///
+    /// # Platform Behavior
+    /// - **Windows**: Matched against `EXCEPTION_RECORD::ExceptionCode`
+    /// - **Other platforms**: Ignored
+    ///
+    /// # Default
+    /// Empty - All exceptions are reported
+    pub fn exclude_exception_codes<I>(mut self, codes: I) -> Self
+    where
+        I: IntoIterator<Item = u32>,
+    {
+        let excluded = &mut self.config.platform_options.excluded_exception_codes;
+        for code in codes {
+            if !excluded.contains(&code) {
+                excluded.push(code);
+            }
+        }
+        self
+    }
+
+    /// Coexist with the .NET runtime in the same process
+    ///
+    /// For Rust libraries loaded into a .NET host. Equivalent to:
+    ///
+    /// ```rust
+    /// # use crashpad_rs::{CrashpadConfig, ExceptionFilterMode, CLR_EXCEPTION_CODES};
+    /// let config = CrashpadConfig::builder()
+    ///     .exception_filter(ExceptionFilterMode::VectoredFirst)
+    ///     .exclude_exception_codes(CLR_EXCEPTION_CODES.iter().copied())
+    ///     .build();
+    /// ```
+    ///
+    /// Managed exceptions are left to the CLR, while native crashes, including
+    /// stack overflows the CLR would fail fast on, are still reported.
+    ///
+    /// # Platform Behavior
+    /// - **Windows**: See above
+    /// - **Other platforms**: Ignored
+    pub fn coexist_with_clr(self) -> Self {
+        self.exception_filter(ExceptionFilterMode::VectoredFirst)
+            .exclude_exception_codes(crate::CLR_EXCEPTION_CODES.iter().copied())
+    }
+
+    /// Choose which Mach exception types are sent to the handler
+    ///
+    /// Types left out of the mask are released to the system crash reporter
+    /// or an attached debugger.
+    ///
+    /// # Platform Behavior
+    /// - **macOS**: Applied to the task's exception ports after the handler
+    ///   has started
+    /// - **Other platforms**: Ignored
+    ///
+    /// # Default
+    /// [`MachExceptionMask::DEFAULT`] - `EXC_CRASH`, `EXC_RESOURCE` and `EXC_GUARD`
+    ///
+    /// # Example
+    /// ```rust
+    /// # use crashpad_rs::{CrashpadConfig, MachExceptionMask};
+    /// // Don't write dumps for CPU or wakeup limit violations
+    /// let config = CrashpadConfig::builder()
+    ///     .mach_exception_mask(MachExceptionMask::CRASH | MachExceptionMask::GUARD)
+    ///     .build();
+    /// ```
+    pub fn mach_exception_mask(mut self, mask: MachExceptionMask) -> Self {
+        self.config.platform_options.mach_exception_mask = mask;
+        self
+    }
+
+    /// Set the signals Crashpad installs handlers for
+    ///
+    /// Only signals from `CRASH_SIGNALS` (Crashpad's default set, which
+    /// includes `SIGSYS` for seccomp violations) can be handled; anything
+    /// else makes starting the handler fail with
+    /// [`CrashpadError::InvalidConfiguration`].
+    ///
+    /// # Platform Behavior
+    /// - **Linux/Android**: Signals left out keep their current handler
+    /// - **Other platforms**: Ignored
+    ///
+    /// # Default
+    /// All of `CRASH_SIGNALS`
+    pub fn crash_signals<I>(mut self, signals: I) -> Self
+    where
+        I: IntoIterator<Item = i32>,
+    {
+        self.config.platform_options.crash_signals = Some(signals.into_iter().collect());
+        self
+    }
+
+    /// Don't install handlers for these signals
+    ///
+    /// For signals another library in the process owns, e.g. `SIGABRT`.
+    ///
+    /// # Platform Behavior
+    /// - **Linux/Android**: Removed from the handled signals
+    /// - **Other platforms**: Ignored
+    ///
+    /// # Example
+    /// ```rust
+    /// # use crashpad_rs::CrashpadConfig;
+    /// const SIGABRT: i32 = 6;
+    /// let config = CrashpadConfig::builder()
+    ///     .exclude_signals([SIGABRT])
+    ///     .build();
+    /// ```
+    pub fn exclude_signals<I>(mut self, signals: I) -> Self
+    where
+        I: IntoIterator<Item = i32>,
+    {
+        self.config
+            .platform_options
+            .excluded_signals
+            .extend(signals);
+        self
     }
-}
 
-/// Builder for CrashpadConfig
-#[derive(Default)]
-pub struct CrashpadConfigBuilder {
-    config: CrashpadConfig,
-}
+    /// Refuse to start if another handler is installed for a handled signal
+    ///
+    /// Starting fails with [`CrashpadError::SignalHandlerConflict`], naming
+    /// the module of the handler that was there first. Use
+    /// `installed_signal_handlers()` to inspect handlers without failing.
+    ///
+    /// # Platform Behavior
+    /// - **Linux/Android**: Checked right before the handlers are installed
+    /// - **Other platforms**: Ignored
+    ///
+    /// # Default
+    /// `false` - Existing handlers are replaced (Crashpad forwards to them after
+    /// writing a dump)
+    pub fn reject_signal_conflicts(mut self, enabled: bool) -> Self {
+        self.config.platform_options.reject_signal_conflicts = enabled;
+        self
+    }
 
-impl CrashpadConfigBuilder {
-    /// Set the handler path
-    pub fn handler_path<P: AsRef<Path>>(mut self, path: P) -> Self {
-        self.config.handler_path = path.as_ref().to_path_buf();
+    /// Apply a preset for coexisting with another runtime in the process
+    ///
+    /// See [`Coexistence`] for what each preset configures. Options set after
+    /// this call override the preset.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use crashpad_rs::{Coexistence, CrashpadConfig};
+    /// // In a JNI library, started from JNI_OnLoad
+    /// let config = CrashpadConfig::builder()
+    ///     .coexistence(Coexistence::Jvm)
+    ///     .build();
+    /// ```
+    pub fn coexistence(mut self, preset: Coexistence) -> Self {
+        preset.apply(&mut self.config.platform_options);
         self
     }
 
-    /// Set the database path
-    pub fn database_path<P: AsRef<Path>>(mut self, path: P) -> Self {
-        self.config.database_path = path.as_ref().to_path_buf();
+    /// Choose when the previously installed signal handler runs
+    ///
+    /// See [`SignalChaining`]. Use [`SignalChaining::BeforeDump`] when
+    /// Crashpad is started after a runtime that handles faults itself.
+    ///
+    /// # Platform Behavior
+    /// - **Linux/Android**: Applies to handlers installed before Crashpad started
+    /// - **macOS**: Ignored, signal handlers already run before Crashpad's
+    ///   Mach exception handler sees the crash
+    /// - **Windows/iOS**: Ignored
+    ///
+    /// # Default
+    /// [`SignalChaining::AfterDump`] - Previous handler runs after the dump
+    pub fn signal_chaining(mut self, chaining: SignalChaining) -> Self {
+        self.config.platform_options.signal_chaining = chaining;
         self
     }
 
-    /// Set the metrics path
-    pub fn metrics_path<P: AsRef<Path>>(mut self, path: P) -> Self {
-        self.config.metrics_path = path.as_ref().to_path_buf();
+    /// Reserve memory and file descriptors for the crash path
+    ///
+    /// A crash from running out of memory or file descriptors can also make
+    /// writing the dump fail. `memory_bytes` of committed memory and up to 64
+    /// spare file descriptors are set aside when the handler starts, and
+    /// released when a crash signal arrives, right before the dump is
+    /// requested. The memory counts towards the process' resident size.
+    ///
+    /// # Platform Behavior
+    /// - **Linux/Android**: Released by Crashpad's signal handler, after
+    ///   previous handlers ran with [`SignalChaining::BeforeDump`]
+    /// - **Other platforms**: Ignored, the dump is written by the handler
+    ///   process without help from the crashed one
+    ///
+    /// # Default
+    /// Nothing reserved
+    ///
+    /// # Example
+    /// ```rust
+    /// # use crashpad_rs::CrashpadConfig;
+    /// let config = CrashpadConfig::builder()
+    ///     .crash_reserve(1024 * 1024, 4)
+    ///     .build();
+    /// ```
+    pub fn crash_reserve(mut self, memory_bytes: usize, file_descriptors: usize) -> Self {
+        self.config.platform_options.crash_reserve_memory = memory_bytes;
+        self.config.platform_options.crash_reserve_file_descriptors = file_descriptors;
         self
     }
 
-    /// Set the upload URL
-    pub fn url<S: Into<String>>(mut self, url: S) -> Self {
-        self.config.url = Some(url.into());
+    /// Control how long the handler process may outlive the app
+    ///
+    /// See [`HandlerLifetime`]. Tying the handler to the app keeps a
+    /// handler from lingering, e.g. while an installer replaces it, at the
+    /// cost of uploading crash reports on the next start instead of right
+    /// after the crash.
+    ///
+    /// # Platform Behavior
+    /// - **Windows**: The handler is assigned to a Job Object after it has
+    ///   started
+    /// - **Other platforms**: Ignored
+    ///
+    /// # Default
+    /// [`HandlerLifetime::Independent`] - The handler exits on its own
+    ///
+    /// # Example
+    /// ```rust
+    /// # use crashpad_rs::{CrashpadConfig, HandlerLifetime};
+    /// # use std::time::Duration;
+    /// let config = CrashpadConfig::builder()
+    ///     .handler_lifetime(HandlerLifetime::GracePeriod(Duration::from_secs(5)))
+    ///     .build();
+    /// ```
+    pub fn handler_lifetime(mut self, lifetime: HandlerLifetime) -> Self {
+        self.config.platform_options.handler_lifetime = lifetime;
         self
     }
 
-    /// Control upload rate limiting
+    /// Choose how the handler process is started on Android
     ///
-    /// Limits crash report uploads to one per hour when enabled.
+    /// See [`AndroidHandlerLaunch`]. With anything but
+    /// [`AndroidHandlerLaunch::Executable`], no handler file is searched for
+    /// and [`handler_path`](Self::handler_path), handler verification and
+    /// the handler version annotation are ignored.
     ///
     /// # Platform Behavior
-    /// - **Desktop/Linux/Android**: Passed as handler process argument
-    /// - **iOS/tvOS/watchOS**: Currently ignored (hardcoded to false in Crashpad)
+    /// - **Android**: See above
+    /// - **Other platforms**: Ignored
     ///
     /// # Default
-    /// `true` - Rate limiting enabled
-    pub fn rate_limit(mut self, enabled: bool) -> Self {
-        if !enabled {
+    /// [`AndroidHandlerLaunch::Executable`] - `libcrashpad_handler.so` is
+    /// executed at startup
+    ///
+    /// # Example
+    /// ```rust
+    /// # use crashpad_rs::{AndroidHandlerLaunch, CrashpadConfig};
+    /// let config = CrashpadConfig::builder()
+    ///     .android_handler_launch(AndroidHandlerLaunch::Linker {
+    ///         trampoline: "libcrashpad_handler_trampoline.so".to_string(),
+    ///         library: "libmyapp.so".to_string(),
+    ///         env: None,
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn android_handler_launch(mut self, launch: AndroidHandlerLaunch) -> Self {
+        self.config.platform_options.android_handler_launch = launch;
+        self
+    }
+
+    /// Use this executable as the handler
+    ///
+    /// Requires the `handler-linked` feature, which links crashpad_handler's
+    /// code into the application, and a call to
+    /// [`run_linked_handler_if_requested`](crate::run_linked_handler_if_requested)
+    /// at the start of `main`. Replaces the handler path, and the handler
+    /// version annotation is ignored. See the `run_linked_handler_if_requested`
+    /// documentation for the trade-offs against a separate handler.
+    ///
+    /// # Platform Behavior
+    /// - **Windows/macOS/Linux**: See above
+    /// - **Android/iOS/tvOS/watchOS**: Not available
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use crashpad_rs::CrashpadConfig;
+    /// let config = CrashpadConfig::builder()
+    ///     .linked_handler()
+    ///     .build();
+    /// ```
+    #[cfg(feature = "handler-linked")]
+    pub fn linked_handler(mut self) -> Self {
+        if let Ok(exe) = env::current_exe() {
+            self.config.handler_path = exe;
+        }
+        if !self.config.uses_linked_handler() {
             self.config
                 .handler_arguments
-                .push("--no-rate-limit".to_string());
+                .insert(0, crate::LINKED_HANDLER_ARGUMENT.to_string());
         }
         self
     }
 
-    /// Control gzip compression for uploads
+    /// Redact secrets from annotations before they are passed to the handler
+    ///
+    /// See [`RedactionRules`]; use `RedactionRules::default()` for common
+    /// credential keys, email addresses and tokens.
     ///
     /// # Platform Behavior
-    /// - **Desktop/Linux/Android**: Passed as handler process argument
-    /// - **iOS/tvOS/watchOS**: Currently ignored (hardcoded to true in Crashpad)
+    /// - **All platforms**: Applied to the annotations given to
+    ///   [`start_with_config`](crate::CrashpadClient::start_with_config),
+    ///   and to [breadcrumbs](crate::breadcrumbs) recorded once it started,
+    ///   as values of the key `breadcrumb`
     ///
     /// # Default
-    /// `true` - Gzip compression enabled
-    pub fn upload_gzip(mut self, enabled: bool) -> Self {
-        if !enabled {
-            self.config
-                .handler_arguments
-                .push("--no-upload-gzip".to_string());
-        }
+    /// No redaction - Annotations are stored as given
+    pub fn redaction(mut self, rules: RedactionRules) -> Self {
+        self.config.redaction = Some(rules);
         self
     }
 
-    /// Control periodic database maintenance tasks
+    /// Add an annotation computed on a background thread after start
+    ///
+    /// `compute` runs once the handler is running, so expensive metadata
+    /// (GPU model, installed plugins) doesn't delay
+    /// [`start_with_config`](crate::CrashpadClient::start_with_config). The
+    /// value is set as an annotation of this module when ready, after
+    /// redaction and Breakpad renaming: dumps written before that don't
+    /// have it, and like all module annotations it is cut to
+    /// [`MAX_VALUE_LEN`](crate::limits::MAX_VALUE_LEN). A process annotation
+    /// with the same key takes precedence.
+    ///
+    /// Annotations are computed one after another, in the order they were
+    /// added. One that panics is left out.
     ///
     /// # Platform Behavior
-    /// - **Desktop/Linux/Android**: Passed as handler process argument
-    /// - **iOS/tvOS/watchOS**: Currently ignored (uses internal pruning thread)
+    /// - **All platforms**: See above
+    ///
+    /// # Example
+    /// ```rust
+    /// # use crashpad_rs::CrashpadConfig;
+    /// # fn detect_gpu() -> String { "unknown".to_string() }
+    /// let config = CrashpadConfig::builder()
+    ///     .annotation_lazy("gpu", || detect_gpu())
+    ///     .build();
+    /// ```
+    pub fn annotation_lazy<K, F>(mut self, key: K, compute: F) -> Self
+    where
+        K: Into<String>,
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        self.config.lazy_annotations.push(LazyAnnotation {
+            key: key.into(),
+            compute: Arc::new(compute),
+        });
+        self
+    }
+
+    /// Bound the time [`start_with_config`](crate::CrashpadClient::start_with_config)
+    /// adds to app launch
+    ///
+    /// Starting measures its phases (see [`last_startup`](crate::last_startup)).
+    /// Once `budget` is spent, optional work that hasn't begun yet, the
+    /// [handler version annotation](Self::handler_version_annotation), the
+    /// [janitor](Self::database_janitor),
+    /// [report expiry](Self::expire_pending_reports) and
+    /// [burst coalescing](Self::crash_burst_limit), runs on a background
+    /// thread after the handler started instead; the
+    /// [`DeferredTask`](crate::DeferredTask)s are listed in the timing.
+    /// [Lazy annotations](Self::annotation_lazy) always run in the
+    /// background. Finding the handler, writing settings and starting the
+    /// handler are always done, so the budget can be exceeded by them and
+    /// by the optional step in progress when it runs out.
+    ///
+    /// # Platform Behavior
+    /// - **All platforms**: See above
     ///
     /// # Default
-    /// `true` - Periodic tasks enabled
-    pub fn periodic_tasks(mut self, enabled: bool) -> Self {
-        if !enabled {
-            self.config
-                .handler_arguments
-                .push("--no-periodic-tasks".to_string());
-        }
+    /// `None` - Everything runs before `start_with_config` returns
+    pub fn startup_budget(mut self, budget: Duration) -> Self {
+        self.config.startup_budget = Some(budget);
         self
     }
 
-    /// Control client identification via URL
+    /// Upload annotations under the field names a Breakpad server expects
+    ///
+    /// Annotation keys are renamed with [`BreakpadFields`] before they are
+    /// passed to the handler (after [`redaction`](Self::redaction)). The
+    /// minidump itself is already sent as `upload_file_minidump`, like
+    /// Breakpad does.
     ///
     /// # Platform Behavior
-    /// - **Desktop/Linux/Android**: Passed as handler process argument
-    /// - **iOS/tvOS/watchOS**: Currently ignored (hardcoded to true in Crashpad)
+    /// - **All platforms**: Applied to the annotations given to
+    ///   [`start_with_config`](crate::CrashpadClient::start_with_config)
     ///
     /// # Default
-    /// `true` - Client identification enabled
-    pub fn identify_client_via_url(mut self, enabled: bool) -> Self {
-        if !enabled {
-            self.config
-                .handler_arguments
-                .push("--no-identify-client-via-url".to_string());
-        }
+    /// No mapping - Annotations are uploaded under their own names
+    pub fn breakpad_fields(mut self, fields: BreakpadFields) -> Self {
+        self.config.breakpad_fields = Some(fields);
         self
     }
 
@@ -315,6 +1472,50 @@ mod tests {
         assert_eq!(config.url.as_deref(), Some("https://crashes.example.com"));
     }
 
+    #[test]
+    fn test_url_failover() {
+        let config = CrashpadConfig::builder()
+            .url_failover([
+                "https://us.crashes.example.com",
+                "https://eu.crashes.example.com",
+            ])
+            .build();
+
+        assert_eq!(config.url(), Some("https://us.crashes.example.com"));
+        assert_eq!(
+            config.upload_urls().collect::<Vec<_>>(),
+            vec![
+                "https://us.crashes.example.com",
+                "https://eu.crashes.example.com"
+            ]
+        );
+
+        let config = CrashpadConfig::builder()
+            .url("https://crashes.example.com")
+            .url_failover(Vec::<String>::new())
+            .build();
+        assert_eq!(config.url(), None);
+        assert_eq!(config.upload_urls().count(), 0);
+    }
+
+    #[test]
+    fn test_http_headers() {
+        let config = CrashpadConfig::builder()
+            .http_header("Authorization", "Bearer old")
+            .http_header("X-Api-Key", "key")
+            .http_header("authorization", "Bearer secret")
+            .build();
+
+        assert_eq!(
+            config.http_headers(),
+            vec![("X-Api-Key", "key"), ("authorization", "Bearer secret")]
+        );
+        let debug = format!("{config:?}");
+        assert!(debug.contains("\"X-Api-Key\": \"<redacted>\""));
+        assert!(!debug.contains("secret"));
+        assert!(CrashpadConfig::new().http_headers().is_empty());
+    }
+
     #[test]
     #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
     fn test_handler_path_fallback() {
@@ -347,6 +1548,42 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn test_resolve_handler_with_trace() {
+        use crate::HandlerSource;
+
+        let config = CrashpadConfig::builder()
+            .handler_path("/explicit/path/crashpad_handler")
+            .build();
+        let resolution = config.resolve_handler_with_trace();
+        assert_eq!(
+            resolution.path,
+            Some(PathBuf::from("/explicit/path/crashpad_handler"))
+        );
+        assert_eq!(resolution.candidates.len(), 1);
+        assert_eq!(
+            resolution.candidates[0].to_string(),
+            "config /explicit/path/crashpad_handler: set explicitly, doesn't exist"
+        );
+
+        // Every candidate up to the accepted one, in search order
+        let resolution = CrashpadConfig::default().resolve_handler_with_trace();
+        let sources: Vec<HandlerSource> = resolution
+            .candidates
+            .iter()
+            .map(|candidate| candidate.source)
+            .collect();
+        let all = [
+            HandlerSource::Environment,
+            HandlerSource::ExecutableDirectory,
+            HandlerSource::WorkingDirectory,
+        ];
+        assert_eq!(sources, all[..sources.len()]);
+        let accepted = resolution.candidates.iter().filter(|c| c.accepted).count();
+        assert_eq!(accepted, usize::from(resolution.path.is_some()));
+    }
+
     #[test]
     #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
     fn test_handler_env_var() {
@@ -373,6 +1610,36 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn test_bundled_handler() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bundled = temp_dir.path().join("crashpad_handler");
+        std::fs::write(&bundled, b"handler").unwrap();
+
+        // Existing bundled path is used when no explicit path is set
+        let config = CrashpadConfig::new().with_bundled_handler_from(bundled.to_str());
+        assert_eq!(config.handler_path().unwrap(), bundled);
+
+        // Explicit path is kept
+        let config = CrashpadConfig::builder()
+            .handler_path("/explicit/path/crashpad_handler")
+            .build()
+            .with_bundled_handler_from(bundled.to_str());
+        assert_eq!(
+            config.handler_path,
+            PathBuf::from("/explicit/path/crashpad_handler")
+        );
+
+        // Missing bundled path falls back to the runtime search
+        let config =
+            CrashpadConfig::new().with_bundled_handler_from(Some("/nonexistent/crashpad_handler"));
+        assert_ne!(
+            config.handler_path,
+            PathBuf::from("/nonexistent/crashpad_handler")
+        );
+    }
+
     #[test]
     fn test_handler_arguments_high_level() {
         // Test high-level API methods
@@ -443,4 +1710,131 @@ mod tests {
         let config = CrashpadConfig::default();
         assert!(config.handler_arguments.is_empty());
     }
+
+    #[test]
+    fn test_coexist_with_clr() {
+        let config = CrashpadConfig::builder()
+            .exclude_exception_codes([crate::CLR_EXCEPTION_CODE])
+            .coexist_with_clr()
+            .build();
+
+        let options = config.platform_options();
+        assert_eq!(options.exception_filter, ExceptionFilterMode::VectoredFirst);
+        // Codes are not duplicated
+        assert_eq!(options.excluded_exception_codes, crate::CLR_EXCEPTION_CODES);
+
+        assert_eq!(
+            CrashpadConfig::default().platform_options(),
+            &PlatformOptions::default()
+        );
+    }
+
+    #[test]
+    fn test_signal_options() {
+        let config = CrashpadConfig::builder()
+            .crash_signals([11, 31])
+            .exclude_signals([6])
+            .signal_chaining(SignalChaining::BeforeDump)
+            .build();
+
+        let options = config.platform_options();
+        assert_eq!(options.crash_signals.as_deref(), Some(&[11, 31][..]));
+        assert_eq!(options.excluded_signals, vec![6]);
+        assert_eq!(options.signal_chaining, SignalChaining::BeforeDump);
+
+        let options = CrashpadConfig::default().platform_options().clone();
+        assert_eq!(options.crash_signals, None);
+        assert_eq!(options.signal_chaining, SignalChaining::AfterDump);
+    }
+
+    #[test]
+    fn test_crash_reserve() {
+        let config = CrashpadConfig::builder()
+            .crash_reserve(1024 * 1024, 4)
+            .build();
+        let options = config.platform_options();
+        assert_eq!(options.crash_reserve_memory, 1024 * 1024);
+        assert_eq!(options.crash_reserve_file_descriptors, 4);
+
+        let options = CrashpadConfig::default().platform_options().clone();
+        assert_eq!(options.crash_reserve_memory, 0);
+        assert_eq!(options.crash_reserve_file_descriptors, 0);
+    }
+
+    #[test]
+    fn test_handler_lifetime() {
+        let config = CrashpadConfig::builder()
+            .handler_lifetime(HandlerLifetime::GracePeriod(Duration::from_secs(5)))
+            .build();
+        assert_eq!(
+            config.platform_options().handler_lifetime,
+            HandlerLifetime::GracePeriod(Duration::from_secs(5))
+        );
+
+        assert_eq!(
+            CrashpadConfig::default()
+                .platform_options()
+                .handler_lifetime,
+            HandlerLifetime::Independent
+        );
+    }
+
+    #[cfg(feature = "handler-linked")]
+    #[test]
+    fn test_linked_handler() {
+        let config = CrashpadConfig::builder()
+            .handler_argument("--no-rate-limit")
+            .linked_handler()
+            .linked_handler()
+            .build();
+        assert!(config.uses_linked_handler());
+        assert_eq!(config.handler_path, env::current_exe().unwrap());
+        assert_eq!(
+            config.handler_arguments,
+            vec![crate::LINKED_HANDLER_ARGUMENT, "--no-rate-limit"]
+        );
+    }
+
+    #[test]
+    fn test_annotation_lazy() {
+        let config = CrashpadConfig::builder()
+            .annotation_lazy("gpu", || "Mesa".to_string())
+            .annotation_lazy("plugins", || "3".to_string())
+            .build();
+
+        let annotations: Vec<_> = config
+            .lazy_annotations()
+            .iter()
+            .map(|annotation| (annotation.key.as_str(), (annotation.compute)()))
+            .collect();
+        assert_eq!(
+            annotations,
+            [("gpu", "Mesa".to_string()), ("plugins", "3".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_startup_budget() {
+        assert_eq!(CrashpadConfig::default().startup_budget(), None);
+        let config = CrashpadConfig::builder()
+            .startup_budget(Duration::from_millis(50))
+            .build();
+        assert_eq!(config.startup_budget(), Some(Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn test_android_handler_launch() {
+        let launch = AndroidHandlerLaunch::Java {
+            class_name: "com.example.CrashHandler".to_string(),
+            env: Some(vec!["CLASSPATH=/data/app/base.apk".to_string()]),
+        };
+        let config = CrashpadConfig::builder()
+            .android_handler_launch(launch.clone())
+            .build();
+        assert_eq!(config.platform_options().android_handler_launch, launch);
+
+        // No handler file is needed
+        #[cfg(target_os = "android")]
+        assert_eq!(config.handler_path().unwrap(), PathBuf::new());
+    }
 }
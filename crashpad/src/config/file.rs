@@ -0,0 +1,224 @@
+//! TOML/JSON representation of [`CrashpadConfig`].
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::CrashpadConfig;
+use crate::{CrashpadError, HandlerLog, Result};
+
+/// Settings of a [`CrashpadConfig`] that can be deployed in a file.
+///
+/// Every field is optional; missing ones keep their defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct ConfigFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    handler_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    database_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metrics_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failover_urls: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uploads_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    handler_arguments: Vec<String>,
+    handler_version_annotation: bool,
+    diagnostics_annotation: bool,
+    container_annotations: bool,
+    timezone_annotations: bool,
+    #[cfg(feature = "machine-id")]
+    machine_id_annotation: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    handler_log: Option<HandlerLogFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    handler_working_directory: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    handler_umask: Option<u32>,
+    /// [`database_janitor`](crate::CrashpadConfigBuilder::database_janitor)
+    /// max age in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    database_janitor_secs: Option<u64>,
+}
+
+/// [`HandlerLog`] in a config file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HandlerLogFile {
+    /// Log file, `crashpad_handler.log` in the database if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keep: Option<usize>,
+}
+
+impl From<ConfigFile> for CrashpadConfig {
+    fn from(file: ConfigFile) -> Self {
+        let mut config = CrashpadConfig::default();
+        if let Some(path) = file.handler_path {
+            config.handler_path = path;
+        }
+        if let Some(path) = file.database_path {
+            config.database_path = path;
+        }
+        if let Some(path) = file.metrics_path {
+            config.metrics_path = path;
+        }
+        config.url = file.url;
+        config.failover_urls = file.failover_urls;
+        config.uploads_enabled = file.uploads_enabled;
+        config.handler_arguments = file.handler_arguments;
+        config.handler_version_annotation = file.handler_version_annotation;
+        config.diagnostics_annotation = file.diagnostics_annotation;
+        config.container_annotations = file.container_annotations;
+        config.timezone_annotations = file.timezone_annotations;
+        #[cfg(feature = "machine-id")]
+        {
+            config.machine_id_annotation = file.machine_id_annotation;
+        }
+        config.handler_log = file.handler_log.map(|log| {
+            let mut handler_log = match log.path {
+                Some(path) => HandlerLog::file(path),
+                None => HandlerLog::in_database(),
+            };
+            if let Some(max_size) = log.max_size {
+                handler_log = handler_log.max_size(max_size);
+            }
+            if let Some(keep) = log.keep {
+                handler_log = handler_log.keep(keep);
+            }
+            handler_log
+        });
+        config.handler_working_directory = file.handler_working_directory;
+        config.handler_umask = file.handler_umask.map(|mask| mask & 0o777);
+        config.janitor_max_age = file.database_janitor_secs.map(Duration::from_secs);
+        config
+    }
+}
+
+impl From<CrashpadConfig> for ConfigFile {
+    fn from(config: CrashpadConfig) -> Self {
+        Self {
+            handler_path: (!config.handler_path.as_os_str().is_empty())
+                .then_some(config.handler_path),
+            database_path: Some(config.database_path),
+            metrics_path: Some(config.metrics_path),
+            url: config.url,
+            failover_urls: config.failover_urls,
+            uploads_enabled: config.uploads_enabled,
+            handler_arguments: config.handler_arguments,
+            handler_version_annotation: config.handler_version_annotation,
+            diagnostics_annotation: config.diagnostics_annotation,
+            container_annotations: config.container_annotations,
+            timezone_annotations: config.timezone_annotations,
+            #[cfg(feature = "machine-id")]
+            machine_id_annotation: config.machine_id_annotation,
+            handler_log: config.handler_log.map(|log| HandlerLogFile {
+                path: log.file_path().map(Path::to_path_buf),
+                max_size: Some(log.max_size_bytes()),
+                keep: Some(log.keep_files()),
+            }),
+            handler_working_directory: config.handler_working_directory,
+            handler_umask: config.handler_umask,
+            database_janitor_secs: config.janitor_max_age.map(|age| age.as_secs()),
+        }
+    }
+}
+
+/// Parse `contents` as TOML or JSON, by the extension of `path`.
+pub(super) fn parse(path: &Path, contents: &str) -> Result<CrashpadConfig> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        CrashpadError::InvalidConfiguration(format!("{}: {e}", path.display()))
+    };
+    let file: ConfigFile = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(contents).map_err(|e| invalid(&e))?,
+        Some("json") => serde_json::from_str(contents).map_err(|e| invalid(&e))?,
+        _ => return Err(invalid(&"expected a .toml or .json file")),
+    };
+    Ok(file.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_toml() {
+        let config = parse(
+            Path::new("crashpad.toml"),
+            r#"
+            database_path = "/var/crash/myapp"
+            url = "https://crashes.example.com/submit"
+            failover_urls = ["https://eu.crashes.example.com/submit"]
+            uploads_enabled = true
+            handler_arguments = ["--no-rate-limit"]
+            handler_umask = 0o027
+            database_janitor_secs = 86400
+
+            [handler_log]
+            keep = 5
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.database_path, Path::new("/var/crash/myapp"));
+        assert_eq!(
+            config.upload_urls().collect::<Vec<_>>(),
+            vec![
+                "https://crashes.example.com/submit",
+                "https://eu.crashes.example.com/submit"
+            ]
+        );
+        assert_eq!(config.uploads_enabled, Some(true));
+        assert_eq!(config.handler_arguments, vec!["--no-rate-limit"]);
+        assert_eq!(config.handler_umask, Some(0o027));
+        assert_eq!(config.janitor_max_age, Some(Duration::from_secs(86400)));
+        assert_eq!(config.handler_log, Some(HandlerLog::in_database().keep(5)));
+        // Unset fields keep their defaults
+        assert_eq!(config.metrics_path, CrashpadConfig::default().metrics_path);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = parse(Path::new("crashpad.json"), r#"{"databse_path": "/tmp"}"#).unwrap_err();
+        assert!(error.to_string().contains("databse_path"));
+
+        assert!(parse(Path::new("crashpad.yaml"), "").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let builder = CrashpadConfig::builder()
+            .handler_path("/opt/app/crashpad_handler")
+            .database_path("/var/crash/myapp")
+            .url("https://crashes.example.com/submit")
+            .uploads_enabled(false)
+            .timezone_annotations(true);
+        #[cfg(feature = "machine-id")]
+        let builder = builder.machine_id_annotation(true);
+        let config = builder
+            .handler_log(HandlerLog::file("/var/log/handler.log").max_size(4096))
+            .build();
+
+        let json = serde_json::to_string(&config).unwrap();
+        let parsed: CrashpadConfig = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(
+            ConfigFile::from(parsed.clone()),
+            ConfigFile::from(config.clone())
+        );
+        assert_eq!(
+            toml::from_str::<CrashpadConfig>(&toml::to_string(&config).unwrap())
+                .map(ConfigFile::from)
+                .unwrap(),
+            ConfigFile::from(config)
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Container and Kubernetes identifiers for annotations.
+
+use std::collections::HashMap;
+
+/// Annotations identifying the container the process runs in.
+///
+/// Empty outside of containers. Inside one, any of these that can be
+/// determined are returned:
+/// - `container_id`: the 64-digit container ID (Docker, containerd, CRI-O,
+///   Podman), from `/proc/self/cgroup` or `/proc/self/mountinfo`
+/// - `container_cgroup`: the process's cgroup path
+/// - `container_hostname`: the contents of `/etc/hostname`
+/// - `k8s_pod_name`: `KUBERNETES_POD_NAME` or `POD_NAME`, falling back to
+///   the hostname inside Kubernetes
+/// - `k8s_namespace`: `KUBERNETES_NAMESPACE` or `POD_NAMESPACE`, falling
+///   back to the service account namespace file
+/// - `k8s_node_name`: `KUBERNETES_NODE_NAME` or `NODE_NAME`
+///
+/// The `POD_*` and `NODE_NAME` variables are the usual names for values
+/// exposed through the Kubernetes downward API; set them in the pod spec.
+///
+/// A process is considered containerized if a container ID is found,
+/// `KUBERNETES_SERVICE_HOST` or `container` is set, or `/.dockerenv` or
+/// `/run/.containerenv` exists.
+///
+/// Only environment variables are read on platforms other than
+/// Linux/Android.
+pub fn container_annotations() -> HashMap<String, String> {
+    let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let read = |path: &str| std::fs::read_to_string(path).ok();
+    collect(env, read)
+}
+
+fn collect(
+    env: impl Fn(&str) -> Option<String>,
+    read: impl Fn(&str) -> Option<String>,
+) -> HashMap<String, String> {
+    let cgroup = read("/proc/self/cgroup");
+    let container_id = cgroup.as_deref().and_then(find_container_id).or_else(|| {
+        read("/proc/self/mountinfo")
+            .as_deref()
+            .and_then(mountinfo_container_id)
+    });
+    let kubernetes = env("KUBERNETES_SERVICE_HOST").is_some();
+
+    let containerized = container_id.is_some()
+        || kubernetes
+        || env("container").is_some()
+        || read("/.dockerenv").is_some()
+        || read("/run/.containerenv").is_some();
+    if !containerized {
+        return HashMap::new();
+    }
+
+    let first = |names: &[&str]| names.iter().find_map(|name| env(name));
+    let hostname = read("/etc/hostname")
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+
+    let mut annotations = HashMap::new();
+    let mut insert = |key: &str, value: Option<String>| {
+        if let Some(value) = value {
+            annotations.insert(key.to_string(), value);
+        }
+    };
+    insert("container_id", container_id);
+    insert("container_cgroup", cgroup.as_deref().and_then(cgroup_path));
+    insert(
+        "k8s_pod_name",
+        first(&["KUBERNETES_POD_NAME", "POD_NAME"])
+            .or_else(|| hostname.clone().filter(|_| kubernetes)),
+    );
+    insert("container_hostname", hostname);
+    insert(
+        "k8s_namespace",
+        first(&["KUBERNETES_NAMESPACE", "POD_NAMESPACE"]).or_else(|| {
+            read("/var/run/secrets/kubernetes.io/serviceaccount/namespace")
+                .map(|namespace| namespace.trim().to_string())
+                .filter(|namespace| !namespace.is_empty())
+        }),
+    );
+    insert(
+        "k8s_node_name",
+        first(&["KUBERNETES_NODE_NAME", "NODE_NAME"]),
+    );
+    annotations
+}
+
+/// Cgroup path of the process: the unified (cgroup v2) entry if there is
+/// one, otherwise the first. `None` for the root cgroup, which is what a
+/// process in its own cgroup namespace sees.
+fn cgroup_path(cgroup: &str) -> Option<String> {
+    let paths: Vec<&str> = cgroup
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .collect();
+    let unified = cgroup.lines().find_map(|line| line.strip_prefix("0::"));
+    unified
+        .or_else(|| paths.first().copied())
+        .filter(|path| *path != "/")
+        .map(str::to_string)
+}
+
+/// First 64-digit hex ID in the cgroup paths, e.g. in
+/// `/system.slice/docker-<id>.scope` or `/kubepods/burstable/pod<uid>/<id>`.
+fn find_container_id(cgroup: &str) -> Option<String> {
+    cgroup
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .flat_map(|path| path.split(['/', '-', '.']))
+        .find(|part| is_container_id(part))
+        .map(str::to_string)
+}
+
+/// Container ID from the bind mounts Docker and Podman set up for
+/// `/etc/hostname`, e.g. `/var/lib/docker/containers/<id>/hostname`.
+fn mountinfo_container_id(mountinfo: &str) -> Option<String> {
+    mountinfo
+        .lines()
+        .filter(|line| line.contains("/containers/"))
+        .filter_map(|line| line.split(' ').nth(3))
+        .flat_map(|root| root.split('/'))
+        .find(|part| is_container_id(part))
+        .map(str::to_string)
+}
+
+fn is_container_id(part: &str) -> bool {
+    part.len() == 64 && part.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "4f1c9d3e8b7a6f5e4d3c2b1a0f9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e";
+
+    fn collect_from(env: &[(&str, &str)], files: &[(&str, &str)]) -> HashMap<String, String> {
+        collect(
+            |name| {
+                env.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            },
+            |path| {
+                files
+                    .iter()
+                    .find(|(key, _)| *key == path)
+                    .map(|(_, value)| value.to_string())
+            },
+        )
+    }
+
+    #[test]
+    fn test_not_containerized() {
+        let annotations = collect_from(
+            &[("POD_NAME", "web-0")],
+            &[
+                (
+                    "/proc/self/cgroup",
+                    "0::/user.slice/user-1000.slice/session-2.scope\n",
+                ),
+                ("/etc/hostname", "laptop\n"),
+            ],
+        );
+        assert!(annotations.is_empty());
+    }
+
+    #[test]
+    fn test_docker_cgroup_v1() {
+        let cgroup = format!("12:memory:/docker/{ID}\n11:cpu,cpuacct:/docker/{ID}\n");
+        let annotations = collect_from(
+            &[],
+            &[
+                ("/proc/self/cgroup", &cgroup),
+                ("/etc/hostname", "4f1c9d3e8b7a\n"),
+            ],
+        );
+
+        assert_eq!(annotations["container_id"], ID);
+        assert_eq!(annotations["container_cgroup"], format!("/docker/{ID}"));
+        assert_eq!(annotations["container_hostname"], "4f1c9d3e8b7a");
+        assert!(!annotations.contains_key("k8s_pod_name"));
+    }
+
+    #[test]
+    fn test_kubernetes_cgroup_namespace() {
+        // With a cgroup namespace the path is "/", the ID comes from the
+        // hostname bind mount. Sandbox (pause container) IDs are ignored.
+        let mountinfo = format!(
+            "1234 1200 253:1 /var/lib/containerd/io.containerd.grpc.v1.cri/sandboxes/x/hostname /etc/hostname rw - ext4 /dev/vda1 rw\n\
+             1235 1200 253:1 /var/lib/docker/containers/{ID}/hostname /etc/hostname rw - ext4 /dev/vda1 rw\n"
+        );
+        let annotations = collect_from(
+            &[
+                ("KUBERNETES_SERVICE_HOST", "10.96.0.1"),
+                ("NODE_NAME", "node-3"),
+            ],
+            &[
+                ("/proc/self/cgroup", "0::/\n"),
+                ("/proc/self/mountinfo", &mountinfo),
+                ("/etc/hostname", "web-7d9f-abcde\n"),
+                (
+                    "/var/run/secrets/kubernetes.io/serviceaccount/namespace",
+                    "prod",
+                ),
+            ],
+        );
+
+        assert_eq!(annotations["container_id"], ID);
+        assert!(!annotations.contains_key("container_cgroup"));
+        assert_eq!(annotations["k8s_pod_name"], "web-7d9f-abcde");
+        assert_eq!(annotations["k8s_namespace"], "prod");
+        assert_eq!(annotations["k8s_node_name"], "node-3");
+    }
+
+    #[test]
+    fn test_downward_api_env_wins() {
+        let cgroup = format!("0::/kubepods.slice/cri-containerd-{ID}.scope\n");
+        let annotations = collect_from(
+            &[
+                ("KUBERNETES_SERVICE_HOST", "10.96.0.1"),
+                ("POD_NAME", "web-0"),
+                ("POD_NAMESPACE", "staging"),
+            ],
+            &[
+                ("/proc/self/cgroup", &cgroup),
+                ("/etc/hostname", "other\n"),
+                (
+                    "/var/run/secrets/kubernetes.io/serviceaccount/namespace",
+                    "prod",
+                ),
+            ],
+        );
+
+        assert_eq!(annotations["container_id"], ID);
+        assert_eq!(annotations["k8s_pod_name"], "web-0");
+        assert_eq!(annotations["k8s_namespace"], "staging");
+    }
+}
@@ -0,0 +1,462 @@
+//! Detection of sessions that ended in a crash.
+//!
+//! [`start_session`] leaves a marker file in the database directory that
+//! [`end_session`] removes on a clean shutdown. A marker still there at the
+//! next start means the previous session didn't shut down: it crashed, or
+//! was killed. Counting such sessions in a row tells a single crash, worth a
+//! "we crashed" dialog, from a crash loop, where the application should start
+//! in a safe mode.
+//!
+//! Each process has its own marker, `session_running.<pid>`, so processes
+//! sharing a database, like several instances of the application or a
+//! helper process, don't count each other's running sessions as crashed.
+//! At start, markers of processes that are no longer running are taken
+//! over: each is counted by only one of the processes starting at once,
+//! and removed. The next session then continues the longest run of crashes
+//! among them. A marker whose process ID was reused by a running process is
+//! only taken over once that process exits.
+//!
+//! # Release health
+//!
+//! Sessions started with [`start_session_with_health_pings`] also keep
+//! their outcome, so a backend can compute crash-free session rates. A
+//! clean shutdown renames the marker to `sessions/<session id>.exited`, and
+//! a marker taken over at the next start is renamed to
+//! `sessions/<session id>.abnormal`. Renaming is atomic, so a crash during
+//! shutdown leaves either the running marker or the outcome, never both.
+//! The outcomes wait in the database until they are sent with
+//! `upload::send_session_pings` (`upload` feature).
+//!
+//! ```rust,no_run
+//! use crashpad_rs::crash_loop;
+//!
+//! crash_loop::start_session("./crashes")?;
+//! if crash_loop::consecutive_crash_count() >= 3 {
+//!     // Start without plugins, reset the settings, ...
+//! } else if crash_loop::did_crash_last_session() {
+//!     // Offer to send the report
+//! }
+//! // ...
+//! crash_loop::end_session()?;
+//! # Ok::<(), crashpad_rs::CrashpadError>(())
+//! ```
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::clock::{IdGen, RandomIds};
+use crate::fs::{Fs, StdFs};
+use crate::Result;
+
+/// Marker in the database directory, `session_running.<pid>`, holding the
+/// number of sessions before the running one that didn't shut down cleanly
+/// and, on a second line, the session ID. Versions before per-process
+/// markers wrote only the number, without the process ID.
+const MARKER: &str = "session_running";
+
+/// Directory of the outcomes of ended sessions, see [`SessionStatus`].
+#[cfg_attr(not(feature = "upload"), allow(dead_code))]
+pub(crate) const SESSIONS_DIR: &str = "sessions";
+
+/// The session started by [`start_session`].
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Session {
+    marker: PathBuf,
+    /// Where a clean shutdown moves the marker to, with health pings
+    outcome: Option<PathBuf>,
+    /// Sessions in a row before this one that didn't shut down cleanly
+    crashes: u32,
+}
+
+/// How a session ended, in the file name of its outcome.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "upload"), allow(dead_code))]
+pub(crate) enum SessionStatus {
+    /// Shut down cleanly with [`end_session`]
+    Exited,
+    /// Crashed, or was killed
+    Abnormal,
+}
+
+impl SessionStatus {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Exited => "exited",
+            Self::Abnormal => "abnormal",
+        }
+    }
+}
+
+impl std::fmt::Display for SessionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Record the start of a session in `database_path`
+///
+/// Takes over the markers of sessions that ended without a clean shutdown,
+/// see the [module docs](self), and writes this process's. Call it once at
+/// startup, before [`did_crash_last_session`] and
+/// [`consecutive_crash_count`]; calling it again starts a new session.
+///
+/// # Errors
+///
+/// [`CrashpadError::IoError`](crate::CrashpadError::IoError) if the marker
+/// can't be written.
+pub fn start_session<P: AsRef<Path>>(database_path: P) -> Result<()> {
+    start(database_path.as_ref(), false)
+}
+
+/// Like [`start_session`], and keep the outcome of this session and of
+/// the ended ones it takes over for health pings
+///
+/// See [Release health](self#release-health).
+///
+/// # Errors
+///
+/// [`CrashpadError::IoError`](crate::CrashpadError::IoError) if the marker
+/// or an outcome can't be written.
+pub fn start_session_with_health_pings<P: AsRef<Path>>(database_path: P) -> Result<()> {
+    start(database_path.as_ref(), true)
+}
+
+fn start(database: &Path, health_pings: bool) -> Result<()> {
+    let session = begin(
+        &StdFs,
+        &RandomIds,
+        database,
+        std::process::id(),
+        is_running,
+        health_pings,
+    )?;
+    *SESSION.lock().unwrap_or_else(|e| e.into_inner()) = Some(session);
+    Ok(())
+}
+
+/// Record a clean shutdown of the session
+///
+/// Call it when the application exits normally. Does nothing without a
+/// session started by [`start_session`].
+///
+/// # Errors
+///
+/// [`CrashpadError::IoError`](crate::CrashpadError::IoError) if the marker
+/// can't be removed, or renamed to the outcome; the next session then
+/// counts this one as crashed.
+pub fn end_session() -> Result<()> {
+    let session = SESSION.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some(session) = session {
+        end(&StdFs, &session)?;
+    }
+    Ok(())
+}
+
+/// Whether the previous session didn't shut down cleanly
+///
+/// `false` before [`start_session`], and for the first session in a
+/// database.
+pub fn did_crash_last_session() -> bool {
+    consecutive_crash_count() > 0
+}
+
+/// Number of sessions in a row before this one that didn't shut down
+/// cleanly, 0 before [`start_session`]
+pub fn consecutive_crash_count() -> u32 {
+    SESSION
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map_or(0, |session| session.crashes)
+}
+
+/// Take over the markers in `database` of processes that aren't running
+/// and write the marker of process `pid`
+fn begin(
+    fs: &impl Fs,
+    ids: &impl IdGen,
+    database: &Path,
+    pid: u32,
+    is_running: impl Fn(u32) -> bool,
+    health_pings: bool,
+) -> io::Result<Session> {
+    fs.create_dir_all(database)?;
+    if health_pings {
+        fs.create_dir_all(&database.join(SESSIONS_DIR))?;
+    }
+    let mut crashes = 0;
+    for path in fs.files(database)? {
+        let ended = match marker_pid(&path) {
+            // The previous process with this ID, or an older version
+            Some(Some(marker_pid)) if marker_pid != pid => !is_running(marker_pid),
+            Some(_) => true,
+            None => false,
+        };
+        if ended {
+            if let Some(count) = take_over(fs, ids, database, &path, health_pings)? {
+                crashes = crashes.max(count.saturating_add(1));
+            }
+        }
+    }
+
+    let id = ids.new_id();
+    let marker = database.join(format!("{MARKER}.{pid}"));
+    let temporary = marker.with_extension(format!("{pid}.{}.tmp", ids.new_id()));
+    fs.write(&temporary, format!("{crashes}\n{id}").as_bytes())?;
+    if let Err(e) = fs.rename(&temporary, &marker) {
+        let _ = fs.remove_file(&temporary);
+        return Err(e);
+    }
+    Ok(Session {
+        marker,
+        outcome: health_pings.then(|| outcome_path(database, &id, SessionStatus::Exited)),
+        crashes,
+    })
+}
+
+/// The process ID of the session marker `path`: `Some(None)` for a marker
+/// written before per-process markers, `None` if `path` isn't a marker
+fn marker_pid(path: &Path) -> Option<Option<u32>> {
+    let name = path.file_name()?.to_str()?.strip_prefix(MARKER)?;
+    if name.is_empty() {
+        return Some(None);
+    }
+    name.strip_prefix('.')?.parse().ok().map(Some)
+}
+
+/// The crash count and session ID in a marker. An unreadable count still
+/// means the session crashed, and counts as 0.
+fn parse_marker(marker: &[u8]) -> (u32, Option<String>) {
+    let marker = String::from_utf8_lossy(marker);
+    let mut lines = marker.lines();
+    let count = lines
+        .next()
+        .and_then(|count| count.trim().parse().ok())
+        .unwrap_or(0);
+    let id = lines
+        .next()
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric()))
+        .map(str::to_string);
+    (count, id)
+}
+
+/// Take the marker of an ended session away from other starting processes
+/// and return its count. `None` if another process took it over first.
+/// The marker becomes the session's abnormal outcome with `health_pings`,
+/// and is removed otherwise.
+fn take_over(
+    fs: &impl Fs,
+    ids: &impl IdGen,
+    database: &Path,
+    marker: &Path,
+    health_pings: bool,
+) -> io::Result<Option<u32>> {
+    let (count, id) = match fs.read(marker) {
+        Ok(marker) => parse_marker(&marker),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let taken = if health_pings {
+        let id = id.unwrap_or_else(|| ids.new_id());
+        outcome_path(database, &id, SessionStatus::Abnormal)
+    } else {
+        marker.with_extension(format!("{}.tmp", ids.new_id()))
+    };
+    match fs.rename(marker, &taken) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        result => result?,
+    }
+    if !health_pings {
+        fs.remove_file(&taken)?;
+    }
+    Ok(Some(count))
+}
+
+/// Path of the outcome of session `id` in `database`
+fn outcome_path(database: &Path, id: &str, status: SessionStatus) -> PathBuf {
+    database.join(SESSIONS_DIR).join(format!("{id}.{status}"))
+}
+
+/// Outcomes of ended sessions in `database`, waiting to be sent: their
+/// paths, session IDs and statuses
+#[cfg(feature = "upload")]
+pub(crate) fn session_outcomes(
+    fs: &impl Fs,
+    database: &Path,
+) -> io::Result<Vec<(PathBuf, String, SessionStatus)>> {
+    let files = match fs.files(&database.join(SESSIONS_DIR)) {
+        Ok(files) => files,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(files
+        .into_iter()
+        .filter_map(|path| {
+            let id = path.file_stem()?.to_str()?.to_string();
+            let status = match path.extension()?.to_str()? {
+                "exited" => SessionStatus::Exited,
+                "abnormal" => SessionStatus::Abnormal,
+                _ => return None,
+            };
+            Some((path, id, status))
+        })
+        .collect())
+}
+
+/// Whether process `pid` is running. Processes that can't be queried, like
+/// those of other users, count as running.
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Whether process `pid` is running. Processes that can't be queried, like
+/// those of other users, count as running.
+#[cfg(windows)]
+fn is_running(pid: u32) -> bool {
+    use std::os::raw::c_void;
+
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+    const STILL_ACTIVE: u32 = 259;
+    const ERROR_ACCESS_DENIED: i32 = 5;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn OpenProcess(access: u32, inherit: i32, pid: u32) -> *mut c_void;
+        fn GetExitCodeProcess(process: *mut c_void, code: *mut u32) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    if process.is_null() {
+        return io::Error::last_os_error().raw_os_error() == Some(ERROR_ACCESS_DENIED);
+    }
+    let mut code = 0;
+    let queried = unsafe { GetExitCodeProcess(process, &mut code) } != 0;
+    unsafe { CloseHandle(process) };
+    !queried || code == STILL_ACTIVE
+}
+
+fn end(fs: &impl Fs, session: &Session) -> io::Result<()> {
+    let ended = match &session.outcome {
+        Some(outcome) => fs.rename(&session.marker, outcome),
+        None => fs.remove_file(&session.marker),
+    };
+    match ended {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::manual::SequentialIds;
+    use crate::fs::memory::MemoryFs;
+
+    const PID: u32 = 100;
+
+    #[test]
+    fn test_consecutive_crashes() {
+        let (fs, ids) = (MemoryFs::default(), SequentialIds::default());
+        let database = Path::new("crashes");
+        let dead = |_| false;
+
+        let session = begin(&fs, &ids, database, PID, dead, false).unwrap();
+        assert_eq!(session.crashes, 0);
+        // Never ended: crashed twice in a row, once with a reused ID
+        begin(&fs, &ids, database, PID + 1, dead, false).unwrap();
+        let session = begin(&fs, &ids, database, PID + 1, dead, false).unwrap();
+        assert_eq!(session.crashes, 2);
+        assert_eq!(
+            fs.files(database).unwrap(),
+            [database.join("session_running.101")]
+        );
+        assert_eq!(parse_marker(&fs.read(&session.marker).unwrap()).0, 2);
+
+        end(&fs, &session).unwrap();
+        assert_eq!(
+            begin(&fs, &ids, database, PID, dead, false)
+                .unwrap()
+                .crashes,
+            0
+        );
+    }
+
+    #[test]
+    fn test_shared_database() {
+        let (fs, ids) = (MemoryFs::default(), SequentialIds::default());
+        let database = Path::new("crashes");
+        let running = |pid| pid == PID;
+
+        let first = begin(&fs, &ids, database, PID, running, false).unwrap();
+        // The first process is still running
+        let second = begin(&fs, &ids, database, PID + 1, running, false).unwrap();
+        assert_eq!(second.crashes, 0);
+        assert!(fs.exists(&first.marker));
+
+        // The second crashed
+        assert_eq!(
+            begin(&fs, &ids, database, PID + 2, running, false)
+                .unwrap()
+                .crashes,
+            1
+        );
+        assert!(!fs.exists(&second.marker));
+    }
+
+    #[test]
+    fn test_unreadable_and_old_markers() {
+        let fs = MemoryFs::default()
+            .with_file("crashes/session_running.7", "garbage")
+            .with_file("crashes/session_running", "3")
+            .with_file("crashes/session_running.8.id0.tmp", "5");
+        let ids = SequentialIds::default();
+        let session = begin(&fs, &ids, Path::new("crashes"), PID, |_| false, false).unwrap();
+        assert_eq!(session.crashes, 4);
+        assert_eq!(
+            fs.files(Path::new("crashes")).unwrap(),
+            [
+                PathBuf::from("crashes/session_running.100"),
+                PathBuf::from("crashes/session_running.8.id0.tmp"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_health_pings() {
+        let (fs, ids) = (MemoryFs::default(), SequentialIds::default());
+        let database = Path::new("crashes");
+        let dead = |_| false;
+
+        // Shut down cleanly, then crashed
+        let session = begin(&fs, &ids, database, PID, dead, true).unwrap();
+        end(&fs, &session).unwrap();
+        let crashed = begin(&fs, &ids, database, PID + 1, dead, true).unwrap();
+        let session = begin(&fs, &ids, database, PID + 2, dead, true).unwrap();
+        assert_eq!(session.crashes, 1);
+        assert!(!fs.exists(&crashed.marker));
+        assert_eq!(
+            fs.files(&database.join(SESSIONS_DIR)).unwrap(),
+            [
+                database.join("sessions/id0.exited"),
+                database.join("sessions/id2.abnormal"),
+            ]
+        );
+        assert_eq!(parse_marker(b"3\nid7"), (3, Some("id7".to_string())));
+        assert_eq!(parse_marker(b"3\n../x"), (3, None));
+    }
+
+    #[test]
+    fn test_is_running() {
+        assert!(is_running(std::process::id()));
+    }
+}
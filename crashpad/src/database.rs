@@ -0,0 +1,1249 @@
+//! Access to the reports in a Crashpad database.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crashpad_rs_sys::*;
+
+use crate::backend::path_to_cstring;
+use crate::burst::{self, BurstPolicy, BurstSummary};
+use crate::claim;
+#[cfg(feature = "serde")]
+use crate::claim::ReportClaim;
+use crate::clock::{RandomIds, SystemClock};
+use crate::dropped::{self, DroppedEvent};
+use crate::expiry::{self, ExpiryPolicy};
+use crate::file_name;
+use crate::fs::{Fs, StdFs};
+#[cfg(feature = "machine-id")]
+use crate::machine_id;
+use crate::minidump;
+use crate::prune::PruneCondition;
+use crate::taxonomy::CRASH_CATEGORY;
+use crate::timestamp::Timestamp;
+use crate::{CrashpadError, Result};
+
+/// Extension of the annotation sidecar written next to a report's minidump.
+pub(crate) const ANNOTATIONS_EXTENSION: &str = "annotations.json";
+
+/// Annotation added to reports whose crash was probably a stack overflow
+const PROBABLE_STACK_OVERFLOW: &str = "probable_stack_overflow";
+
+/// How often [`CrashReportDatabase::upload_pending_blocking`] checks the
+/// pending reports.
+const UPLOAD_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A crash report stored in a [`CrashReportDatabase`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashReport {
+    /// Identifies the report in the database
+    pub uuid: String,
+    /// The minidump file
+    pub file_path: PathBuf,
+    /// ID assigned by the server when the report was uploaded
+    pub id: Option<String>,
+    /// When the report was created; see [`CrashReport::created`]
+    pub creation_time: SystemTime,
+    pub uploaded: bool,
+    /// See [`CrashReport::last_upload_attempt`]
+    pub last_upload_attempt_time: Option<SystemTime>,
+    pub upload_attempts: u32,
+    /// Upload was requested with [`CrashReportDatabase::request_upload`]
+    pub upload_explicitly_requested: bool,
+    /// Size of the minidump and its attachments in bytes
+    pub total_size: u64,
+}
+
+impl CrashReport {
+    /// Copy a report out of the FFI struct.
+    ///
+    /// # Safety
+    /// The string pointers of `report` must be valid C strings.
+    unsafe fn from_raw(report: &crashpad_report_t) -> Self {
+        let string = |ptr: *const c_char| {
+            if ptr.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(ptr).to_string_lossy().into_owned()
+            }
+        };
+        let id = string(report.id);
+
+        Self {
+            uuid: string(report.uuid),
+            file_path: PathBuf::from(string(report.file_path)),
+            id: (!id.is_empty()).then_some(id),
+            creation_time: from_unix_time(report.creation_time).unwrap_or(UNIX_EPOCH),
+            uploaded: report.uploaded,
+            last_upload_attempt_time: from_unix_time(report.last_upload_attempt_time),
+            upload_attempts: report.upload_attempts.max(0) as u32,
+            upload_explicitly_requested: report.upload_explicitly_requested,
+            total_size: report.total_size,
+        }
+    }
+
+    /// [`creation_time`](Self::creation_time) in the local timezone.
+    ///
+    /// Crashpad records report times as Unix time, so they are the same on
+    /// every platform; the offset is the one in effect at that time.
+    /// [`Timestamp::to_rfc3339`] gives the UTC form used in crate-written
+    /// metadata.
+    pub fn created(&self) -> Timestamp {
+        Timestamp::local(self.creation_time)
+    }
+
+    /// [`last_upload_attempt_time`](Self::last_upload_attempt_time) in the
+    /// local timezone, `None` if no upload was attempted
+    pub fn last_upload_attempt(&self) -> Option<Timestamp> {
+        self.last_upload_attempt_time.map(Timestamp::local)
+    }
+
+    /// Annotations recorded in the report
+    ///
+    /// With the `serde` feature, read from the `<uuid>.annotations.json`
+    /// sidecar next to the minidump. For a report without one, the
+    /// annotations are read from the minidump and the sidecar is written, so
+    /// only the first call has to open the dump. Listing annotations of a
+    /// large database stays cheap after that. Without the feature, every
+    /// call reads the minidump.
+    ///
+    /// Reports of crashes that were probably stack overflows also have
+    /// `probable_stack_overflow=true`, see
+    /// [`Minidump::probable_stack_overflow`](minidump::Minidump::probable_stack_overflow),
+    /// and classified reports have `crash_category`, see
+    /// [`Minidump::crash_category`](minidump::Minidump::crash_category).
+    pub fn annotations(&self) -> Result<HashMap<String, String>> {
+        self.annotations_in(&StdFs)
+    }
+
+    /// [`annotations`](Self::annotations), with the files in `fs`
+    pub(crate) fn annotations_in(&self, fs: &impl Fs) -> Result<HashMap<String, String>> {
+        #[cfg(feature = "serde")]
+        let sidecar = self.annotations_path();
+        #[cfg(feature = "serde")]
+        match fs.read(&sidecar) {
+            Ok(json) => return Ok(serde_json::from_slice(&json).map_err(std::io::Error::from)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let annotations =
+            report_annotations(&minidump::Minidump::from_bytes(fs.read(&self.file_path)?)?)?;
+        // The sidecar is only a cache; a read-only database still works
+        #[cfg(feature = "serde")]
+        let _ = write_sidecar(fs, &sidecar, &annotations);
+        Ok(annotations)
+    }
+
+    /// A file name for the report from `template`, without extension
+    ///
+    /// For harvesters that organize copied dumps without opening each one;
+    /// the report's own file keeps its name, as Crashpad finds reports by
+    /// it. Placeholders:
+    /// - `{uuid}`: the report's UUID
+    /// - `{timestamp}`: its creation time in UTC, e.g. `20240501T123000Z`
+    /// - `{fingerprint}`: the crash fingerprint, see
+    ///   [`Minidump::fingerprint`](minidump::Minidump::fingerprint)
+    /// - `{<key>}`: the value of annotation `<key>`, e.g. `{version}`
+    ///
+    /// Values that are missing, like annotations the dump doesn't have,
+    /// become `unknown`. Characters other than ASCII letters, digits, `.`,
+    /// `-` and `_` are replaced by `_`, and an empty name by the UUID.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use crashpad_rs::CrashReportDatabase;
+    ///
+    /// let database = CrashReportDatabase::open("./crashpad_db")?;
+    /// for report in database.completed_reports()? {
+    ///     let name = report.file_name("{version}-{fingerprint}-{uuid}");
+    ///     std::fs::copy(&report.file_path, format!("/srv/dumps/{name}.dmp"))?;
+    /// }
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    pub fn file_name(&self, template: &str) -> String {
+        // A dump without readable annotations still gets a name
+        file_name::render(template, self, &self.annotations().unwrap_or_default())
+    }
+
+    /// How long the handler took to write the minidump
+    ///
+    /// Measured from the file's timestamps, from its creation to its last
+    /// write, so it covers writing the dump but not the time between the
+    /// crash and the handler starting to write. `None` if the minidump is
+    /// gone, or the filesystem doesn't record creation times (e.g. some
+    /// Linux filesystems and kernels before 4.11). See
+    /// [`metrics::DumpWriteStats`](crate::metrics::DumpWriteStats) to
+    /// aggregate it over reports.
+    pub fn dump_write_duration(&self) -> Option<Duration> {
+        let metadata = std::fs::metadata(&self.file_path).ok()?;
+        let created = metadata.created().ok()?;
+        Some(
+            metadata
+                .modified()
+                .ok()?
+                .duration_since(created)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Read the report's minidump, see [`Minidump`](crate::minidump::Minidump)
+    pub fn minidump(&self) -> Result<minidump::Minidump> {
+        minidump::Minidump::open(&self.file_path)
+    }
+
+    /// Path of the annotation sidecar
+    pub fn annotations_path(&self) -> PathBuf {
+        self.file_path.with_extension(ANNOTATIONS_EXTENSION)
+    }
+}
+
+/// Outcome of [`CrashReportDatabase::upload_pending_blocking`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UploadSummary {
+    /// Reports the server accepted
+    pub uploaded: Vec<CrashReport>,
+    /// Reports the handler finished without uploading them: the upload
+    /// failed, was rate limited, or uploads are disabled
+    pub not_uploaded: Vec<CrashReport>,
+    /// Reports still pending when the time ran out
+    pub pending: Vec<CrashReport>,
+}
+
+impl UploadSummary {
+    fn add(&mut self, report: CrashReport, still_pending: bool) {
+        if report.uploaded {
+            self.uploaded.push(report);
+        } else if still_pending {
+            self.pending.push(report);
+        } else {
+            self.not_uploaded.push(report);
+        }
+    }
+}
+
+/// Outcome of [`CrashReportDatabase::statistics`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseStatistics {
+    /// See [`CrashReportDatabase::client_id`]
+    pub client_id: String,
+    pub uploads_enabled: bool,
+    /// See [`CrashReportDatabase::last_upload_attempt_time`]
+    pub last_upload_attempt_time: Option<SystemTime>,
+    pub pending_reports: usize,
+    pub completed_reports: usize,
+    /// Upload attempts the server accepted
+    pub uploads_succeeded: u64,
+    /// Upload attempts that failed, e.g. with a network or server error
+    pub uploads_failed: u64,
+}
+
+impl DatabaseStatistics {
+    /// Add the upload attempts of `reports`. Every attempt of a report but
+    /// the one that uploaded it failed.
+    fn count_uploads<'a>(&mut self, reports: impl Iterator<Item = &'a CrashReport>) {
+        for report in reports {
+            let succeeded = u32::from(report.uploaded);
+            self.uploads_succeeded += u64::from(succeeded);
+            self.uploads_failed += u64::from(report.upload_attempts.saturating_sub(succeeded));
+        }
+    }
+}
+
+/// Write `annotations` to `path` through a temporary file, so readers never
+/// see a partial sidecar.
+#[cfg(feature = "serde")]
+pub(crate) fn write_sidecar(
+    fs: &impl Fs,
+    path: &Path,
+    annotations: &HashMap<String, String>,
+) -> std::io::Result<()> {
+    let json = serde_json::to_vec(annotations)?;
+    let temporary = path.with_extension("json.tmp");
+    fs.write(&temporary, &json)?;
+    fs.rename(&temporary, path)
+}
+
+/// Annotations of a report: those of its dump, plus
+/// `probable_stack_overflow=true` if
+/// [`probable_stack_overflow`](minidump::Minidump::probable_stack_overflow)
+/// and the [`crash_category`](minidump::Minidump::crash_category)
+pub(crate) fn report_annotations(dump: &minidump::Minidump) -> Result<HashMap<String, String>> {
+    let mut annotations = dump.annotations()?;
+    if dump.probable_stack_overflow()? {
+        annotations.insert(PROBABLE_STACK_OVERFLOW.to_string(), "true".to_string());
+    }
+    if let Some(category) = dump.crash_category()? {
+        annotations.insert(CRASH_CATEGORY.to_string(), category.to_string());
+    }
+    Ok(annotations)
+}
+
+/// Run `hook` on the annotations of `report` and write its changes to the
+/// minidump and the sidecar, see
+/// [`CrashReportDatabase::request_upload_with`]. Returns what `hook` returned.
+fn review_report<F>(fs: &impl Fs, report: &CrashReport, hook: F) -> Result<bool>
+where
+    F: FnOnce(&mut HashMap<String, String>) -> bool,
+{
+    let data = fs.read(&report.file_path)?;
+    let original = minidump::Minidump::from_bytes(data.clone())?.annotations()?;
+    let mut annotations = original.clone();
+    let upload = hook(&mut annotations);
+    if annotations == original {
+        return Ok(upload);
+    }
+
+    let rewritten = minidump::rewrite_annotations(&data, &annotations).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} has no Crashpad annotations", report.file_path.display()),
+        )
+    })?;
+    let temporary = report.file_path.with_extension("dmp.tmp");
+    fs.write(&temporary, &rewritten)?;
+    fs.rename(&temporary, &report.file_path)?;
+    #[cfg(feature = "serde")]
+    {
+        let sidecar = report_annotations(&minidump::Minidump::from_bytes(rewritten)?)?;
+        write_sidecar(fs, &report.annotations_path(), &sidecar)?;
+    }
+    Ok(upload)
+}
+
+/// Write the missing annotation sidecars of `reports`, see
+/// [`CrashReportDatabase::index_annotations`].
+#[cfg(feature = "serde")]
+fn index_annotations(fs: &impl Fs, reports: &[CrashReport]) -> Result<usize> {
+    let mut written = 0;
+    for report in reports {
+        let sidecar = report.annotations_path();
+        if fs.exists(&sidecar) {
+            continue;
+        }
+        let annotations = fs
+            .read(&report.file_path)
+            .map_err(CrashpadError::from)
+            .and_then(minidump::Minidump::from_bytes)
+            .and_then(|dump| report_annotations(&dump));
+        if let Ok(annotations) = annotations {
+            write_sidecar(fs, &sidecar, &annotations)?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+/// Seconds since the epoch, `None` for 0 ("never").
+fn from_unix_time(seconds: i64) -> Option<SystemTime> {
+    (seconds > 0).then(|| UNIX_EPOCH + Duration::from_secs(seconds as u64))
+}
+
+/// Reports written by a Crashpad handler.
+///
+/// Opens the same database directory passed to
+/// [`CrashpadConfigBuilder::database_path`](crate::CrashpadConfigBuilder::database_path).
+/// The handler may be writing to the database at the same time; Crashpad
+/// coordinates access with file locks.
+///
+/// # Example
+///
+/// ```no_run
+/// use crashpad_rs::CrashReportDatabase;
+///
+/// let database = CrashReportDatabase::open("./crashpad_db")?;
+/// for report in database.pending_reports()? {
+///     println!("{} {}", report.uuid, report.file_path.display());
+/// }
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+pub struct CrashReportDatabase {
+    handle: crashpad_database_t,
+    path: PathBuf,
+}
+
+impl CrashReportDatabase {
+    /// Open the database at `path`, creating it if it doesn't exist
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_impl(path.as_ref(), true)
+    }
+
+    /// Open the database at `path`, failing if it doesn't exist
+    pub fn open_existing<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_impl(path.as_ref(), false)
+    }
+
+    fn open_impl(path: &Path, create: bool) -> Result<Self> {
+        let path_c = path_to_cstring(path)?;
+        let handle = unsafe { crashpad_database_open(path_c.as_ptr(), create) };
+        if handle.is_null() {
+            return Err(CrashpadError::DatabaseError(format!(
+                "Failed to open {}",
+                path.display()
+            )));
+        }
+        Ok(Self {
+            handle,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Directory of the database
+    #[cfg_attr(not(feature = "upload"), allow(dead_code))]
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reports waiting to be uploaded
+    pub fn pending_reports(&self) -> Result<Vec<CrashReport>> {
+        self.reports(false)
+    }
+
+    /// Reports that were uploaded or won't be
+    pub fn completed_reports(&self) -> Result<Vec<CrashReport>> {
+        self.reports(true)
+    }
+
+    fn reports(&self, completed: bool) -> Result<Vec<CrashReport>> {
+        let mut reports = Vec::new();
+        let status = unsafe {
+            crashpad_database_get_reports(
+                self.handle,
+                completed,
+                Some(collect_report),
+                &mut reports as *mut Vec<CrashReport> as *mut c_void,
+            )
+        };
+        check_status(status, None)?;
+        Ok(reports)
+    }
+
+    /// Look up a report by UUID
+    pub fn report(&self, uuid: &str) -> Result<CrashReport> {
+        let uuid_c = uuid_to_cstring(uuid)?;
+        let mut reports = Vec::new();
+        let status = unsafe {
+            crashpad_database_look_up_report(
+                self.handle,
+                uuid_c.as_ptr(),
+                Some(collect_report),
+                &mut reports as *mut Vec<CrashReport> as *mut c_void,
+            )
+        };
+        check_status(status, Some(uuid))?;
+        reports
+            .pop()
+            .ok_or_else(|| CrashpadError::ReportNotFound(uuid.to_string()))
+    }
+
+    /// Delete a report, its minidump, its annotation sidecar, its claim and
+    /// its retry state
+    pub fn delete_report(&self, uuid: &str) -> Result<()> {
+        let sidecar = self.report(uuid)?.annotations_path();
+        let uuid_c = uuid_to_cstring(uuid)?;
+        let status = unsafe { crashpad_database_delete_report(self.handle, uuid_c.as_ptr()) };
+        check_status(status, Some(uuid))?;
+        claim::remove_claim(&StdFs, &self.path, uuid, &SystemClock, &RandomIds)?;
+        #[cfg(feature = "upload")]
+        crate::retry::remove_state(&StdFs, &self.path, uuid)?;
+        match StdFs.remove_file(&sidecar) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the handler may upload reports
+    ///
+    /// New databases start with uploads disabled.
+    pub fn uploads_enabled(&self) -> Result<bool> {
+        let mut enabled = false;
+        if unsafe { crashpad_database_get_uploads_enabled(self.handle, &mut enabled) } {
+            Ok(enabled)
+        } else {
+            Err(CrashpadError::DatabaseError(
+                "Failed to read settings".to_string(),
+            ))
+        }
+    }
+
+    /// Allow or forbid the handler to upload reports
+    ///
+    /// Takes effect for the next upload, also for a running handler.
+    pub fn set_uploads_enabled(&self, enabled: bool) -> Result<()> {
+        if unsafe { crashpad_database_set_uploads_enabled(self.handle, enabled) } {
+            Ok(())
+        } else {
+            Err(CrashpadError::DatabaseError(
+                "Failed to write settings".to_string(),
+            ))
+        }
+    }
+
+    /// The client ID the handler sends with every report
+    ///
+    /// A UUID created with the database and kept in its settings, so
+    /// telemetry can match crash-reporting health to the reports on the
+    /// server.
+    pub fn client_id(&self) -> Result<String> {
+        let mut client_id = [0 as c_char; 37];
+        if unsafe { crashpad_database_get_client_id(self.handle, client_id.as_mut_ptr()) } {
+            let client_id = unsafe { CStr::from_ptr(client_id.as_ptr()) };
+            Ok(client_id.to_string_lossy().into_owned())
+        } else {
+            Err(CrashpadError::DatabaseError(
+                "Failed to read settings".to_string(),
+            ))
+        }
+    }
+
+    /// When the handler last tried to upload a report, `None` if it never
+    /// did
+    pub fn last_upload_attempt_time(&self) -> Result<Option<SystemTime>> {
+        let mut time = 0;
+        if unsafe { crashpad_database_get_last_upload_attempt_time(self.handle, &mut time) } {
+            Ok(from_unix_time(time))
+        } else {
+            Err(CrashpadError::DatabaseError(
+                "Failed to read settings".to_string(),
+            ))
+        }
+    }
+
+    /// Crash-reporting health, for telemetry
+    ///
+    /// Reads the settings and counts the uploads of the reports still in
+    /// the database; reports that were pruned or deleted are no longer
+    /// counted. Unlike [`metrics::read`](crate::metrics::read), works with
+    /// any handler.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use crashpad_rs::CrashReportDatabase;
+    ///
+    /// let statistics = CrashReportDatabase::open("./crashpad_db")?.statistics()?;
+    /// println!(
+    ///     "{}: {} uploads, {} failed",
+    ///     statistics.client_id, statistics.uploads_succeeded, statistics.uploads_failed
+    /// );
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    pub fn statistics(&self) -> Result<DatabaseStatistics> {
+        let pending = self.pending_reports()?;
+        let completed = self.completed_reports()?;
+        let mut statistics = DatabaseStatistics {
+            client_id: self.client_id()?,
+            uploads_enabled: self.uploads_enabled()?,
+            last_upload_attempt_time: self.last_upload_attempt_time()?,
+            pending_reports: pending.len(),
+            completed_reports: completed.len(),
+            uploads_succeeded: 0,
+            uploads_failed: 0,
+        };
+        statistics.count_uploads(pending.iter().chain(&completed));
+        Ok(statistics)
+    }
+
+    /// Crashes the handler couldn't write a dump for, oldest first
+    ///
+    /// Filled from the handler's metrics each time the client starts, so
+    /// the crashes of a handler run show up after the next start, timed
+    /// by the handler's last metrics update. Only handlers that record
+    /// metrics (see [`metrics`](crate::metrics)) report them; with others
+    /// this is always empty. The journal keeps the last 100 events.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use crashpad_rs::CrashReportDatabase;
+    ///
+    /// for event in CrashReportDatabase::open("./crashpad_db")?.dropped_events()? {
+    ///     println!("{:?}: {} crashes without dump ({})", event.time, event.count, event.reason);
+    /// }
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    pub fn dropped_events(&self) -> Result<Vec<DroppedEvent>> {
+        Ok(dropped::read_journal(&StdFs, &self.path)?)
+    }
+
+    /// The hashed machine identifier added by
+    /// [`machine_id_annotation`](crate::CrashpadConfigBuilder::machine_id_annotation)
+    ///
+    /// `None` before the handler first started with the annotation, after
+    /// [`clear_machine_id`](Self::clear_machine_id), and on platforms
+    /// without a machine ID.
+    #[cfg(feature = "machine-id")]
+    pub fn machine_id(&self) -> Result<Option<String>> {
+        let Some(raw) = machine_id::raw_machine_id() else {
+            return Ok(None);
+        };
+        Ok(machine_id::current(&StdFs, &self.path, &raw)?)
+    }
+
+    /// Replace the machine identifier with a new, unrelated one
+    ///
+    /// Reports sent after the next handler start can no longer be grouped
+    /// with earlier ones. Returns the new identifier, `None` on platforms
+    /// without a machine ID.
+    #[cfg(feature = "machine-id")]
+    pub fn rotate_machine_id(&self) -> Result<Option<String>> {
+        machine_id::rotate(&StdFs, &self.path)?;
+        self.machine_id()
+    }
+
+    /// Forget the machine identifier
+    ///
+    /// No `machine_id` annotation is added from the next handler start on,
+    /// even with the annotation enabled, until
+    /// [`rotate_machine_id`](Self::rotate_machine_id) creates a new one.
+    #[cfg(feature = "machine-id")]
+    pub fn clear_machine_id(&self) -> Result<()> {
+        Ok(machine_id::clear(&StdFs, &self.path)?)
+    }
+
+    /// Claim a report for `owner` for `ttl`, before processing it
+    ///
+    /// Returns `false` if another owner holds a live claim; the owner's own
+    /// claim is renewed. See [`ReportClaim`] for the protocol, and
+    /// release the claim with [`unclaim`](Self::unclaim) when done. Owner IDs
+    /// should be unique per agent, e.g. a name and a process ID. Needs the
+    /// `serde` feature.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use crashpad_rs::CrashReportDatabase;
+    /// use std::time::Duration;
+    ///
+    /// let database = CrashReportDatabase::open("./crashpad_db")?;
+    /// for report in database.pending_reports()? {
+    ///     if database.claim(&report.uuid, "harvester-1", Duration::from_secs(300))? {
+    ///         // Ship report.file_path, then
+    ///         database.delete_report(&report.uuid)?;
+    ///     }
+    /// }
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if `uuid` isn't a UUID or
+    /// `owner` is empty, [`CrashpadError::IoError`] if the claim can't be
+    /// written.
+    #[cfg(feature = "serde")]
+    pub fn claim(&self, uuid: &str, owner: &str, ttl: Duration) -> Result<bool> {
+        check_uuid(uuid)?;
+        if owner.is_empty() {
+            return Err(CrashpadError::InvalidConfiguration(
+                "Claim owner is empty".to_string(),
+            ));
+        }
+        Ok(claim::claim(
+            &StdFs,
+            &self.path,
+            uuid,
+            owner,
+            ttl,
+            &SystemClock,
+            &RandomIds,
+        )?)
+    }
+
+    /// Release `owner`'s claim on a report
+    ///
+    /// Does nothing if the report isn't claimed, or claimed by another
+    /// owner.
+    #[cfg(feature = "serde")]
+    pub fn unclaim(&self, uuid: &str, owner: &str) -> Result<()> {
+        check_uuid(uuid)?;
+        Ok(claim::unclaim(
+            &StdFs,
+            &self.path,
+            uuid,
+            owner,
+            &SystemClock,
+            &RandomIds,
+        )?)
+    }
+
+    /// The live claim on a report, `None` if it isn't claimed or the claim
+    /// expired
+    #[cfg(feature = "serde")]
+    pub fn claim_of(&self, uuid: &str) -> Result<Option<ReportClaim>> {
+        check_uuid(uuid)?;
+        Ok(claim::live_claim(&StdFs, &self.path, uuid, &SystemClock)?)
+    }
+
+    /// Write annotation sidecars for completed reports that don't have one
+    ///
+    /// Returns the number of sidecars written. Reports whose minidump can't
+    /// be read are skipped. Run this in the background at startup to make
+    /// the first [`CrashReport::annotations`] call of a triage UI cheap.
+    /// Needs the `serde` feature, which the sidecars are written with.
+    #[cfg(feature = "serde")]
+    pub fn index_annotations(&self) -> Result<usize> {
+        index_annotations(&StdFs, &self.completed_reports()?)
+    }
+
+    /// Complete a pending report without uploading it
+    ///
+    /// The report is recorded as throttled, like reports skipped by the
+    /// handler's rate limit, and can still be uploaded later with
+    /// [`request_upload`](Self::request_upload).
+    pub fn skip_upload(&self, uuid: &str) -> Result<()> {
+        let uuid_c = uuid_to_cstring(uuid)?;
+        let status = unsafe { crashpad_database_skip_upload(self.handle, uuid_c.as_ptr()) };
+        check_status(status, Some(uuid))
+    }
+
+    /// Record an upload of pending report `uuid` made by the application
+    ///
+    /// With `Some(id)`, the server's report ID, the report is completed as
+    /// uploaded. With `None`, a failed attempt is recorded and the report
+    /// stays pending. For reports sent with `upload::send_report`, so the
+    /// database, its metrics and [`statistics`](Self::statistics) reflect
+    /// them like the handler's uploads.
+    pub fn record_upload(&self, uuid: &str, id: Option<&str>) -> Result<()> {
+        let uuid_c = uuid_to_cstring(uuid)?;
+        let id_c = CString::new(id.unwrap_or_default()).map_err(|_| {
+            CrashpadError::InvalidConfiguration("Report ID contains null byte".to_string())
+        })?;
+        let status = unsafe {
+            crashpad_database_record_upload(
+                self.handle,
+                uuid_c.as_ptr(),
+                id.is_some(),
+                id_c.as_ptr(),
+            )
+        };
+        check_status(status, Some(uuid))
+    }
+
+    /// Add a pending report with the minidump `data`, returning its UUID
+    pub(crate) fn add_report(&self, data: &[u8]) -> Result<String> {
+        let mut uuid = [0 as c_char; 37];
+        let status = unsafe {
+            crashpad_database_add_report(self.handle, data.as_ptr(), data.len(), uuid.as_mut_ptr())
+        };
+        check_status(status, None)?;
+        Ok(unsafe { CStr::from_ptr(uuid.as_ptr()) }
+            .to_string_lossy()
+            .into_owned())
+    }
+
+    /// Fold a burst of pending reports into one summary report
+    ///
+    /// If more than the policy's maximum number of pending reports were
+    /// created within its window, as when a service crash-loops, only the
+    /// oldest of them are left for upload. The others are completed without
+    /// uploading (see [`skip_upload`](Self::skip_upload)) and summarized in
+    /// a new pending report with their count, first and last creation
+    /// times and crash fingerprints, see [`BurstSummary`]. A pending
+    /// summary from an earlier call is merged into the new one, so the
+    /// server gets one summary per burst, however often this runs.
+    ///
+    /// Returns `None` if there is no burst. Run it before the handler
+    /// starts, as [`CrashpadConfigBuilder::crash_burst_limit`](crate::CrashpadConfigBuilder::crash_burst_limit)
+    /// does, so the handler doesn't upload the burst first.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use crashpad_rs::{BurstPolicy, CrashReportDatabase};
+    /// use std::time::Duration;
+    ///
+    /// let database = CrashReportDatabase::open("./crashpad_db")?;
+    /// let policy = BurstPolicy::new(5, Duration::from_secs(10 * 60));
+    /// if let Some(burst) = database.coalesce_burst(&policy)? {
+    ///     eprintln!("{} crashes since {:?}", burst.count, burst.first);
+    /// }
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    pub fn coalesce_burst(&self, policy: &BurstPolicy) -> Result<Option<BurstSummary>> {
+        burst::coalesce(self, policy, &SystemClock)
+    }
+
+    /// Give up on pending reports older than the policy's maximum age
+    ///
+    /// A machine that was offline for months would otherwise upload all
+    /// its old reports on reconnect. Expired reports are completed without
+    /// uploading (see [`skip_upload`](Self::skip_upload)), or deleted with
+    /// [`ExpiryPolicy::delete_expired`]. Reports whose upload was requested
+    /// with [`request_upload`](Self::request_upload) and claimed reports
+    /// (see [`claim`](Self::claim)) don't expire. Returns the UUIDs of the
+    /// expired reports.
+    ///
+    /// Run it before the handler starts, as
+    /// [`CrashpadConfigBuilder::expire_pending_reports`](crate::CrashpadConfigBuilder::expire_pending_reports)
+    /// does, so the handler doesn't upload them first.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use crashpad_rs::{CrashReportDatabase, ExpiryPolicy};
+    /// use std::time::Duration;
+    ///
+    /// let database = CrashReportDatabase::open("./crashpad_db")?;
+    /// let policy = ExpiryPolicy::new(Duration::from_secs(30 * 24 * 60 * 60));
+    /// let expired = database.expire_stale_reports(&policy)?;
+    /// println!("{} reports expired", expired.len());
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    pub fn expire_stale_reports(&self, policy: &ExpiryPolicy) -> Result<Vec<String>> {
+        expiry::expire(self, policy, &SystemClock)
+    }
+
+    /// Delete the reports that match `condition`
+    ///
+    /// Returns the number of reports deleted. Uses Crashpad's own pruning,
+    /// which the handler also runs with its default limits (365 days or
+    /// 128 MB) when periodic tasks are enabled. Pending reports are
+    /// considered too, except claimed ones (see [`claim`](Self::claim));
+    /// nothing is deleted if the claims can't be read. Annotation sidecars
+    /// of deleted reports are left for [`clean_database`](crate::clean_database).
+    ///
+    /// See [`DatabasePruner`](crate::DatabasePruner) to prune periodically.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use crashpad_rs::{CrashReportDatabase, PruneCondition};
+    ///
+    /// let database = CrashReportDatabase::open("./crashpad_db")?;
+    /// let pruned = database.prune(&PruneCondition::new().max_size_mb(20));
+    /// println!("Deleted {pruned} old reports");
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    pub fn prune(&self, condition: &PruneCondition) -> usize {
+        if condition.is_empty() {
+            return 0;
+        }
+        let Ok(claimed) = claim::claimed_reports(&StdFs, &self.path, &SystemClock) else {
+            return 0;
+        };
+        let claimed: Vec<CString> = claimed
+            .into_iter()
+            .filter_map(|uuid| CString::new(uuid).ok())
+            .collect();
+        let keep: Vec<*const c_char> = claimed.iter().map(|uuid| uuid.as_ptr()).collect();
+        unsafe {
+            crashpad_database_prune_except(
+                self.handle,
+                condition.age_days(),
+                condition.size_kb(),
+                condition.requires_both(),
+                keep.as_ptr(),
+                keep.len(),
+            )
+        }
+    }
+
+    /// Ask the handler to upload a report
+    ///
+    /// Works for completed reports too, e.g. ones skipped by rate limiting.
+    /// The upload happens the next time the handler processes pending
+    /// reports, if a URL is configured.
+    pub fn request_upload(&self, uuid: &str) -> Result<()> {
+        let uuid_c = uuid_to_cstring(uuid)?;
+        let status = unsafe { crashpad_database_request_upload(self.handle, uuid_c.as_ptr()) };
+        check_status(status, Some(uuid))
+    }
+
+    /// Let `hook` review and edit the annotations of a report, then request
+    /// its upload if it returns `true`
+    ///
+    /// `hook` gets the annotations of the report's minidump and can change,
+    /// add or remove them, e.g. to strip user names from paths. Changes are
+    /// written to the minidump, which is what gets uploaded, and to the
+    /// annotation sidecar. If `hook` returns `false`, a pending report is
+    /// completed without upload, so it isn't sent unreviewed later. Returns
+    /// whether the upload was requested.
+    ///
+    /// For every upload to be reviewed, keep the handler's uploads disabled
+    /// with [`set_uploads_enabled`](Self::set_uploads_enabled): the handler
+    /// then only sends reports whose upload was requested, and completes the
+    /// others without upload, where they wait for review.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use crashpad_rs::CrashReportDatabase;
+    ///
+    /// let database = CrashReportDatabase::open("./crashpad_db")?;
+    /// for report in database.completed_reports()? {
+    ///     if report.uploaded || report.upload_explicitly_requested {
+    ///         continue;
+    ///     }
+    ///     database.request_upload_with(&report.uuid, |annotations| {
+    ///         annotations.remove("user_name");
+    ///         for value in annotations.values_mut() {
+    ///             *value = value.replace("/home/alice", "/home/<user>");
+    ///         }
+    ///         true
+    ///     })?;
+    /// }
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::ReportNotFound`] if there is no such report, and
+    /// [`CrashpadError::IoError`] if the minidump can't be read or
+    /// rewritten, or wasn't written by Crashpad.
+    pub fn request_upload_with<F>(&self, uuid: &str, hook: F) -> Result<bool>
+    where
+        F: FnOnce(&mut HashMap<String, String>) -> bool,
+    {
+        let report = self.report(uuid)?;
+        let upload = review_report(&StdFs, &report, hook)?;
+        if upload {
+            self.request_upload(uuid)?;
+        } else if self
+            .pending_reports()?
+            .iter()
+            .any(|pending| pending.uuid == uuid)
+        {
+            self.skip_upload(uuid)?;
+        }
+        Ok(upload)
+    }
+
+    /// Wait up to `max_duration` for the handler to upload the pending reports
+    ///
+    /// For command line tools and batch jobs that exit right after a
+    /// failure: call it after [`dump_without_crash`](crate::CrashpadClient::dump_without_crash)
+    /// or before exiting, so the handler gets the chance to upload before the
+    /// process (and with it, often the handler) goes away.
+    ///
+    /// If uploads are enabled, the pending reports are marked as explicitly
+    /// requested first, which exempts them from the handler's rate limit.
+    /// The upload itself is done by the running handler: it uploads a new
+    /// report right after writing it, while older pending reports are only
+    /// picked up on its next periodic scan. Returns early once none of the
+    /// reports is pending anymore. Reports claimed by another agent (see
+    /// [`claim`](Self::claim)) are left to it and not in the summary.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use crashpad_rs::CrashReportDatabase;
+    /// use std::time::Duration;
+    ///
+    /// let database = CrashReportDatabase::open("./crashpad_db")?;
+    /// let summary = database.upload_pending_blocking(Duration::from_secs(5))?;
+    /// for report in &summary.uploaded {
+    ///     eprintln!("Crash report sent: {}", report.id.as_deref().unwrap_or("?"));
+    /// }
+    /// # Ok::<(), crashpad_rs::CrashpadError>(())
+    /// ```
+    pub fn upload_pending_blocking(&self, max_duration: Duration) -> Result<UploadSummary> {
+        let deadline = Instant::now() + max_duration;
+        let claimed: HashSet<String> = claim::claimed_reports(&StdFs, &self.path, &SystemClock)?
+            .into_iter()
+            .collect();
+        let waiting: Vec<CrashReport> = self
+            .pending_reports()?
+            .into_iter()
+            .filter(|report| !claimed.contains(&report.uuid))
+            .collect();
+        if waiting.is_empty() {
+            return Ok(UploadSummary::default());
+        }
+
+        if matches!(self.uploads_enabled(), Ok(true)) {
+            for report in &waiting {
+                // Best effort: the handler may already be done with it
+                let _ = self.request_upload(&report.uuid);
+            }
+        }
+
+        let mut pending: HashSet<String>;
+        loop {
+            pending = self
+                .pending_reports()?
+                .into_iter()
+                .map(|report| report.uuid)
+                .collect();
+            let now = Instant::now();
+            if now >= deadline || waiting.iter().all(|report| !pending.contains(&report.uuid)) {
+                break;
+            }
+            std::thread::sleep(UPLOAD_POLL_INTERVAL.min(deadline - now));
+        }
+
+        let mut summary = UploadSummary::default();
+        for report in waiting {
+            match self.report(&report.uuid) {
+                Ok(report) => {
+                    let still_pending = pending.contains(&report.uuid);
+                    summary.add(report, still_pending);
+                }
+                // Deleted in the meantime
+                Err(CrashpadError::ReportNotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(summary)
+    }
+}
+
+impl Drop for CrashReportDatabase {
+    fn drop(&mut self) {
+        unsafe {
+            crashpad_database_close(self.handle);
+        }
+    }
+}
+
+// The database is only used from one thread at a time (no Sync), and
+// Crashpad's database objects aren't tied to the thread that created them
+unsafe impl Send for CrashReportDatabase {}
+
+/// Visitor that pushes reports into the `Vec<CrashReport>` at `context`.
+unsafe extern "C" fn collect_report(context: *mut c_void, report: *const crashpad_report_t) {
+    let reports = &mut *(context as *mut Vec<CrashReport>);
+    reports.push(CrashReport::from_raw(&*report));
+}
+
+/// Check that `uuid` is a report UUID, which is also safe as a file name
+#[cfg(feature = "serde")]
+fn check_uuid(uuid: &str) -> Result<()> {
+    if uuid.is_empty() || !uuid.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        return Err(CrashpadError::InvalidConfiguration(
+            "Invalid report UUID".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn uuid_to_cstring(uuid: &str) -> Result<CString> {
+    CString::new(uuid)
+        .map_err(|_| CrashpadError::InvalidConfiguration("Invalid report UUID".to_string()))
+}
+
+/// Convert a `CRASHPAD_DATABASE_*` status into a result.
+fn check_status(status: c_int, uuid: Option<&str>) -> Result<()> {
+    let message = match status as u32 {
+        CRASHPAD_DATABASE_NO_ERROR => return Ok(()),
+        CRASHPAD_DATABASE_REPORT_NOT_FOUND => {
+            return Err(CrashpadError::ReportNotFound(
+                uuid.unwrap_or_default().to_string(),
+            ))
+        }
+        CRASHPAD_DATABASE_FILE_SYSTEM_ERROR => "file system error",
+        CRASHPAD_DATABASE_DATABASE_ERROR => "database is corrupt",
+        CRASHPAD_DATABASE_BUSY_ERROR => "report is in use",
+        CRASHPAD_DATABASE_CANNOT_REQUEST_UPLOAD => "upload cannot be requested",
+        _ => "unknown error",
+    };
+    Err(CrashpadError::DatabaseError(message.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::memory::MemoryFs;
+
+    #[test]
+    fn test_check_status() {
+        assert!(check_status(CRASHPAD_DATABASE_NO_ERROR as c_int, None).is_ok());
+        assert!(matches!(
+            check_status(CRASHPAD_DATABASE_REPORT_NOT_FOUND as c_int, Some("abc")),
+            Err(CrashpadError::ReportNotFound(uuid)) if uuid == "abc"
+        ));
+        assert!(matches!(
+            check_status(CRASHPAD_DATABASE_BUSY_ERROR as c_int, None),
+            Err(CrashpadError::DatabaseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_report_from_raw() {
+        let uuid = CString::new("00112233-4455-6677-8899-aabbccddeeff").unwrap();
+        let file_path = CString::new("/db/completed/report.dmp").unwrap();
+        let id = CString::new("").unwrap();
+        let raw = crashpad_report_t {
+            uuid: uuid.as_ptr(),
+            file_path: file_path.as_ptr(),
+            id: id.as_ptr(),
+            creation_time: 1_700_000_000,
+            uploaded: false,
+            last_upload_attempt_time: 0,
+            upload_attempts: 0,
+            upload_explicitly_requested: true,
+            total_size: 4096,
+        };
+
+        let report = unsafe { CrashReport::from_raw(&raw) };
+
+        assert_eq!(report.uuid, "00112233-4455-6677-8899-aabbccddeeff");
+        assert_eq!(report.file_path, Path::new("/db/completed/report.dmp"));
+        assert_eq!(report.id, None);
+        assert_eq!(
+            report.creation_time,
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+        );
+        assert_eq!(report.last_upload_attempt_time, None);
+        assert_eq!(report.created().to_rfc3339(), "2023-11-14T22:13:20Z");
+        assert_eq!(report.last_upload_attempt(), None);
+        assert!(report.upload_explicitly_requested);
+        assert_eq!(report.total_size, 4096);
+    }
+
+    fn report_at(file_path: PathBuf) -> CrashReport {
+        CrashReport {
+            uuid: "00112233-4455-6677-8899-aabbccddeeff".to_string(),
+            file_path,
+            id: None,
+            creation_time: UNIX_EPOCH,
+            uploaded: false,
+            last_upload_attempt_time: None,
+            upload_attempts: 0,
+            upload_explicitly_requested: false,
+            total_size: 0,
+        }
+    }
+
+    #[test]
+    fn test_count_uploads() {
+        let mut uploaded = report_at(PathBuf::from("db/completed/a.dmp"));
+        uploaded.uploaded = true;
+        uploaded.upload_attempts = 3;
+        let mut failed = report_at(PathBuf::from("db/completed/b.dmp"));
+        failed.upload_attempts = 5;
+        // Skipped without an attempt
+        let skipped = report_at(PathBuf::from("db/completed/c.dmp"));
+
+        let mut statistics = DatabaseStatistics {
+            client_id: String::new(),
+            uploads_enabled: true,
+            last_upload_attempt_time: None,
+            pending_reports: 0,
+            completed_reports: 3,
+            uploads_succeeded: 0,
+            uploads_failed: 0,
+        };
+        statistics.count_uploads([uploaded, failed, skipped].iter());
+        assert_eq!(statistics.uploads_succeeded, 1);
+        assert_eq!(statistics.uploads_failed, 7);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_annotations_sidecar() {
+        let dump_path = PathBuf::from("db/completed/report.dmp");
+        let fs = MemoryFs::default().with_file(
+            &dump_path,
+            minidump::tests::dump_with_annotations(&[("product", "MyApp")], &[]),
+        );
+        let report = report_at(dump_path.clone());
+        assert_eq!(
+            report.annotations_path(),
+            Path::new("db/completed/report.annotations.json")
+        );
+
+        assert_eq!(report.annotations_in(&fs).unwrap()["product"], "MyApp");
+        assert!(fs.exists(&report.annotations_path()));
+
+        // Later calls don't need the minidump
+        fs.remove_file(&dump_path).unwrap();
+        assert_eq!(report.annotations_in(&fs).unwrap()["product"], "MyApp");
+    }
+
+    #[test]
+    fn test_review_report() {
+        let dump_path = PathBuf::from("db/pending/report.dmp");
+        let dump =
+            minidump::tests::dump_with_annotations(&[("product", "MyApp"), ("user", "alice")], &[]);
+        let fs = MemoryFs::default().with_file(&dump_path, dump.clone());
+        let report = report_at(dump_path.clone());
+
+        // Unchanged annotations leave the dump alone
+        assert!(!review_report(&fs, &report, |_| false).unwrap());
+        assert_eq!(fs.read(&dump_path).unwrap(), dump);
+
+        assert!(review_report(&fs, &report, |annotations| {
+            annotations.remove("user");
+            true
+        })
+        .unwrap());
+        let annotations = minidump::Minidump::from_bytes(fs.read(&dump_path).unwrap())
+            .unwrap()
+            .annotations()
+            .unwrap();
+        assert!(!annotations.contains_key("user"));
+        assert_eq!(annotations["product"], "MyApp");
+        // The sidecar follows
+        assert!(!report.annotations_in(&fs).unwrap().contains_key("user"));
+    }
+
+    #[test]
+    fn test_stack_overflow_annotation() {
+        let dump_path = PathBuf::from("db/completed/report.dmp");
+        let module = ("/usr/bin/app", 0x5555_0000_0000, 0x10000);
+        let fs = MemoryFs::default().with_file(
+            &dump_path,
+            minidump::tests::amd64_crash_dump(0xb, minidump::tests::STACK_START - 8, module, &[]),
+        );
+        let annotations = report_at(dump_path).annotations_in(&fs).unwrap();
+        assert_eq!(annotations[PROBABLE_STACK_OVERFLOW], "true");
+        assert_eq!(annotations[CRASH_CATEGORY], "stack-overflow");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_index_annotations() {
+        let dump = minidump::tests::dump_with_annotations(&[("product", "MyApp")], &[]);
+        let fs = MemoryFs::default()
+            .with_file("db/completed/a.dmp", &dump)
+            .with_file("db/completed/b.dmp", &dump)
+            .with_file("db/completed/b.annotations.json", "{}")
+            .with_file("db/completed/c.dmp", "not a minidump");
+        let reports =
+            ["a", "b", "c"].map(|name| report_at(format!("db/completed/{name}.dmp").into()));
+
+        assert_eq!(index_annotations(&fs, &reports).unwrap(), 1);
+        assert!(fs.exists(Path::new("db/completed/a.annotations.json")));
+        assert!(!fs.exists(Path::new("db/completed/c.annotations.json")));
+    }
+
+    #[test]
+    fn test_upload_summary() {
+        let uploaded = CrashReport {
+            uploaded: true,
+            ..report_at(PathBuf::from("uploaded.dmp"))
+        };
+        let failed = report_at(PathBuf::from("failed.dmp"));
+        let pending = report_at(PathBuf::from("pending.dmp"));
+
+        let mut summary = UploadSummary::default();
+        summary.add(uploaded.clone(), false);
+        summary.add(failed.clone(), false);
+        summary.add(pending.clone(), true);
+
+        assert_eq!(
+            summary,
+            UploadSummary {
+                uploaded: vec![uploaded],
+                not_uploaded: vec![failed],
+                pending: vec![pending],
+            }
+        );
+    }
+
+    #[test]
+    fn test_annotations_invalid_dump() {
+        let fs = MemoryFs::default().with_file("report.dmp", b"not a minidump");
+        let report = report_at(PathBuf::from("report.dmp"));
+
+        assert!(matches!(
+            report.annotations_in(&fs),
+            Err(CrashpadError::IoError(e)) if e.kind() == std::io::ErrorKind::InvalidData
+        ));
+        assert!(!fs.exists(&report.annotations_path()));
+    }
+}
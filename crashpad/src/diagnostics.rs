@@ -0,0 +1,195 @@
+//! Detection of system settings that reduce dump quality.
+
+use std::fmt;
+
+/// How much a [`Diagnostic`] affects crash reporting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Dumps may be less useful than expected
+    Info,
+    /// Dumps may be missing or unusable
+    Warning,
+}
+
+/// A system setting known to interfere with dump capture or stack walking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable identifier, e.g. `ptrace_restricted`
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{severity} [{}]: {}", self.code, self.message)
+    }
+}
+
+/// Check the running system for settings that break or degrade dumps.
+///
+/// Cheap enough to call at startup. Detected on Linux/Android:
+/// - `ptrace_restricted`: Yama `ptrace_scope` keeps the handler from
+///   reading the crashed process, so no dump is written
+/// - `aslr_disabled`: address randomization is off, usually a sign of a
+///   debugging or benchmarking setup the dumps won't be representative of
+/// - `stack_unlimited`: with an unlimited stack rlimit, the kernel uses the
+///   legacy memory layout where an overflowing stack can run into other
+///   mappings instead of faulting on its guard page
+/// - `thp_always`: transparent huge pages for all memory inflate the
+///   memory captured around faulting addresses
+///
+/// Returns nothing on other platforms.
+pub fn startup_diagnostics() -> Vec<Diagnostic> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let read = |path: &str| std::fs::read_to_string(path).ok();
+        linux_diagnostics(read, stack_unlimited())
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        Vec::new()
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn stack_unlimited() -> bool {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    let ok = unsafe { libc::getrlimit(libc::RLIMIT_STACK, &mut limit) } == 0;
+    ok && limit.rlim_cur == libc::RLIM_INFINITY
+}
+
+/// Diagnostics from the contents of `/proc` and `/sys` files returned by
+/// `read`.
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+fn linux_diagnostics<F>(read: F, stack_unlimited: bool) -> Vec<Diagnostic>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut diagnostics = Vec::new();
+    let number = |path: &str| read(path).and_then(|s| s.trim().parse::<u32>().ok());
+
+    // 0 and 1 allow the handler, which Crashpad registers with PR_SET_PTRACER
+    if let Some(scope) = number("/proc/sys/kernel/yama/ptrace_scope").filter(|s| *s >= 2) {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            code: "ptrace_restricted",
+            message: format!(
+                "kernel.yama.ptrace_scope is {scope}; the handler cannot read crashed processes \
+                 without CAP_SYS_PTRACE"
+            ),
+        });
+    }
+
+    if number("/proc/sys/kernel/randomize_va_space") == Some(0) {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Info,
+            code: "aslr_disabled",
+            message: "Address space layout randomization is disabled \
+                      (kernel.randomize_va_space = 0)"
+                .to_string(),
+        });
+    }
+
+    if stack_unlimited {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            code: "stack_unlimited",
+            message: "Stack size limit is unlimited; stack overflows may not fault on a \
+                      guard page"
+                .to_string(),
+        });
+    }
+
+    let thp = read("/sys/kernel/mm/transparent_hugepage/enabled");
+    if thp.as_deref().and_then(selected_option) == Some("always") {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Info,
+            code: "thp_always",
+            message: "Transparent huge pages are enabled for all memory".to_string(),
+        });
+    }
+
+    diagnostics
+}
+
+/// The bracketed choice of a sysfs option list like `always [madvise] never`.
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+fn selected_option(options: &str) -> Option<&str> {
+    options
+        .split_whitespace()
+        .find_map(|option| option.strip_prefix('[')?.strip_suffix(']'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn diagnose(files: &[(&str, &str)], stack_unlimited: bool) -> Vec<&'static str> {
+        let files: HashMap<_, _> = files.iter().copied().collect();
+        linux_diagnostics(
+            |path| files.get(path).map(|s| s.to_string()),
+            stack_unlimited,
+        )
+        .into_iter()
+        .map(|d| d.code)
+        .collect()
+    }
+
+    #[test]
+    fn test_healthy_system() {
+        let files = [
+            ("/proc/sys/kernel/yama/ptrace_scope", "1\n"),
+            ("/proc/sys/kernel/randomize_va_space", "2\n"),
+            (
+                "/sys/kernel/mm/transparent_hugepage/enabled",
+                "always [madvise] never\n",
+            ),
+        ];
+        assert!(diagnose(&files, false).is_empty());
+        // Missing files (no Yama, no THP) are not a problem either
+        assert!(diagnose(&[], false).is_empty());
+    }
+
+    #[test]
+    fn test_problematic_system() {
+        let files = [
+            ("/proc/sys/kernel/yama/ptrace_scope", "3\n"),
+            ("/proc/sys/kernel/randomize_va_space", "0\n"),
+            (
+                "/sys/kernel/mm/transparent_hugepage/enabled",
+                "[always] madvise never\n",
+            ),
+        ];
+        assert_eq!(
+            diagnose(&files, true),
+            vec![
+                "ptrace_restricted",
+                "aslr_disabled",
+                "stack_unlimited",
+                "thp_always"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let diagnostic = Diagnostic {
+            severity: Severity::Warning,
+            code: "stack_unlimited",
+            message: "Stack size limit is unlimited".to_string(),
+        };
+        assert_eq!(
+            diagnostic.to_string(),
+            "warning [stack_unlimited]: Stack size limit is unlimited"
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! Journal of crashes the handler couldn't write a dump for.
+//!
+//! Crashpad counts every capture in the `Crashpad.ExceptionCaptureResult`
+//! histogram of its metrics (see [`metrics`](crate::metrics)) but writes
+//! nothing to the database when a capture fails, so a period without
+//! reports can't be told apart from one without crashes. When the client
+//! starts, before the new handler rotates the metrics, the failures the
+//! previous handler counted are appended to `dropped_events.log` in the
+//! database, one `<seconds since the Unix epoch> <capture result> <count>`
+//! line each, timed by the last write of the metrics file.
+//! [`CrashReportDatabase::dropped_events`](crate::CrashReportDatabase::dropped_events)
+//! reads them back.
+//!
+//! Only handlers that record metrics, built with Chromium's `base`, feed
+//! the journal.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::fs::{Fs, StdFs};
+use crate::metrics::Histogram;
+
+/// Journal file in the database
+const JOURNAL_FILE: &str = "dropped_events.log";
+
+/// Metrics file of the running handler, which becomes the previous one's
+/// when the next handler starts
+const ACTIVE_METRICS_FILE: &str = "CrashpadMetrics-active.pma";
+
+/// Histogram of `CaptureResult`s
+const CAPTURE_RESULT: &str = "Crashpad.ExceptionCaptureResult";
+
+/// Oldest events are dropped beyond this
+const MAX_EVENTS: usize = 100;
+
+/// Why the handler didn't write a dump, from Crashpad's
+/// `Metrics::CaptureResult`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DropReason {
+    /// `unexpected-exception-behavior`: the exception port was set up with
+    /// a behavior the handler doesn't support (macOS)
+    UnexpectedExceptionBehavior,
+    /// `suspend-failed`: the crashed process couldn't be suspended
+    SuspendFailed,
+    /// `snapshot-failed`: the crashed process couldn't be read
+    SnapshotFailed,
+    /// `exception-init-failed`: the exception couldn't be read
+    ExceptionInitializationFailed,
+    /// `prepare-report-failed`: the database couldn't create a report, e.g.
+    /// on a full disk
+    PrepareReportFailed,
+    /// `minidump-write-failed`: writing the dump failed
+    MinidumpWriteFailed,
+    /// `finish-report-failed`: the database couldn't complete the report
+    FinishReportFailed,
+    /// `ptrace-failed`: the handler couldn't attach to the crashed process,
+    /// directly or through a ptrace broker (Linux, Android)
+    PtraceFailed,
+    /// `sanitization-failed`: sanitization was requested but couldn't be
+    /// set up
+    SanitizationFailed,
+    /// `sanitized-out`: sanitization skipped the dump
+    SkippedBySanitization,
+    /// `other`: a result this crate doesn't know
+    Other(i32),
+}
+
+impl DropReason {
+    /// Reason of a non-successful `CaptureResult` `value`
+    fn from_capture_result(value: i32) -> Self {
+        match value {
+            1 => Self::UnexpectedExceptionBehavior,
+            2 => Self::SuspendFailed,
+            3 => Self::SnapshotFailed,
+            4 => Self::ExceptionInitializationFailed,
+            5 => Self::PrepareReportFailed,
+            6 => Self::MinidumpWriteFailed,
+            7 => Self::FinishReportFailed,
+            8 | 9 => Self::PtraceFailed,
+            10 => Self::SanitizationFailed,
+            11 => Self::SkippedBySanitization,
+            value => Self::Other(value),
+        }
+    }
+
+    /// The stable name, e.g. `minidump-write-failed`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UnexpectedExceptionBehavior => "unexpected-exception-behavior",
+            Self::SuspendFailed => "suspend-failed",
+            Self::SnapshotFailed => "snapshot-failed",
+            Self::ExceptionInitializationFailed => "exception-init-failed",
+            Self::PrepareReportFailed => "prepare-report-failed",
+            Self::MinidumpWriteFailed => "minidump-write-failed",
+            Self::FinishReportFailed => "finish-report-failed",
+            Self::PtraceFailed => "ptrace-failed",
+            Self::SanitizationFailed => "sanitization-failed",
+            Self::SkippedBySanitization => "sanitized-out",
+            Self::Other(_) => "other",
+        }
+    }
+}
+
+impl fmt::Display for DropReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Crashes of one handler run that got no dump for the same reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DroppedEvent {
+    /// When the handler last updated its metrics, to the second; the
+    /// crashes happened before
+    pub time: SystemTime,
+    pub reason: DropReason,
+    /// Number of crashes
+    pub count: u64,
+}
+
+/// Path of the journal in `database`
+fn journal_path(database: &Path) -> PathBuf {
+    database.join(JOURNAL_FILE)
+}
+
+/// Journal lines of the failures in the capture result histogram of one
+/// handler run, last updated at `time`
+fn journal_lines(capture_results: &Histogram, time: SystemTime) -> Vec<String> {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    capture_results
+        .buckets
+        .iter()
+        .filter(|bucket| bucket.min != 0 && bucket.count > 0)
+        .map(|bucket| format!("{seconds} {} {}", bucket.min, bucket.count))
+        .collect()
+}
+
+fn parse_line(line: &str) -> Option<DroppedEvent> {
+    let mut fields = line.split(' ');
+    let event = DroppedEvent {
+        time: UNIX_EPOCH + Duration::from_secs(fields.next()?.parse().ok()?),
+        reason: DropReason::from_capture_result(fields.next()?.parse().ok()?),
+        count: fields.next()?.parse().ok()?,
+    };
+    fields.next().is_none().then_some(event)
+}
+
+/// Append the failures in `capture_results`, counted by a handler run that
+/// last updated its metrics at `time`, to the journal of `database`
+///
+/// A run already in the journal, recognized by its time, isn't added again.
+fn append(
+    fs: &impl Fs,
+    database: &Path,
+    capture_results: &Histogram,
+    time: SystemTime,
+) -> io::Result<()> {
+    let new = journal_lines(capture_results, time);
+    let Some(first) = new.first() else {
+        return Ok(());
+    };
+    let path = journal_path(database);
+    let journal = match fs.read(&path) {
+        Ok(journal) => String::from_utf8_lossy(&journal).into_owned(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let run = first.split(' ').next();
+    let mut lines: Vec<&str> = journal.lines().collect();
+    if lines.iter().any(|line| line.split(' ').next() == run) {
+        return Ok(());
+    }
+    lines.extend(new.iter().map(String::as_str));
+    let keep = &lines[lines.len().saturating_sub(MAX_EVENTS)..];
+
+    let temporary = path.with_extension("log.tmp");
+    fs.write(&temporary, format!("{}\n", keep.join("\n")).as_bytes())?;
+    fs.rename(&temporary, &path)
+}
+
+/// Journal the failed captures of the previous handler, whose metrics are
+/// in `metrics_dir`, in `database`. Run before a new handler starts.
+pub(crate) fn journal_previous_run(database: &Path, metrics_dir: &Path) -> io::Result<()> {
+    let file = metrics_dir.join(ACTIVE_METRICS_FILE);
+    let Ok(time) = std::fs::metadata(&file).and_then(|metadata| metadata.modified()) else {
+        return Ok(());
+    };
+    // Unreadable metrics only mean nothing is journaled
+    let Ok(metrics) = crate::metrics::read(&file) else {
+        return Ok(());
+    };
+    match metrics.histogram(CAPTURE_RESULT) {
+        Some(capture_results) => append(&StdFs, database, capture_results, time),
+        None => Ok(()),
+    }
+}
+
+/// The journal of `database`, oldest first.
+pub(crate) fn read_journal(fs: &impl Fs, database: &Path) -> io::Result<Vec<DroppedEvent>> {
+    match fs.read(&journal_path(database)) {
+        Ok(journal) => Ok(String::from_utf8_lossy(&journal)
+            .lines()
+            .filter_map(parse_line)
+            .collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::memory::MemoryFs;
+    use crate::metrics::{Bucket, HistogramKind};
+
+    fn capture_results(counts: &[(i32, u64)]) -> Histogram {
+        Histogram {
+            name: CAPTURE_RESULT.to_string(),
+            kind: HistogramKind::Linear,
+            buckets: counts
+                .iter()
+                .map(|&(value, count)| Bucket {
+                    min: value,
+                    max: value + 1,
+                    count,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_journal() {
+        let fs = MemoryFs::default();
+        let database = Path::new("db");
+        fs.create_dir_all(database).unwrap();
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        // Successful captures aren't drops
+        append(&fs, database, &capture_results(&[(0, 4)]), time).unwrap();
+        assert!(read_journal(&fs, database).unwrap().is_empty());
+
+        let histogram = capture_results(&[(0, 4), (5, 2), (42, 1)]);
+        append(&fs, database, &histogram, time).unwrap();
+        // The same run again, as when the handler didn't start
+        append(&fs, database, &histogram, time).unwrap();
+        assert_eq!(
+            read_journal(&fs, database).unwrap(),
+            vec![
+                DroppedEvent {
+                    time,
+                    reason: DropReason::PrepareReportFailed,
+                    count: 2,
+                },
+                DroppedEvent {
+                    time,
+                    reason: DropReason::Other(42),
+                    count: 1,
+                },
+            ]
+        );
+        assert_eq!(
+            fs.read(&journal_path(database)).unwrap(),
+            b"1700000000 5 2\n1700000000 42 1\n"
+        );
+    }
+
+    #[test]
+    fn test_journal_keeps_recent_events() {
+        let fs = MemoryFs::default();
+        let database = Path::new("db");
+        fs.create_dir_all(database).unwrap();
+
+        for run in 0..MAX_EVENTS as u64 + 5 {
+            let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000 + run);
+            append(&fs, database, &capture_results(&[(6, 1)]), time).unwrap();
+        }
+        let events = read_journal(&fs, database).unwrap();
+        assert_eq!(events.len(), MAX_EVENTS);
+        assert_eq!(
+            events[0].time,
+            UNIX_EPOCH + Duration::from_secs(1_700_000_005)
+        );
+        assert_eq!(events[0].reason, DropReason::MinidumpWriteFailed);
+
+        // Lines that don't parse are skipped
+        fs.write(&journal_path(database), b"garbage\n1 3 1\n")
+            .unwrap();
+        assert_eq!(read_journal(&fs, database).unwrap().len(), 1);
+    }
+}
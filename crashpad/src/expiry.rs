@@ -0,0 +1,115 @@
+//! Expiry of pending reports that are too old to be worth uploading.
+
+use std::time::{Duration, SystemTime};
+
+use crate::clock::Clock;
+use crate::{CrashReport, CrashReportDatabase, Result};
+
+/// When [`CrashReportDatabase::expire_stale_reports`] gives up on pending
+/// reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpiryPolicy {
+    max_age: Duration,
+    delete: bool,
+}
+
+impl ExpiryPolicy {
+    /// Expire pending reports created more than `max_age` ago
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            delete: false,
+        }
+    }
+
+    /// Delete expired reports instead of keeping them as completed
+    pub fn delete_expired(mut self, delete: bool) -> Self {
+        self.delete = delete;
+        self
+    }
+}
+
+/// Reports of `reports` created more than the policy's maximum age before
+/// `now`. Reports whose upload was requested explicitly never expire.
+fn stale(reports: Vec<CrashReport>, policy: &ExpiryPolicy, now: SystemTime) -> Vec<CrashReport> {
+    let Some(since) = now.checked_sub(policy.max_age) else {
+        return Vec::new();
+    };
+    reports
+        .into_iter()
+        .filter(|report| report.creation_time < since && !report.upload_explicitly_requested)
+        .collect()
+}
+
+/// See [`CrashReportDatabase::expire_stale_reports`].
+pub(crate) fn expire(
+    database: &CrashReportDatabase,
+    policy: &ExpiryPolicy,
+    clock: &impl Clock,
+) -> Result<Vec<String>> {
+    let mut expired = Vec::new();
+    for report in stale(database.pending_reports()?, policy, clock.now()) {
+        // Left to the agent processing it
+        #[cfg(feature = "serde")]
+        if !matches!(database.claim_of(&report.uuid), Ok(None)) {
+            continue;
+        }
+        let result = if policy.delete {
+            database.delete_report(&report.uuid)
+        } else {
+            database.skip_upload(&report.uuid)
+        };
+        // Skip reports a running handler is uploading right now
+        if result.is_ok() {
+            expired.push(report.uuid);
+        }
+    }
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::UNIX_EPOCH;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn report(uuid: &str, days: u64) -> CrashReport {
+        CrashReport {
+            uuid: uuid.to_string(),
+            file_path: PathBuf::from(format!("{uuid}.dmp")),
+            id: None,
+            creation_time: UNIX_EPOCH + Duration::from_secs(days * DAY),
+            uploaded: false,
+            last_upload_attempt_time: None,
+            upload_attempts: 0,
+            upload_explicitly_requested: false,
+            total_size: 0,
+        }
+    }
+
+    #[test]
+    fn test_stale() {
+        let policy = ExpiryPolicy::new(Duration::from_secs(30 * DAY));
+        let now = UNIX_EPOCH + Duration::from_secs(100 * DAY);
+        let mut requested = report("requested", 10);
+        requested.upload_explicitly_requested = true;
+        let reports = vec![
+            report("old", 10),
+            report("recent", 90),
+            requested,
+            report("limit", 70),
+        ];
+
+        let uuids: Vec<String> = stale(reports, &policy, now)
+            .into_iter()
+            .map(|report| report.uuid)
+            .collect();
+        assert_eq!(uuids, vec!["old"]);
+
+        // Older than the epoch
+        let policy = ExpiryPolicy::new(Duration::from_secs(u64::MAX));
+        assert!(stale(vec![report("old", 0)], &policy, now).is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+//! Converting minidumps to JSON.
+//!
+//! Pipelines that don't parse minidumps, like log indexers or a data
+//! warehouse, can ingest reports as JSON documents instead:
+//!
+//! ```json
+//! {
+//!   "exception": { "thread_id": 7, "code": 11, "flags": 0, "address": "0x555500001a2b" },
+//!   "threads": [
+//!     { "id": 7, "name": "main", "stack_start": "0x7ff00000", "stack_size": 4096 }
+//!   ],
+//!   "modules": [
+//!     {
+//!       "name": "/usr/bin/app",
+//!       "base_address": "0x555500000000",
+//!       "size": 65536,
+//!       "debug_file": "app",
+//!       "debug_id": "030201000504070608090A0B0C0D0E0F0"
+//!     }
+//!   ],
+//!   "fingerprint": "0x0000000b@app+0x1a2b",
+//!   "annotations": { "product": "MyApp", "version": "1.0.0" }
+//! }
+//! ```
+//!
+//! Addresses are hex strings, as 64-bit addresses don't fit the numbers of
+//! many JSON parsers. `exception` and `fingerprint` are `null` for dumps
+//! taken without a crash. [`report_to_json`] adds a `report` object with
+//! the report's database metadata. `cargo xtask dump2json <minidump>` prints
+//! the document of a dump file.
+//!
+//! ```rust,no_run
+//! use crashpad_rs::{export, CrashReportDatabase};
+//!
+//! let database = CrashReportDatabase::open("./crashpad_db")?;
+//! for report in database.completed_reports()? {
+//!     println!("{}", export::report_to_json(&report)?);
+//! }
+//! # Ok::<(), crashpad_rs::CrashpadError>(())
+//! ```
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::minidump::Minidump;
+use crate::{timestamp, CrashReport, Result};
+
+/// `address` as a hex string
+fn hex(address: u64) -> String {
+    format!("{address:#x}")
+}
+
+/// The JSON document of `dump`, with `annotations` as its annotations
+pub fn to_json(dump: &Minidump, annotations: &HashMap<String, String>) -> Result<Value> {
+    let exception = dump.exception()?.map(|exception| {
+        json!({
+            "thread_id": exception.thread_id,
+            "code": exception.code,
+            "flags": exception.flags,
+            "address": hex(exception.address),
+        })
+    });
+    let threads: Vec<Value> = dump
+        .threads()?
+        .into_iter()
+        .map(|thread| {
+            json!({
+                "id": thread.id,
+                "name": thread.name,
+                "stack_start": hex(thread.stack_start),
+                "stack_size": thread.stack_size,
+            })
+        })
+        .collect();
+    let modules: Vec<Value> = dump
+        .modules()?
+        .into_iter()
+        .map(|module| {
+            json!({
+                "name": module.name,
+                "base_address": hex(module.base_address),
+                "size": module.size,
+                "debug_file": module.debug_file,
+                "debug_id": module.debug_id,
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "exception": exception,
+        "threads": threads,
+        "modules": modules,
+        "fingerprint": dump.fingerprint()?,
+        "annotations": annotations,
+    }))
+}
+
+/// The JSON document of the minidump at `path`, with the annotations of the
+/// dump as [`CrashReport::annotations`] reports them
+pub fn minidump_to_json<P: AsRef<Path>>(path: P) -> Result<Value> {
+    let dump = Minidump::open(path)?;
+    let annotations = crate::database::report_annotations(&dump)?;
+    to_json(&dump, &annotations)
+}
+
+/// The JSON document of `report`, with its annotations and a `report`
+/// object holding its UUID, creation time, upload state and server ID
+pub fn report_to_json(report: &CrashReport) -> Result<Value> {
+    let mut document = to_json(&report.minidump()?, &report.annotations()?)?;
+    document["report"] = json!({
+        "uuid": report.uuid,
+        "creation_time": timestamp::rfc3339(report.creation_time),
+        "uploaded": report.uploaded,
+        "server_id": report.id,
+        "upload_attempts": report.upload_attempts,
+    });
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minidump::tests::{amd64_crash_dump, dump_with_annotations, STACK_START};
+
+    #[test]
+    fn test_to_json() {
+        let module = ("/usr/bin/app", 0x5555_0000_0000, 0x10000);
+        let dump =
+            Minidump::from_bytes(amd64_crash_dump(0xb, 0x5555_0000_1a2b, module, &[])).unwrap();
+        let annotations = HashMap::from([("product".to_string(), "MyApp".to_string())]);
+        let document = to_json(&dump, &annotations).unwrap();
+
+        assert_eq!(document["exception"]["code"], 11);
+        assert_eq!(document["exception"]["address"], "0x555500001a2b");
+        assert_eq!(document["threads"][0]["id"], 7);
+        assert_eq!(document["threads"][0]["stack_start"], hex(STACK_START));
+        assert_eq!(document["threads"][0]["name"], "main");
+        assert!(document["threads"][1]["name"].is_null());
+        assert_eq!(document["modules"][0]["name"], "/usr/bin/app");
+        assert_eq!(document["modules"][0]["base_address"], "0x555500000000");
+        assert_eq!(document["fingerprint"], "0x0000000b@app+0x1a2b");
+        assert_eq!(document["annotations"]["product"], "MyApp");
+
+        // No exception without a crash
+        let dump =
+            Minidump::from_bytes(dump_with_annotations(&[("product", "MyApp")], &[])).unwrap();
+        let document = to_json(&dump, &HashMap::new()).unwrap();
+        assert!(document["exception"].is_null());
+        assert!(document["fingerprint"].is_null());
+        assert_eq!(document["modules"], json!([]));
+    }
+}
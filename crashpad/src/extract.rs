@@ -0,0 +1,270 @@
+//! Hardened extraction of embedded or downloaded handler binaries.
+//!
+//! Writing an executable to a shared temp directory and running it is a
+//! classic TOCTOU target on multi-user systems. [`extract_handler`] guards
+//! against that by:
+//!
+//! - creating a fresh directory with a randomized name (exclusive creation,
+//!   never reusing an existing one)
+//! - restricting the directory and the handler to the owner (`0700` on Unix)
+//! - creating the handler file exclusively (`O_EXCL`) inside that directory
+//! - syncing it to disk and reading it back to verify the contents
+//!
+//! Not available on iOS/tvOS/watchOS, which use an in-process handler.
+
+use std::collections::hash_map::RandomState;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{CrashpadError, Result};
+
+/// Number of attempts to find an unused random directory name.
+const MAX_ATTEMPTS: u32 = 16;
+
+/// A handler written by [`extract_handler`].
+///
+/// The private directory holding the handler is removed when this value is
+/// dropped, unless [`persist`](Self::persist) is called. The handler must stay
+/// on disk while Crashpad may need to (re)start it.
+#[derive(Debug)]
+pub struct ExtractedHandler {
+    dir: PathBuf,
+    path: PathBuf,
+    persist: bool,
+}
+
+impl ExtractedHandler {
+    /// Path of the extracted handler executable.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keep the handler on disk and return its path.
+    pub fn persist(mut self) -> PathBuf {
+        self.persist = true;
+        self.path.clone()
+    }
+}
+
+impl Drop for ExtractedHandler {
+    fn drop(&mut self) {
+        if !self.persist {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+/// Write a handler binary to a new private directory and verify it.
+///
+/// `base_dir` is the parent of the private directory; it defaults to
+/// [`std::env::temp_dir`]. The handler gets the platform's handler file name.
+///
+/// # Platform Behavior
+/// - **Unix**: Directory and handler are created with mode `0700`, and the
+///   base directory must not be writable by others unless it is sticky (like `/tmp`)
+/// - **Windows**: Relies on the per-user ACLs of the base directory
+///
+/// # Example
+/// ```no_run
+/// # use crashpad_rs::{extract_handler, CrashpadConfig};
+/// # let handler_bytes: &[u8] = &[];
+/// let handler = extract_handler(handler_bytes, None)?;
+/// let config = CrashpadConfig::builder()
+///     .handler_path(handler.path())
+///     .build();
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+pub fn extract_handler(contents: &[u8], base_dir: Option<&Path>) -> Result<ExtractedHandler> {
+    let base_dir = base_dir.map_or_else(std::env::temp_dir, Path::to_path_buf);
+    check_base_dir(&base_dir)?;
+
+    let dir = create_private_dir(&base_dir)?;
+    let extracted = ExtractedHandler {
+        path: dir.join(handler_file_name()),
+        dir,
+        persist: false,
+    };
+
+    write_exclusive(&extracted.path, contents)?;
+    verify_contents(&extracted.path, contents)?;
+
+    Ok(extracted)
+}
+
+fn handler_file_name() -> &'static str {
+    if cfg!(target_os = "android") {
+        "libcrashpad_handler.so"
+    } else if cfg!(windows) {
+        "crashpad_handler.exe"
+    } else {
+        "crashpad_handler"
+    }
+}
+
+/// Reject base directories where others could replace our directory.
+#[cfg(unix)]
+fn check_base_dir(base_dir: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(base_dir)?.permissions().mode();
+    let others_writable = mode & 0o022 != 0;
+    let sticky = mode & 0o1000 != 0;
+    if others_writable && !sticky {
+        return Err(CrashpadError::InvalidConfiguration(format!(
+            "Extraction directory {} is writable by other users",
+            base_dir.display()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_base_dir(_base_dir: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Create a new directory with a random name that didn't exist before.
+fn create_private_dir(base_dir: &Path) -> Result<PathBuf> {
+    for _ in 0..MAX_ATTEMPTS {
+        let dir = base_dir.join(format!("crashpad-handler-{:016x}", random_u64()));
+        match create_dir_owner_only(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(CrashpadError::IoError(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "Failed to create a unique extraction directory",
+    )))
+}
+
+#[cfg(unix)]
+fn create_dir_owner_only(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new().mode(0o700).create(dir)
+}
+
+#[cfg(not(unix))]
+fn create_dir_owner_only(dir: &Path) -> io::Result<()> {
+    fs::create_dir(dir)
+}
+
+/// Create the handler with `O_EXCL` and owner-only permissions.
+fn write_exclusive(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o700);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Read the handler back and make sure it is exactly what we wrote.
+fn verify_contents(path: &Path, expected: &[u8]) -> Result<()> {
+    let mut file = File::open(path)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let metadata = file.metadata()?;
+        let dir_metadata = fs::metadata(path.parent().unwrap_or(path))?;
+        if metadata.uid() != dir_metadata.uid() || metadata.permissions().mode() & 0o077 != 0 {
+            return Err(integrity_error(path, "unexpected owner or permissions"));
+        }
+    }
+
+    let mut actual = Vec::with_capacity(expected.len());
+    file.read_to_end(&mut actual)?;
+    if actual != expected {
+        return Err(integrity_error(path, "contents changed after write"));
+    }
+    Ok(())
+}
+
+fn integrity_error(path: &Path, reason: &str) -> CrashpadError {
+    CrashpadError::HandlerIntegrityError {
+        path: path.to_path_buf(),
+        reason: reason.to_string(),
+    }
+}
+
+/// Unpredictable value from the std hasher's random keys.
+fn random_u64() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    if let Ok(elapsed) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(elapsed.as_nanos());
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_extract_handler() {
+        let temp_dir = TempDir::new().unwrap();
+        let handler = extract_handler(b"handler binary", Some(temp_dir.path())).unwrap();
+
+        assert_eq!(fs::read(handler.path()).unwrap(), b"handler binary");
+        assert_eq!(
+            handler.path().file_name().unwrap().to_str().unwrap(),
+            handler_file_name()
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(handler.path()), 0o700);
+            assert_eq!(mode(handler.path().parent().unwrap()), 0o700);
+        }
+    }
+
+    #[test]
+    fn test_extract_handler_uses_fresh_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = extract_handler(b"a", Some(temp_dir.path())).unwrap();
+        let second = extract_handler(b"b", Some(temp_dir.path())).unwrap();
+
+        assert_ne!(first.path(), second.path());
+    }
+
+    #[test]
+    fn test_extracted_handler_cleanup() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let handler = extract_handler(b"handler", Some(temp_dir.path())).unwrap();
+        let dir = handler.path().parent().unwrap().to_path_buf();
+        drop(handler);
+        assert!(!dir.exists());
+
+        let handler = extract_handler(b"handler", Some(temp_dir.path())).unwrap();
+        let path = handler.persist();
+        assert!(path.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_extract_handler_rejects_shared_base_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::set_permissions(temp_dir.path(), fs::Permissions::from_mode(0o777)).unwrap();
+
+        assert!(extract_handler(b"handler", Some(temp_dir.path())).is_err());
+    }
+}
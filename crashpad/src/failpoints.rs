@@ -0,0 +1,42 @@
+//! Fault injection for tests.
+//!
+//! With the `failpoints` feature, the points below can be configured with the
+//! [`fail`](https://docs.rs/fail) crate to make the corresponding operation
+//! fail, so error handling can be tested without a broken handler or a
+//! read-only disk. Without the feature they compile to nothing.
+//!
+//! ```ignore
+//! let scenario = fail::FailScenario::setup();
+//! fail::cfg(crashpad_rs::failpoints::HANDLER_START, "return").unwrap();
+//! assert!(client.start_with_config(&config, &annotations).is_err());
+//! scenario.teardown();
+//! ```
+
+// Only referenced by the fail points themselves
+#![cfg_attr(not(feature = "failpoints"), allow(dead_code))]
+
+/// Spawning the handler fails with [`HandlerSpawnFailed`](crate::CrashpadError::HandlerSpawnFailed).
+pub const HANDLER_START: &str = "crashpad::handler_start";
+
+/// Creating the database and metrics directories fails with an
+/// [`IoError`](crate::CrashpadError::IoError). The `return(msg)` argument
+/// becomes the error message.
+pub const DATABASE_CREATE: &str = "crashpad::database_create";
+
+/// Uploading a report from Rust, with `upload::send_report` or each attempt
+/// of `upload::send_pending`, fails with an
+/// [`UploadFailed`](crate::CrashpadError::UploadFailed) error without a
+/// status, so the retry queue backs off. The `return(msg)` argument becomes
+/// the error message.
+pub const UPLOAD: &str = "crashpad::upload";
+
+/// Return early from the enclosing function with `$e` when the fail point is
+/// active. Expands to nothing without the `failpoints` feature.
+macro_rules! fail_point {
+    ($name:expr, $e:expr) => {
+        #[cfg(feature = "failpoints")]
+        fail::fail_point!($name, $e);
+    };
+}
+
+pub(crate) use fail_point;
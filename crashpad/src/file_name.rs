@@ -0,0 +1,110 @@
+//! File names of reports from templates, for harvesters organizing dumps.
+//!
+//! Crashpad finds reports in its database by `<uuid>.dmp`, so the files in
+//! the database keep their names; templates name the copies made for
+//! harvesters, see [`CrashReport::file_name`] and
+//! [`ArtifactExport::file_name_template`](crate::ArtifactExport::file_name_template).
+
+use std::collections::HashMap;
+
+use crate::{minidump, timestamp, CrashReport};
+
+/// File name for `report` from `template`, see [`CrashReport::file_name`].
+pub(crate) fn render(
+    template: &str,
+    report: &CrashReport,
+    annotations: &HashMap<String, String>,
+) -> String {
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        name.push_str(&sanitize(&rest[..start]));
+        let value = match &rest[start + 1..end] {
+            "uuid" => Some(report.uuid.clone()),
+            "timestamp" => Some(timestamp::compact(report.creation_time)),
+            // Only read when used: it needs the minidump
+            "fingerprint" => minidump::read_fingerprint(&report.file_path).ok().flatten(),
+            key => annotations.get(key).cloned(),
+        };
+        name.push_str(&sanitize(value.as_deref().unwrap_or("unknown")));
+        rest = &rest[end + 1..];
+    }
+    name.push_str(&sanitize(rest));
+
+    if name.is_empty() {
+        report.uuid.clone()
+    } else {
+        name
+    }
+}
+
+/// Replace characters that aren't safe in file names on every platform.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn report(file_path: PathBuf) -> CrashReport {
+        CrashReport {
+            uuid: "00112233-4455-6677-8899-aabbccddeeff".to_string(),
+            file_path,
+            id: None,
+            creation_time: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            uploaded: false,
+            last_upload_attempt_time: None,
+            upload_attempts: 0,
+            upload_explicitly_requested: false,
+            total_size: 4,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let report = report(PathBuf::from("report.dmp"));
+        let annotations = HashMap::from([("version".to_string(), "1.2.3 beta/4".to_string())]);
+
+        assert_eq!(
+            render("{timestamp}-{uuid}", &report, &annotations),
+            "20231114T221320Z-00112233-4455-6677-8899-aabbccddeeff"
+        );
+        assert_eq!(
+            render("{version}_{channel}", &report, &annotations),
+            "1.2.3_beta_4_unknown"
+        );
+        assert_eq!(render("../{", &report, &annotations), "..__");
+        assert_eq!(render("", &report, &annotations), report.uuid);
+    }
+
+    #[test]
+    fn test_render_fingerprint() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dump = temp_dir.path().join("report.dmp");
+        // A dump without an exception has no fingerprint
+        std::fs::write(&dump, minidump::annotations_dump(&[("prod", "app")])).unwrap();
+        let annotations = HashMap::new();
+
+        assert_eq!(
+            render("{fingerprint}-{uuid}", &report(dump), &annotations),
+            "unknown-00112233-4455-6677-8899-aabbccddeeff"
+        );
+        let missing = report(temp_dir.path().join("missing.dmp"));
+        assert_eq!(render("{fingerprint}", &missing, &annotations), "unknown");
+    }
+}
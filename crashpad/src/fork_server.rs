@@ -0,0 +1,493 @@
+//! Spawning the handler from a small process forked early (Linux).
+//!
+//! Forking copies the page tables of the forking process, which takes a
+//! while and a lot of memory in a process with a multi-GB heap. Started at
+//! the beginning of `main`, the fork server is a copy of the process while it
+//! is still small; once it runs, `start_with_config` asks it to spawn the
+//! handler instead of forking the application.
+//!
+//! The application and the server talk over a `SOCK_SEQPACKET` socket pair.
+//! A request carries the handler's command line and spawn settings, and the
+//! handler's end of the client socket as `SCM_RIGHTS`; the reply is the
+//! handler's process ID or an `errno`.
+
+use std::ffi::{CString, OsStr, OsString};
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::raw::c_int;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::{Mutex, OnceLock};
+
+use crate::backend::{handler_socket_pair, StartRequest};
+use crate::{CrashpadError, Result};
+
+/// The application's end of the socket to the server.
+static SERVER: OnceLock<Mutex<OwnedFd>> = OnceLock::new();
+
+/// Largest request, in bytes
+const MAX_REQUEST_SIZE: usize = 256 * 1024;
+
+const TAG_ARGUMENT: u8 = b'a';
+const TAG_LOG: u8 = b'l';
+const TAG_WORKING_DIRECTORY: u8 = b'w';
+const TAG_UMASK: u8 = b'u';
+
+/// Fork the fork server that later handlers are spawned from.
+///
+/// Call this first thing in `main`, before the process starts threads or
+/// grows its heap: the server is a copy of the process at this point, and
+/// only a process without other threads can be forked safely. Handlers
+/// started by [`start_with_config`](crate::CrashpadClient::start_with_config)
+/// afterwards are spawned by the server; Crashpad doesn't restart a handler
+/// spawned this way. The server exits when this process does.
+///
+/// Calling it again does nothing.
+///
+/// # Errors
+///
+/// [`CrashpadError::InvalidConfiguration`] if the process already runs more
+/// than one thread, [`CrashpadError::IoError`] if the socket or the fork
+/// fails.
+///
+/// # Example
+/// ```rust,no_run
+/// crashpad_rs::start_fork_server().expect("fork server");
+///
+/// // ... allocate the large heap, then start Crashpad as usual
+/// ```
+pub fn start_fork_server() -> Result<()> {
+    if SERVER.get().is_some() {
+        return Ok(());
+    }
+    if thread_count() > 1 {
+        return Err(CrashpadError::InvalidConfiguration(
+            "Start the fork server before starting threads".to_string(),
+        ));
+    }
+
+    let mut fds = [-1; 2];
+    if unsafe {
+        libc::socketpair(
+            libc::AF_UNIX,
+            libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC,
+            0,
+            fds.as_mut_ptr(),
+        )
+    } != 0
+    {
+        return Err(io::Error::last_os_error().into());
+    }
+    // SAFETY: Both descriptors were just created and are owned by no one
+    let (app, server) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error().into()),
+        0 => {
+            drop(app);
+            serve(server)
+        }
+        _ => {
+            drop(server);
+            let _ = SERVER.set(Mutex::new(app));
+            Ok(())
+        }
+    }
+}
+
+/// Spawn the handler of `request` through the fork server, if one runs.
+///
+/// Returns the client's end of the handler socket and the handler's
+/// process ID.
+pub(crate) fn spawn_handler(request: &StartRequest<'_>) -> Result<Option<(OwnedFd, u32)>> {
+    let Some(server) = SERVER.get() else {
+        return Ok(None);
+    };
+    if !request.handler_path.exists() {
+        return Err(CrashpadError::HandlerNotFound {
+            path: request.handler_path.to_path_buf(),
+            trace: Vec::new(),
+        });
+    }
+
+    let (handler_socket, client_socket) = handler_socket_pair()?;
+    let message = SpawnRequest::from_start(request).encode();
+    let spawn_failed = |os_error: Option<i32>| CrashpadError::HandlerSpawnFailed {
+        path: request.handler_path.to_path_buf(),
+        os_error,
+        trace: Vec::new(),
+    };
+
+    let server = server.lock().unwrap_or_else(|e| e.into_inner());
+    send_with_fd(server.as_raw_fd(), &message, handler_socket.as_raw_fd())
+        .map_err(|e| spawn_failed(e.raw_os_error()))?;
+    let mut reply = [0u8; 8];
+    let len = unsafe {
+        libc::recv(
+            server.as_raw_fd(),
+            reply.as_mut_ptr().cast(),
+            reply.len(),
+            0,
+        )
+    };
+    if len != reply.len() as isize {
+        // The server is gone
+        return Err(spawn_failed(io::Error::last_os_error().raw_os_error()));
+    }
+
+    let pid = i32::from_le_bytes(reply[..4].try_into().unwrap());
+    let errno = i32::from_le_bytes(reply[4..].try_into().unwrap());
+    if pid <= 0 {
+        return Err(spawn_failed((errno != 0).then_some(errno)));
+    }
+    Ok(Some((client_socket, pid as u32)))
+}
+
+/// Threads of this process, from `/proc/self/task`.
+fn thread_count() -> usize {
+    std::fs::read_dir("/proc/self/task").map_or(1, |tasks| tasks.count())
+}
+
+/// A handler to spawn, as sent to the server.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SpawnRequest {
+    /// Command line, starting with the handler path
+    arguments: Vec<OsString>,
+    log: Option<PathBuf>,
+    working_directory: Option<PathBuf>,
+    umask: Option<u32>,
+}
+
+impl SpawnRequest {
+    fn from_start(request: &StartRequest<'_>) -> Self {
+        let mut arguments = vec![request.handler_path.as_os_str().to_os_string()];
+        let mut argument = |name: &str, value: &OsStr| {
+            let mut argument = OsString::from(format!("--{name}="));
+            argument.push(value);
+            arguments.push(argument);
+        };
+        argument("database", request.database_path.as_os_str());
+        if !request.metrics_path.as_os_str().is_empty() {
+            argument("metrics-dir", request.metrics_path.as_os_str());
+        }
+        if let Some(url) = request.url {
+            argument("url", OsStr::new(url));
+        }
+        for (key, value) in request.annotations {
+            argument("annotation", OsStr::new(&format!("{key}={value}")));
+        }
+        arguments.extend(request.handler_arguments.iter().map(OsString::from));
+
+        Self {
+            arguments,
+            log: request.handler_log.map(Path::to_path_buf),
+            working_directory: request.handler_working_directory.map(Path::to_path_buf),
+            umask: request.handler_umask,
+        }
+    }
+
+    /// Records of a tag byte, a little-endian `u32` length and the value
+    fn encode(&self) -> Vec<u8> {
+        let mut message = Vec::new();
+        let mut record = |tag: u8, value: &[u8]| {
+            message.push(tag);
+            message.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            message.extend_from_slice(value);
+        };
+        for argument in &self.arguments {
+            record(TAG_ARGUMENT, argument.as_bytes());
+        }
+        if let Some(log) = &self.log {
+            record(TAG_LOG, log.as_os_str().as_bytes());
+        }
+        if let Some(dir) = &self.working_directory {
+            record(TAG_WORKING_DIRECTORY, dir.as_os_str().as_bytes());
+        }
+        if let Some(umask) = self.umask {
+            record(TAG_UMASK, &umask.to_le_bytes());
+        }
+        message
+    }
+
+    fn decode(mut message: &[u8]) -> Option<Self> {
+        let mut request = Self::default();
+        while let [tag, rest @ ..] = message {
+            let len = u32::from_le_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+            let value = rest.get(4..4 + len)?;
+            message = &rest[4 + len..];
+            let os_string = || OsString::from_vec(value.to_vec());
+            match *tag {
+                TAG_ARGUMENT => request.arguments.push(os_string()),
+                TAG_LOG => request.log = Some(os_string().into()),
+                TAG_WORKING_DIRECTORY => request.working_directory = Some(os_string().into()),
+                TAG_UMASK => request.umask = Some(u32::from_le_bytes(value.try_into().ok()?)),
+                _ => return None,
+            }
+        }
+        (!request.arguments.is_empty()).then_some(request)
+    }
+}
+
+/// Run the server on `socket` until the application exits.
+fn serve(socket: OwnedFd) -> ! {
+    unsafe {
+        // Exit with the application, and don't leave exited handlers behind
+        libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+        libc::signal(libc::SIGCHLD, libc::SIG_IGN);
+    }
+
+    let mut buffer = vec![0u8; MAX_REQUEST_SIZE];
+    loop {
+        let (len, client) = match recv_with_fd(socket.as_raw_fd(), &mut buffer) {
+            Ok((0, _)) => unsafe { libc::_exit(0) },
+            Ok(received) => received,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => unsafe { libc::_exit(1) },
+        };
+
+        let result = match (SpawnRequest::decode(&buffer[..len]), client) {
+            (Some(request), Some(client)) => spawn(&request, &client),
+            _ => Err(libc::EINVAL),
+        };
+        let (pid, errno) = match result {
+            Ok(pid) => (pid, 0),
+            Err(errno) => (-1, errno),
+        };
+        let mut reply = [0u8; 8];
+        reply[..4].copy_from_slice(&pid.to_le_bytes());
+        reply[4..].copy_from_slice(&errno.to_le_bytes());
+        unsafe {
+            libc::send(
+                socket.as_raw_fd(),
+                reply.as_ptr().cast(),
+                reply.len(),
+                libc::MSG_NOSIGNAL,
+            )
+        };
+    }
+}
+
+/// Fork and exec the handler of `request`, serving `client`. Returns its
+/// process ID, or the `errno` of the failed step.
+fn spawn(request: &SpawnRequest, client: &OwnedFd) -> std::result::Result<i32, i32> {
+    let to_cstring = |value: &OsStr| CString::new(value.as_bytes()).map_err(|_| libc::EINVAL);
+    let mut arguments = request
+        .arguments
+        .iter()
+        .map(|argument| to_cstring(argument))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    // Like Crashpad's StartHandler: the socket is shared with child processes
+    arguments.push(CString::new(format!("--initial-client-fd={}", client.as_raw_fd())).unwrap());
+    arguments.push(CString::new("--shared-client-connection").unwrap());
+    let mut argv: Vec<*const libc::c_char> = arguments.iter().map(|a| a.as_ptr()).collect();
+    argv.push(ptr::null());
+    let log = request
+        .log
+        .as_deref()
+        .map(|p| to_cstring(p.as_os_str()))
+        .transpose()?;
+    let working_directory = request
+        .working_directory
+        .as_deref()
+        .map(|p| to_cstring(p.as_os_str()))
+        .transpose()?;
+
+    // Reports the errno of a failed step before exec; closed by exec
+    let mut status = [-1; 2];
+    if unsafe { libc::pipe2(status.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(errno());
+    }
+
+    let pid = unsafe { libc::fork() };
+    if pid == 0 {
+        unsafe {
+            libc::close(status[0]);
+            let fail = |status: c_int| -> ! {
+                let errno = errno().to_le_bytes();
+                libc::write(status, errno.as_ptr().cast(), errno.len());
+                libc::_exit(127)
+            };
+            libc::signal(libc::SIGCHLD, libc::SIG_DFL);
+            if let Some(mask) = request.umask {
+                libc::umask(mask as libc::mode_t);
+            }
+            if let Some(dir) = &working_directory {
+                if libc::chdir(dir.as_ptr()) != 0 {
+                    fail(status[1]);
+                }
+            }
+            if let Some(log) = &log {
+                let fd = libc::open(
+                    log.as_ptr(),
+                    libc::O_WRONLY | libc::O_APPEND | libc::O_CREAT | libc::O_CLOEXEC,
+                    0o644,
+                );
+                if fd < 0 || libc::dup2(fd, libc::STDERR_FILENO) < 0 {
+                    fail(status[1]);
+                }
+            }
+            if libc::fcntl(client.as_raw_fd(), libc::F_SETFD, 0) != 0 {
+                fail(status[1]);
+            }
+            libc::execv(argv[0], argv.as_ptr());
+            fail(status[1]);
+        }
+    }
+
+    unsafe { libc::close(status[1]) };
+    let status = unsafe { OwnedFd::from_raw_fd(status[0]) };
+    if pid < 0 {
+        return Err(errno());
+    }
+    let mut child_errno = [0u8; 4];
+    let len = loop {
+        let len = unsafe {
+            libc::read(
+                status.as_raw_fd(),
+                child_errno.as_mut_ptr().cast(),
+                child_errno.len(),
+            )
+        };
+        if len >= 0 || errno() != libc::EINTR {
+            break len;
+        }
+    };
+    if len == child_errno.len() as isize {
+        return Err(i32::from_le_bytes(child_errno));
+    }
+    Ok(pid)
+}
+
+fn errno() -> i32 {
+    io::Error::last_os_error().raw_os_error().unwrap_or(0)
+}
+
+/// Send `data` with a duplicate of `fd` attached.
+fn send_with_fd(socket: RawFd, data: &[u8], fd: RawFd) -> io::Result<()> {
+    let mut iov = libc::iovec {
+        iov_base: data.as_ptr() as *mut _,
+        iov_len: data.len(),
+    };
+    let space = unsafe { libc::CMSG_SPACE(mem::size_of::<c_int>() as u32) } as usize;
+    let mut control = vec![0u8; space];
+    let mut message: libc::msghdr = unsafe { mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr().cast();
+    message.msg_controllen = space as _;
+    unsafe {
+        let header = libc::CMSG_FIRSTHDR(&message);
+        (*header).cmsg_level = libc::SOL_SOCKET;
+        (*header).cmsg_type = libc::SCM_RIGHTS;
+        (*header).cmsg_len = libc::CMSG_LEN(mem::size_of::<c_int>() as u32) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(header).cast::<c_int>(), fd);
+    }
+
+    if unsafe { libc::sendmsg(socket, &message, libc::MSG_NOSIGNAL) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Receive a message into `buffer`, with the descriptor attached to it.
+/// A length of 0 means the other end was closed.
+fn recv_with_fd(socket: RawFd, buffer: &mut [u8]) -> io::Result<(usize, Option<OwnedFd>)> {
+    let mut iov = libc::iovec {
+        iov_base: buffer.as_mut_ptr().cast(),
+        iov_len: buffer.len(),
+    };
+    let space = unsafe { libc::CMSG_SPACE(mem::size_of::<c_int>() as u32) } as usize;
+    let mut control = vec![0u8; space];
+    let mut message: libc::msghdr = unsafe { mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr().cast();
+    message.msg_controllen = space as _;
+
+    let len = unsafe { libc::recvmsg(socket, &mut message, libc::MSG_CMSG_CLOEXEC) };
+    if len < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut fd = None;
+    unsafe {
+        let header = libc::CMSG_FIRSTHDR(&message);
+        if !header.is_null()
+            && (*header).cmsg_level == libc::SOL_SOCKET
+            && (*header).cmsg_type == libc::SCM_RIGHTS
+        {
+            let raw = ptr::read_unaligned(libc::CMSG_DATA(header).cast::<c_int>());
+            fd = Some(OwnedFd::from_raw_fd(raw));
+        }
+    }
+    Ok((len as usize, fd))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_request_round_trip() {
+        let annotations = HashMap::from([("product".to_string(), "MyApp".to_string())]);
+        let arguments = ["--no-rate-limit".to_string()];
+        let request = StartRequest {
+            handler_path: Path::new("/opt/app/crashpad_handler"),
+            database_path: Path::new("/var/db"),
+            metrics_path: Path::new(""),
+            url: Some("https://crashes.example.com"),
+            annotations: &annotations,
+            handler_arguments: &arguments,
+            handler_log: Some(Path::new("/var/db/handler.log")),
+            handler_working_directory: None,
+            handler_umask: Some(0o077),
+            platform_options: &Default::default(),
+        };
+
+        let spawn = SpawnRequest::from_start(&request);
+        assert_eq!(
+            spawn.arguments,
+            [
+                "/opt/app/crashpad_handler",
+                "--database=/var/db",
+                "--url=https://crashes.example.com",
+                "--annotation=product=MyApp",
+                "--no-rate-limit",
+            ]
+            .map(OsString::from)
+        );
+        assert_eq!(SpawnRequest::decode(&spawn.encode()), Some(spawn));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert_eq!(SpawnRequest::decode(&[]), None);
+        assert_eq!(
+            SpawnRequest::decode(&[TAG_ARGUMENT, 9, 0, 0, 0, b'x']),
+            None
+        );
+        assert_eq!(SpawnRequest::decode(&[b'?', 0, 0, 0, 0]), None);
+    }
+
+    #[test]
+    fn test_fd_passing() {
+        let mut fds = [-1; 2];
+        assert_eq!(
+            unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_SEQPACKET, 0, fds.as_mut_ptr()) },
+            0
+        );
+        let (a, b) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+        let (passed, _other) = handler_socket_pair().unwrap();
+        send_with_fd(a.as_raw_fd(), b"spawn", passed.as_raw_fd()).unwrap();
+        let mut buffer = [0u8; 16];
+        let (len, fd) = recv_with_fd(b.as_raw_fd(), &mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"spawn");
+        assert!(fd.is_some());
+
+        drop(a);
+        assert_eq!(recv_with_fd(b.as_raw_fd(), &mut buffer).unwrap().0, 0);
+    }
+}
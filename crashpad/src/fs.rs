@@ -0,0 +1,303 @@
+//! Filesystem access of the database and bundle code.
+//!
+//! Reading and writing sidecars, attachments and bundles goes through an
+//! [`Fs`] implementation: [`StdFs`] in the public API, which calls
+//! `std::fs`, and [`memory::MemoryFs`] in tests, which keeps files in memory
+//! so they don't need temporary directories. Storage that isn't a plain
+//! path, like content URIs on Android, can be added as another
+//! implementation.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The filesystem operations used by the database and bundle code.
+pub(crate) trait Fs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Link `to` to the file `from`, failing with
+    /// [`io::ErrorKind::AlreadyExists`] if `to` exists: a create that never
+    /// replaces a file or shows a partial one
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Files directly in the directory `path`, sorted by name
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    fn files(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Open `path` for reading
+    #[cfg_attr(not(feature = "bundle"), allow(dead_code))]
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+
+    /// Create or truncate `path` for writing. The data is durable once the
+    /// writer was flushed.
+    #[cfg_attr(not(feature = "bundle"), allow(dead_code))]
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + '_>>;
+}
+
+/// The real filesystem.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct StdFs;
+
+impl Fs for StdFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        std::fs::write(path, data)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        std::fs::rename(from, to)
+    }
+
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        std::fs::hard_link(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn files(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + '_>> {
+        Ok(Box::new(SyncOnFlush(File::create(path)?)))
+    }
+}
+
+/// A file that is synced to disk when flushed.
+struct SyncOnFlush(File);
+
+impl Write for SyncOnFlush {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()?;
+        self.0.sync_all()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod memory {
+    use std::cell::RefCell;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::io::{self, Read, Write};
+    use std::path::{Path, PathBuf};
+
+    use super::Fs;
+
+    /// Files and directories in memory.
+    ///
+    /// Like a real filesystem, files can only be written to directories
+    /// that exist; [`with_file`](Self::with_file) creates them for test
+    /// setup.
+    #[derive(Debug, Default)]
+    pub(crate) struct MemoryFs {
+        pub files: RefCell<BTreeMap<PathBuf, Vec<u8>>>,
+        pub dirs: RefCell<BTreeSet<PathBuf>>,
+    }
+
+    impl MemoryFs {
+        /// Add a file, and the directories leading to it
+        pub fn with_file(self, path: impl AsRef<Path>, data: impl AsRef<[u8]>) -> Self {
+            let path = path.as_ref();
+            if let Some(parent) = path.parent() {
+                self.create_dir_all(parent).unwrap();
+            }
+            self.write(path, data.as_ref()).unwrap();
+            self
+        }
+
+        fn check_parent(&self, path: &Path) -> io::Result<()> {
+            match path.parent() {
+                Some(parent)
+                    if !parent.as_os_str().is_empty() && !self.dirs.borrow().contains(parent) =>
+                {
+                    Err(not_found(parent))
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+
+    fn not_found(path: &Path) -> io::Error {
+        io::Error::new(io::ErrorKind::NotFound, path.display().to_string())
+    }
+
+    impl Fs for MemoryFs {
+        fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+            self.files
+                .borrow()
+                .get(path)
+                .cloned()
+                .ok_or_else(|| not_found(path))
+        }
+
+        fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+            self.check_parent(path)?;
+            self.files
+                .borrow_mut()
+                .insert(path.to_path_buf(), data.to_vec());
+            Ok(())
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.check_parent(to)?;
+            let data = self
+                .files
+                .borrow_mut()
+                .remove(from)
+                .ok_or_else(|| not_found(from))?;
+            self.files.borrow_mut().insert(to.to_path_buf(), data);
+            Ok(())
+        }
+
+        fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.check_parent(to)?;
+            if self.exists(to) {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    to.display().to_string(),
+                ));
+            }
+            // Copies, as no test writes to a linked file
+            let data = self.read(from)?;
+            self.files.borrow_mut().insert(to.to_path_buf(), data);
+            Ok(())
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            self.files
+                .borrow_mut()
+                .remove(path)
+                .map(|_| ())
+                .ok_or_else(|| not_found(path))
+        }
+
+        fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+            let mut dirs = self.dirs.borrow_mut();
+            for dir in path.ancestors() {
+                if !dir.as_os_str().is_empty() {
+                    dirs.insert(dir.to_path_buf());
+                }
+            }
+            Ok(())
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            self.files.borrow().contains_key(path) || self.dirs.borrow().contains(path)
+        }
+
+        fn files(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+            if !self.dirs.borrow().contains(path) {
+                return Err(not_found(path));
+            }
+            Ok(self
+                .files
+                .borrow()
+                .keys()
+                .filter(|file| file.parent() == Some(path))
+                .cloned()
+                .collect())
+        }
+
+        fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+            Ok(Box::new(io::Cursor::new(self.read(path)?)))
+        }
+
+        fn create(&self, path: &Path) -> io::Result<Box<dyn Write + '_>> {
+            self.write(path, &[])?;
+            Ok(Box::new(MemoryFile {
+                fs: self,
+                path: path.to_path_buf(),
+                data: Vec::new(),
+            }))
+        }
+    }
+
+    /// A file being written, stored when flushed or dropped.
+    struct MemoryFile<'a> {
+        fs: &'a MemoryFs,
+        path: PathBuf,
+        data: Vec<u8>,
+    }
+
+    impl Write for MemoryFile<'_> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.fs.write(&self.path, &self.data)
+        }
+    }
+
+    impl Drop for MemoryFile<'_> {
+        fn drop(&mut self) {
+            let _ = self.flush();
+        }
+    }
+
+    #[test]
+    fn test_memory_fs() {
+        let fs = MemoryFs::default().with_file("db/new/a.dmp", b"MDMP");
+        assert_eq!(fs.read(Path::new("db/new/a.dmp")).unwrap(), b"MDMP");
+        assert!(fs.exists(Path::new("db/new")));
+
+        // No directory to write to
+        assert!(fs.write(Path::new("db/pending/b.dmp"), b"").is_err());
+        assert!(fs.files(Path::new("db/pending")).is_err());
+
+        fs.create_dir_all(Path::new("db/pending")).unwrap();
+        fs.rename(Path::new("db/new/a.dmp"), Path::new("db/pending/a.dmp"))
+            .unwrap();
+        assert_eq!(
+            fs.files(Path::new("db/pending")).unwrap(),
+            [PathBuf::from("db/pending/a.dmp")]
+        );
+        assert!(fs.files(Path::new("db/new")).unwrap().is_empty());
+
+        let mut file = fs.create(Path::new("db/pending/b.dmp")).unwrap();
+        file.write_all(b"written").unwrap();
+        drop(file);
+        assert_eq!(fs.read(Path::new("db/pending/b.dmp")).unwrap(), b"written");
+    }
+}
@@ -0,0 +1,127 @@
+//! Process-wide client for applications that initialize Crashpad once.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::{CrashpadClient, CrashpadConfig, CrashpadError, Result};
+
+/// Client started by [`init`], until its guard is dropped
+static GLOBAL_CLIENT: Mutex<Option<Arc<CrashpadClient>>> = Mutex::new(None);
+
+/// Create a client, start the handler and install the client globally.
+///
+/// This is the single call most applications need at the top of `main()`.
+/// The client stays reachable through [`global_client`] until the returned
+/// guard is dropped.
+///
+/// Unlike [`init!`](crate::init), no handler bundling or annotations are
+/// added; the configuration is used as is.
+///
+/// # Errors
+///
+/// [`CrashpadError::AlreadyInitialized`] if the guard of a previous call is
+/// still alive, otherwise the errors of
+/// [`CrashpadClient::start_with_config`].
+///
+/// # Example
+/// ```no_run
+/// fn main() -> crashpad_rs::Result<()> {
+///     let _guard = crashpad_rs::init(
+///         crashpad_rs::CrashpadConfig::builder()
+///             .database_path("./crashes")
+///             .build(),
+///     )?;
+///
+///     // Your application code here
+///     Ok(())
+/// }
+/// ```
+pub fn init(config: CrashpadConfig) -> Result<CrashpadGuard> {
+    let mut global = lock();
+    if global.is_some() {
+        return Err(CrashpadError::AlreadyInitialized);
+    }
+
+    let client = CrashpadClient::new()?;
+    client.start_with_config(&config, &HashMap::new())?;
+
+    let client = Arc::new(client);
+    *global = Some(client.clone());
+    Ok(CrashpadGuard { client })
+}
+
+/// The client installed by [`init`], if its guard is alive
+pub fn global_client() -> Option<Arc<CrashpadClient>> {
+    lock().clone()
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Option<Arc<CrashpadClient>>> {
+    // The lock is never held across user code, a poisoned value is still valid
+    GLOBAL_CLIENT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Keeps the client started by [`init`] installed.
+///
+/// Dereferences to the [`CrashpadClient`]. Dropping the guard removes the
+/// client from [`global_client`], after which [`init`] may be called again.
+///
+/// # Platform Behavior
+///
+/// Crashpad doesn't uninstall its crash handlers when the client is
+/// destroyed: on all platforms, crashes after the guard is dropped are still
+/// forwarded to the handler process for as long as it runs.
+#[must_use = "dropping the guard detaches the global client"]
+pub struct CrashpadGuard {
+    client: Arc<CrashpadClient>,
+}
+
+impl std::ops::Deref for CrashpadGuard {
+    type Target = CrashpadClient;
+
+    fn deref(&self) -> &CrashpadClient {
+        &self.client
+    }
+}
+
+impl Drop for CrashpadGuard {
+    fn drop(&mut self) {
+        let mut global = lock();
+        if global
+            .as_ref()
+            .is_some_and(|client| Arc::ptr_eq(client, &self.client))
+        {
+            *global = None;
+        }
+    }
+}
+
+impl std::fmt::Debug for CrashpadGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CrashpadGuard").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_detaches_global_client() {
+        let client = Arc::new(CrashpadClient::new().unwrap());
+        *lock() = Some(client.clone());
+        let guard = CrashpadGuard {
+            client: client.clone(),
+        };
+
+        assert!(global_client().is_some_and(|global| Arc::ptr_eq(&global, &client)));
+        assert!(matches!(
+            init(CrashpadConfig::default()),
+            Err(CrashpadError::AlreadyInitialized)
+        ));
+
+        drop(guard);
+        assert!(global_client().is_none());
+    }
+}
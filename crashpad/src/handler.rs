@@ -0,0 +1,540 @@
+//! Utilities for inspecting the external `crashpad_handler` binary.
+
+use std::fmt;
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+use std::process::Command;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use std::process::Stdio;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use crate::{CrashpadError, Result};
+
+/// Version reported by a `crashpad_handler` binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HandlerVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl HandlerVersion {
+    /// Parse a line of `crashpad_handler --version` output.
+    ///
+    /// The handler prints `<path> (Crashpad) <version>` followed by a
+    /// copyright line.
+    fn from_version_line(line: &str) -> Option<Self> {
+        let (_, rest) = line.split_once("(Crashpad)")?;
+        rest.split_whitespace().next()?.parse().ok()
+    }
+}
+
+impl FromStr for HandlerVersion {
+    type Err = CrashpadError;
+
+    /// Parse `major.minor[.patch]`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || CrashpadError::HandlerVersion(format!("Invalid version '{s}'"));
+
+        let mut parts = s.trim().split('.');
+        let mut next = |required: bool| -> Result<u32> {
+            match parts.next() {
+                Some(part) => part.parse().map_err(|_| invalid()),
+                None if required => Err(invalid()),
+                None => Ok(0),
+            }
+        };
+
+        let version = Self {
+            major: next(true)?,
+            minor: next(true)?,
+            patch: next(false)?,
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for HandlerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Query the version of a `crashpad_handler` binary.
+///
+/// Runs the handler with `--version`. Useful for asserting at deployment time
+/// that the shipped handler matches expectations.
+///
+/// Not available on iOS/tvOS/watchOS, which use an in-process handler.
+///
+/// # Example
+/// ```no_run
+/// let version = crashpad_rs::handler_version("./crashpad_handler")?;
+/// println!("crashpad_handler {version}");
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+pub fn handler_version<P: AsRef<Path>>(path: P) -> Result<HandlerVersion> {
+    let path = path.as_ref();
+    let output = Command::new(path).arg("--version").output()?;
+
+    // Crashpad's tool support writes the version to stderr; check stdout too
+    // in case that ever changes
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    stderr
+        .lines()
+        .chain(stdout.lines())
+        .find_map(HandlerVersion::from_version_line)
+        .ok_or_else(|| {
+            CrashpadError::HandlerVersion(format!(
+                "No version in output of '{} --version'",
+                path.display()
+            ))
+        })
+}
+
+/// Where [`CrashpadConfig::resolve_handler_with_trace`](crate::CrashpadConfig::resolve_handler_with_trace)
+/// looked for the handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HandlerSource {
+    /// [`CrashpadConfigBuilder::handler_path`](crate::CrashpadConfigBuilder::handler_path),
+    /// or the bundled handler
+    Config,
+    /// The `CRASHPAD_HANDLER` environment variable
+    Environment,
+    /// The directory of the executable
+    ExecutableDirectory,
+    /// The current working directory
+    WorkingDirectory,
+}
+
+impl fmt::Display for HandlerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Config => "config",
+            Self::Environment => "CRASHPAD_HANDLER",
+            Self::ExecutableDirectory => "executable directory",
+            Self::WorkingDirectory => "working directory",
+        })
+    }
+}
+
+/// A path considered for the handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandlerCandidate {
+    pub source: HandlerSource,
+    /// Empty if the source had no path, e.g. an unset environment variable
+    pub path: PathBuf,
+    pub accepted: bool,
+    /// Why the path was accepted or rejected, e.g. `doesn't exist`
+    pub reason: String,
+}
+
+impl HandlerCandidate {
+    #[cfg_attr(
+        any(target_os = "ios", target_os = "tvos", target_os = "watchos"),
+        allow(dead_code)
+    )]
+    pub(crate) fn new(
+        source: HandlerSource,
+        path: impl Into<PathBuf>,
+        accepted: bool,
+        reason: &str,
+    ) -> Self {
+        Self {
+            source,
+            path: path.into(),
+            accepted,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for HandlerCandidate {
+    /// `source path: reason`, e.g. `config /opt/app/crashpad_handler: exists`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)?;
+        if !self.path.as_os_str().is_empty() {
+            write!(f, " {}", self.path.display())?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// Result of [`CrashpadConfig::resolve_handler_with_trace`](crate::CrashpadConfig::resolve_handler_with_trace).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HandlerResolution {
+    /// The chosen handler, `None` if no candidate was accepted. Empty if no
+    /// handler executable is needed, as on iOS.
+    pub path: Option<PathBuf>,
+    /// Candidates in the order they were tried, up to the accepted one
+    pub candidates: Vec<HandlerCandidate>,
+}
+
+/// `(candidates: a; b)` for `candidates`, empty if there are none.
+pub(crate) fn trace_suffix(candidates: &[HandlerCandidate]) -> String {
+    if candidates.is_empty() {
+        return String::new();
+    }
+    let candidates: Vec<String> = candidates.iter().map(ToString::to_string).collect();
+    format!(" (candidates: {})", candidates.join("; "))
+}
+
+/// How the handler binary is verified before it is executed.
+///
+/// Verification happens right before the handler is spawned. It reduces, but
+/// doesn't eliminate, the window in which the file could be swapped; keep the
+/// handler in a directory only trusted users can write to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum HandlerVerification {
+    /// Don't verify the handler
+    #[default]
+    None,
+
+    /// Verify the platform code signature
+    ///
+    /// - **Windows**: Authenticode (`Get-AuthenticodeSignature` must report `Valid`)
+    /// - **macOS**: `codesign --verify --strict`
+    /// - **Linux/Android**: Not supported, use [`HandlerVerification::Minisign`]
+    CodeSignature,
+
+    /// Verify a detached minisign signature stored next to the handler
+    /// as `<handler>.minisig`
+    #[cfg(feature = "minisign")]
+    Minisign {
+        /// Base64 minisign public key (the second line of `minisign.pub`)
+        public_key: String,
+    },
+}
+
+/// How long the handler process may outlive the app on Windows.
+///
+/// The handler is a separate process. After the app exits it keeps running
+/// until the upload in progress, if any, is done, so that the report of a
+/// crash can still be sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum HandlerLifetime {
+    /// The handler exits on its own after the app (Crashpad's behavior)
+    #[default]
+    Independent,
+
+    /// The handler is terminated when the app exits, however it exits
+    ///
+    /// The handler is assigned to a Job Object that is closed with the app.
+    /// Dumps are written before a crashing app terminates, but are only
+    /// uploaded by the next handler.
+    KillWithApp,
+
+    /// Like [`KillWithApp`](Self::KillWithApp), but a normal exit waits up
+    /// to the given time for pending reports to be uploaded first
+    ///
+    /// Crashes still terminate the handler right away.
+    GracePeriod(Duration),
+}
+
+/// Whether the handler started by a client is still running, see
+/// [`CrashpadClient::handler_status`](crate::CrashpadClient::handler_status).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HandlerStatus {
+    /// The handler process is alive
+    Running,
+    /// The handler process is gone; crashes aren't reported until a handler
+    /// is started again
+    Exited,
+    /// No handler was started, or its state can't be told, e.g. for a
+    /// handler that Android starts only at crash
+    Unknown,
+}
+
+/// How the handler of a client was started, see
+/// [`CrashpadClient::handler_info`](crate::CrashpadClient::handler_info).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandlerInfo {
+    /// Process ID of the handler, `None` where it isn't known
+    pub pid: Option<u32>,
+    /// The handler executable and its arguments, as passed to Crashpad.
+    /// Crashpad adds arguments for connecting to the client, e.g.
+    /// `--initial-client-fd`.
+    pub command_line: Vec<String>,
+    /// When the handler was started
+    pub spawned_at: SystemTime,
+}
+
+/// How the handler process is started on Android.
+///
+/// Since Android 10 (API 29), apps targeting it can't execute files from
+/// their data directory, and depending on the packaging the bundled
+/// `libcrashpad_handler.so` may not be extracted to where it can be
+/// executed either. The other variants start the handler through
+/// `app_process` or the system linker instead.
+///
+/// Except for [`Executable`](Self::Executable), the handler is started
+/// when the app crashes, not at startup, so no handler runs while the app is
+/// healthy. A handler started with
+/// [`CrashpadClient::start_handler_for_client`](crate::CrashpadClient::start_handler_for_client)
+/// is started right away. Handler logs, working directory and umask don't
+/// apply.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum AndroidHandlerLaunch {
+    /// Execute the handler file found by the handler path search
+    #[default]
+    Executable,
+
+    /// Run the Java class `class_name` with `app_process`
+    ///
+    /// The class's `main` must call `CrashpadHandlerMain` of a native
+    /// library through JNI, passing on its arguments.
+    Java {
+        /// Fully qualified class name, e.g. `com.example.CrashHandler`
+        class_name: String,
+        /// `VAR=value` environment of `app_process`, e.g. with the
+        /// `CLASSPATH` of the APK. `None` uses the app's environment.
+        env: Option<Vec<String>>,
+    },
+
+    /// Run the trampoline library `trampoline` with the system linker,
+    /// which loads `library` and calls its `CrashpadHandlerMain`
+    ///
+    /// `trampoline` is built by Crashpad as `crashpad_handler_trampoline`.
+    /// Both are library names found by the linker in the app's native
+    /// library directory, e.g. `libcrashpad_handler_trampoline.so`.
+    Linker {
+        trampoline: String,
+        library: String,
+        /// `VAR=value` environment of the linker, `None` uses the app's
+        /// environment
+        env: Option<Vec<String>>,
+    },
+}
+
+/// Verify the handler binary at `path`.
+///
+/// Returns [`CrashpadError::HandlerIntegrityError`] if the binary is unsigned,
+/// modified, or can't be verified on this platform.
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+pub fn verify_handler<P: AsRef<Path>>(path: P, verification: &HandlerVerification) -> Result<()> {
+    let path = path.as_ref();
+    match verification {
+        HandlerVerification::None => Ok(()),
+        HandlerVerification::CodeSignature => verify_code_signature(path),
+        #[cfg(feature = "minisign")]
+        HandlerVerification::Minisign { public_key } => verify_minisign(path, public_key),
+    }
+}
+
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+fn integrity_error(path: &Path, reason: impl Into<String>) -> CrashpadError {
+    CrashpadError::HandlerIntegrityError {
+        path: path.to_path_buf(),
+        reason: reason.into(),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn verify_code_signature(path: &Path) -> Result<()> {
+    // Use the absolute path so a PATH entry can't substitute PowerShell
+    let system_root = std::env::var_os("SystemRoot").unwrap_or_else(|| "C:\\Windows".into());
+    let powershell = Path::new(&system_root)
+        .join("System32")
+        .join("WindowsPowerShell")
+        .join("v1.0")
+        .join("powershell.exe");
+
+    // Pass the path through the environment to avoid quoting issues
+    let output = Command::new(powershell)
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            "(Get-AuthenticodeSignature -LiteralPath $env:CRASHPAD_VERIFY_PATH).Status",
+        ])
+        .env("CRASHPAD_VERIFY_PATH", path)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| integrity_error(path, format!("Failed to run PowerShell: {e}")))?;
+
+    let status = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() && status == "Valid" {
+        Ok(())
+    } else {
+        Err(integrity_error(
+            path,
+            format!("Authenticode signature status: {status}"),
+        ))
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn verify_code_signature(path: &Path) -> Result<()> {
+    let output = Command::new("/usr/bin/codesign")
+        .args(["--verify", "--strict"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| integrity_error(path, format!("Failed to run codesign: {e}")))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(integrity_error(
+            path,
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ))
+    }
+}
+
+#[cfg(not(any(
+    target_os = "macos",
+    target_os = "windows",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos"
+)))]
+fn verify_code_signature(path: &Path) -> Result<()> {
+    Err(integrity_error(
+        path,
+        "Code signature verification is not supported on this platform, use minisign",
+    ))
+}
+
+#[cfg(all(
+    feature = "minisign",
+    not(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))
+))]
+fn verify_minisign(path: &Path, public_key: &str) -> Result<()> {
+    use minisign_verify::{PublicKey, Signature};
+
+    let mut signature_path = path.as_os_str().to_owned();
+    signature_path.push(".minisig");
+
+    let public_key = PublicKey::from_base64(public_key)
+        .map_err(|e| CrashpadError::InvalidConfiguration(format!("Invalid public key: {e}")))?;
+    let signature = std::fs::read_to_string(&signature_path)
+        .map_err(|e| integrity_error(path, format!("Failed to read signature: {e}")))?;
+    let signature = Signature::decode(&signature)
+        .map_err(|e| integrity_error(path, format!("Invalid signature: {e}")))?;
+    let binary = std::fs::read(path)?;
+
+    public_key
+        .verify(&binary, &signature, false)
+        .map_err(|e| integrity_error(path, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        let version: HandlerVersion = "0.8.0".parse().unwrap();
+        assert_eq!(
+            version,
+            HandlerVersion {
+                major: 0,
+                minor: 8,
+                patch: 0
+            }
+        );
+        assert_eq!(version.to_string(), "0.8.0");
+
+        // Patch is optional
+        assert_eq!("1.2".parse::<HandlerVersion>().unwrap().patch, 0);
+
+        assert!("".parse::<HandlerVersion>().is_err());
+        assert!("1".parse::<HandlerVersion>().is_err());
+        assert!("1.2.3.4".parse::<HandlerVersion>().is_err());
+        assert!("1.x.0".parse::<HandlerVersion>().is_err());
+    }
+
+    #[test]
+    fn test_parse_version_line() {
+        let line = "/usr/local/bin/crashpad_handler (Crashpad) 0.8.0";
+        assert_eq!(
+            HandlerVersion::from_version_line(line),
+            Some("0.8.0".parse().unwrap())
+        );
+        assert_eq!(
+            HandlerVersion::from_version_line("Copyright 2014 The Crashpad Authors"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_version_ordering() {
+        let old: HandlerVersion = "0.8.0".parse().unwrap();
+        let new: HandlerVersion = "0.10.1".parse().unwrap();
+        assert!(old < new);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_handler_version_runs_binary() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let handler = temp_dir.path().join("crashpad_handler");
+        std::fs::write(
+            &handler,
+            "#!/bin/sh\necho \"$0 (Crashpad) 0.8.0\" >&2\necho 'Copyright 2014 The Crashpad Authors' >&2\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&handler, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(handler_version(&handler).unwrap(), "0.8.0".parse().unwrap());
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn test_handler_version_missing_binary() {
+        assert!(handler_version("/nonexistent/crashpad_handler").is_err());
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn test_verify_handler_none() {
+        assert!(
+            verify_handler("/nonexistent/crashpad_handler", &HandlerVerification::None).is_ok()
+        );
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+    fn test_verify_handler_unsigned() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let handler = temp_dir.path().join("crashpad_handler");
+        std::fs::write(&handler, b"not a signed binary").unwrap();
+
+        let result = verify_handler(&handler, &HandlerVerification::CodeSignature);
+        assert!(matches!(
+            result,
+            Err(CrashpadError::HandlerIntegrityError { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "minisign",
+        not(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))
+    ))]
+    fn test_verify_handler_minisign_missing_signature() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let handler = temp_dir.path().join("crashpad_handler");
+        std::fs::write(&handler, b"handler").unwrap();
+
+        let verification = HandlerVerification::Minisign {
+            public_key: "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3".to_string(),
+        };
+        assert!(matches!(
+            verify_handler(&handler, &verification),
+            Err(CrashpadError::HandlerIntegrityError { .. })
+        ));
+    }
+}
@@ -0,0 +1,167 @@
+//! Log file for the external handler process.
+
+use std::path::{Path, PathBuf};
+
+/// File name used by [`HandlerLog::in_database`].
+const DEFAULT_FILE_NAME: &str = "crashpad_handler.log";
+
+/// Where `crashpad_handler` writes its log.
+///
+/// The handler logs warnings and errors (failed uploads, database problems)
+/// to stderr, which it inherits from the application. A `HandlerLog` points
+/// that stderr at a file instead, so the log survives in the field.
+///
+/// The file is rotated when the handler starts: if it has grown past
+/// [`max_size`](Self::max_size), it is renamed to `<name>.1` (shifting older
+/// files up to [`keep`](Self::keep)) and a new file is started.
+///
+/// `crashpad_handler` has no verbosity option; its log only contains what
+/// Crashpad considers worth reporting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandlerLog {
+    path: Option<PathBuf>,
+    max_size: u64,
+    keep: usize,
+}
+
+impl HandlerLog {
+    /// Log to `crashpad_handler.log` inside the database directory
+    pub fn in_database() -> Self {
+        Self {
+            path: None,
+            max_size: 1024 * 1024,
+            keep: 3,
+        }
+    }
+
+    /// Log to the file at `path`
+    pub fn file<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: Some(path.as_ref().to_path_buf()),
+            ..Self::in_database()
+        }
+    }
+
+    /// Size in bytes past which the log is rotated at handler start
+    /// (1 MiB by default)
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = bytes;
+        self
+    }
+
+    /// Number of rotated files to keep (3 by default, 0 to discard the log
+    /// on rotation)
+    pub fn keep(mut self, files: usize) -> Self {
+        self.keep = files;
+        self
+    }
+
+    /// Explicitly configured log file, `None` for the database default
+    #[cfg(feature = "serde")]
+    pub(crate) fn file_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn max_size_bytes(&self) -> u64 {
+        self.max_size
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn keep_files(&self) -> usize {
+        self.keep
+    }
+
+    /// Path of the log file for a handler using `database_path`.
+    pub(crate) fn path(&self, database_path: &Path) -> PathBuf {
+        self.path
+            .clone()
+            .unwrap_or_else(|| database_path.join(DEFAULT_FILE_NAME))
+    }
+
+    /// Rotate the log at `path` if it is too large.
+    pub(crate) fn rotate(&self, path: &Path) -> std::io::Result<()> {
+        match std::fs::metadata(path) {
+            Ok(metadata) if metadata.len() > self.max_size => {}
+            Ok(_) => return Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        if self.keep == 0 {
+            return std::fs::remove_file(path);
+        }
+        for index in (1..self.keep).rev() {
+            let from = rotated_path(path, index);
+            if from.exists() {
+                std::fs::rename(&from, rotated_path(path, index + 1))?;
+            }
+        }
+        std::fs::rename(path, rotated_path(path, 1))
+    }
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_path() {
+        let database = Path::new("/var/crashes");
+        assert_eq!(
+            HandlerLog::in_database().path(database),
+            database.join("crashpad_handler.log")
+        );
+        assert_eq!(
+            HandlerLog::file("/var/log/handler.log").path(database),
+            Path::new("/var/log/handler.log")
+        );
+    }
+
+    #[test]
+    fn test_rotate() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("handler.log");
+        let log = HandlerLog::file(&path).max_size(4).keep(2);
+
+        // Missing and small logs are left alone
+        log.rotate(&path).unwrap();
+        std::fs::write(&path, "1234").unwrap();
+        log.rotate(&path).unwrap();
+        assert!(path.exists());
+
+        for contents in ["first", "second", "third"] {
+            std::fs::write(&path, contents).unwrap();
+            log.rotate(&path).unwrap();
+        }
+
+        assert!(!path.exists());
+        let read = |index| std::fs::read_to_string(rotated_path(&path, index)).unwrap();
+        assert_eq!(read(1), "third");
+        assert_eq!(read(2), "second");
+        assert!(!rotated_path(&path, 3).exists());
+    }
+
+    #[test]
+    fn test_rotate_without_keeping() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("handler.log");
+        std::fs::write(&path, "too large").unwrap();
+
+        HandlerLog::file(&path)
+            .max_size(1)
+            .keep(0)
+            .rotate(&path)
+            .unwrap();
+
+        assert!(!path.exists());
+        assert!(!rotated_path(&path, 1).exists());
+    }
+}
@@ -0,0 +1,317 @@
+//! Recent values of selected keys, included in every dump.
+//!
+//! Annotations only show the last value of a key. A [`ValueHistory`] keeps
+//! the last few values of one key with the time each was recorded, in a
+//! buffer allocated and registered with Crashpad up front, so recording
+//! never allocates and every dump written afterwards carries the history.
+//! [`Minidump::value_histories`](crate::minidump::Minidump::value_histories)
+//! reads it back.
+//!
+//! The buffer is a Crashpad annotation of a user-defined type: a header of
+//! four little-endian `u32` (format version, capacity, maximum value length,
+//! number of values recorded), followed by `capacity` slots of a `u64` time
+//! in milliseconds since the Unix epoch, a `u32` length and the value.
+
+use std::sync::atomic::{compiler_fence, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crashpad_rs_sys::{
+    crashpad_annotation_register, CRASHPAD_ANNOTATION_NAME_MAX_LENGTH,
+    CRASHPAD_ANNOTATION_VALUE_MAX_SIZE,
+};
+
+use crate::{CrashpadError, Result};
+
+/// User-defined annotation type of a history, `"vh"`
+pub(crate) const VALUE_HISTORY_TYPE: u16 = 0x7668;
+
+/// Format version in the header
+const FORMAT_VERSION: u32 = 1;
+
+const HEADER_SIZE: usize = 16;
+
+/// Time and length in front of each value
+const SLOT_HEADER_SIZE: usize = 12;
+
+/// Largest buffer Crashpad includes in a dump
+const MAX_BUFFER_SIZE: usize = CRASHPAD_ANNOTATION_VALUE_MAX_SIZE as usize - 1;
+
+/// A value a key had, and when it was recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueChange {
+    pub time: SystemTime,
+    pub value: String,
+}
+
+/// History of the values of one key, included in every dump.
+///
+/// Created with [`register`](Self::register), which allocates a buffer for
+/// `capacity` values of up to `max_value_len` bytes each. Once full, each
+/// new value replaces the oldest one. Registered histories live for the rest
+/// of the process, so register each key once, e.g. in a `static`
+/// `OnceLock`.
+///
+/// # Example
+/// ```rust,no_run
+/// use crashpad_rs::ValueHistory;
+///
+/// let state = ValueHistory::register("connection_state", 32, 16)?;
+/// state.record("connecting");
+/// state.record("connected");
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+#[derive(Debug)]
+pub struct ValueHistory {
+    key: String,
+    ring: Mutex<Ring>,
+}
+
+impl ValueHistory {
+    /// Register a history of `key` with room for `capacity` values of up to
+    /// `max_value_len` bytes
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if `capacity` is zero, `key`
+    /// is longer than 255 bytes or contains a NUL byte, or the buffer would
+    /// be larger than Crashpad includes in a dump (about 20 KiB; each value
+    /// takes `max_value_len` plus 12 bytes).
+    pub fn register(key: &str, capacity: usize, max_value_len: usize) -> Result<&'static Self> {
+        if capacity == 0 {
+            return Err(CrashpadError::InvalidConfiguration(format!(
+                "History of {key} has no capacity"
+            )));
+        }
+        if key.len() >= CRASHPAD_ANNOTATION_NAME_MAX_LENGTH as usize || key.contains('\0') {
+            return Err(CrashpadError::InvalidConfiguration(format!(
+                "Invalid history key {key:?}"
+            )));
+        }
+        let size = buffer_size(capacity, max_value_len).filter(|&size| size <= MAX_BUFFER_SIZE);
+        let Some(size) = size else {
+            return Err(CrashpadError::InvalidConfiguration(format!(
+                "History of {key} needs more than {MAX_BUFFER_SIZE} bytes"
+            )));
+        };
+
+        // Crashpad reads the buffer until the process exits
+        let buffer: &'static mut [u8] = Box::leak(vec![0u8; size].into_boxed_slice());
+        let ring = Ring::new(buffer, capacity, max_value_len);
+
+        let name = std::ffi::CString::new(key).expect("key has no NUL bytes");
+        let registered = unsafe {
+            crashpad_annotation_register(
+                name.as_ptr(),
+                VALUE_HISTORY_TYPE,
+                ring.buffer.as_mut_ptr().cast(),
+                size as u32,
+            )
+        };
+        if !registered {
+            return Err(CrashpadError::InvalidConfiguration(format!(
+                "Crashpad rejected the history of {key}"
+            )));
+        }
+
+        Ok(Box::leak(Box::new(Self {
+            key: key.to_string(),
+            ring: Mutex::new(ring),
+        })))
+    }
+
+    /// The key this history records
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Record `value` as the current value, with the current time
+    ///
+    /// Values longer than `max_value_len` are truncated at a character
+    /// boundary.
+    pub fn record(&self, value: impl AsRef<str>) {
+        let mut ring = self.ring.lock().unwrap_or_else(|e| e.into_inner());
+        ring.push(SystemTime::now(), value.as_ref());
+    }
+}
+
+/// Bytes needed for `capacity` values of up to `max_value_len` bytes
+fn buffer_size(capacity: usize, max_value_len: usize) -> Option<usize> {
+    max_value_len
+        .checked_add(SLOT_HEADER_SIZE)?
+        .checked_mul(capacity)?
+        .checked_add(HEADER_SIZE)
+}
+
+/// The history buffer, in the format described in the module docs.
+#[derive(Debug)]
+struct Ring {
+    buffer: &'static mut [u8],
+    capacity: usize,
+    max_value_len: usize,
+    count: u32,
+}
+
+impl Ring {
+    fn new(buffer: &'static mut [u8], capacity: usize, max_value_len: usize) -> Self {
+        buffer[0..4].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+        buffer[4..8].copy_from_slice(&(capacity as u32).to_le_bytes());
+        buffer[8..12].copy_from_slice(&(max_value_len as u32).to_le_bytes());
+        Self {
+            buffer,
+            capacity,
+            max_value_len,
+            count: 0,
+        }
+    }
+
+    fn push(&mut self, time: SystemTime, value: &str) {
+        let value = truncate(value, self.max_value_len);
+        let millis = time
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+
+        let slot_size = SLOT_HEADER_SIZE + self.max_value_len;
+        let slot = HEADER_SIZE + (self.count as usize % self.capacity) * slot_size;
+        let slot = &mut self.buffer[slot..slot + slot_size];
+        slot[0..8].copy_from_slice(&millis.to_le_bytes());
+        slot[8..12].copy_from_slice(&(value.len() as u32).to_le_bytes());
+        slot[12..12 + value.len()].copy_from_slice(value.as_bytes());
+
+        // A dump taken while the slot is written must not count it yet
+        compiler_fence(Ordering::SeqCst);
+        self.count = self.count.wrapping_add(1);
+        self.buffer[12..16].copy_from_slice(&self.count.to_le_bytes());
+    }
+}
+
+/// `value` cut to at most `max_len` bytes at a character boundary
+pub(crate) fn truncate(value: &str, max_len: usize) -> &str {
+    if value.len() <= max_len {
+        return value;
+    }
+    let end = (0..=max_len)
+        .rev()
+        .find(|&end| value.is_char_boundary(end))
+        .unwrap_or(0);
+    &value[..end]
+}
+
+/// Values of a history buffer read from a dump, oldest first. `None` if
+/// the buffer is malformed.
+pub(crate) fn decode(bytes: &[u8]) -> Option<Vec<ValueChange>> {
+    let u32_at = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            bytes.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    if u32_at(0)? != FORMAT_VERSION {
+        return None;
+    }
+    let capacity = u32_at(4)? as usize;
+    let max_value_len = u32_at(8)? as usize;
+    let count = u32_at(12)? as usize;
+    let slot_size = SLOT_HEADER_SIZE + max_value_len;
+    if capacity == 0 || bytes.len() < buffer_size(capacity, max_value_len)? {
+        return None;
+    }
+
+    // Once full, the oldest slot may have been half overwritten by a
+    // record in progress at the time of the dump
+    let len = if count < capacity {
+        count
+    } else {
+        capacity - 1
+    };
+    (count - len..count)
+        .map(|index| {
+            let slot = HEADER_SIZE + (index % capacity) * slot_size;
+            let millis = u64::from_le_bytes(bytes.get(slot..slot + 8)?.try_into().ok()?);
+            let value_len = (u32_at(slot + 8)? as usize).min(max_value_len);
+            let value = bytes.get(slot + 12..slot + 12 + value_len)?;
+            Some(ValueChange {
+                time: UNIX_EPOCH + Duration::from_millis(millis),
+                value: String::from_utf8_lossy(value).into_owned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(capacity: usize, max_value_len: usize) -> Ring {
+        let size = buffer_size(capacity, max_value_len).unwrap();
+        Ring::new(
+            Box::leak(vec![0u8; size].into_boxed_slice()),
+            capacity,
+            max_value_len,
+        )
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut ring = ring(4, 8);
+        assert_eq!(decode(ring.buffer).unwrap(), vec![]);
+
+        ring.push(at(1), "idle");
+        ring.push(at(2), "connecting");
+        let history = decode(ring.buffer).unwrap();
+        assert_eq!(
+            history,
+            vec![
+                ValueChange {
+                    time: at(1),
+                    value: "idle".to_string()
+                },
+                ValueChange {
+                    time: at(2),
+                    value: "connecti".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wraps_around() {
+        let mut ring = ring(3, 4);
+        for seconds in 1..=5 {
+            ring.push(at(seconds), &seconds.to_string());
+        }
+
+        // The oldest remaining slot is skipped, it may be incomplete
+        let values: Vec<_> = decode(ring.buffer)
+            .unwrap()
+            .into_iter()
+            .map(|change| change.value)
+            .collect();
+        assert_eq!(values, ["4", "5"]);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("héllo", 2), "h");
+        assert_eq!(truncate("héllo", 3), "hé");
+        assert_eq!(truncate("abc", 8), "abc");
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(decode(&[]).is_none());
+        let mut ring = ring(2, 4);
+        ring.push(at(1), "a");
+        assert!(decode(&ring.buffer[..20]).is_none());
+    }
+
+    #[test]
+    fn test_register_limits() {
+        assert!(ValueHistory::register("empty", 0, 16).is_err());
+        assert!(ValueHistory::register("huge", 1024, 1024).is_err());
+        assert!(ValueHistory::register(&"k".repeat(256), 1, 1).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+//! Removal of files left behind in the database by interrupted dumps.
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::clock::{Clock, SystemClock};
+use crate::database::ANNOTATIONS_EXTENSION;
+
+/// Directories holding reports, `reports` on Windows.
+const REPORT_DIRS: &[&str] = &["new", "pending", "completed", "reports"];
+
+/// Directory holding in-progress dumps; anything left there is orphaned.
+const NEW_DIR: &str = "new";
+
+/// Directory holding one subdirectory of attachments per report.
+pub(crate) const ATTACHMENTS_DIR: &str = "attachments";
+
+/// Result of the cleanup run by the last start, see [`last_cleanup`].
+static LAST_CLEANUP: Mutex<Option<CleanupReport>> = Mutex::new(None);
+
+/// What [`clean_database`] removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanupReport {
+    /// Files and directories that were removed
+    pub removed: Vec<PathBuf>,
+    /// Stale entries that could not be removed, with the error
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// Remove stale temporary files from a Crashpad database.
+///
+/// Removes entries older than `max_age`:
+/// - `.tmp` and `.lock` files anywhere in the database
+/// - dumps in `new/` that were never finished
+/// - attachment directories and annotation sidecars whose report no longer
+///   exists
+///
+/// Crashpad's handler cleans up after itself with periodic tasks enabled;
+/// this is for databases whose handler was killed or runs without them.
+/// Newer entries are kept, so a handler of another process writing to the
+/// same database is not disturbed.
+pub fn clean_database<P: AsRef<Path>>(database: P, max_age: Duration) -> CleanupReport {
+    remove_entries(stale_entries(database.as_ref(), max_age, &SystemClock))
+}
+
+/// Result of the cleanup done when the handler was last started with
+/// [`CrashpadConfigBuilder::database_janitor`](crate::CrashpadConfigBuilder::database_janitor).
+pub fn last_cleanup() -> Option<CleanupReport> {
+    LAST_CLEANUP
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+pub(crate) fn record_cleanup(report: CleanupReport) {
+    *LAST_CLEANUP.lock().unwrap_or_else(|e| e.into_inner()) = Some(report);
+}
+
+/// Entries of `database` that [`clean_database`] removes now.
+fn stale_entries(database: &Path, max_age: Duration, clock: &impl Clock) -> Vec<PathBuf> {
+    let now = clock.now();
+    let is_stale = |path: &Path| {
+        std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .map(|modified| now.duration_since(modified).unwrap_or_default() > max_age)
+            .unwrap_or(false)
+    };
+
+    let mut stale = Vec::new();
+    let mut report_ids = HashSet::new();
+    let mut sidecars = Vec::new();
+    for dir in REPORT_DIRS {
+        for path in files(&database.join(dir)) {
+            if let Some(id) = sidecar_report_id(&path) {
+                sidecars.push((id, path));
+                continue;
+            }
+            if let Some(stem) = path.file_stem() {
+                report_ids.insert(stem.to_os_string());
+            }
+            let temporary = path
+                .extension()
+                .is_some_and(|ext| ext == "tmp" || ext == "lock");
+            if (temporary || *dir == NEW_DIR) && is_stale(&path) {
+                stale.push(path);
+            }
+        }
+    }
+
+    for (id, path) in sidecars {
+        if !report_ids.contains(&id) && is_stale(&path) {
+            stale.push(path);
+        }
+    }
+
+    for path in files(database) {
+        if path.extension().is_some_and(|ext| ext == "tmp") && is_stale(&path) {
+            stale.push(path);
+        }
+    }
+
+    if let Ok(entries) = std::fs::read_dir(database.join(ATTACHMENTS_DIR)) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() && !report_ids.contains(&entry.file_name()) && is_stale(&path) {
+                stale.push(path);
+            }
+        }
+    }
+
+    stale.sort();
+    stale
+}
+
+/// Report ID of an annotation sidecar (`<uuid>.annotations.json`).
+fn sidecar_report_id(path: &Path) -> Option<OsString> {
+    let name = path.file_name()?.to_str()?;
+    let id = name
+        .strip_suffix(ANNOTATIONS_EXTENSION)?
+        .strip_suffix('.')?;
+    Some(OsString::from(id))
+}
+
+fn remove_entries(paths: Vec<PathBuf>) -> CleanupReport {
+    let mut report = CleanupReport::default();
+    for path in paths {
+        let result = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        match result {
+            Ok(()) => report.removed.push(path),
+            Err(e) => report.failed.push((path, e.to_string())),
+        }
+    }
+    report
+}
+
+/// Regular files directly in `dir`.
+fn files(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::manual::ManualClock;
+    use std::time::SystemTime;
+    use tempfile::TempDir;
+
+    fn touch(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"").unwrap();
+    }
+
+    #[test]
+    fn test_stale_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = temp_dir.path();
+        touch(&db.join("new/aaaa.dmp"));
+        touch(&db.join("pending/bbbb.dmp"));
+        touch(&db.join("pending/bbbb.lock"));
+        touch(&db.join("completed/cccc.dmp"));
+        touch(&db.join("completed/cccc.annotations.json"));
+        touch(&db.join("completed/eeee.annotations.json"));
+        touch(&db.join("settings.dat.tmp"));
+        touch(&db.join("attachments/bbbb/log.txt"));
+        touch(&db.join("attachments/dddd/log.txt"));
+
+        // Nothing is old enough yet
+        let clock = ManualClock::at(SystemTime::now());
+        assert!(stale_entries(db, Duration::from_secs(60), &clock).is_empty());
+
+        clock.advance(Duration::from_secs(120));
+        assert_eq!(
+            stale_entries(db, Duration::from_secs(60), &clock),
+            vec![
+                db.join("attachments/dddd"),
+                db.join("completed/eeee.annotations.json"),
+                db.join("new/aaaa.dmp"),
+                db.join("pending/bbbb.lock"),
+                db.join("settings.dat.tmp"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remove_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = temp_dir.path();
+        touch(&db.join("pending/bbbb.lock"));
+        touch(&db.join("attachments/dddd/log.txt"));
+
+        let report = remove_entries(vec![
+            db.join("attachments/dddd"),
+            db.join("pending/bbbb.lock"),
+            db.join("pending/missing.lock"),
+        ]);
+
+        assert_eq!(
+            report.removed,
+            vec![db.join("attachments/dddd"), db.join("pending/bbbb.lock")]
+        );
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, db.join("pending/missing.lock"));
+        assert!(db.join("attachments").exists());
+    }
+}
@@ -0,0 +1,187 @@
+//! Annotations with values longer than the simple annotation dictionary
+//! holds.
+//!
+//! Simple annotations are cut at [`MAX_VALUE_LEN`](crate::limits::MAX_VALUE_LEN)
+//! bytes. A [`LargeAnnotation`] is a Crashpad string annotation instead: a
+//! length-delimited value in a buffer allocated and registered up front, up
+//! to [`MAX_LARGE_VALUE_LEN`](crate::limits::MAX_LARGE_VALUE_LEN) bytes.
+//! Every dump written while it is set carries the value, and
+//! [`Minidump::annotations`](crate::minidump::Minidump::annotations) reads it
+//! back with the simple annotations.
+//!
+//! Crashpad only sends simple annotations as form fields when uploading;
+//! large annotations are in the minidump for the crash server to read.
+
+use std::ffi::CString;
+use std::sync::atomic::{compiler_fence, Ordering};
+use std::sync::Mutex;
+
+use crashpad_rs_sys::{
+    crashpad_annotation_t, crashpad_string_annotation_register,
+    crashpad_string_annotation_set_size, CRASHPAD_ANNOTATION_NAME_MAX_LENGTH,
+};
+
+use crate::history::truncate;
+use crate::limits::MAX_LARGE_VALUE_LEN;
+use crate::{CrashpadError, Result};
+
+/// An annotation whose value can be up to about 20 KiB long.
+///
+/// Created with [`register`](Self::register), which allocates a buffer for
+/// values of up to `max_len` bytes. Registered annotations live for the
+/// rest of the process, so register each key once, e.g. in a `static`
+/// `OnceLock`.
+///
+/// # Truncation
+///
+/// Values longer than `max_len` are cut at the last character boundary
+/// that fits, without a marker; [`set`](Self::set) returns whether that
+/// happened. The simple annotations of the dump win over a large annotation
+/// with the same key.
+///
+/// # Example
+/// ```rust,no_run
+/// use crashpad_rs::LargeAnnotation;
+///
+/// let breadcrumbs = LargeAnnotation::register("breadcrumbs", 16 * 1024)?;
+/// breadcrumbs.set(r#"[{"t":1714563000,"event":"login"}]"#);
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+#[derive(Debug)]
+pub struct LargeAnnotation {
+    key: String,
+    value: Mutex<Value>,
+}
+
+/// The registered buffer and its annotation.
+#[derive(Debug)]
+struct Value {
+    buffer: &'static mut [u8],
+    annotation: crashpad_annotation_t,
+    /// Bytes of the buffer set, 0 while cleared
+    len: usize,
+}
+
+// SAFETY: The annotation is only passed to Crashpad with the mutex held,
+// and is never freed
+unsafe impl Send for Value {}
+
+impl LargeAnnotation {
+    /// Register an annotation of `key` with room for values of up to
+    /// `max_len` bytes
+    ///
+    /// The annotation is left out of dumps until a value is set.
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if `max_len` is zero or over
+    /// [`MAX_LARGE_VALUE_LEN`], or `key` is longer than 255 bytes or
+    /// contains a NUL byte.
+    pub fn register(key: &str, max_len: usize) -> Result<&'static Self> {
+        if max_len == 0 || max_len > MAX_LARGE_VALUE_LEN {
+            return Err(CrashpadError::InvalidConfiguration(format!(
+                "Annotation {key} must hold 1 to {MAX_LARGE_VALUE_LEN} bytes"
+            )));
+        }
+        if key.len() >= CRASHPAD_ANNOTATION_NAME_MAX_LENGTH as usize || key.contains('\0') {
+            return Err(CrashpadError::InvalidConfiguration(format!(
+                "Invalid annotation key {key:?}"
+            )));
+        }
+
+        // Crashpad reads the buffer until the process exits
+        let buffer: &'static mut [u8] = Box::leak(vec![0u8; max_len].into_boxed_slice());
+        let name = CString::new(key).expect("key has no NUL bytes");
+        let annotation = unsafe {
+            crashpad_string_annotation_register(
+                name.as_ptr(),
+                buffer.as_mut_ptr().cast(),
+                max_len as u32,
+            )
+        };
+        if annotation.is_null() {
+            return Err(CrashpadError::InvalidConfiguration(format!(
+                "Crashpad rejected the annotation {key}"
+            )));
+        }
+
+        Ok(Box::leak(Box::new(Self {
+            key: key.to_string(),
+            value: Mutex::new(Value {
+                buffer,
+                annotation,
+                len: 0,
+            }),
+        })))
+    }
+
+    /// The key of this annotation
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Set the value included in dumps from now on
+    ///
+    /// Returns `true` if the value was truncated to fit, see
+    /// [Truncation](Self#truncation). An empty value is like
+    /// [`clear`](Self::clear).
+    pub fn set(&self, value: impl AsRef<str>) -> bool {
+        let value = value.as_ref();
+        let mut slot = self.value.lock().unwrap_or_else(|e| e.into_inner());
+        let stored = truncate(value, slot.buffer.len());
+        let old = crate::annotation_audit::is_enabled()
+            .then(|| String::from_utf8_lossy(&slot.buffer[..slot.len]).into_owned());
+
+        // A dump taken while the buffer is written leaves the annotation out
+        // instead of mixing two values
+        unsafe { crashpad_string_annotation_set_size(slot.annotation, 0) };
+        compiler_fence(Ordering::SeqCst);
+        slot.buffer[..stored.len()].copy_from_slice(stored.as_bytes());
+        compiler_fence(Ordering::SeqCst);
+        unsafe { crashpad_string_annotation_set_size(slot.annotation, stored.len() as u32) };
+
+        slot.len = stored.len();
+        if let Some(old) = old {
+            crate::annotation_audit::record(&self.key, &old, stored);
+        }
+        stored.len() < value.len()
+    }
+
+    /// Leave the annotation out of dumps until a value is set again
+    pub fn clear(&self) {
+        let mut slot = self.value.lock().unwrap_or_else(|e| e.into_inner());
+        unsafe { crashpad_string_annotation_set_size(slot.annotation, 0) };
+        let old = std::mem::take(&mut slot.len);
+        if crate::annotation_audit::is_enabled() {
+            crate::annotation_audit::record(
+                &self.key,
+                &String::from_utf8_lossy(&slot.buffer[..old]),
+                "",
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_invalid() {
+        assert!(LargeAnnotation::register("empty", 0).is_err());
+        assert!(LargeAnnotation::register("huge", MAX_LARGE_VALUE_LEN + 1).is_err());
+        assert!(LargeAnnotation::register("nul\0key", 16).is_err());
+        assert!(LargeAnnotation::register(&"k".repeat(256), 16).is_err());
+    }
+
+    #[test]
+    fn test_set_truncates() {
+        let annotation = LargeAnnotation::register("large_annotation_test", 4).unwrap();
+        assert_eq!(annotation.key(), "large_annotation_test");
+        assert!(!annotation.set("abcd"));
+        // Cut before the 2-byte character
+        assert!(annotation.set("abcé"));
+        assert_eq!(&annotation.value.lock().unwrap().buffer[..3], b"abc");
+        annotation.clear();
+    }
+}
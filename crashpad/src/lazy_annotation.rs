@@ -0,0 +1,144 @@
+//! Annotations computed in the background after the handler started.
+//!
+//! Process annotations are passed to the handler when it starts, so
+//! computing an expensive one (probing the GPU, reading a large file) delays
+//! startup. A lazy annotation is computed on a background thread once the
+//! handler is running instead, and set as an annotation of this module,
+//! which Crashpad reads whenever it writes a dump.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+
+use crashpad_rs_sys::crashpad_set_module_annotation;
+
+use crate::{BreakpadFields, RedactionRules};
+
+/// Key and function of an annotation computed after start.
+#[derive(Clone)]
+pub(crate) struct LazyAnnotation {
+    pub key: String,
+    pub compute: Arc<dyn Fn() -> String + Send + Sync>,
+}
+
+impl fmt::Debug for LazyAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyAnnotation")
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Compute `annotations` on a background thread and set each value as soon
+/// as it is ready, after redaction and renaming like the process
+/// annotations.
+///
+/// Keys among the process annotations given to the handler, `eager`, are
+/// skipped, since those would hide them anyway.
+pub(crate) fn spawn(
+    annotations: &[LazyAnnotation],
+    eager: &HashMap<String, String>,
+    redaction: Option<&RedactionRules>,
+    breakpad_fields: Option<&BreakpadFields>,
+) {
+    if annotations.is_empty() {
+        return;
+    }
+    let annotations = annotations.to_vec();
+    let eager: HashSet<String> = eager.keys().cloned().collect();
+    let redaction = redaction.cloned();
+    let breakpad_fields = breakpad_fields.cloned();
+
+    // Without a thread, the annotations are missing, like a failed probe
+    let _ = thread::Builder::new()
+        .name("crashpad-lazy-annotations".to_string())
+        .spawn(move || {
+            for annotation in annotations {
+                // A panicking function only loses its own annotation
+                let Ok(value) = panic::catch_unwind(AssertUnwindSafe(|| (annotation.compute)()))
+                else {
+                    continue;
+                };
+                let (key, value) = resolve(
+                    &annotation.key,
+                    &value,
+                    redaction.as_ref(),
+                    breakpad_fields.as_ref(),
+                );
+                if !eager.contains(&key) {
+                    set_module_annotation(&key, &value);
+                }
+            }
+        });
+}
+
+/// Set `value` as the annotation `key` of this module, after redaction and
+/// renaming like the process annotations.
+pub(crate) fn set(
+    key: &str,
+    value: &str,
+    redaction: Option<&RedactionRules>,
+    breakpad_fields: Option<&BreakpadFields>,
+) {
+    let (key, value) = resolve(key, value, redaction, breakpad_fields);
+    set_module_annotation(&key, &value);
+}
+
+/// Key and value of an annotation as stored: redacted, then renamed.
+fn resolve(
+    key: &str,
+    value: &str,
+    redaction: Option<&RedactionRules>,
+    breakpad_fields: Option<&BreakpadFields>,
+) -> (String, String) {
+    let value = match redaction {
+        Some(rules) => rules.redact(key, value).into_owned(),
+        None => value.to_string(),
+    };
+    let key = match breakpad_fields {
+        Some(fields) => fields.field_name(key).to_string(),
+        None => key.to_string(),
+    };
+    (key, value)
+}
+
+fn set_module_annotation(key: &str, value: &str) {
+    let (Ok(key), Ok(value)) = (CString::new(key), CString::new(value)) else {
+        return;
+    };
+    unsafe { crashpad_set_module_annotation(key.as_ptr(), value.as_ptr()) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(
+            resolve("gpu", "Mesa", None, None),
+            ("gpu".to_string(), "Mesa".to_string())
+        );
+
+        let rules = RedactionRules::new().redact_key("gpu");
+        let fields = BreakpadFields::new().map("gpu", "GPU");
+        let (key, value) = resolve("gpu", "Mesa", Some(&rules), Some(&fields));
+        assert_eq!(key, "GPU");
+        assert_ne!(value, "Mesa");
+    }
+
+    #[test]
+    fn test_debug_hides_function() {
+        let annotation = LazyAnnotation {
+            key: "gpu".to_string(),
+            compute: Arc::new(|| "Mesa".to_string()),
+        };
+        assert_eq!(
+            format!("{annotation:?}"),
+            r#"LazyAnnotation { key: "gpu", .. }"#
+        );
+    }
+}
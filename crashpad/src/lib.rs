@@ -2,26 +2,235 @@
 //!
 //! This crate provides a safe, idiomatic Rust interface to the Crashpad crash reporting library.
 
+pub mod annotation_audit;
+#[cfg(feature = "serde")]
+mod artifacts;
+#[cfg(feature = "tokio")]
+mod async_api;
+mod backend;
+pub mod breadcrumbs;
+mod breakpad;
+#[cfg(feature = "bundle")]
+mod bundle;
+mod burst;
+mod claim;
 mod client;
+mod clock;
+mod coexistence;
 mod config;
+mod container;
+pub mod crash_loop;
+mod database;
+mod diagnostics;
+mod dropped;
+mod expiry;
+#[cfg(feature = "serde")]
+pub mod export;
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+mod extract;
+#[cfg(feature = "failpoints")]
+pub mod failpoints;
+#[cfg(not(feature = "failpoints"))]
+mod failpoints;
+mod file_name;
+#[cfg(target_os = "linux")]
+mod fork_server;
+mod fs;
+mod global;
+mod handler;
+mod handler_log;
+mod history;
+mod janitor;
+mod large_annotation;
+mod lazy_annotation;
+pub mod limits;
+#[cfg(feature = "handler-linked")]
+mod linked_handler;
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub mod logging;
+#[cfg(feature = "machine-id")]
+mod machine_id;
+mod macros;
+pub mod metrics;
+pub mod minidump;
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+pub mod multiprocess;
+mod prune;
+#[cfg(feature = "upload")]
+mod quota;
+pub mod realtime;
+mod redaction;
+mod reporting;
+#[cfg(feature = "upload")]
+mod retry;
+mod settings;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod signals;
+mod stack;
+mod startup;
+#[cfg(feature = "symbolicate")]
+pub mod symbolicate;
+mod taxonomy;
+#[cfg(feature = "qa")]
+pub mod testing;
+mod thread_key;
+mod timestamp;
+#[cfg(feature = "upload")]
+pub mod upload;
+mod upload_monitor;
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+mod watchdog;
 
+#[doc(hidden)]
+pub use macros::__private;
+
+#[cfg(feature = "serde")]
+pub use artifacts::{ArtifactExport, ArtifactExporter};
+#[cfg(feature = "tokio")]
+pub use async_api::UploadEvents;
+#[cfg(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))]
+pub use backend::UploadBehavior;
+pub use breakpad::{BreakpadFields, MINIDUMP_FIELD};
+#[cfg(feature = "bundle")]
+pub use bundle::ImportedReport;
+pub use burst::{last_burst, BurstPolicy, BurstSummary};
+#[cfg(feature = "serde")]
+pub use claim::ReportClaim;
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+pub use client::current_thread_id;
 pub use client::CrashpadClient;
+pub use coexistence::{
+    Coexistence, ExceptionFilterMode, MachExceptionMask, SignalChaining, CLR_COM_EXCEPTION_CODE,
+    CLR_EXCEPTION_CODE, CLR_EXCEPTION_CODES,
+};
 pub use config::{CrashpadConfig, CrashpadConfigBuilder};
+pub use container::container_annotations;
+pub use database::{CrashReport, CrashReportDatabase, DatabaseStatistics, UploadSummary};
+pub use diagnostics::{startup_diagnostics, Diagnostic, Severity};
+pub use dropped::{DropReason, DroppedEvent};
+pub use expiry::ExpiryPolicy;
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+pub use extract::{extract_handler, ExtractedHandler};
+#[cfg(target_os = "linux")]
+pub use fork_server::start_fork_server;
+pub use global::{global_client, init, CrashpadGuard};
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+pub use handler::{handler_version, verify_handler};
+pub use handler::{
+    AndroidHandlerLaunch, HandlerCandidate, HandlerInfo, HandlerLifetime, HandlerResolution,
+    HandlerSource, HandlerStatus, HandlerVerification, HandlerVersion,
+};
+pub use handler_log::HandlerLog;
+pub use history::{ValueChange, ValueHistory};
+pub use janitor::{clean_database, last_cleanup, CleanupReport};
+pub use large_annotation::LargeAnnotation;
+#[cfg(feature = "handler-linked")]
+pub use linked_handler::{
+    add_user_stream, run_linked_handler_if_requested, CrashedProcess, LINKED_HANDLER_ARGUMENT,
+};
+pub use prune::{DatabasePruner, PruneCondition};
+pub use redaction::{RedactionRules, REDACTED};
+pub use reporting::{CrashReporting, RecordingReporter};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use signals::{
+    handler_conflicts, installed_signal_handlers, ConflictKind, HandlerConflict, SignalHandlerInfo,
+    CRASH_SIGNALS,
+};
+pub use stack::{annotate_thread_stack, stack_annotations};
+pub use startup::{last_startup, DeferredTask, StartupTiming};
+use std::path::PathBuf;
+pub use taxonomy::{set_crash_category_hint, CrashCategory};
 use thiserror::Error;
+pub use thread_key::{ThreadCrashKey, ThreadCrashKeyGuard};
+pub use timestamp::{timezone_annotations, Timestamp};
+pub use upload_monitor::{UploadEvent, UploadMonitor};
+#[cfg(not(any(target_os = "ios", target_os = "tvos", target_os = "watchos")))]
+pub use watchdog::HandlerWatchdog;
 
 #[derive(Error, Debug)]
 pub enum CrashpadError {
     #[error("Failed to initialize Crashpad")]
     InitializationFailed,
 
-    #[error("Failed to start handler")]
-    HandlerStartFailed,
+    /// The handler executable doesn't exist
+    #[error("Handler not found at {}{}", .path.display(), handler::trace_suffix(.trace))]
+    HandlerNotFound {
+        path: PathBuf,
+        /// How the handler path was found, see
+        /// [`CrashpadConfig::resolve_handler_with_trace`]; empty if it was
+        /// given directly
+        trace: Vec<HandlerCandidate>,
+    },
+
+    /// The handler process could not be started, or exited before the
+    /// client connected to it
+    #[error(
+        "Failed to spawn handler {}{}{}",
+        .path.display(),
+        os_error_suffix(.os_error),
+        handler::trace_suffix(.trace)
+    )]
+    HandlerSpawnFailed {
+        path: PathBuf,
+        /// `errno`, or `GetLastError()` on Windows, if known
+        os_error: Option<i32>,
+        /// See [`HandlerNotFound`](Self::HandlerNotFound)
+        trace: Vec<HandlerCandidate>,
+    },
+
+    /// Registering with an already running handler failed
+    #[error("Failed to register with handler {endpoint}{}", os_error_suffix(.os_error))]
+    IpcRegistrationFailed {
+        /// Pipe or Mach service name of the handler
+        endpoint: String,
+        os_error: Option<i32>,
+    },
+
+    #[error("Handler integrity check failed for {path}: {reason}")]
+    HandlerIntegrityError { path: PathBuf, reason: String },
+
+    #[error("Failed to query handler version: {0}")]
+    HandlerVersion(String),
+
+    #[error("Signal {signal} is already handled by {handler}")]
+    SignalHandlerConflict { signal: i32, handler: String },
 
     #[error("Invalid configuration: {0}")]
     InvalidConfiguration(String),
 
+    #[error("Crash report not found: {0}")]
+    ReportNotFound(String),
+
+    #[error("Crash report database error: {0}")]
+    DatabaseError(String),
+
+    /// [`init`] was called while the guard of a previous call is alive
+    #[error("Crashpad is already initialized")]
+    AlreadyInitialized,
+
+    /// A report couldn't be sent with `upload::send_report`
+    #[error("Upload failed{}: {message}", status_suffix(.status))]
+    UploadFailed {
+        /// HTTP status, if the server answered
+        status: Option<u16>,
+        /// Response body, or why the server couldn't be reached
+        message: String,
+    },
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 }
 
 pub type Result<T> = std::result::Result<T, CrashpadError>;
+
+/// ` with status <status>` of an HTTP status, empty if unknown.
+fn status_suffix(status: &Option<u16>) -> String {
+    status.map_or_else(String::new, |status| format!(" with status {status}"))
+}
+
+/// `: <description>` of an OS error code, empty if unknown.
+fn os_error_suffix(os_error: &Option<i32>) -> String {
+    os_error.map_or_else(String::new, |code| {
+        format!(": {}", std::io::Error::from_raw_os_error(code))
+    })
+}
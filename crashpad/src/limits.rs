@@ -0,0 +1,118 @@
+//! Limits of Crashpad's annotation storage.
+//!
+//! Crashpad keeps the simple annotations of a module in a fixed-size
+//! dictionary (`SimpleStringDictionary`): keys and values beyond these
+//! lengths are truncated, entries beyond the capacity are dropped. Longer
+//! values fit in a [`LargeAnnotation`](crate::LargeAnnotation). Extra
+//! memory ranges are kept in a fixed-size bag as well. The
+//! values come from the vendored Crashpad headers; building the FFI wrapper
+//! fails if they no longer match.
+//!
+//! Applications with a fixed annotation schema can check it at compile time
+//! with [`assert_annotation_limits!`](crate::assert_annotation_limits).
+
+use crashpad_rs_sys::{
+    CRASHPAD_ANNOTATION_VALUE_MAX_SIZE, CRASHPAD_EXTRA_MEMORY_RANGES,
+    CRASHPAD_SIMPLE_DICTIONARY_ENTRIES, CRASHPAD_SIMPLE_DICTIONARY_KEY_SIZE,
+    CRASHPAD_SIMPLE_DICTIONARY_VALUE_SIZE,
+};
+
+/// Maximum number of simple annotations per module
+pub const MAX_ANNOTATIONS: usize = CRASHPAD_SIMPLE_DICTIONARY_ENTRIES as usize;
+
+/// Maximum length of an annotation key in bytes
+pub const MAX_KEY_LEN: usize = CRASHPAD_SIMPLE_DICTIONARY_KEY_SIZE as usize - 1;
+
+/// Maximum length of an annotation value in bytes
+pub const MAX_VALUE_LEN: usize = CRASHPAD_SIMPLE_DICTIONARY_VALUE_SIZE as usize - 1;
+
+/// Maximum length of a [`LargeAnnotation`](crate::LargeAnnotation) value
+/// in bytes
+pub const MAX_LARGE_VALUE_LEN: usize = CRASHPAD_ANNOTATION_VALUE_MAX_SIZE as usize - 1;
+
+/// Maximum number of memory ranges added with
+/// [`CrashpadClient::add_memory_range`](crate::CrashpadClient::add_memory_range)
+pub const MAX_MEMORY_RANGES: usize = CRASHPAD_EXTRA_MEMORY_RANGES as usize;
+
+/// Check an annotation schema against the [limits](crate::limits) at
+/// compile time.
+///
+/// Takes the keys with the maximum length of their values. Fails to compile
+/// if there are more than [`MAX_ANNOTATIONS`] keys, or a key or value length
+/// is over [`MAX_KEY_LEN`] or [`MAX_VALUE_LEN`]. Keys can be any constant
+/// `&str` expression.
+///
+/// # Example
+/// ```rust
+/// const SESSION_ID: &str = "session_id";
+///
+/// crashpad_rs::assert_annotation_limits! {
+///     "product" => 32,
+///     "version" => 16,
+///     SESSION_ID => 36,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// crashpad_rs::assert_annotation_limits! {
+///     "stack_summary" => 4096,
+/// }
+/// ```
+#[macro_export]
+macro_rules! assert_annotation_limits {
+    ($($key:expr => $max_value_len:expr),* $(,)?) => {
+        #[allow(unused_comparisons)]
+        const _: () = {
+            let keys: &[&str] = &[$($key),*];
+            assert!(
+                keys.len() <= $crate::limits::MAX_ANNOTATIONS,
+                "more annotations than crashpad_rs::limits::MAX_ANNOTATIONS"
+            );
+            $(
+                assert!(
+                    $key.len() <= $crate::limits::MAX_KEY_LEN,
+                    concat!(
+                        "annotation key ",
+                        stringify!($key),
+                        " is longer than crashpad_rs::limits::MAX_KEY_LEN"
+                    )
+                );
+                assert!(
+                    $max_value_len <= $crate::limits::MAX_VALUE_LEN,
+                    concat!(
+                        "value of annotation ",
+                        stringify!($key),
+                        " can be longer than crashpad_rs::limits::MAX_VALUE_LEN"
+                    )
+                );
+            )*
+        };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits() {
+        assert_eq!(MAX_ANNOTATIONS, 64);
+        assert_eq!(MAX_KEY_LEN, 255);
+        assert_eq!(MAX_VALUE_LEN, 255);
+        assert_eq!(MAX_MEMORY_RANGES, 64);
+    }
+
+    // Exactly at the limits
+    const LONGEST_KEY: &str = {
+        const KEY: [u8; MAX_KEY_LEN] = [b'k'; MAX_KEY_LEN];
+        match std::str::from_utf8(&KEY) {
+            Ok(key) => key,
+            Err(_) => panic!(),
+        }
+    };
+    crate::assert_annotation_limits! {
+        LONGEST_KEY => MAX_VALUE_LEN,
+        "product" => 0,
+    }
+    crate::assert_annotation_limits! {}
+}
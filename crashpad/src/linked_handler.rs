@@ -0,0 +1,260 @@
+//! Running the handler from the application's own executable.
+//!
+//! With the `handler-linked` feature, crashpad_handler's code is linked into
+//! the application. Crashpad then starts the application's executable as the
+//! handler, with [`LINKED_HANDLER_ARGUMENT`] in front of the handler
+//! arguments, and [`run_linked_handler_if_requested`] turns that process into
+//! the handler. Apps that can't ship a separate executable still get crash
+//! capture this way, with some trade-offs:
+//!
+//! - the handler process maps the whole application binary, so it uses more
+//!   memory than crashpad_handler
+//! - static initializers of the application and its libraries run in the
+//!   handler process too, as does anything in `main` before
+//!   [`run_linked_handler_if_requested`]
+//! - the handler version annotation isn't available, as the executable
+//!   doesn't answer `--version` the way crashpad_handler does
+//!
+//! In return, the handler runs the application's code: [`add_user_stream`]
+//! adds streams of the application's own format to every dump.
+//!
+//! Not available on Android, which can launch the handler from a library
+//! with `AndroidHandlerLaunch::Linker`, or on iOS/tvOS/watchOS, which use an
+//! in-process handler.
+
+use std::env;
+use std::ffi::{c_void, CString, OsString};
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+
+use crashpad_rs_sys::{
+    crashpad_handler_add_user_stream, crashpad_handler_main, crashpad_process_snapshot_annotation,
+    crashpad_process_snapshot_pid, crashpad_process_snapshot_read_memory,
+    crashpad_process_snapshot_t, crashpad_stream_buffer_append, crashpad_stream_buffer_t,
+};
+
+use crate::{CrashpadError, Result};
+
+/// Argument that tells [`run_linked_handler_if_requested`] to run the handler.
+pub const LINKED_HANDLER_ARGUMENT: &str = "--crashpad-rs-linked-handler";
+
+/// Run the handler and exit if this process was started as the handler.
+///
+/// Call this first thing in `main` of an application configured with
+/// [`CrashpadConfigBuilder::linked_handler`](crate::CrashpadConfigBuilder::linked_handler).
+/// Returns immediately in the application process; in the handler process,
+/// it runs the handler until it shuts down and exits with its exit code.
+///
+/// # Example
+/// ```rust,no_run
+/// // At the start of main
+/// crashpad_rs::run_linked_handler_if_requested();
+///
+/// let config = crashpad_rs::CrashpadConfig::builder()
+///     .linked_handler()
+///     .database_path("./crashes")
+///     .build();
+/// ```
+pub fn run_linked_handler_if_requested() {
+    let args: Vec<OsString> = env::args_os().collect();
+    if !args
+        .iter()
+        .skip(1)
+        .any(|arg| arg == LINKED_HANDLER_ARGUMENT)
+    {
+        return;
+    }
+
+    let args = handler_args(args);
+    let mut argv: Vec<*mut c_char> = args.iter().map(|arg| arg.as_ptr() as *mut _).collect();
+    argv.push(std::ptr::null_mut());
+
+    let code = unsafe { crashpad_handler_main(args.len() as i32, argv.as_mut_ptr()) };
+    std::process::exit(code);
+}
+
+/// Stream types up to this one are reserved for the minidump format
+const LAST_RESERVED_STREAM: u32 = 0xffff;
+
+/// The process a dump is being written of, as seen from the handler.
+///
+/// Passed to the producers of [`add_user_stream`]. The handler is a
+/// different process, so the application's data is read from the crashed
+/// process's memory, e.g. at an address it published in an annotation.
+pub struct CrashedProcess<'a> {
+    snapshot: crashpad_process_snapshot_t,
+    _snapshot: PhantomData<&'a ()>,
+}
+
+impl CrashedProcess<'_> {
+    /// Process ID of the crashed process
+    pub fn pid(&self) -> u64 {
+        unsafe { crashpad_process_snapshot_pid(self.snapshot) }
+    }
+
+    /// Fill `buffer` with the memory at `address` in the crashed process
+    ///
+    /// Returns `false` if any of it can't be read.
+    pub fn read_memory(&self, address: u64, buffer: &mut [u8]) -> bool {
+        unsafe {
+            crashpad_process_snapshot_read_memory(
+                self.snapshot,
+                address,
+                buffer.len(),
+                buffer.as_mut_ptr().cast(),
+            )
+        }
+    }
+
+    /// Value of the annotation `key` of the crashed process
+    ///
+    /// Looks at the process annotations given to the handler, then the
+    /// module annotations, such as
+    /// [lazy annotations](crate::CrashpadConfigBuilder::annotation_lazy).
+    pub fn annotation(&self, key: &str) -> Option<String> {
+        let key = CString::new(key).ok()?;
+        let mut len = 0;
+        let found = unsafe {
+            crashpad_process_snapshot_annotation(
+                self.snapshot,
+                key.as_ptr(),
+                std::ptr::null_mut(),
+                0,
+                &mut len,
+            )
+        };
+        if !found {
+            return None;
+        }
+
+        let mut buffer = vec![0u8; len + 1];
+        unsafe {
+            crashpad_process_snapshot_annotation(
+                self.snapshot,
+                key.as_ptr(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                &mut len,
+            )
+        };
+        buffer.truncate(len);
+        Some(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+/// Add a stream of type `stream_type` to every dump the linked handler
+/// writes.
+///
+/// `produce` runs in the handler process for each dump and returns the
+/// contents of the stream, or `None` to leave it out of that dump. Register
+/// streams before [`run_linked_handler_if_requested`], in every process:
+/// only the handler process uses them. Read them back with
+/// [`Minidump::user_stream`](crate::minidump::Minidump::user_stream).
+///
+/// # Errors
+///
+/// [`CrashpadError::InvalidConfiguration`] if `stream_type` is reserved for
+/// the minidump format (`0xffff` and below).
+///
+/// # Example
+/// ```rust,no_run
+/// use crashpad_rs::CrashedProcess;
+///
+/// // The application publishes the address of its state, see
+/// // CrashpadConfigBuilder::annotation; the handler reads it at crash time
+/// crashpad_rs::add_user_stream(0x4741_4d45, |process: &CrashedProcess| {
+///     let address = process.annotation("game_state_addr")?.parse().ok()?;
+///     let mut state = vec![0u8; 4096];
+///     process.read_memory(address, &mut state).then_some(state)
+/// })?;
+/// crashpad_rs::run_linked_handler_if_requested();
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+pub fn add_user_stream<F>(stream_type: u32, produce: F) -> Result<()>
+where
+    F: Fn(&CrashedProcess<'_>) -> Option<Vec<u8>> + Send + Sync + 'static,
+{
+    if stream_type <= LAST_RESERVED_STREAM {
+        return Err(CrashpadError::InvalidConfiguration(format!(
+            "Stream type {stream_type:#x} is reserved"
+        )));
+    }
+
+    // Used until the handler exits
+    let context = Box::into_raw(Box::new(produce));
+    unsafe {
+        crashpad_handler_add_user_stream(stream_type, Some(produce_stream::<F>), context.cast())
+    };
+    Ok(())
+}
+
+unsafe extern "C" fn produce_stream<F>(
+    context: *mut c_void,
+    process: crashpad_process_snapshot_t,
+    buffer: crashpad_stream_buffer_t,
+) -> bool
+where
+    F: Fn(&CrashedProcess<'_>) -> Option<Vec<u8>>,
+{
+    let produce = &*(context as *const F);
+    let process = CrashedProcess {
+        snapshot: process,
+        _snapshot: PhantomData,
+    };
+    // A panicking producer only loses its own stream
+    match panic::catch_unwind(AssertUnwindSafe(|| produce(&process))) {
+        Ok(Some(data)) => {
+            crashpad_stream_buffer_append(buffer, data.as_ptr().cast(), data.len());
+            true
+        }
+        _ => false,
+    }
+}
+
+/// The command line for the handler: `args` without the marker argument.
+fn handler_args(args: Vec<OsString>) -> Vec<CString> {
+    args.into_iter()
+        .enumerate()
+        .filter(|(i, arg)| *i == 0 || arg != LINKED_HANDLER_ARGUMENT)
+        .filter_map(|(_, arg)| CString::new(os_string_bytes(arg)).ok())
+        .collect()
+}
+
+#[cfg(unix)]
+fn os_string_bytes(arg: OsString) -> Vec<u8> {
+    use std::os::unix::ffi::OsStringExt;
+    arg.into_vec()
+}
+
+/// The handler expects UTF-8 arguments on Windows.
+#[cfg(windows)]
+fn os_string_bytes(arg: OsString) -> Vec<u8> {
+    arg.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handler_args() {
+        let args = handler_args(
+            ["/app/myapp", LINKED_HANDLER_ARGUMENT, "--database=/db"]
+                .map(OsString::from)
+                .to_vec(),
+        );
+        assert_eq!(
+            args,
+            [
+                CString::new("/app/myapp").unwrap(),
+                CString::new("--database=/db").unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reserved_stream_type() {
+        assert!(add_user_stream(0x7, |_: &CrashedProcess| None).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! Log records and tracing events as breadcrumbs.
+//!
+//! With the `log` feature, [`BreadcrumbLogger`] is a [`log::Log`] that
+//! records each record as a [`breadcrumb!`](crate::breadcrumb), optionally
+//! passing it on to the application's own logger. With the `tracing`
+//! feature, [`BreadcrumbLayer`] does the same for the events of a
+//! `tracing_subscriber` registry. Either way, existing instrumentation
+//! shows up in crash reports without new calls.
+//!
+//! Errors are also set as the `last_error_log` annotation, so the last one
+//! is visible in the report list without opening the dump. Both pass the
+//! [redaction rules](crate::CrashpadConfigBuilder::redaction) of the
+//! started client, like other breadcrumbs and annotations, so tokens and
+//! addresses in log lines don't end up in reports.
+//!
+//! ```rust,ignore
+//! // log
+//! crashpad_rs::logging::BreadcrumbLogger::new(log::LevelFilter::Info)
+//!     .forward_to(Box::new(env_logger::Logger::from_default_env()))
+//!     .install()?;
+//!
+//! // tracing
+//! use tracing_subscriber::prelude::*;
+//! tracing_subscriber::registry()
+//!     .with(crashpad_rs::logging::BreadcrumbLayer::new(tracing::Level::INFO))
+//!     .with(tracing_subscriber::fmt::layer())
+//!     .init();
+//! ```
+
+use std::fmt::Write;
+
+/// Annotation holding the last error
+const LAST_ERROR: &str = "last_error_log";
+
+/// Record a breadcrumb of `message` at `level` from `target`, as
+/// `LEVEL target: message`, redacted by the active rules
+fn record(level: &str, target: &str, message: &str, is_error: bool) {
+    let line = format!("{level} {target}: {message}");
+    crate::breadcrumbs::record(&line);
+    if is_error {
+        crate::redaction::with_active(|rules| {
+            crate::lazy_annotation::set(LAST_ERROR, &line, rules, None);
+        });
+    }
+}
+
+#[cfg(feature = "log")]
+pub use self::log_adapter::BreadcrumbLogger;
+
+#[cfg(feature = "log")]
+mod log_adapter {
+    use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+    /// A [`Log`] recording breadcrumbs, see the [module docs](super).
+    pub struct BreadcrumbLogger {
+        level: LevelFilter,
+        forward: Option<Box<dyn Log>>,
+    }
+
+    impl BreadcrumbLogger {
+        /// Record records of `level` and more severe
+        pub fn new(level: LevelFilter) -> Self {
+            Self {
+                level,
+                forward: None,
+            }
+        }
+
+        /// Pass every record on to `logger` as well, whatever its level
+        pub fn forward_to(mut self, logger: Box<dyn Log>) -> Self {
+            self.forward = Some(logger);
+            self
+        }
+
+        /// Install as the logger of the `log` crate
+        ///
+        /// Raises the maximum level so records both this logger and the
+        /// forwarded one want get through.
+        ///
+        /// # Errors
+        ///
+        /// [`SetLoggerError`] if a logger was installed already.
+        pub fn install(self) -> Result<(), SetLoggerError> {
+            let max = match &self.forward {
+                Some(_) => LevelFilter::Trace,
+                None => self.level,
+            };
+            log::set_boxed_logger(Box::new(self))?;
+            log::set_max_level(max);
+            Ok(())
+        }
+    }
+
+    impl Log for BreadcrumbLogger {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.level() <= self.level
+                || self
+                    .forward
+                    .as_ref()
+                    .is_some_and(|logger| logger.enabled(metadata))
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            if record.level() <= self.level {
+                super::record(
+                    record.level().as_str(),
+                    record.target(),
+                    &record.args().to_string(),
+                    record.level() == Level::Error,
+                );
+            }
+            if let Some(logger) = &self.forward {
+                logger.log(record);
+            }
+        }
+
+        fn flush(&self) {
+            if let Some(logger) = &self.forward {
+                logger.flush();
+            }
+        }
+    }
+
+    impl std::fmt::Debug for BreadcrumbLogger {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("BreadcrumbLogger")
+                .field("level", &self.level)
+                .field("forward", &self.forward.is_some())
+                .finish()
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub use self::tracing_layer::BreadcrumbLayer;
+
+#[cfg(feature = "tracing")]
+mod tracing_layer {
+    use tracing::field::{Field, Visit};
+    use tracing::{Event, Level, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer};
+
+    /// A `tracing_subscriber` [`Layer`] recording events as breadcrumbs,
+    /// see the [module docs](super).
+    #[derive(Debug, Clone, Copy)]
+    pub struct BreadcrumbLayer {
+        level: Level,
+    }
+
+    impl BreadcrumbLayer {
+        /// Record events of `level` and more severe
+        pub fn new(level: Level) -> Self {
+            Self { level }
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for BreadcrumbLayer {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let metadata = event.metadata();
+            if *metadata.level() > self.level {
+                return;
+            }
+            let mut message = EventMessage::default();
+            event.record(&mut message);
+            super::record(
+                metadata.level().as_str(),
+                metadata.target(),
+                &message.0,
+                *metadata.level() == Level::ERROR,
+            );
+        }
+    }
+
+    /// The message of an event followed by its other fields as
+    /// `name=value`.
+    #[derive(Default)]
+    struct EventMessage(String);
+
+    impl Visit for EventMessage {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            super::append_field(&mut self.0, field.name(), format_args!("{value:?}"));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            super::append_field(&mut self.0, field.name(), format_args!("{value}"));
+        }
+    }
+}
+
+/// Append a field to `line`: the `message` field as is, others as
+/// `name=value`, separated by spaces
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+fn append_field(line: &mut String, name: &str, value: std::fmt::Arguments<'_>) {
+    if !line.is_empty() {
+        line.push(' ');
+    }
+    if name != "message" {
+        let _ = write!(line, "{name}=");
+    }
+    let _ = line.write_fmt(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_field() {
+        let mut line = String::new();
+        append_field(&mut line, "message", format_args!("opened file"));
+        append_field(&mut line, "path", format_args!("{:?}", "/etc/app.toml"));
+        assert_eq!(line, r#"opened file path="/etc/app.toml""#);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_record_redacts() {
+        // Other tests activate the same rules
+        crate::redaction::set_active(Some(&crate::RedactionRules::default()));
+
+        record(
+            "WARN",
+            "app::auth",
+            "no reply from jane.doe@example.com",
+            false,
+        );
+
+        let messages = crate::breadcrumbs::tests::recorded();
+        assert!(messages
+            .iter()
+            .any(|m| m == "WARN app::auth: no reply from [REDACTED]"));
+        assert!(!messages.iter().any(|m| m.contains("jane.doe")));
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_logger_levels() {
+        use log::{Level, LevelFilter, Log, Metadata};
+
+        let logger = BreadcrumbLogger::new(LevelFilter::Warn);
+        let metadata = |level| Metadata::builder().level(level).build();
+        assert!(logger.enabled(&metadata(Level::Error)));
+        assert!(!logger.enabled(&metadata(Level::Info)));
+    }
+}
@@ -0,0 +1,233 @@
+//! A hashed machine identifier for grouping reports by device.
+//!
+//! Raw hardware identifiers are personal data, so they never leave the
+//! machine. The annotation is a SHA-256 hash of the OS's machine ID and a
+//! random per-database salt, kept next to the database settings in
+//! `machine_id.salt`: reports from one device share it, but it can't be
+//! matched to the device or to the same device in another application.
+//! Replacing the salt rotates the identifier; an empty salt file means it
+//! was cleared and no identifier is added.
+
+use std::fmt::Write;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::Path;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+
+use crate::fs::Fs;
+
+/// Annotation holding the identifier
+pub(crate) const ANNOTATION: &str = "machine_id";
+
+/// Salt file in the database directory
+const SALT_FILE: &str = "machine_id.salt";
+
+/// Length of a new salt in bytes
+const SALT_SIZE: usize = 32;
+
+/// Length of the identifier in hex digits, 128 bits
+const ID_LENGTH: usize = 32;
+
+/// The OS's identifier of this machine, `None` where none is available
+pub(crate) fn raw_machine_id() -> Option<String> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+            .iter()
+            .find_map(|path| std::fs::read_to_string(path).ok())
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let output = Command::new("/usr/sbin/ioreg")
+            .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+            .stdin(Stdio::null())
+            .output()
+            .ok()?;
+        parse_ioreg(&String::from_utf8_lossy(&output.stdout))
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let output = Command::new("reg")
+            .args([
+                "query",
+                r"HKLM\SOFTWARE\Microsoft\Cryptography",
+                "/v",
+                "MachineGuid",
+            ])
+            .stdin(Stdio::null())
+            .output()
+            .ok()?;
+        parse_reg_query(&String::from_utf8_lossy(&output.stdout))
+    }
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "windows"
+    )))]
+    {
+        None
+    }
+}
+
+/// `IOPlatformUUID` of `ioreg -rd1 -c IOPlatformExpertDevice` output
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_ioreg(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "\"IOPlatformUUID\"").then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// `MachineGuid` of `reg query` output, `    MachineGuid    REG_SZ    <guid>`
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn parse_reg_query(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        if fields.next()? != "MachineGuid" {
+            return None;
+        }
+        fields.nth(1).map(str::to_string)
+    })
+}
+
+/// Identifier of the machine with ID `raw` for the salt
+fn derive(salt: &[u8], raw: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(raw.as_bytes());
+    let mut id = String::with_capacity(ID_LENGTH);
+    for byte in &hasher.finalize()[..ID_LENGTH / 2] {
+        let _ = write!(id, "{byte:02x}");
+    }
+    id
+}
+
+/// A new random salt, from the OS-seeded keys of `RandomState`
+fn new_salt() -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    for i in 0..4u64 {
+        let mut random = std::collections::hash_map::RandomState::new().build_hasher();
+        random.write_u64(i);
+        hasher.update(random.finish().to_le_bytes());
+    }
+    if let Ok(since) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.update(since.as_nanos().to_le_bytes());
+    }
+    hasher.update(std::process::id().to_le_bytes());
+    hasher.finalize()[..SALT_SIZE].to_vec()
+}
+
+/// The salt of `database`, `None` if there is none yet
+fn read_salt(fs: &impl Fs, database: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs.read(&database.join(SALT_FILE)) {
+        Ok(salt) => Ok(Some(salt)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_salt(fs: &impl Fs, database: &Path, salt: &[u8]) -> io::Result<()> {
+    let path = database.join(SALT_FILE);
+    fs.create_dir_all(database)?;
+    let temporary = path.with_extension("salt.tmp");
+    fs.write(&temporary, salt)?;
+    fs.rename(&temporary, &path)
+}
+
+/// The identifier of the machine with ID `raw` in `database`, creating the
+/// salt on first use. `None` if the identifier was cleared.
+pub(crate) fn machine_id(fs: &impl Fs, database: &Path, raw: &str) -> io::Result<Option<String>> {
+    let salt = match read_salt(fs, database)? {
+        Some(salt) => salt,
+        None => {
+            let salt = new_salt();
+            write_salt(fs, database, &salt)?;
+            salt
+        }
+    };
+    Ok((!salt.is_empty()).then(|| derive(&salt, raw)))
+}
+
+/// The identifier of the machine with ID `raw` in `database`, without
+/// creating a salt. `None` if there is none yet or it was cleared.
+pub(crate) fn current(fs: &impl Fs, database: &Path, raw: &str) -> io::Result<Option<String>> {
+    Ok(read_salt(fs, database)?
+        .filter(|salt| !salt.is_empty())
+        .map(|salt| derive(&salt, raw)))
+}
+
+/// Replace the salt of `database`, so the identifier changes
+pub(crate) fn rotate(fs: &impl Fs, database: &Path) -> io::Result<()> {
+    write_salt(fs, database, &new_salt())
+}
+
+/// Forget the salt of `database`, so no identifier is added until it is
+/// rotated
+pub(crate) fn clear(fs: &impl Fs, database: &Path) -> io::Result<()> {
+    write_salt(fs, database, &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::memory::MemoryFs;
+
+    const RAW: &str = "4c4c4544-0042-3510-8052-b4c04f4e3732";
+
+    #[test]
+    fn test_machine_id() {
+        let fs = MemoryFs::default();
+        let database = Path::new("db");
+
+        assert_eq!(current(&fs, database, RAW).unwrap(), None);
+        let id = machine_id(&fs, database, RAW).unwrap().unwrap();
+        assert_eq!(id.len(), ID_LENGTH);
+        assert!(!id.contains(RAW));
+        // Stable until rotated
+        assert_eq!(machine_id(&fs, database, RAW).unwrap(), Some(id.clone()));
+        assert_eq!(current(&fs, database, RAW).unwrap(), Some(id.clone()));
+
+        rotate(&fs, database).unwrap();
+        let rotated = machine_id(&fs, database, RAW).unwrap().unwrap();
+        assert_ne!(rotated, id);
+
+        // Not recreated once cleared
+        clear(&fs, database).unwrap();
+        assert_eq!(machine_id(&fs, database, RAW).unwrap(), None);
+        assert_eq!(current(&fs, database, RAW).unwrap(), None);
+    }
+
+    #[test]
+    fn test_salted() {
+        assert_eq!(derive(b"salt", RAW), derive(b"salt", RAW));
+        assert_ne!(derive(b"salt", RAW), derive(b"other", RAW));
+        assert_ne!(new_salt(), new_salt());
+    }
+
+    #[test]
+    fn test_parse_platform_ids() {
+        let ioreg = r#"+-o J316sAP  <class IOPlatformExpertDevice>
+    {
+      "IOPlatformSerialNumber" = "C02XXXXXXXXX"
+      "IOPlatformUUID" = "4C4C4544-0042-3510-8052-B4C04F4E3732"
+    }"#;
+        assert_eq!(
+            parse_ioreg(ioreg).as_deref(),
+            Some("4C4C4544-0042-3510-8052-B4C04F4E3732")
+        );
+
+        let reg = "\r\nHKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Cryptography\r\n    MachineGuid    REG_SZ    1b2c3d4e-0000-4000-8000-123456789abc\r\n";
+        assert_eq!(
+            parse_reg_query(reg).as_deref(),
+            Some("1b2c3d4e-0000-4000-8000-123456789abc")
+        );
+        assert_eq!(parse_reg_query("ERROR: not found"), None);
+    }
+}
@@ -0,0 +1,130 @@
+/// Initialize Crashpad with the recommended setup in a single line.
+///
+/// Expands to a [`CrashpadConfig`](crate::CrashpadConfig) that uses the handler
+/// copied by `crashpad-handler-bundler` (the compile-time `CRASHPAD_HANDLER_PATH`
+/// env of the calling crate) and starts the handler with `product` and `version`
+/// annotations taken from the calling crate's `CARGO_PKG_NAME` and
+/// `CARGO_PKG_VERSION`.
+///
+/// The [`vcs_annotations!`](crate::vcs_annotations) of the calling crate are
+/// added as well, if its build script records them.
+///
+/// If the bundled handler path is not set or no longer exists at runtime, the
+/// regular handler search is used instead (see `CrashpadConfig`).
+///
+/// Evaluates to `Result<CrashpadClient>`. Keep the returned client alive for as
+/// long as crashes should be captured.
+///
+/// # Example
+/// ```no_run
+/// fn main() -> crashpad_rs::Result<()> {
+///     let _crashpad = crashpad_rs::init!()?;
+///
+///     // Your application code here
+///     Ok(())
+/// }
+/// ```
+///
+/// A builder can be passed to override the defaults. An explicit handler path
+/// in the builder takes precedence over the bundled one.
+///
+/// ```no_run
+/// # fn main() -> crashpad_rs::Result<()> {
+/// let _crashpad = crashpad_rs::init!(crashpad_rs::CrashpadConfig::builder()
+///     .database_path("./crashes")
+///     .url("https://crashes.example.com/submit"))?;
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! init {
+    () => {
+        $crate::init!($crate::CrashpadConfig::builder())
+    };
+    ($builder:expr) => {{
+        let mut annotations = $crate::vcs_annotations!();
+        annotations.insert(
+            ::std::string::String::from("product"),
+            ::std::string::String::from(env!("CARGO_PKG_NAME")),
+        );
+        annotations.insert(
+            ::std::string::String::from("version"),
+            ::std::string::String::from(env!("CARGO_PKG_VERSION")),
+        );
+        $crate::__private::init_with_bundled_handler(
+            $builder,
+            option_env!("CRASHPAD_HANDLER_PATH"),
+            &annotations,
+        )
+    }};
+}
+
+/// Git metadata of the calling crate as annotations.
+///
+/// Evaluates to a `HashMap<String, String>` with `git_commit`, `git_branch`
+/// and `git_dirty`, taken from the environment variables exported at compile
+/// time by `crashpad_handler_bundler::emit_vcs_metadata()` in the calling
+/// crate's build script. Entries whose variable isn't set are left out, so
+/// the map is empty without the build script call.
+///
+/// [`init!`](crate::init) includes these annotations; use this macro to add
+/// them when starting the handler yourself.
+///
+/// # Example
+/// ```no_run
+/// // build.rs:
+/// // fn main() {
+/// //     crashpad_handler_bundler::bundle().expect("Failed to bundle handler");
+/// //     crashpad_handler_bundler::emit_vcs_metadata();
+/// // }
+///
+/// # fn main() -> crashpad_rs::Result<()> {
+/// let mut annotations = crashpad_rs::vcs_annotations!();
+/// annotations.insert("product".to_string(), "MyApp".to_string());
+///
+/// let client = crashpad_rs::CrashpadClient::new()?;
+/// client.start_with_config(&crashpad_rs::CrashpadConfig::default(), &annotations)?;
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! vcs_annotations {
+    () => {{
+        let mut annotations = ::std::collections::HashMap::new();
+        for (key, value) in [
+            ("git_commit", option_env!("CRASHPAD_GIT_COMMIT")),
+            ("git_branch", option_env!("CRASHPAD_GIT_BRANCH")),
+            ("git_dirty", option_env!("CRASHPAD_GIT_DIRTY")),
+        ] {
+            if let Some(value) = value {
+                annotations.insert(
+                    ::std::string::String::from(key),
+                    ::std::string::String::from(value),
+                );
+            }
+        }
+        annotations
+    }};
+}
+
+/// Implementation details of the exported macros. Not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    use std::collections::HashMap;
+
+    use crate::{CrashpadClient, CrashpadConfigBuilder, Result};
+
+    pub fn init_with_bundled_handler(
+        builder: CrashpadConfigBuilder,
+        bundled_handler: Option<&str>,
+        annotations: &HashMap<String, String>,
+    ) -> Result<CrashpadClient> {
+        // The calling crate's bundled handler is preferred over the one
+        // recorded when this crate was built
+        let config = builder.build().with_bundled_handler_from(bundled_handler);
+
+        let client = CrashpadClient::new()?;
+        client.start_with_config(&config, annotations)?;
+        Ok(client)
+    }
+}
@@ -0,0 +1,652 @@
+//! Reading the metrics recorded by the handler.
+//!
+//! A handler started with a
+//! [`metrics_path`](crate::CrashpadConfigBuilder::metrics_path) records its
+//! UMA histograms (`Crashpad.CrashReportPending`,
+//! `Crashpad.CrashUpload.AttemptSuccessful`, ...) in Chromium's persistent
+//! histogram files there, `CrashpadMetrics-active.pma` for the running
+//! handler and `CrashpadMetrics.pma` for the previous one. [`read`] parses
+//! them into [`Histogram`]s.
+//!
+//! Only handlers built with Chromium's `base` library record metrics, such
+//! as the ones shipped with Chromium or Electron. The standalone handler
+//! this crate builds uses mini_chromium, whose metrics are no-ops, and
+//! leaves the directory empty.
+//!
+//! ```no_run
+//! let metrics = crashpad_rs::metrics::read("./crashpad_metrics")?;
+//! if let Some(uploads) = metrics.histogram("Crashpad.CrashUpload.AttemptSuccessful") {
+//!     println!("{} failed, {} succeeded", uploads.count(0), uploads.count(1));
+//! }
+//! # Ok::<(), crashpad_rs::CrashpadError>(())
+//! ```
+//!
+//! [`DumpWriteStats`] aggregates how long the handler took to write the
+//! dumps of a database, which the handler doesn't record as a metric, so
+//! regressions in dump latency, which extend the hang users see at crash
+//! time, show up.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{CrashReport, Result};
+
+/// `kGlobalCookie` of a `PersistentMemoryAllocator`
+const GLOBAL_COOKIE: u32 = 0x4083_05dc;
+
+/// `kBlockCookieAllocated`, the cookie of a completely allocated block
+const BLOCK_COOKIE_ALLOCATED: u32 = 0xc879_9269;
+
+/// Size of a `BlockHeader`: size, cookie, type ID and next reference
+const BLOCK_HEADER_SIZE: usize = 16;
+
+/// `kReferenceQueue`, the head of the list of iterable blocks
+const QUEUE: usize = 48;
+
+/// `PersistentHistogramData::kPersistentTypeId`
+const HISTOGRAM_TYPE: u32 = 0xf164_5913;
+
+/// `SampleRecord::kPersistentTypeId`, a sample of a sparse histogram
+const SAMPLE_RECORD_TYPE: u32 = 0x8fe6_a6a0;
+
+/// Offsets in `PersistentHistogramData`
+const SAMPLES_METADATA: usize = 32;
+const LOGGED_METADATA: usize = 56;
+const NAME: usize = 80;
+
+/// Metrics read by [`read`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metrics {
+    histograms: BTreeMap<String, Histogram>,
+}
+
+impl Metrics {
+    /// All histograms, ordered by name
+    pub fn histograms(&self) -> impl Iterator<Item = &Histogram> {
+        self.histograms.values()
+    }
+
+    /// The histogram named `name`, e.g. `Crashpad.CrashReportPending`
+    pub fn histogram(&self, name: &str) -> Option<&Histogram> {
+        self.histograms.get(name)
+    }
+
+    /// No metrics were recorded
+    pub fn is_empty(&self) -> bool {
+        self.histograms.is_empty()
+    }
+
+    /// Add the samples of `histogram` to the one with the same name
+    fn add(&mut self, histogram: Histogram) {
+        match self.histograms.get_mut(&histogram.name) {
+            Some(existing) => {
+                for bucket in histogram.buckets {
+                    existing.add(bucket.min, bucket.max, bucket.count);
+                }
+            }
+            None => {
+                self.histograms.insert(histogram.name.clone(), histogram);
+            }
+        }
+    }
+}
+
+/// Kind of a [`Histogram`], which determines its buckets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HistogramKind {
+    /// Exponentially growing buckets, e.g. sizes
+    Exponential,
+    /// Equal buckets, used for enumerations
+    Linear,
+    /// `0` (false) and `1` (true)
+    Boolean,
+    /// Explicitly listed bucket ranges
+    Custom,
+    /// One bucket per recorded value, e.g. exception codes
+    Sparse,
+}
+
+/// A histogram recorded by the handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    /// Name, e.g. `Crashpad.ExceptionCaptureResult`
+    pub name: String,
+    pub kind: HistogramKind,
+    /// Buckets with samples, ordered by value
+    pub buckets: Vec<Bucket>,
+}
+
+/// Samples within a range of values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bucket {
+    /// Smallest value of the bucket
+    pub min: i32,
+    /// End of the bucket, exclusive
+    pub max: i32,
+    pub count: u64,
+}
+
+impl Histogram {
+    /// Number of samples
+    pub fn total_count(&self) -> u64 {
+        self.buckets.iter().map(|bucket| bucket.count).sum()
+    }
+
+    /// Number of samples in the bucket of `value`; for enumerations and
+    /// booleans, how often `value` was recorded
+    pub fn count(&self, value: i32) -> u64 {
+        self.buckets
+            .iter()
+            .find(|bucket| bucket.min <= value && value < bucket.max)
+            .map_or(0, |bucket| bucket.count)
+    }
+
+    fn add(&mut self, min: i32, max: i32, count: u64) {
+        if count == 0 {
+            return;
+        }
+        match self.buckets.binary_search_by_key(&min, |bucket| bucket.min) {
+            Ok(index) => self.buckets[index].count += count,
+            Err(index) => self.buckets.insert(index, Bucket { min, max, count }),
+        }
+    }
+}
+
+/// Dump write durations of a set of reports, see
+/// [`CrashReport::dump_write_duration`].
+///
+/// # Example
+///
+/// ```no_run
+/// use crashpad_rs::{metrics::DumpWriteStats, CrashReportDatabase};
+///
+/// let database = CrashReportDatabase::open("./crashpad_db")?;
+/// let stats = DumpWriteStats::from_reports(&database.completed_reports()?);
+/// if let Some(p95) = stats.p95 {
+///     println!("95% of {} dumps were written within {p95:?}", stats.count);
+/// }
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DumpWriteStats {
+    /// Reports with a known duration
+    pub count: usize,
+    /// The shortest duration, `None` without any
+    pub min: Option<Duration>,
+    pub median: Option<Duration>,
+    /// The duration 95% of the dumps took at most
+    pub p95: Option<Duration>,
+    pub max: Option<Duration>,
+}
+
+impl DumpWriteStats {
+    /// Aggregate the dump write durations of `reports`. Reports without a
+    /// known duration are left out.
+    pub fn from_reports<'a>(reports: impl IntoIterator<Item = &'a CrashReport>) -> Self {
+        Self::from_durations(
+            reports
+                .into_iter()
+                .filter_map(CrashReport::dump_write_duration)
+                .collect(),
+        )
+    }
+
+    fn from_durations(mut durations: Vec<Duration>) -> Self {
+        durations.sort();
+        // Nearest rank
+        let percentile = |percent: usize| {
+            let rank = (durations.len() * percent + 99) / 100;
+            durations.get(rank.max(1) - 1).copied()
+        };
+        Self {
+            count: durations.len(),
+            min: durations.first().copied(),
+            median: percentile(50),
+            p95: percentile(95),
+            max: durations.last().copied(),
+        }
+    }
+}
+
+/// Read the metrics at `path`: a metrics directory, whose `.pma` files are
+/// combined, or a single file.
+///
+/// A directory without metrics files gives empty [`Metrics`].
+///
+/// # Errors
+///
+/// [`CrashpadError::IoError`](crate::CrashpadError::IoError) if a file can't
+/// be read, with [`InvalidData`](io::ErrorKind::InvalidData) if it isn't a
+/// persistent histogram file.
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Metrics> {
+    let path = path.as_ref();
+    let mut metrics = Metrics::default();
+    if path.is_dir() {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            let file = entry?.path();
+            if file.extension().is_some_and(|extension| extension == "pma") {
+                files.push(file);
+            }
+        }
+        files.sort();
+        for file in files {
+            read_file(&file, &mut metrics)?;
+        }
+    } else {
+        read_file(path, &mut metrics)?;
+    }
+    Ok(metrics)
+}
+
+fn read_file(path: &Path, metrics: &mut Metrics) -> Result<()> {
+    let data = std::fs::read(path)?;
+    let histograms = parse(&data).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not a persistent histogram file", path.display()),
+        )
+    })?;
+    for histogram in histograms {
+        metrics.add(histogram);
+    }
+    Ok(())
+}
+
+/// Histograms of a `PersistentMemoryAllocator` segment, `None` if `data`
+/// isn't one
+fn parse(data: &[u8]) -> Option<Vec<Histogram>> {
+    let memory = Memory(data);
+    if memory.u32(0)? != GLOBAL_COOKIE {
+        return None;
+    }
+    // The file may be larger than the segment it holds
+    let memory = Memory(data.get(..memory.u32(4)? as usize).unwrap_or(data));
+
+    // Iterable blocks, in the order they were made iterable
+    let mut blocks = Vec::new();
+    let mut reference = memory.u32(QUEUE + 12)? as usize;
+    // A corrupt list may loop; there can't be more blocks than headers fit
+    let mut steps = data.len() / BLOCK_HEADER_SIZE;
+    while reference != QUEUE && reference != 0 && steps > 0 {
+        steps -= 1;
+        if let Some(block) = memory.block(reference) {
+            blocks.push(block);
+        }
+        // A truncated file ends the list
+        let Some(next) = memory.u32(reference + 12) else {
+            break;
+        };
+        reference = next as usize;
+    }
+
+    // Samples of sparse histograms, by the ID of their samples
+    let mut records: BTreeMap<u64, Vec<(i32, i32)>> = BTreeMap::new();
+    for block in blocks
+        .iter()
+        .filter(|block| block.type_id == SAMPLE_RECORD_TYPE)
+    {
+        let (Some(id), Some(value), Some(count)) = (
+            memory.u64(block.data),
+            memory.i32(block.data + 8),
+            memory.i32(block.data + 12),
+        ) else {
+            continue;
+        };
+        records.entry(id).or_default().push((value, count));
+    }
+
+    let histograms = blocks
+        .iter()
+        .filter(|block| block.type_id == HISTOGRAM_TYPE)
+        .filter_map(|block| memory.histogram(block, &records))
+        .collect();
+    Some(histograms)
+}
+
+/// An allocated block
+struct Block {
+    /// Offset of the data after the header
+    data: usize,
+    /// End of the block
+    end: usize,
+    type_id: u32,
+}
+
+struct Memory<'a>(&'a [u8]);
+
+impl Memory<'_> {
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.0.get(offset..offset.checked_add(4)?)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    fn i32(&self, offset: usize) -> Option<i32> {
+        self.u32(offset).map(|value| value as i32)
+    }
+
+    fn u64(&self, offset: usize) -> Option<u64> {
+        let bytes = self.0.get(offset..offset.checked_add(8)?)?;
+        Some(u64::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    fn block(&self, reference: usize) -> Option<Block> {
+        let size = self.u32(reference)? as usize;
+        let end = reference.checked_add(size)?;
+        if self.u32(reference + 4)? != BLOCK_COOKIE_ALLOCATED
+            || size < BLOCK_HEADER_SIZE
+            || end > self.0.len()
+        {
+            return None;
+        }
+        Some(Block {
+            data: reference + BLOCK_HEADER_SIZE,
+            end,
+            type_id: self.u32(reference + 8)?,
+        })
+    }
+
+    /// The `PersistentHistogramData` in `block`
+    fn histogram(
+        &self,
+        block: &Block,
+        records: &BTreeMap<u64, Vec<(i32, i32)>>,
+    ) -> Option<Histogram> {
+        let data = block.data;
+        let kind = match self.u32(data)? {
+            0 => HistogramKind::Exponential,
+            1 => HistogramKind::Linear,
+            2 => HistogramKind::Boolean,
+            3 => HistogramKind::Custom,
+            4 => HistogramKind::Sparse,
+            _ => return None,
+        };
+        let name = self.0.get(data + NAME..block.end)?;
+        let name = &name[..name.iter().position(|&b| b == 0)?];
+        let mut histogram = Histogram {
+            name: String::from_utf8_lossy(name).into_owned(),
+            kind,
+            buckets: Vec::new(),
+        };
+
+        // Unlogged and logged samples together make up all samples
+        let metadata = [data + SAMPLES_METADATA, data + LOGGED_METADATA];
+        if kind == HistogramKind::Sparse {
+            for metadata in metadata {
+                let samples = records.get(&self.u64(metadata)?).into_iter().flatten();
+                for &(value, count) in samples {
+                    histogram.add(value, value.saturating_add(1), count.max(0) as u64);
+                }
+            }
+            return Some(histogram);
+        }
+
+        let bucket_count = self.u32(data + 16)? as usize;
+        let ranges = self.block(self.u32(data + 20)? as usize)?;
+        let range = |index: usize| self.i32(ranges.data + index * 4);
+        if ranges.data + (bucket_count + 1) * 4 > ranges.end {
+            return None;
+        }
+
+        match self.u32(data + 28)? {
+            // Counts aren't allocated while there is at most one sample per
+            // set, which is kept in the metadata as a bucket index and count
+            0 => {
+                for metadata in metadata {
+                    let single_sample = self.u32(metadata + 20)?;
+                    if single_sample == 0 || single_sample == u32::MAX {
+                        continue;
+                    }
+                    let (bucket, count) = (
+                        (single_sample & 0xffff) as usize,
+                        u64::from(single_sample >> 16),
+                    );
+                    if bucket < bucket_count {
+                        histogram.add(range(bucket)?, range(bucket + 1)?, count);
+                    }
+                }
+            }
+            counts => {
+                let counts = self.block(counts as usize)?;
+                if counts.data + bucket_count * 8 > counts.end {
+                    return None;
+                }
+                for bucket in 0..bucket_count {
+                    let count = [bucket, bucket_count + bucket]
+                        .into_iter()
+                        .filter_map(|index| self.i32(counts.data + index * 4))
+                        .map(|count| count.max(0) as u64)
+                        .sum();
+                    histogram.add(range(bucket)?, range(bucket + 1)?, count);
+                }
+            }
+        }
+        Some(histogram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_write_stats() {
+        assert_eq!(
+            DumpWriteStats::from_durations(Vec::new()),
+            DumpWriteStats::default()
+        );
+
+        let millis: Vec<Duration> = (1..=20).rev().map(Duration::from_millis).collect();
+        let stats = DumpWriteStats::from_durations(millis);
+        assert_eq!(stats.count, 20);
+        assert_eq!(stats.min, Some(Duration::from_millis(1)));
+        assert_eq!(stats.median, Some(Duration::from_millis(10)));
+        assert_eq!(stats.p95, Some(Duration::from_millis(19)));
+        assert_eq!(stats.max, Some(Duration::from_millis(20)));
+    }
+
+    /// A persistent memory segment with blocks appended to its iterable queue
+    struct Segment(Vec<u8>);
+
+    impl Segment {
+        fn new() -> Self {
+            let mut data = vec![0u8; QUEUE + BLOCK_HEADER_SIZE];
+            data[0..4].copy_from_slice(&GLOBAL_COOKIE.to_le_bytes());
+            // The empty queue points to itself
+            data[QUEUE + 12..QUEUE + 16].copy_from_slice(&(QUEUE as u32).to_le_bytes());
+            Self(data)
+        }
+
+        /// Append a block with `contents`, returning its reference
+        fn block(&mut self, type_id: u32, contents: &[u8]) -> u32 {
+            let reference = self.0.len();
+            let size = (BLOCK_HEADER_SIZE + contents.len()) as u32;
+            for value in [size, BLOCK_COOKIE_ALLOCATED, type_id, 0] {
+                self.0.extend_from_slice(&value.to_le_bytes());
+            }
+            self.0.extend_from_slice(contents);
+            reference as u32
+        }
+
+        /// Append a block and make it iterable
+        fn iterable(&mut self, type_id: u32, contents: &[u8]) -> u32 {
+            let reference = self.block(type_id, contents);
+            let mut last = QUEUE;
+            loop {
+                let next = u32::from_le_bytes(self.0[last + 12..last + 16].try_into().unwrap());
+                if next as usize == QUEUE {
+                    break;
+                }
+                last = next as usize;
+            }
+            self.0[last + 12..last + 16].copy_from_slice(&reference.to_le_bytes());
+            let end = reference as usize + 12;
+            self.0[end..end + 4].copy_from_slice(&(QUEUE as u32).to_le_bytes());
+            reference
+        }
+
+        fn histogram(
+            &mut self,
+            kind: u32,
+            name: &str,
+            ranges: &[i32],
+            counts: Option<&[i32]>,
+            single_sample: u32,
+            ids: (u64, u64),
+        ) {
+            let ranges_ref = self.block(0, &words(ranges));
+            let counts_ref = counts.map_or(0, |counts| self.block(0, &words(counts)));
+            let mut data = vec![0u8; NAME];
+            let bucket_count = ranges.len().saturating_sub(1) as u32;
+            for (offset, value) in [
+                (0, kind),
+                (16, bucket_count),
+                (20, ranges_ref),
+                (28, counts_ref),
+                (SAMPLES_METADATA + 20, single_sample),
+            ] {
+                data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            }
+            data[SAMPLES_METADATA..SAMPLES_METADATA + 8].copy_from_slice(&ids.0.to_le_bytes());
+            data[LOGGED_METADATA..LOGGED_METADATA + 8].copy_from_slice(&ids.1.to_le_bytes());
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(&[0; 8]);
+            self.iterable(HISTOGRAM_TYPE, &data);
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            let size = self.0.len() as u32;
+            self.0[4..8].copy_from_slice(&size.to_le_bytes());
+            self.0
+        }
+    }
+
+    fn words(values: &[i32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    fn sample_record(id: u64, value: i32, count: i32) -> Vec<u8> {
+        let mut data = id.to_le_bytes().to_vec();
+        data.extend_from_slice(&value.to_le_bytes());
+        data.extend_from_slice(&count.to_le_bytes());
+        data
+    }
+
+    fn segment() -> Vec<u8> {
+        let mut segment = Segment::new();
+        // Enumeration with 3 values and an overflow bucket; unlogged counts
+        // first, then the logged ones
+        segment.histogram(
+            1,
+            "Crashpad.CrashReportPending",
+            &[0, 1, 2, 3, i32::MAX],
+            Some(&[2, 0, 1, 0, 1, 0, 0, 0]),
+            0,
+            (1, 2),
+        );
+        // One sample of true, kept in the metadata
+        segment.histogram(
+            2,
+            "Crashpad.CrashUpload.AttemptSuccessful",
+            &[0, 1, 2, i32::MAX],
+            None,
+            1 | (1 << 16),
+            (3, 4),
+        );
+        segment.histogram(4, "Crashpad.ExceptionCode.Win", &[], None, 0, (5, 6));
+        segment.iterable(SAMPLE_RECORD_TYPE, &sample_record(5, -1073741819, 2));
+        segment.iterable(SAMPLE_RECORD_TYPE, &sample_record(6, -1073741819, 1));
+        segment.iterable(SAMPLE_RECORD_TYPE, &sample_record(5, 3, 1));
+        // Not a histogram
+        segment.iterable(0x1234, &[0; 8]);
+        segment.finish()
+    }
+
+    #[test]
+    fn test_parse() {
+        let histograms = parse(&segment()).unwrap();
+        assert_eq!(histograms.len(), 3);
+
+        let pending = &histograms[0];
+        assert_eq!(pending.name, "Crashpad.CrashReportPending");
+        assert_eq!(pending.kind, HistogramKind::Linear);
+        assert_eq!(
+            pending.buckets,
+            vec![
+                Bucket {
+                    min: 0,
+                    max: 1,
+                    count: 3
+                },
+                Bucket {
+                    min: 2,
+                    max: 3,
+                    count: 1
+                },
+            ]
+        );
+        assert_eq!(pending.count(0), 3);
+        assert_eq!(pending.count(1), 0);
+        assert_eq!(pending.total_count(), 4);
+
+        let uploads = &histograms[1];
+        assert_eq!(uploads.kind, HistogramKind::Boolean);
+        assert_eq!((uploads.count(0), uploads.count(1)), (0, 1));
+
+        let codes = &histograms[2];
+        assert_eq!(codes.kind, HistogramKind::Sparse);
+        assert_eq!(codes.count(-1073741819), 3);
+        assert_eq!(codes.count(3), 1);
+        assert_eq!(codes.total_count(), 4);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse(b"").is_none());
+        assert!(parse(&[0u8; 64]).is_none());
+
+        // A truncated file keeps the complete histograms
+        let data = segment();
+        let histograms = parse(&data[..data.len() - 40]).unwrap();
+        assert_eq!(histograms.len(), 3);
+        let histograms = parse(&data[..QUEUE + BLOCK_HEADER_SIZE]).unwrap();
+        assert!(histograms.is_empty());
+    }
+
+    #[test]
+    fn test_read_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read(dir.path()).unwrap().is_empty());
+
+        std::fs::write(dir.path().join("CrashpadMetrics.pma"), segment()).unwrap();
+        std::fs::write(dir.path().join("CrashpadMetrics-active.pma"), segment()).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let metrics = read(dir.path()).unwrap();
+        let names: Vec<_> = metrics.histograms().map(|h| h.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "Crashpad.CrashReportPending",
+                "Crashpad.CrashUpload.AttemptSuccessful",
+                "Crashpad.ExceptionCode.Win"
+            ]
+        );
+        let pending = metrics.histogram("Crashpad.CrashReportPending").unwrap();
+        assert_eq!(pending.count(0), 6);
+
+        let file = dir.path().join("CrashpadMetrics.pma");
+        assert_eq!(read(&file).unwrap().histograms().count(), 3);
+
+        std::fs::write(&file, "not metrics").unwrap();
+        assert!(matches!(
+            read(dir.path()),
+            Err(crate::CrashpadError::IoError(e)) if e.kind() == io::ErrorKind::InvalidData
+        ));
+    }
+}
@@ -0,0 +1,1445 @@
+//! Reading minidump files.
+//!
+//! [`Minidump`] reads what triage tools and tests usually need from a dump
+//! written by Crashpad: the exception, the threads, the loaded modules and
+//! the annotations. Memory, thread contexts and the other streams are not
+//! exposed; with the `symbolicate` feature, the `symbolicate` module
+//! uses them to unwind stacks.
+//!
+//! ```no_run
+//! use crashpad_rs::minidump::Minidump;
+//! use crashpad_rs::CrashReportDatabase;
+//!
+//! let database = CrashReportDatabase::open("./crashpad_db")?;
+//! for report in database.completed_reports()? {
+//!     let dump = Minidump::open(&report.file_path)?;
+//!     if let Some(exception) = dump.exception()? {
+//!         println!("{}: exception {:#x} at {:#x}", report.uuid, exception.code, exception.address);
+//!     }
+//!     for module in dump.modules()? {
+//!         println!("  {:#x} {}", module.base_address, module.name);
+//!     }
+//! }
+//! # Ok::<(), crashpad_rs::CrashpadError>(())
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::Path;
+
+use crate::annotation_audit::{self, AnnotationChange};
+use crate::breadcrumbs::{self, Breadcrumb};
+use crate::history::{self, ValueChange};
+use crate::stack;
+use crate::taxonomy::{self, CrashCategory};
+use crate::thread_key;
+use crate::Result;
+
+/// `MDMP`, little-endian
+const SIGNATURE: u32 = 0x504d_444d;
+
+/// `MINIDUMP_VERSION`, the low half of the header's version field
+const VERSION: u32 = 0xa793;
+
+/// `kMinidumpStreamTypeCrashpadInfo`
+const CRASHPAD_INFO_STREAM: u32 = 0x4350_0001;
+
+/// `ThreadListStream`
+const THREAD_LIST_STREAM: u32 = 3;
+
+/// `ModuleListStream`
+const MODULE_LIST_STREAM: u32 = 4;
+
+/// `MemoryListStream`
+const MEMORY_LIST_STREAM: u32 = 5;
+
+/// `ExceptionStream`
+const EXCEPTION_STREAM: u32 = 6;
+
+/// `SystemInfoStream`
+#[cfg(feature = "symbolicate")]
+const SYSTEM_INFO_STREAM: u32 = 7;
+
+/// `ThreadNamesStream`
+const THREAD_NAMES_STREAM: u32 = 24;
+
+/// `EXCEPTION_STACK_OVERFLOW` (Windows)
+const EXCEPTION_STACK_OVERFLOW: u32 = 0xc000_00fd;
+
+/// `STATUS_ACCESS_VIOLATION` (Windows)
+const ACCESS_VIOLATION_WINDOWS: u32 = 0xc000_0005;
+
+/// Exception codes of invalid memory accesses: `SIGSEGV` and `SIGBUS`
+/// (Linux/Android) and `EXC_BAD_ACCESS` (macOS/iOS). On Windows, the
+/// exception address is that of the instruction, not the memory.
+const ACCESS_VIOLATION_CODES: [u32; 3] = [11, 7, 1];
+
+/// How far below the stack pointer an overflowing access may land, e.g. a
+/// large frame being set up
+const STACK_POINTER_SLACK: u64 = 64 * 1024;
+
+/// CodeView record signatures: `RSDS` (PDB 7.0) and `BpEL` (ELF build ID)
+const CODEVIEW_PDB70: u32 = 0x5344_5352;
+const CODEVIEW_BUILD_ID: u32 = 0x4270_454c;
+
+/// Size of a `MINIDUMP_THREAD`
+const THREAD_SIZE: usize = 48;
+
+/// Size of a `MINIDUMP_THREAD_NAME`, which is packed
+const THREAD_NAME_SIZE: usize = 12;
+
+/// Size of a `MINIDUMP_MEMORY_DESCRIPTOR`
+const MEMORY_DESCRIPTOR_SIZE: usize = 16;
+
+/// Size of a `MINIDUMP_MODULE`
+const MODULE_SIZE: usize = 108;
+
+/// Size of a `MinidumpCrashpadInfo`
+const CRASHPAD_INFO_SIZE: usize = 52;
+
+/// Size of a `MINIDUMP_DIRECTORY` entry
+const DIRECTORY_ENTRY_SIZE: usize = 12;
+
+/// Size of a `MinidumpModuleCrashpadInfoLink`
+const MODULE_LINK_SIZE: usize = 12;
+
+/// Size of a `MinidumpAnnotation`
+const ANNOTATION_SIZE: usize = 12;
+
+/// `Annotation::Type::kString`
+const ANNOTATION_STRING: u16 = 1;
+
+/// `Annotation::Type::kUserDefinedStart`
+const ANNOTATION_USER_DEFINED: u16 = 0x8000;
+
+/// A minidump file, read into memory.
+#[derive(Debug, Clone)]
+pub struct Minidump {
+    data: Vec<u8>,
+}
+
+/// The exception that caused the dump.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exception {
+    /// Thread the exception happened on
+    pub thread_id: u32,
+    /// Exception code: the signal number on Linux/Android, the Mach
+    /// exception type on macOS/iOS, the `EXCEPTION_*`/NTSTATUS code on
+    /// Windows
+    pub code: u32,
+    pub flags: u32,
+    /// Address of the instruction (or, for memory access faults on some
+    /// platforms, the memory) that faulted
+    pub address: u64,
+}
+
+/// A thread of the dumped process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thread {
+    pub id: u32,
+    /// Name of the thread, if the platform records one
+    pub name: Option<String>,
+    /// Lowest address of the captured stack memory
+    pub stack_start: u64,
+    /// Size of the captured stack memory in bytes
+    pub stack_size: u64,
+}
+
+/// A module (executable or shared library) loaded in the dumped process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    /// Path of the module file
+    pub name: String,
+    pub base_address: u64,
+    pub size: u64,
+    /// Name of the debug file in Breakpad symbol stores, e.g. `app.pdb` on
+    /// Windows and the module's file name elsewhere
+    pub debug_file: Option<String>,
+    /// Breakpad debug ID, identifying the build of the module
+    pub debug_id: Option<String>,
+}
+
+impl Module {
+    /// Whether `address` is inside the module
+    pub fn contains(&self, address: u64) -> bool {
+        (self.base_address..self.base_address.saturating_add(self.size)).contains(&address)
+    }
+
+    /// File name of the module, without directories
+    pub fn file_name(&self) -> &str {
+        self.name.rsplit(['/', '\\']).next().unwrap_or_default()
+    }
+}
+
+impl Minidump {
+    /// Read the minidump at `path`
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_bytes(std::fs::read(path)?)
+            .map_err(|_| invalid(format!("{} is not a valid minidump", path.display())).into())
+    }
+
+    /// Use `data` as the contents of a minidump file
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let dump = Self { data };
+        let reader = dump.reader();
+        if reader.u32(0) != Some(SIGNATURE) {
+            return Err(invalid("not a minidump").into());
+        }
+        // The stream directory must be readable
+        reader
+            .stream(0)
+            .ok_or_else(|| invalid("truncated minidump"))?;
+        Ok(dump)
+    }
+
+    /// The contents of the minidump file
+    #[cfg_attr(not(feature = "upload"), allow(dead_code))]
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// The exception that caused the dump, `None` for a dump requested
+    /// without a crash, e.g. by [`dump_without_crash`](crate::CrashpadClient::dump_without_crash)
+    pub fn exception(&self) -> Result<Option<Exception>> {
+        let dump = self.reader();
+        let Some(stream) = dump.stream(EXCEPTION_STREAM).ok_or_else(truncated)? else {
+            return Ok(None);
+        };
+        // MINIDUMP_EXCEPTION_STREAM: thread ID and alignment, then the
+        // MINIDUMP_EXCEPTION with the code first and the address at 16
+        let exception = (|| {
+            Some(Exception {
+                thread_id: dump.u32(stream)?,
+                code: dump.u32(stream + 8)?,
+                flags: dump.u32(stream + 12)?,
+                address: dump.u64(stream + 24)?,
+            })
+        })();
+        exception.map(Some).ok_or_else(|| truncated().into())
+    }
+
+    /// Threads of the process at the time of the dump
+    pub fn threads(&self) -> Result<Vec<Thread>> {
+        let dump = self.reader();
+        let threads = (|| {
+            let Some(list) = dump.stream(THREAD_LIST_STREAM)? else {
+                return Some(Vec::new());
+            };
+            let names = dump.thread_names()?;
+            (0..dump.u32(list)? as usize)
+                .map(|index| {
+                    let thread = list + 4 + index * THREAD_SIZE;
+                    let id = dump.u32(thread)?;
+                    Some(Thread {
+                        id,
+                        name: names.get(&id).cloned(),
+                        stack_start: dump.u64(thread + 24)?,
+                        stack_size: u64::from(dump.u32(thread + 32)?),
+                    })
+                })
+                .collect()
+        })();
+        threads.ok_or_else(|| truncated().into())
+    }
+
+    /// Modules loaded in the process at the time of the dump
+    pub fn modules(&self) -> Result<Vec<Module>> {
+        let dump = self.reader();
+        let modules = (|| {
+            let Some(list) = dump.stream(MODULE_LIST_STREAM)? else {
+                return Some(Vec::new());
+            };
+            (0..dump.u32(list)? as usize)
+                .map(|index| {
+                    let module = list + 4 + index * MODULE_SIZE;
+                    let name = dump.utf16_string(dump.u32(module + 20)? as usize)?;
+                    let (debug_file, debug_id) = match dump.location_with_size(module + 76)? {
+                        Some((rva, size)) => dump.codeview(rva, size, &name),
+                        None => (None, None),
+                    };
+                    Some(Module {
+                        name,
+                        base_address: dump.u64(module)?,
+                        size: u64::from(dump.u32(module + 8)?),
+                        debug_file,
+                        debug_id,
+                    })
+                })
+                .collect()
+        })();
+        modules.ok_or_else(|| truncated().into())
+    }
+
+    /// Annotations of the dump
+    ///
+    /// Combines the process annotations (those passed to the handler at
+    /// start) with the simple annotations of each module; process
+    /// annotations win. String annotations of the modules, like those of a
+    /// [`LargeAnnotation`](crate::LargeAnnotation), are included unless a
+    /// simple annotation has the same key.
+    pub fn annotations(&self) -> Result<HashMap<String, String>> {
+        let dump = self.reader();
+        let annotations = match dump.stream(CRASHPAD_INFO_STREAM).ok_or_else(truncated)? {
+            Some(info) => dump.crashpad_info(info),
+            // Not written by Crashpad, or by a handler without annotations
+            None => Some(HashMap::new()),
+        };
+        annotations.ok_or_else(|| truncated().into())
+    }
+
+    /// Value histories of the dump, by key
+    ///
+    /// The values recorded with each [`ValueHistory`](crate::ValueHistory)
+    /// of the process, oldest first, including the
+    /// [`annotation_changes`](Self::annotation_changes) trail.
+    pub fn value_histories(&self) -> Result<HashMap<String, Vec<ValueChange>>> {
+        let dump = self.reader();
+        let Some(info) = dump.stream(CRASHPAD_INFO_STREAM).ok_or_else(truncated)? else {
+            return Ok(HashMap::new());
+        };
+        let mut histories = HashMap::new();
+        for (name, value) in dump
+            .annotation_objects(info, ANNOTATION_USER_DEFINED + history::VALUE_HISTORY_TYPE)
+            .ok_or_else(truncated)?
+        {
+            if let Some(history) = history::decode(value) {
+                histories.insert(name, history);
+            }
+        }
+        Ok(histories)
+    }
+
+    /// Values of the [`ThreadCrashKey`](crate::ThreadCrashKey) `key` by
+    /// thread ID
+    ///
+    /// Empty if the dump has no such key.
+    pub fn thread_crash_key(&self, key: &str) -> Result<BTreeMap<u64, String>> {
+        Ok(self
+            .annotations()?
+            .get(key)
+            .map(|annotation| thread_key::decode(annotation))
+            .unwrap_or_default())
+    }
+
+    /// Annotation changes recorded by the
+    /// [`annotation_audit`](crate::annotation_audit) trail, oldest first
+    ///
+    /// Empty if the process didn't enable it.
+    pub fn annotation_changes(&self) -> Result<Vec<AnnotationChange>> {
+        let histories = self.value_histories()?;
+        Ok(histories
+            .get(annotation_audit::KEY)
+            .map(|changes| changes.iter().map(annotation_audit::decode).collect())
+            .unwrap_or_default())
+    }
+
+    /// Breadcrumbs of the dump, oldest first
+    ///
+    /// The events recorded with [`breadcrumb!`](crate::breadcrumb), found in
+    /// the memory the dump captured. Empty if the process recorded none.
+    pub fn breadcrumbs(&self) -> Result<Vec<Breadcrumb>> {
+        let dump = self.reader();
+        let Some(list) = dump.stream(MEMORY_LIST_STREAM).ok_or_else(truncated)? else {
+            return Ok(Vec::new());
+        };
+        let count = dump.u32(list).ok_or_else(truncated)? as usize;
+        for index in 0..count {
+            let descriptor = list + 4 + index * MEMORY_DESCRIPTOR_SIZE;
+            let start = dump.u64(descriptor).ok_or_else(truncated)?;
+            let Some((rva, size)) = dump
+                .location_with_size(descriptor + 8)
+                .ok_or_else(truncated)?
+            else {
+                continue;
+            };
+            let memory = rva
+                .checked_add(size)
+                .and_then(|end| self.data.get(rva..end))
+                .ok_or_else(truncated)?;
+            // Crashpad may merge the buffer with neighbouring ranges
+            let first = (8 - (start % 8) as usize) % 8;
+            for offset in (first..memory.len().saturating_sub(7)).step_by(8) {
+                if memory[offset..offset + 8] != breadcrumbs::MAGIC.to_le_bytes() {
+                    continue;
+                }
+                if let Some(events) = breadcrumbs::decode(&memory[offset..]) {
+                    return Ok(events);
+                }
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// Contents of the first stream of type `stream_type`, `None` if the
+    /// dump has none
+    ///
+    /// Reads streams added by the application, e.g. with
+    /// [`add_user_stream`](crate::add_user_stream) in a linked handler.
+    pub fn user_stream(&self, stream_type: u32) -> Result<Option<&[u8]>> {
+        let dump = self.reader();
+        let Some((rva, size)) = dump.stream_with_size(stream_type).ok_or_else(truncated)? else {
+            return Ok(None);
+        };
+        let data = rva
+            .checked_add(size)
+            .and_then(|end| self.data.get(rva..end))
+            .ok_or_else(truncated)?;
+        Ok(Some(data))
+    }
+
+    /// Crash fingerprint, `None` for a dump without an exception
+    ///
+    /// The exception code and the crash address relative to the module it
+    /// is in, e.g. `0xc0000005@app.exe+0x1a2b`, which stays the same across
+    /// runs of the same build despite address space layout randomization.
+    pub fn fingerprint(&self) -> Result<Option<String>> {
+        let Some(exception) = self.exception()? else {
+            return Ok(None);
+        };
+        let code = exception.code;
+        let fingerprint = match self
+            .modules()?
+            .into_iter()
+            .find(|module| module.contains(exception.address))
+        {
+            Some(module) => format!(
+                "{code:#010x}@{}+{:#x}",
+                module.file_name(),
+                exception.address - module.base_address
+            ),
+            None => format!("{code:#010x}@{:#x}", exception.address),
+        };
+        Ok(Some(fingerprint))
+    }
+
+    /// Whether the crash was probably a stack overflow
+    ///
+    /// True for Windows' `EXCEPTION_STACK_OVERFLOW`, and for access
+    /// violations (`SIGSEGV`/`SIGBUS`, `EXC_BAD_ACCESS`) at an address in the
+    /// guard region below a stack recorded with
+    /// [`stack_annotations`](crate::stack_annotations), or just below the
+    /// crashing thread's stack pointer. False for dumps without an
+    /// exception.
+    pub fn probable_stack_overflow(&self) -> Result<bool> {
+        let Some(exception) = self.exception()? else {
+            return Ok(false);
+        };
+        if exception.code == EXCEPTION_STACK_OVERFLOW {
+            return Ok(true);
+        }
+        if !ACCESS_VIOLATION_CODES.contains(&exception.code) {
+            return Ok(false);
+        }
+
+        let address = exception.address;
+        if stack::recorded_stacks(&self.annotations()?)
+            .iter()
+            .any(|stack| stack.guards(address))
+        {
+            return Ok(true);
+        }
+        // The captured stack starts at the stack pointer, which an overflow
+        // leaves right above the faulting address
+        let below_stack_pointer = self
+            .threads()?
+            .into_iter()
+            .find(|thread| thread.id == exception.thread_id)
+            .is_some_and(|thread| {
+                (thread.stack_start.saturating_sub(STACK_POINTER_SLACK)..thread.stack_start)
+                    .contains(&address)
+            });
+        Ok(below_stack_pointer)
+    }
+
+    /// Category of the crash, `None` for a dump requested without a crash
+    /// and without a [hint](crate::set_crash_category_hint)
+    ///
+    /// The hint wins; otherwise a
+    /// [probable stack overflow](Self::probable_stack_overflow) is
+    /// [`CrashCategory::StackOverflow`] and other exceptions are classified
+    /// by their code and fault address.
+    pub fn crash_category(&self) -> Result<Option<CrashCategory>> {
+        let hint = self
+            .annotations()?
+            .get(taxonomy::CRASH_CATEGORY_HINT)
+            .and_then(|hint| hint.parse().ok());
+        if hint.is_some() {
+            return Ok(hint);
+        }
+        let Some(exception) = self.exception()? else {
+            return Ok(None);
+        };
+        if self.probable_stack_overflow()? {
+            return Ok(Some(CrashCategory::StackOverflow));
+        }
+        let fault_address = match exception.code {
+            // The address of an access violation is the second parameter;
+            // the exception address is that of the instruction
+            ACCESS_VIOLATION_WINDOWS => self.exception_parameter(1),
+            _ => Some(exception.address),
+        };
+        Ok(Some(CrashCategory::of_exception(
+            exception.code,
+            fault_address,
+        )))
+    }
+
+    /// `ExceptionInformation[index]` of the exception, if it has that many
+    /// parameters
+    fn exception_parameter(&self, index: usize) -> Option<u64> {
+        let dump = self.reader();
+        let stream = dump.stream(EXCEPTION_STREAM)??;
+        if index >= dump.u32(stream + 32)? as usize {
+            return None;
+        }
+        dump.u64(stream + 40 + index * 8)
+    }
+
+    /// `ProcessorArchitecture` from the system info stream
+    #[cfg(feature = "symbolicate")]
+    pub(crate) fn processor_architecture(&self) -> Option<u16> {
+        let dump = self.reader();
+        let info = dump.stream(SYSTEM_INFO_STREAM)??;
+        let bytes = self.data.get(info..info + 2)?;
+        Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Raw CPU context of thread `thread_id`: the context at the time of
+    /// the exception for the crashing thread, the one from the thread list
+    /// otherwise
+    #[cfg(feature = "symbolicate")]
+    pub(crate) fn thread_context(&self, thread_id: u32) -> Option<&[u8]> {
+        let dump = self.reader();
+        let exception = self.exception().ok().flatten();
+        let (rva, size) = match exception.filter(|exception| exception.thread_id == thread_id) {
+            Some(_) => {
+                let stream = dump.stream(EXCEPTION_STREAM)??;
+                dump.location_with_size(stream + 160)??
+            }
+            None => {
+                let thread = dump.thread(thread_id)?;
+                dump.location_with_size(thread + 40)??
+            }
+        };
+        self.data.get(rva..rva.checked_add(size)?)
+    }
+
+    /// Start address and contents of the stack memory captured for thread
+    /// `thread_id`
+    #[cfg(feature = "symbolicate")]
+    pub(crate) fn thread_stack(&self, thread_id: u32) -> Option<(u64, &[u8])> {
+        let dump = self.reader();
+        let thread = dump.thread(thread_id)?;
+        let start = dump.u64(thread + 24)?;
+        let size = dump.u32(thread + 32)? as usize;
+        let rva = dump.u32(thread + 36)? as usize;
+        Some((start, self.data.get(rva..rva.checked_add(size)?)?))
+    }
+
+    fn reader(&self) -> Reader<'_> {
+        Reader(&self.data)
+    }
+}
+
+/// Breakpad debug ID: the first 16 bytes of `id` as a GUID (the first
+/// three fields little-endian), followed by `age`, in upper-case hex
+pub(crate) fn debug_id(id: &[u8], age: u32) -> String {
+    let mut guid = [0u8; 16];
+    let len = id.len().min(16);
+    guid[..len].copy_from_slice(&id[..len]);
+    guid[..4].reverse();
+    guid[4..6].reverse();
+    guid[6..8].reverse();
+    let hex: String = guid.iter().map(|b| format!("{b:02X}")).collect();
+    format!("{hex}{age:X}")
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn truncated() -> io::Error {
+    invalid("truncated or corrupt minidump")
+}
+
+/// Fingerprint of the minidump at `path`, see [`Minidump::fingerprint`].
+pub(crate) fn read_fingerprint(path: &Path) -> Result<Option<String>> {
+    Minidump::open(path)?.fingerprint()
+}
+
+/// A minidump with only a Crashpad info stream holding `annotations`.
+///
+/// Used for synthetic reports: the handler uploads it like any other
+/// report, with the annotations as form fields.
+pub(crate) fn annotations_dump(annotations: &[(&str, &str)]) -> Vec<u8> {
+    let info = 32 + DIRECTORY_ENTRY_SIZE;
+    let mut data = vec![0u8; info + CRASHPAD_INFO_SIZE];
+    let put = |data: &mut Vec<u8>, offset: usize, value: u32| {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    };
+
+    put(&mut data, 0, SIGNATURE);
+    put(&mut data, 4, VERSION);
+    put(&mut data, 8, 1);
+    put(&mut data, 12, 32);
+    put(&mut data, 32, CRASHPAD_INFO_STREAM);
+    put(&mut data, 36, CRASHPAD_INFO_SIZE as u32);
+    put(&mut data, 40, info as u32);
+    // MinidumpCrashpadInfo::kVersion
+    put(&mut data, info, 1);
+
+    let mut strings = Vec::new();
+    for (key, value) in annotations {
+        for string in [key, value] {
+            strings.push(data.len() as u32);
+            data.extend_from_slice(&(string.len() as u32).to_le_bytes());
+            data.extend_from_slice(string.as_bytes());
+            // MinidumpUTF8String is NUL-terminated
+            data.push(0);
+        }
+    }
+    let dictionary = data.len();
+    data.extend_from_slice(&(annotations.len() as u32).to_le_bytes());
+    for string in strings {
+        data.extend_from_slice(&string.to_le_bytes());
+    }
+    let size = data.len() - dictionary;
+    put(&mut data, info + 36, size as u32);
+    put(&mut data, info + 40, dictionary as u32);
+
+    data
+}
+
+/// `data` with its annotations changed to `annotations`, see
+/// [`CrashReportDatabase::request_upload_with`](crate::CrashReportDatabase::request_upload_with).
+///
+/// Removed annotations are dropped from the dictionaries and annotation
+/// lists holding them, changed values are appended and pointed to, and new
+/// annotations are added to the process annotations. The rewritten
+/// dictionaries and lists are appended too: nothing is overwritten in
+/// place, so the other streams stay intact. `None` if the dump has no
+/// Crashpad info stream or is corrupt.
+pub(crate) fn rewrite_annotations(
+    data: &[u8],
+    annotations: &HashMap<String, String>,
+) -> Option<Vec<u8>> {
+    let dump = Reader(data);
+    let info = dump.stream(CRASHPAD_INFO_STREAM)??;
+    let original = dump.crashpad_info(info)?;
+    let mut out = data.to_vec();
+
+    let mut changed: HashMap<&str, u32> = HashMap::new();
+    for (key, value) in annotations {
+        if original.get(key) != Some(value) {
+            changed.insert(key, append_string(&mut out, value.as_bytes())?);
+        }
+    }
+    let mut added = Vec::new();
+    for key in annotations
+        .keys()
+        .filter(|key| !original.contains_key(*key))
+    {
+        added.push((
+            append_string(&mut out, key.as_bytes())?,
+            changed[key.as_str()],
+        ));
+    }
+    // Value RVA of the annotation `key` at `value`, `None` to drop it
+    let value_of = |key: &str, value: u32| {
+        annotations
+            .contains_key(key)
+            .then(|| changed.get(key).copied().unwrap_or(value))
+    };
+
+    // MinidumpSimpleStringDictionary at the location descriptor `location`
+    let rewrite_dictionary = |out: &mut Vec<u8>, location: usize, added: &[(u32, u32)]| {
+        let mut entries = Vec::new();
+        let mut unchanged = added.is_empty();
+        if let Some(rva) = dump.location(location)? {
+            for index in 0..dump.u32(rva)? as usize {
+                let entry = rva + 4 + index * 8;
+                let key = dump.string(dump.u32(entry)? as usize)?;
+                let value = dump.u32(entry + 4)?;
+                match value_of(&key, value) {
+                    Some(new) => {
+                        unchanged &= new == value;
+                        entries.push([dump.u32(entry)?, new]);
+                    }
+                    None => unchanged = false,
+                }
+            }
+        }
+        if unchanged {
+            return Some(());
+        }
+        entries.extend(added.iter().map(|&(key, value)| [key, value]));
+        let mut dictionary = (entries.len() as u32).to_le_bytes().to_vec();
+        for value in entries.iter().flatten() {
+            dictionary.extend_from_slice(&value.to_le_bytes());
+        }
+        let rva = append(out, &dictionary)?;
+        put(out, location, dictionary.len() as u32);
+        put(out, location + 4, rva);
+        Some(())
+    };
+
+    // MinidumpAnnotationList at the location descriptor `location`; only
+    // string annotations are annotations of the report
+    let rewrite_list = |out: &mut Vec<u8>, location: usize| {
+        let Some(rva) = dump.location(location)? else {
+            return Some(());
+        };
+        // The count, then the entries
+        let mut list = vec![0; 4];
+        let mut count = 0u32;
+        let mut unchanged = true;
+        for index in 0..dump.u32(rva)? as usize {
+            let entry = rva + 4 + index * ANNOTATION_SIZE;
+            let name = dump.u32(entry)?;
+            let kind = dump.u32(entry + 4)?;
+            let mut value = dump.u32(entry + 8)?;
+            if kind & 0xffff == ANNOTATION_STRING as u32 {
+                match value_of(&dump.string(name as usize)?, value) {
+                    Some(new) => {
+                        unchanged &= new == value;
+                        value = new;
+                    }
+                    None => {
+                        unchanged = false;
+                        continue;
+                    }
+                }
+            }
+            for field in [name, kind, value] {
+                list.extend_from_slice(&field.to_le_bytes());
+            }
+            count += 1;
+        }
+        if unchanged {
+            return Some(());
+        }
+        put(&mut list, 0, count);
+        let rva = append(out, &list)?;
+        put(out, location, list.len() as u32);
+        put(out, location + 4, rva);
+        Some(())
+    };
+
+    if let Some(modules) = dump.location(info + 44)? {
+        for index in 0..dump.u32(modules)? as usize {
+            let link = modules + 4 + index * MODULE_LINK_SIZE;
+            if let Some(module) = dump.location(link + 4)? {
+                rewrite_dictionary(&mut out, module + 12, &[])?;
+                rewrite_list(&mut out, module + 20)?;
+            }
+        }
+    }
+    rewrite_dictionary(&mut out, info + 36, &added)?;
+    Some(out)
+}
+
+fn put(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Append `bytes` at a 4-byte boundary, returning their RVA. `None` past
+/// the 4 GiB a minidump can address.
+fn append(data: &mut Vec<u8>, bytes: &[u8]) -> Option<u32> {
+    data.resize((data.len() + 3) & !3, 0);
+    let rva = u32::try_from(data.len()).ok()?;
+    u32::try_from(data.len() + bytes.len()).ok()?;
+    data.extend_from_slice(bytes);
+    Some(rva)
+}
+
+/// Append a `MinidumpUTF8String`, returning its RVA
+fn append_string(data: &mut Vec<u8>, string: &[u8]) -> Option<u32> {
+    let mut bytes = u32::try_from(string.len()).ok()?.to_le_bytes().to_vec();
+    bytes.extend_from_slice(string);
+    // NUL-terminated
+    bytes.push(0);
+    append(data, &bytes)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.0.get(offset..offset.checked_add(4)?)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    fn u64(&self, offset: usize) -> Option<u64> {
+        let bytes = self.0.get(offset..offset.checked_add(8)?)?;
+        Some(u64::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    /// Offset of the first stream of type `kind`, `None` if there is none.
+    /// Fails if the data isn't a minidump.
+    fn stream(&self, kind: u32) -> Option<Option<usize>> {
+        Some(self.stream_with_size(kind)?.map(|(rva, _)| rva))
+    }
+
+    /// Offset and size of the first stream of type `kind`, `None` if there
+    /// is none. Fails if the data isn't a minidump.
+    fn stream_with_size(&self, kind: u32) -> Option<Option<(usize, usize)>> {
+        if self.u32(0)? != SIGNATURE {
+            return None;
+        }
+        let stream_count = self.u32(8)? as usize;
+        let directory = self.u32(12)? as usize;
+
+        for index in 0..stream_count {
+            let entry = directory + index * DIRECTORY_ENTRY_SIZE;
+            if self.u32(entry)? == kind {
+                let size = self.u32(entry + 4)? as usize;
+                return Some(Some((self.u32(entry + 8)? as usize, size)));
+            }
+        }
+        Some(None)
+    }
+
+    /// Thread IDs to names from the `MINIDUMP_THREAD_NAME_LIST`, if any
+    fn thread_names(&self) -> Option<HashMap<u32, String>> {
+        let Some(list) = self.stream(THREAD_NAMES_STREAM)? else {
+            return Some(HashMap::new());
+        };
+        (0..self.u32(list)? as usize)
+            .map(|index| {
+                let entry = list + 4 + index * THREAD_NAME_SIZE;
+                let rva = usize::try_from(self.u64(entry + 4)?).ok()?;
+                Some((self.u32(entry)?, self.utf16_string(rva)?))
+            })
+            .collect()
+    }
+
+    /// `MINIDUMP_LOCATION_DESCRIPTOR` at `offset`, `None` if empty
+    fn location(&self, offset: usize) -> Option<Option<usize>> {
+        Some(self.location_with_size(offset)?.map(|(rva, _)| rva))
+    }
+
+    /// `MINIDUMP_LOCATION_DESCRIPTOR` at `offset` as RVA and size, `None`
+    /// if empty
+    fn location_with_size(&self, offset: usize) -> Option<Option<(usize, usize)>> {
+        let size = self.u32(offset)? as usize;
+        let rva = self.u32(offset + 4)? as usize;
+        Some((size > 0).then_some((rva, size)))
+    }
+
+    /// Offset of the `MINIDUMP_THREAD` of thread `thread_id`
+    #[cfg(feature = "symbolicate")]
+    fn thread(&self, thread_id: u32) -> Option<usize> {
+        let list = self.stream(THREAD_LIST_STREAM)??;
+        (0..self.u32(list)? as usize)
+            .map(|index| list + 4 + index * THREAD_SIZE)
+            .find(|&thread| self.u32(thread) == Some(thread_id))
+    }
+
+    /// Debug file and Breakpad debug ID from the CodeView record at `rva`
+    fn codeview(
+        &self,
+        rva: usize,
+        size: usize,
+        module_name: &str,
+    ) -> (Option<String>, Option<String>) {
+        let file_name = || module_name.rsplit(['/', '\\']).next().map(str::to_string);
+        let Some(record) = self.0.get(rva..rva.saturating_add(size)) else {
+            return (None, None);
+        };
+        match self.u32(rva) {
+            Some(CODEVIEW_PDB70) if record.len() >= 24 => {
+                let age = self.u32(rva + 20).unwrap_or_default();
+                let pdb = &record[24..];
+                let pdb = &pdb[..pdb.iter().position(|&b| b == 0).unwrap_or(pdb.len())];
+                let pdb = String::from_utf8_lossy(pdb);
+                let debug_file = pdb.rsplit(['/', '\\']).next().map(str::to_string);
+                (
+                    debug_file.or_else(file_name),
+                    Some(debug_id(&record[4..20], age)),
+                )
+            }
+            Some(CODEVIEW_BUILD_ID) if record.len() > 4 => {
+                (file_name(), Some(debug_id(&record[4..], 0)))
+            }
+            _ => (None, None),
+        }
+    }
+
+    /// `MinidumpCrashpadInfo` at `rva`
+    fn crashpad_info(&self, rva: usize) -> Option<HashMap<String, String>> {
+        let mut annotations = HashMap::new();
+
+        for (name, value) in self.annotation_objects(rva, ANNOTATION_STRING)? {
+            annotations.insert(name, String::from_utf8_lossy(value).into_owned());
+        }
+
+        // version, report_id and client_id come before the annotations
+        if let Some(modules) = self.location(rva + 44)? {
+            for index in 0..self.u32(modules)? as usize {
+                let link = modules + 4 + index * MODULE_LINK_SIZE;
+                let Some(module) = self.location(link + 4)? else {
+                    continue;
+                };
+                // MinidumpModuleCrashpadInfo: version, list_annotations,
+                // simple_annotations
+                if let Some(dictionary) = self.location(module + 12)? {
+                    self.dictionary(dictionary, &mut annotations)?;
+                }
+            }
+        }
+        if let Some(dictionary) = self.location(rva + 36)? {
+            self.dictionary(dictionary, &mut annotations)?;
+        }
+
+        Some(annotations)
+    }
+
+    /// Names and values of the annotation objects of type `kind` of all
+    /// modules in the `MinidumpCrashpadInfo` at `rva`
+    fn annotation_objects(&self, rva: usize, kind: u16) -> Option<Vec<(String, &[u8])>> {
+        let mut annotations = Vec::new();
+        let Some(modules) = self.location(rva + 44)? else {
+            return Some(annotations);
+        };
+        for index in 0..self.u32(modules)? as usize {
+            let link = modules + 4 + index * MODULE_LINK_SIZE;
+            let Some(module) = self.location(link + 4)? else {
+                continue;
+            };
+            // MinidumpModuleCrashpadInfo: annotation_objects follows
+            // simple_annotations
+            let Some(list) = self.location(module + 20)? else {
+                continue;
+            };
+            for index in 0..self.u32(list)? as usize {
+                let entry = list + 4 + index * ANNOTATION_SIZE;
+                if self.u32(entry + 4)? & 0xffff != kind as u32 {
+                    continue;
+                }
+                let name = self.string(self.u32(entry)? as usize)?;
+                let value = self.u32(entry + 8)? as usize;
+                let length = self.u32(value)? as usize;
+                let bytes = self.0.get(value + 4..(value + 4).checked_add(length)?)?;
+                annotations.push((name, bytes));
+            }
+        }
+        Some(annotations)
+    }
+
+    /// `MinidumpSimpleStringDictionary` at `rva`
+    fn dictionary(&self, rva: usize, into: &mut HashMap<String, String>) -> Option<()> {
+        for index in 0..self.u32(rva)? as usize {
+            let entry = rva + 4 + index * 8;
+            let key = self.string(self.u32(entry)? as usize)?;
+            let value = self.string(self.u32(entry + 4)? as usize)?;
+            into.insert(key, value);
+        }
+        Some(())
+    }
+
+    /// `MinidumpUTF8String` at `rva`
+    fn string(&self, rva: usize) -> Option<String> {
+        let length = self.u32(rva)? as usize;
+        let bytes = self.0.get(rva + 4..(rva + 4).checked_add(length)?)?;
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    /// `MINIDUMP_STRING` (UTF-16) at `rva`
+    fn utf16_string(&self, rva: usize) -> Option<String> {
+        let length = self.u32(rva)? as usize;
+        let bytes = self.0.get(rva + 4..(rva + 4).checked_add(length)?)?;
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .collect();
+        Some(String::from_utf16_lossy(&units))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A minidump with only a Crashpad info stream.
+    pub(crate) fn dump_with_annotations(
+        process: &[(&str, &str)],
+        module: &[(&str, &str)],
+    ) -> Vec<u8> {
+        let mut data = vec![0u8; 32 + DIRECTORY_ENTRY_SIZE + 52];
+        let put = |data: &mut Vec<u8>, offset: usize, value: u32| {
+            data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        let append_dictionary = |data: &mut Vec<u8>, entries: &[(&str, &str)]| {
+            let mut strings = Vec::new();
+            for (key, value) in entries {
+                for string in [key, value] {
+                    strings.push(data.len() as u32);
+                    data.extend_from_slice(&(string.len() as u32).to_le_bytes());
+                    data.extend_from_slice(string.as_bytes());
+                }
+            }
+            let rva = data.len();
+            data.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+            for string in strings {
+                data.extend_from_slice(&string.to_le_bytes());
+            }
+            (data.len() - rva, rva)
+        };
+
+        put(&mut data, 0, SIGNATURE);
+        put(&mut data, 8, 1);
+        put(&mut data, 12, 32);
+        let info = 32 + DIRECTORY_ENTRY_SIZE;
+        put(&mut data, 32, CRASHPAD_INFO_STREAM);
+        put(&mut data, 36, 52);
+        put(&mut data, 40, info as u32);
+
+        let (size, rva) = append_dictionary(&mut data, process);
+        put(&mut data, info + 36, size as u32);
+        put(&mut data, info + 40, rva as u32);
+
+        let (size, dictionary) = append_dictionary(&mut data, module);
+        let module_info = data.len();
+        data.extend_from_slice(&[0; 28]);
+        put(&mut data, module_info + 12, size as u32);
+        put(&mut data, module_info + 16, dictionary as u32);
+        let list = data.len();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&28u32.to_le_bytes());
+        data.extend_from_slice(&(module_info as u32).to_le_bytes());
+        put(&mut data, info + 44, 16);
+        put(&mut data, info + 48, list as u32);
+
+        data
+    }
+
+    fn parse(bytes: Vec<u8>) -> Minidump {
+        Minidump::from_bytes(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_annotations() {
+        let data = dump_with_annotations(
+            &[("product", "MyApp"), ("version", "1.0.0")],
+            &[("version", "module"), ("gpu", "none")],
+        );
+
+        let annotations = parse(data).annotations().unwrap();
+
+        assert_eq!(annotations.len(), 3);
+        assert_eq!(annotations["product"], "MyApp");
+        assert_eq!(annotations["version"], "1.0.0");
+        assert_eq!(annotations["gpu"], "none");
+    }
+
+    #[test]
+    fn test_value_histories() {
+        let mut buffer = [0u8; 16 + 2 * 14];
+        for (offset, value) in [(0, 1u32), (4, 2), (8, 2), (12, 1)] {
+            buffer[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        buffer[16..24].copy_from_slice(&1500u64.to_le_bytes());
+        buffer[24..28].copy_from_slice(&2u32.to_le_bytes());
+        buffer[28..30].copy_from_slice(b"up");
+
+        // A Crashpad info stream with one module holding two annotation
+        // objects, only one of them a history
+        let mut data = vec![0u8; 32 + DIRECTORY_ENTRY_SIZE + CRASHPAD_INFO_SIZE];
+        let put = |data: &mut Vec<u8>, offset: usize, value: u32| {
+            data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        put(&mut data, 0, SIGNATURE);
+        put(&mut data, 8, 1);
+        put(&mut data, 12, 32);
+        let info = 32 + DIRECTORY_ENTRY_SIZE;
+        put(&mut data, 32, CRASHPAD_INFO_STREAM);
+        put(&mut data, 36, CRASHPAD_INFO_SIZE as u32);
+        put(&mut data, 40, info as u32);
+
+        let mut objects = Vec::new();
+        for (name, kind, value) in [
+            (
+                "state",
+                ANNOTATION_USER_DEFINED + history::VALUE_HISTORY_TYPE,
+                &buffer[..],
+            ),
+            ("other", 1, &b"text"[..]),
+        ] {
+            let name_rva = data.len() as u32;
+            data.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            data.extend_from_slice(name.as_bytes());
+            data.push(0);
+            let value_rva = data.len() as u32;
+            data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            data.extend_from_slice(value);
+            objects.push((name_rva, kind, value_rva));
+        }
+        let list = data.len();
+        data.extend_from_slice(&(objects.len() as u32).to_le_bytes());
+        for (name, kind, value) in objects {
+            data.extend_from_slice(&name.to_le_bytes());
+            data.extend_from_slice(&(kind as u32).to_le_bytes());
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let module_info = data.len();
+        data.extend_from_slice(&[0; 28]);
+        let size = (data.len() - list) as u32;
+        put(&mut data, module_info + 20, size);
+        put(&mut data, module_info + 24, list as u32);
+        let links = data.len();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&28u32.to_le_bytes());
+        data.extend_from_slice(&(module_info as u32).to_le_bytes());
+        put(&mut data, info + 44, 16);
+        put(&mut data, info + 48, links as u32);
+
+        let dump = parse(data);
+        let histories = dump.value_histories().unwrap();
+        assert_eq!(histories.len(), 1);
+        assert_eq!(
+            histories["state"],
+            vec![ValueChange {
+                time: std::time::UNIX_EPOCH + std::time::Duration::from_millis(1500),
+                value: "up".to_string(),
+            }]
+        );
+        // The string annotation is one of the annotations
+        assert_eq!(dump.annotations().unwrap()["other"], "text");
+        assert!(!dump.annotations().unwrap().contains_key("state"));
+
+        let empty = parse(dump_with_annotations(&[("product", "MyApp")], &[]));
+        assert!(empty.value_histories().unwrap().is_empty());
+    }
+
+    /// Stack start of thread 7 in [`crash_dump`], also its `rsp` and `rbp`.
+    pub(crate) const STACK_START: u64 = 0x7ff0_0000;
+
+    /// A minidump of a crash at `address` on thread 7, with threads 7 and 8
+    /// (named "main") and one module.
+    fn crash_dump(code: u32, address: u64, module: (&str, u64, u32)) -> Vec<u8> {
+        amd64_crash_dump(code, address, module, &[])
+    }
+
+    /// [`crash_dump`] of an x86-64 process, with `rip` at `address` and
+    /// `stack` at the start of the 4 KiB stack of thread 7. The module has
+    /// the build ID `00 01 .. 0f`.
+    pub(crate) fn amd64_crash_dump(
+        code: u32,
+        address: u64,
+        module: (&str, u64, u32),
+        stack: &[u64],
+    ) -> Vec<u8> {
+        let mut data = vec![0u8; 32 + 5 * DIRECTORY_ENTRY_SIZE];
+        let put = |data: &mut Vec<u8>, offset: usize, value: u32| {
+            data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        let put64 = |data: &mut Vec<u8>, offset: usize, value: u64| {
+            data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        };
+        let append_string = |data: &mut Vec<u8>, string: &str| {
+            let rva = data.len();
+            let units: Vec<u16> = string.encode_utf16().collect();
+            data.extend_from_slice(&(units.len() as u32 * 2).to_le_bytes());
+            for unit in units {
+                data.extend_from_slice(&unit.to_le_bytes());
+            }
+            rva
+        };
+        let mut streams = 0;
+        let mut add_stream = |data: &mut Vec<u8>, kind: u32, rva: usize| {
+            let entry = 32 + streams * DIRECTORY_ENTRY_SIZE;
+            put(data, entry, kind);
+            put(data, entry + 8, rva as u32);
+            streams += 1;
+        };
+        put(&mut data, 0, SIGNATURE);
+        put(&mut data, 8, 5);
+        put(&mut data, 12, 32);
+
+        // PROCESSOR_ARCHITECTURE_AMD64
+        let system_info = data.len();
+        data.extend_from_slice(&[0; 56]);
+        data[system_info] = 9;
+        add_stream(&mut data, 7, system_info);
+
+        // CONTEXT_AMD64 with rsp, rbp and rip
+        let context = data.len();
+        data.extend_from_slice(&[0; 1232]);
+        put64(&mut data, context + 152, STACK_START);
+        put64(&mut data, context + 160, STACK_START);
+        put64(&mut data, context + 248, address);
+
+        let exception = data.len();
+        data.extend_from_slice(&[0; 168]);
+        put(&mut data, exception, 7);
+        put(&mut data, exception + 8, code);
+        put64(&mut data, exception + 24, address);
+        put(&mut data, exception + 160, 1232);
+        put(&mut data, exception + 164, context as u32);
+        add_stream(&mut data, EXCEPTION_STREAM, exception);
+
+        let (path, base, size) = module;
+        let name = append_string(&mut data, path);
+        let codeview = data.len();
+        data.extend_from_slice(&CODEVIEW_BUILD_ID.to_le_bytes());
+        data.extend((0..16).map(|b| b as u8));
+        let list = data.len();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        let entry = data.len();
+        data.extend_from_slice(&[0; MODULE_SIZE]);
+        put64(&mut data, entry, base);
+        put(&mut data, entry + 8, size);
+        put(&mut data, entry + 20, name as u32);
+        put(&mut data, entry + 76, 20);
+        put(&mut data, entry + 80, codeview as u32);
+        add_stream(&mut data, MODULE_LIST_STREAM, list);
+
+        let memory = data.len();
+        data.extend_from_slice(&[0; 0x1000]);
+        for (index, &word) in stack.iter().enumerate() {
+            put64(&mut data, memory + index * 8, word);
+        }
+        let list = data.len();
+        data.extend_from_slice(&2u32.to_le_bytes());
+        for (id, stack) in [(7, STACK_START), (8, 0x7fe0_0000)] {
+            let thread = data.len();
+            data.extend_from_slice(&[0; THREAD_SIZE]);
+            put(&mut data, thread, id);
+            put64(&mut data, thread + 24, stack);
+            put(&mut data, thread + 32, 0x1000);
+            put(&mut data, thread + 36, memory as u32);
+        }
+        add_stream(&mut data, THREAD_LIST_STREAM, list);
+
+        let name = append_string(&mut data, "main");
+        let list = data.len();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&7u32.to_le_bytes());
+        data.extend_from_slice(&(name as u64).to_le_bytes());
+        add_stream(&mut data, THREAD_NAMES_STREAM, list);
+
+        data
+    }
+
+    #[test]
+    fn test_crash_dump() {
+        let module = ("/usr/bin/app", 0x5555_0000_0000, 0x10000);
+        let dump = parse(crash_dump(0xb, 0x5555_0000_1a2b, module));
+
+        assert_eq!(
+            dump.exception().unwrap(),
+            Some(Exception {
+                thread_id: 7,
+                code: 0xb,
+                flags: 0,
+                address: 0x5555_0000_1a2b,
+            })
+        );
+        assert_eq!(
+            dump.threads().unwrap(),
+            vec![
+                Thread {
+                    id: 7,
+                    name: Some("main".to_string()),
+                    stack_start: 0x7ff0_0000,
+                    stack_size: 0x1000,
+                },
+                Thread {
+                    id: 8,
+                    name: None,
+                    stack_start: 0x7fe0_0000,
+                    stack_size: 0x1000,
+                },
+            ]
+        );
+        let modules = dump.modules().unwrap();
+        assert_eq!(modules.len(), 1);
+        assert_eq!(modules[0].name, "/usr/bin/app");
+        assert_eq!(modules[0].file_name(), "app");
+        assert_eq!(modules[0].debug_file.as_deref(), Some("app"));
+        assert_eq!(
+            modules[0].debug_id.as_deref(),
+            Some("030201000504070608090A0B0C0D0E0F0")
+        );
+        assert!(dump.annotations().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_probable_stack_overflow() {
+        let module = ("/usr/bin/app", 0x5555_0000_0000, 0x10000);
+        let overflow = parse(crash_dump(0xb, STACK_START - 0x100, module));
+        assert!(overflow.probable_stack_overflow().unwrap());
+        let windows = parse(crash_dump(0xc000_00fd, 0x5555_0000_1a2b, module));
+        assert!(windows.probable_stack_overflow().unwrap());
+
+        let null_dereference = parse(crash_dump(0xb, 0, module));
+        assert!(!null_dereference.probable_stack_overflow().unwrap());
+        let abort = parse(crash_dump(6, STACK_START - 0x100, module));
+        assert!(!abort.probable_stack_overflow().unwrap());
+        let no_exception = parse(dump_with_annotations(&[("product", "MyApp")], &[]));
+        assert!(!no_exception.probable_stack_overflow().unwrap());
+    }
+
+    #[test]
+    fn test_crash_category() {
+        let module = ("/usr/bin/app", 0x5555_0000_0000, 0x10000);
+        let category = |code, address| {
+            parse(crash_dump(code, address, module))
+                .crash_category()
+                .unwrap()
+        };
+        assert_eq!(category(0xb, 0x8), Some(CrashCategory::NullDereference));
+        assert_eq!(
+            category(0xb, STACK_START - 0x100),
+            Some(CrashCategory::StackOverflow)
+        );
+        assert_eq!(category(6, 0x5555_0000_1a2b), Some(CrashCategory::Abort));
+        // No parameters to tell the fault address
+        assert_eq!(category(0xc000_0005, 0), Some(CrashCategory::Other));
+
+        let requested = parse(dump_with_annotations(&[("product", "MyApp")], &[]));
+        assert_eq!(requested.crash_category().unwrap(), None);
+        let hang = parse(dump_with_annotations(
+            &[],
+            &[("crash_category_hint", "hang")],
+        ));
+        assert_eq!(hang.crash_category().unwrap(), Some(CrashCategory::Hang));
+    }
+
+    #[test]
+    fn test_breadcrumbs() {
+        // A memory list with a stack and a range where the buffer follows 8
+        // other bytes, as if merged with a neighbouring range
+        let buffer = crate::breadcrumbs::tests::buffer(4, 16, &["started", "opened file"]);
+        let mut data = vec![0u8; 32 + DIRECTORY_ENTRY_SIZE];
+        data[0..4].copy_from_slice(&SIGNATURE.to_le_bytes());
+        data[8..12].copy_from_slice(&1u32.to_le_bytes());
+        data[12..16].copy_from_slice(&32u32.to_le_bytes());
+        data[32..36].copy_from_slice(&MEMORY_LIST_STREAM.to_le_bytes());
+        let list = data.len();
+        data[40..44].copy_from_slice(&(list as u32).to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        let ranges = [
+            (0x7ff0_0000u64, vec![0xaa; 64]),
+            (0x5000_0ff8, {
+                let mut memory = vec![0u8; 8];
+                memory.extend_from_slice(&buffer);
+                memory
+            }),
+        ];
+        let mut rva = list + 4 + ranges.len() * MEMORY_DESCRIPTOR_SIZE;
+        for (start, memory) in &ranges {
+            data.extend_from_slice(&start.to_le_bytes());
+            data.extend_from_slice(&(memory.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(rva as u32).to_le_bytes());
+            rva += memory.len();
+        }
+        for (_, memory) in &ranges {
+            data.extend_from_slice(memory);
+        }
+
+        let events = parse(data).breadcrumbs().unwrap();
+        let messages: Vec<_> = events.iter().map(|event| event.message.as_str()).collect();
+        assert_eq!(messages, ["started", "opened file"]);
+
+        let none = parse(dump_with_annotations(&[("product", "MyApp")], &[]));
+        assert!(none.breadcrumbs().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_debug_id() {
+        // RSDS GUID {3B8E0D51-4D6A-4E9B-8C6F-2A1B0C9D8E7F}, age 2
+        let guid = [
+            0x51, 0x0d, 0x8e, 0x3b, 0x6a, 0x4d, 0x9b, 0x4e, 0x8c, 0x6f, 0x2a, 0x1b, 0x0c, 0x9d,
+            0x8e, 0x7f,
+        ];
+        assert_eq!(debug_id(&guid, 2), "3B8E0D514D6A4E9B8C6F2A1B0C9D8E7F2");
+
+        // Short ELF build IDs are padded
+        assert_eq!(
+            debug_id(&[1, 2, 3, 4], 0),
+            "040302010000000000000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let module = ("/usr/bin/app", 0x5555_0000_0000, 0x10000);
+        let dump = parse(crash_dump(0xb, 0x5555_0000_1a2b, module));
+        assert_eq!(
+            dump.fingerprint().unwrap().as_deref(),
+            Some("0x0000000b@app+0x1a2b")
+        );
+
+        // Outside of any module
+        let module = ("C:\\app.exe", 0x40_0000, 0x1000);
+        let dump = parse(crash_dump(0xc000_0005, 0x10, module));
+        assert_eq!(
+            dump.fingerprint().unwrap().as_deref(),
+            Some("0xc0000005@0x10")
+        );
+
+        // No exception
+        let dump = parse(dump_with_annotations(&[], &[]));
+        assert_eq!(dump.fingerprint().unwrap(), None);
+        assert!(dump.threads().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_annotations_dump() {
+        let dump = parse(annotations_dump(&[
+            ("product", "MyApp"),
+            ("burst_count", "12"),
+        ]));
+
+        let annotations = dump.annotations().unwrap();
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(annotations["product"], "MyApp");
+        assert_eq!(annotations["burst_count"], "12");
+        assert_eq!(dump.exception().unwrap(), None);
+    }
+
+    #[test]
+    fn test_rewrite_annotations() {
+        let data = dump_with_annotations(
+            &[("product", "MyApp"), ("user", "alice")],
+            &[("user", "module"), ("path", "/home/alice/app")],
+        );
+        let mut annotations = parse(data.clone()).annotations().unwrap();
+        assert_eq!(rewrite_annotations(&data, &annotations).unwrap(), data);
+
+        annotations.remove("user");
+        annotations.insert("path".to_string(), "/home/[user]/app".to_string());
+        annotations.insert("scrubbed".to_string(), "true".to_string());
+        let rewritten = rewrite_annotations(&data, &annotations).unwrap();
+        assert_eq!(parse(rewritten).annotations().unwrap(), annotations);
+
+        assert!(rewrite_annotations(&annotations_dump(&[])[..32], &annotations).is_none());
+    }
+
+    #[test]
+    fn test_user_stream() {
+        let dump = parse(annotations_dump(&[("product", "MyApp")]));
+        assert_eq!(dump.user_stream(0x4741_4d45).unwrap(), None);
+
+        // Any stream can be read, e.g. the Crashpad info stream
+        let info = dump.user_stream(CRASHPAD_INFO_STREAM).unwrap().unwrap();
+        assert_eq!(info.len(), CRASHPAD_INFO_SIZE);
+        assert_eq!(&info[..4], &1u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Minidump::from_bytes(b"not a minidump".to_vec()).is_err());
+
+        let data = dump_with_annotations(&[("product", "MyApp")], &[]);
+        let truncated = parse(data[..60].to_vec());
+        assert!(matches!(
+            truncated.annotations(),
+            Err(crate::CrashpadError::IoError(e)) if e.kind() == io::ErrorKind::InvalidData
+        ));
+    }
+}
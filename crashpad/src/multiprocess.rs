@@ -0,0 +1,430 @@
+//! One handler shared by a parent process and the children it spawns.
+//!
+//! Starting a handler per process is wasteful for applications that spawn
+//! many workers. Instead, the parent starts a [`SharedHandler`] and passes
+//! its [`HandlerConnection`] to each child through the environment
+//! ([`HANDLER_ENV`]); children call [`connect_from_env`] to report crashes
+//! to the same handler without starting one. The connection is:
+//!
+//! - **Windows**: the name of the handler's pipe
+//! - **Linux/Android**: the handler's socket, inherited by the child, and
+//!   the handler's process ID
+//! - **macOS**: the name of a Mach service the handler checks in under
+//!
+//! ```no_run
+//! use crashpad_rs::multiprocess::{self, SharedHandler};
+//! use crashpad_rs::CrashpadConfig;
+//! use std::process::Command;
+//!
+//! // Parent
+//! let config = CrashpadConfig::builder().database_path("./crashes").build();
+//! let handler = SharedHandler::start(&config, &Default::default())?;
+//! let mut worker = Command::new("worker");
+//! handler.configure_child(&mut worker)?;
+//! worker.spawn()?;
+//!
+//! // Worker, at startup; keep the client alive
+//! let client = multiprocess::connect_from_env()?;
+//! # Ok::<(), crashpad_rs::CrashpadError>(())
+//! ```
+//!
+//! Not available on iOS/tvOS/watchOS, which have no handler process.
+
+use std::collections::HashMap;
+use std::env;
+use std::process::Command;
+
+use crate::{CrashpadClient, CrashpadConfig, CrashpadError, Result};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+/// Environment variable holding the connection of the shared handler.
+pub const HANDLER_ENV: &str = "CRASHPAD_RS_SHARED_HANDLER";
+
+/// How a child process reaches a shared handler.
+///
+/// Serialized into [`HANDLER_ENV`] by [`SharedHandler::configure_child`]
+/// as `pipe:<name>` (Windows), `socket:<fd>:<pid>` (Linux/Android) or
+/// `mach:<service>` (macOS).
+#[derive(Debug)]
+pub struct HandlerConnection {
+    #[cfg(target_os = "windows")]
+    pipe: String,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    socket: OwnedFd,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    handler_pid: u32,
+    #[cfg(target_os = "macos")]
+    service: String,
+}
+
+impl HandlerConnection {
+    /// The value of [`HANDLER_ENV`] for a child process
+    pub fn to_env_value(&self) -> String {
+        #[cfg(target_os = "windows")]
+        {
+            format!("pipe:{}", self.pipe)
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            format!("socket:{}:{}", self.socket.as_raw_fd(), self.handler_pid)
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            format!("mach:{}", self.service)
+        }
+    }
+
+    /// Parse a value written by [`to_env_value`](Self::to_env_value) in the
+    /// parent process
+    ///
+    /// On Linux/Android, this takes ownership of the inherited socket and
+    /// makes it close-on-exec again, so it must be called at most once per
+    /// value.
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if `value` isn't a connection
+    /// for this platform, or the socket wasn't inherited.
+    pub fn from_env_value(value: &str) -> Result<Self> {
+        let invalid =
+            || CrashpadError::InvalidConfiguration(format!("Invalid handler connection {value:?}"));
+        let (scheme, rest) = value.split_once(':').ok_or_else(invalid)?;
+
+        #[cfg(target_os = "windows")]
+        {
+            if scheme != "pipe" || rest.is_empty() {
+                return Err(invalid());
+            }
+            Ok(Self {
+                pipe: rest.to_string(),
+            })
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let (fd, pid) = rest.split_once(':').ok_or_else(invalid)?;
+            let fd: RawFd = fd.parse().map_err(|_| invalid())?;
+            let handler_pid = pid.parse().map_err(|_| invalid())?;
+            if scheme != "socket" || fd < 0 {
+                return Err(invalid());
+            }
+            // Only take descriptors that are open; the parent may not have
+            // passed it to this process
+            if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+                return Err(invalid());
+            }
+            Ok(Self {
+                socket: unsafe { OwnedFd::from_raw_fd(fd) },
+                handler_pid,
+            })
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            if scheme != "mach" || rest.is_empty() {
+                return Err(invalid());
+            }
+            Ok(Self {
+                service: rest.to_string(),
+            })
+        }
+    }
+
+    /// Register `client` with the handler, taking the connection
+    fn connect(self, client: &CrashpadClient) -> Result<()> {
+        #[cfg(target_os = "windows")]
+        {
+            client.set_handler_ipc_pipe(&self.pipe)
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            client.set_handler_socket(self.socket, Some(self.handler_pid))
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            client.set_handler_mach_service(&self.service)
+        }
+    }
+}
+
+/// A handler started by this process to be shared with its children.
+///
+/// This process reports its own crashes to the handler through
+/// [`client`](Self::client). On macOS, the handler is stopped when this
+/// value is dropped, so keep it alive until the children have exited; on
+/// other platforms the handler exits on its own once no process is
+/// connected.
+pub struct SharedHandler {
+    client: CrashpadClient,
+    connection: HandlerConnection,
+    #[cfg(target_os = "macos")]
+    process: std::process::Child,
+}
+
+impl SharedHandler {
+    /// Start a handler for this process and its children
+    ///
+    /// Takes the same configuration as
+    /// [`start_with_config`](CrashpadClient::start_with_config). On macOS,
+    /// the handler is started by this crate rather than Crashpad, so it
+    /// isn't restarted if it dies and
+    /// [`handler_umask`](crate::CrashpadConfigBuilder::handler_umask) is
+    /// ignored.
+    pub fn start(config: &CrashpadConfig, annotations: &HashMap<String, String>) -> Result<Self> {
+        let client = CrashpadClient::new()?;
+
+        #[cfg(target_os = "windows")]
+        {
+            client.start_with_config(config, annotations)?;
+            let pipe = client.get_handler_ipc_pipe()?;
+            Ok(Self {
+                client,
+                connection: HandlerConnection { pipe },
+            })
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            client.start_with_config(config, annotations)?;
+            let (socket, handler_pid) = client.handler_socket()?;
+            Ok(Self {
+                client,
+                connection: HandlerConnection {
+                    socket,
+                    handler_pid,
+                },
+            })
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            let (service, process) = mach::start(&client, config, annotations)?;
+            Ok(Self {
+                client,
+                connection: HandlerConnection { service },
+                process,
+            })
+        }
+    }
+
+    /// The client of this process
+    pub fn client(&self) -> &CrashpadClient {
+        &self.client
+    }
+
+    /// The connection children use
+    pub fn connection(&self) -> &HandlerConnection {
+        &self.connection
+    }
+
+    /// Set up `command` so the child reports crashes to this handler
+    ///
+    /// Sets [`HANDLER_ENV`], and on Linux/Android passes the socket to the
+    /// child. The child still has to call [`connect_from_env`].
+    pub fn configure_child(&self, command: &mut Command) -> Result<()> {
+        command.env(HANDLER_ENV, self.connection.to_env_value());
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            use std::os::unix::process::CommandExt;
+
+            let fd = self.connection.socket.as_raw_fd();
+            // Only clears the flag in the forked child, before it execs
+            unsafe {
+                command.pre_exec(move || {
+                    let flags = libc::fcntl(fd, libc::F_GETFD);
+                    if flags == -1
+                        || libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC) == -1
+                    {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for SharedHandler {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// Report crashes of this process to the shared handler of its parent
+///
+/// Reads [`HANDLER_ENV`] and registers a new client with the handler.
+/// Returns `None` if the variable isn't set, e.g. when the process wasn't
+/// started by a [`SharedHandler`]. Keep the returned client alive for as
+/// long as crashes should be reported.
+///
+/// # Errors
+///
+/// [`CrashpadError::InvalidConfiguration`] if the variable is malformed,
+/// [`CrashpadError::IpcRegistrationFailed`] if the handler can't be reached.
+pub fn connect_from_env() -> Result<Option<CrashpadClient>> {
+    let Ok(value) = env::var(HANDLER_ENV) else {
+        return Ok(None);
+    };
+    let connection = HandlerConnection::from_env_value(&value)?;
+    let client = CrashpadClient::new()?;
+    connection.connect(&client)?;
+    Ok(Some(client))
+}
+
+/// Handlers that check in with the bootstrap server (macOS).
+#[cfg(target_os = "macos")]
+mod mach {
+    use std::collections::HashMap;
+    use std::fs::OpenOptions;
+    use std::process::{Child, Command, Stdio};
+    use std::thread;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    use crate::backend::{NativeBackend, StartRequest};
+    use crate::client::with_start_request;
+    use crate::{CrashpadClient, CrashpadConfig, CrashpadError, Result};
+
+    /// How long the handler may take to check in
+    const CHECK_IN_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Start a handler under a new service name and register `client` with
+    /// it
+    pub(super) fn start(
+        client: &CrashpadClient,
+        config: &CrashpadConfig,
+        annotations: &HashMap<String, String>,
+    ) -> Result<(String, Child)> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.subsec_nanos());
+        let service = format!("org.crashpad-rs.handler.{}.{nanos}", std::process::id());
+
+        let mut process = None;
+        with_start_request(&NativeBackend, config, annotations, |request| {
+            process = Some(spawn(request, &service)?);
+            Ok(())
+        })?;
+        let mut process = process.expect("handler was spawned");
+
+        let deadline = Instant::now() + CHECK_IN_TIMEOUT;
+        loop {
+            match client.set_handler_mach_service(&service) {
+                Ok(()) => break,
+                Err(_) if Instant::now() < deadline => {
+                    if let Ok(Some(_)) = process.try_wait() {
+                        return Err(CrashpadError::HandlerSpawnFailed {
+                            path: config.handler_path()?,
+                            os_error: None,
+                            trace: config.resolve_handler_with_trace().candidates,
+                        });
+                    }
+                    thread::sleep(Duration::from_millis(20));
+                }
+                Err(e) => {
+                    let _ = process.kill();
+                    let _ = process.wait();
+                    return Err(e);
+                }
+            }
+        }
+        Ok((service, process))
+    }
+
+    /// Spawn the handler of `request`, checking in under `service`
+    fn spawn(request: &StartRequest<'_>, service: &str) -> Result<Child> {
+        let mut command = Command::new(request.handler_path);
+        command
+            .args(request.handler_arguments)
+            .arg(format!("--database={}", request.database_path.display()))
+            .arg(format!("--mach-service={service}"))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null());
+        if !request.metrics_path.as_os_str().is_empty() {
+            command.arg(format!("--metrics-dir={}", request.metrics_path.display()));
+        }
+        if let Some(url) = request.url {
+            command.arg(format!("--url={url}"));
+        }
+        for (key, value) in request.annotations {
+            command.arg(format!("--annotation={key}={value}"));
+        }
+        if let Some(log) = request.handler_log {
+            let log = OpenOptions::new().create(true).append(true).open(log)?;
+            command.stderr(log);
+        }
+        if let Some(dir) = request.handler_working_directory {
+            command.current_dir(dir);
+        }
+
+        command
+            .spawn()
+            .map_err(|e| CrashpadError::HandlerSpawnFailed {
+                path: request.handler_path.to_path_buf(),
+                os_error: e.raw_os_error(),
+                trace: Vec::new(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_env_value() {
+        for value in ["", "pipe", "unknown:value", "socket:x:1", "mach:"] {
+            assert!(HandlerConnection::from_env_value(value).is_err(), "{value}");
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_socket_env_value() {
+        use std::os::fd::IntoRawFd;
+
+        let (server, client) = CrashpadClient::handler_socket_pair().unwrap();
+        let connection = HandlerConnection {
+            socket: client,
+            handler_pid: 42,
+        };
+        let fd = connection.socket.as_raw_fd();
+        assert_eq!(connection.to_env_value(), format!("socket:{fd}:42"));
+
+        // Hand the descriptor over like an exec would
+        let fd = connection.socket.into_raw_fd();
+        let parsed = HandlerConnection::from_env_value(&format!("socket:{fd}:42")).unwrap();
+        assert_eq!(parsed.socket.as_raw_fd(), fd);
+        assert_eq!(parsed.handler_pid, 42);
+        drop(server);
+
+        // Not open in this process
+        assert!(HandlerConnection::from_env_value(&format!("socket:{}:42", i32::MAX)).is_err());
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_pipe_env_value() {
+        let connection =
+            HandlerConnection::from_env_value(r"pipe:\\.\pipe\crashpad_1_ABC").unwrap();
+        assert_eq!(connection.pipe, r"\\.\pipe\crashpad_1_ABC");
+        assert_eq!(connection.to_env_value(), r"pipe:\\.\pipe\crashpad_1_ABC");
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_mach_env_value() {
+        let connection = HandlerConnection::from_env_value("mach:org.example.handler").unwrap();
+        assert_eq!(connection.service, "org.example.handler");
+        assert_eq!(connection.to_env_value(), "mach:org.example.handler");
+    }
+}
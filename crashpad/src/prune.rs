@@ -0,0 +1,196 @@
+//! Size and age limits for the reports kept in a database.
+
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::{CrashReportDatabase, Result};
+
+/// Which reports [`CrashReportDatabase::prune`] deletes.
+///
+/// Built from up to two limits:
+/// - [`max_age_days`](Self::max_age_days): reports older than this
+/// - [`max_size_mb`](Self::max_size_mb): reports beyond this total size,
+///   counting from the newest report
+///
+/// With both set, a report is deleted if it exceeds either of them, or only
+/// if it exceeds both with [`require_both`](Self::require_both). Without any
+/// limit, nothing is deleted.
+///
+/// # Example
+///
+/// ```
+/// use crashpad_rs::PruneCondition;
+///
+/// // Keep at most 90 days and 50 MB of reports
+/// let condition = PruneCondition::new().max_age_days(90).max_size_mb(50);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneCondition {
+    max_age_days: Option<u32>,
+    max_size_mb: Option<u64>,
+    require_both: bool,
+}
+
+impl PruneCondition {
+    /// A condition without limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delete reports created more than `days` days ago
+    pub fn max_age_days(mut self, days: u32) -> Self {
+        self.max_age_days = Some(days);
+        self
+    }
+
+    /// Delete the oldest reports once the reports add up to more than `mb`
+    /// megabytes
+    pub fn max_size_mb(mut self, mb: u64) -> Self {
+        self.max_size_mb = Some(mb);
+        self
+    }
+
+    /// Only delete reports that exceed both limits
+    ///
+    /// # Default
+    ///
+    /// `false`: a report exceeding either limit is deleted.
+    pub fn require_both(mut self, require_both: bool) -> Self {
+        self.require_both = require_both;
+        self
+    }
+
+    /// Whether the condition has no limits, so that nothing is deleted
+    pub fn is_empty(&self) -> bool {
+        self.age_days() == 0 && self.size_kb() == 0
+    }
+
+    /// Age limit for the FFI, 0 if unset
+    pub(crate) fn age_days(&self) -> i32 {
+        self.max_age_days
+            .map_or(0, |days| days.min(i32::MAX as u32) as i32)
+    }
+
+    /// Size limit in kilobytes for the FFI, 0 if unset
+    pub(crate) fn size_kb(&self) -> u64 {
+        self.max_size_mb.map_or(0, |mb| mb.saturating_mul(1024))
+    }
+
+    pub(crate) fn requires_both(&self) -> bool {
+        self.require_both
+    }
+}
+
+/// Prunes a database periodically on a background thread.
+///
+/// For long-running processes whose database would otherwise only be
+/// pruned when the handler's periodic tasks run, e.g. with the handler
+/// started with `--no-periodic-tasks`. The database is pruned right away
+/// and then once per interval. The thread stops when the pruner is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use crashpad_rs::{DatabasePruner, PruneCondition};
+/// use std::time::Duration;
+///
+/// let _pruner = DatabasePruner::start(
+///     "./crashpad_db",
+///     PruneCondition::new().max_age_days(30).max_size_mb(100),
+///     Duration::from_secs(60 * 60),
+/// )?;
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+pub struct DatabasePruner {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DatabasePruner {
+    /// Prune the database at `database_path` with `condition` every
+    /// `interval`
+    pub fn start<P: AsRef<Path>>(
+        database_path: P,
+        condition: PruneCondition,
+        interval: Duration,
+    ) -> Result<Self> {
+        let database = CrashReportDatabase::open(database_path)?;
+
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stop = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("crashpad-pruner".to_string())
+            .spawn(move || {
+                let (stopped, condvar) = &*thread_stop;
+                loop {
+                    database.prune(&condition);
+
+                    let guard = stopped.lock().unwrap_or_else(|e| e.into_inner());
+                    let (guard, _) = condvar
+                        .wait_timeout_while(guard, interval, |stopped| !*stopped)
+                        .unwrap_or_else(|e| e.into_inner());
+                    if *guard {
+                        return;
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for DatabasePruner {
+    fn drop(&mut self) {
+        let (stopped, condvar) = &*self.stop;
+        *stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for DatabasePruner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DatabasePruner").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_condition() {
+        let condition = PruneCondition::new();
+        assert!(condition.is_empty());
+        assert_eq!(condition.age_days(), 0);
+        assert_eq!(condition.size_kb(), 0);
+
+        // A zero limit is the same as none
+        assert!(PruneCondition::new().max_age_days(0).is_empty());
+    }
+
+    #[test]
+    fn test_condition_limits() {
+        let condition = PruneCondition::new()
+            .max_age_days(90)
+            .max_size_mb(50)
+            .require_both(true);
+        assert!(!condition.is_empty());
+        assert_eq!(condition.age_days(), 90);
+        assert_eq!(condition.size_kb(), 50 * 1024);
+        assert!(condition.requires_both());
+
+        let huge = PruneCondition::new()
+            .max_age_days(u32::MAX)
+            .max_size_mb(u64::MAX);
+        assert_eq!(huge.age_days(), i32::MAX);
+        assert_eq!(huge.size_kb(), u64::MAX);
+    }
+}
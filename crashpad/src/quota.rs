@@ -0,0 +1,210 @@
+//! Per-fingerprint upload counters of reports sent with
+//! [`upload::send_pending`].
+//!
+//! The uploads of each crash fingerprint are counted in `quota.json` in the
+//! database, with the start of the current period in seconds since the
+//! Unix epoch, e.g. `{"0x0000000b@app+0x1a2b":{"since":1714563000,"uploads":3}}`.
+//! A period starts with the first upload of a fingerprint and ends after
+//! the policy's period; counters of ended periods are dropped on the next
+//! write.
+//!
+//! [`upload::send_pending`]: crate::upload::send_pending
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::clock::Clock;
+use crate::fs::Fs;
+
+/// File of the counters in the database.
+const QUOTA_FILE: &str = "quota.json";
+
+/// At most `max_uploads` reports of one crash fingerprint per `period`,
+/// see [`RetryPolicy::fingerprint_quota`](crate::upload::RetryPolicy::fingerprint_quota).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FingerprintQuota {
+    pub(crate) max_uploads: u32,
+    pub(crate) period: Duration,
+}
+
+/// Uploads of one fingerprint in the current period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Counter {
+    /// To the second
+    since: SystemTime,
+    uploads: u32,
+}
+
+/// Upload counters by fingerprint.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Counters(BTreeMap<String, Counter>);
+
+impl Counters {
+    /// Read the counters of `database`. Unreadable counters are treated as
+    /// none, so a corrupt file doesn't block uploads.
+    pub(crate) fn read(fs: &impl Fs, database: &Path) -> io::Result<Self> {
+        match fs.read(&quota_path(database)) {
+            Ok(json) => Ok(Self::parse(&json).unwrap_or_default()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether a report with `fingerprint` may be uploaded now
+    pub(crate) fn allows(
+        &self,
+        quota: &FingerprintQuota,
+        fingerprint: &str,
+        clock: &impl Clock,
+    ) -> bool {
+        let now = clock.now();
+        self.0
+            .get(fingerprint)
+            .filter(|counter| !counter.ended(quota, now))
+            .map_or(true, |counter| counter.uploads < quota.max_uploads)
+    }
+
+    /// Count an upload of a report with `fingerprint` just now and write
+    /// the counters to `database`
+    pub(crate) fn record_upload(
+        &mut self,
+        fs: &impl Fs,
+        database: &Path,
+        quota: &FingerprintQuota,
+        fingerprint: &str,
+        clock: &impl Clock,
+    ) -> io::Result<()> {
+        let now = clock.now();
+        self.0.retain(|_, counter| !counter.ended(quota, now));
+        let since = UNIX_EPOCH + Duration::from_secs(seconds(now));
+        self.0
+            .entry(fingerprint.to_string())
+            .or_insert(Counter { since, uploads: 0 })
+            .uploads += 1;
+
+        let path = quota_path(database);
+        let temporary = path.with_extension("json.tmp");
+        fs.write(&temporary, &self.to_json())?;
+        fs.rename(&temporary, &path)
+    }
+
+    fn parse(json: &[u8]) -> Option<Self> {
+        let value: serde_json::Value = serde_json::from_slice(json).ok()?;
+        let counters = value
+            .as_object()?
+            .iter()
+            .filter_map(|(fingerprint, counter)| {
+                let counter = Counter {
+                    since: UNIX_EPOCH + Duration::from_secs(counter.get("since")?.as_u64()?),
+                    uploads: u32::try_from(counter.get("uploads")?.as_u64()?).ok()?,
+                };
+                Some((fingerprint.clone(), counter))
+            })
+            .collect();
+        Some(Self(counters))
+    }
+
+    fn to_json(&self) -> Vec<u8> {
+        let counters: serde_json::Map<String, serde_json::Value> = self
+            .0
+            .iter()
+            .map(|(fingerprint, counter)| {
+                let value = serde_json::json!({
+                    "since": seconds(counter.since),
+                    "uploads": counter.uploads,
+                });
+                (fingerprint.clone(), value)
+            })
+            .collect();
+        serde_json::Value::Object(counters).to_string().into_bytes()
+    }
+}
+
+impl Counter {
+    fn ended(&self, quota: &FingerprintQuota, now: SystemTime) -> bool {
+        self.since
+            .checked_add(quota.period)
+            .is_some_and(|end| end <= now)
+    }
+}
+
+/// Path of the counters in `database`
+fn quota_path(database: &Path) -> PathBuf {
+    database.join(QUOTA_FILE)
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::manual::ManualClock;
+    use crate::fs::memory::MemoryFs;
+
+    const FINGERPRINT: &str = "0x0000000b@app+0x1a2b";
+
+    #[test]
+    fn test_quota() {
+        let fs = MemoryFs::default();
+        let database = Path::new("db");
+        fs.create_dir_all(database).unwrap();
+        let quota = FingerprintQuota {
+            max_uploads: 2,
+            period: Duration::from_secs(24 * 3600),
+        };
+        let clock = ManualClock::at(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+
+        let mut counters = Counters::read(&fs, database).unwrap();
+        assert!(counters.allows(&quota, FINGERPRINT, &clock));
+        counters
+            .record_upload(&fs, database, &quota, FINGERPRINT, &clock)
+            .unwrap();
+        counters
+            .record_upload(&fs, database, &quota, FINGERPRINT, &clock)
+            .unwrap();
+        assert!(!counters.allows(&quota, FINGERPRINT, &clock));
+        assert!(counters.allows(&quota, "other", &clock));
+
+        // The counters survive a restart
+        let counters = Counters::read(&fs, database).unwrap();
+        clock.advance(Duration::from_secs(3600));
+        assert!(!counters.allows(&quota, FINGERPRINT, &clock));
+        clock.advance(quota.period - Duration::from_secs(3600));
+        assert!(counters.allows(&quota, FINGERPRINT, &clock));
+    }
+
+    #[test]
+    fn test_record_upload_drops_ended_periods() {
+        let fs = MemoryFs::default();
+        let database = Path::new("db");
+        fs.create_dir_all(database).unwrap();
+        let quota = FingerprintQuota {
+            max_uploads: 1,
+            period: Duration::from_secs(60),
+        };
+        let clock = ManualClock::at(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+
+        let mut counters = Counters::default();
+        counters
+            .record_upload(&fs, database, &quota, FINGERPRINT, &clock)
+            .unwrap();
+        clock.advance(Duration::from_secs(60));
+        counters
+            .record_upload(&fs, database, &quota, "other", &clock)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(fs.read(&quota_path(database)).unwrap()).unwrap(),
+            r#"{"other":{"since":1700000060,"uploads":1}}"#
+        );
+
+        // Unreadable counters don't block uploads
+        fs.write(&quota_path(database), b"[").unwrap();
+        let counters = Counters::read(&fs, database).unwrap();
+        assert!(counters.allows(&quota, "other", &clock));
+    }
+}
@@ -0,0 +1,272 @@
+//! Non-fatal snapshots that keep clear of real-time threads.
+//!
+//! While a dump is written, the handler suspends every thread of the
+//! process, in an order Crashpad chooses. For an audio or video thread that
+//! must meet a deadline every few milliseconds, a suspension in the middle
+//! of its callback is an audible glitch. Crashes can't wait, but non-fatal
+//! snapshots can: [`CrashpadClient::dump_without_crash_realtime`] waits, up
+//! to a time box, until no registered real-time thread is inside its
+//! callback, so the suspension falls into the gap before the next one.
+//!
+//! Dumps written this way are annotated with the real-time threads and
+//! whether each was idle (`realtime_threads`, e.g. `audio:idle`), how long
+//! the snapshot waited (`realtime_wait_ms`), and how long the previous
+//! snapshot kept the process suspended (`realtime_last_suspension_ms`),
+//! since a dump can't contain its own duration.
+//!
+//! ```rust,no_run
+//! use crashpad_rs::realtime::{self, RealtimeCapture};
+//! use std::time::Duration;
+//! # let client = crashpad_rs::CrashpadClient::new()?;
+//!
+//! // On the audio thread
+//! let audio = realtime::register_thread("audio");
+//! loop {
+//!     let _callback = audio.callback();
+//!     // Fill the buffer
+//! #   break;
+//! }
+//!
+//! // Elsewhere, on an error worth a snapshot
+//! let capture = RealtimeCapture::new(Duration::from_millis(20));
+//! let timing = client.dump_without_crash_realtime(&capture);
+//! println!("suspended for {:?}", timing.suspended);
+//! # Ok::<(), crashpad_rs::CrashpadError>(())
+//! ```
+//!
+//! [`CrashpadClient::dump_without_crash_realtime`]: crate::CrashpadClient::dump_without_crash_realtime
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Annotation listing the real-time threads and their state
+const THREADS: &str = "realtime_threads";
+
+/// Annotation holding how long the snapshot waited for the threads
+const WAIT: &str = "realtime_wait_ms";
+
+/// Annotation holding the suspension of the previous snapshot
+const LAST_SUSPENSION: &str = "realtime_last_suspension_ms";
+
+/// How often the snapshot checks whether the threads are idle
+const POLL_INTERVAL: Duration = Duration::from_micros(250);
+
+/// The registered real-time threads, in registration order.
+static REGISTRY: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+/// Suspension of the last snapshot in microseconds, 0 before the first.
+static LAST_SUSPENSION_MICROS: AtomicU64 = AtomicU64::new(0);
+
+struct Entry {
+    id: u64,
+    name: String,
+    busy: Arc<AtomicBool>,
+}
+
+/// A thread registered with [`register_thread`].
+///
+/// Unregistered when dropped.
+#[derive(Debug)]
+pub struct RealtimeThread {
+    id: u64,
+    busy: Arc<AtomicBool>,
+}
+
+impl RealtimeThread {
+    /// Mark the thread as inside its callback until the guard is dropped
+    ///
+    /// Lock-free and allocation-free, so it can be called from the
+    /// callback itself.
+    pub fn callback(&self) -> CallbackGuard<'_> {
+        self.busy.store(true, Ordering::Release);
+        CallbackGuard { busy: &self.busy }
+    }
+}
+
+impl Drop for RealtimeThread {
+    fn drop(&mut self) {
+        REGISTRY
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|entry| entry.id != self.id);
+    }
+}
+
+/// Marks a [`RealtimeThread`] as inside its callback while alive.
+#[derive(Debug)]
+pub struct CallbackGuard<'a> {
+    busy: &'a AtomicBool,
+}
+
+impl Drop for CallbackGuard<'_> {
+    fn drop(&mut self) {
+        self.busy.store(false, Ordering::Release);
+    }
+}
+
+/// Register a real-time thread as `name`, e.g. `audio`
+///
+/// Call it on the thread before its first callback, not inside one: it
+/// takes a lock and allocates.
+pub fn register_thread(name: impl Into<String>) -> RealtimeThread {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let busy = Arc::new(AtomicBool::new(false));
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Entry {
+            id,
+            name: name.into(),
+            busy: busy.clone(),
+        });
+    RealtimeThread { id, busy }
+}
+
+/// How long [`CrashpadClient::dump_without_crash_realtime`] waits for the
+/// real-time threads.
+///
+/// [`CrashpadClient::dump_without_crash_realtime`]: crate::CrashpadClient::dump_without_crash_realtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RealtimeCapture {
+    max_wait: Duration,
+}
+
+impl RealtimeCapture {
+    /// Wait up to `max_wait` for every real-time thread to leave its
+    /// callback, then take the snapshot anyway
+    pub fn new(max_wait: Duration) -> Self {
+        Self { max_wait }
+    }
+}
+
+impl Default for RealtimeCapture {
+    /// Wait up to 50 ms, a few periods of a typical audio callback
+    fn default() -> Self {
+        Self::new(Duration::from_millis(50))
+    }
+}
+
+/// How a snapshot of [`CrashpadClient::dump_without_crash_realtime`] went.
+///
+/// [`CrashpadClient::dump_without_crash_realtime`]: crate::CrashpadClient::dump_without_crash_realtime
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotTiming {
+    /// Time spent waiting for the real-time threads
+    pub waited: Duration,
+    /// Time the dump took to write, the longest any thread, real-time or
+    /// not, was suspended
+    pub suspended: Duration,
+    /// Real-time threads still inside their callback when the time box
+    /// ran out
+    pub busy_threads: Vec<String>,
+}
+
+/// Wait until no thread of `busy` is set, up to `max_wait`. Returns the
+/// time waited.
+fn wait_idle(busy: &[Arc<AtomicBool>], max_wait: Duration) -> Duration {
+    let start = Instant::now();
+    while busy.iter().any(|busy| busy.load(Ordering::Acquire)) && start.elapsed() < max_wait {
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    start.elapsed()
+}
+
+/// `name:idle` or `name:busy` for each thread, comma-separated
+fn describe(threads: &[(String, bool)]) -> String {
+    threads
+        .iter()
+        .map(|(name, busy)| format!("{name}:{}", if *busy { "busy" } else { "idle" }))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn set_annotation(key: &str, value: Option<&str>) {
+    crate::annotation_audit::set_module_annotation(key, value);
+}
+
+/// See [`CrashpadClient::dump_without_crash_realtime`]: wait for the
+/// threads, annotate, then `dump`.
+///
+/// [`CrashpadClient::dump_without_crash_realtime`]: crate::CrashpadClient::dump_without_crash_realtime
+pub(crate) fn snapshot(capture: &RealtimeCapture, dump: impl FnOnce()) -> SnapshotTiming {
+    let (names, busy): (Vec<String>, Vec<Arc<AtomicBool>>) = REGISTRY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|entry| (entry.name.clone(), entry.busy.clone()))
+        .unzip();
+
+    let waited = wait_idle(&busy, capture.max_wait);
+    let threads: Vec<(String, bool)> = names
+        .into_iter()
+        .zip(busy.iter().map(|busy| busy.load(Ordering::Acquire)))
+        .collect();
+
+    set_annotation(THREADS, Some(&describe(&threads)));
+    set_annotation(WAIT, Some(&waited.as_millis().to_string()));
+    let last = LAST_SUSPENSION_MICROS.load(Ordering::Relaxed);
+    set_annotation(
+        LAST_SUSPENSION,
+        (last > 0).then(|| (last / 1000).to_string()).as_deref(),
+    );
+
+    let start = Instant::now();
+    dump();
+    let suspended = start.elapsed();
+    LAST_SUSPENSION_MICROS.store(
+        suspended.as_micros().clamp(1, u64::MAX as u128) as u64,
+        Ordering::Relaxed,
+    );
+
+    // Keep them out of crash dumps
+    for key in [THREADS, WAIT, LAST_SUSPENSION] {
+        set_annotation(key, None);
+    }
+
+    SnapshotTiming {
+        waited,
+        suspended,
+        busy_threads: threads
+            .into_iter()
+            .filter(|(_, busy)| *busy)
+            .map(|(name, _)| name)
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_idle() {
+        let idle = Arc::new(AtomicBool::new(false));
+        assert!(
+            wait_idle(std::slice::from_ref(&idle), Duration::from_secs(10))
+                < Duration::from_secs(1)
+        );
+
+        // Gives up once the time box runs out
+        let busy = Arc::new(AtomicBool::new(true));
+        let waited = wait_idle(&[idle, busy], Duration::from_millis(5));
+        assert!(waited >= Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_callback_guard() {
+        let thread = register_thread("audio");
+        {
+            let _callback = thread.callback();
+            assert!(thread.busy.load(Ordering::Acquire));
+        }
+        assert!(!thread.busy.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn test_describe() {
+        let threads = [("audio".to_string(), false), ("video".to_string(), true)];
+        assert_eq!(describe(&threads), "audio:idle,video:busy");
+    }
+}
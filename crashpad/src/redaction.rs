@@ -0,0 +1,364 @@
+//! Redaction of secrets from annotations.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::RwLock;
+
+#[cfg(feature = "regex")]
+use regex::{NoExpand, Regex};
+
+use crate::{CrashpadError, Result};
+
+/// Text that replaces redacted values by default.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Rules of the last client started with a configuration, for values
+/// recorded after the start, see [`with_active`].
+static ACTIVE: RwLock<Option<RedactionRules>> = RwLock::new(None);
+
+/// Key globs redacted by [`RedactionRules::default`].
+const DEFAULT_KEYS: &[&str] = &[
+    "*password*",
+    "*passwd*",
+    "*secret*",
+    "*token*",
+    "*api_key*",
+    "*apikey*",
+    "*authorization*",
+    "*cookie*",
+    "*credential*",
+    "*private_key*",
+];
+
+/// Value patterns redacted by [`RedactionRules::default`].
+#[cfg(feature = "regex")]
+const DEFAULT_PATTERNS: &[&str] = &[
+    // Email addresses
+    r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+    // Bearer tokens in copied Authorization headers
+    r"(?i)bearer\s+[A-Za-z0-9._~+/-]+=*",
+    // JSON Web Tokens
+    r"eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+",
+    // AWS access key IDs
+    r"\bAKIA[0-9A-Z]{16}\b",
+];
+
+/// Rules that remove secrets from annotations before they reach a dump.
+///
+/// Two kinds of rules are supported:
+/// - **Key globs** (`*` and `?` wildcards, case-insensitive) replace the
+///   whole value of every matching key.
+/// - **Value patterns** (regular expressions) replace every match inside any
+///   value. They need the `regex` feature.
+///
+/// [`RedactionRules::default`] covers common credential keys, and with the
+/// `regex` feature email addresses and bearer tokens; [`RedactionRules::new`]
+/// starts empty.
+///
+/// # Example
+///
+/// ```
+/// use crashpad_rs::RedactionRules;
+///
+/// let rules = RedactionRules::new().redact_key("*session*");
+///
+/// assert_eq!(rules.redact("session_id", "abc"), "[REDACTED]");
+/// assert_eq!(rules.redact("path", "/home/user-42"), "/home/user-42");
+/// ```
+#[derive(Debug, Clone)]
+pub struct RedactionRules {
+    keys: Vec<String>,
+    #[cfg(feature = "regex")]
+    patterns: Vec<Regex>,
+    replacement: String,
+}
+
+impl Default for RedactionRules {
+    fn default() -> Self {
+        let mut rules = Self::new();
+        for key in DEFAULT_KEYS {
+            rules = rules.redact_key(*key);
+        }
+        #[cfg(feature = "regex")]
+        for pattern in DEFAULT_PATTERNS {
+            rules
+                .patterns
+                .push(Regex::new(pattern).expect("default redaction pattern is valid"));
+        }
+        rules
+    }
+}
+
+impl RedactionRules {
+    /// Create a ruleset that redacts nothing
+    pub fn new() -> Self {
+        Self {
+            keys: Vec::new(),
+            #[cfg(feature = "regex")]
+            patterns: Vec::new(),
+            replacement: REDACTED.to_string(),
+        }
+    }
+
+    /// Load rules from a file
+    ///
+    /// One rule per line, `#` starts a comment:
+    ///
+    /// ```text
+    /// # Redact the value of matching keys
+    /// key: *session*
+    /// # Redact matches inside any value
+    /// pattern: user-\d+
+    /// # Text redacted values are replaced with
+    /// replacement: <removed>
+    /// ```
+    ///
+    /// The file's rules start from an empty ruleset; add `defaults` on a line
+    /// of its own to include the [default](RedactionRules::default) rules.
+    /// `pattern:` rules need the `regex` feature.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        Self::parse(&contents).map_err(|e| match e {
+            CrashpadError::InvalidConfiguration(msg) => {
+                CrashpadError::InvalidConfiguration(format!("{}: {msg}", path.display()))
+            }
+            e => e,
+        })
+    }
+
+    /// Parse rules in the [`from_file`](Self::from_file) format
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut rules = Self::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "defaults" {
+                let defaults = Self::default();
+                rules.keys.extend(defaults.keys);
+                #[cfg(feature = "regex")]
+                rules.patterns.extend(defaults.patterns);
+                continue;
+            }
+
+            let invalid = |reason: &str| {
+                CrashpadError::InvalidConfiguration(format!("line {}: {reason}", index + 1))
+            };
+            let (kind, value) = line
+                .split_once(':')
+                .ok_or_else(|| invalid("expected `key:`, `pattern:` or `replacement:`"))?;
+            let value = value.trim();
+            rules = match kind.trim() {
+                "key" => rules.redact_key(value),
+                #[cfg(feature = "regex")]
+                "pattern" => rules
+                    .redact_pattern(value)
+                    .map_err(|e| invalid(&e.to_string()))?,
+                #[cfg(not(feature = "regex"))]
+                "pattern" => return Err(invalid("`pattern:` needs the `regex` feature")),
+                "replacement" => rules.replacement(value),
+                other => return Err(invalid(&format!("unknown rule `{other}`"))),
+            };
+        }
+        Ok(rules)
+    }
+
+    /// Redact the whole value of keys matching `glob`
+    pub fn redact_key<S: Into<String>>(mut self, glob: S) -> Self {
+        self.keys.push(glob.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Redact every match of the regular expression `pattern` in values
+    ///
+    /// Fails with [`CrashpadError::InvalidConfiguration`] if `pattern` is
+    /// not a valid regular expression.
+    #[cfg(feature = "regex")]
+    pub fn redact_pattern(mut self, pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern).map_err(|e| {
+            CrashpadError::InvalidConfiguration(format!("Invalid redaction pattern: {e}"))
+        })?;
+        self.patterns.push(regex);
+        Ok(self)
+    }
+
+    /// Text redacted values are replaced with (`[REDACTED]` by default)
+    pub fn replacement<S: Into<String>>(mut self, replacement: S) -> Self {
+        self.replacement = replacement.into();
+        self
+    }
+
+    /// Whether the ruleset redacts nothing
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "regex")]
+        if !self.patterns.is_empty() {
+            return false;
+        }
+        self.keys.is_empty()
+    }
+
+    /// Redact a single annotation value
+    pub fn redact<'a>(&self, key: &str, value: &'a str) -> Cow<'a, str> {
+        let key = key.to_ascii_lowercase();
+        if self.keys.iter().any(|glob| glob_matches(glob, &key)) {
+            return Cow::Owned(self.replacement.clone());
+        }
+
+        #[allow(unused_mut)]
+        let mut value = Cow::Borrowed(value);
+        #[cfg(feature = "regex")]
+        for pattern in &self.patterns {
+            if let Cow::Owned(redacted) = pattern.replace_all(&value, NoExpand(&self.replacement)) {
+                value = Cow::Owned(redacted);
+            }
+        }
+        value
+    }
+
+    /// Redact every value of `annotations`
+    pub fn redact_annotations(
+        &self,
+        annotations: &HashMap<String, String>,
+    ) -> HashMap<String, String> {
+        annotations
+            .iter()
+            .map(|(key, value)| (key.clone(), self.redact(key, value).into_owned()))
+            .collect()
+    }
+}
+
+/// Redact what is recorded from now on, like breadcrumbs, with `rules`
+pub(crate) fn set_active(rules: Option<&RedactionRules>) {
+    *ACTIVE.write().unwrap_or_else(|e| e.into_inner()) = rules.cloned();
+}
+
+/// Run `f` with the rules of the running client, `None` if it has none
+pub(crate) fn with_active<R>(f: impl FnOnce(Option<&RedactionRules>) -> R) -> R {
+    f(ACTIVE.read().unwrap_or_else(|e| e.into_inner()).as_ref())
+}
+
+/// Match `text` against a glob with `*` and `?` wildcards.
+fn glob_matches(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut g, mut t) = (0, 0);
+    // Position after the last `*` and the text position it matched up to
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match glob.get(g) {
+            Some('*') => {
+                backtrack = Some((g + 1, t));
+                g += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                g += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((after_star, matched)) => {
+                    g = after_star;
+                    t = matched + 1;
+                    backtrack = Some((after_star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*token*", "auth_token_v2"));
+        assert!(glob_matches("*token*", "token"));
+        assert!(glob_matches("user_?", "user_1"));
+        assert!(glob_matches("a*b*c", "axxbyyc"));
+        assert!(!glob_matches("a*b*c", "axxbyy"));
+        assert!(!glob_matches("token", "tokens"));
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let rules = RedactionRules::default();
+
+        assert_eq!(rules.redact("API_TOKEN", "abc123"), REDACTED);
+        assert_eq!(rules.redact("db_password", "hunter2"), REDACTED);
+        assert!(matches!(
+            rules.redact("version", "1.0.0"),
+            Cow::Borrowed("1.0.0")
+        ));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_default_patterns() {
+        let rules = RedactionRules::default();
+
+        assert_eq!(
+            rules.redact("last_error", "mail to jane.doe@example.com failed"),
+            "mail to [REDACTED] failed"
+        );
+        assert_eq!(
+            rules.redact("request", "Authorization: Bearer abc.def-ghi"),
+            "Authorization: [REDACTED]"
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_parse() {
+        let rules = RedactionRules::parse(
+            "# comment\n\nkey: *session*\npattern: user-\\d+\nreplacement: <removed>\n",
+        )
+        .unwrap();
+
+        assert_eq!(rules.redact("Session_ID", "abc"), "<removed>");
+        assert_eq!(rules.redact("path", "/home/user-42/x"), "/home/<removed>/x");
+        assert_eq!(rules.redact("api_token", "abc"), "abc");
+
+        let rules = RedactionRules::parse("defaults\nkey: build_host").unwrap();
+        assert_eq!(rules.redact("api_token", "abc"), REDACTED);
+        assert_eq!(rules.redact("build_host", "ci-7"), REDACTED);
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let rules =
+            RedactionRules::parse("# comment\n\nkey: *session*\nreplacement: <removed>\n").unwrap();
+
+        assert_eq!(rules.redact("Session_ID", "abc"), "<removed>");
+        assert_eq!(rules.redact("api_token", "abc"), "abc");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for contents in ["nonsense", "color: red", "pattern: ("] {
+            let error = RedactionRules::parse(contents).unwrap_err();
+            assert!(
+                matches!(&error, CrashpadError::InvalidConfiguration(msg) if msg.starts_with("line 1:")),
+                "{contents}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_redact_annotations() {
+        let annotations = HashMap::from([
+            ("version".to_string(), "1.0.0".to_string()),
+            ("secret_key".to_string(), "s3cr3t".to_string()),
+        ]);
+
+        let redacted = RedactionRules::default().redact_annotations(&annotations);
+
+        assert_eq!(redacted["version"], "1.0.0");
+        assert_eq!(redacted["secret_key"], REDACTED);
+    }
+}
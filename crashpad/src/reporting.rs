@@ -0,0 +1,99 @@
+//! Object-safe interface to crash reporting for dependency injection.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::CrashpadClient;
+
+/// What application code needs from a crash reporter.
+///
+/// Implemented by [`CrashpadClient`] and by [`RecordingReporter`], so code
+/// can take an `Arc<dyn CrashReporting>` and be unit-tested without
+/// starting a handler.
+///
+/// # Example
+///
+/// ```
+/// use crashpad_rs::{CrashReporting, RecordingReporter};
+/// use std::sync::Arc;
+///
+/// struct Importer {
+///     reporter: Arc<dyn CrashReporting>,
+/// }
+///
+/// impl Importer {
+///     fn import(&self, data: &[u8]) {
+///         if data.is_empty() {
+///             // Unexpected, but recoverable: capture the state and go on
+///             self.reporter.dump_without_crash();
+///         }
+///     }
+/// }
+///
+/// let reporter = Arc::new(RecordingReporter::new());
+/// let importer = Importer { reporter: reporter.clone() };
+/// importer.import(&[]);
+/// assert_eq!(reporter.dumps(), 1);
+/// ```
+pub trait CrashReporting: Send + Sync {
+    /// Capture a dump of the calling thread without crashing
+    ///
+    /// See [`CrashpadClient::dump_without_crash`].
+    fn dump_without_crash(&self);
+}
+
+impl CrashReporting for CrashpadClient {
+    fn dump_without_crash(&self) {
+        CrashpadClient::dump_without_crash(self);
+    }
+}
+
+/// A [`CrashReporting`] implementation that only counts calls.
+///
+/// Use it in tests, or as the reporter when crash reporting is disabled.
+#[derive(Debug, Default)]
+pub struct RecordingReporter {
+    dumps: AtomicUsize,
+}
+
+impl RecordingReporter {
+    /// Create a reporter that has recorded nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of [`dump_without_crash`](CrashReporting::dump_without_crash)
+    /// calls so far
+    pub fn dumps(&self) -> usize {
+        self.dumps.load(Ordering::Relaxed)
+    }
+}
+
+impl CrashReporting for RecordingReporter {
+    fn dump_without_crash(&self) {
+        self.dumps.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_recording_reporter_as_trait_object() {
+        let recorder = Arc::new(RecordingReporter::new());
+        let reporter: Arc<dyn CrashReporting> = recorder.clone();
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let reporter = reporter.clone();
+                std::thread::spawn(move || reporter.dump_without_crash())
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(recorder.dumps(), 4);
+    }
+}
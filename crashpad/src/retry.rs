@@ -0,0 +1,343 @@
+//! Retry state of reports sent with [`upload::send_pending`].
+//!
+//! Each failed upload of a report is counted in `retry/<uuid>.json` in the
+//! database, with the time of the next attempt in seconds since the Unix
+//! epoch, e.g. `{"failures":2,"next_attempt":1714563000}`. The state
+//! survives restarts, so an application that is offline for days keeps
+//! backing off instead of retrying on every launch, and it is removed once
+//! the report is sent or given up on.
+//!
+//! [`upload::send_pending`]: crate::upload::send_pending
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::clock::Clock;
+use crate::fs::Fs;
+use crate::quota::FingerprintQuota;
+
+/// Directory of the retry state files in the database.
+const RETRY_DIR: &str = "retry";
+
+/// When and how often [`upload::send_pending`] retries failed uploads, and
+/// how many reports of one crash it sends.
+///
+/// After the `n`th failure, the next attempt waits `initial * 2^(n - 1)`,
+/// up to the maximum backoff. Once a report failed the maximum number of
+/// attempts, it is completed without upload, like reports skipped by the
+/// handler's rate limit; it can still be sent later with
+/// [`CrashReportDatabase::request_upload`].
+///
+/// # Example
+///
+/// ```
+/// use crashpad_rs::upload::RetryPolicy;
+/// use std::time::Duration;
+///
+/// # fn on_wifi() -> bool { true }
+/// let policy = RetryPolicy::new()
+///     .max_attempts(10)
+///     .backoff(Duration::from_secs(30), Duration::from_secs(6 * 3600))
+///     .only_when(on_wifi);
+/// ```
+///
+/// [`upload::send_pending`]: crate::upload::send_pending
+/// [`CrashReportDatabase::request_upload`]: crate::CrashReportDatabase::request_upload
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    condition: Option<Arc<dyn Fn() -> bool + Send + Sync>>,
+    quota: Option<FingerprintQuota>,
+}
+
+impl RetryPolicy {
+    /// The default policy: 5 attempts, backing off from 1 minute to 1 day,
+    /// whatever the connection
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give up on a report after `attempts` failed uploads
+    ///
+    /// # Default
+    ///
+    /// 5. 0 is treated as 1.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Wait `initial` after the first failure, doubling after each further
+    /// one up to `max`
+    ///
+    /// # Default
+    ///
+    /// 1 minute, up to 1 day.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Only send while `condition` returns `true`, e.g. while the device is
+    /// on Wi-Fi or not on a metered connection
+    ///
+    /// Checked before each report. Reports not sent because of it don't
+    /// count as failed attempts.
+    pub fn only_when<F>(mut self, condition: F) -> Self
+    where
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        self.condition = Some(Arc::new(condition));
+        self
+    }
+
+    /// Send at most `max_uploads` reports of one crash fingerprint per
+    /// `period`, e.g. a day
+    ///
+    /// Keeps a single frequent crash from using up the upload budget and
+    /// the server's quota. Further reports with the fingerprint are
+    /// completed without upload; reports without an exception, and reports
+    /// whose upload was requested with
+    /// [`CrashReportDatabase::request_upload`], are always sent. The
+    /// counters are kept in `quota.json` in the database, so they survive
+    /// restarts.
+    ///
+    /// # Default
+    ///
+    /// No quota.
+    pub fn fingerprint_quota(mut self, max_uploads: u32, period: Duration) -> Self {
+        self.quota = Some(FingerprintQuota {
+            max_uploads,
+            period,
+        });
+        self
+    }
+
+    /// The quota of [`fingerprint_quota`](Self::fingerprint_quota)
+    pub(crate) fn quota(&self) -> Option<&FingerprintQuota> {
+        self.quota.as_ref()
+    }
+
+    /// Whether the condition of [`only_when`](Self::only_when) allows
+    /// sending now
+    pub(crate) fn allows_sending(&self) -> bool {
+        self.condition
+            .as_ref()
+            .map_or(true, |condition| condition())
+    }
+
+    /// Wait after the `failures`th failure
+    fn backoff_after(&self, failures: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(failures.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(60),
+            max_backoff: Duration::from_secs(24 * 3600),
+            condition: None,
+            quota: None,
+        }
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("only_when", &self.condition.is_some())
+            .field("fingerprint_quota", &self.quota)
+            .finish()
+    }
+}
+
+/// Failed uploads of a report so far
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RetryState {
+    failures: u32,
+    /// To the second
+    next_attempt: SystemTime,
+}
+
+impl RetryState {
+    fn parse(json: &[u8]) -> Option<Self> {
+        let value: serde_json::Value = serde_json::from_slice(json).ok()?;
+        Some(Self {
+            failures: u32::try_from(value.get("failures")?.as_u64()?).ok()?,
+            next_attempt: UNIX_EPOCH + Duration::from_secs(value.get("next_attempt")?.as_u64()?),
+        })
+    }
+
+    fn to_json(&self) -> Vec<u8> {
+        let next_attempt = self
+            .next_attempt
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        serde_json::json!({ "failures": self.failures, "next_attempt": next_attempt })
+            .to_string()
+            .into_bytes()
+    }
+}
+
+/// What became of a report after an upload attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    /// Sent; the state is removed
+    Sent,
+    /// Failed; retried at the given time
+    Retry(SystemTime),
+    /// Failed for the last time; the state is removed
+    GiveUp,
+}
+
+/// Path of the retry state of report `uuid` in `database`
+fn state_path(database: &Path, uuid: &str) -> PathBuf {
+    database.join(RETRY_DIR).join(format!("{uuid}.json"))
+}
+
+/// The retry state of report `uuid`. Unreadable state is treated as none,
+/// so a corrupt file doesn't block the report forever.
+fn read_state(fs: &impl Fs, database: &Path, uuid: &str) -> io::Result<Option<RetryState>> {
+    match fs.read(&state_path(database, uuid)) {
+        Ok(json) => Ok(RetryState::parse(&json)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether report `uuid` may be sent now
+pub(crate) fn is_due(
+    fs: &impl Fs,
+    database: &Path,
+    uuid: &str,
+    clock: &impl Clock,
+) -> io::Result<bool> {
+    let now = clock.now();
+    Ok(read_state(fs, database, uuid)?.map_or(true, |state| state.next_attempt <= now))
+}
+
+/// Update the state of report `uuid` after an attempt just now that
+/// `succeeded` or not
+pub(crate) fn record_attempt(
+    fs: &impl Fs,
+    database: &Path,
+    uuid: &str,
+    policy: &RetryPolicy,
+    succeeded: bool,
+    clock: &impl Clock,
+) -> io::Result<Outcome> {
+    if succeeded {
+        remove_state(fs, database, uuid)?;
+        return Ok(Outcome::Sent);
+    }
+
+    let failures = read_state(fs, database, uuid)?.map_or(0, |state| state.failures) + 1;
+    if failures >= policy.max_attempts {
+        remove_state(fs, database, uuid)?;
+        return Ok(Outcome::GiveUp);
+    }
+
+    let state = RetryState {
+        failures,
+        next_attempt: clock.now() + policy.backoff_after(failures),
+    };
+    let path = state_path(database, uuid);
+    fs.create_dir_all(&database.join(RETRY_DIR))?;
+    let temporary = path.with_extension("json.tmp");
+    fs.write(&temporary, &state.to_json())?;
+    fs.rename(&temporary, &path)?;
+    Ok(Outcome::Retry(state.next_attempt))
+}
+
+/// Remove the retry state of report `uuid`, if any
+pub(crate) fn remove_state(fs: &impl Fs, database: &Path, uuid: &str) -> io::Result<()> {
+    match fs.remove_file(&state_path(database, uuid)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::manual::ManualClock;
+    use crate::fs::memory::MemoryFs;
+
+    const UUID: &str = "00112233-4455-6677-8899-aabbccddeeff";
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new().backoff(Duration::from_secs(10), Duration::from_secs(100));
+        assert_eq!(policy.backoff_after(1), Duration::from_secs(10));
+        assert_eq!(policy.backoff_after(2), Duration::from_secs(20));
+        assert_eq!(policy.backoff_after(4), Duration::from_secs(80));
+        assert_eq!(policy.backoff_after(5), Duration::from_secs(100));
+        assert_eq!(policy.backoff_after(64), Duration::from_secs(100));
+    }
+
+    #[test]
+    fn test_record_attempt() {
+        let fs = MemoryFs::default();
+        let database = Path::new("db");
+        let policy = RetryPolicy::new()
+            .max_attempts(3)
+            .backoff(Duration::from_secs(60), Duration::from_secs(3600));
+        let clock = ManualClock::at(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+
+        assert!(is_due(&fs, database, UUID, &clock).unwrap());
+        assert_eq!(
+            record_attempt(&fs, database, UUID, &policy, false, &clock).unwrap(),
+            Outcome::Retry(clock.now() + Duration::from_secs(60))
+        );
+        assert!(!is_due(&fs, database, UUID, &clock).unwrap());
+        clock.advance(Duration::from_secs(60));
+        assert!(is_due(&fs, database, UUID, &clock).unwrap());
+
+        assert_eq!(
+            record_attempt(&fs, database, UUID, &policy, false, &clock).unwrap(),
+            Outcome::Retry(clock.now() + Duration::from_secs(120))
+        );
+        assert_eq!(
+            record_attempt(&fs, database, UUID, &policy, false, &clock).unwrap(),
+            Outcome::GiveUp
+        );
+        assert!(!fs.exists(&state_path(database, UUID)));
+
+        // Success forgets the failures
+        record_attempt(&fs, database, UUID, &policy, false, &clock).unwrap();
+        assert_eq!(
+            record_attempt(&fs, database, UUID, &policy, true, &clock).unwrap(),
+            Outcome::Sent
+        );
+        assert!(is_due(&fs, database, UUID, &clock).unwrap());
+    }
+
+    #[test]
+    fn test_only_when() {
+        assert!(RetryPolicy::new().allows_sending());
+        assert!(!RetryPolicy::new().only_when(|| false).allows_sending());
+
+        // Unreadable state doesn't block the report
+        let fs = MemoryFs::default();
+        let database = Path::new("db");
+        fs.create_dir_all(&database.join(RETRY_DIR)).unwrap();
+        fs.write(&state_path(database, UUID), b"{").unwrap();
+        assert!(is_due(&fs, database, UUID, &ManualClock::at(UNIX_EPOCH)).unwrap());
+    }
+}
@@ -0,0 +1,130 @@
+//! Writing the client ID to the database settings.
+//!
+//! Crashpad creates a random client ID with the database and has no API to
+//! change it, so [`write_client_id`] edits its settings file directly. The
+//! file is a raw `Settings::Data` struct in native byte order: the magic
+//! `CPds`, a version, option bits, padding, the last upload attempt time and
+//! the client ID as a `UUID` struct.
+
+use std::io;
+use std::path::Path;
+
+use crate::fs::Fs;
+
+/// Settings file in the database directory
+const SETTINGS_FILE: &str = "settings.dat";
+
+/// `Settings::Data::kSettingsMagic`, the multi-character constant `'CPds'`
+const MAGIC: u32 = u32::from_be_bytes(*b"CPds");
+
+/// `Settings::Data::kSettingsVersion`
+const VERSION: u32 = 1;
+
+/// Offset of the client ID, after the magic, version, options, padding and
+/// last upload attempt time
+const CLIENT_ID: usize = 24;
+
+/// Size of `Settings::Data`
+const DATA_SIZE: usize = CLIENT_ID + 16;
+
+/// Bytes of the UUID `uuid` in its canonical form, e.g.
+/// `00112233-4455-6677-8899-aabbccddeeff`, in string order
+pub(crate) fn parse_uuid(uuid: &str) -> Option<[u8; 16]> {
+    let bytes = uuid.as_bytes();
+    if bytes.len() != 36 || [8, 13, 18, 23].iter().any(|&i| bytes[i] != b'-') {
+        return None;
+    }
+    let digits = uuid.replace('-', "");
+    if digits.len() != 32 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut parsed = [0; 16];
+    for (i, byte) in parsed.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(digits.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(parsed)
+}
+
+/// A `UUID` struct: `data_1` to `data_3` are integers in native byte order,
+/// `data_4` and `data_5` bytes
+fn uuid_struct(uuid: &[u8; 16]) -> [u8; 16] {
+    let mut data = *uuid;
+    let data_1 = u32::from_be_bytes([uuid[0], uuid[1], uuid[2], uuid[3]]);
+    data[0..4].copy_from_slice(&data_1.to_ne_bytes());
+    let data_2 = u16::from_be_bytes([uuid[4], uuid[5]]);
+    data[4..6].copy_from_slice(&data_2.to_ne_bytes());
+    let data_3 = u16::from_be_bytes([uuid[6], uuid[7]]);
+    data[6..8].copy_from_slice(&data_3.to_ne_bytes());
+    data
+}
+
+/// Set the client ID in the settings of `database` to `uuid`, keeping the
+/// other settings. Settings that are missing or unreadable are replaced
+/// with defaults, as Crashpad does. Call it before the handler starts.
+pub(crate) fn write_client_id(fs: &impl Fs, database: &Path, uuid: &[u8; 16]) -> io::Result<()> {
+    let path = database.join(SETTINGS_FILE);
+    let mut data = match fs.read(&path) {
+        Ok(data) if data.len() >= DATA_SIZE && data[0..4] == MAGIC.to_ne_bytes() => data,
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {
+            let mut data = vec![0; DATA_SIZE];
+            data[0..4].copy_from_slice(&MAGIC.to_ne_bytes());
+            data[4..8].copy_from_slice(&VERSION.to_ne_bytes());
+            data
+        }
+    };
+    data[CLIENT_ID..DATA_SIZE].copy_from_slice(&uuid_struct(uuid));
+
+    fs.create_dir_all(database)?;
+    let temporary = path.with_extension("dat.tmp");
+    fs.write(&temporary, &data)?;
+    fs.rename(&temporary, &path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::memory::MemoryFs;
+
+    const UUID: &str = "00112233-4455-6677-8899-aabbccddeeff";
+
+    #[test]
+    fn test_parse_uuid() {
+        assert_eq!(
+            parse_uuid(UUID),
+            Some([
+                0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+                0xee, 0xff
+            ])
+        );
+        assert_eq!(parse_uuid("00112233445566778899aabbccddeeff"), None);
+        assert_eq!(parse_uuid("0011223-34455-6677-8899-aabbccddeeff"), None);
+        assert_eq!(parse_uuid("00112233-4455-6677-8899-aabbccddeefg"), None);
+        assert_eq!(parse_uuid("00112233-4455-6677-8899-aabbccddeeé"), None);
+        assert_eq!(parse_uuid("00112233-4455-6677-8899-aabbccdd+eef"), None);
+    }
+
+    #[test]
+    fn test_write_client_id() {
+        let uuid = parse_uuid(UUID).unwrap();
+        let fs = MemoryFs::default();
+        let database = Path::new("db");
+
+        write_client_id(&fs, database, &uuid).unwrap();
+        let data = fs.read(&database.join(SETTINGS_FILE)).unwrap();
+        assert_eq!(data.len(), DATA_SIZE);
+        assert_eq!(data[0..4], MAGIC.to_ne_bytes());
+        assert_eq!(data[CLIENT_ID..CLIENT_ID + 4], 0x0011_2233u32.to_ne_bytes());
+        assert_eq!(data[CLIENT_ID + 8..], uuid[8..]);
+
+        // The other settings are kept
+        let mut settings = data.clone();
+        settings[8] = 1;
+        fs.write(&database.join(SETTINGS_FILE), &settings).unwrap();
+        let other = parse_uuid("ffeeddcc-bbaa-9988-7766-554433221100").unwrap();
+        write_client_id(&fs, database, &other).unwrap();
+        let data = fs.read(&database.join(SETTINGS_FILE)).unwrap();
+        assert_eq!(data[8], 1);
+        assert_eq!(data[CLIENT_ID + 8..], other[8..]);
+    }
+}
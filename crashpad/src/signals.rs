@@ -0,0 +1,303 @@
+//! Signal handler inspection on Linux and Android.
+//!
+//! Crashpad installs handlers for the crash signals in [`CRASH_SIGNALS`].
+//! Other libraries in the process (crash reporters, language runtimes) may
+//! want some of the same signals; [`installed_signal_handlers`] shows who
+//! currently owns them, and [`handler_conflicts`] compares them with what was
+//! installed when Crashpad started.
+
+use std::ffi::CStr;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Signals Crashpad installs handlers for by default.
+pub const CRASH_SIGNALS: &[i32] = &[
+    libc::SIGABRT,
+    libc::SIGBUS,
+    libc::SIGFPE,
+    libc::SIGILL,
+    libc::SIGQUIT,
+    libc::SIGSEGV,
+    libc::SIGSYS,
+    libc::SIGTRAP,
+    libc::SIGXCPU,
+    libc::SIGXFSZ,
+];
+
+/// A signal handler installed in the current process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignalHandlerInfo {
+    /// Signal number
+    pub signal: i32,
+    /// Address of the handler function
+    pub address: usize,
+    /// Shared object or executable containing the handler, if known
+    pub module: Option<PathBuf>,
+    /// Nearest exported symbol to the handler, if known
+    pub symbol: Option<String>,
+}
+
+impl fmt::Display for SignalHandlerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "signal {}: {:#x}", self.signal, self.address)?;
+        if let Some(symbol) = &self.symbol {
+            write!(f, " ({symbol})")?;
+        }
+        if let Some(module) = &self.module {
+            write!(f, " in {}", module.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// When a conflicting handler was installed relative to Crashpad's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConflictKind {
+    /// Installed before Crashpad, which replaced it
+    ///
+    /// Crashpad forwards the signal to this handler after writing a dump, but
+    /// the handler no longer runs first. Runtimes that recover from faults
+    /// themselves (JVM, Go, Mono) break this way.
+    InstalledBeforeCrashpad,
+
+    /// Installed after Crashpad, replacing Crashpad's handler
+    ///
+    /// Crashes are only reported if the new handler forwards to Crashpad.
+    InstalledAfterCrashpad,
+}
+
+/// A signal handler that competes with Crashpad's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandlerConflict {
+    pub kind: ConflictKind,
+    pub handler: SignalHandlerInfo,
+}
+
+impl fmt::Display for HandlerConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let when = match self.kind {
+            ConflictKind::InstalledBeforeCrashpad => "replaced by Crashpad",
+            ConflictKind::InstalledAfterCrashpad => "replaces Crashpad",
+        };
+        write!(f, "{} ({when})", self.handler)
+    }
+}
+
+/// Handlers seen around the most recent handler start.
+#[derive(Debug)]
+struct StartSnapshot {
+    /// Other handlers installed right before Crashpad's
+    before: Vec<SignalHandlerInfo>,
+    /// Crashpad's own handlers right after starting
+    crashpad: Vec<SignalHandlerInfo>,
+}
+
+static START_SNAPSHOT: Mutex<Option<StartSnapshot>> = Mutex::new(None);
+
+/// Remember the handlers around a successful handler start.
+pub(crate) fn record_start(before: Vec<SignalHandlerInfo>, crashpad: Vec<SignalHandlerInfo>) {
+    let mut snapshot = START_SNAPSHOT.lock().unwrap_or_else(|e| e.into_inner());
+
+    // On restart, the handlers seen before are the previous start's own
+    let before = before
+        .into_iter()
+        .filter(|handler| {
+            !snapshot.as_ref().is_some_and(|previous| {
+                previous
+                    .crashpad
+                    .iter()
+                    .any(|h| h.address == handler.address)
+            })
+        })
+        .collect();
+
+    *snapshot = Some(StartSnapshot { before, crashpad });
+}
+
+/// Report signal handlers that compete with Crashpad's.
+///
+/// Before the handler is started, every handler installed for a crash signal
+/// is reported as [`ConflictKind::InstalledBeforeCrashpad`]. After it is
+/// started, this also reports handlers that replaced Crashpad's since.
+///
+/// Call it once after initialization (and after loading other native
+/// libraries) to find out why crashes are missing or a runtime misbehaves.
+///
+/// # Example
+/// ```no_run
+/// for conflict in crashpad_rs::handler_conflicts() {
+///     eprintln!("crash handler conflict: {conflict}");
+/// }
+/// ```
+pub fn handler_conflicts() -> Vec<HandlerConflict> {
+    let snapshot = START_SNAPSHOT.lock().unwrap_or_else(|e| e.into_inner());
+    find_conflicts(snapshot.as_ref(), installed_signal_handlers(CRASH_SIGNALS))
+}
+
+fn find_conflicts(
+    snapshot: Option<&StartSnapshot>,
+    current: Vec<SignalHandlerInfo>,
+) -> Vec<HandlerConflict> {
+    let conflict = |kind| move |handler| HandlerConflict { kind, handler };
+
+    let Some(snapshot) = snapshot else {
+        return current
+            .into_iter()
+            .map(conflict(ConflictKind::InstalledBeforeCrashpad))
+            .collect();
+    };
+
+    let replaced = snapshot
+        .before
+        .iter()
+        .cloned()
+        .map(conflict(ConflictKind::InstalledBeforeCrashpad));
+
+    // A handled signal whose handler is no longer Crashpad's was taken over
+    let overriding = current
+        .into_iter()
+        .filter(|handler| {
+            snapshot
+                .crashpad
+                .iter()
+                .any(|c| c.signal == handler.signal && c.address != handler.address)
+        })
+        .map(conflict(ConflictKind::InstalledAfterCrashpad));
+
+    replaced.chain(overriding).collect()
+}
+
+/// List the handlers currently installed for `signals`.
+///
+/// Signals with the default action or ignored are left out, so an empty
+/// result means nobody else has claimed them.
+///
+/// # Example
+/// ```no_run
+/// for handler in crashpad_rs::installed_signal_handlers(crashpad_rs::CRASH_SIGNALS) {
+///     eprintln!("already installed: {handler}");
+/// }
+/// ```
+pub fn installed_signal_handlers(signals: &[i32]) -> Vec<SignalHandlerInfo> {
+    signals
+        .iter()
+        .filter_map(|&signal| installed_signal_handler(signal))
+        .collect()
+}
+
+fn installed_signal_handler(signal: i32) -> Option<SignalHandlerInfo> {
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    // Passing a null new action only queries the current one
+    if unsafe { libc::sigaction(signal, std::ptr::null(), &mut action) } != 0 {
+        return None;
+    }
+
+    let address = action.sa_sigaction;
+    if address == libc::SIG_DFL || address == libc::SIG_IGN {
+        return None;
+    }
+
+    let (module, symbol) = describe_address(address);
+    Some(SignalHandlerInfo {
+        signal,
+        address,
+        module,
+        symbol,
+    })
+}
+
+/// Find the module and symbol containing `address`.
+fn describe_address(address: usize) -> (Option<PathBuf>, Option<String>) {
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    if unsafe { libc::dladdr(address as *const libc::c_void, &mut info) } == 0 {
+        return (None, None);
+    }
+
+    let to_string = |ptr: *const libc::c_char| {
+        (!ptr.is_null()).then(|| {
+            unsafe { CStr::from_ptr(ptr) }
+                .to_string_lossy()
+                .into_owned()
+        })
+    };
+    (
+        to_string(info.dli_fname).map(PathBuf::from),
+        to_string(info.dli_sname),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn test_handler(_: libc::c_int) {}
+
+    #[test]
+    fn test_installed_signal_handlers() {
+        // SIGURG is unused by the test harness and ignored by default
+        let signal = libc::SIGURG;
+        assert!(installed_signal_handlers(&[signal]).is_empty());
+
+        let handler: extern "C" fn(libc::c_int) = test_handler;
+        let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+        action.sa_sigaction = handler as usize;
+        let mut old: libc::sigaction = unsafe { std::mem::zeroed() };
+        unsafe { libc::sigaction(signal, &action, &mut old) };
+
+        let handlers = installed_signal_handlers(&[signal]);
+        unsafe { libc::sigaction(signal, &old, std::ptr::null_mut()) };
+
+        assert_eq!(handlers.len(), 1);
+        assert_eq!(handlers[0].signal, signal);
+        assert_eq!(handlers[0].address, handler as usize);
+        assert!(handlers[0].module.is_some());
+    }
+
+    fn handler(signal: i32, address: usize) -> SignalHandlerInfo {
+        SignalHandlerInfo {
+            signal,
+            address,
+            module: None,
+            symbol: None,
+        }
+    }
+
+    #[test]
+    fn test_find_conflicts_before_start() {
+        let conflicts = find_conflicts(None, vec![handler(libc::SIGSEGV, 0x1000)]);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, ConflictKind::InstalledBeforeCrashpad);
+    }
+
+    #[test]
+    fn test_find_conflicts_after_start() {
+        let snapshot = StartSnapshot {
+            before: vec![handler(libc::SIGABRT, 0x1000)],
+            crashpad: vec![
+                handler(libc::SIGABRT, 0x2000),
+                handler(libc::SIGSEGV, 0x2000),
+            ],
+        };
+
+        // Crashpad's handlers still in place
+        let current = vec![
+            handler(libc::SIGABRT, 0x2000),
+            handler(libc::SIGSEGV, 0x2000),
+        ];
+        let conflicts = find_conflicts(Some(&snapshot), current);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, ConflictKind::InstalledBeforeCrashpad);
+        assert_eq!(conflicts[0].handler.signal, libc::SIGABRT);
+
+        // SIGSEGV taken over after start
+        let current = vec![
+            handler(libc::SIGABRT, 0x2000),
+            handler(libc::SIGSEGV, 0x3000),
+        ];
+        let conflicts = find_conflicts(Some(&snapshot), current);
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[1].kind, ConflictKind::InstalledAfterCrashpad);
+        assert_eq!(conflicts[1].handler.address, 0x3000);
+    }
+}
@@ -0,0 +1,176 @@
+//! Stack bounds of known threads, for triaging stack overflows.
+//!
+//! A stack overflow shows up as an access violation at an address that
+//! looks unrelated to the code that crashed. Recording where the stacks of
+//! the main thread and other known threads are, and how large their guard
+//! regions are, lets [`Minidump::probable_stack_overflow`] tell that the
+//! fault hit the guard region below a stack.
+//!
+//! [`Minidump::probable_stack_overflow`]: crate::minidump::Minidump::probable_stack_overflow
+
+use std::collections::HashMap;
+use std::thread;
+
+use crashpad_rs_sys::crashpad_current_thread_stack;
+
+/// Prefix of the stack annotation keys
+pub(crate) const STACK_PREFIX: &str = "stack_";
+
+/// Bounds of a thread's stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct StackBounds {
+    /// Lowest address
+    pub low: u64,
+    /// One past the highest address
+    pub high: u64,
+    /// Size of the guard region below `low`, 0 if there is none
+    pub guard_size: u64,
+}
+
+impl StackBounds {
+    fn current() -> Option<Self> {
+        let (mut low, mut high, mut guard_size) = (0, 0, 0);
+        unsafe { crashpad_current_thread_stack(&mut low, &mut high, &mut guard_size) }.then_some(
+            Self {
+                low,
+                high,
+                guard_size,
+            },
+        )
+    }
+
+    /// Whether `address` is in the guard region, or in the page below the
+    /// stack if it has none
+    pub(crate) fn guards(&self, address: u64) -> bool {
+        let guard = self.guard_size.max(4096);
+        (self.low.saturating_sub(guard)..self.low).contains(&address)
+    }
+
+    /// `0x<low>-0x<high>`, as in the `stack_<thread>` annotation
+    fn format(&self) -> String {
+        format!("{:#x}-{:#x}", self.low, self.high)
+    }
+
+    fn parse(range: &str, guard_size: Option<&str>) -> Option<Self> {
+        let hex = |value: &str| u64::from_str_radix(value.strip_prefix("0x")?, 16).ok();
+        let (low, high) = range.split_once('-')?;
+        Some(Self {
+            low: hex(low)?,
+            high: hex(high)?,
+            guard_size: guard_size.and_then(|size| size.parse().ok()).unwrap_or(0),
+        })
+    }
+}
+
+/// Annotations describing the stack of the calling thread.
+///
+/// For a thread named `<thread>` (`main` for the main thread, `unnamed`
+/// for threads without a name):
+/// - `stack_<thread>`: the stack's address range, e.g.
+///   `0x7ffd1c000000-0x7ffd1c800000`
+/// - `stack_<thread>_size`: its size in bytes
+/// - `stack_<thread>_guard`: size of the guard region below it in bytes,
+///   `0` if the stack has none
+///
+/// Empty if the bounds can't be determined. Call it from each thread whose
+/// stack should be known, or use
+/// [`stack_annotations`](crate::CrashpadConfigBuilder::stack_annotations)
+/// for the thread starting Crashpad and [`annotate_thread_stack`] in
+/// threads started later.
+pub fn stack_annotations() -> HashMap<String, String> {
+    let Some(bounds) = StackBounds::current() else {
+        return HashMap::new();
+    };
+    let key = format!("{STACK_PREFIX}{}", thread_key());
+    HashMap::from([
+        (
+            format!("{key}_size"),
+            (bounds.high - bounds.low).to_string(),
+        ),
+        (format!("{key}_guard"), bounds.guard_size.to_string()),
+        (key, bounds.format()),
+    ])
+}
+
+/// Record the stack of the calling thread in crash reports
+///
+/// Sets the [`stack_annotations`] of the thread as annotations of this
+/// module, so threads started after Crashpad can be added. Call it at the
+/// start of the thread.
+///
+/// # Example
+/// ```rust,no_run
+/// std::thread::Builder::new()
+///     .name("worker".to_string())
+///     .spawn(|| {
+///         crashpad_rs::annotate_thread_stack();
+///         // ...
+///     })
+///     .unwrap();
+/// ```
+pub fn annotate_thread_stack() {
+    for (key, value) in stack_annotations() {
+        crate::lazy_annotation::set(&key, &value, None, None);
+    }
+}
+
+/// Stack bounds recorded in `annotations` by [`stack_annotations`]
+pub(crate) fn recorded_stacks(annotations: &HashMap<String, String>) -> Vec<StackBounds> {
+    annotations
+        .iter()
+        .filter(|(key, _)| key.starts_with(STACK_PREFIX))
+        .filter_map(|(key, range)| {
+            StackBounds::parse(
+                range,
+                annotations.get(&format!("{key}_guard")).map(String::as_str),
+            )
+        })
+        .collect()
+}
+
+/// Annotation key of the calling thread
+fn thread_key() -> String {
+    thread::current()
+        .name()
+        .unwrap_or("unnamed")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stack_annotations() {
+        let annotations = thread::Builder::new()
+            .name("stack-test".to_string())
+            .spawn(stack_annotations)
+            .unwrap()
+            .join()
+            .unwrap();
+        let range = &annotations["stack_stack_test"];
+        let size: u64 = annotations["stack_stack_test_size"].parse().unwrap();
+        assert!(size > 0);
+
+        let stacks = recorded_stacks(&annotations);
+        assert_eq!(stacks.len(), 1);
+        assert_eq!(stacks[0].format(), *range);
+        assert_eq!(stacks[0].high - stacks[0].low, size);
+    }
+
+    #[test]
+    fn test_guards() {
+        let stack = StackBounds::parse("0x10000-0x20000", Some("8192")).unwrap();
+        assert!(stack.guards(0x10000 - 8192));
+        assert!(stack.guards(0xffff));
+        assert!(!stack.guards(0x10000));
+        assert!(!stack.guards(0x10000 - 8193));
+
+        // At least a page without a guard
+        let stack = StackBounds::parse("0x10000-0x20000", None).unwrap();
+        assert!(stack.guards(0xf000));
+        assert_eq!(StackBounds::parse("10000-20000", None), None);
+    }
+}
@@ -0,0 +1,169 @@
+//! Time spent starting Crashpad, and work deferred to stay within a budget.
+//!
+//! `start_with_config` measures its phases and records them for
+//! [`last_startup`]. With a
+//! [`startup_budget`](crate::CrashpadConfigBuilder::startup_budget), optional
+//! work that would begin after the budget is spent runs on a background
+//! thread once the handler is running instead of delaying the caller.
+
+use std::mem;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+static LAST_STARTUP: Mutex<Option<StartupTiming>> = Mutex::new(None);
+
+/// Optional work of `start_with_config` that can be deferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeferredTask {
+    /// The `crashpad_handler_version` annotation, set as a module
+    /// annotation once known instead of being passed to the handler
+    HandlerVersion,
+    /// [`database_janitor`](crate::CrashpadConfigBuilder::database_janitor)
+    Janitor,
+    /// [`crash_burst_limit`](crate::CrashpadConfigBuilder::crash_burst_limit),
+    /// which may then run while the handler uploads
+    BurstCoalescing,
+    /// [`expire_pending_reports`](crate::CrashpadConfigBuilder::expire_pending_reports),
+    /// which may then run while the handler uploads
+    ReportExpiry,
+}
+
+/// How long the phases of the last successful start took.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StartupTiming {
+    /// Finding and verifying the handler, and completing the annotations
+    pub handler_resolution: Duration,
+    /// Creating directories, writing settings and rotating the handler log
+    pub settings_io: Duration,
+    /// Janitor, report expiry and burst coalescing, when not deferred
+    pub maintenance: Duration,
+    /// Starting the handler, or connecting to it
+    pub spawn: Duration,
+    /// Work moved to a background thread because the budget was spent
+    pub deferred: Vec<DeferredTask>,
+}
+
+impl StartupTiming {
+    /// Time `start_with_config` took in total
+    pub fn total(&self) -> Duration {
+        self.handler_resolution + self.settings_io + self.maintenance + self.spawn
+    }
+}
+
+/// Timing of the last successful `start_with_config`, `None` before the
+/// first
+///
+/// # Example
+/// ```rust,no_run
+/// if let Some(timing) = crashpad_rs::last_startup() {
+///     println!("Crashpad took {:?}, deferred {:?}", timing.total(), timing.deferred);
+/// }
+/// ```
+pub fn last_startup() -> Option<StartupTiming> {
+    LAST_STARTUP
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+type Task = Box<dyn FnOnce() + Send>;
+
+/// Clock and deferred work of one start.
+pub(crate) struct Startup {
+    budget: Option<Duration>,
+    started: Instant,
+    phase_started: Instant,
+    timing: StartupTiming,
+    deferred: Vec<Task>,
+}
+
+impl Startup {
+    pub(crate) fn new(budget: Option<Duration>) -> Self {
+        let now = Instant::now();
+        Self {
+            budget,
+            started: now,
+            phase_started: now,
+            timing: StartupTiming::default(),
+            deferred: Vec::new(),
+        }
+    }
+
+    /// Whether optional work should be deferred, i.e. the budget is spent
+    pub(crate) fn over_budget(&self) -> bool {
+        self.budget
+            .is_some_and(|budget| self.started.elapsed() >= budget)
+    }
+
+    /// Run `task` after the handler started, on a background thread
+    pub(crate) fn defer(&mut self, kind: DeferredTask, task: impl FnOnce() + Send + 'static) {
+        self.timing.deferred.push(kind);
+        self.deferred.push(Box::new(task));
+    }
+
+    /// End the current phase, adding its time to the field `phase` selects
+    pub(crate) fn end_phase(&mut self, phase: fn(&mut StartupTiming) -> &mut Duration) {
+        let now = Instant::now();
+        *phase(&mut self.timing) += now - self.phase_started;
+        self.phase_started = now;
+    }
+
+    /// Record the timing for [`last_startup`] and start the deferred work
+    pub(crate) fn finish(mut self) {
+        let deferred = mem::take(&mut self.deferred);
+        *LAST_STARTUP.lock().unwrap_or_else(|e| e.into_inner()) = Some(self.timing);
+
+        if deferred.is_empty() {
+            return;
+        }
+        // Without a thread, the work is skipped like a failed janitor run
+        let _ = thread::Builder::new()
+            .name("crashpad-deferred-startup".to_string())
+            .spawn(move || {
+                for task in deferred {
+                    task();
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_no_budget() {
+        let startup = Startup::new(None);
+        thread::sleep(Duration::from_millis(5));
+        assert!(!startup.over_budget());
+    }
+
+    #[test]
+    fn test_over_budget() {
+        let startup = Startup::new(Some(Duration::ZERO));
+        assert!(startup.over_budget());
+        let startup = Startup::new(Some(Duration::from_secs(3600)));
+        assert!(!startup.over_budget());
+    }
+
+    #[test]
+    fn test_phases_and_deferred_work() {
+        let mut startup = Startup::new(Some(Duration::ZERO));
+        thread::sleep(Duration::from_millis(5));
+        startup.end_phase(|timing| &mut timing.handler_resolution);
+        startup.end_phase(|timing| &mut timing.spawn);
+
+        let (tx, rx) = mpsc::channel();
+        startup.defer(DeferredTask::Janitor, move || tx.send(()).unwrap());
+        let timing = startup.timing.clone();
+        assert!(timing.handler_resolution >= Duration::from_millis(5));
+        assert!(timing.total() >= timing.handler_resolution + timing.spawn);
+        assert_eq!(timing.deferred, [DeferredTask::Janitor]);
+
+        startup.finish();
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(last_startup().is_some());
+    }
+}
@@ -0,0 +1,999 @@
+//! Local symbolication of minidumps.
+//!
+//! [`Symbolizer`] unwinds a thread of a [`Minidump`] and resolves its
+//! frames to functions and source lines, without a crash server. Symbols
+//! come from Breakpad symbol files (`.sym`, as written by `dump_syms`) in a
+//! symbol store, or from the DWARF debug info of the binaries themselves.
+//! Dumps of x86-64 and ARM64 processes are supported.
+//!
+//! ```no_run
+//! use crashpad_rs::minidump::Minidump;
+//! use crashpad_rs::symbolicate::Symbolizer;
+//!
+//! let dump = Minidump::open("./crash.dmp")?;
+//! let trace = Symbolizer::new()
+//!     .symbol_path("./symbols")
+//!     .binary_path("./target/debug")
+//!     .stack_trace(&dump)?;
+//! println!("{trace}");
+//! # Ok::<(), crashpad_rs::CrashpadError>(())
+//! ```
+//!
+//! Frames are unwound with the call frame information (`STACK CFI`) of the
+//! symbol files where available, and by following frame pointers
+//! otherwise. Without either, the trace stops at the crashing frame.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::minidump::{self, Exception, Minidump, Module};
+use crate::Result;
+
+/// Frames after which unwinding stops, e.g. on stack corruption
+const MAX_FRAMES: usize = 256;
+
+/// `PROCESSOR_ARCHITECTURE_*` values of the system info stream
+const PROCESSOR_AMD64: u16 = 9;
+const PROCESSOR_ARM64: u16 = 12;
+const PROCESSOR_ARM64_BREAKPAD: u16 = 0x8003;
+
+/// How the address of a frame was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameTrust {
+    /// From the thread's CPU context
+    Context,
+    /// Unwound with call frame information from a symbol file
+    CallFrameInfo,
+    /// Unwound by following frame pointers
+    FramePointer,
+}
+
+/// A frame of a [`StackTrace`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Instruction pointer: the faulting instruction for the first frame,
+    /// the return address for the others
+    pub address: u64,
+    /// File name of the module containing the address
+    pub module: Option<String>,
+    /// Offset of the address in the module
+    pub module_offset: Option<u64>,
+    /// Function name, demangled when it comes from DWARF
+    pub function: Option<String>,
+    /// Source file, as recorded in the symbols
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub trust: FrameTrust,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#018x}", self.address)?;
+        if let Some(function) = &self.function {
+            write!(f, " in {function}")?;
+        }
+        if let Some(file) = &self.file {
+            write!(f, " at {file}")?;
+            if let Some(line) = self.line {
+                write!(f, ":{line}")?;
+            }
+        }
+        if let (Some(module), Some(offset)) = (&self.module, self.module_offset) {
+            write!(f, " ({module}+{offset:#x})")?;
+        }
+        Ok(())
+    }
+}
+
+/// The symbolicated stack of a thread, innermost frame first.
+///
+/// Its [`Display`](fmt::Display) output is a human-readable trace:
+///
+/// ```text
+/// Thread 7 "main", exception 0x0000000b at 0x0000555500001a2b
+///  #0  0x0000555500001a2b in crash_here at src/main.rs:42 (app+0x1a2b)
+///  #1  0x0000555500001c00 in main at src/main.rs:12 (app+0x1c00)
+///  #2  0x00007f3e4c229d90 (libc.so.6+0x29d90)
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackTrace {
+    pub thread_id: u32,
+    pub thread_name: Option<String>,
+    /// The exception, if it happened on this thread
+    pub exception: Option<Exception>,
+    pub frames: Vec<Frame>,
+}
+
+impl fmt::Display for StackTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Thread {}", self.thread_id)?;
+        if let Some(name) = &self.thread_name {
+            write!(f, " \"{name}\"")?;
+        }
+        if let Some(exception) = &self.exception {
+            write!(
+                f,
+                ", exception {:#010x} at {:#018x}",
+                exception.code, exception.address
+            )?;
+        }
+        writeln!(f)?;
+        for (index, frame) in self.frames.iter().enumerate() {
+            writeln!(f, " #{index:<3}{frame}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Unwinds and symbolicates minidumps with local symbols.
+///
+/// # Example
+///
+/// ```no_run
+/// use crashpad_rs::minidump::Minidump;
+/// use crashpad_rs::symbolicate::Symbolizer;
+///
+/// let symbolizer = Symbolizer::new().symbol_path("/var/symbols");
+/// let dump = Minidump::open("./crash.dmp")?;
+/// for frame in symbolizer.stack_trace(&dump)?.frames {
+///     println!("{}", frame.function.as_deref().unwrap_or("??"));
+/// }
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Symbolizer {
+    symbol_paths: Vec<PathBuf>,
+    binary_paths: Vec<PathBuf>,
+}
+
+impl Symbolizer {
+    /// A symbolizer using only the DWARF of the binaries at the paths
+    /// recorded in the dump
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look for Breakpad symbol files in the symbol store `dir`, laid out
+    /// as `<debug file>/<debug ID>/<debug file>.sym`, with `.pdb` replaced
+    /// by `.sym` on Windows
+    ///
+    /// Can be called several times; symbol files take precedence over
+    /// DWARF.
+    pub fn symbol_path<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.symbol_paths.push(dir.into());
+        self
+    }
+
+    /// Look for binaries with DWARF debug info in `dir`, by module file name
+    ///
+    /// For dumps from another machine, or binaries moved since. Binaries
+    /// whose build ID doesn't match the module in the dump are ignored.
+    ///
+    /// # Platform Behavior
+    ///
+    /// - **Windows**: only binaries with DWARF (e.g. built with MinGW);
+    ///   PDB files are not read, convert them to `.sym` files with
+    ///   `dump_syms` instead
+    /// - **macOS**: `.dSYM` bundles next to the binary are used
+    pub fn binary_path<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.binary_paths.push(dir.into());
+        self
+    }
+
+    /// Stack trace of the thread that crashed, or of the first thread for a
+    /// dump without an exception
+    pub fn stack_trace(&self, dump: &Minidump) -> Result<StackTrace> {
+        let thread_id = match dump.exception()? {
+            Some(exception) => exception.thread_id,
+            None => dump
+                .threads()?
+                .first()
+                .map(|thread| thread.id)
+                .ok_or_else(|| invalid("minidump has no threads"))?,
+        };
+        self.thread_stack_trace(dump, thread_id)
+    }
+
+    /// Stack trace of thread `thread_id`
+    pub fn thread_stack_trace(&self, dump: &Minidump, thread_id: u32) -> Result<StackTrace> {
+        let thread = dump
+            .threads()?
+            .into_iter()
+            .find(|thread| thread.id == thread_id)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no thread {thread_id} in minidump"),
+                )
+            })?;
+        let exception = dump
+            .exception()?
+            .filter(|exception| exception.thread_id == thread_id);
+        let arch = match dump.processor_architecture() {
+            Some(PROCESSOR_AMD64) => Arch::Amd64,
+            Some(PROCESSOR_ARM64 | PROCESSOR_ARM64_BREAKPAD) => Arch::Arm64,
+            Some(other) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("unsupported processor architecture {other}"),
+                )
+                .into())
+            }
+            None => return Err(invalid("minidump has no system info").into()),
+        };
+        let registers = dump
+            .thread_context(thread_id)
+            .and_then(|context| arch.registers(context))
+            .ok_or_else(|| invalid(format!("no CPU context for thread {thread_id}")))?;
+        let stack = dump
+            .thread_stack(thread_id)
+            .map(|(start, bytes)| Stack { start, bytes })
+            .unwrap_or(Stack {
+                start: 0,
+                bytes: &[],
+            });
+
+        let mut resolver = Resolver {
+            symbolizer: self,
+            modules: dump.modules()?,
+            symbols: HashMap::new(),
+        };
+        let frames = unwind(arch, registers, &stack, &mut resolver);
+
+        Ok(StackTrace {
+            thread_id,
+            thread_name: thread.name,
+            exception,
+            frames,
+        })
+    }
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Register values by their Breakpad names, e.g. `$rip` or `x29`
+type Registers = HashMap<String, u64>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arch {
+    Amd64,
+    Arm64,
+}
+
+impl Arch {
+    fn pc(self) -> &'static str {
+        match self {
+            Arch::Amd64 => "$rip",
+            Arch::Arm64 => "pc",
+        }
+    }
+
+    fn sp(self) -> &'static str {
+        match self {
+            Arch::Amd64 => "$rsp",
+            Arch::Arm64 => "sp",
+        }
+    }
+
+    fn fp(self) -> &'static str {
+        match self {
+            Arch::Amd64 => "$rbp",
+            Arch::Arm64 => "x29",
+        }
+    }
+
+    /// Registers a callee preserves, kept for callers without a CFI rule
+    fn callee_saved(self) -> &'static [&'static str] {
+        match self {
+            Arch::Amd64 => &["$rbx", "$rbp", "$r12", "$r13", "$r14", "$r15"],
+            Arch::Arm64 => &[
+                "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28", "x29",
+            ],
+        }
+    }
+
+    /// Registers of a `CONTEXT_AMD64` or `MINIDUMP_CONTEXT_ARM64`
+    fn registers(self, context: &[u8]) -> Option<Registers> {
+        let read = |offset: usize| -> Option<u64> {
+            let bytes = context.get(offset..offset + 8)?;
+            Some(u64::from_le_bytes(bytes.try_into().ok()?))
+        };
+        let mut registers = Registers::new();
+        match self {
+            Arch::Amd64 => {
+                let names = [
+                    "$rax", "$rcx", "$rdx", "$rbx", "$rsp", "$rbp", "$rsi", "$rdi", "$r8", "$r9",
+                    "$r10", "$r11", "$r12", "$r13", "$r14", "$r15", "$rip",
+                ];
+                for (index, name) in names.into_iter().enumerate() {
+                    registers.insert(name.to_string(), read(120 + index * 8)?);
+                }
+            }
+            Arch::Arm64 => {
+                for index in 0..31 {
+                    registers.insert(format!("x{index}"), read(8 + index * 8)?);
+                }
+                registers.insert("sp".to_string(), read(256)?);
+                registers.insert("pc".to_string(), read(264)?);
+            }
+        }
+        Some(registers)
+    }
+
+    /// Code address without pointer authentication bits
+    fn strip(self, address: u64) -> u64 {
+        match self {
+            Arch::Amd64 => address,
+            Arch::Arm64 => address & 0x0000_ffff_ffff_ffff,
+        }
+    }
+}
+
+/// Stack memory captured in the dump.
+struct Stack<'a> {
+    start: u64,
+    bytes: &'a [u8],
+}
+
+impl Stack<'_> {
+    fn read(&self, address: u64) -> Option<u64> {
+        let offset = usize::try_from(address.checked_sub(self.start)?).ok()?;
+        let bytes = self.bytes.get(offset..offset.checked_add(8)?)?;
+        Some(u64::from_le_bytes(bytes.try_into().ok()?))
+    }
+}
+
+fn unwind(
+    arch: Arch,
+    mut registers: Registers,
+    stack: &Stack,
+    resolver: &mut Resolver,
+) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    let mut trust = FrameTrust::Context;
+    while frames.len() < MAX_FRAMES {
+        let Some(pc) = registers.get(arch.pc()).map(|&pc| arch.strip(pc)) else {
+            break;
+        };
+        if pc == 0 {
+            break;
+        }
+        // Return addresses point after the call, which may be the first
+        // instruction of the next function
+        let lookup = if frames.is_empty() { pc } else { pc - 1 };
+        let module = resolver.module(lookup);
+        let mut frame = Frame {
+            address: pc,
+            module: None,
+            module_offset: None,
+            function: None,
+            file: None,
+            line: None,
+            trust,
+        };
+        let mut rules = None;
+        if let Some(index) = module {
+            let module = &resolver.modules[index];
+            let offset = lookup - module.base_address;
+            frame.module = Some(module.file_name().to_string());
+            frame.module_offset = Some(pc - module.base_address);
+            match resolver.symbols(index) {
+                Some(Symbols::Breakpad(symbols)) => {
+                    symbols.resolve(offset, &mut frame);
+                    rules = symbols.cfi_rules(offset);
+                }
+                Some(Symbols::Dwarf(dwarf)) => dwarf.resolve(offset, &mut frame),
+                None => {}
+            }
+        }
+        frames.push(frame);
+
+        let sp = registers.get(arch.sp()).copied().unwrap_or_default();
+        let caller = rules
+            .and_then(|rules| cfi_step(arch, &registers, &rules, stack))
+            .map(|registers| (registers, FrameTrust::CallFrameInfo))
+            .or_else(|| {
+                frame_pointer_step(arch, &registers, stack).map(|r| (r, FrameTrust::FramePointer))
+            });
+        match caller {
+            // The stack grows down; anything else is a loop or garbage
+            Some((caller, caller_trust))
+                if caller
+                    .get(arch.sp())
+                    .is_some_and(|&caller_sp| caller_sp > sp) =>
+            {
+                registers = caller;
+                trust = caller_trust;
+            }
+            _ => break,
+        }
+    }
+    frames
+}
+
+/// Caller registers from the `STACK CFI` rules of the callee
+fn cfi_step(
+    arch: Arch,
+    registers: &Registers,
+    rules: &[(String, String)],
+    stack: &Stack,
+) -> Option<Registers> {
+    let (_, cfa) = rules.iter().find(|(register, _)| register == ".cfa")?;
+    let cfa = evaluate(cfa, registers, stack)?;
+    let mut callee = registers.clone();
+    callee.insert(".cfa".to_string(), cfa);
+
+    let mut caller = Registers::new();
+    for register in arch.callee_saved() {
+        if let Some(&value) = registers.get(*register) {
+            caller.insert(register.to_string(), value);
+        }
+    }
+    caller.insert(arch.sp().to_string(), cfa);
+    for (register, expression) in rules {
+        let value = match register.as_str() {
+            ".cfa" => continue,
+            ".ra" => (arch.pc(), evaluate(expression, &callee, stack)?),
+            register => match evaluate(expression, &callee, stack) {
+                Some(value) => (register, value),
+                None => continue,
+            },
+        };
+        caller.insert(value.0.to_string(), value.1);
+    }
+    caller.contains_key(arch.pc()).then_some(caller)
+}
+
+/// Caller registers from the frame record at the frame pointer: the saved
+/// frame pointer, followed by the return address
+fn frame_pointer_step(arch: Arch, registers: &Registers, stack: &Stack) -> Option<Registers> {
+    let fp = *registers.get(arch.fp())?;
+    let caller_fp = stack.read(fp)?;
+    let caller_pc = stack.read(fp.checked_add(8)?)?;
+
+    let mut caller = Registers::new();
+    for register in arch.callee_saved() {
+        if let Some(&value) = registers.get(*register) {
+            caller.insert(register.to_string(), value);
+        }
+    }
+    caller.insert(arch.fp().to_string(), caller_fp);
+    caller.insert(arch.pc().to_string(), caller_pc);
+    caller.insert(arch.sp().to_string(), fp.checked_add(16)?);
+    Some(caller)
+}
+
+/// Evaluate a Breakpad postfix expression, e.g. `$rsp 8 + ^`
+fn evaluate(expression: &str, registers: &Registers, stack: &Stack) -> Option<u64> {
+    let mut operands: Vec<u64> = Vec::new();
+    for token in expression.split_whitespace() {
+        let value = match token {
+            "+" | "-" | "*" | "/" | "%" | "@" => {
+                let b = operands.pop()?;
+                let a = operands.pop()?;
+                match token {
+                    "+" => a.wrapping_add(b),
+                    "-" => a.wrapping_sub(b),
+                    "*" => a.wrapping_mul(b),
+                    "/" => a.checked_div(b)?,
+                    "%" => a.checked_rem(b)?,
+                    // Align down to a power of two
+                    _ => a & !b.wrapping_sub(1),
+                }
+            }
+            "^" => stack.read(operands.pop()?)?,
+            token => match token.parse::<i64>() {
+                Ok(number) => number as u64,
+                Err(_) => *registers.get(token)?,
+            },
+        };
+        operands.push(value);
+    }
+    match operands[..] {
+        [value] => Some(value),
+        _ => None,
+    }
+}
+
+/// Symbols of the modules of a dump, loaded on first use.
+struct Resolver<'a> {
+    symbolizer: &'a Symbolizer,
+    modules: Vec<Module>,
+    symbols: HashMap<usize, Option<Symbols>>,
+}
+
+impl Resolver<'_> {
+    fn module(&self, address: u64) -> Option<usize> {
+        self.modules
+            .iter()
+            .position(|module| module.contains(address))
+    }
+
+    fn symbols(&mut self, index: usize) -> Option<&Symbols> {
+        let module = &self.modules[index];
+        let symbolizer = self.symbolizer;
+        self.symbols
+            .entry(index)
+            .or_insert_with(|| {
+                symbolizer
+                    .symbol_file(module)
+                    .map(Symbols::Breakpad)
+                    .or_else(|| {
+                        symbolizer
+                            .dwarf(module)
+                            .map(|dwarf| Symbols::Dwarf(Box::new(dwarf)))
+                    })
+            })
+            .as_ref()
+    }
+}
+
+enum Symbols {
+    Breakpad(SymbolFile),
+    Dwarf(Box<Dwarf>),
+}
+
+impl Symbolizer {
+    fn symbol_file(&self, module: &Module) -> Option<SymbolFile> {
+        let debug_file = module.debug_file.as_deref()?;
+        let debug_id = module.debug_id.as_deref()?;
+        let stem = match debug_file.len().checked_sub(4) {
+            Some(at) if debug_file[at..].eq_ignore_ascii_case(".pdb") => &debug_file[..at],
+            _ => debug_file,
+        };
+        self.symbol_paths.iter().find_map(|dir| {
+            let path = dir
+                .join(debug_file)
+                .join(debug_id)
+                .join(format!("{stem}.sym"));
+            let text = std::fs::read_to_string(path).ok()?;
+            Some(SymbolFile::parse(&text))
+        })
+    }
+
+    fn dwarf(&self, module: &Module) -> Option<Dwarf> {
+        let recorded = PathBuf::from(&module.name);
+        let candidates = std::iter::once(recorded).chain(
+            self.binary_paths
+                .iter()
+                .map(|dir| dir.join(module.file_name())),
+        );
+        candidates
+            .filter(|path| path.is_file())
+            .find_map(|path| Dwarf::load(&path, module.debug_id.as_deref()))
+    }
+}
+
+/// A function of a symbol file.
+#[derive(Debug, PartialEq, Eq)]
+struct Function {
+    address: u64,
+    size: u64,
+    name: String,
+    /// Address, size, line and file number, sorted by address
+    lines: Vec<(u64, u64, u32, u32)>,
+}
+
+/// `STACK CFI INIT` record with the `STACK CFI` records that follow it.
+#[derive(Debug, PartialEq, Eq)]
+struct CfiRange {
+    address: u64,
+    size: u64,
+    rules: String,
+    deltas: Vec<(u64, String)>,
+}
+
+/// The parts of a Breakpad symbol file used for stack traces.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SymbolFile {
+    files: HashMap<u32, String>,
+    functions: Vec<Function>,
+    publics: Vec<(u64, String)>,
+    cfi: Vec<CfiRange>,
+}
+
+impl SymbolFile {
+    /// Parse the text of a `.sym` file, skipping malformed records
+    fn parse(text: &str) -> Self {
+        let hex = |value: &str| u64::from_str_radix(value, 16).ok();
+        let mut symbols = Self::default();
+        for line in text.lines() {
+            let line = line.trim_end();
+            if let Some(rest) = line.strip_prefix("FILE ") {
+                if let Some((number, name)) = rest.split_once(' ') {
+                    if let Ok(number) = number.parse() {
+                        symbols.files.insert(number, name.to_string());
+                    }
+                }
+            } else if let Some(rest) = line.strip_prefix("FUNC ") {
+                let rest = rest.strip_prefix("m ").unwrap_or(rest);
+                let mut fields = rest.splitn(4, ' ');
+                if let (Some(address), Some(size), Some(_), Some(name)) =
+                    (fields.next(), fields.next(), fields.next(), fields.next())
+                {
+                    if let (Some(address), Some(size)) = (hex(address), hex(size)) {
+                        symbols.functions.push(Function {
+                            address,
+                            size,
+                            name: name.to_string(),
+                            lines: Vec::new(),
+                        });
+                    }
+                }
+            } else if let Some(rest) = line.strip_prefix("PUBLIC ") {
+                let rest = rest.strip_prefix("m ").unwrap_or(rest);
+                let mut fields = rest.splitn(3, ' ');
+                if let (Some(address), Some(_), Some(name)) =
+                    (fields.next(), fields.next(), fields.next())
+                {
+                    if let Some(address) = hex(address) {
+                        symbols.publics.push((address, name.to_string()));
+                    }
+                }
+            } else if let Some(rest) = line.strip_prefix("STACK CFI INIT ") {
+                let mut fields = rest.splitn(3, ' ');
+                if let (Some(address), Some(size), Some(rules)) =
+                    (fields.next(), fields.next(), fields.next())
+                {
+                    if let (Some(address), Some(size)) = (hex(address), hex(size)) {
+                        symbols.cfi.push(CfiRange {
+                            address,
+                            size,
+                            rules: rules.to_string(),
+                            deltas: Vec::new(),
+                        });
+                    }
+                }
+            } else if let Some(rest) = line.strip_prefix("STACK CFI ") {
+                if let (Some((address, rules)), Some(range)) =
+                    (rest.split_once(' '), symbols.cfi.last_mut())
+                {
+                    if let Some(address) = hex(address) {
+                        range.deltas.push((address, rules.to_string()));
+                    }
+                }
+            } else if line.starts_with(|c: char| c.is_ascii_hexdigit()) {
+                // Line record of the preceding FUNC
+                let fields: Vec<&str> = line.split(' ').collect();
+                if let ([address, size, number, file], Some(function)) =
+                    (&fields[..], symbols.functions.last_mut())
+                {
+                    if let (Some(address), Some(size), Ok(number), Ok(file)) =
+                        (hex(address), hex(size), number.parse(), file.parse())
+                    {
+                        function.lines.push((address, size, number, file));
+                    }
+                }
+            }
+        }
+        symbols.functions.sort_by_key(|function| function.address);
+        for function in &mut symbols.functions {
+            function.lines.sort_by_key(|line| line.0);
+        }
+        symbols.publics.sort_by_key(|public| public.0);
+        symbols.cfi.sort_by_key(|range| range.address);
+        symbols
+    }
+
+    fn resolve(&self, offset: u64, frame: &mut Frame) {
+        let function = self.functions[..self
+            .functions
+            .partition_point(|function| function.address <= offset)]
+            .last()
+            .filter(|function| offset - function.address < function.size);
+        let Some(function) = function else {
+            let public =
+                self.publics[..self.publics.partition_point(|public| public.0 <= offset)].last();
+            frame.function = public.map(|(_, name)| name.clone());
+            return;
+        };
+        frame.function = Some(function.name.clone());
+        let line = function.lines[..function.lines.partition_point(|line| line.0 <= offset)]
+            .last()
+            .filter(|line| offset - line.0 < line.1);
+        if let Some(&(_, _, number, file)) = line {
+            frame.file = self.files.get(&file).cloned();
+            frame.line = Some(number);
+        }
+    }
+
+    /// CFI rules in effect at `offset`, by register
+    fn cfi_rules(&self, offset: u64) -> Option<Vec<(String, String)>> {
+        let range = self.cfi[..self.cfi.partition_point(|range| range.address <= offset)]
+            .last()
+            .filter(|range| offset - range.address < range.size)?;
+        let mut rules: Vec<(String, String)> = Vec::new();
+        let deltas = range
+            .deltas
+            .iter()
+            .take_while(|(address, _)| *address <= offset)
+            .map(|(_, rules)| rules);
+        for text in std::iter::once(&range.rules).chain(deltas) {
+            for (register, expression) in parse_rules(text) {
+                match rules.iter_mut().find(|(known, _)| *known == register) {
+                    Some(rule) => rule.1 = expression,
+                    None => rules.push((register, expression)),
+                }
+            }
+        }
+        Some(rules)
+    }
+}
+
+/// Split `.cfa: $rsp 8 + .ra: .cfa -8 + ^` into registers and expressions
+fn parse_rules(text: &str) -> Vec<(String, String)> {
+    let mut rules: Vec<(String, String)> = Vec::new();
+    for token in text.split_whitespace() {
+        match (token.strip_suffix(':'), rules.last_mut()) {
+            (Some(register), _) => rules.push((register.to_string(), String::new())),
+            (None, Some((_, expression))) => {
+                if !expression.is_empty() {
+                    expression.push(' ');
+                }
+                expression.push_str(token);
+            }
+            (None, None) => {}
+        }
+    }
+    rules
+}
+
+/// DWARF debug info of a binary.
+struct Dwarf {
+    loader: addr2line::Loader,
+    /// Address the binary is linked at, corresponding to the module base
+    image_base: u64,
+}
+
+impl Dwarf {
+    /// Load the DWARF of the binary at `path`, if it matches `debug_id`
+    fn load(path: &Path, debug_id: Option<&str>) -> Option<Self> {
+        use object::{Object, ObjectSegment};
+
+        let data = std::fs::read(path).ok()?;
+        let file = object::File::parse(&*data).ok()?;
+        if let (Some(expected), Some(actual)) = (debug_id, object_debug_id(&file)) {
+            if !expected.eq_ignore_ascii_case(&actual) {
+                return None;
+            }
+        }
+        let image_base = match file.relative_address_base() {
+            0 => file
+                .segments()
+                .filter(|segment| segment.file_range().1 > 0)
+                .map(|segment| segment.address())
+                .min()
+                .unwrap_or(0),
+            base => base,
+        };
+        let loader = addr2line::Loader::new(path).ok()?;
+        Some(Self { loader, image_base })
+    }
+
+    fn resolve(&self, offset: u64, frame: &mut Frame) {
+        let probe = self.image_base.wrapping_add(offset);
+        // The innermost inlined function, where the code actually is
+        if let Ok(mut frames) = self.loader.find_frames(probe) {
+            if let Ok(Some(found)) = frames.next() {
+                frame.function = found
+                    .function
+                    .and_then(|function| function.demangle().ok().map(|name| name.into_owned()));
+                if let Some(location) = found.location {
+                    frame.file = location.file.map(str::to_string);
+                    frame.line = location.line;
+                }
+            }
+        }
+        if frame.function.is_none() {
+            frame.function = self
+                .loader
+                .find_symbol(probe)
+                .map(|name| addr2line::demangle_auto(name.into(), None).into_owned());
+        }
+    }
+}
+
+/// Breakpad debug ID of a binary, as the module's CodeView record has it
+fn object_debug_id(file: &object::File) -> Option<String> {
+    use object::Object;
+
+    if let Ok(Some(pdb)) = file.pdb_info() {
+        return Some(minidump::debug_id(&pdb.guid(), pdb.age()));
+    }
+    if let Ok(Some(uuid)) = file.mach_uuid() {
+        // Crashpad stores the UUID in byte order, not as a GUID
+        let hex: String = uuid.iter().map(|b| format!("{b:02X}")).collect();
+        return Some(format!("{hex}0"));
+    }
+    match file.build_id() {
+        Ok(Some(build_id)) => Some(minidump::debug_id(build_id, 0)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minidump::tests::{amd64_crash_dump, STACK_START};
+
+    const SYMBOLS: &str = "\
+MODULE Linux x86_64 030201000504070608090A0B0C0D0E0F0 app
+INFO CODE_ID 000102030405060708090A0B0C0D0E0F
+FILE 0 src/main.rs
+FILE 1 src/lib.rs
+FUNC 1000 100 0 app::crash_here
+1000 20 41 0
+1020 e0 42 0
+FUNC m 1100 80 0 app::main
+1100 80 12 1
+PUBLIC 2000 0 _start
+STACK CFI INIT 1000 100 .cfa: $rsp 8 + .ra: .cfa -8 + ^
+STACK CFI 1001 .cfa: $rsp 16 + $rbp: .cfa -16 + ^
+";
+
+    fn registers(values: &[(&str, u64)]) -> Registers {
+        values
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_symbol_file() {
+        let symbols = SymbolFile::parse(SYMBOLS);
+        assert_eq!(symbols.functions.len(), 2);
+        assert_eq!(symbols.functions[1].name, "app::main");
+        assert_eq!(symbols.publics, vec![(0x2000, "_start".to_string())]);
+
+        let frame = |offset| {
+            let mut frame = Frame {
+                address: 0,
+                module: None,
+                module_offset: None,
+                function: None,
+                file: None,
+                line: None,
+                trust: FrameTrust::Context,
+            };
+            symbols.resolve(offset, &mut frame);
+            (frame.function, frame.file, frame.line)
+        };
+        assert_eq!(
+            frame(0x1025),
+            (
+                Some("app::crash_here".to_string()),
+                Some("src/main.rs".to_string()),
+                Some(42)
+            )
+        );
+        assert_eq!(
+            frame(0x1100),
+            (
+                Some("app::main".to_string()),
+                Some("src/lib.rs".to_string()),
+                Some(12)
+            )
+        );
+        assert_eq!(frame(0x2100), (Some("_start".to_string()), None, None));
+        assert_eq!(frame(0x10), (None, None, None));
+    }
+
+    #[test]
+    fn test_cfi_rules() {
+        let symbols = SymbolFile::parse(SYMBOLS);
+        assert_eq!(
+            symbols.cfi_rules(0x1000).unwrap(),
+            vec![
+                (".cfa".to_string(), "$rsp 8 +".to_string()),
+                (".ra".to_string(), ".cfa -8 + ^".to_string()),
+            ]
+        );
+        // The delta replaces the CFA rule and adds one for rbp
+        assert_eq!(
+            symbols.cfi_rules(0x1050).unwrap(),
+            vec![
+                (".cfa".to_string(), "$rsp 16 +".to_string()),
+                (".ra".to_string(), ".cfa -8 + ^".to_string()),
+                ("$rbp".to_string(), ".cfa -16 + ^".to_string()),
+            ]
+        );
+        assert_eq!(symbols.cfi_rules(0x1100), None);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let words: Vec<u8> = [0x1111u64, 0x2222]
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect();
+        let stack = Stack {
+            start: 0x1000,
+            bytes: &words,
+        };
+        let registers = registers(&[("$rsp", 0x1000), (".cfa", 0x1010)]);
+
+        assert_eq!(evaluate("$rsp 8 +", &registers, &stack), Some(0x1008));
+        assert_eq!(evaluate("$rsp 8 + ^", &registers, &stack), Some(0x2222));
+        assert_eq!(evaluate(".cfa -16 + ^", &registers, &stack), Some(0x1111));
+        assert_eq!(evaluate("$rsp 4 - 16 @", &registers, &stack), Some(0xff0));
+
+        // Unknown register, memory outside the stack, malformed
+        assert_eq!(evaluate("$r99", &registers, &stack), None);
+        assert_eq!(evaluate("$rsp 64 + ^", &registers, &stack), None);
+        assert_eq!(evaluate("$rsp +", &registers, &stack), None);
+        assert_eq!(evaluate("$rsp 8", &registers, &stack), None);
+    }
+
+    #[test]
+    fn test_stack_trace_with_symbol_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("app/030201000504070608090A0B0C0D0E0F0");
+        std::fs::create_dir_all(&store).unwrap();
+        std::fs::write(store.join("app.sym"), SYMBOLS).unwrap();
+
+        // Frame record of crash_here, then one of main ending the chain
+        let base = 0x5555_0000_0000;
+        let stack = [STACK_START + 0x10, base + 0x1110, 0, base + 0x2010];
+        let dump = Minidump::from_bytes(amd64_crash_dump(
+            0xb,
+            base + 0x1025,
+            ("/usr/bin/app", base, 0x10000),
+            &stack,
+        ))
+        .unwrap();
+
+        let trace = Symbolizer::new()
+            .symbol_path(dir.path())
+            .stack_trace(&dump)
+            .unwrap();
+
+        assert_eq!(trace.thread_id, 7);
+        assert_eq!(trace.thread_name.as_deref(), Some("main"));
+        let functions: Vec<_> = trace
+            .frames
+            .iter()
+            .map(|frame| (frame.function.as_deref(), frame.line, frame.trust))
+            .collect();
+        // crash_here's CFI finds main; main has no CFI
+        assert_eq!(
+            functions,
+            vec![
+                (Some("app::crash_here"), Some(42), FrameTrust::Context),
+                (Some("app::main"), Some(12), FrameTrust::CallFrameInfo),
+                (Some("_start"), None, FrameTrust::FramePointer),
+            ]
+        );
+        assert_eq!(trace.frames[1].module_offset, Some(0x1110));
+
+        let text = trace.to_string();
+        assert!(text.starts_with("Thread 7 \"main\", exception 0x0000000b at"));
+        assert!(text
+            .contains(" #0  0x0000555500001025 in app::crash_here at src/main.rs:42 (app+0x1025)"));
+    }
+
+    #[test]
+    fn test_stack_trace_without_symbols() {
+        let base = 0x5555_0000_0000;
+        let stack = [STACK_START + 0x10, base + 0x1110, 0, 0];
+        let dump = Minidump::from_bytes(amd64_crash_dump(
+            0xb,
+            0x10,
+            ("/nonexistent/app", base, 0x10000),
+            &stack,
+        ))
+        .unwrap();
+
+        let trace = Symbolizer::new().stack_trace(&dump).unwrap();
+
+        assert_eq!(trace.frames.len(), 2);
+        assert_eq!(trace.frames[0].module, None);
+        assert_eq!(trace.frames[1].module.as_deref(), Some("app"));
+        assert_eq!(trace.frames[1].function, None);
+        assert_eq!(trace.frames[1].trust, FrameTrust::FramePointer);
+        assert!(Symbolizer::new().thread_stack_trace(&dump, 99).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! A stable classification of crashes for dashboards.
+//!
+//! Crash servers group reports by signature, which differs between
+//! backends. [`CrashCategory`] sorts them into a few kinds that mean the
+//! same everywhere, from the exception in the dump and a hint the
+//! application sets at capture time for kinds the exception can't show
+//! (a watchdog firing, a GPU reset). Reports carry the category as the
+//! `crash_category` annotation, see
+//! [`CrashReport::annotations`](crate::CrashReport::annotations).
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Annotation holding the category of a report
+pub(crate) const CRASH_CATEGORY: &str = "crash_category";
+
+/// Module annotation set by [`set_crash_category_hint`]
+pub(crate) const CRASH_CATEGORY_HINT: &str = "crash_category_hint";
+
+/// Fault addresses below this are dereferences of null pointers, or of
+/// fields of null structs
+const NULL_PAGE_LIMIT: u64 = 64 * 1024;
+
+/// Kind of a crash, with a stable name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CrashCategory {
+    /// `null-deref`: an invalid access at an address close to zero
+    NullDereference,
+    /// `stack-overflow`: see
+    /// [`Minidump::probable_stack_overflow`](crate::minidump::Minidump::probable_stack_overflow)
+    StackOverflow,
+    /// `abort`: `abort()`, including Rust panics with `panic = "abort"` and
+    /// failed assertions in C/C++
+    Abort,
+    /// `oom`: the process ran out of memory. Detected from the exception on
+    /// Windows only; elsewhere an allocation failure aborts, so set the
+    /// hint from an out-of-memory handler.
+    OutOfMemory,
+    /// `hang`: a dump requested by a watchdog, set as a hint
+    Hang,
+    /// `gpu-reset`: the GPU driver lost the device, set as a hint
+    GpuReset,
+    /// `other`: any other exception
+    Other,
+}
+
+impl CrashCategory {
+    /// The stable name, e.g. `null-deref`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NullDereference => "null-deref",
+            Self::StackOverflow => "stack-overflow",
+            Self::Abort => "abort",
+            Self::OutOfMemory => "oom",
+            Self::Hang => "hang",
+            Self::GpuReset => "gpu-reset",
+            Self::Other => "other",
+        }
+    }
+
+    /// Category of an exception `code` at `fault_address`, for a crash that
+    /// wasn't a stack overflow
+    pub(crate) fn of_exception(code: u32, fault_address: Option<u64>) -> Self {
+        match code {
+            // SIGABRT; EXC_CRASH (macOS); STATUS_STACK_BUFFER_OVERRUN, raised
+            // by __fastfail, and STATUS_FATAL_APP_EXIT (Windows)
+            6 | 10 | 0xc000_0409 | 0x4000_0015 => Self::Abort,
+            // STATUS_NO_MEMORY, STATUS_COMMITMENT_LIMIT
+            0xc000_0017 | 0xc000_012d => Self::OutOfMemory,
+            // SIGSEGV, SIGBUS, EXC_BAD_ACCESS, STATUS_ACCESS_VIOLATION
+            11 | 7 | 1 | 0xc000_0005
+                if fault_address.is_some_and(|address| address < NULL_PAGE_LIMIT) =>
+            {
+                Self::NullDereference
+            }
+            _ => Self::Other,
+        }
+    }
+}
+
+impl fmt::Display for CrashCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CrashCategory {
+    type Err = ();
+
+    /// Parse a stable name
+    fn from_str(name: &str) -> Result<Self, ()> {
+        [
+            Self::NullDereference,
+            Self::StackOverflow,
+            Self::Abort,
+            Self::OutOfMemory,
+            Self::Hang,
+            Self::GpuReset,
+            Self::Other,
+        ]
+        .into_iter()
+        .find(|category| category.as_str() == name)
+        .ok_or(())
+    }
+}
+
+/// Set the category of dumps written from now on, or clear it with `None`
+///
+/// For crashes the exception doesn't classify: set
+/// [`CrashCategory::Hang`] before a watchdog calls
+/// [`dump_without_crash`](crate::CrashpadClient::dump_without_crash), or
+/// [`CrashCategory::GpuReset`] before crashing on a lost device. The hint
+/// wins over the exception, so clear it once the dump is written.
+///
+/// # Example
+/// ```rust,no_run
+/// use crashpad_rs::{set_crash_category_hint, CrashCategory};
+/// # let client = crashpad_rs::CrashpadClient::new().unwrap();
+///
+/// set_crash_category_hint(Some(CrashCategory::Hang));
+/// client.dump_without_crash();
+/// set_crash_category_hint(None);
+/// ```
+pub fn set_crash_category_hint(category: Option<CrashCategory>) {
+    crate::annotation_audit::set_module_annotation(
+        CRASH_CATEGORY_HINT,
+        category.as_ref().map(CrashCategory::as_str),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_round_trip() {
+        for name in [
+            "null-deref",
+            "stack-overflow",
+            "abort",
+            "oom",
+            "hang",
+            "gpu-reset",
+            "other",
+        ] {
+            let category: CrashCategory = name.parse().unwrap();
+            assert_eq!(category.to_string(), name);
+        }
+        assert!("segfault".parse::<CrashCategory>().is_err());
+    }
+
+    #[test]
+    fn test_of_exception() {
+        let category = CrashCategory::of_exception;
+        assert_eq!(category(11, Some(0x10)), CrashCategory::NullDereference);
+        assert_eq!(category(11, Some(0x5555_0000)), CrashCategory::Other);
+        assert_eq!(category(0xc000_0005, None), CrashCategory::Other);
+        assert_eq!(category(6, Some(0)), CrashCategory::Abort);
+        assert_eq!(category(0xc000_0409, None), CrashCategory::Abort);
+        assert_eq!(category(0xc000_0017, None), CrashCategory::OutOfMemory);
+    }
+}
@@ -0,0 +1,97 @@
+//! Deliberate crashes for verifying the crash pipeline in QA builds.
+//!
+//! Available with the `qa` feature. Wire [`simulate`] to a hidden menu or
+//! command line switch of a QA build to check end to end that crashes reach
+//! the database and the upload server, without shipping hand-written
+//! undefined behavior in application code.
+//!
+//! ```no_run
+//! use crashpad_rs::testing::{simulate, CrashKind};
+//!
+//! # fn on_menu_item(item: &str) {
+//! match item {
+//!     "Crash: segfault" => simulate(CrashKind::Segv),
+//!     "Crash: stack overflow" => simulate(CrashKind::StackOverflow),
+//!     _ => {}
+//! }
+//! # }
+//! ```
+
+use std::ffi::c_void;
+
+extern "C" {
+    fn abort() -> !;
+    fn malloc(size: usize) -> *mut c_void;
+    fn free(ptr: *mut c_void);
+}
+
+/// Kind of crash triggered by [`simulate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CrashKind {
+    /// Write to an invalid address (`SIGSEGV`, `EXCEPTION_ACCESS_VIOLATION`,
+    /// `EXC_BAD_ACCESS`)
+    Segv,
+    /// C runtime `abort()` (`SIGABRT`). Unlike [`std::process::abort`], this
+    /// is reported by Crashpad on Windows too.
+    Abort,
+    /// Unbounded recursion on the calling thread
+    StackOverflow,
+    /// Free the same allocation twice. Detected by the system allocator,
+    /// which aborts the process.
+    HeapCorruption,
+    /// Call a pure virtual C++ function, which aborts through the C++
+    /// runtime
+    PureVirtual,
+}
+
+/// Crash the process.
+///
+/// The crash happens on the calling thread and is reported like any real
+/// crash, provided the handler has been started.
+///
+/// # Platform Behavior
+///
+/// - **Windows**: [`CrashKind::HeapCorruption`] raises
+///   `STATUS_HEAP_CORRUPTION` as a fail-fast exception, which only reaches
+///   Crashpad with its WER module registered. [`CrashKind::StackOverflow`]
+///   is reported as `EXCEPTION_STACK_OVERFLOW`.
+/// - **Linux/Android/macOS**: [`CrashKind::StackOverflow`] faults on the
+///   guard page and is reported as a segmentation fault; it is handled on
+///   the alternate signal stack Rust sets up for each thread.
+pub fn simulate(kind: CrashKind) -> ! {
+    match kind {
+        CrashKind::Segv => segv(),
+        CrashKind::Abort => {}
+        CrashKind::StackOverflow => {
+            recurse(0);
+        }
+        CrashKind::HeapCorruption => unsafe {
+            let ptr = std::hint::black_box(malloc(64));
+            free(ptr);
+            free(ptr);
+        },
+        CrashKind::PureVirtual => unsafe {
+            crashpad_rs_sys::crashpad_simulate_pure_virtual_call();
+        },
+    }
+
+    // Also the fallback if a crash above didn't terminate the process
+    unsafe { abort() }
+}
+
+fn segv() {
+    // Non-null and aligned, so no debug precondition check fires before the
+    // access; the first page is never mapped
+    let address = 8 as *mut u32;
+    unsafe {
+        std::ptr::write_volatile(address, 42);
+    }
+}
+
+#[allow(unconditional_recursion)]
+#[inline(never)]
+fn recurse(depth: u64) -> u64 {
+    // A frame large enough to overflow quickly, kept alive across the call
+    let frame = std::hint::black_box([depth as u8; 1024]);
+    recurse(depth + 1) + u64::from(frame[0])
+}
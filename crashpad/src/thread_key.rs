@@ -0,0 +1,237 @@
+//! Annotations with one value per thread.
+//!
+//! An annotation has one value for the whole process, so threads setting a
+//! key like `current_operation` overwrite each other, and the dump shows
+//! whichever thread set it last. A [`ThreadCrashKey`] keeps a value per
+//! thread instead and stores them together in a
+//! [`LargeAnnotation`](crate::LargeAnnotation) of the key, one
+//! `<thread ID>: <value>` line per thread, ordered by thread ID. The IDs are
+//! those of the threads in the dump on Linux, Android, Windows and macOS.
+//! [`Minidump::thread_crash_key`](crate::minidump::Minidump::thread_crash_key)
+//! reads the values back.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::history::truncate;
+use crate::limits::MAX_LARGE_VALUE_LEN;
+use crate::{CrashpadError, LargeAnnotation, Result};
+
+/// Longest thread ID with its `: ` and line break, in bytes
+const LINE_OVERHEAD: usize = 20 + 3;
+
+/// A crash key with a value per thread.
+///
+/// Created with [`register`](Self::register), which allocates room for
+/// `max_threads` values of up to `max_value_len` bytes each. Like a
+/// [`LargeAnnotation`], registered keys live for the rest of the process,
+/// so register each key once, e.g. in a `static` `OnceLock`.
+///
+/// A thread's value stays until the thread clears it, also after the
+/// thread exits, so use [`set_scoped`](Self::set_scoped) for values that
+/// describe work in progress. Once `max_threads` threads hold a value,
+/// values set by further threads are ignored until one is cleared.
+///
+/// # Example
+/// ```rust,no_run
+/// use crashpad_rs::ThreadCrashKey;
+///
+/// let operation = ThreadCrashKey::register("current_operation", 64, 32)?;
+/// std::thread::scope(|scope| {
+///     scope.spawn(|| {
+///         let _operation = operation.set_scoped("GET /users/42");
+///         // Handle the request
+///     });
+///     scope.spawn(|| {
+///         let _operation = operation.set_scoped("POST /orders");
+///         // Handle the request
+///     });
+/// });
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+#[derive(Debug)]
+pub struct ThreadCrashKey {
+    annotation: &'static LargeAnnotation,
+    max_value_len: usize,
+    max_threads: usize,
+    values: Mutex<BTreeMap<u64, String>>,
+}
+
+impl ThreadCrashKey {
+    /// Register a per-thread key `key` with room for `max_threads` values
+    /// of up to `max_value_len` bytes
+    ///
+    /// # Errors
+    ///
+    /// [`CrashpadError::InvalidConfiguration`] if `max_threads` or
+    /// `max_value_len` is zero, the values would take more than
+    /// [`MAX_LARGE_VALUE_LEN`] bytes (each takes `max_value_len` plus 23),
+    /// or the key is invalid, see [`LargeAnnotation::register`].
+    pub fn register(key: &str, max_value_len: usize, max_threads: usize) -> Result<&'static Self> {
+        let size = max_value_len
+            .checked_add(LINE_OVERHEAD)
+            .and_then(|line| line.checked_mul(max_threads))
+            .filter(|&size| max_value_len > 0 && max_threads > 0 && size <= MAX_LARGE_VALUE_LEN);
+        let Some(size) = size else {
+            return Err(CrashpadError::InvalidConfiguration(format!(
+                "Crash key {key} must hold 1 to {MAX_LARGE_VALUE_LEN} bytes"
+            )));
+        };
+        let annotation = LargeAnnotation::register(key, size)?;
+        Ok(Box::leak(Box::new(Self {
+            annotation,
+            max_value_len,
+            max_threads,
+            values: Mutex::new(BTreeMap::new()),
+        })))
+    }
+
+    /// The key of this crash key
+    pub fn key(&self) -> &str {
+        self.annotation.key()
+    }
+
+    /// Set the value of the calling thread, included in dumps from now on
+    ///
+    /// Values longer than `max_value_len` are cut at a character boundary,
+    /// and line breaks are replaced by spaces. An empty value is like
+    /// [`clear`](Self::clear).
+    pub fn set(&self, value: impl AsRef<str>) {
+        let value = truncate(value.as_ref(), self.max_value_len).replace(['\r', '\n'], " ");
+        let thread = thread_id();
+        let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        if value.is_empty() {
+            values.remove(&thread);
+        } else if values.len() < self.max_threads || values.contains_key(&thread) {
+            values.insert(thread, value);
+        } else {
+            return;
+        }
+        self.annotation.set(encode(&values));
+    }
+
+    /// Set the value of the calling thread until the returned guard is
+    /// dropped
+    pub fn set_scoped(&self, value: impl AsRef<str>) -> ThreadCrashKeyGuard<'_> {
+        self.set(value);
+        ThreadCrashKeyGuard { key: self }
+    }
+
+    /// Remove the value of the calling thread
+    pub fn clear(&self) {
+        self.set("");
+    }
+}
+
+/// Clears the value of a [`ThreadCrashKey`] on its thread when dropped.
+#[derive(Debug)]
+#[must_use = "the value is cleared when the guard is dropped"]
+pub struct ThreadCrashKeyGuard<'a> {
+    key: &'a ThreadCrashKey,
+}
+
+impl Drop for ThreadCrashKeyGuard<'_> {
+    fn drop(&mut self) {
+        self.key.clear();
+    }
+}
+
+/// The annotation value of `values`
+fn encode(values: &BTreeMap<u64, String>) -> String {
+    values
+        .iter()
+        .map(|(thread, value)| format!("{thread}: {value}\n"))
+        .collect()
+}
+
+/// Values by thread ID of an annotation written by a [`ThreadCrashKey`].
+/// Lines that don't parse are skipped.
+pub(crate) fn decode(annotation: &str) -> BTreeMap<u64, String> {
+    annotation
+        .lines()
+        .filter_map(|line| {
+            let (thread, value) = line.split_once(": ")?;
+            Some((thread.parse().ok()?, value.to_string()))
+        })
+        .collect()
+}
+
+/// ID of the calling thread, as in dumps where the platform has one
+fn thread_id() -> u64 {
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    {
+        crate::current_thread_id()
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        (unsafe { libc::syscall(libc::SYS_gettid) }) as u64
+    }
+    #[cfg(not(any(
+        target_os = "windows",
+        target_os = "macos",
+        target_os = "linux",
+        target_os = "android"
+    )))]
+    {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        thread_local!(static ID: u64 = NEXT_ID.fetch_add(1, Ordering::Relaxed));
+        ID.with(|id| *id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let values = BTreeMap::from([
+            (42, "GET /users/42".to_string()),
+            (7, "POST /orders".to_string()),
+        ]);
+        let annotation = encode(&values);
+        assert_eq!(annotation, "7: POST /orders\n42: GET /users/42\n");
+        assert_eq!(decode(&annotation), values);
+        assert_eq!(
+            decode("garbage\n9: a: b"),
+            BTreeMap::from([(9, "a: b".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_threads_keep_their_values() {
+        let key = ThreadCrashKey::register("thread_key_test", 16, 4).unwrap();
+        assert_eq!(key.key(), "thread_key_test");
+        key.set("main\nline");
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let _guard = key.set_scoped("worker");
+                let values = key.values.lock().unwrap();
+                assert_eq!(values.len(), 2);
+                assert!(values.values().any(|value| value == "worker"));
+            });
+        });
+
+        let values = key.values.lock().unwrap().clone();
+        assert_eq!(values.into_values().collect::<Vec<_>>(), ["main line"]);
+        key.clear();
+        assert!(key.values.lock().unwrap().is_empty());
+
+        // A full key ignores further threads
+        let single = ThreadCrashKey::register("thread_key_single_test", 16, 1).unwrap();
+        single.set("main");
+        std::thread::scope(|scope| {
+            scope.spawn(|| single.set("ignored"));
+        });
+        let values = single.values.lock().unwrap().clone();
+        assert_eq!(values.into_values().collect::<Vec<_>>(), ["main"]);
+    }
+
+    #[test]
+    fn test_register_invalid() {
+        assert!(ThreadCrashKey::register("no_threads", 16, 0).is_err());
+        assert!(ThreadCrashKey::register("no_value", 0, 4).is_err());
+        assert!(ThreadCrashKey::register("huge", MAX_LARGE_VALUE_LEN, 2).is_err());
+    }
+}
@@ -0,0 +1,405 @@
+//! Timestamps in crate-generated metadata.
+//!
+//! Metadata the crate writes (exported artifacts, burst annotations) uses
+//! RFC 3339 in UTC, whatever the platform or the process's timezone. The
+//! local offset is only reported separately, through [`Timestamp`] and the
+//! [`timezone_annotations`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crashpad_rs_sys::crashpad_utc_offset;
+
+/// Broken-down time: year, month, day, hour, minute, second.
+type Fields = (i64, u32, u32, u32, u32, u32);
+
+/// A point in time with the UTC offset of the timezone it was observed in.
+///
+/// Returned by [`CrashReport::created`](crate::CrashReport::created) and
+/// [`CrashReport::last_upload_attempt`](crate::CrashReport::last_upload_attempt).
+/// Formats as RFC 3339 in UTC; [`Timestamp::to_local_rfc3339`] keeps the
+/// offset.
+///
+/// # Example
+/// ```rust
+/// use crashpad_rs::Timestamp;
+///
+/// let time = Timestamp::parse("2024-05-01T21:30:00+09:00").unwrap();
+/// assert_eq!(time.utc_offset(), 9 * 3600);
+/// assert_eq!(time.to_string(), "2024-05-01T12:30:00Z");
+/// assert_eq!(time.to_local_rfc3339(), "2024-05-01T21:30:00+09:00");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timestamp {
+    time: SystemTime,
+    utc_offset: i32,
+}
+
+impl Timestamp {
+    /// `time` in the process's local timezone
+    pub fn local(time: SystemTime) -> Self {
+        Self {
+            time,
+            utc_offset: local_utc_offset(time),
+        }
+    }
+
+    /// `time` in UTC
+    pub fn utc(time: SystemTime) -> Self {
+        Self {
+            time,
+            utc_offset: 0,
+        }
+    }
+
+    /// Parse an RFC 3339 timestamp, e.g. `2024-05-01T12:30:00Z` or
+    /// `2024-05-01T21:30:00.250+09:00`
+    ///
+    /// A timezone (`Z` or an offset) is required. Returns `None` for
+    /// malformed timestamps and times before the Unix epoch.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (seconds, nanos, utc_offset) = parse(value)?;
+        let seconds = u64::try_from(seconds).ok()?;
+        Some(Self {
+            time: UNIX_EPOCH + Duration::new(seconds, nanos),
+            utc_offset,
+        })
+    }
+
+    /// The point in time
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// Offset of the timezone from UTC in seconds, positive east of UTC
+    pub fn utc_offset(&self) -> i32 {
+        self.utc_offset
+    }
+
+    /// RFC 3339 in UTC, e.g. `2024-05-01T12:30:00Z`
+    pub fn to_rfc3339(&self) -> String {
+        rfc3339(self.time)
+    }
+
+    /// RFC 3339 in the timezone, e.g. `2024-05-01T21:30:00+09:00`
+    pub fn to_local_rfc3339(&self) -> String {
+        let seconds = unix_seconds(self.time) + i64::from(self.utc_offset);
+        let (year, month, day, hour, minute, second) = fields(seconds);
+        format!(
+            "{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}{}",
+            offset(self.utc_offset)
+        )
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_rfc3339())
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.time
+    }
+}
+
+/// Annotations describing the process's timezone.
+///
+/// - `utc_offset`: the current offset from UTC, e.g. `+09:00`
+/// - `timezone`: the IANA timezone name, e.g. `Asia/Seoul`, if it can be
+///   determined: from `TZ`, else the `/etc/localtime` link or
+///   `/etc/timezone`
+///
+/// Report timestamps are in UTC; these tell what local time the user saw.
+/// On Windows only `TZ` is read for the name.
+pub fn timezone_annotations() -> HashMap<String, String> {
+    let mut annotations = HashMap::new();
+    annotations.insert(
+        "utc_offset".to_string(),
+        offset(local_utc_offset(SystemTime::now())),
+    );
+    if let Some(zone) = timezone_name() {
+        annotations.insert("timezone".to_string(), zone);
+    }
+    annotations
+}
+
+fn timezone_name() -> Option<String> {
+    if let Some(zone) = std::env::var("TZ").ok().and_then(|tz| zone_from_tz(&tz)) {
+        return Some(zone);
+    }
+    if cfg!(windows) {
+        return None;
+    }
+    std::fs::read_link("/etc/localtime")
+        .ok()
+        .and_then(|target| zone_from_path(&target.to_string_lossy()))
+        .or_else(|| {
+            std::fs::read_to_string("/etc/timezone")
+                .ok()
+                .map(|zone| zone.trim().to_string())
+                .filter(|zone| !zone.is_empty())
+        })
+}
+
+/// IANA name in a `TZ` value, e.g. `Europe/Berlin` or `:Europe/Berlin`.
+/// POSIX rules such as `CET-1CEST` aren't names.
+fn zone_from_tz(tz: &str) -> Option<String> {
+    let tz = tz.strip_prefix(':').unwrap_or(tz);
+    if tz.starts_with('/') {
+        return zone_from_path(tz);
+    }
+    let rule = tz.contains(',') || !tz.contains('/') && tz.bytes().any(|b| b.is_ascii_digit());
+    (!tz.is_empty() && !rule).then(|| tz.to_string())
+}
+
+/// IANA name in a path into the zoneinfo database, e.g.
+/// `/usr/share/zoneinfo/Asia/Seoul`
+fn zone_from_path(path: &str) -> Option<String> {
+    let (_, zone) = path.rsplit_once("zoneinfo/")?;
+    (!zone.is_empty()).then(|| zone.to_string())
+}
+
+fn local_utc_offset(time: SystemTime) -> i32 {
+    // SAFETY: Only reads the timezone settings
+    unsafe { crashpad_utc_offset(unix_seconds(time)) }
+}
+
+/// `+hh:mm` or `-hh:mm`
+fn offset(utc_offset: i32) -> String {
+    let sign = if utc_offset < 0 { '-' } else { '+' };
+    let minutes = utc_offset.unsigned_abs() / 60;
+    format!("{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// RFC 3339 UTC timestamp, e.g. `2024-05-01T12:30:00Z`
+pub(crate) fn rfc3339(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = fields(unix_seconds(time));
+    format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z")
+}
+
+/// Compact UTC timestamp for file names, e.g. `20240501T123000Z`
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+pub(crate) fn compact(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = fields(unix_seconds(time));
+    format!("{year:04}{month:02}{day:02}T{hour:02}{minute:02}{second:02}Z")
+}
+
+/// Parse an RFC 3339 timestamp with any timezone
+pub(crate) fn parse_rfc3339(value: &str) -> Option<SystemTime> {
+    Timestamp::parse(value).map(|timestamp| timestamp.time)
+}
+
+/// Seconds since the epoch, nanoseconds and UTC offset of an RFC 3339
+/// timestamp
+fn parse(value: &str) -> Option<(i64, u32, i32)> {
+    let number = |range: std::ops::Range<usize>| -> Option<u32> {
+        let digits = value.get(range)?;
+        digits
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| digits.parse().ok())?
+    };
+    let bytes = value.as_bytes();
+    if bytes.len() < 20
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+    let year = i64::from(number(0..4)?);
+    let month = number(5..7)?;
+    let day = number(8..10)?;
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    // Fraction of a second, beyond nanoseconds ignored
+    let mut rest = &value[19..];
+    let mut nanos = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        for (i, digit) in fraction.bytes().take(digits.min(9)).enumerate() {
+            nanos += u32::from(digit - b'0') * 10u32.pow(8 - i as u32);
+        }
+        rest = &fraction[digits..];
+    }
+
+    let utc_offset = match rest.as_bytes() {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), _, _, b':', _, _] => {
+            let start = value.len() - rest.len();
+            let hours = number(start + 1..start + 3)?;
+            let minutes = number(start + 4..start + 6)?;
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = (hours * 3600 + minutes * 60) as i32;
+            if *sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+
+    // Civil date to days, from Howard Hinnant's `days_from_civil`
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let mp = i64::from(if month > 2 { month - 3 } else { month + 9 });
+    let day_of_year = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400 + i64::from(hour * 3600 + minute * 60 + second);
+    Some((seconds - i64::from(utc_offset), nanos, utc_offset))
+}
+
+/// Seconds since the epoch. Times before the epoch don't occur in reports;
+/// they are clamped.
+fn unix_seconds(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn fields(seconds: i64) -> Fields {
+    let days = seconds.div_euclid(86_400);
+    let of_day = seconds.rem_euclid(86_400);
+
+    // Days to civil date, from Howard Hinnant's `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (
+        year,
+        month,
+        day,
+        (of_day / 3600) as u32,
+        (of_day / 60 % 60) as u32,
+        (of_day % 60) as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(rfc3339(time), "2023-11-14T22:13:20Z");
+        assert_eq!(compact(time), "20231114T221320Z");
+
+        // Leap day
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_251_199);
+        assert_eq!(rfc3339(time), "2024-02-29T23:59:59Z");
+    }
+
+    #[test]
+    fn test_parse() {
+        for seconds in [0, 951_782_400, 1_700_000_000, 1_709_251_199, 4_102_444_800] {
+            let time = UNIX_EPOCH + Duration::from_secs(seconds);
+            assert_eq!(parse_rfc3339(&rfc3339(time)), Some(time));
+        }
+
+        assert_eq!(parse_rfc3339("2023-11-14T22:13:20"), None);
+        assert_eq!(parse_rfc3339("2023-13-14T22:13:20Z"), None);
+        assert_eq!(parse_rfc3339("2023-11-14T22:1x:20Z"), None);
+        assert_eq!(parse_rfc3339("1969-12-31T23:59:59Z"), None);
+        assert_eq!(parse_rfc3339("2023-11-14T22:13:20.Z"), None);
+        assert_eq!(parse_rfc3339("2023-11-14T22:13:20+24:00"), None);
+        assert_eq!(parse_rfc3339("2023-11-14T22:13:20+0900"), None);
+    }
+
+    #[test]
+    fn test_parse_offsets() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for value in [
+            "2023-11-14T22:13:20z",
+            "2023-11-15T07:13:20+09:00",
+            "2023-11-14T17:43:20-04:30",
+        ] {
+            assert_eq!(parse_rfc3339(value), Some(time), "{value}");
+        }
+
+        let timestamp = Timestamp::parse("2023-11-14T17:43:20.250-04:30").unwrap();
+        assert_eq!(timestamp.time(), time + Duration::from_millis(250));
+        assert_eq!(timestamp.utc_offset(), -(4 * 3600 + 30 * 60));
+        assert_eq!(timestamp.to_rfc3339(), "2023-11-14T22:13:20Z");
+        assert_eq!(timestamp.to_local_rfc3339(), "2023-11-14T17:43:20-04:30");
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let timestamp = Timestamp::utc(time);
+        assert_eq!(timestamp.to_string(), "2023-11-14T22:13:20Z");
+        assert_eq!(timestamp.to_local_rfc3339(), "2023-11-14T22:13:20+00:00");
+        assert_eq!(SystemTime::from(timestamp), time);
+
+        // Whatever the local timezone, the UTC form is the same
+        assert_eq!(Timestamp::local(time).to_rfc3339(), rfc3339(time));
+    }
+
+    #[test]
+    fn test_timezone_names() {
+        assert_eq!(zone_from_tz("Asia/Seoul").as_deref(), Some("Asia/Seoul"));
+        assert_eq!(
+            zone_from_tz(":Europe/Berlin").as_deref(),
+            Some("Europe/Berlin")
+        );
+        assert_eq!(zone_from_tz("UTC").as_deref(), Some("UTC"));
+        assert_eq!(zone_from_tz("Etc/GMT+9").as_deref(), Some("Etc/GMT+9"));
+        assert_eq!(
+            zone_from_tz("/usr/share/zoneinfo/America/New_York").as_deref(),
+            Some("America/New_York")
+        );
+        assert_eq!(zone_from_tz("CET-1CEST,M3.5.0,M10.5.0/3"), None);
+        assert_eq!(zone_from_tz("/etc/localtime"), None);
+        assert_eq!(zone_from_tz(""), None);
+
+        assert_eq!(
+            zone_from_path("../usr/share/zoneinfo/Asia/Seoul").as_deref(),
+            Some("Asia/Seoul")
+        );
+        assert_eq!(zone_from_path("/usr/share/zoneinfo/"), None);
+    }
+
+    #[test]
+    fn test_timezone_annotations() {
+        let annotations = timezone_annotations();
+        let offset = &annotations["utc_offset"];
+        assert_eq!(offset.len(), 6);
+        assert!(offset.starts_with(['+', '-']));
+        assert_eq!(super::offset(-1800), "-00:30");
+        assert_eq!(super::offset(20_700), "+05:45");
+    }
+}
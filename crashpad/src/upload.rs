@@ -0,0 +1,790 @@
+//! Uploading a minidump from Rust (`upload` feature).
+//!
+//! The handler uploads reports by itself, when and where its configuration
+//! says. With uploads disabled, e.g. until the user agrees or while the
+//! application decides the endpoint at runtime, [`send_report`] uploads a
+//! single dump the way the handler would: a `multipart/form-data` POST
+//! with the report's annotations as form fields and the dump as
+//! `upload_file_minidump`, so the same server accepts it. Like the
+//! handler's `upload_gzip`, the body can be compressed (see
+//! [`Compression`]), which matters for large dumps on metered connections;
+//! [`last_upload_size`] tells how much was saved.
+//!
+//! ```rust,no_run
+//! use crashpad_rs::upload::{self, Compression};
+//! use crashpad_rs::CrashReportDatabase;
+//!
+//! let database = CrashReportDatabase::open("./crashpad_db")?;
+//! for report in database.pending_reports()? {
+//!     let id = upload::send_report(
+//!         &report.file_path,
+//!         ["https://crashes.example.com/submit"],
+//!         &[("Authorization", "Bearer secret")],
+//!         Compression::Gzip,
+//!     )?;
+//!     println!("Sent {} as {id}", report.uuid);
+//!     database.record_upload(&report.uuid, Some(&id))?;
+//! }
+//! # Ok::<(), crashpad_rs::CrashpadError>(())
+//! ```
+//!
+//! [`send_pending`] does this for every pending report, retrying failed
+//! uploads later according to a [`RetryPolicy`], so reports written while
+//! the device is offline are sent once it's back.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::claim;
+use crate::clock::{IdGen, RandomIds, SystemClock};
+use crate::crash_loop;
+use crate::fs::{Fs, StdFs};
+use crate::quota::Counters;
+use crate::retry::{self, Outcome};
+use crate::{CrashReportDatabase, CrashpadError, Result};
+
+pub use crate::retry::RetryPolicy;
+
+/// Form field holding the dump, as named by Crashpad's uploader
+const MINIDUMP_FIELD: &str = "upload_file_minidump";
+
+/// How request bodies are compressed.
+///
+/// Compressed bodies are sent with a `Content-Encoding` header, as the
+/// handler does with `upload_gzip`. A server that can't decode them answers
+/// 415 Unsupported Media Type, and the report is sent again uncompressed,
+/// so a compression is an offer, not a requirement.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// Send the body as is
+    None,
+    /// gzip, understood by most servers, including Crashpad's and
+    /// Breakpad's collectors
+    #[default]
+    Gzip,
+    /// Zstandard, smaller and faster than gzip where the server supports it
+    /// (`zstd` feature)
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// The `Content-Encoding` of the compression, `None` for none
+    fn content_encoding(self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Gzip => Some("gzip"),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => Some("zstd"),
+        }
+    }
+
+    fn compress(self, body: &[u8]) -> std::io::Result<Cow<'_, [u8]>> {
+        match self {
+            Self::None => Ok(Cow::Borrowed(body)),
+            Self::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(body)?;
+                encoder.finish().map(Cow::Owned)
+            }
+            #[cfg(feature = "zstd")]
+            Self::Zstd => zstd::encode_all(body, 0).map(Cow::Owned),
+        }
+    }
+}
+
+/// Sizes of the last report sent, see [`last_upload_size`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadSize {
+    /// Bytes of the `multipart/form-data` body
+    pub body: usize,
+    /// Bytes sent, after compression
+    pub sent: usize,
+    /// The compression the server accepted
+    pub compression: Compression,
+}
+
+static LAST_UPLOAD_SIZE: Mutex<Option<UploadSize>> = Mutex::new(None);
+
+/// Sizes of the last report [`send_report`] or [`send_pending`] sent, for
+/// telemetry on the data uploads use
+///
+/// `None` before the first report was sent.
+pub fn last_upload_size() -> Option<UploadSize> {
+    *LAST_UPLOAD_SIZE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Upload the minidump at `path` to the first of `urls` that accepts it,
+/// returning the report ID the server responded with
+///
+/// The URLs are tried in order, so a fallback endpoint receives the report
+/// when the primary is down, as with
+/// [`CrashpadConfig::upload_urls`](crate::CrashpadConfig::upload_urls).
+/// `headers` are added to each request, e.g. for authentication as set with
+/// [`CrashpadConfig::http_headers`](crate::CrashpadConfig::http_headers),
+/// and the body is compressed with `compression`. The form fields are the
+/// annotations in the dump, as the handler sends them.
+///
+/// # Errors
+///
+/// [`CrashpadError::IoError`] if the dump can't be read or isn't a
+/// minidump, [`CrashpadError::InvalidConfiguration`] without URLs, and
+/// [`CrashpadError::UploadFailed`] with the last URL's failure if no
+/// server can be reached or all answer with an error status.
+pub fn send_report<P, I, S>(
+    path: P,
+    urls: I,
+    headers: &[(&str, &str)],
+    compression: Compression,
+) -> Result<String>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    crate::failpoints::fail_point!(crate::failpoints::UPLOAD, injected_failure);
+    let urls: Vec<S> = urls.into_iter().collect();
+    let urls: Vec<&str> = urls.iter().map(AsRef::as_ref).collect();
+    send(path.as_ref(), &urls, headers, compression).map(|(_, id)| id)
+}
+
+/// One attempt of [`send_pending`] to send the report at `path`
+///
+/// URLs that failed before the one accepting the report are moved to the
+/// end of `urls`, so later reports of the same run try them last.
+fn attempt(
+    path: &Path,
+    urls: &mut [&str],
+    headers: &[(&str, &str)],
+    compression: Compression,
+) -> Result<String> {
+    crate::failpoints::fail_point!(crate::failpoints::UPLOAD, injected_failure);
+    let (accepted, id) = send(path, urls, headers, compression)?;
+    urls.rotate_left(accepted);
+    Ok(id)
+}
+
+/// The [`crate::failpoints::UPLOAD`] error, with the `return(msg)` argument
+/// as message
+#[cfg(feature = "failpoints")]
+fn injected_failure(msg: Option<String>) -> Result<String> {
+    Err(CrashpadError::UploadFailed {
+        status: None,
+        message: msg.unwrap_or_else(|| "injected upload failure".to_string()),
+    })
+}
+
+/// Post the dump at `path` to each of `urls` until one accepts it,
+/// returning that URL's index and the report ID
+fn send(
+    path: &Path,
+    urls: &[&str],
+    headers: &[(&str, &str)],
+    compression: Compression,
+) -> Result<(usize, String)> {
+    let minidump = crate::minidump::Minidump::from_bytes(std::fs::read(path)?)?;
+    let annotations = crate::database::report_annotations(&minidump)?;
+    let dump = minidump.into_bytes();
+    let mut fields: Vec<(String, String)> = annotations.into_iter().collect();
+    fields.sort();
+
+    let file_name = path
+        .file_name()
+        .map_or_else(|| "upload.dmp".into(), |name| name.to_string_lossy());
+    let boundary = boundary(&RandomIds);
+    let content_type = format!("multipart/form-data; boundary={boundary}");
+    let body = multipart_body(&boundary, &fields, &file_name, &dump);
+    let compressed = compression.compress(&body)?;
+
+    let mut last_error = CrashpadError::InvalidConfiguration("No upload URL".to_string());
+    for (index, url) in urls.iter().enumerate() {
+        let mut result = post(url, &content_type, &compressed, compression, headers);
+        let mut sent = (compressed.len(), compression);
+        // The server doesn't take the compression; offer it the plain body
+        if compression != Compression::None
+            && matches!(
+                result,
+                Err(CrashpadError::UploadFailed {
+                    status: Some(415),
+                    ..
+                })
+            )
+        {
+            result = post(url, &content_type, &body, Compression::None, headers);
+            sent = (body.len(), Compression::None);
+        }
+        match result {
+            Ok(id) => {
+                *LAST_UPLOAD_SIZE.lock().unwrap_or_else(|e| e.into_inner()) = Some(UploadSize {
+                    body: body.len(),
+                    sent: sent.0,
+                    compression: sent.1,
+                });
+                return Ok((index, id));
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// POST `body` of `content_type`, compressed with `compression`, to `url`
+fn post(
+    url: &str,
+    content_type: &str,
+    body: &[u8],
+    compression: Compression,
+    headers: &[(&str, &str)],
+) -> Result<String> {
+    let mut request = ureq::post(url).set("Content-Type", content_type);
+    if let Some(encoding) = compression.content_encoding() {
+        request = request.set("Content-Encoding", encoding);
+    }
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    match request.send_bytes(body) {
+        Ok(response) => Ok(response.into_string()?.trim().to_string()),
+        Err(ureq::Error::Status(status, response)) => Err(CrashpadError::UploadFailed {
+            status: Some(status),
+            message: response
+                .into_string()
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+        }),
+        Err(ureq::Error::Transport(transport)) => Err(CrashpadError::UploadFailed {
+            status: None,
+            message: transport.to_string(),
+        }),
+    }
+}
+
+/// Send the outcomes of ended sessions in `database_path` to `url`, as
+/// release-health pings
+///
+/// The sessions must have been started with
+/// [`crash_loop::start_session_with_health_pings`](crate::crash_loop::start_session_with_health_pings).
+/// Each outcome is POSTed as a small JSON object, e.g.
+/// `{"session":"<32 hex digits>","status":"exited"}`, with status
+/// `exited` for a clean shutdown and `abnormal` for a session that crashed
+/// or was killed, so a backend can compute crash-free session rates.
+/// `headers` are added to each request. Sent outcomes are removed; after a
+/// failure, the rest stay for the next call. Returns the number of pings
+/// sent.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use crashpad_rs::{crash_loop, upload};
+///
+/// crash_loop::start_session_with_health_pings("./crashpad_db")?;
+/// upload::send_session_pings("./crashpad_db", "https://health.example.com/sessions", &[])?;
+/// // ... on normal exit
+/// crash_loop::end_session()?;
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+///
+/// # Errors
+///
+/// [`CrashpadError::IoError`] if the outcomes can't be read or removed, and
+/// [`CrashpadError::UploadFailed`] if a ping isn't accepted.
+pub fn send_session_pings<P: AsRef<Path>>(
+    database_path: P,
+    url: &str,
+    headers: &[(&str, &str)],
+) -> Result<usize> {
+    let mut sent = 0;
+    for (path, id, status) in crash_loop::session_outcomes(&StdFs, database_path.as_ref())? {
+        let ping = serde_json::json!({ "session": id, "status": status.as_str() });
+        post(
+            url,
+            "application/json",
+            ping.to_string().as_bytes(),
+            Compression::None,
+            headers,
+        )?;
+        match StdFs.remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => sent += 1,
+        }
+    }
+    Ok(sent)
+}
+
+/// Outcome of [`send_pending`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueueSummary {
+    /// UUIDs of the reports sent, with the report IDs the server responded
+    /// with
+    pub sent: Vec<(String, String)>,
+    /// UUIDs of the reports whose upload failed, with when they are retried
+    pub retrying: Vec<(String, SystemTime)>,
+    /// UUIDs of the reports that failed their last attempt, now completed
+    /// without upload
+    pub given_up: Vec<String>,
+    /// UUIDs of the reports still backing off from an earlier failure
+    pub waiting: Vec<String>,
+    /// UUIDs of the reports over [`RetryPolicy::fingerprint_quota`], now
+    /// completed without upload
+    pub over_quota: Vec<String>,
+    /// Sending stopped because the condition of
+    /// [`RetryPolicy::only_when`] didn't allow it
+    pub deferred: bool,
+}
+
+/// Send the pending reports of `database` to the first of `urls` that
+/// accepts them, as [`send_report`] does, following `policy`
+///
+/// Call it at startup, periodically, or when the device comes online.
+/// A report is retried later only if every URL failed. An endpoint that
+/// failed is tried last for the rest of the call, so reports don't wait on
+/// an unreachable primary one after another.
+/// Reports backing off from an earlier failure, and reports claimed by
+/// another agent (see [`CrashReportDatabase::claim`]), are left for later.
+/// Reports over the policy's
+/// [`fingerprint_quota`](RetryPolicy::fingerprint_quota) are completed
+/// without upload.
+/// Every attempt is recorded in the database like the handler's own, so
+/// [`CrashReportDatabase::statistics`] count them. Use it with the
+/// handler's uploads disabled, or both may send the same report.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use crashpad_rs::upload::{self, Compression, RetryPolicy};
+/// use crashpad_rs::CrashReportDatabase;
+///
+/// # fn on_wifi() -> bool { true }
+/// let database = CrashReportDatabase::open("./crashpad_db")?;
+/// let summary = upload::send_pending(
+///     &database,
+///     [
+///         "https://us.crashes.example.com/submit",
+///         "https://eu.crashes.example.com/submit",
+///     ],
+///     &[],
+///     Compression::Gzip,
+///     &RetryPolicy::new().only_when(on_wifi),
+/// )?;
+/// println!("Sent {} reports", summary.sent.len());
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+///
+/// # Errors
+///
+/// [`CrashpadError::IoError`] if the retry state or the claims can't be
+/// read or written, and database errors. Failed uploads aren't errors;
+/// they are in the summary.
+pub fn send_pending<I, S>(
+    database: &CrashReportDatabase,
+    urls: I,
+    headers: &[(&str, &str)],
+    compression: Compression,
+    policy: &RetryPolicy,
+) -> Result<QueueSummary>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let urls: Vec<S> = urls.into_iter().collect();
+    let mut urls: Vec<&str> = urls.iter().map(AsRef::as_ref).collect();
+    let claimed: HashSet<String> = claim::claimed_reports(&StdFs, database.path(), &SystemClock)?
+        .into_iter()
+        .collect();
+
+    let mut counters = Counters::read(&StdFs, database.path())?;
+    let mut summary = QueueSummary::default();
+    for report in database.pending_reports()? {
+        if claimed.contains(&report.uuid) {
+            continue;
+        }
+        if !retry::is_due(&StdFs, database.path(), &report.uuid, &SystemClock)? {
+            summary.waiting.push(report.uuid);
+            continue;
+        }
+        if !policy.allows_sending() {
+            summary.deferred = true;
+            break;
+        }
+
+        let fingerprint = policy
+            .quota()
+            .filter(|_| !report.upload_explicitly_requested)
+            .and_then(|_| crate::minidump::read_fingerprint(&report.file_path).ok())
+            .flatten();
+        if let (Some(quota), Some(fingerprint)) = (policy.quota(), &fingerprint) {
+            if !counters.allows(quota, fingerprint, &SystemClock) {
+                database.skip_upload(&report.uuid)?;
+                retry::remove_state(&StdFs, database.path(), &report.uuid)?;
+                summary.over_quota.push(report.uuid);
+                continue;
+            }
+        }
+
+        let result = attempt(&report.file_path, &mut urls, headers, compression);
+        database.record_upload(&report.uuid, result.as_deref().ok())?;
+        match retry::record_attempt(
+            &StdFs,
+            database.path(),
+            &report.uuid,
+            policy,
+            result.is_ok(),
+            &SystemClock,
+        )? {
+            Outcome::Sent => {
+                if let (Some(quota), Some(fingerprint)) = (policy.quota(), &fingerprint) {
+                    counters.record_upload(
+                        &StdFs,
+                        database.path(),
+                        quota,
+                        fingerprint,
+                        &SystemClock,
+                    )?;
+                }
+                summary.sent.push((report.uuid, result.unwrap_or_default()));
+            }
+            Outcome::Retry(at) => summary.retrying.push((report.uuid, at)),
+            Outcome::GiveUp => {
+                database.skip_upload(&report.uuid)?;
+                summary.given_up.push(report.uuid);
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// A boundary unlikely to occur in the dump, like Crashpad's
+/// `---MultipartBoundary-<32 hex digits>---`
+fn boundary(ids: &impl IdGen) -> String {
+    format!("---MultipartBoundary-{}---", ids.new_id())
+}
+
+/// `name` quoted for a `Content-Disposition` header: quotes are
+/// percent-encoded and line breaks dropped, as Crashpad does
+fn quote(name: &str) -> String {
+    let escaped: String = name
+        .chars()
+        .filter(|c| *c != '\r' && *c != '\n')
+        .collect::<String>()
+        .replace('"', "%22");
+    format!("\"{escaped}\"")
+}
+
+/// The `multipart/form-data` body of `fields` and the dump
+fn multipart_body(
+    boundary: &str,
+    fields: &[(String, String)],
+    file_name: &str,
+    dump: &[u8],
+) -> Vec<u8> {
+    let mut body = Vec::with_capacity(dump.len() + 1024);
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name={}\r\n\r\n{value}\r\n",
+                quote(name)
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name={}; filename={}\r\n\
+             Content-Type: application/octet-stream\r\n\r\n",
+            quote(MINIDUMP_FIELD),
+            quote(file_name)
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(dump);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multipart_body() {
+        let fields = vec![("ver".to_string(), "1.0".to_string())];
+        let body = multipart_body("B", &fields, "a.dmp", b"MDMP");
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "--B\r\nContent-Disposition: form-data; name=\"ver\"\r\n\r\n1.0\r\n\
+             --B\r\nContent-Disposition: form-data; name=\"upload_file_minidump\"; \
+             filename=\"a.dmp\"\r\nContent-Type: application/octet-stream\r\n\r\n\
+             MDMP\r\n--B--\r\n"
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("plain"), "\"plain\"");
+        assert_eq!(quote("a\"b\r\nc"), "\"a%22bc\"");
+        assert_ne!(boundary(&RandomIds), boundary(&RandomIds));
+    }
+
+    #[test]
+    fn test_compress() {
+        use std::io::Read;
+
+        let body = multipart_body("B", &[], "a.dmp", &[0; 4096]);
+        assert!(matches!(
+            Compression::None.compress(&body).unwrap(),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(Compression::None.content_encoding(), None);
+
+        let gzip = Compression::Gzip.compress(&body).unwrap();
+        assert!(gzip.len() < body.len() / 10);
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&gzip[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+        assert_eq!(Compression::default().content_encoding(), Some("gzip"));
+
+        #[cfg(feature = "zstd")]
+        {
+            let zstd = Compression::Zstd.compress(&body).unwrap();
+            assert_eq!(zstd::decode_all(&zstd[..]).unwrap(), body);
+        }
+    }
+
+    /// Read an HTTP request with a `Content-Length` from `stream`, returning
+    /// the lowercased head and the body
+    fn read_request(stream: &mut impl std::io::Read) -> (String, Vec<u8>) {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                let head = String::from_utf8_lossy(&request[..end]).to_lowercase();
+                let length: usize = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                if request.len() >= end + 4 + length {
+                    return (head, request[end + 4..end + 4 + length].to_vec());
+                }
+            }
+            let read = stream.read(&mut buffer).unwrap();
+            assert_ne!(read, 0);
+            request.extend_from_slice(&buffer[..read]);
+        }
+    }
+
+    #[test]
+    fn test_unsupported_compression_is_sent_plain() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        #[cfg(feature = "failpoints")]
+        let scenario = fail::FailScenario::setup();
+
+        // Rejects the compressed body, then accepts the plain one
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/submit", server.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in [
+                &b"HTTP/1.1 415 Unsupported Media Type\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"[..],
+                b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nid",
+            ] {
+                let (mut stream, _) = server.accept().unwrap();
+                requests.push(read_request(&mut stream));
+                stream.write_all(response).unwrap();
+            }
+            requests
+        });
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dump = temp_dir.path().join("report.dmp");
+        std::fs::write(&dump, crate::minidump::annotations_dump(&[("prod", "app")])).unwrap();
+        let sent = send_report(&dump, [&url], &[], Compression::Gzip);
+        let size = last_upload_size();
+        #[cfg(feature = "failpoints")]
+        scenario.teardown();
+
+        assert_eq!(sent.unwrap(), "id");
+        let requests = server.join().unwrap();
+        let (compressed_head, compressed) = &requests[0];
+        let (plain_head, plain) = &requests[1];
+        assert!(compressed_head.contains("\r\ncontent-encoding: gzip"));
+        assert!(!plain_head.contains("content-encoding"));
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(&decoded, plain);
+        let size = size.unwrap();
+        assert_eq!(size.compression, Compression::None);
+        assert_eq!(size.sent, size.body);
+    }
+
+    #[test]
+    fn test_send_session_pings() {
+        use std::io::Write;
+        use std::net::TcpListener;
+
+        // Accepts the first ping, then fails
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/sessions", server.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in [
+                &b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"[..],
+                b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            ] {
+                let (mut stream, _) = server.accept().unwrap();
+                requests.push(read_request(&mut stream));
+                stream.write_all(response).unwrap();
+            }
+            requests
+        });
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sessions = temp_dir.path().join(crash_loop::SESSIONS_DIR);
+        std::fs::create_dir_all(&sessions).unwrap();
+        std::fs::write(sessions.join("aaaa.exited"), "").unwrap();
+        std::fs::write(sessions.join("bbbb.abnormal"), "").unwrap();
+
+        let sent = send_session_pings(temp_dir.path(), &url, &[("X-Release", "1.0")]);
+        assert!(matches!(
+            sent,
+            Err(CrashpadError::UploadFailed {
+                status: Some(503),
+                ..
+            })
+        ));
+        let requests = server.join().unwrap();
+        let (head, body) = &requests[0];
+        assert!(head.contains("\r\ncontent-type: application/json"));
+        assert!(head.contains("\r\nx-release: 1.0"));
+        assert_eq!(body, br#"{"session":"aaaa","status":"exited"}"#);
+        assert_eq!(requests[1].1, br#"{"session":"bbbb","status":"abnormal"}"#);
+        // The failed ping is sent again next time
+        assert!(!sessions.join("aaaa.exited").exists());
+        assert!(sessions.join("bbbb.abnormal").exists());
+    }
+
+    #[test]
+    fn test_failover() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        // Keeps the UPLOAD fail point of other tests off
+        #[cfg(feature = "failpoints")]
+        let scenario = fail::FailScenario::setup();
+
+        // Nothing listens on the primary once its listener is dropped
+        let primary = TcpListener::bind("127.0.0.1:0").unwrap();
+        let primary_url = format!("http://{}/submit", primary.local_addr().unwrap());
+        drop(primary);
+
+        let fallback = TcpListener::bind("127.0.0.1:0").unwrap();
+        let fallback_url = format!("http://{}/submit", fallback.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for _ in 0..2 {
+                let (mut stream, _) = fallback.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 4096];
+                while !request.ends_with(b"--\r\n") {
+                    let read = stream.read(&mut buffer).unwrap();
+                    assert_ne!(read, 0);
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nid",
+                    )
+                    .unwrap();
+                requests.push(String::from_utf8_lossy(&request).into_owned());
+            }
+            requests
+        });
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dump = temp_dir.path().join("report.dmp");
+        std::fs::write(&dump, crate::minidump::annotations_dump(&[("prod", "app")])).unwrap();
+        let config = crate::CrashpadConfig::builder()
+            .url_failover([&primary_url, &fallback_url])
+            .http_header("Authorization", "Bearer secret")
+            .build();
+
+        let none = Compression::None;
+        let sent = send_report(&dump, config.upload_urls(), &config.http_headers(), none);
+        let mut urls = [primary_url.as_str(), fallback_url.as_str()];
+        let attempted = attempt(&dump, &mut urls, &[], none);
+        let unreachable = send_report(&dump, [&primary_url], &[], none);
+        let no_url = send_report(&dump, Vec::<String>::new(), &[], none);
+        #[cfg(feature = "failpoints")]
+        scenario.teardown();
+
+        assert_eq!(sent.unwrap(), "id");
+        assert_eq!(attempted.unwrap(), "id");
+        assert_eq!(urls, [fallback_url.as_str(), primary_url.as_str()]);
+        assert!(matches!(
+            unreachable,
+            Err(CrashpadError::UploadFailed { status: None, .. })
+        ));
+        assert!(matches!(
+            no_url,
+            Err(CrashpadError::InvalidConfiguration(_))
+        ));
+        let requests = server.join().unwrap();
+        for request in &requests {
+            assert!(request.starts_with("POST /submit "));
+            assert!(request.contains("name=\"prod\"\r\n\r\napp\r\n"));
+        }
+        assert!(requests[0].contains("\r\nAuthorization: Bearer secret\r\n"));
+    }
+
+    #[cfg(feature = "failpoints")]
+    #[test]
+    fn test_send_pending_retries_failed_uploads() {
+        use std::time::Duration;
+
+        let scenario = fail::FailScenario::setup();
+        fail::cfg(crate::failpoints::UPLOAD, "return(offline)").unwrap();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let database = CrashReportDatabase::open(temp_dir.path()).unwrap();
+        let uuid = database
+            .add_report(&crate::minidump::annotations_dump(&[("prod", "app")]))
+            .unwrap();
+        let report = database.pending_reports().unwrap().remove(0);
+        let policy = RetryPolicy::new()
+            .max_attempts(2)
+            .backoff(Duration::ZERO, Duration::ZERO);
+        let url = "https://crashes.example.com/submit";
+
+        let gzip = Compression::Gzip;
+        let direct = send_report(&report.file_path, [url], &[], gzip);
+        let first = send_pending(&database, [url], &[], gzip, &policy).unwrap();
+        let second = send_pending(&database, [url], &[], gzip, &policy).unwrap();
+        scenario.teardown();
+
+        match direct {
+            Err(CrashpadError::UploadFailed { status, message }) => {
+                assert_eq!(status, None);
+                assert_eq!(message, "offline");
+            }
+            other => panic!("expected an upload failure, got {other:?}"),
+        }
+        assert_eq!(first.retrying.len(), 1);
+        assert_eq!(first.retrying[0].0, uuid);
+        assert!(first.sent.is_empty());
+        assert_eq!(second.given_up, vec![uuid]);
+        assert!(database.pending_reports().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+//! Notifications about the handler's uploads.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::{CrashReport, CrashReportDatabase, CrashpadError, Result};
+
+/// How often [`UploadMonitor::start`] checks the database.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What happened to a report that was pending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadEvent {
+    /// The server accepted the report
+    Uploaded {
+        report: CrashReport,
+        /// ID the server assigned to the report
+        id: String,
+    },
+    /// The handler tried to upload the report and failed
+    Failed { report: CrashReport },
+    /// The handler completed the report without trying to upload it: it
+    /// was throttled by the rate limit, or uploads are disabled
+    Skipped { report: CrashReport },
+}
+
+impl UploadEvent {
+    /// Event for a report that is no longer pending.
+    fn completed(report: CrashReport) -> Self {
+        if report.uploaded {
+            let id = report.id.clone().unwrap_or_default();
+            Self::Uploaded { report, id }
+        } else if report.upload_attempts > 0 {
+            Self::Failed { report }
+        } else {
+            Self::Skipped { report }
+        }
+    }
+
+    /// The report the event is about
+    pub fn report(&self) -> &CrashReport {
+        match self {
+            Self::Uploaded { report, .. } | Self::Failed { report } | Self::Skipped { report } => {
+                report
+            }
+        }
+    }
+}
+
+/// Calls back when the handler finishes with a pending report.
+///
+/// A background thread polls the database and reports every report that
+/// leaves the pending state, e.g. to show "crash report sent" in the UI.
+/// Reports that were already completed when the monitor started are not
+/// reported. The thread stops when the monitor is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use crashpad_rs::{UploadEvent, UploadMonitor};
+///
+/// let _monitor = UploadMonitor::start("./crashpad_db", |event| match event {
+///     UploadEvent::Uploaded { id, .. } => println!("Crash report sent ({id})"),
+///     UploadEvent::Failed { report } => println!("Upload of {} failed", report.uuid),
+///     UploadEvent::Skipped { .. } => {}
+/// })?;
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+pub struct UploadMonitor {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl UploadMonitor {
+    /// Monitor the database at `database_path`, checking once a second
+    pub fn start<P, F>(database_path: P, callback: F) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        F: FnMut(UploadEvent) + Send + 'static,
+    {
+        Self::start_with_interval(database_path, DEFAULT_INTERVAL, callback)
+    }
+
+    /// Monitor the database at `database_path`, checking every `interval`
+    pub fn start_with_interval<P, F>(
+        database_path: P,
+        interval: Duration,
+        mut callback: F,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        F: FnMut(UploadEvent) + Send + 'static,
+    {
+        let database = CrashReportDatabase::open(database_path)?;
+        let mut pending = by_uuid(database.pending_reports()?);
+
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stop = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("crashpad-upload-monitor".to_string())
+            .spawn(move || {
+                let (stopped, condvar) = &*thread_stop;
+                loop {
+                    let guard = stopped.lock().unwrap_or_else(|e| e.into_inner());
+                    let (guard, _) = condvar
+                        .wait_timeout_while(guard, interval, |stopped| !*stopped)
+                        .unwrap_or_else(|e| e.into_inner());
+                    if *guard {
+                        return;
+                    }
+                    drop(guard);
+
+                    // Try again on the next tick, e.g. while the database is busy
+                    if let Ok(events) = poll(&database, &mut pending) {
+                        events.into_iter().for_each(&mut callback);
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for UploadMonitor {
+    fn drop(&mut self) {
+        let (stopped, condvar) = &*self.stop;
+        *stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for UploadMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UploadMonitor").finish_non_exhaustive()
+    }
+}
+
+fn by_uuid(reports: Vec<CrashReport>) -> HashMap<String, CrashReport> {
+    reports
+        .into_iter()
+        .map(|report| (report.uuid.clone(), report))
+        .collect()
+}
+
+/// Events for the reports of `pending` that left the pending state, which
+/// is updated to the current pending reports.
+fn poll(
+    database: &CrashReportDatabase,
+    pending: &mut HashMap<String, CrashReport>,
+) -> Result<Vec<UploadEvent>> {
+    let current = by_uuid(database.pending_reports()?);
+    let mut events = Vec::new();
+    for uuid in finished(pending, &current) {
+        match database.report(&uuid) {
+            Ok(report) => events.push(UploadEvent::completed(report)),
+            // Deleted without being completed
+            Err(CrashpadError::ReportNotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    *pending = current;
+    Ok(events)
+}
+
+/// UUIDs in `previous` that are not in `current`.
+fn finished(
+    previous: &HashMap<String, CrashReport>,
+    current: &HashMap<String, CrashReport>,
+) -> Vec<String> {
+    previous
+        .keys()
+        .filter(|uuid| !current.contains_key(*uuid))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::UNIX_EPOCH;
+
+    fn report(uuid: &str) -> CrashReport {
+        CrashReport {
+            uuid: uuid.to_string(),
+            file_path: PathBuf::from(format!("{uuid}.dmp")),
+            id: None,
+            creation_time: UNIX_EPOCH,
+            uploaded: false,
+            last_upload_attempt_time: None,
+            upload_attempts: 0,
+            upload_explicitly_requested: false,
+            total_size: 0,
+        }
+    }
+
+    #[test]
+    fn test_completed_event() {
+        let uploaded = CrashReport {
+            uploaded: true,
+            id: Some("server-42".to_string()),
+            upload_attempts: 1,
+            ..report("a")
+        };
+        assert!(matches!(
+            UploadEvent::completed(uploaded),
+            UploadEvent::Uploaded { id, .. } if id == "server-42"
+        ));
+
+        let failed = CrashReport {
+            upload_attempts: 1,
+            ..report("b")
+        };
+        assert!(matches!(
+            UploadEvent::completed(failed),
+            UploadEvent::Failed { .. }
+        ));
+
+        let event = UploadEvent::completed(report("c"));
+        assert!(matches!(event, UploadEvent::Skipped { .. }));
+        assert_eq!(event.report().uuid, "c");
+    }
+
+    #[test]
+    fn test_finished() {
+        let previous = by_uuid(vec![report("a"), report("b")]);
+        let current = by_uuid(vec![report("b"), report("c")]);
+
+        assert_eq!(finished(&previous, &current), vec!["a".to_string()]);
+    }
+}
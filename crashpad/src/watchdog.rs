@@ -0,0 +1,111 @@
+//! Restarting the handler when it dies.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::{CrashpadClient, CrashpadConfig, HandlerStatus, Result};
+
+/// Restarts the handler of a client when it exits.
+///
+/// A background thread checks [`CrashpadClient::handler_status`] every
+/// interval. Once the handler has exited, e.g. killed by the OOM killer, it
+/// starts a new one with the same configuration, like
+/// [`CrashpadClient::restart_handler`], and passes the outcome to the
+/// callback. A handler stopped with [`CrashpadClient::stop_handler`] isn't
+/// restarted. The thread stops when the watchdog is dropped.
+///
+/// # Platform Behavior
+/// - **Linux/Android/macOS**: The handler is restarted
+/// - **Windows**: Crashpad can't register a process with a second handler,
+///   so the callback is passed an error instead
+///
+/// # Example
+///
+/// ```no_run
+/// use crashpad_rs::{CrashpadClient, CrashpadConfig, HandlerWatchdog};
+/// use std::collections::HashMap;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let client = Arc::new(CrashpadClient::new()?);
+/// let config = CrashpadConfig::default();
+/// let annotations = HashMap::new();
+/// client.start_with_config(&config, &annotations)?;
+///
+/// let _watchdog = HandlerWatchdog::start(
+///     client,
+///     config,
+///     annotations,
+///     Duration::from_secs(5),
+///     |result| match result {
+///         Ok(()) => eprintln!("crashpad_handler restarted"),
+///         Err(e) => eprintln!("crashpad_handler is gone: {e}"),
+///     },
+/// )?;
+/// # Ok::<(), crashpad_rs::CrashpadError>(())
+/// ```
+pub struct HandlerWatchdog {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HandlerWatchdog {
+    /// Check the handler of `client` every `interval`, restarting it with
+    /// `config` and `annotations` when it has exited
+    pub fn start<F>(
+        client: Arc<CrashpadClient>,
+        config: CrashpadConfig,
+        annotations: HashMap<String, String>,
+        interval: Duration,
+        mut on_restart: F,
+    ) -> Result<Self>
+    where
+        F: FnMut(Result<()>) + Send + 'static,
+    {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stop = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("crashpad-watchdog".to_string())
+            .spawn(move || {
+                let (stopped, condvar) = &*thread_stop;
+                loop {
+                    let guard = stopped.lock().unwrap_or_else(|e| e.into_inner());
+                    let (guard, _) = condvar
+                        .wait_timeout_while(guard, interval, |stopped| !*stopped)
+                        .unwrap_or_else(|e| e.into_inner());
+                    if *guard {
+                        return;
+                    }
+                    drop(guard);
+
+                    if client.handler_status() == HandlerStatus::Exited {
+                        on_restart(client.restart_handler(&config, &annotations));
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for HandlerWatchdog {
+    fn drop(&mut self) {
+        let (stopped, condvar) = &*self.stop;
+        *stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for HandlerWatchdog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HandlerWatchdog").finish_non_exhaustive()
+    }
+}
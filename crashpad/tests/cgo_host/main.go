@@ -0,0 +1,49 @@
+// Go host for tests/cgo_host_test.rs.
+//
+// Usage: cgo_host <handler> <database> recover|crash
+package main
+
+/*
+int crashpad_guest_start(const char* handler_path, const char* database_path);
+void crashpad_guest_crash(void);
+*/
+import "C"
+
+import (
+	"fmt"
+	"os"
+)
+
+func nilDeref() (err error) {
+	defer func() {
+		if r := recover(); r != nil {
+			err = fmt.Errorf("%v", r)
+		}
+	}()
+	var p *int
+	*p = 1
+	return nil
+}
+
+func main() {
+	if len(os.Args) != 4 {
+		fmt.Fprintln(os.Stderr, "usage: cgo_host <handler> <database> recover|crash")
+		os.Exit(2)
+	}
+
+	if C.crashpad_guest_start(C.CString(os.Args[1]), C.CString(os.Args[2])) != 0 {
+		fmt.Println("start failed")
+		os.Exit(1)
+	}
+	fmt.Println("started")
+
+	switch os.Args[3] {
+	case "recover":
+		// Go must still turn faults in Go code into panics
+		if err := nilDeref(); err != nil {
+			fmt.Println("recovered:", err)
+		}
+	case "crash":
+		C.crashpad_guest_crash()
+	}
+}
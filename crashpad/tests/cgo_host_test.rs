@@ -0,0 +1,118 @@
+//! Integration test for the Go cgo coexistence preset
+//!
+//! Links the `cgo_guest` example (a static library) into the Go program in
+//! `tests/cgo_host` and checks that Go keeps handling its own faults while
+//! crashes in Rust code still produce dumps. Skipped when `go` or the
+//! handler is not available.
+#![cfg(target_os = "linux")]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Build the Go host against the example static library.
+fn build_host(out_dir: &Path) -> Option<PathBuf> {
+    if Command::new("go").arg("version").output().is_err() {
+        println!("go not found, skipping test");
+        return None;
+    }
+
+    // Examples are built by `cargo test` next to the deps directory
+    let exe = std::env::current_exe().unwrap();
+    let profile_dir = exe.parent()?.parent()?;
+    let guest_lib = profile_dir.join("examples").join("libcgo_guest.a");
+    if !guest_lib.exists() {
+        println!("{} not found, skipping test", guest_lib.display());
+        return None;
+    }
+
+    let host = out_dir.join("cgo_host");
+    let status = Command::new("go")
+        .args(["build", "-o"])
+        .arg(&host)
+        .arg("main.go")
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cgo_host"))
+        .env("CGO_ENABLED", "1")
+        .env(
+            "CGO_LDFLAGS",
+            format!(
+                "{} -lstdc++ -lpthread -ldl -lm",
+                guest_lib.to_str().unwrap()
+            ),
+        )
+        .status()
+        .expect("Failed to run go build");
+    assert!(status.success(), "go build failed");
+    Some(host)
+}
+
+fn handler_path() -> Option<PathBuf> {
+    let handler = PathBuf::from(option_env!("CRASHPAD_HANDLER_PATH")?);
+    if handler.exists() {
+        Some(handler)
+    } else {
+        println!("Handler not found, skipping test");
+        None
+    }
+}
+
+fn run_host(host: &Path, handler: &Path, database: &Path, mode: &str) -> Output {
+    Command::new(host)
+        .arg(handler)
+        .arg(database)
+        .arg(mode)
+        .output()
+        .expect("Failed to run cgo host")
+}
+
+/// Wait for the handler to write a minidump.
+fn wait_for_dump(database: &Path) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        for dir in ["pending", "completed", "new"] {
+            let found = std::fs::read_dir(database.join(dir))
+                .map(|entries| {
+                    entries
+                        .flatten()
+                        .any(|e| e.path().extension().is_some_and(|ext| ext == "dmp"))
+                })
+                .unwrap_or(false);
+            if found {
+                return true;
+            }
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    false
+}
+
+#[test]
+fn test_go_recovers_own_faults() {
+    let temp_dir = TempDir::new().unwrap();
+    let (Some(host), Some(handler)) = (build_host(temp_dir.path()), handler_path()) else {
+        return;
+    };
+
+    let output = run_host(&host, &handler, &temp_dir.path().join("db"), "recover");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success(), "host failed: {output:?}");
+    assert!(stdout.contains("started"), "{stdout}");
+    assert!(stdout.contains("recovered:"), "{stdout}");
+}
+
+#[test]
+fn test_rust_crash_in_go_host_writes_dump() {
+    let temp_dir = TempDir::new().unwrap();
+    let (Some(host), Some(handler)) = (build_host(temp_dir.path()), handler_path()) else {
+        return;
+    };
+    let database = temp_dir.path().join("db");
+
+    let output = run_host(&host, &handler, &database, "crash");
+
+    assert!(!output.status.success(), "host should crash: {output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("started"));
+    assert!(wait_for_dump(&database), "No minidump written");
+}
@@ -0,0 +1,151 @@
+//! Crash tests that verify real dumps
+//!
+//! Each test re-runs this test binary as a child process that starts the
+//! handler and then crashes, and checks the dump the handler writes with
+//! `crashpad-test-support`. The crashing process is never the test runner
+//! itself, so these tests work under both `cargo test` and `cargo nextest`.
+//! Skipped when the handler is not available.
+#![cfg(not(any(target_os = "ios", target_os = "android")))]
+
+use crashpad_rs::minidump::Minidump;
+#[cfg(feature = "qa")]
+use crashpad_rs::testing::{simulate, CrashKind};
+use crashpad_rs::{CrashReportDatabase, CrashpadClient, CrashpadConfig};
+use crashpad_test_support::{expect_dump, ExceptionKind};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Set in the child process to the kind of crash to trigger
+const CHILD_CRASH_ENV: &str = "CRASHPAD_CRASH_TEST_KIND";
+/// Set in the child process to the database to write dumps to
+const CHILD_DATABASE_ENV: &str = "CRASHPAD_CRASH_TEST_DATABASE";
+
+fn handler_path() -> Option<PathBuf> {
+    let handler = PathBuf::from(option_env!("CRASHPAD_HANDLER_PATH")?);
+    if handler.exists() {
+        Some(handler)
+    } else {
+        println!("Handler not found, skipping test");
+        None
+    }
+}
+
+/// Run [`crash_child`] in a new process and wait for it to exit.
+fn run_child(kind: &str, database: &Path) -> Output {
+    Command::new(std::env::current_exe().unwrap())
+        .args(["crash_child", "--exact", "--ignored", "--nocapture"])
+        .env(CHILD_CRASH_ENV, kind)
+        .env(CHILD_DATABASE_ENV, database)
+        .output()
+        .expect("Failed to run crash child")
+}
+
+/// Crash a child with `kind` and verify the dump it leaves behind.
+fn assert_crash_writes_dump(kind: &str, expected: ExceptionKind) {
+    if handler_path().is_none() {
+        return;
+    }
+    let temp_dir = TempDir::new().unwrap();
+    let database = temp_dir.path().join("db");
+
+    let expectation = expect_dump(&database)
+        .with_annotation("crash_test", kind)
+        .with_exception_kind(expected);
+    let output = run_child(kind, &database);
+
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("handler started"),
+        "child failed to start the handler: {output:?}"
+    );
+    assert!(!output.status.success(), "child should crash: {output:?}");
+    expectation.within(Duration::from_secs(10));
+}
+
+/// Child side of the crash tests, only does something when run by
+/// [`run_child`].
+#[test]
+#[ignore = "run as a child process by the crash tests"]
+fn crash_child() {
+    let Ok(kind) = std::env::var(CHILD_CRASH_ENV) else {
+        return;
+    };
+    let database = PathBuf::from(std::env::var(CHILD_DATABASE_ENV).unwrap());
+    let config = CrashpadConfig::builder()
+        .handler_path(handler_path().unwrap())
+        .database_path(&database)
+        .metrics_path(database.join("metrics"))
+        .build();
+    let annotations = HashMap::from([("crash_test".to_string(), kind.clone())]);
+
+    // Keep the client alive until the crash
+    let client = CrashpadClient::new().unwrap();
+    client.start_with_config(&config, &annotations).unwrap();
+    println!("handler started");
+
+    match kind.as_str() {
+        "segv" => unsafe {
+            std::ptr::null_mut::<i32>().write_volatile(42);
+        },
+        "abort" => std::process::abort(),
+        #[cfg(feature = "qa")]
+        "qa-stack-overflow" => simulate(CrashKind::StackOverflow),
+        #[cfg(feature = "qa")]
+        "qa-heap-corruption" => simulate(CrashKind::HeapCorruption),
+        _ => panic!("unknown crash kind {kind}"),
+    }
+    unreachable!("child did not crash");
+}
+
+#[test]
+fn test_segv_writes_dump() {
+    assert_crash_writes_dump("segv", ExceptionKind::Segv);
+}
+
+#[test]
+fn test_crash_dump_readable_with_minidump_module() {
+    if handler_path().is_none() {
+        return;
+    }
+    let temp_dir = TempDir::new().unwrap();
+    let database = temp_dir.path().join("db");
+
+    let expectation = expect_dump(&database).with_annotation("crash_test", "segv");
+    run_child("segv", &database);
+    expectation.within(Duration::from_secs(10));
+
+    let database = CrashReportDatabase::open(&database).unwrap();
+    let mut reports = database.pending_reports().unwrap();
+    reports.extend(database.completed_reports().unwrap());
+    let dump = Minidump::open(&reports[0].file_path).unwrap();
+
+    assert_eq!(dump.annotations().unwrap()["crash_test"], "segv");
+    let exception = dump.exception().unwrap().expect("dump has no exception");
+    let threads = dump.threads().unwrap();
+    assert!(threads
+        .iter()
+        .any(|thread| thread.id == exception.thread_id));
+    assert!(!dump.modules().unwrap().is_empty());
+    assert!(dump.fingerprint().unwrap().is_some());
+}
+
+// Rust's abort uses __fastfail on Windows, which skips exception handlers
+#[cfg(unix)]
+#[test]
+fn test_abort_writes_dump() {
+    assert_crash_writes_dump("abort", ExceptionKind::Abort);
+}
+
+#[cfg(all(feature = "qa", unix))]
+#[test]
+fn test_simulated_stack_overflow_writes_dump() {
+    assert_crash_writes_dump("qa-stack-overflow", ExceptionKind::Segv);
+}
+
+#[cfg(all(feature = "qa", unix))]
+#[test]
+fn test_simulated_heap_corruption_writes_dump() {
+    assert_crash_writes_dump("qa-heap-corruption", ExceptionKind::Abort);
+}
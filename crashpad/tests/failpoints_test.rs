@@ -0,0 +1,54 @@
+//! Error paths exercised through the `failpoints` feature
+//!
+//! Run with `cargo nextest run -p crashpad-rs --features failpoints --test failpoints_test`.
+#![cfg(all(
+    feature = "failpoints",
+    not(any(target_os = "ios", target_os = "tvos", target_os = "watchos"))
+))]
+
+use crashpad_rs::{failpoints, CrashpadClient, CrashpadConfig, CrashpadError};
+use fail::FailScenario;
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+fn test_config(temp_dir: &TempDir) -> CrashpadConfig {
+    // Fail points trigger before the handler would run, any file will do
+    CrashpadConfig::builder()
+        .handler_path(std::env::current_exe().unwrap())
+        .database_path(temp_dir.path().join("app").join("crashpad_db"))
+        .metrics_path(temp_dir.path().join("app").join("crashpad_metrics"))
+        .build()
+}
+
+#[test]
+fn test_handler_start_failure() {
+    let scenario = FailScenario::setup();
+    fail::cfg(failpoints::HANDLER_START, "return").unwrap();
+
+    let temp_dir = TempDir::new().unwrap();
+    let client = CrashpadClient::new().unwrap();
+    let result = client.start_with_config(&test_config(&temp_dir), &HashMap::new());
+    scenario.teardown();
+
+    assert!(matches!(
+        result,
+        Err(CrashpadError::HandlerSpawnFailed { .. })
+    ));
+}
+
+#[test]
+fn test_database_create_failure() {
+    let scenario = FailScenario::setup();
+    fail::cfg(failpoints::DATABASE_CREATE, "return(disk full)").unwrap();
+
+    let temp_dir = TempDir::new().unwrap();
+    let client = CrashpadClient::new().unwrap();
+    let result = client.start_with_config(&test_config(&temp_dir), &HashMap::new());
+    scenario.teardown();
+
+    match result {
+        Err(CrashpadError::IoError(e)) => assert_eq!(e.to_string(), "disk full"),
+        other => panic!("expected an IO error, got {other:?}"),
+    }
+    assert!(!temp_dir.path().join("app").exists());
+}
@@ -0,0 +1,81 @@
+//! A second client reporting to a handler started by another, over its pipe.
+//! A separate test binary, since registering installs a process-wide
+//! exception filter.
+#![cfg(target_os = "windows")]
+
+use crashpad_rs::{CrashpadClient, CrashpadConfig};
+use crashpad_test_support::expect_dump;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tempfile::TempDir;
+
+#[test]
+fn test_shared_ipc_pipe() {
+    let handler_path = find_crashpad_handler();
+    if !handler_path.exists() {
+        println!("Handler not found, skipping IPC pipe test");
+        return;
+    }
+
+    let temp_dir = TempDir::new().expect("Should be able to create temp directory");
+    let database_path = temp_dir.path().join("crashpad_db");
+    let config = CrashpadConfig::builder()
+        .handler_path(&handler_path)
+        .database_path(&database_path)
+        .metrics_path(temp_dir.path().join("crashpad_metrics"))
+        .build();
+
+    let parent = CrashpadClient::new().expect("CrashpadClient::new() should succeed");
+    assert!(parent.get_handler_ipc_pipe().is_err());
+
+    let mut annotations = HashMap::new();
+    annotations.insert("test_id".to_string(), "test_shared_ipc_pipe".to_string());
+    parent
+        .start_with_config(&config, &annotations)
+        .expect("Handler should start");
+    let pipe = parent
+        .get_handler_ipc_pipe()
+        .expect("Started handler should have a pipe");
+    assert!(
+        pipe.starts_with(r"\\.\pipe\"),
+        "Unexpected pipe name {pipe}"
+    );
+
+    // What a child process does with the pipe name it was given
+    let child = CrashpadClient::new().expect("CrashpadClient::new() should succeed");
+    child
+        .set_handler_ipc_pipe(&pipe)
+        .expect("Should register with the running handler");
+    assert_eq!(child.get_handler_ipc_pipe().unwrap(), pipe);
+
+    let expectation = expect_dump(&database_path);
+    child.dump_without_crash();
+    let dump = expectation
+        .with_annotation("test_id", "test_shared_ipc_pipe")
+        .within(Duration::from_secs(10));
+    println!("✓ Dump written over the pipe to {}", dump.path.display());
+}
+
+// Same lookup as integration_test.rs
+fn find_crashpad_handler() -> PathBuf {
+    let platform = format!(
+        "{}-{}",
+        std::env::consts::OS,
+        if cfg!(target_arch = "x86_64") {
+            "x64"
+        } else {
+            "arm64"
+        }
+    );
+
+    let possible_paths = [
+        format!("third_party/crashpad_checkout/crashpad/out/{platform}/crashpad_handler.exe"),
+        format!("../third_party/crashpad_checkout/crashpad/out/{platform}/crashpad_handler.exe"),
+    ];
+    possible_paths
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from("crashpad_handler.exe"))
+}
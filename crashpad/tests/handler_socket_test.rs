@@ -0,0 +1,74 @@
+//! A handler started for other processes, reached over an inherited socket.
+//! A separate test binary, since registering installs process-wide signal
+//! handlers.
+#![cfg(any(target_os = "linux", target_os = "android"))]
+
+use crashpad_rs::{CrashpadClient, CrashpadConfig};
+use crashpad_test_support::expect_dump;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tempfile::TempDir;
+
+#[test]
+fn test_handler_for_client() {
+    let handler_path = find_crashpad_handler();
+    if !handler_path.exists() {
+        println!("Handler not found, skipping handler socket test");
+        return;
+    }
+
+    let temp_dir = TempDir::new().expect("Should be able to create temp directory");
+    let database_path = temp_dir.path().join("crashpad_db");
+    let config = CrashpadConfig::builder()
+        .handler_path(&handler_path)
+        .database_path(&database_path)
+        .metrics_path(temp_dir.path().join("crashpad_metrics"))
+        .build();
+
+    let (server, client_socket) =
+        CrashpadClient::handler_socket_pair().expect("Should create a socket pair");
+    let mut annotations = HashMap::new();
+    annotations.insert("test_id".to_string(), "test_handler_for_client".to_string());
+    CrashpadClient::start_handler_for_client(&config, &annotations, server)
+        .expect("Handler for client should start");
+
+    // No handler of its own to hand out
+    let client = CrashpadClient::new().expect("CrashpadClient::new() should succeed");
+    assert!(client.handler_socket().is_err());
+
+    // What a sandboxed child does with the inherited client end
+    client
+        .set_handler_socket(client_socket, None)
+        .expect("Should register with the running handler");
+
+    let expectation = expect_dump(&database_path);
+    client.dump_without_crash();
+    let dump = expectation
+        .with_annotation("test_id", "test_handler_for_client")
+        .within(Duration::from_secs(10));
+    println!("✓ Dump written over the socket to {}", dump.path.display());
+}
+
+// Same lookup as integration_test.rs
+fn find_crashpad_handler() -> PathBuf {
+    let platform = format!(
+        "{}-{}",
+        std::env::consts::OS,
+        if cfg!(target_arch = "x86_64") {
+            "x64"
+        } else {
+            "arm64"
+        }
+    );
+
+    let possible_paths = [
+        format!("third_party/crashpad_checkout/crashpad/out/{platform}/crashpad_handler"),
+        format!("../third_party/crashpad_checkout/crashpad/out/{platform}/crashpad_handler"),
+    ];
+    possible_paths
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from("crashpad_handler"))
+}
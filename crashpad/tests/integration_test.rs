@@ -1,6 +1,8 @@
 use crashpad_rs::CrashpadClient;
+use crashpad_test_support::expect_dump;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
 use tempfile::TempDir;
 
 #[test]
@@ -115,6 +117,15 @@ fn test_with_annotations() {
             "Handler with annotations should start successfully: {result:?}"
         );
         println!("✓ Handler with annotations started successfully");
+
+        // The annotations must end up in the dump
+        let expectation = expect_dump(&database_path);
+        client.dump_without_crash();
+        let dump = expectation
+            .with_annotation("test_id", "test_with_annotations")
+            .with_annotation("version", "1.0.0")
+            .within(Duration::from_secs(10));
+        println!("✓ Annotated dump written to {}", dump.path.display());
     } else {
         println!("Handler not found, skipping annotation test");
     }
@@ -149,3 +160,17 @@ fn find_crashpad_handler() -> PathBuf {
     // Return dummy path if not found (test will handle it)
     PathBuf::from("crashpad_handler")
 }
+
+#[test]
+fn test_vcs_annotations() {
+    // This crate's build script doesn't record git metadata, so only
+    // variables set in the build environment can show up
+    let annotations = crashpad_rs::vcs_annotations!();
+    for key in annotations.keys() {
+        assert!(["git_commit", "git_branch", "git_dirty"].contains(&key.as_str()));
+    }
+    assert_eq!(
+        annotations.contains_key("git_commit"),
+        option_env!("CRASHPAD_GIT_COMMIT").is_some()
+    );
+}
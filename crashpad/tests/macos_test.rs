@@ -24,4 +24,15 @@ mod macos_tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_dump_of_unknown_thread() -> Result<()> {
+        let client = CrashpadClient::new()?;
+
+        // No thread has ID 0; the lookup fails before anything is suspended
+        assert!(client.dump_without_crash_of_thread(0).is_err());
+        assert_ne!(crashpad_rs::current_thread_id(), 0);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,288 @@
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// How the target runs the handler
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Platform {
+    Linux,
+    Android,
+    MacOs,
+    Windows,
+    /// iOS/tvOS/watchOS, in-process handler
+    Ios,
+}
+
+impl Platform {
+    fn from_target(target: &str) -> Result<Self> {
+        Ok(
+            if target.contains("-apple-ios")
+                || target.contains("-apple-tvos")
+                || target.contains("-apple-watchos")
+            {
+                Platform::Ios
+            } else if target.contains("-apple-darwin") {
+                Platform::MacOs
+            } else if target.contains("-windows") {
+                Platform::Windows
+            } else if target.contains("-android") {
+                Platform::Android
+            } else if target.contains("-linux") {
+                Platform::Linux
+            } else {
+                bail!("Unsupported target: {target}");
+            },
+        )
+    }
+
+    fn is_apple(self) -> bool {
+        matches!(self, Platform::MacOs | Platform::Ios)
+    }
+}
+
+/// Fields of the crashpad-rs config file format (`CrashpadConfig::from_file`)
+const PATH_FIELDS: &[&str] = &[
+    "handler_path",
+    "database_path",
+    "metrics_path",
+    "handler_working_directory",
+];
+const BOOL_FIELDS: &[&str] = &[
+    "uploads_enabled",
+    "handler_version_annotation",
+    "diagnostics_annotation",
+    "container_annotations",
+    "timezone_annotations",
+];
+const INTEGER_FIELDS: &[&str] = &["handler_umask", "database_janitor_secs"];
+
+/// Fields that configure the external handler process
+const HANDLER_PROCESS_FIELDS: &[&str] = &[
+    "handler_path",
+    "handler_arguments",
+    "handler_version_annotation",
+    "handler_log",
+    "handler_working_directory",
+    "handler_umask",
+];
+
+#[derive(Default)]
+struct Findings {
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl Findings {
+    fn error(&mut self, field: &str, message: impl std::fmt::Display) {
+        self.errors.push(format!("{field}: {message}"));
+    }
+
+    fn warning(&mut self, field: &str, message: impl std::fmt::Display) {
+        self.warnings.push(format!("{field}: {message}"));
+    }
+}
+
+pub fn check_config(file: &Path, target: Option<String>) -> Result<()> {
+    let target = match target {
+        Some(target) => target,
+        None => host_target()?,
+    };
+    let platform = Platform::from_target(&target)?;
+    println!("🔍 Checking {} for {}", file.display(), target);
+
+    let contents = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    let value: Value = match file.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(&contents).context("Invalid TOML")?,
+        Some("json") => serde_json::from_str(&contents).context("Invalid JSON")?,
+        _ => bail!("Expected a .toml or .json file"),
+    };
+    let Value::Object(config) = value else {
+        bail!("Expected a table of settings at the top level");
+    };
+
+    let mut findings = Findings::default();
+    check(&config, platform, &mut findings);
+
+    for warning in &findings.warnings {
+        println!("  ⚠ warning: {warning}");
+    }
+    for error in &findings.errors {
+        println!("  ✗ error: {error}");
+    }
+    if !findings.errors.is_empty() {
+        bail!(
+            "{} error(s), {} warning(s)",
+            findings.errors.len(),
+            findings.warnings.len()
+        );
+    }
+    println!(
+        "✅ Config is valid ({} warning(s))",
+        findings.warnings.len()
+    );
+    Ok(())
+}
+
+fn check(config: &Map<String, Value>, platform: Platform, findings: &mut Findings) {
+    for (field, value) in config {
+        let field = field.as_str();
+        if PATH_FIELDS.contains(&field) {
+            check_path(field, value, platform, findings);
+        } else if BOOL_FIELDS.contains(&field) {
+            if !value.is_boolean() {
+                findings.error(field, "expected true or false");
+            }
+        } else if INTEGER_FIELDS.contains(&field) {
+            if !value.is_u64() {
+                findings.error(field, "expected a non-negative integer");
+            }
+        } else {
+            match field {
+                "url" => check_url(field, value, platform, findings),
+                "failover_urls" => match value.as_array() {
+                    Some(urls) => {
+                        for url in urls {
+                            check_url(field, url, platform, findings);
+                        }
+                    }
+                    None => findings.error(field, "expected an array of URLs"),
+                },
+                "handler_arguments" => {
+                    if !value
+                        .as_array()
+                        .is_some_and(|args| args.iter().all(Value::is_string))
+                    {
+                        findings.error(field, "expected an array of strings");
+                    }
+                }
+                "handler_log" => check_handler_log(value, platform, findings),
+                _ => findings.error(field, "unknown field"),
+            }
+        }
+    }
+
+    if config.contains_key("failover_urls") && !config.contains_key("url") {
+        findings.error("failover_urls", "set without a primary `url`");
+    }
+
+    // Handler mode availability
+    for field in HANDLER_PROCESS_FIELDS {
+        if platform == Platform::Ios && config.contains_key(*field) {
+            findings.warning(
+                field,
+                "ignored, iOS/tvOS/watchOS use the in-process handler",
+            );
+        }
+    }
+    if platform == Platform::Windows && config.contains_key("handler_umask") {
+        findings.warning("handler_umask", "ignored on Windows");
+    }
+    if let Some(mask) = config.get("handler_umask").and_then(Value::as_u64) {
+        if mask > 0o777 {
+            findings.warning("handler_umask", "only the low 9 bits are used");
+        }
+    }
+    if config.get("diagnostics_annotation") == Some(&Value::Bool(true))
+        && !matches!(platform, Platform::Linux | Platform::Android)
+    {
+        findings.warning("diagnostics_annotation", "only collected on Linux/Android");
+    }
+    if !config.contains_key("url") {
+        findings.warning("url", "not set, reports are kept locally only");
+    }
+}
+
+fn check_path(field: &str, value: &Value, platform: Platform, findings: &mut Findings) {
+    let Some(path) = value.as_str() else {
+        findings.error(field, "expected a path string");
+        return;
+    };
+    if path.is_empty() {
+        findings.error(field, "empty path");
+        return;
+    }
+
+    if platform == Platform::Windows {
+        let bytes = path.as_bytes();
+        let drive = bytes.len() >= 3
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && matches!(bytes[2], b'\\' | b'/');
+        if path.starts_with('/') && !path.starts_with("//") {
+            findings.error(field, format!("{path} is not an absolute Windows path"));
+        } else if !drive && !path.starts_with(r"\\") && !path.starts_with("//") {
+            findings.warning(field, "relative, resolved against the working directory");
+        }
+        if field == "handler_path" && !path.to_ascii_lowercase().ends_with(".exe") {
+            findings.warning(field, "handler executables end in .exe on Windows");
+        }
+    } else {
+        let bytes = path.as_bytes();
+        if path.contains('\\')
+            || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+        {
+            findings.error(field, format!("{path} is a Windows path"));
+        } else if !path.starts_with('/') && platform != Platform::Ios {
+            findings.warning(field, "relative, resolved against the working directory");
+        }
+    }
+}
+
+fn check_url(field: &str, value: &Value, platform: Platform, findings: &mut Findings) {
+    let Some(url) = value.as_str() else {
+        findings.error(field, "expected a URL string");
+        return;
+    };
+    let Some((scheme, rest)) = url.split_once("://") else {
+        findings.error(field, format!("{url} has no scheme"));
+        return;
+    };
+    match scheme {
+        "https" => {}
+        "http" if platform.is_apple() => findings.warning(
+            field,
+            format!("{url} uses plain HTTP, which App Transport Security blocks by default"),
+        ),
+        "http" => findings.warning(field, format!("{url} sends reports unencrypted")),
+        _ => findings.error(
+            field,
+            format!("{url}: unsupported scheme {scheme}, use https"),
+        ),
+    }
+    if rest.split(['/', '?']).next().unwrap_or_default().is_empty() {
+        findings.error(field, format!("{url} has no host"));
+    }
+}
+
+fn check_handler_log(value: &Value, platform: Platform, findings: &mut Findings) {
+    let Some(log) = value.as_object() else {
+        findings.error("handler_log", "expected a table");
+        return;
+    };
+    for (key, value) in log {
+        let field = format!("handler_log.{key}");
+        match key.as_str() {
+            "path" => check_path(&field, value, platform, findings),
+            "max_size" | "keep" => {
+                if !value.is_u64() {
+                    findings.error(&field, "expected a non-negative integer");
+                }
+            }
+            _ => findings.error(&field, "unknown field"),
+        }
+    }
+}
+
+/// Host triple from `rustc -vV`
+fn host_target() -> Result<String> {
+    let output = std::process::Command::new("rustc")
+        .arg("-vV")
+        .output()
+        .context("Failed to run rustc")?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(str::to_string)
+        .context("Could not determine target triple")
+}
@@ -0,0 +1,31 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use xshell::{cmd, Shell};
+
+/// Convert a minidump to JSON with the `export` module of crashpad-rs
+///
+/// Runs the crate's `dump2json` example, as the conversion needs the crate
+/// itself. The document goes to stdout, or to `out` if given.
+pub fn dump2json(sh: &Shell, minidump: &Path, out: Option<PathBuf>) -> Result<()> {
+    if !minidump.is_file() {
+        bail!("{} is not a file", minidump.display());
+    }
+
+    let json = cmd!(
+        sh,
+        "cargo run --quiet --package crashpad-rs --features serde --example dump2json -- {minidump}"
+    )
+    .read()
+    .with_context(|| format!("Failed to convert {}", minidump.display()))?;
+
+    match out {
+        Some(out) => {
+            fs::write(&out, json + "\n")
+                .with_context(|| format!("Failed to write {}", out.display()))?;
+            eprintln!("✅ Wrote {}", out.display());
+        }
+        None => println!("{json}"),
+    }
+    Ok(())
+}
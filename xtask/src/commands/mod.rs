@@ -1,15 +1,21 @@
 pub mod build;
+pub mod check_config;
 pub mod deps;
 pub mod dist;
+pub mod dump2json;
 pub mod prebuilt;
+pub mod symbols;
 pub mod symlink;
 pub mod test;
 pub mod tools;
 
 pub use build::build;
+pub use check_config::check_config;
 pub use deps::update_deps;
 pub use dist::dist;
+pub use dump2json::dump2json;
 pub use prebuilt::build_prebuilt;
+pub use symbols::{symbols, upload_symbols, SymbolServer};
 pub use symlink::create_symlinks;
 pub use test::test;
 pub use tools::install_tools;
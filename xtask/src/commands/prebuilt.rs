@@ -274,12 +274,11 @@ pub fn build_prebuilt(sh: &Shell, target: Option<String>) -> Result<()> {
 
     // Generate checksum
     println!("🔐 Generating checksum...");
-    let archive_content = fs::read(&archive_path)?;
-    let digest = sha256::digest(&archive_content[..]);
-    // Fix: use proper extension replacement
-    let checksum_path = format!("{}.sha256", archive_path.display());
-    let checksum_path = PathBuf::from(checksum_path);
-    fs::write(&checksum_path, format!("{}  {}\n", digest, archive_name))?;
+    let checksum_path = write_checksum(&archive_path)?;
+
+    // Standalone handler archive for `cargo binstall crashpad-handler`
+    let handler_archive =
+        create_handler_archive(sh, &prebuilt_dir, &archive_dir, &version, &target)?;
 
     // Simulate GitHub download by copying to cache and extracting
     println!("\n📥 Simulating GitHub download to cache...");
@@ -322,12 +321,64 @@ pub fn build_prebuilt(sh: &Shell, target: Option<String>) -> Result<()> {
     println!("  📁 Cache: {}", cache_dir.display());
     println!("  📦 Archive: {}", archive_path.display());
     println!("  🔐 Checksum: {}", checksum_path.display());
+    if let Some(handler_archive) = &handler_archive {
+        println!("  📦 Handler archive: {}", handler_archive.display());
+    }
     println!("\n📤 Ready to upload to GitHub Releases!");
     println!("\n🧪 Test locally with: cargo build --package crashpad-rs-sys --features prebuilt");
 
     Ok(())
 }
 
+/// Write `<archive>.sha256` in `sha256sum` format next to `archive_path`
+fn write_checksum(archive_path: &Path) -> Result<PathBuf> {
+    let archive_content = fs::read(archive_path)?;
+    let digest = sha256::digest(&archive_content[..]);
+    let archive_name = archive_path
+        .file_name()
+        .context("Archive path has no file name")?
+        .to_string_lossy();
+    let checksum_path = PathBuf::from(format!("{}.sha256", archive_path.display()));
+    fs::write(&checksum_path, format!("{}  {}\n", digest, archive_name))?;
+    Ok(checksum_path)
+}
+
+/// Package the handler alone as `crashpad-handler-{version}-{target}.tar.gz`,
+/// the archive the crashpad-handler crate's binstall metadata points to.
+/// `None` for targets without a handler executable (iOS, Android).
+fn create_handler_archive(
+    sh: &Shell,
+    prebuilt_dir: &Path,
+    archive_dir: &Path,
+    version: &str,
+    target: &str,
+) -> Result<Option<PathBuf>> {
+    if target.contains("ios") || target.contains("android") {
+        return Ok(None);
+    }
+
+    let handler_name = if target.contains("windows") {
+        "crashpad_handler.exe"
+    } else {
+        "crashpad_handler"
+    };
+    if !prebuilt_dir.join(handler_name).exists() {
+        anyhow::bail!("{} missing from {}", handler_name, prebuilt_dir.display());
+    }
+
+    let archive_name = format!("crashpad-handler-{}-{}.tar.gz", version, target);
+    let archive_path = archive_dir.join(&archive_name);
+    println!("📦 Creating handler archive: {}", archive_name);
+    cmd!(
+        sh,
+        "tar -czf {archive_path} -C {prebuilt_dir} {handler_name}"
+    )
+    .run()?;
+    write_checksum(&archive_path)?;
+
+    Ok(Some(archive_path))
+}
+
 /// Get package version from Cargo.toml
 fn get_package_version(workspace_root: &Path) -> Result<String> {
     // Parse version from workspace inheritance
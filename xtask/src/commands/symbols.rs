@@ -0,0 +1,264 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use xshell::{cmd, Shell};
+
+use crate::utils::find_workspace_root;
+
+/// Extensions of shared libraries and executables on any target
+const BINARY_EXTENSIONS: &[&str] = &["so", "dylib", "exe", "dll"];
+
+/// Generate Breakpad symbol files for built binaries
+///
+/// Runs `dump_syms` on each binary and writes its output to
+/// `<out>/<debug file>/<debug ID>/<debug file>.sym`, the layout symbol
+/// servers and `minidump_stackwalk` expect.
+pub fn symbols(
+    sh: &Shell,
+    binaries: Vec<PathBuf>,
+    out: Option<PathBuf>,
+    release: bool,
+    target: Option<String>,
+    dump_syms: Option<PathBuf>,
+) -> Result<()> {
+    println!("🔣 Generating Breakpad symbols...");
+
+    let workspace_root = find_workspace_root(sh)?;
+    let out = out.unwrap_or_else(|| workspace_root.join("target").join("symbols"));
+    let dump_syms = dump_syms
+        .or_else(|| std::env::var_os("DUMP_SYMS").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("dump_syms"));
+
+    let binaries = if binaries.is_empty() {
+        let mut profile_dir = workspace_root.join("target");
+        if let Some(target) = &target {
+            profile_dir.push(target);
+        }
+        profile_dir.push(if release { "release" } else { "debug" });
+        let found = find_binaries(&profile_dir)?;
+        if found.is_empty() {
+            bail!(
+                "No binaries in {}. Build first, or pass the binaries to process",
+                profile_dir.display()
+            );
+        }
+        found
+    } else {
+        binaries
+    };
+
+    let mut written = 0;
+    for binary in &binaries {
+        let symbols = cmd!(sh, "{dump_syms} {binary}")
+            .quiet()
+            .read()
+            .with_context(|| {
+                format!(
+                    "{} failed for {}. Install dump_syms with `cargo install dump_syms`, \
+                     or pass --dump-syms (or set DUMP_SYMS) to use another one",
+                    dump_syms.display(),
+                    binary.display()
+                )
+            })?;
+        let Some(path) = symbol_path(&out, &symbols) else {
+            println!("  ⚠️  {}: no MODULE record, skipped", binary.display());
+            continue;
+        };
+        fs::create_dir_all(path.parent().expect("symbol path has parents"))?;
+        fs::write(&path, symbols + "\n")?;
+        println!("  ✓ {}", path.strip_prefix(&out).unwrap_or(&path).display());
+        written += 1;
+    }
+
+    println!("\n✅ {written} symbol file(s) written to {}", out.display());
+    Ok(())
+}
+
+/// Executables and shared libraries directly in `dir`
+fn find_binaries(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    let mut binaries = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let is_binary = match path.extension().and_then(|e| e.to_str()) {
+            Some(extension) => BINARY_EXTENSIONS.contains(&extension),
+            None => is_executable(&path),
+        };
+        if is_binary {
+            binaries.push(path);
+        }
+    }
+    binaries.sort();
+    Ok(binaries)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .map(|metadata| metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    false
+}
+
+/// Where the symbol file starting with `MODULE <os> <arch> <id> <name>`
+/// goes in the store at `out`
+fn symbol_path(out: &Path, symbols: &str) -> Option<PathBuf> {
+    let module = symbols.lines().next()?.strip_prefix("MODULE ")?;
+    let mut fields = module.splitn(4, ' ');
+    let (_os, _arch, id, name) = (
+        fields.next()?,
+        fields.next()?,
+        fields.next()?,
+        fields.next()?,
+    );
+    // Windows: app.pdb/<id>/app.sym
+    let stem = name
+        .strip_suffix(".pdb")
+        .or_else(|| name.strip_suffix(".PDB"))
+        .unwrap_or(name);
+    Some(out.join(name).join(id).join(format!("{stem}.sym")))
+}
+
+/// Symbol upload protocol of a symbol server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SymbolServer {
+    /// Mozilla Tecken (`POST /upload/` with an `Auth-Token` header)
+    Tecken,
+    /// Sentry debug files (`POST /api/0/projects/<org>/<project>/files/dsyms/`)
+    Sentry,
+}
+
+/// Upload a Breakpad symbol store to a symbol server
+///
+/// Packs the `.sym` files of `symbols` (default: target/symbols, as written
+/// by [`symbols`]) into `target/symbols.zip`, keeping the store layout both
+/// servers expect, and posts it with curl. The token is passed to curl on
+/// stdin so it doesn't show up in process listings.
+pub fn upload_symbols(
+    sh: &Shell,
+    server: &str,
+    token: Option<String>,
+    protocol: SymbolServer,
+    project: Option<String>,
+    symbols: Option<PathBuf>,
+    dry_run: bool,
+) -> Result<()> {
+    println!("📤 Uploading Breakpad symbols...");
+
+    let workspace_root = find_workspace_root(sh)?;
+    let store = symbols.unwrap_or_else(|| workspace_root.join("target").join("symbols"));
+    let url = upload_url(server, protocol, project.as_deref())?;
+    let token = match token.or_else(|| std::env::var("SYMBOL_SERVER_TOKEN").ok()) {
+        Some(token) if !token.is_empty() => Some(token),
+        _ if dry_run => None,
+        _ => bail!("No token. Pass --token or set SYMBOL_SERVER_TOKEN"),
+    };
+
+    let mut files = Vec::new();
+    find_symbol_files(&store, &mut files)
+        .with_context(|| format!("Failed to read symbol store {}", store.display()))?;
+    files.sort();
+    if files.is_empty() {
+        bail!(
+            "No .sym files in {}. Run `cargo xtask symbols` first",
+            store.display()
+        );
+    }
+
+    let archive = workspace_root.join("target").join("symbols.zip");
+    write_archive(&archive, &store, &files)?;
+    println!(
+        "  ✓ {} symbol file(s) packed into {}",
+        files.len(),
+        archive.display()
+    );
+
+    let Some(token) = token else {
+        println!("\n✅ Dry run, would upload to {url}");
+        return Ok(());
+    };
+    let header = match protocol {
+        SymbolServer::Tecken => format!("Auth-Token: {token}"),
+        SymbolServer::Sentry => format!("Authorization: Bearer {token}"),
+    };
+    let form = format!("file=@{}", archive.display());
+    let response = cmd!(
+        sh,
+        "curl --silent --show-error --fail-with-body -H @- -F {form} {url}"
+    )
+    .quiet()
+    .stdin(header + "\n")
+    .read()
+    .with_context(|| format!("Upload to {url} failed"))?;
+    if !response.trim().is_empty() {
+        println!("  {}", response.trim());
+    }
+
+    println!("\n✅ {} symbol file(s) uploaded to {url}", files.len());
+    Ok(())
+}
+
+/// Upload endpoint of `server` for `protocol`
+fn upload_url(server: &str, protocol: SymbolServer, project: Option<&str>) -> Result<String> {
+    let server = server.trim_end_matches('/');
+    match protocol {
+        SymbolServer::Tecken => Ok(format!("{server}/upload/")),
+        SymbolServer::Sentry => {
+            let Some((org, project)) = project.and_then(|project| project.split_once('/')) else {
+                bail!("Sentry uploads need --project <org>/<project>");
+            };
+            Ok(format!(
+                "{server}/api/0/projects/{org}/{project}/files/dsyms/"
+            ))
+        }
+    }
+}
+
+/// `.sym` files anywhere under `dir`
+fn find_symbol_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_symbol_files(&path, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "sym") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Zip `files` under their paths relative to `store`
+fn write_archive(archive: &Path, store: &Path, files: &[PathBuf]) -> Result<()> {
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    let mut zip = zip::ZipWriter::new(
+        fs::File::create(archive)
+            .with_context(|| format!("Failed to create {}", archive.display()))?,
+    );
+    let options = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .large_file(true);
+    for file in files {
+        // Zip paths always use `/`
+        let name = file
+            .strip_prefix(store)?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        zip.start_file(name, options)?;
+        zip.write_all(&fs::read(file)?)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
@@ -5,7 +5,11 @@ use anyhow::Result;
 use clap::{Parser, Subcommand};
 use xshell::Shell;
 
-use commands::{build, build_prebuilt, create_symlinks, dist, install_tools, test, update_deps};
+use commands::{
+    build, build_prebuilt, check_config, create_symlinks, dist, dump2json, install_tools, symbols,
+    test, update_deps, upload_symbols, SymbolServer,
+};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(author, version, about = "Development tasks for crashpad-rs")]
@@ -42,6 +46,63 @@ enum Commands {
         #[arg(long)]
         target: Option<String>,
     },
+    /// Validate a crashpad-rs config file (TOML/JSON) for a target platform
+    CheckConfig {
+        /// Config file to check
+        #[arg(long)]
+        file: PathBuf,
+        /// Target triple (optional, defaults to current)
+        #[arg(long)]
+        target: Option<String>,
+    },
+    /// Convert a minidump to JSON (exception, threads, modules, annotations)
+    #[command(name = "dump2json")]
+    Dump2Json {
+        /// Minidump to convert
+        minidump: PathBuf,
+        /// File to write the JSON to (default: stdout)
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Generate Breakpad symbol files (.sym) for built binaries with dump_syms
+    Symbols {
+        /// Binaries to process (default: executables and shared libraries
+        /// in the target directory)
+        binaries: Vec<PathBuf>,
+        /// Symbol store to write to (default: target/symbols)
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Use release binaries
+        #[arg(long)]
+        release: bool,
+        /// Target triple (optional, defaults to current)
+        #[arg(long)]
+        target: Option<String>,
+        /// dump_syms executable (default: $DUMP_SYMS, or dump_syms on PATH)
+        #[arg(long)]
+        dump_syms: Option<PathBuf>,
+    },
+    /// Upload Breakpad symbols to a Tecken (Mozilla) or Sentry symbol server
+    UploadSymbols {
+        /// Symbol server, e.g. https://symbols.mozilla.org or https://sentry.io
+        #[arg(long)]
+        server: String,
+        /// API token (default: $SYMBOL_SERVER_TOKEN)
+        #[arg(long)]
+        token: Option<String>,
+        /// Upload protocol of the server
+        #[arg(long, value_enum, default_value_t = SymbolServer::Tecken)]
+        protocol: SymbolServer,
+        /// Sentry project as <org>/<project>
+        #[arg(long)]
+        project: Option<String>,
+        /// Symbol store to upload (default: target/symbols)
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+        /// Pack the symbols without uploading them
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 fn main() -> Result<()> {
@@ -64,6 +125,23 @@ fn main() -> Result<()> {
         Commands::UpdateDeps { create_pr } => update_deps(&sh, create_pr)?,
         Commands::Symlink => create_symlinks(&sh)?,
         Commands::BuildPrebuilt { target } => build_prebuilt(&sh, target)?,
+        Commands::CheckConfig { file, target } => check_config(&file, target)?,
+        Commands::Dump2Json { minidump, out } => dump2json(&sh, &minidump, out)?,
+        Commands::Symbols {
+            binaries,
+            out,
+            release,
+            target,
+            dump_syms,
+        } => symbols(&sh, binaries, out, release, target, dump_syms)?,
+        Commands::UploadSymbols {
+            server,
+            token,
+            protocol,
+            project,
+            symbols,
+            dry_run,
+        } => upload_symbols(&sh, &server, token, protocol, project, symbols, dry_run)?,
     }
 
     Ok(())